@@ -0,0 +1,94 @@
+//! Benchmarks of matching request paths against the registered routes of a `WebRouter`.
+//!
+//! Run them with `cargo bench -p browzer_web`. Every benchmark registers the same mix of static,
+//! `:param` and `*wildcard` routes at different route counts, so the matching cost can be compared
+//! as the number of routes grows.
+
+// external crate imports
+use browzer_web::{
+    request::Request,
+    router::{NotFoundCache, WebRouter},
+    utils::{HttpMethod, HttpStatusCode},
+};
+use criterion::{black_box, criterion_group, criterion_main, BatchSize, BenchmarkId, Criterion};
+
+// the route counts every benchmark is run with
+const ROUTE_COUNTS: [usize; 3] = [10, 100, 1000];
+
+// builds a router with `count` routes, a third of each kind
+fn router_with_routes(count: usize) -> WebRouter {
+    let mut router = WebRouter::new();
+    for i in 0..count {
+        let path = match i % 3 {
+            0 => format!("/api/v1/resource{}/items", i),
+            1 => format!("/api/v1/resource{}/:id/details", i),
+            _ => format!("/files/bucket{}/*path", i),
+        };
+        let _ = router.add(path, HttpMethod::GET, |mut c| {
+            return c.send_string(HttpStatusCode::OK, "ok");
+        });
+    }
+    return router;
+}
+
+// the paths matching the route registered last of each kind, and a path matching none of them
+fn request_paths(count: usize) -> [(&'static str, String); 4] {
+    let last = |kind: usize| (0..count).rev().find(|i| i % 3 == kind).unwrap_or(0);
+    return [
+        ("static", format!("/api/v1/resource{}/items", last(0))),
+        ("param", format!("/api/v1/resource{}/42/details", last(1))),
+        ("wildcard", format!("/files/bucket{}/css/main.css", last(2))),
+        ("not_found", "/api/v2/missing".to_string()),
+    ];
+}
+
+fn bench_find(c: &mut Criterion) {
+    let mut group = c.benchmark_group("route_tree_find");
+    for count in ROUTE_COUNTS {
+        let router = router_with_routes(count);
+        let routes = router.route_table();
+        for (kind, path) in request_paths(count) {
+            group.bench_with_input(BenchmarkId::new(kind, count), &path, |b, path| {
+                b.iter(|| routes.find(black_box(path), &HttpMethod::GET));
+            });
+        }
+    }
+    group.finish();
+}
+
+fn bench_handle_request(c: &mut Criterion) {
+    let mut group = c.benchmark_group("handle_request");
+    for count in ROUTE_COUNTS {
+        let router = router_with_routes(count);
+        for (kind, path) in request_paths(count) {
+            let request_line = format!("GET {} HTTP/1.1", path);
+            group.bench_with_input(BenchmarkId::new(kind, count), &request_line, |b, line| {
+                b.iter_batched(
+                    || Request::new(&[line.to_string()]).unwrap(),
+                    |request| router.handle_request(request),
+                    BatchSize::SmallInput,
+                );
+            });
+        }
+
+        // the same path matching no route, answered from the not found cache
+        let mut cached_router = router_with_routes(count);
+        cached_router.not_found_cache = Some(NotFoundCache::new(1024));
+        let request_line = format!("GET {} HTTP/1.1", request_paths(count)[3].1);
+        group.bench_with_input(
+            BenchmarkId::new("not_found_cached", count),
+            &request_line,
+            |b, line| {
+                b.iter_batched(
+                    || Request::new(&[line.to_string()]).unwrap(),
+                    |request| cached_router.handle_request(request),
+                    BatchSize::SmallInput,
+                );
+            },
+        );
+    }
+    group.finish();
+}
+
+criterion_group!(benches, bench_find, bench_handle_request);
+criterion_main!(benches);
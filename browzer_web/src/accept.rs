@@ -0,0 +1,254 @@
+//! This module handles the reporting of failed and dropped connections in the accept loop of the
+//! `WebServer`, and of connections which failed while they were being served.
+//!
+//! Accept errors tend to come in storms: once the process runs out of file descriptors(`EMFILE`),
+//! every single `accept` call fails immediately and logging each failure floods the console while
+//! the loop spins at full speed. Reports are therefore rate-limited and counted, and the accept
+//! loop briefly backs off while file descriptors are exhausted, giving in-flight connections time
+//! to finish and release theirs. A client sending malformed requests could flood the console just
+//! the same, so the errors of served connections share the rate limit.
+
+// standard library imports
+use std::{
+    error, fmt, io,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+// the accept loop backs off for this long after the first fd exhaustion error, doubling with every
+// consecutive one up to `MAX_BACKOFF`
+const MIN_BACKOFF: Duration = Duration::from_millis(10);
+const MAX_BACKOFF: Duration = Duration::from_secs(1);
+
+/// A shared hook which receives the rate-limited accept error reports.
+pub type AcceptErrorHook = Arc<dyn Fn(&AcceptErrorReport) + 'static + Send + Sync>;
+
+/// What failed about a connection reported to the accept error hook.
+///
+/// # Variants
+///
+/// - `Accept` - Accepting the connection failed.
+/// - `Dropped` - The connection was accepted but had to be dropped because it couldn't be handed
+///   to a worker thread.
+/// - `Connection` - Serving the connection failed, like when the client sent a malformed request
+///   head or the connection broke off.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AcceptErrorKind {
+    Accept,
+    Dropped,
+    Connection,
+}
+
+/// A report of a failed or dropped connection, as passed to the accept error hook.
+///
+/// # Fields
+///
+/// - `error` - The error which caused the connection to fail.
+/// - `kind` - The `AcceptErrorKind`, what failed about the connection.
+/// - `suppressed` - The number of errors since the previous report which weren't reported because
+///   of the rate limit.
+/// - `total` - The total number of errors since the server started listening.
+/// - `fd_exhausted` - Whether the error means the process ran out of file descriptors.
+/// - `backoff` - How long the accept loop pauses before accepting the next connection, if at all.
+#[derive(Debug)]
+pub struct AcceptErrorReport<'a> {
+    pub error: &'a dyn error::Error,
+    pub kind: AcceptErrorKind,
+    pub suppressed: u64,
+    pub total: u64,
+    pub fd_exhausted: bool,
+    pub backoff: Option<Duration>,
+}
+
+impl fmt::Display for AcceptErrorReport<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.kind {
+            AcceptErrorKind::Accept => {
+                write!(f, "Failed to establish a connection, Error: {}", self.error)?
+            }
+            AcceptErrorKind::Dropped => {
+                write!(f, "Dropped an incoming connection, Error: {}", self.error)?
+            }
+            AcceptErrorKind::Connection => write!(
+                f,
+                "Failed to handle incoming request, Error: {}",
+                self.error
+            )?,
+        }
+        if self.suppressed > 0 {
+            write!(f, " ({} similar errors suppressed)", self.suppressed)?;
+        }
+        if let Some(backoff) = self.backoff {
+            write!(
+                f,
+                " (file descriptors exhausted, pausing accepts for {:?})",
+                backoff
+            )?;
+        }
+        return Ok(());
+    }
+}
+
+/// Configuration of the accept error reporting.
+///
+/// # Fields
+///
+/// - `interval` - The minimum time between two reports(defaults to 1 second), errors in between
+///   are only counted.
+/// - `hook` - An optional hook which receives the reports instead of them being printed to the
+///   console.
+// ----- AcceptErrorLog struct
+pub struct AcceptErrorLog {
+    pub interval: Duration,
+    pub hook: Option<AcceptErrorHook>,
+}
+
+impl fmt::Debug for AcceptErrorLog {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("AcceptErrorLog")
+            .field("interval", &self.interval)
+            .field(
+                "hook",
+                &"Option<Arc<dyn Fn(&AcceptErrorReport) + 'static + Send + Sync>>",
+            )
+            .finish()
+    }
+}
+
+// default implementation for AcceptErrorLog struct
+impl Default for AcceptErrorLog {
+    fn default() -> Self {
+        return AcceptErrorLog {
+            interval: Duration::from_secs(1),
+            hook: None,
+        };
+    }
+}
+
+impl AcceptErrorLog {
+    // creates the counting state used by a single run of the accept loop
+    pub(crate) fn tracker(&self) -> AcceptErrorTracker {
+        return AcceptErrorTracker {
+            reporter: Arc::new(ErrorReporter {
+                interval: self.interval,
+                hook: self.hook.clone(),
+                counts: Mutex::new(ReportCounts::default()),
+            }),
+            backoff: None,
+        };
+    }
+}
+
+// the state of the accept error reporting which is owned by the accept loop, the counts are
+// shared with the connections it accepted
+pub(crate) struct AcceptErrorTracker {
+    reporter: Arc<ErrorReporter>,
+    backoff: Option<Duration>,
+}
+
+impl AcceptErrorTracker {
+    // records a failed `accept` call, returning how long the accept loop should pause
+    pub(crate) fn accept_failed(&mut self, error: &io::Error) -> Option<Duration> {
+        let fd_exhausted = is_fd_exhaustion(error);
+        self.backoff = match (fd_exhausted, self.backoff) {
+            (false, _) => None,
+            (true, None) => Some(MIN_BACKOFF),
+            (true, Some(backoff)) => Some((backoff * 2).min(MAX_BACKOFF)),
+        };
+        self.reporter
+            .report(error, AcceptErrorKind::Accept, fd_exhausted, self.backoff);
+        return self.backoff;
+    }
+
+    // records a connection which was accepted but couldn't be handed to a worker thread
+    pub(crate) fn connection_dropped(&mut self, error: &dyn error::Error) {
+        self.reporter
+            .report(error, AcceptErrorKind::Dropped, false, self.backoff);
+    }
+
+    // records a successfully accepted connection, which ends any ongoing backoff
+    pub(crate) fn accepted(&mut self) {
+        self.backoff = None;
+    }
+
+    // the reporter the failures of the accepted connections are recorded with
+    pub(crate) fn reporter(&self) -> Arc<ErrorReporter> {
+        return Arc::clone(&self.reporter);
+    }
+}
+
+// rate-limits and counts the error reports of an accept loop and of the connections it accepted
+pub(crate) struct ErrorReporter {
+    interval: Duration,
+    hook: Option<AcceptErrorHook>,
+    counts: Mutex<ReportCounts>,
+}
+
+#[derive(Default)]
+struct ReportCounts {
+    last_report: Option<Instant>,
+    suppressed: u64,
+    total: u64,
+}
+
+impl ErrorReporter {
+    // records a connection which failed while it was being served
+    pub(crate) fn connection_failed(&self, error: &dyn error::Error) {
+        self.report(error, AcceptErrorKind::Connection, false, None);
+    }
+
+    fn report(
+        &self,
+        error: &dyn error::Error,
+        kind: AcceptErrorKind,
+        fd_exhausted: bool,
+        backoff: Option<Duration>,
+    ) {
+        // the counts are only locked while they are updated, not while the hook runs
+        let (suppressed, total) = {
+            let mut counts = self.counts.lock().unwrap_or_else(|e| e.into_inner());
+            counts.total += 1;
+            let now = Instant::now();
+            match counts.last_report {
+                Some(last_report) if now.duration_since(last_report) < self.interval => {
+                    counts.suppressed += 1;
+                    return;
+                }
+                _ => {}
+            }
+            counts.last_report = Some(now);
+            (std::mem::take(&mut counts.suppressed), counts.total)
+        };
+
+        let report = AcceptErrorReport {
+            error,
+            kind,
+            suppressed,
+            total,
+            fd_exhausted,
+            backoff,
+        };
+        match self.hook {
+            Some(ref hook) => (hook)(&report),
+            None => eprintln!("{}", report),
+        }
+    }
+}
+
+// the Winsock error code for too many open sockets, which `libc` doesn't define
+#[cfg(windows)]
+const WSAEMFILE: i32 = 10024;
+
+// whether an I/O error means that the process(`EMFILE`) or the whole system(`ENFILE`) ran out of
+// file descriptors. `io::ErrorKind` has no stable kind for it, so the OS error code is checked
+fn is_fd_exhaustion(error: &io::Error) -> bool {
+    #[cfg(unix)]
+    return matches!(
+        error.raw_os_error(),
+        Some(libc::EMFILE) | Some(libc::ENFILE)
+    );
+    #[cfg(windows)]
+    return error.raw_os_error() == Some(WSAEMFILE);
+    #[cfg(not(any(unix, windows)))]
+    return false;
+}
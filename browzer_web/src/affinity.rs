@@ -0,0 +1,214 @@
+//! This module pins server threads to CPU cores, which keeps the caches of a core warm for the
+//! thread running on it and improves tail latency on dedicated hosts.
+//!
+//! Pinning is only supported on Linux(using `sched_setaffinity`), on other platforms the functions
+//! here don't do anything and report that no thread was pinned.
+
+// standard library imports
+use std::thread::JoinHandle;
+
+/// Describes which CPU cores the threads of a `WebServer` are pinned to.
+///
+/// # Fields
+///
+/// - `workers` - The cores of the worker threads, the first worker is pinned to the first core,
+///   the second worker to the second core and so on, starting over from the first core if there
+///   are more workers than cores. Empty to leave the workers unpinned.
+/// - `acceptors` - The cores of the threads accepting connections, one per listener of the server
+///   in the order of `WebServer::local_addrs`(the first one is the thread calling
+///   `WebServer::listen`), starting over from the first core if there are more listeners than
+///   cores. Empty to leave them unpinned.
+///
+/// # Examples
+///
+/// ```rust
+/// use browzer_web::affinity::CoreMap;
+///
+/// // four workers on cores 2 to 5, accepting connections on cores 0 and 1
+/// let core_map = CoreMap::new(vec![2, 3, 4, 5]).acceptor(0).acceptor(1);
+///
+/// assert_eq!(core_map.workers, vec![2, 3, 4, 5]);
+/// assert_eq!(core_map.acceptors, vec![0, 1]);
+/// assert_eq!(core_map.acceptor_core(2), Some(0));
+/// ```
+// ----- CoreMap struct
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CoreMap {
+    pub workers: Vec<usize>,
+    pub acceptors: Vec<usize>,
+}
+
+impl CoreMap {
+    /// Creates a new `CoreMap` pinning the workers to the given cores, leaving the acceptor threads
+    /// unpinned.
+    ///
+    /// # Arguments
+    ///
+    /// - `workers` - The cores of the worker threads, in the order of the workers.
+    pub fn new(workers: Vec<usize>) -> CoreMap {
+        return CoreMap {
+            workers,
+            acceptors: Vec::new(),
+        };
+    }
+
+    /// Pins the acceptor thread of the next listener to the given core, the first call picks the
+    /// core of the first listener, the second call the one of the second listener and so on.
+    ///
+    /// # Arguments
+    ///
+    /// - `core` - The core of the thread accepting connections.
+    pub fn acceptor(mut self, core: usize) -> CoreMap {
+        self.acceptors.push(core);
+        return self;
+    }
+
+    /// Returns the core the acceptor thread of a listener is pinned to.
+    ///
+    /// # Arguments
+    ///
+    /// - `listener` - The index of the listener, in the order of `WebServer::local_addrs`.
+    ///
+    /// # Returns
+    ///
+    /// - `Option<usize>` - The core, `None` if the acceptor threads are left unpinned.
+    pub fn acceptor_core(&self, listener: usize) -> Option<usize> {
+        if self.acceptors.is_empty() {
+            return None;
+        }
+        return Some(self.acceptors[listener % self.acceptors.len()]);
+    }
+
+    /// Creates a `CoreMap` spreading the given number of workers over the cores the process is
+    /// allowed to run on, and the acceptor threads of the listeners as well(the first listener on
+    /// the first core, the second one on the second core and so on).
+    ///
+    /// # Arguments
+    ///
+    /// - `workers` - The number of worker threads.
+    ///
+    /// # Returns
+    ///
+    /// - `CoreMap` - The core map, empty on platforms where pinning isn't supported.
+    pub fn round_robin(workers: usize) -> CoreMap {
+        let cores = available_cores();
+        if cores.is_empty() {
+            return CoreMap::default();
+        }
+        return CoreMap {
+            workers: (0..workers).map(|i| cores[i % cores.len()]).collect(),
+            acceptors: cores,
+        };
+    }
+}
+
+/// Returns whether threads can be pinned to cores on this platform.
+pub fn is_supported() -> bool {
+    return cfg!(target_os = "linux");
+}
+
+/// Returns the cores the process is allowed to run on, in ascending order.
+///
+/// # Returns
+///
+/// - `Vec<usize>` - The indexes of the cores, empty if they couldn't be determined or pinning
+///   isn't supported on this platform.
+pub fn available_cores() -> Vec<usize> {
+    #[cfg(target_os = "linux")]
+    {
+        // SAFETY: `cpu_set_t` is a plain bit set, for which all zeroes is a valid(empty) value
+        let mut set: libc::cpu_set_t = unsafe { std::mem::zeroed() };
+        // SAFETY: `sched_getaffinity` only writes to the set passed to it, which is valid for the
+        // duration of the call and as large as the size passed along
+        let result =
+            unsafe { libc::sched_getaffinity(0, std::mem::size_of::<libc::cpu_set_t>(), &mut set) };
+        if result != 0 {
+            return Vec::new();
+        }
+        return (0..libc::CPU_SETSIZE as usize)
+            // SAFETY: every core checked is within the set
+            .filter(|core| unsafe { libc::CPU_ISSET(*core, &set) })
+            .collect();
+    }
+    #[cfg(not(target_os = "linux"))]
+    return Vec::new();
+}
+
+/// Pins the calling thread to a core.
+///
+/// # Arguments
+///
+/// - `core` - The index of the core.
+///
+/// # Returns
+///
+/// - `bool` - `true` if the thread was pinned, `false` if the core doesn't exist, the process
+///   isn't allowed to run on it or pinning isn't supported on this platform.
+pub fn pin_current_thread(core: usize) -> bool {
+    #[cfg(target_os = "linux")]
+    {
+        let set = match core_set(core) {
+            Some(set) => set,
+            None => return false,
+        };
+        // SAFETY: the set is valid for the duration of the call and as large as the size passed
+        // along, a pid of 0 refers to the calling thread
+        return unsafe { libc::sched_setaffinity(0, std::mem::size_of::<libc::cpu_set_t>(), &set) }
+            == 0;
+    }
+    #[cfg(not(target_os = "linux"))]
+    {
+        let _ = core;
+        return false;
+    }
+}
+
+/// Pins a spawned thread to a core.
+///
+/// # Arguments
+///
+/// - `handle` - The `JoinHandle` of the thread, which has to be still running.
+/// - `core` - The index of the core.
+///
+/// # Returns
+///
+/// - `bool` - `true` if the thread was pinned, `false` if the core doesn't exist, the process
+///   isn't allowed to run on it or pinning isn't supported on this platform.
+pub fn pin_thread<T>(handle: &JoinHandle<T>, core: usize) -> bool {
+    #[cfg(target_os = "linux")]
+    {
+        use std::os::unix::thread::JoinHandleExt;
+
+        let set = match core_set(core) {
+            Some(set) => set,
+            None => return false,
+        };
+        // SAFETY: the thread of a `JoinHandle` isn't detached or joined while the handle is
+        // borrowed, and the set is valid for the duration of the call
+        return unsafe {
+            libc::pthread_setaffinity_np(
+                handle.as_pthread_t(),
+                std::mem::size_of::<libc::cpu_set_t>(),
+                &set,
+            )
+        } == 0;
+    }
+    #[cfg(not(target_os = "linux"))]
+    {
+        let _ = (handle, core);
+        return false;
+    }
+}
+
+// builds a cpu set containing only the given core, `None` if the core is outside of the set
+#[cfg(target_os = "linux")]
+fn core_set(core: usize) -> Option<libc::cpu_set_t> {
+    if core >= libc::CPU_SETSIZE as usize {
+        return None;
+    }
+    // SAFETY: `cpu_set_t` is a plain bit set, for which all zeroes is a valid(empty) value
+    let mut set: libc::cpu_set_t = unsafe { std::mem::zeroed() };
+    // SAFETY: the core was checked to be within the set above
+    unsafe { libc::CPU_SET(core, &mut set) };
+    return Some(set);
+}
@@ -0,0 +1,45 @@
+//! This module contains authentication helpers which apps(and the framework's own admin endpoints)
+//! can build their login flows on.
+//!
+//! `BasicAuth` and `BearerAuth` protect route handlers with the `Basic` and `Bearer`
+//! authentication schemes, requests they let through carry the authenticated `Identity`(see
+//! `Context::identity`). With the `jwt` feature, `JwtAuth` does the same for JSON Web Tokens.
+
+pub mod basic;
+pub mod bearer;
+#[cfg(feature = "jwt")]
+pub mod jwt;
+pub mod totp;
+
+pub use basic::BasicAuth;
+pub use bearer::BearerAuth;
+#[cfg(feature = "jwt")]
+pub use jwt::JwtAuth;
+
+/// The authenticated client of a request, see `Context::identity`.
+///
+/// # Fields
+///
+/// - `scheme` - The authentication scheme the client authenticated with, like `Basic`.
+/// - `subject` - Who the client is, the username for `Basic` authentication and whatever the
+///   token validator returned for `Bearer` authentication(or the `sub` claim of a JSON Web Token).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Identity {
+    pub scheme: String,
+    pub subject: String,
+}
+
+// splits an `Authorization` header value into it's scheme and credentials, the scheme is compared
+// case-insensitively
+pub(crate) fn credentials<'a>(authorization: &'a str, scheme: &str) -> Option<&'a str> {
+    let (given_scheme, credentials) = authorization.trim().split_once(' ')?;
+    match given_scheme.eq_ignore_ascii_case(scheme) {
+        true => return Some(credentials.trim()),
+        false => return None,
+    }
+}
+
+// quotes a value for an auth-param of a `WWW-Authenticate` header
+pub(crate) fn quote(value: &str) -> String {
+    return format!("\"{}\"", value.replace('\\', "\\\\").replace('"', "\\\""));
+}
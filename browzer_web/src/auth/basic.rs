@@ -0,0 +1,146 @@
+//! This module implements HTTP `Basic` authentication(RFC 7617), usernames and passwords sent
+//! with every request.
+//!
+//! Basic credentials are only encoded, not encrypted, so only use it over HTTPS.
+
+// internal crate imports
+use crate::{auth, context, response, utils};
+
+// standard library imports
+use std::{fmt, sync::Arc};
+
+/// A boxed closure verifying a username and password.
+pub type CredentialsVerifier = Box<dyn Fn(&str, &str) -> bool + 'static + Send + Sync>;
+
+/// Protects route handlers with `Basic` authentication.
+///
+/// Requests without valid credentials are answered with `401 Unauthorized` and a
+/// `WWW-Authenticate` header making browsers ask for a username and password, without running the
+/// handler. Requests with valid credentials reach the handler with their `Identity`(the username)
+/// available through `Context::identity`.
+///
+/// # Examples
+///
+/// ```rust,no_run
+/// use browzer_web::{auth::BasicAuth, utils::{constant_time_eq, HttpStatusCode}, WebServer};
+///
+/// let mut server = WebServer::new("127.0.0.1:8080".to_string(), 4);
+/// let auth = BasicAuth::new("admin area", |username, password| {
+///     return username == "admin" && constant_time_eq(password.as_bytes(), b"hunter2");
+/// });
+///
+/// server.get("/admin", auth.wrap(|mut c| {
+///     let user = c.identity().map(|identity| identity.subject.clone()).unwrap_or_default();
+///     return c.send_string(HttpStatusCode::OK, &format!("Hello, {}", user));
+/// }));
+/// ```
+// ----- BasicAuth struct
+#[derive(Clone)]
+pub struct BasicAuth {
+    realm: String,
+    verifier: Arc<CredentialsVerifier>,
+}
+
+impl fmt::Debug for BasicAuth {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("BasicAuth")
+            .field("realm", &self.realm)
+            .field(
+                "verifier",
+                &"Box<dyn Fn(&str, &str) -> bool + 'static + Send + Sync>",
+            )
+            .finish()
+    }
+}
+
+impl BasicAuth {
+    /// Creates a new `BasicAuth`.
+    ///
+    /// # Arguments
+    ///
+    /// - `realm` - The name of the protected area, shown to users by some browsers.
+    /// - `verifier` - A closure returning whether a username and password are valid. Compare
+    ///   passwords with `utils::constant_time_eq` or a password hash function, so their comparison
+    ///   doesn't leak how much of a password was guessed right.
+    pub fn new<F>(realm: &str, verifier: F) -> BasicAuth
+    where
+        F: Fn(&str, &str) -> bool + 'static + Send + Sync,
+    {
+        return BasicAuth {
+            realm: realm.to_string(),
+            verifier: Arc::new(Box::new(verifier)),
+        };
+    }
+
+    /// Returns the identity of the client of a request, `None` if it didn't send valid `Basic`
+    /// credentials.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use browzer_web::{auth::BasicAuth, context::Context, request::Request};
+    ///
+    /// let auth = BasicAuth::new("api", |username, password| username == "axew" && password == "s3cret");
+    ///
+    /// let mut request = Request::default();
+    /// // base64 of "axew:s3cret"
+    /// request.headers.insert("Authorization".to_string(), "Basic YXhldzpzM2NyZXQ=".to_string());
+    ///
+    /// let identity = auth.authenticate(&Context::new(request)).unwrap();
+    /// assert_eq!(identity.subject, "axew");
+    /// ```
+    pub fn authenticate(&self, c: &context::Context) -> Option<auth::Identity> {
+        let authorization = c.request.header("Authorization")?;
+        let encoded = auth::credentials(authorization, "Basic")?;
+        let decoded = String::from_utf8(utils::base64_decode(encoded)?).ok()?;
+        let (username, password) = decoded.split_once(':')?;
+        match (self.verifier)(username, password) {
+            true => {
+                return Some(auth::Identity {
+                    scheme: "Basic".to_string(),
+                    subject: username.to_string(),
+                })
+            }
+            false => return None,
+        }
+    }
+
+    /// Wraps a route handler, so that it only runs for requests with valid credentials.
+    ///
+    /// # Arguments
+    ///
+    /// - `handler` - The route handler to protect.
+    ///
+    /// # Returns
+    ///
+    /// - A route handler which can be registered with `WebServer::get` and friends.
+    pub fn wrap<F>(
+        &self,
+        handler: F,
+    ) -> impl Fn(context::Context) -> response::Response + 'static + Send + Sync
+    where
+        F: Fn(context::Context) -> response::Response + 'static + Send + Sync,
+    {
+        let auth = self.clone();
+        return move |mut c: context::Context| match auth.authenticate(&c) {
+            Some(identity) => {
+                c.identity = Some(identity);
+                return handler(c);
+            }
+            None => return auth.challenge(&mut c),
+        };
+    }
+
+    // the response asking the client to authenticate
+    fn challenge(&self, c: &mut context::Context) -> response::Response {
+        let mut response = c.error_response(utils::HttpStatusCode::Unauthorized);
+        response.headers.insert(
+            "WWW-Authenticate".to_string(),
+            format!(
+                "Basic realm={}, charset=\"UTF-8\"",
+                auth::quote(&self.realm)
+            ),
+        );
+        return response;
+    }
+}
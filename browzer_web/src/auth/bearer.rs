@@ -0,0 +1,145 @@
+//! This module implements `Bearer` token authentication(RFC 6750), the scheme APIs use for access
+//! tokens sent in the `Authorization` header.
+
+// internal crate imports
+use crate::{auth, context, response, utils};
+
+// standard library imports
+use std::{fmt, sync::Arc};
+
+/// A boxed closure validating a token, returning the subject it was issued to.
+pub type TokenValidator = Box<dyn Fn(&str) -> Option<String> + 'static + Send + Sync>;
+
+/// Protects route handlers with `Bearer` token authentication.
+///
+/// Requests without a valid token are answered with `401 Unauthorized` and a `WWW-Authenticate`
+/// header, carrying `error="invalid_token"` if a token was sent but rejected, without running the
+/// handler. Requests with a valid token reach the handler with their `Identity` available through
+/// `Context::identity`.
+///
+/// # Examples
+///
+/// ```rust,no_run
+/// use browzer_web::{auth::BearerAuth, utils::HttpStatusCode, WebServer};
+///
+/// let mut server = WebServer::new("127.0.0.1:8080".to_string(), 4);
+/// let auth = BearerAuth::new("api", |token| match token {
+///     "token-of-axew" => Some("axew".to_string()),
+///     _ => None,
+/// });
+///
+/// server.get("/me", auth.wrap(|mut c| {
+///     let user = c.identity().map(|identity| identity.subject.clone()).unwrap_or_default();
+///     return c.send_string(HttpStatusCode::OK, &user);
+/// }));
+/// ```
+// ----- BearerAuth struct
+#[derive(Clone)]
+pub struct BearerAuth {
+    realm: String,
+    validator: Arc<TokenValidator>,
+}
+
+impl fmt::Debug for BearerAuth {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("BearerAuth")
+            .field("realm", &self.realm)
+            .field(
+                "validator",
+                &"Box<dyn Fn(&str) -> Option<String> + 'static + Send + Sync>",
+            )
+            .finish()
+    }
+}
+
+impl BearerAuth {
+    /// Creates a new `BearerAuth`.
+    ///
+    /// # Arguments
+    ///
+    /// - `realm` - The name of the protected area.
+    /// - `validator` - A closure returning the subject a token was issued to(like a user id), or
+    ///   `None` if the token isn't valid.
+    pub fn new<F>(realm: &str, validator: F) -> BearerAuth
+    where
+        F: Fn(&str) -> Option<String> + 'static + Send + Sync,
+    {
+        return BearerAuth {
+            realm: realm.to_string(),
+            validator: Arc::new(Box::new(validator)),
+        };
+    }
+
+    /// Returns the identity of the client of a request, `None` if it didn't send a valid `Bearer`
+    /// token.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use browzer_web::{auth::BearerAuth, context::Context, request::Request};
+    ///
+    /// let auth = BearerAuth::new("api", |token| (token == "abc").then(|| "axew".to_string()));
+    ///
+    /// let mut request = Request::default();
+    /// request.headers.insert("Authorization".to_string(), "Bearer abc".to_string());
+    ///
+    /// let identity = auth.authenticate(&Context::new(request)).unwrap();
+    /// assert_eq!(identity.subject, "axew");
+    /// ```
+    pub fn authenticate(&self, c: &context::Context) -> Option<auth::Identity> {
+        let token = self.token(c)?;
+        return (self.validator)(token).map(|subject| auth::Identity {
+            scheme: "Bearer".to_string(),
+            subject,
+        });
+    }
+
+    /// Wraps a route handler, so that it only runs for requests with a valid token.
+    ///
+    /// # Arguments
+    ///
+    /// - `handler` - The route handler to protect.
+    ///
+    /// # Returns
+    ///
+    /// - A route handler which can be registered with `WebServer::get` and friends.
+    pub fn wrap<F>(
+        &self,
+        handler: F,
+    ) -> impl Fn(context::Context) -> response::Response + 'static + Send + Sync
+    where
+        F: Fn(context::Context) -> response::Response + 'static + Send + Sync,
+    {
+        let auth = self.clone();
+        return move |mut c: context::Context| match auth.authenticate(&c) {
+            Some(identity) => {
+                c.identity = Some(identity);
+                return handler(c);
+            }
+            None => {
+                let invalid_token = auth.token(&c).is_some();
+                return auth.challenge(&mut c, invalid_token);
+            }
+        };
+    }
+
+    // the token of a request, `None` if it has no `Bearer` authorization
+    fn token<'a>(&self, c: &'a context::Context) -> Option<&'a str> {
+        let authorization = c.request.header("Authorization")?;
+        return auth::credentials(authorization, "Bearer").filter(|token| !token.is_empty());
+    }
+
+    // the response asking the client to authenticate, telling it that it's token was rejected if
+    // it sent one
+    fn challenge(&self, c: &mut context::Context, invalid_token: bool) -> response::Response {
+        let mut response = c.error_response(utils::HttpStatusCode::Unauthorized);
+        let mut challenge = format!("Bearer realm={}", auth::quote(&self.realm));
+        if invalid_token {
+            challenge.push_str(", error=\"invalid_token\"");
+        }
+        response
+            .headers
+            .insert("WWW-Authenticate".to_string(), challenge);
+        return response;
+    }
+}
@@ -0,0 +1,525 @@
+//! This module implements JSON Web Token(JWT, RFC 7519) authentication, for APIs whose clients
+//! send tokens issued by an identity provider or by the app itself as `Bearer` tokens.
+//!
+//! Tokens signed with HMAC-SHA256(`HS256`, with a shared secret) and RSASSA-PKCS1-v1_5 with
+//! SHA-256(`RS256`, with the public key of the issuer) are supported, the signatures are checked
+//! using `ring`. Requires the `jwt` feature.
+//!
+//! # Examples
+//!
+//! ```rust
+//! use browzer_web::auth::jwt::JwtAuth;
+//! use serde_json::json;
+//!
+//! let auth = JwtAuth::hs256(b"a secret of at least 32 bytes....").issuer("browzer");
+//! let token = auth.sign(&json!({"sub": "axew", "iss": "browzer"})).unwrap();
+//!
+//! let claims = auth.verify(&token).unwrap();
+//! assert_eq!(claims.subject(), Some("axew"));
+//! ```
+
+// external crate imports
+use ring::{hmac, signature};
+use serde::de::DeserializeOwned;
+use serde_json::{Map, Value};
+
+// internal crate imports
+use crate::{auth, context, error, response, utils};
+
+// standard library imports
+use std::{
+    sync::Arc,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+/// The claims of a verified token, available to handlers through `Context::get::<Claims>()`.
+///
+/// # Examples
+///
+/// ```rust
+/// use browzer_web::auth::jwt::JwtAuth;
+/// use serde::Deserialize;
+/// use serde_json::json;
+///
+/// #[derive(Deserialize)]
+/// struct Custom {
+///     sub: String,
+///     roles: Vec<String>,
+/// }
+///
+/// let auth = JwtAuth::hs256(b"a secret of at least 32 bytes....");
+/// let token = auth.sign(&json!({"sub": "axew", "roles": ["admin"]})).unwrap();
+/// let claims = auth.verify(&token).unwrap();
+///
+/// assert_eq!(claims.get("roles"), Some(&json!(["admin"])));
+/// let custom: Custom = claims.deserialize().unwrap();
+/// assert_eq!(custom.roles, vec!["admin"]);
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct Claims {
+    values: Map<String, Value>,
+}
+
+impl Claims {
+    /// Returns the value of a claim.
+    pub fn get(&self, name: &str) -> Option<&Value> {
+        return self.values.get(name);
+    }
+
+    /// Returns the subject(`sub`) the token was issued to.
+    pub fn subject(&self) -> Option<&str> {
+        return self.get("sub").and_then(Value::as_str);
+    }
+
+    /// Returns the issuer(`iss`) of the token.
+    pub fn issuer(&self) -> Option<&str> {
+        return self.get("iss").and_then(Value::as_str);
+    }
+
+    /// Returns the audiences(`aud`) the token is intended for, which may be a single string or an
+    /// array in the token.
+    pub fn audience(&self) -> Vec<&str> {
+        match self.get("aud") {
+            Some(Value::String(audience)) => return vec![audience.as_str()],
+            Some(Value::Array(audiences)) => {
+                return audiences.iter().filter_map(Value::as_str).collect();
+            }
+            _ => return vec![],
+        }
+    }
+
+    /// Returns the time the token expires at(`exp`), in seconds since the Unix epoch. Like all
+    /// NumericDate claims it may be fractional.
+    pub fn expires_at(&self) -> Option<f64> {
+        return self.get("exp").and_then(Value::as_f64);
+    }
+
+    /// Returns the time the token is valid from(`nbf`), in seconds since the Unix epoch.
+    pub fn not_before(&self) -> Option<f64> {
+        return self.get("nbf").and_then(Value::as_f64);
+    }
+
+    /// Returns the time the token was issued at(`iat`), in seconds since the Unix epoch.
+    pub fn issued_at(&self) -> Option<f64> {
+        return self.get("iat").and_then(Value::as_f64);
+    }
+
+    /// Deserializes the claims into a custom type.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `serde_json::Error` if the claims don't match the type.
+    pub fn deserialize<T: DeserializeOwned>(&self) -> Result<T, serde_json::Error> {
+        return serde_json::from_value(Value::Object(self.values.clone()));
+    }
+}
+
+// the key the signatures of tokens are checked with
+#[derive(Debug, Clone)]
+enum Key {
+    Hs256(hmac::Key),
+    Rs256(Arc<signature::RsaPublicKeyComponents<Vec<u8>>>),
+    Rs256Der(Arc<Vec<u8>>),
+}
+
+/// Protects route handlers with JSON Web Token authentication.
+///
+/// Requests have to send a token in the `Authorization: Bearer <token>` header. A token is
+/// accepted if it's `alg` matches the key, it's signature is valid, it hasn't expired(`exp`), is
+/// already valid(`nbf`) and, if configured, was issued by the expected issuer(`iss`) for the
+/// expected audience(`aud`). Expiry is checked with a leeway of 60 seconds by default, to allow
+/// for clocks which are slightly off.
+///
+/// `JwtAuth::wrap` protects a handler and `JwtAuth::middleware` every route after it, answering
+/// requests without a valid token with `401 Unauthorized`. With `JwtAuth::optional` requests
+/// without a token are let through too, for routes where authentication is optional, while
+/// requests with an invalid token are still rejected. Either way handlers get the claims with
+/// `Context::get::<Claims>()`, and the `sub` claim as the `Identity` of `Context::identity`.
+///
+/// # Examples
+///
+/// ```rust,no_run
+/// use browzer_web::{auth::jwt::{Claims, JwtAuth}, utils::HttpStatusCode, WebServer};
+///
+/// let mut server = WebServer::new("127.0.0.1:8080".to_string(), 4);
+/// let auth = JwtAuth::rs256_jwk("<modulus of the issuer>", "AQAB")
+///     .unwrap()
+///     .issuer("https://auth.example.com")
+///     .audience("api");
+///
+/// server.get("/me", auth.wrap(|mut c| {
+///     let subject = c.get::<Claims>().and_then(|claims| claims.subject()).unwrap_or_default();
+///     let subject = subject.to_string();
+///     return c.send_string(HttpStatusCode::OK, &subject);
+/// }));
+/// ```
+// ----- JwtAuth struct
+#[derive(Debug, Clone)]
+pub struct JwtAuth {
+    key: Key,
+    realm: String,
+    issuer: Option<String>,
+    audience: Option<String>,
+    leeway: Duration,
+    optional: bool,
+}
+
+impl JwtAuth {
+    /// Creates a new `JwtAuth` accepting tokens signed with `HS256` and the given secret, which
+    /// should be at least 32 random bytes.
+    pub fn hs256(secret: &[u8]) -> JwtAuth {
+        return JwtAuth::with_key(Key::Hs256(hmac::Key::new(hmac::HMAC_SHA256, secret)));
+    }
+
+    /// Creates a new `JwtAuth` accepting tokens signed with `RS256` by the owner of the given
+    /// public key, in the form identity providers publish it in their JSON Web Key Set.
+    ///
+    /// # Arguments
+    ///
+    /// - `n` - The base64url encoded modulus of the key, the `n` member of the JWK.
+    /// - `e` - The base64url encoded public exponent of the key, the `e` member of the JWK(usually
+    ///   `AQAB`).
+    ///
+    /// # Errors
+    ///
+    /// Returns `JwtError::InvalidKey` if the modulus or the exponent isn't valid base64url.
+    pub fn rs256_jwk(n: &str, e: &str) -> Result<JwtAuth, error::JwtError> {
+        let n = base64url_decode(n)
+            .ok_or_else(|| error::JwtError::InvalidKey("invalid modulus".to_string()))?;
+        let e = base64url_decode(e)
+            .ok_or_else(|| error::JwtError::InvalidKey("invalid exponent".to_string()))?;
+        return Ok(JwtAuth::with_key(Key::Rs256(Arc::new(
+            signature::RsaPublicKeyComponents { n, e },
+        ))));
+    }
+
+    /// Creates a new `JwtAuth` accepting tokens signed with `RS256` by the owner of the given
+    /// public key, DER encoded as a PKCS#1 `RSAPublicKey`(like the output of `openssl rsa -pubin
+    /// -RSAPublicKey_out -outform DER`).
+    pub fn rs256_der(public_key: &[u8]) -> JwtAuth {
+        return JwtAuth::with_key(Key::Rs256Der(Arc::new(public_key.to_vec())));
+    }
+
+    fn with_key(key: Key) -> JwtAuth {
+        return JwtAuth {
+            key,
+            realm: "api".to_string(),
+            issuer: None,
+            audience: None,
+            leeway: Duration::from_secs(60),
+            optional: false,
+        };
+    }
+
+    /// Sets the realm sent in the `WWW-Authenticate` header of rejected requests, `api` by
+    /// default.
+    pub fn realm(mut self, realm: &str) -> JwtAuth {
+        self.realm = realm.to_string();
+        return self;
+    }
+
+    /// Only accepts tokens whose `iss` claim is the given issuer.
+    pub fn issuer(mut self, issuer: &str) -> JwtAuth {
+        self.issuer = Some(issuer.to_string());
+        return self;
+    }
+
+    /// Only accepts tokens whose `aud` claim contains the given audience.
+    pub fn audience(mut self, audience: &str) -> JwtAuth {
+        self.audience = Some(audience.to_string());
+        return self;
+    }
+
+    /// Sets how far the `exp` and `nbf` claims may be off, 60 seconds by default.
+    pub fn leeway(mut self, leeway: Duration) -> JwtAuth {
+        self.leeway = leeway;
+        return self;
+    }
+
+    /// Lets requests without a token through, `false` by default. Requests with a token which
+    /// isn't valid are rejected either way.
+    pub fn optional(mut self, optional: bool) -> JwtAuth {
+        self.optional = optional;
+        return self;
+    }
+
+    /// Verifies a token, returning it's claims.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `JwtError` telling why the token was rejected.
+    pub fn verify(&self, token: &str) -> Result<Claims, error::JwtError> {
+        let malformed = |reason: &str| error::JwtError::Malformed(reason.to_string());
+        let mut parts = token.split('.');
+        let (header, payload, encoded_signature) = match (parts.next(), parts.next(), parts.next())
+        {
+            (Some(header), Some(payload), Some(encoded_signature)) if parts.next().is_none() => {
+                (header, payload, encoded_signature)
+            }
+            _ => return Err(malformed("expected three parts")),
+        };
+        let header = decode_object(header).ok_or_else(|| malformed("invalid header"))?;
+        let algorithm = header.get("alg").and_then(Value::as_str).unwrap_or("none");
+        if algorithm != self.algorithm() {
+            return Err(error::JwtError::UnexpectedAlgorithm(algorithm.to_string()));
+        }
+        let message = &token[..token.len() - encoded_signature.len() - 1];
+        let tag =
+            base64url_decode(encoded_signature).ok_or_else(|| malformed("invalid signature"))?;
+
+        // the signature covers the header and the claims, and is checked before the claims are
+        // even parsed
+        let verified = match self.key {
+            Key::Hs256(ref key) => hmac::verify(key, message.as_bytes(), &tag).is_ok(),
+            Key::Rs256(ref key) => key
+                .verify(
+                    &signature::RSA_PKCS1_2048_8192_SHA256,
+                    message.as_bytes(),
+                    &tag,
+                )
+                .is_ok(),
+            Key::Rs256Der(ref key) => {
+                signature::UnparsedPublicKey::new(&signature::RSA_PKCS1_2048_8192_SHA256, &**key)
+                    .verify(message.as_bytes(), &tag)
+                    .is_ok()
+            }
+        };
+        if !verified {
+            return Err(error::JwtError::InvalidSignature);
+        }
+
+        let claims = Claims {
+            values: decode_object(payload).ok_or_else(|| malformed("invalid claims"))?,
+        };
+        let now = unix_time();
+        let leeway = self.leeway.as_secs_f64();
+        if let Some(expires_at) = numeric_date(&claims, "exp")? {
+            if now >= expires_at + leeway {
+                return Err(error::JwtError::Expired);
+            }
+        }
+        if let Some(not_before) = numeric_date(&claims, "nbf")? {
+            if now + leeway < not_before {
+                return Err(error::JwtError::NotYetValid);
+            }
+        }
+        if let Some(ref issuer) = self.issuer {
+            if claims.issuer() != Some(issuer.as_str()) {
+                return Err(error::JwtError::InvalidIssuer);
+            }
+        }
+        if let Some(ref audience) = self.audience {
+            if !claims.audience().contains(&audience.as_str()) {
+                return Err(error::JwtError::InvalidAudience);
+            }
+        }
+        return Ok(claims);
+    }
+
+    /// Signs claims into a token, for apps issuing their own tokens.
+    ///
+    /// # Returns
+    ///
+    /// - `Option<String>` - The token, `None` for `RS256` keys since signing needs the private key
+    ///   of the issuer, or if the claims aren't a JSON object.
+    pub fn sign(&self, claims: &Value) -> Option<String> {
+        let key = match self.key {
+            Key::Hs256(ref key) => key,
+            Key::Rs256(_) | Key::Rs256Der(_) => return None,
+        };
+        if !claims.is_object() {
+            return None;
+        }
+        let header = base64url_encode(br#"{"alg":"HS256","typ":"JWT"}"#);
+        let payload = base64url_encode(claims.to_string().as_bytes());
+        let message = format!("{}.{}", header, payload);
+        let signature = hmac::sign(key, message.as_bytes());
+        return Some(format!(
+            "{}.{}",
+            message,
+            base64url_encode(signature.as_ref())
+        ));
+    }
+
+    /// Returns the claims of the token sent with a request, verifying it.
+    ///
+    /// # Errors
+    ///
+    /// Returns `JwtError::Malformed` if the request has no `Bearer` token, or a `JwtError` telling
+    /// why the token was rejected.
+    pub fn authenticate(&self, c: &context::Context) -> Result<Claims, error::JwtError> {
+        match token(c) {
+            Some(token) => return self.verify(token),
+            None => return Err(error::JwtError::Malformed("missing token".to_string())),
+        }
+    }
+
+    /// Wraps a route handler, so that it only runs for requests with a valid token.
+    ///
+    /// # Arguments
+    ///
+    /// - `handler` - The route handler to protect.
+    ///
+    /// # Returns
+    ///
+    /// - A route handler which can be registered with `WebServer::get` and friends.
+    pub fn wrap<F>(
+        &self,
+        handler: F,
+    ) -> impl Fn(context::Context) -> response::Response + 'static + Send + Sync
+    where
+        F: Fn(context::Context) -> response::Response + 'static + Send + Sync,
+    {
+        let auth = self.clone();
+        return move |mut c: context::Context| match auth.check(&mut c) {
+            None => return handler(c),
+            Some(challenge) => return challenge,
+        };
+    }
+
+    /// Returns a middleware attaching the claims of valid tokens to requests and answering the
+    /// other ones with `401 Unauthorized`, see `Context::abort`. Requests without a token are let
+    /// through if the authentication is `JwtAuth::optional`.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// use browzer_web::{auth::jwt::{Claims, JwtAuth}, utils::HttpStatusCode, WebServer};
+    ///
+    /// let mut server = WebServer::new("127.0.0.1:8080".to_string(), 4);
+    /// let auth = JwtAuth::hs256(b"a secret of at least 32 bytes....").optional(true);
+    /// server.middleware(auth.middleware());
+    ///
+    /// server.get("/", |mut c| match c.get::<Claims>().and_then(|claims| claims.subject()) {
+    ///     Some(subject) => {
+    ///         let greeting = format!("Hello, {}", subject);
+    ///         return c.send_string(HttpStatusCode::OK, &greeting);
+    ///     }
+    ///     None => return c.send_string(HttpStatusCode::OK, "Hello, stranger"),
+    /// });
+    /// ```
+    pub fn middleware(
+        &self,
+    ) -> impl Fn(context::Context) -> context::Context + 'static + Send + Sync {
+        let auth = self.clone();
+        return move |mut c: context::Context| {
+            if let Some(challenge) = auth.check(&mut c) {
+                c.abort(challenge);
+            }
+            return c;
+        };
+    }
+
+    // attaches the claims of the token of a request, returning the challenge the request is
+    // rejected with if it has no valid token(a missing one is fine for optional authentication)
+    fn check(&self, c: &mut context::Context) -> Option<response::Response> {
+        match self.authenticate(c) {
+            Ok(claims) => {
+                attach(c, claims);
+                return None;
+            }
+            Err(_) if self.optional && token(c).is_none() => return None,
+            Err(e) => {
+                let invalid_token = token(c).is_some();
+                return Some(self.challenge(c, invalid_token, &e));
+            }
+        }
+    }
+
+    // the name of the algorithm tokens have to be signed with
+    fn algorithm(&self) -> &'static str {
+        match self.key {
+            Key::Hs256(_) => return "HS256",
+            Key::Rs256(_) | Key::Rs256Der(_) => return "RS256",
+        }
+    }
+
+    // the response asking the client to authenticate, telling it why it's token was rejected if
+    // it sent one(RFC 6750)
+    fn challenge(
+        &self,
+        c: &mut context::Context,
+        invalid_token: bool,
+        error: &error::JwtError,
+    ) -> response::Response {
+        let mut response = c.error_response(utils::HttpStatusCode::Unauthorized);
+        let mut challenge = format!("Bearer realm={}", auth::quote(&self.realm));
+        if invalid_token {
+            challenge.push_str(&format!(
+                ", error=\"invalid_token\", error_description={}",
+                auth::quote(&error.to_string())
+            ));
+        }
+        response
+            .headers
+            .insert("WWW-Authenticate".to_string(), challenge);
+        return response;
+    }
+}
+
+// the token of a request, `None` if it has no `Bearer` authorization
+fn token(c: &context::Context) -> Option<&str> {
+    let authorization = c.request.header("Authorization")?;
+    return auth::credentials(authorization, "Bearer").filter(|token| !token.is_empty());
+}
+
+// makes the claims and the identity of a verified token available to the handler
+fn attach(c: &mut context::Context, claims: Claims) {
+    c.identity = Some(auth::Identity {
+        scheme: "Bearer".to_string(),
+        subject: claims.subject().unwrap_or_default().to_string(),
+    });
+    c.set(claims);
+}
+
+// decodes a base64url encoded JSON object
+fn decode_object(encoded: &str) -> Option<Map<String, Value>> {
+    let decoded = base64url_decode(encoded)?;
+    match serde_json::from_slice(&decoded) {
+        Ok(Value::Object(object)) => return Some(object),
+        _ => return None,
+    }
+}
+
+// encodes bytes as base64url without padding, as JSON Web Tokens do
+fn base64url_encode(bytes: &[u8]) -> String {
+    return utils::base64_encode(bytes)
+        .trim_end_matches('=')
+        .replace('+', "-")
+        .replace('/', "_");
+}
+
+// decodes base64url without padding, `None` if it isn't valid base64url
+fn base64url_decode(encoded: &str) -> Option<Vec<u8>> {
+    if encoded.contains(['+', '/', '=']) {
+        return None;
+    }
+    let mut standard = encoded.replace('-', "+").replace('_', "/");
+    while !standard.len().is_multiple_of(4) {
+        standard.push('=');
+    }
+    return utils::base64_decode(&standard);
+}
+
+// returns the current time in seconds since the Unix epoch
+fn unix_time() -> f64 {
+    return SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs_f64();
+}
+
+// reads a NumericDate claim, a claim which is present but isn't a finite number makes the token
+// malformed instead of skipping the check
+fn numeric_date(claims: &Claims, name: &str) -> Result<Option<f64>, error::JwtError> {
+    return match claims.get(name) {
+        None => Ok(None),
+        Some(value) => match value.as_f64() {
+            Some(date) if date.is_finite() => Ok(Some(date)),
+            _ => Err(error::JwtError::Malformed(format!(
+                "`{}` isn't a NumericDate",
+                name
+            ))),
+        },
+    };
+}
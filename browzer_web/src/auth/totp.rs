@@ -0,0 +1,339 @@
+//! This module implements time-based one-time passwords(TOTP, RFC 6238), the 6 digit codes shown
+//! by authenticator apps, for adding a second factor to a login.
+//!
+//! The HMAC-SHA1 and HMAC-SHA256 computations are implemented here(on top of the hash functions of
+//! the `digest` module), so two factor authentication doesn't pull in any extra dependencies.
+//!
+//! # Examples
+//!
+//! ```rust
+//! use browzer_web::auth::totp::Totp;
+//!
+//! // when the user enables two factor authentication, generate and store a secret for them and
+//! // show them the provisioning URI(usually as a QR code) to scan with their authenticator app
+//! let totp = Totp::new(Totp::generate_secret());
+//! let uri = totp.provisioning_uri("Browzer", "axew@example.com");
+//! assert!(uri.starts_with("otpauth://totp/Browzer:axew%40example.com?secret="));
+//!
+//! // when they log in, check the code they entered
+//! let code = totp.code();
+//! assert!(totp.verify(&code));
+//! ```
+
+// internal crate imports
+use crate::{digest, error, utils};
+
+// standard library imports
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// The length of secrets generated by `Totp::generate_secret` in bytes, the length of an HMAC-SHA1
+/// output as recommended by RFC 4226.
+pub const SECRET_LENGTH: usize = 20;
+
+// the base32 alphabet(RFC 4648) secrets are exchanged in
+const BASE32_ALPHABET: &[u8; 32] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZ234567";
+
+/// The hash function one-time passwords are computed with.
+///
+/// Most authenticator apps only support `Sha1`, which is still secure in HMAC.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TotpAlgorithm {
+    Sha1,
+    Sha256,
+}
+
+impl TotpAlgorithm {
+    /// Returns the name of the algorithm as used in provisioning URIs, like `SHA1`.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            TotpAlgorithm::Sha1 => return "SHA1",
+            TotpAlgorithm::Sha256 => return "SHA256",
+        }
+    }
+}
+
+/// A generator and validator of time-based one-time passwords for a single secret.
+///
+/// By default codes have 6 digits, change every 30 seconds and are computed with HMAC-SHA1, which
+/// is what authenticator apps expect. A code is accepted during the 30 seconds before and after
+/// it's own period too, to allow for clocks being slightly off and users typing slowly.
+///
+/// # Examples
+///
+/// ```rust
+/// use browzer_web::auth::totp::{Totp, TotpAlgorithm};
+///
+/// // the test vectors of RFC 6238
+/// let totp = Totp::new(b"12345678901234567890".to_vec()).digits(8);
+/// assert_eq!(totp.code_at(59), "94287082");
+/// assert_eq!(totp.code_at(1111111109), "07081804");
+///
+/// let totp = Totp::new(b"12345678901234567890123456789012".to_vec())
+///     .digits(8)
+///     .algorithm(TotpAlgorithm::Sha256);
+/// assert_eq!(totp.code_at(59), "46119246");
+/// assert_eq!(totp.code_at(20000000000), "77737706");
+/// ```
+// ----- Totp struct
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Totp {
+    secret: Vec<u8>,
+    digits: u32,
+    step: u64,
+    skew: u64,
+    algorithm: TotpAlgorithm,
+}
+
+impl Totp {
+    /// Creates a new `Totp` for a secret, with the default settings.
+    ///
+    /// # Arguments
+    ///
+    /// - `secret` - The shared secret, see `Totp::generate_secret`.
+    pub fn new(secret: Vec<u8>) -> Totp {
+        return Totp {
+            secret,
+            digits: 6,
+            step: 30,
+            skew: 1,
+            algorithm: TotpAlgorithm::Sha1,
+        };
+    }
+
+    /// Creates a new `Totp` for a base32 encoded secret, the format authenticator apps use.
+    ///
+    /// Lowercase letters, spaces, dashes and `=` padding are accepted too, so secrets can be
+    /// entered the way they are usually displayed.
+    ///
+    /// # Arguments
+    ///
+    /// - `secret` - The base32 encoded secret.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `TotpError::InvalidSecret` error if the secret contains a character which isn't
+    /// part of the base32 alphabet, or is empty.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use browzer_web::auth::totp::Totp;
+    ///
+    /// let totp = Totp::from_base32("gezd gnbv gy3t qojq").unwrap();
+    /// assert_eq!(totp.secret_base32(), "GEZDGNBVGY3TQOJQ");
+    ///
+    /// assert!(Totp::from_base32("not base32!").is_err());
+    /// ```
+    pub fn from_base32(secret: &str) -> Result<Totp, error::TotpError> {
+        let mut bytes = Vec::with_capacity(secret.len() * 5 / 8);
+        let mut buffer: u32 = 0;
+        let mut bits = 0;
+        for c in secret.chars() {
+            if c == ' ' || c == '-' || c == '=' {
+                continue;
+            }
+            let value = match BASE32_ALPHABET
+                .iter()
+                .position(|b| *b as char == c.to_ascii_uppercase())
+            {
+                Some(value) => value as u32,
+                None => {
+                    return Err(error::TotpError::InvalidSecret(format!(
+                        "{:?} isn't a base32 character",
+                        c
+                    )))
+                }
+            };
+            buffer = (buffer << 5) | value;
+            bits += 5;
+            if bits >= 8 {
+                bits -= 8;
+                bytes.push((buffer >> bits) as u8);
+                buffer &= (1 << bits) - 1;
+            }
+        }
+        if bytes.is_empty() {
+            return Err(error::TotpError::InvalidSecret(
+                "the secret is empty".to_string(),
+            ));
+        }
+        return Ok(Totp::new(bytes));
+    }
+
+    /// Generates a random secret of `SECRET_LENGTH` bytes.
+    pub fn generate_secret() -> Vec<u8> {
+        let mut secret = Vec::with_capacity(SECRET_LENGTH);
+        while secret.len() < SECRET_LENGTH {
+            secret.extend(utils::slug::random_bytes().take(SECRET_LENGTH - secret.len()));
+        }
+        return secret;
+    }
+
+    /// Sets the number of digits of the codes, 6 by default.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `digits` isn't between 6 and 9, shorter codes are too easy to guess.
+    pub fn digits(mut self, digits: u32) -> Totp {
+        assert!((6..=9).contains(&digits));
+        self.digits = digits;
+        return self;
+    }
+
+    /// Sets how long a code is valid, 30 seconds by default.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `step` is shorter than a second.
+    pub fn step(mut self, step: Duration) -> Totp {
+        assert!(step.as_secs() > 0);
+        self.step = step.as_secs();
+        return self;
+    }
+
+    /// Sets how many periods before and after the current one codes are still accepted from, 1
+    /// by default.
+    pub fn skew(mut self, skew: u64) -> Totp {
+        self.skew = skew;
+        return self;
+    }
+
+    /// Sets the hash function codes are computed with, `TotpAlgorithm::Sha1` by default.
+    pub fn algorithm(mut self, algorithm: TotpAlgorithm) -> Totp {
+        self.algorithm = algorithm;
+        return self;
+    }
+
+    /// Returns the secret, base32 encoded without padding.
+    pub fn secret_base32(&self) -> String {
+        let mut encoded = String::with_capacity(self.secret.len().div_ceil(5) * 8);
+        let mut buffer: u32 = 0;
+        let mut bits = 0;
+        for byte in self.secret.iter() {
+            buffer = (buffer << 8) | *byte as u32;
+            bits += 8;
+            while bits >= 5 {
+                bits -= 5;
+                encoded.push(BASE32_ALPHABET[((buffer >> bits) & 31) as usize] as char);
+            }
+            buffer &= (1 << bits) - 1;
+        }
+        if bits > 0 {
+            encoded.push(BASE32_ALPHABET[((buffer << (5 - bits)) & 31) as usize] as char);
+        }
+        return encoded;
+    }
+
+    /// Returns the `otpauth://` URI authenticator apps are set up with, usually shown as a QR code.
+    ///
+    /// # Arguments
+    ///
+    /// - `issuer` - The name of the app, shown by the authenticator app.
+    /// - `account` - The name of the user's account, like their email address.
+    pub fn provisioning_uri(&self, issuer: &str, account: &str) -> String {
+        let issuer = utils::percent_encode(issuer);
+        return format!(
+            "otpauth://totp/{}:{}?secret={}&issuer={}&algorithm={}&digits={}&period={}",
+            issuer,
+            utils::percent_encode(account),
+            self.secret_base32(),
+            issuer,
+            self.algorithm.as_str(),
+            self.digits,
+            self.step
+        );
+    }
+
+    /// Returns the code for the current time.
+    pub fn code(&self) -> String {
+        return self.code_at(unix_time());
+    }
+
+    /// Returns the code for a point in time.
+    ///
+    /// # Arguments
+    ///
+    /// - `unix_time` - The point in time, in seconds since the Unix epoch.
+    pub fn code_at(&self, unix_time: u64) -> String {
+        return self.code_for_step(unix_time / self.step);
+    }
+
+    /// Checks a code entered by the user against the current time.
+    ///
+    /// Every code stays valid for a while, so remember the step returned by `Totp::verify_step`
+    /// for each user and reject codes of that step or earlier ones, if a code which was observed
+    /// by an attacker must not be usable a second time.
+    pub fn verify(&self, code: &str) -> bool {
+        return self.verify_step(code, unix_time()).is_some();
+    }
+
+    /// Checks a code entered by the user against a point in time.
+    ///
+    /// # Arguments
+    ///
+    /// - `code` - The code entered by the user, surrounding whitespace is ignored.
+    /// - `unix_time` - The point in time, in seconds since the Unix epoch.
+    ///
+    /// # Returns
+    ///
+    /// - `Option<u64>` - The time step(the number of periods since the Unix epoch) the code
+    ///   belongs to, `None` if it doesn't match any of the accepted steps.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use browzer_web::auth::totp::Totp;
+    ///
+    /// let totp = Totp::new(b"12345678901234567890".to_vec());
+    /// let code = totp.code_at(1_000_000);
+    ///
+    /// assert_eq!(totp.verify_step(&code, 1_000_000), Some(33333));
+    /// // the previous period is still accepted, older ones aren't
+    /// assert_eq!(totp.verify_step(&code, 1_000_030), Some(33333));
+    /// assert_eq!(totp.verify_step(&code, 1_000_060), None);
+    /// ```
+    pub fn verify_step(&self, code: &str, unix_time: u64) -> Option<u64> {
+        let code = code.trim();
+        if code.len() != self.digits as usize {
+            return None;
+        }
+        let current = unix_time / self.step;
+        let first = current.saturating_sub(self.skew);
+        let last = current.saturating_add(self.skew);
+        // every accepted step is checked, so the time taken doesn't reveal which one matched
+        let mut matched = None;
+        for step in first..=last {
+            if utils::constant_time_eq(self.code_for_step(step).as_bytes(), code.as_bytes()) {
+                matched = Some(step);
+            }
+        }
+        return matched;
+    }
+
+    // computes the code of a time step(RFC 4226 with the step as the counter)
+    fn code_for_step(&self, step: u64) -> String {
+        let counter = step.to_be_bytes();
+        let digest = match self.algorithm {
+            TotpAlgorithm::Sha1 => digest::hmac_sha1(&self.secret, &counter),
+            TotpAlgorithm::Sha256 => digest::hmac_sha256(&self.secret, &counter),
+        };
+        // dynamic truncation, the last 4 bits of the digest pick the 4 bytes the code is taken from
+        let offset = (digest[digest.len() - 1] & 0x0f) as usize;
+        let binary = u32::from_be_bytes([
+            digest[offset] & 0x7f,
+            digest[offset + 1],
+            digest[offset + 2],
+            digest[offset + 3],
+        ]);
+        let code = binary as u64 % 10u64.pow(self.digits);
+        return format!("{:0width$}", code, width = self.digits as usize);
+    }
+}
+
+// returns the current time in seconds since the Unix epoch
+fn unix_time() -> u64 {
+    return SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs();
+}
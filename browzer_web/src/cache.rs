@@ -0,0 +1,416 @@
+//! This module caches the responses of selected routes in memory, so that repeated `GET` requests
+//! for them skip the route handler, see `WebServer::response_cache`.
+//!
+//! Cached entries can be purged from code with `ResponseCache::purge`, or over HTTP through an
+//! optional token protected purge endpoint, and configured URLs can be warmed up when the server
+//! starts listening so that the first visitors already get cached responses.
+
+// internal crate imports
+use crate::{context, request, response, utils};
+
+// standard library imports
+use std::{
+    collections::HashMap,
+    fmt,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+/// The default maximum number of responses held by a `ResponseCache`.
+pub const DEFAULT_MAX_ENTRIES: usize = 1024;
+
+// a cached response and when it was stored
+struct CachedResponse {
+    response: response::Response,
+    stored_at: Instant,
+}
+
+/// An in-memory cache of responses, see `WebServer::response_cache`.
+///
+/// Only the responses for the paths passed to `ResponseCache::path` are cached, keyed by their
+/// path and query string, for the time to live it was created with. The cache is looked
+/// up once the global and route middlewares and the access control policies of a route ran, so
+/// authorization is still enforced for every request, but a cached response is served to every
+/// client passing them. Routes whose responses depend on the client(like it's session) must
+/// therefore not be cached.
+///
+/// A response is only stored if it answers a `GET` request without an `Authorization` header, has
+/// the `200 OK` status code, sets no cookies, isn't streamed and isn't marked `no-store`,
+/// `no-cache` or `private` by it's `Cache-Control` header. Cached responses answer both `GET` and
+/// `HEAD` requests and carry an `Age` header with the seconds since they were stored.
+///
+/// A `ResponseCache` is a handle which can be cloned, all clones share the same entries. This lets
+/// an application keep a clone for purging entries when the content behind them changes.
+///
+/// # Examples
+///
+/// ```rust
+/// use browzer_web::{cache::ResponseCache, request::Request, response::Response};
+/// use browzer_web::utils::HttpStatusCode;
+/// use std::time::Duration;
+///
+/// let cache = ResponseCache::new(Duration::from_secs(60)).path("/blog/*");
+///
+/// let mut request = Request::default();
+/// request.path = "/blog/hello".to_string();
+/// assert!(cache.lookup(&request).is_none());
+///
+/// cache.store(&request, &Response::new(HttpStatusCode::OK, "Hello".to_string()));
+/// assert_eq!(cache.lookup(&request).unwrap().body, "Hello");
+///
+/// // paths which aren't configured are never cached
+/// request.path = "/admin".to_string();
+/// cache.store(&request, &Response::new(HttpStatusCode::OK, "Admin".to_string()));
+/// assert!(cache.lookup(&request).is_none());
+/// ```
+// ----- ResponseCache struct
+#[derive(Clone)]
+pub struct ResponseCache {
+    ttl: Duration,
+    max_entries: usize,
+    paths: Vec<String>,
+    warm_up: Vec<String>,
+    purge_endpoint: Option<(String, String)>,
+    entries: Arc<Mutex<HashMap<String, CachedResponse>>>,
+}
+
+impl fmt::Debug for ResponseCache {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // the token of the purge endpoint is a secret, so only it's path is shown
+        let purge_endpoint = self.purge_endpoint.as_ref().map(|(path, _)| path);
+        f.debug_struct("ResponseCache")
+            .field("ttl", &self.ttl)
+            .field("max_entries", &self.max_entries)
+            .field("paths", &self.paths)
+            .field("warm_up", &self.warm_up)
+            .field("purge_endpoint", &purge_endpoint)
+            .field("entries", &self.len())
+            .finish()
+    }
+}
+
+impl ResponseCache {
+    /// Creates a new `ResponseCache` without any cached paths.
+    ///
+    /// # Arguments
+    ///
+    /// - `ttl` - How long a response is served from the cache after it was generated.
+    pub fn new(ttl: Duration) -> ResponseCache {
+        return ResponseCache {
+            ttl,
+            max_entries: DEFAULT_MAX_ENTRIES,
+            paths: Vec::new(),
+            warm_up: Vec::new(),
+            purge_endpoint: None,
+            entries: Arc::new(Mutex::new(HashMap::new())),
+        };
+    }
+
+    /// Caches the responses for a path.
+    ///
+    /// # Arguments
+    ///
+    /// - `path` - The path, compared without the query string. A path ending in `*` caches every
+    ///   path starting with the part before it.
+    pub fn path(mut self, path: &str) -> ResponseCache {
+        self.paths.push(path.to_string());
+        return self;
+    }
+
+    /// Sets the maximum number of cached responses, `DEFAULT_MAX_ENTRIES` by default. Once it is
+    /// reached, expired responses are dropped first and then the oldest one.
+    pub fn max_entries(mut self, max_entries: usize) -> ResponseCache {
+        self.max_entries = max_entries;
+        return self;
+    }
+
+    /// Sets the URLs requested when the server starts listening, so that their responses are
+    /// cached before the first client asks for them.
+    ///
+    /// The requests go through the router like any other request, middlewares included, but
+    /// without any headers besides `Host`.
+    ///
+    /// # Arguments
+    ///
+    /// - `urls` - Paths like `/blog`, or absolute URLs like `https://example.com/blog` whose host
+    ///   is sent in the `Host` header, which is needed if the server checks it(see
+    ///   `WebServer::allowed_hosts` and `WebServer::canonical_origin`).
+    pub fn warm_up(mut self, urls: &[&str]) -> ResponseCache {
+        self.warm_up = urls.iter().map(|url| url.to_string()).collect();
+        return self;
+    }
+
+    /// Enables an HTTP endpoint purging cached responses, registered by
+    /// `WebServer::response_cache`.
+    ///
+    /// The endpoint answers `POST` requests carrying the token in an `Authorization: Bearer`
+    /// header, with the path to purge in the `path` query parameter(see `ResponseCache::purge`),
+    /// like `POST /_cache/purge?path=/blog/*`. It responds with the number of purged responses as
+    /// JSON, like `{"purged":3}`, or `401 Unauthorized` if the token is missing or wrong.
+    ///
+    /// # Arguments
+    ///
+    /// - `path` - The path of the endpoint.
+    /// - `token` - The secret token clients have to send, which should be long and random.
+    pub fn purge_endpoint(mut self, path: &str, token: &str) -> ResponseCache {
+        self.purge_endpoint = Some((path.to_string(), token.to_string()));
+        return self;
+    }
+
+    /// Returns whether the responses for a path are cached.
+    pub fn caches(&self, path: &str) -> bool {
+        let path = path_without_query(path);
+        return self.paths.iter().any(|pattern| matches(pattern, path));
+    }
+
+    /// Looks up the cached response for a request.
+    ///
+    /// # Returns
+    ///
+    /// - `Option<Response>` - A copy of the cached response with an `Age` header, `None` if the
+    ///   request can't be answered from the cache or no fresh response is cached for it.
+    pub fn lookup(&self, request: &request::Request) -> Option<response::Response> {
+        if !self.accepts(request) {
+            return None;
+        }
+        let key = cache_key(&request.path);
+        let mut entries = self.lock();
+        let age = match entries.get(&key) {
+            Some(entry) => entry.stored_at.elapsed(),
+            None => return None,
+        };
+        if age >= self.ttl {
+            entries.remove(&key);
+            return None;
+        }
+        let mut response = entries.get(&key)?.response.clone();
+        response
+            .headers
+            .insert("Age".to_string(), age.as_secs().to_string());
+        return Some(response);
+    }
+
+    /// Stores the response to a request, if both are eligible for caching.
+    pub fn store(&self, request: &request::Request, response: &response::Response) {
+        // a dedicated `HEAD` route may not generate the body a `GET` request needs
+        if request.method != utils::HttpMethod::GET
+            || !self.accepts(request)
+            || !cacheable(response)
+            || self.max_entries == 0
+        {
+            return;
+        }
+        let key = cache_key(&request.path);
+        let mut entries = self.lock();
+        if !entries.contains_key(&key) && entries.len() >= self.max_entries {
+            let ttl = self.ttl;
+            entries.retain(|_, entry| entry.stored_at.elapsed() < ttl);
+            if entries.len() >= self.max_entries {
+                let oldest = entries
+                    .iter()
+                    .min_by_key(|(_, entry)| entry.stored_at)
+                    .map(|(key, _)| key.clone());
+                if let Some(oldest) = oldest {
+                    entries.remove(&oldest);
+                }
+            }
+        }
+        entries.insert(
+            key,
+            CachedResponse {
+                response: response.clone(),
+                stored_at: Instant::now(),
+            },
+        );
+    }
+
+    /// Purges the cached responses for a path, with any query string.
+    ///
+    /// # Arguments
+    ///
+    /// - `path` - The path. A path ending in `*` purges every path starting with the part before
+    ///   it, `*` alone purges everything.
+    ///
+    /// # Returns
+    ///
+    /// - `usize` - The number of purged responses.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use browzer_web::{cache::ResponseCache, request::Request, response::Response};
+    /// use browzer_web::utils::HttpStatusCode;
+    /// use std::time::Duration;
+    ///
+    /// let cache = ResponseCache::new(Duration::from_secs(60)).path("/blog/*");
+    /// for path in ["/blog/one", "/blog/one?page=2", "/blog/two"] {
+    ///     let mut request = Request::default();
+    ///     request.path = path.to_string();
+    ///     cache.store(&request, &Response::new(HttpStatusCode::OK, path.to_string()));
+    /// }
+    ///
+    /// assert_eq!(cache.purge("/blog/one"), 2);
+    /// assert_eq!(cache.purge("/blog/*"), 1);
+    /// assert!(cache.is_empty());
+    /// ```
+    pub fn purge(&self, path: &str) -> usize {
+        let mut entries = self.lock();
+        let before = entries.len();
+        entries.retain(|key, _| !matches(path, path_without_query(key)));
+        return before - entries.len();
+    }
+
+    /// Purges all cached responses, returning their number.
+    pub fn purge_all(&self) -> usize {
+        let mut entries = self.lock();
+        let purged = entries.len();
+        entries.clear();
+        return purged;
+    }
+
+    /// Returns the number of cached responses, including expired ones not dropped yet.
+    pub fn len(&self) -> usize {
+        return self.lock().len();
+    }
+
+    /// Returns whether no responses are cached.
+    pub fn is_empty(&self) -> bool {
+        return self.len() == 0;
+    }
+
+    /// Returns the path of the purge endpoint, if it is enabled.
+    pub fn purge_endpoint_path(&self) -> Option<&str> {
+        return self.purge_endpoint.as_ref().map(|(path, _)| path.as_str());
+    }
+
+    /// Handles a request to the purge endpoint, see `ResponseCache::purge_endpoint`.
+    pub fn handle_purge(&self, context: &context::Context) -> response::Response {
+        let authorized = match (
+            &self.purge_endpoint,
+            context.request.header("Authorization"),
+        ) {
+            (Some((_, token)), Some(authorization)) => match authorization.split_once(' ') {
+                Some((scheme, credentials)) if scheme.eq_ignore_ascii_case("Bearer") => {
+                    utils::constant_time_eq(credentials.trim().as_bytes(), token.as_bytes())
+                }
+                _ => false,
+            },
+            _ => false,
+        };
+        if !authorized {
+            let mut response = response::Response::new(
+                utils::HttpStatusCode::Unauthorized,
+                "Unauthorized".to_string(),
+            );
+            response
+                .headers
+                .insert("WWW-Authenticate".to_string(), "Bearer".to_string());
+            return response;
+        }
+
+        let path = match context.query_params.get("path") {
+            Some(path) if !path.is_empty() => path.clone(),
+            _ => {
+                return response::Response::new(
+                    utils::HttpStatusCode::BadRequest,
+                    "Missing the path to purge".to_string(),
+                )
+            }
+        };
+        let mut response = response::Response::new(
+            utils::HttpStatusCode::OK,
+            format!("{{\"purged\":{}}}", self.purge(&path)),
+        );
+        response
+            .headers
+            .insert("Content-Type".to_string(), "application/json".to_string());
+        return response;
+    }
+
+    /// Builds the requests for the URLs set with `ResponseCache::warm_up`.
+    pub fn warm_up_requests(&self) -> Vec<request::Request> {
+        return self
+            .warm_up
+            .iter()
+            .map(|url| {
+                let mut request = request::Request::default();
+                let rest = url
+                    .strip_prefix("https://")
+                    .or_else(|| url.strip_prefix("http://"));
+                match rest {
+                    Some(rest) => {
+                        let (host, path) = match rest.find(['/', '?']) {
+                            Some(index) => (&rest[..index], &rest[index..]),
+                            None => (rest, "/"),
+                        };
+                        request.headers.insert("Host".to_string(), host.to_string());
+                        request.path = path.to_string();
+                    }
+                    None => request.path = url.to_string(),
+                }
+                return request;
+            })
+            .collect();
+    }
+
+    // returns whether a request may be answered from(and it's response stored in) the cache
+    fn accepts(&self, request: &request::Request) -> bool {
+        return (request.method == utils::HttpMethod::GET
+            || request.method == utils::HttpMethod::HEAD)
+            && request.header("Authorization").is_none()
+            && self.caches(&request.path);
+    }
+
+    // locks the entries, recovering them if another thread panicked while holding the lock
+    fn lock(&self) -> std::sync::MutexGuard<'_, HashMap<String, CachedResponse>> {
+        match self.entries.lock() {
+            Ok(entries) => return entries,
+            Err(poisoned) => return poisoned.into_inner(),
+        }
+    }
+}
+
+// returns whether a response may be shared with other clients
+fn cacheable(response: &response::Response) -> bool {
+    if response.status_code != utils::HttpStatusCode::OK
+        || response.stream.is_some()
+        || !response.cookies.is_empty()
+    {
+        return false;
+    }
+    return !response.headers.iter().any(|(name, value)| {
+        name.eq_ignore_ascii_case("Set-Cookie")
+            || (name.eq_ignore_ascii_case("Cache-Control")
+                && value.split(',').any(|directive| {
+                    let directive = directive.trim();
+                    directive.eq_ignore_ascii_case("no-store")
+                        || directive.eq_ignore_ascii_case("no-cache")
+                        || directive.eq_ignore_ascii_case("private")
+                }))
+    });
+}
+
+// the router strips the slash of the root path, which the cache keys keep
+fn cache_key(path: &str) -> String {
+    match path.is_empty() || path.starts_with('?') {
+        true => return format!("/{}", path),
+        false => return path.to_string(),
+    }
+}
+
+// strips the query string of a path, keeping the slash of the root path
+fn path_without_query(path: &str) -> &str {
+    let path = path.split('?').next().unwrap_or_default();
+    match path.is_empty() {
+        true => return "/",
+        false => return path,
+    }
+}
+
+// matches a path against a pattern, which matches a prefix if it ends in `*`
+fn matches(pattern: &str, path: &str) -> bool {
+    match pattern.strip_suffix('*') {
+        Some(prefix) => return path.starts_with(prefix),
+        None => return path == pattern,
+    }
+}
@@ -0,0 +1,219 @@
+//! This module provides cancellation tokens, which tell long-running and streaming handlers that
+//! the client of their request already left.
+//!
+//! Every request gets a `CancellationToken` bound to it's connection. The token becomes cancelled
+//! when the server notices that the peer disconnected, either because writing to the connection
+//! failed or because the socket reports a hangup when the token is checked. The server also
+//! cancels the token of a request it gave up on, and a token becomes cancelled once the request
+//! timeout of it's request passed, so handlers notice the timeout while they are still running.
+//!
+//! Hangups are detected by polling the socket(on unix platforms only), which can't tell a client
+//! which closed the connection apart from one which only shut down it's sending side while still
+//! waiting for the response. For TLS connections a hangup is only detected once the peer's TCP
+//! connection is closed as well.
+
+// standard library imports
+use std::{
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
+    time::Instant,
+};
+
+/// A token which becomes cancelled once the client of a request disconnected, or the server gave
+/// up on the request.
+///
+/// Tokens are cheap to clone, and all clones share the same state. Handlers should check
+/// `is_cancelled` between units of work and stop once it returns `true`.
+///
+/// # Examples
+///
+/// ```rust,no_run
+/// # use browzer_web::{utils::HttpStatusCode, WebServer};
+/// let mut server = WebServer::new("127.0.0.1:8080".to_string(), 4);
+///
+/// server.get("/report", |mut c| {
+///     let cancellation = c.cancellation_token();
+///     let mut rows = vec![];
+///     for i in 0..1000 {
+///         if cancellation.is_cancelled() {
+///             // nobody is waiting for the report anymore
+///             break;
+///         }
+///         rows.push(format!("row {}", i));
+///     }
+///     return c.send_string(HttpStatusCode::OK, &rows.join("\n"));
+/// });
+/// ```
+// ----- CancellationToken struct
+#[derive(Debug, Clone, Default)]
+pub struct CancellationToken {
+    state: Arc<TokenState>,
+}
+
+#[derive(Debug, Default)]
+struct TokenState {
+    cancelled: AtomicBool,
+    // the file descriptor of the connection socket, polled for hangups while the request is being
+    // served and cleared before the connection is closed, so a reused descriptor is never polled
+    socket: Mutex<Option<i32>>,
+    // when the request of the token times out, the token is cancelled once it passed
+    deadline: Mutex<Option<Instant>>,
+    // the token this one was created from with `CancellationToken::child`, whose cancellation
+    // cancels this one too
+    parent: Option<CancellationToken>,
+}
+
+impl CancellationToken {
+    /// Creates a new token which isn't bound to a connection, so it's only cancelled by
+    /// `CancellationToken::cancel`.
+    pub fn new() -> CancellationToken {
+        return CancellationToken::default();
+    }
+
+    // creates a token which polls the given connection socket for hangups
+    #[cfg(unix)]
+    pub(crate) fn for_socket(socket: std::os::unix::io::RawFd) -> CancellationToken {
+        let token = CancellationToken::new();
+        if let Ok(mut guard) = token.state.socket.lock() {
+            *guard = Some(socket);
+        }
+        return token;
+    }
+
+    /// Creates a child token, which is cancelled along with this token but can also be cancelled
+    /// on it's own, without cancelling this token.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # use browzer_web::cancel::CancellationToken;
+    /// let token = CancellationToken::new();
+    /// let child = token.child();
+    ///
+    /// child.cancel();
+    /// assert!(!token.is_cancelled());
+    ///
+    /// let child = token.child();
+    /// token.cancel();
+    /// assert!(child.is_cancelled());
+    /// ```
+    pub fn child(&self) -> CancellationToken {
+        return CancellationToken {
+            state: Arc::new(TokenState {
+                parent: Some(self.clone()),
+                ..TokenState::default()
+            }),
+        };
+    }
+
+    /// Cancels the token, and with it all of it's clones.
+    pub fn cancel(&self) {
+        self.state.cancelled.store(true, Ordering::SeqCst);
+    }
+
+    /// Returns whether the token was cancelled, checking the connection for a hangup first if the
+    /// token is bound to one.
+    pub fn is_cancelled(&self) -> bool {
+        if self.state.cancelled.load(Ordering::SeqCst) {
+            return true;
+        }
+        if let Some(ref parent) = self.state.parent {
+            if parent.is_cancelled() {
+                self.cancel();
+                return true;
+            }
+        }
+        let timed_out = match self.state.deadline.lock() {
+            Ok(guard) => guard.is_some_and(|deadline| Instant::now() >= deadline),
+            Err(_) => false,
+        };
+        if timed_out {
+            self.cancel();
+            return true;
+        }
+        let hung_up = match self.state.socket.lock() {
+            Ok(guard) => match *guard {
+                Some(socket) => socket_hung_up(socket),
+                None => false,
+            },
+            Err(_) => false,
+        };
+        if hung_up {
+            self.cancel();
+        }
+        return hung_up;
+    }
+
+    // makes the token cancelled once the deadline of it's request passed
+    pub(crate) fn set_deadline(&self, deadline: Instant) {
+        if let Ok(mut guard) = self.state.deadline.lock() {
+            *guard = Some(deadline);
+        }
+    }
+
+    // stops polling the connection socket, it must be called before the socket is closed
+    pub(crate) fn detach(&self) {
+        if let Ok(mut guard) = self.state.socket.lock() {
+            *guard = None;
+        }
+    }
+}
+
+// detaches a token from it's connection socket when dropped, so that every way of leaving the
+// request(including errors and panics) detaches it before the connection is closed
+pub(crate) struct DetachGuard(pub(crate) CancellationToken);
+
+impl Drop for DetachGuard {
+    fn drop(&mut self) {
+        self.0.detach();
+    }
+}
+
+// checks whether the peer of a socket closed the connection, without consuming any data, pending
+// data(like a pipelined request) means the peer is still there
+#[cfg(unix)]
+fn socket_hung_up(socket: i32) -> bool {
+    let mut poll_fd = libc::pollfd {
+        fd: socket,
+        events: libc::POLLIN,
+        revents: 0,
+    };
+    // SAFETY: `poll` only accesses the single `pollfd` passed to it, and the socket is kept open
+    // while the token is bound to it
+    let ready = unsafe { libc::poll(&mut poll_fd, 1, 0) };
+    if ready <= 0 {
+        return false;
+    }
+    if poll_fd.revents & (libc::POLLHUP | libc::POLLERR) != 0 {
+        return true;
+    }
+    let mut byte = [0u8; 1];
+    // SAFETY: `recv` writes at most one byte into `byte`, which outlives the call
+    let received = unsafe {
+        libc::recv(
+            socket,
+            byte.as_mut_ptr() as *mut libc::c_void,
+            1,
+            libc::MSG_PEEK | libc::MSG_DONTWAIT,
+        )
+    };
+    if received == 0 {
+        // an orderly shutdown of the peer
+        return true;
+    }
+    if received < 0 {
+        let error = std::io::Error::last_os_error();
+        return !matches!(
+            error.kind(),
+            std::io::ErrorKind::WouldBlock | std::io::ErrorKind::Interrupted
+        );
+    }
+    return false;
+}
+
+#[cfg(not(unix))]
+fn socket_hung_up(_socket: i32) -> bool {
+    return false;
+}
@@ -0,0 +1,187 @@
+//! This module redirects requests to the canonical origin of a site, so that it is only reachable
+//! under one scheme and host name(like `https://example.com` rather than also
+//! `http://www.example.com`), which search engines and cookies scoped to a host rely on.
+//!
+//! A `CanonicalOrigin` is enabled with `WebServer::canonical_origin`.
+
+// internal crate imports
+use crate::{error, request, response, utils};
+
+// standard library imports
+use std::fmt;
+
+/// The canonical origin of a site, see `WebServer::canonical_origin`.
+///
+/// Requests arriving with another scheme or host name are redirected to the same path and query
+/// on the canonical origin. `GET` and `HEAD` requests are redirected with `301 Moved Permanently`,
+/// all other methods with `308 Permanent Redirect`, which keeps browsers from turning a `POST`
+/// into a `GET`. The port a request was sent to isn't compared, since it usually differs from the
+/// public one behind a reverse proxy, and requests without a `Host` header are never redirected.
+///
+/// Without a TLS terminating reverse proxy in front, the scheme of a request is the one the
+/// server is listening with. Behind such a proxy, enable `CanonicalOrigin::trust_forwarded_proto`
+/// so that the scheme is taken from the `X-Forwarded-Proto` header the proxy sets.
+///
+/// # Examples
+///
+/// ```rust
+/// use browzer_web::{canonical::CanonicalOrigin, request::Request};
+///
+/// let origin = CanonicalOrigin::parse("https://example.com").unwrap();
+///
+/// let mut request = Request::default();
+/// request.path = "/docs?page=2".to_string();
+/// request.headers.insert("Host".to_string(), "www.example.com".to_string());
+///
+/// let response = origin.redirect(&request, true).unwrap();
+/// assert_eq!(response.headers.get("Location").unwrap(), "https://example.com/docs?page=2");
+///
+/// request.headers.insert("Host".to_string(), "example.com".to_string());
+/// assert!(origin.redirect(&request, true).is_none());
+/// // plain HTTP requests are redirected to HTTPS
+/// assert!(origin.redirect(&request, false).is_some());
+/// ```
+// ----- CanonicalOrigin struct
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CanonicalOrigin {
+    scheme: String,
+    host: String,
+    port: Option<u16>,
+    trust_forwarded_proto: bool,
+}
+
+impl CanonicalOrigin {
+    /// Parses a canonical origin.
+    ///
+    /// # Arguments
+    ///
+    /// - `origin` - The origin, an `http` or `https` scheme followed by a host name and an
+    ///   optional port, like `https://example.com` or `http://localhost:8080`.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `ConfigValueError::InvalidOrigin` error if the origin has another scheme, no
+    /// host name, an invalid port or a path.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use browzer_web::canonical::CanonicalOrigin;
+    ///
+    /// assert!(CanonicalOrigin::parse("https://Example.com/").is_ok());
+    /// assert!(CanonicalOrigin::parse("http://[::1]:8080").is_ok());
+    /// assert!(CanonicalOrigin::parse("example.com").is_err());
+    /// assert!(CanonicalOrigin::parse("https://example.com/docs").is_err());
+    /// ```
+    pub fn parse(origin: &str) -> Result<CanonicalOrigin, error::ConfigValueError> {
+        let invalid = || error::ConfigValueError::InvalidOrigin(origin.to_string());
+        let (scheme, rest) = origin.trim().split_once("://").ok_or_else(invalid)?;
+        let scheme = scheme.to_ascii_lowercase();
+        if scheme != "http" && scheme != "https" {
+            return Err(invalid());
+        }
+        let authority = rest.strip_suffix('/').unwrap_or(rest);
+        if authority.contains(['/', '?', '#', '@']) {
+            return Err(invalid());
+        }
+
+        // IPv6 literals keep their brackets, like in the `Host` header
+        let (host, port) = match authority.starts_with('[') {
+            true => match authority.find(']') {
+                Some(end) => (&authority[..=end], authority[end + 1..].strip_prefix(':')),
+                None => return Err(invalid()),
+            },
+            false => match authority.split_once(':') {
+                Some((host, port)) => (host, Some(port)),
+                None => (authority, None),
+            },
+        };
+        if host.is_empty() {
+            return Err(invalid());
+        }
+        let port = match port {
+            Some(port) => Some(port.parse::<u16>().map_err(|_| invalid())?),
+            None => None,
+        };
+        // the default port of the scheme is left out of the redirect target
+        let port = port.filter(|port| {
+            !((scheme == "http" && *port == 80) || (scheme == "https" && *port == 443))
+        });
+        return Ok(CanonicalOrigin {
+            scheme,
+            host: host.to_ascii_lowercase(),
+            port,
+            trust_forwarded_proto: false,
+        });
+    }
+
+    /// Sets whether the scheme of a request is taken from the `X-Forwarded-Proto` header, `false`
+    /// by default.
+    ///
+    /// Only enable this if all requests come through a reverse proxy which sets the header,
+    /// otherwise clients can pick the scheme themselves.
+    pub fn trust_forwarded_proto(mut self, trust_forwarded_proto: bool) -> CanonicalOrigin {
+        self.trust_forwarded_proto = trust_forwarded_proto;
+        return self;
+    }
+
+    /// Redirects a request which didn't arrive on the canonical origin.
+    ///
+    /// # Arguments
+    ///
+    /// - `request` - The request.
+    /// - `tls` - Whether the request arrived over HTTPS, used unless the scheme is taken from the
+    ///   `X-Forwarded-Proto` header.
+    ///
+    /// # Returns
+    ///
+    /// - `Option<Response>` - The redirect to the canonical origin, `None` if the request already
+    ///   arrived on it or has no `Host` header.
+    pub fn redirect(&self, request: &request::Request, tls: bool) -> Option<response::Response> {
+        let host = request.host()?;
+        let forwarded_proto = match self.trust_forwarded_proto {
+            true => request
+                .header("X-Forwarded-Proto")
+                .and_then(|proto| proto.split(',').next())
+                .map(|proto| proto.trim().to_ascii_lowercase()),
+            false => None,
+        };
+        let scheme = match forwarded_proto {
+            Some(proto) => proto,
+            None => match tls {
+                true => "https".to_string(),
+                false => "http".to_string(),
+            },
+        };
+        if scheme == self.scheme && host == self.host {
+            return None;
+        }
+
+        let status_code = match request.method {
+            utils::HttpMethod::GET | utils::HttpMethod::HEAD => {
+                utils::HttpStatusCode::MovedPermanently
+            }
+            _ => utils::HttpStatusCode::PermanentRedirect,
+        };
+        // the path of the root is empty once formatted by the router
+        let path = match request.path.starts_with('/') {
+            true => request.path.clone(),
+            false => format!("/{}", request.path),
+        };
+        let mut response = response::Response::new(status_code, String::new());
+        response
+            .headers
+            .insert("Location".to_string(), format!("{}{}", self, path));
+        return Some(response);
+    }
+}
+
+// formats the origin like `https://example.com`
+impl fmt::Display for CanonicalOrigin {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.port {
+            Some(port) => return write!(f, "{}://{}:{}", self.scheme, self.host, port),
+            None => return write!(f, "{}://{}", self.scheme, self.host),
+        }
+    }
+}
@@ -0,0 +1,414 @@
+//! This module summarizes the client of a request from it's `User-Agent` header and the standard
+//! client hint headers(`Sec-CH-UA`, `Save-Data`, `DPR` and friends), see `Context::client`.
+//!
+//! The parser is deliberately small: it recognizes the common browser families, platforms and
+//! the usual signs of bots and scripts, which is enough for analytics and for adapting responses
+//! to the client. Client hints take precedence over the `User-Agent` header where they overlap,
+//! since they are more precise. Everything here is a heuristic, clients can send whatever they
+//! like, so never use it for access control.
+
+// internal crate imports
+use crate::request;
+
+// substrings of `User-Agent` headers(lowercase) sent by crawlers, scripts and other automated
+// clients
+const BOT_MARKERS: &[&str] = &[
+    "bot",
+    "crawl",
+    "spider",
+    "slurp",
+    "scrapy",
+    "headless",
+    "phantomjs",
+    "lighthouse",
+    "preview",
+    "facebookexternalhit",
+    "curl/",
+    "wget/",
+    "httpie/",
+    "python-requests",
+    "python-urllib",
+    "aiohttp",
+    "go-http-client",
+    "java/",
+    "okhttp",
+    "apache-httpclient",
+    "libwww-perl",
+    "node-fetch",
+    "axios/",
+    "postmanruntime",
+];
+
+/// The browser family of a client.
+///
+/// # Variants
+///
+/// - `Chrome` - Google Chrome.
+/// - `Chromium` - Chromium and other Chromium based browsers which don't identify themselves.
+/// - `Edge` - Microsoft Edge.
+/// - `Firefox` - Mozilla Firefox.
+/// - `Safari` - Apple Safari.
+/// - `Opera` - Opera.
+/// - `SamsungInternet` - Samsung Internet.
+/// - `InternetExplorer` - Microsoft Internet Explorer.
+/// - `Other` - Anything else, including clients which aren't browsers.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum BrowserFamily {
+    Chrome,
+    Chromium,
+    Edge,
+    Firefox,
+    Safari,
+    Opera,
+    SamsungInternet,
+    InternetExplorer,
+    Other,
+}
+
+impl BrowserFamily {
+    /// Returns the name of the browser family, like `Chrome`.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            BrowserFamily::Chrome => return "Chrome",
+            BrowserFamily::Chromium => return "Chromium",
+            BrowserFamily::Edge => return "Edge",
+            BrowserFamily::Firefox => return "Firefox",
+            BrowserFamily::Safari => return "Safari",
+            BrowserFamily::Opera => return "Opera",
+            BrowserFamily::SamsungInternet => return "Samsung Internet",
+            BrowserFamily::InternetExplorer => return "Internet Explorer",
+            BrowserFamily::Other => return "Other",
+        }
+    }
+}
+
+/// The client hints a request carries, every field is `None`(or empty) if it's header is missing
+/// or invalid.
+///
+/// Browsers only send most hints after the server asked for them with an `Accept-CH` response
+/// header, `Sec-CH-UA`, `Sec-CH-UA-Mobile` and `Sec-CH-UA-Platform` are sent by default.
+///
+/// # Fields
+///
+/// - `brands` - The brands and major versions of `Sec-CH-UA`, like `("Google Chrome", "124")`.
+/// - `mobile` - `Sec-CH-UA-Mobile`, whether the client is a mobile device.
+/// - `platform` - `Sec-CH-UA-Platform`, like `Windows` or `Android`.
+/// - `platform_version` - `Sec-CH-UA-Platform-Version`.
+/// - `model` - `Sec-CH-UA-Model`, the device model.
+/// - `device_memory` - `Sec-CH-Device-Memory`(or `Device-Memory`), in GiB.
+/// - `dpr` - `Sec-CH-DPR`(or `DPR`), the device pixel ratio.
+/// - `viewport_width` - `Sec-CH-Viewport-Width`(or `Viewport-Width`), in CSS pixels.
+/// - `prefers_color_scheme` - `Sec-CH-Prefers-Color-Scheme`, `light` or `dark`.
+/// - `save_data` - `Save-Data`, whether the client asked for reduced data usage.
+/// - `ect` - `ECT`, the effective connection type like `4g`.
+/// - `rtt` - `RTT`, the round trip time in milliseconds.
+/// - `downlink` - `Downlink`, the bandwidth in megabits per second.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ClientHints {
+    pub brands: Vec<(String, String)>,
+    pub mobile: Option<bool>,
+    pub platform: Option<String>,
+    pub platform_version: Option<String>,
+    pub model: Option<String>,
+    pub device_memory: Option<f32>,
+    pub dpr: Option<f32>,
+    pub viewport_width: Option<u32>,
+    pub prefers_color_scheme: Option<String>,
+    pub save_data: bool,
+    pub ect: Option<String>,
+    pub rtt: Option<u32>,
+    pub downlink: Option<f32>,
+}
+
+impl ClientHints {
+    /// Reads the client hints of a request.
+    pub fn from_request(request: &request::Request) -> ClientHints {
+        let header = |names: &[&str]| -> Option<String> {
+            return names
+                .iter()
+                .find_map(|name| request.header(name))
+                .map(|value| value.trim().to_string())
+                .filter(|value| !value.is_empty());
+        };
+        let number = |names: &[&str]| -> Option<f32> {
+            return header(names)
+                .and_then(|value| value.parse::<f32>().ok())
+                .filter(|value| value.is_finite() && *value >= 0.0);
+        };
+
+        return ClientHints {
+            brands: header(&["Sec-CH-UA"])
+                .map(|value| parse_brands(&value))
+                .unwrap_or_default(),
+            mobile: header(&["Sec-CH-UA-Mobile"]).and_then(|value| match value.as_str() {
+                "?1" => Some(true),
+                "?0" => Some(false),
+                _ => None,
+            }),
+            platform: header(&["Sec-CH-UA-Platform"]).map(|value| unquote(&value)),
+            platform_version: header(&["Sec-CH-UA-Platform-Version"]).map(|value| unquote(&value)),
+            model: header(&["Sec-CH-UA-Model"])
+                .map(|value| unquote(&value))
+                .filter(|model| !model.is_empty()),
+            device_memory: number(&["Sec-CH-Device-Memory", "Device-Memory"]),
+            dpr: number(&["Sec-CH-DPR", "DPR"]),
+            viewport_width: header(&["Sec-CH-Viewport-Width", "Viewport-Width"])
+                .and_then(|value| value.parse::<u32>().ok()),
+            prefers_color_scheme: header(&["Sec-CH-Prefers-Color-Scheme"])
+                .map(|value| unquote(&value)),
+            save_data: header(&["Save-Data"])
+                .map(|value| value.eq_ignore_ascii_case("on"))
+                .unwrap_or(false),
+            ect: header(&["ECT"]),
+            rtt: header(&["RTT"]).and_then(|value| value.parse::<u32>().ok()),
+            downlink: number(&["Downlink"]),
+        };
+    }
+}
+
+/// A summary of the client of a request, see `Context::client`.
+///
+/// # Fields
+///
+/// - `user_agent` - The raw `User-Agent` header, if the request has one.
+/// - `browser` - The `BrowserFamily` of the client.
+/// - `browser_version` - The version of the browser, the major version if it comes from client
+///   hints.
+/// - `platform` - The operating system, like `Windows`, `macOS`, `Linux`, `Android`, `iOS` or
+///   `Chrome OS`.
+/// - `mobile` - Whether the client is a mobile device.
+/// - `bot` - Whether the client looks like a crawler, script or other automated client, including
+///   requests without a `User-Agent` header.
+/// - `hints` - The `ClientHints` of the request.
+///
+/// # Examples
+///
+/// ```rust
+/// use browzer_web::{client::{BrowserFamily, ClientInfo}, request::Request};
+///
+/// let mut request = Request::default();
+/// request.headers.insert(
+///     "User-Agent".to_string(),
+///     "Mozilla/5.0 (X11; Linux x86_64; rv:125.0) Gecko/20100101 Firefox/125.0".to_string(),
+/// );
+/// let client = ClientInfo::from_request(&request);
+///
+/// assert_eq!(client.browser, BrowserFamily::Firefox);
+/// assert_eq!(client.browser_version.as_deref(), Some("125.0"));
+/// assert_eq!(client.platform.as_deref(), Some("Linux"));
+/// assert!(!client.mobile);
+/// assert!(!client.bot);
+/// ```
+// ----- ClientInfo struct
+#[derive(Debug, Clone, PartialEq)]
+pub struct ClientInfo {
+    pub user_agent: Option<String>,
+    pub browser: BrowserFamily,
+    pub browser_version: Option<String>,
+    pub platform: Option<String>,
+    pub mobile: bool,
+    pub bot: bool,
+    pub hints: ClientHints,
+}
+
+impl ClientInfo {
+    /// Summarizes the client of a request from it's `User-Agent` and client hint headers.
+    pub fn from_request(request: &request::Request) -> ClientInfo {
+        let user_agent = request
+            .header("User-Agent")
+            .map(|user_agent| user_agent.trim().to_string())
+            .filter(|user_agent| !user_agent.is_empty());
+        let hints = ClientHints::from_request(request);
+        let agent = user_agent.as_deref().unwrap_or("");
+
+        let (browser, browser_version) = match browser_from_brands(&hints.brands) {
+            Some((browser, version)) => (browser, Some(version)),
+            None => browser_from_user_agent(agent),
+        };
+        let platform = match hints.platform {
+            Some(ref platform) if !platform.is_empty() && platform != "Unknown" => {
+                Some(platform.to_string())
+            }
+            _ => platform_from_user_agent(agent).map(|platform| platform.to_string()),
+        };
+        let mobile = match hints.mobile {
+            Some(mobile) => mobile,
+            None => agent.contains("Mobi") || agent.contains("iPhone") || agent.contains("iPod"),
+        };
+        let lowercase = agent.to_ascii_lowercase();
+        let bot = agent.is_empty() || BOT_MARKERS.iter().any(|marker| lowercase.contains(marker));
+
+        return ClientInfo {
+            user_agent,
+            browser,
+            browser_version,
+            platform,
+            mobile,
+            bot,
+            hints,
+        };
+    }
+}
+
+// picks the browser from the `Sec-CH-UA` brands, skipping the made up(GREASE) brands browsers add
+// so that servers don't rely on the order or the exact set of brands
+fn browser_from_brands(brands: &[(String, String)]) -> Option<(BrowserFamily, String)> {
+    let mut chromium = None;
+    for (brand, version) in brands {
+        let browser = match brand.as_str() {
+            "Google Chrome" => BrowserFamily::Chrome,
+            "Microsoft Edge" => BrowserFamily::Edge,
+            "Opera" | "Opera GX" => BrowserFamily::Opera,
+            "Samsung Internet" => BrowserFamily::SamsungInternet,
+            "Chromium" => {
+                chromium = Some((BrowserFamily::Chromium, version.to_string()));
+                continue;
+            }
+            _ => continue,
+        };
+        return Some((browser, version.to_string()));
+    }
+    return chromium;
+}
+
+// picks the browser from the `User-Agent` header, most browsers claim to be several others as
+// well, so the more specific markers are checked first
+fn browser_from_user_agent(agent: &str) -> (BrowserFamily, Option<String>) {
+    const MARKERS: &[(&str, BrowserFamily)] = &[
+        ("Edg/", BrowserFamily::Edge),
+        ("EdgA/", BrowserFamily::Edge),
+        ("EdgiOS/", BrowserFamily::Edge),
+        ("Edge/", BrowserFamily::Edge),
+        ("OPR/", BrowserFamily::Opera),
+        ("Opera/", BrowserFamily::Opera),
+        ("SamsungBrowser/", BrowserFamily::SamsungInternet),
+        ("Firefox/", BrowserFamily::Firefox),
+        ("FxiOS/", BrowserFamily::Firefox),
+        ("CriOS/", BrowserFamily::Chrome),
+        ("Chromium/", BrowserFamily::Chromium),
+        ("Chrome/", BrowserFamily::Chrome),
+    ];
+    for (marker, browser) in MARKERS {
+        if let Some(version) = version_after(agent, marker) {
+            return (*browser, Some(version));
+        }
+    }
+    // Internet Explorer 11 dropped the `MSIE` token, it only has a `Trident/` token and reports
+    // it's version in the `rv:` token
+    if agent.contains("MSIE ") {
+        return (
+            BrowserFamily::InternetExplorer,
+            version_after(agent, "MSIE "),
+        );
+    }
+    if agent.contains("Trident/") {
+        return (BrowserFamily::InternetExplorer, version_after(agent, "rv:"));
+    }
+    // Safari reports it's own version in a `Version/` token, next to a WebKit build number in
+    // it's `Safari/` token
+    if agent.contains("Safari/") && !agent.contains("Android") {
+        return (BrowserFamily::Safari, version_after(agent, "Version/"));
+    }
+    return (BrowserFamily::Other, None);
+}
+
+// picks the operating system from the `User-Agent` header
+fn platform_from_user_agent(agent: &str) -> Option<&'static str> {
+    if agent.contains("Windows") {
+        return Some("Windows");
+    }
+    // Android and iOS devices mention Linux and Mac OS X as well
+    if agent.contains("Android") {
+        return Some("Android");
+    }
+    if agent.contains("iPhone") || agent.contains("iPad") || agent.contains("iPod") {
+        return Some("iOS");
+    }
+    if agent.contains("Mac OS X") || agent.contains("Macintosh") {
+        return Some("macOS");
+    }
+    if agent.contains("CrOS") {
+        return Some("Chrome OS");
+    }
+    if agent.contains("Linux") {
+        return Some("Linux");
+    }
+    return None;
+}
+
+// returns the version number(digits and dots) following a marker, if the marker is present and
+// followed by one
+fn version_after(agent: &str, marker: &str) -> Option<String> {
+    let start = agent.find(marker)? + marker.len();
+    let version: String = agent[start..]
+        .chars()
+        .take_while(|c| c.is_ascii_digit() || *c == '.')
+        .collect();
+    let version = version.trim_end_matches('.');
+    match version.is_empty() {
+        true => return None,
+        false => return Some(version.to_string()),
+    }
+}
+
+// parses the brand list of a `Sec-CH-UA` header, like `"Chromium";v="124", "Not-A.Brand";v="99"`
+fn parse_brands(value: &str) -> Vec<(String, String)> {
+    let mut brands = vec![];
+    for entry in split_unquoted(value, ',') {
+        let mut parts = split_unquoted(entry, ';').into_iter();
+        let brand = unquote(parts.next().unwrap_or(""));
+        if brand.is_empty() {
+            continue;
+        }
+        let version = parts
+            .filter_map(|parameter| parameter.split_once('='))
+            .find(|(name, _)| name.trim() == "v")
+            .map(|(_, version)| unquote(version))
+            .unwrap_or_default();
+        brands.push((brand, version));
+    }
+    return brands;
+}
+
+// splits a structured header value at a separator, ignoring separators inside quoted strings
+fn split_unquoted(value: &str, separator: char) -> Vec<&str> {
+    let mut parts = vec![];
+    let mut quoted = false;
+    let mut escaped = false;
+    let mut start = 0;
+    for (i, c) in value.char_indices() {
+        match c {
+            _ if escaped => escaped = false,
+            '\\' if quoted => escaped = true,
+            '"' => quoted = !quoted,
+            c if c == separator && !quoted => {
+                parts.push(value[start..i].trim());
+                start = i + c.len_utf8();
+            }
+            _ => {}
+        }
+    }
+    parts.push(value[start..].trim());
+    return parts;
+}
+
+// removes the quotes around a structured header string, unescaping it's content
+fn unquote(value: &str) -> String {
+    let value = value.trim();
+    let inner = match value.len() >= 2 && value.starts_with('"') && value.ends_with('"') {
+        true => &value[1..value.len() - 1],
+        false => return value.to_string(),
+    };
+    let mut unquoted = String::with_capacity(inner.len());
+    let mut escaped = false;
+    for c in inner.chars() {
+        match c {
+            '\\' if !escaped => escaped = true,
+            c => {
+                unquoted.push(c);
+                escaped = false;
+            }
+        }
+    }
+    return unquoted;
+}
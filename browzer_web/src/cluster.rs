@@ -0,0 +1,634 @@
+//! This module lets multiple instances of a server share their rate limit state and session
+//! invalidations, so the built-in rate limiter and session stores keep working when a site is
+//! scaled out to a few instances, without running a database or a cache for them.
+//!
+//! Every instance runs a `Cluster`, which listens for messages of the others on a TCP port of it's
+//! own. An instance only needs the address of one other instance(a seed) to join, the instances
+//! gossip the addresses of the peers they know about, so they all end up knowing each other.
+//! Peers which can't be reached for a while are forgotten, seeds are retried forever.
+//!
+//! - `Cluster::rate_limit_store` returns a `RateLimitStore` deciding about requests locally and
+//!   telling the peers about the requests it allowed, which take them from their buckets too.
+//! - `Cluster::session_store` wraps a `SessionStore`, sessions removed from it(like when a user
+//!   logs out) are removed from the stores of the peers too.
+//!
+//! The state is shared on a best-effort basis: changes reach the peers with a delay of about the
+//! gossip interval, and are lost for peers which are unreachable at the time. A client spreading
+//! a burst over all instances can thus exceed it's quota by up to what the instances allow it
+//! during one interval.
+//!
+//! Messages are a single line of JSON each, sent on a new connection and authenticated with an
+//! HMAC-SHA256 signature using the secret shared by all instances:
+//!
+//! ```text
+//! v1,<base64 signature> {"from":"10.0.0.1:7946","sent_at":1714564800,"nonce":"..",..}
+//! ```
+//!
+//! Messages older than 30 seconds are rejected, and so are messages whose nonce was already seen
+//! within that time, so a captured message can't be replayed. Every connection of a peer is read
+//! on a thread of it's own and has to deliver it's message within a couple of seconds, a peer
+//! which is slow or stalls doesn't hold up the others.
+//!
+//! Messages aren't encrypted, so keep the cluster port on a private network.
+
+// external crate imports
+use serde::{Deserialize, Serialize};
+
+// internal crate imports
+use crate::{
+    digest,
+    rate_limit::{MemoryRateLimitStore, Quota, RateLimitDecision, RateLimitStore},
+    sessions::{SessionData, SessionStore},
+    utils,
+};
+
+// standard library imports
+use std::{
+    collections::HashMap,
+    fmt,
+    io::{self, BufRead, BufReader, Read, Write},
+    net::{SocketAddr, TcpListener, TcpStream, ToSocketAddrs},
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, Mutex, MutexGuard, Weak,
+    },
+    thread,
+    time::{Duration, Instant},
+};
+
+/// How often changes are sent to the peers by default.
+pub const DEFAULT_GOSSIP_INTERVAL: Duration = Duration::from_millis(200);
+
+// how often a peer is sent a message when there are no changes, so it knows about this instance
+const HEARTBEAT_INTERVAL: Duration = Duration::from_secs(1);
+
+// the number of failed attempts in a row after which a peer which isn't a seed is forgotten
+const MAX_FAILURES: u32 = 5;
+
+// how long connecting to and writing to a peer may take, and reading the whole message of one
+const PEER_TIMEOUT: Duration = Duration::from_secs(2);
+
+// the largest message accepted, in bytes
+const MAX_MESSAGE_LENGTH: u64 = 1024 * 1024;
+
+// how far the clock of a peer may be off, messages sent longer ago are rejected as replays
+const MAX_MESSAGE_AGE: i64 = 30;
+
+// the most connections of peers read at once, further ones are closed right away
+const MAX_CONCURRENT_RECEIVES: usize = 32;
+
+// how often ignored messages are reported at most
+const INVALID_MESSAGE_REPORT_INTERVAL: Duration = Duration::from_secs(10);
+
+/// A boxed hook which is called with the id of every session removed on a peer.
+pub type InvalidationHook = Box<dyn Fn(&str) + 'static + Send + Sync>;
+
+// the requests allowed for a key since the last message, by the quota they were allowed under
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+struct HitKey {
+    key: String,
+    requests: u32,
+    window_ms: u64,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct Hit {
+    key: String,
+    requests: u32,
+    window_ms: u64,
+    count: u32,
+}
+
+// a message sent to a peer
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct Message {
+    from: String,
+    sent_at: i64,
+    #[serde(default)]
+    nonce: String,
+    #[serde(default)]
+    peers: Vec<String>,
+    #[serde(default)]
+    hits: Vec<Hit>,
+    #[serde(default)]
+    removed_sessions: Vec<String>,
+}
+
+// what an instance knows about a peer
+#[derive(Debug, Clone, Copy)]
+struct Peer {
+    seed: bool,
+    failures: u32,
+    last_sent: Option<Instant>,
+}
+
+// the changes which weren't sent to the peers yet
+#[derive(Debug, Default)]
+struct Outbox {
+    hits: HashMap<HitKey, u32>,
+    removed_sessions: Vec<String>,
+}
+
+// the messages ignored since they were last reported
+#[derive(Debug, Default)]
+struct InvalidMessages {
+    count: u64,
+    reported_at: Option<Instant>,
+}
+
+// the state shared by all clones of a cluster and it's threads
+struct Inner {
+    secret: Vec<u8>,
+    local_addr: SocketAddr,
+    advertised: Mutex<String>,
+    gossip_interval: Mutex<Duration>,
+    peers: Mutex<HashMap<String, Peer>>,
+    outbox: Mutex<Outbox>,
+    buckets: MemoryRateLimitStore,
+    invalidation_hooks: Mutex<Vec<InvalidationHook>>,
+    // the nonces of the messages received within the maximum age, with the time they were sent
+    seen_nonces: Mutex<HashMap<String, i64>>,
+    receiving: AtomicUsize,
+    invalid_messages: Mutex<InvalidMessages>,
+}
+
+/// A member of a cluster of server instances, see the module documentation.
+///
+/// A `Cluster` is cheap to clone, all clones share the same peers and state. It runs until the
+/// last clone of it is dropped.
+///
+/// # Examples
+///
+/// ```rust,no_run
+/// use browzer_web::{
+///     cluster::Cluster, rate_limit::RateLimit, sessions::{MemorySessionStore, Sessions},
+///     WebServer,
+/// };
+/// use std::time::Duration;
+///
+/// let cluster = Cluster::bind("0.0.0.0:7946", b"shared by all instances").unwrap();
+/// cluster.advertise("10.0.0.1:7946");
+/// cluster.join("10.0.0.2:7946");
+///
+/// let mut server = WebServer::new("0.0.0.0:8080".to_string(), 4);
+/// server.rate_limit(
+///     RateLimit::new(100, Duration::from_secs(60)).store(cluster.rate_limit_store()),
+/// );
+/// server.sessions(Sessions::new(
+///     cluster.session_store(MemorySessionStore::new()),
+///     Duration::from_secs(3600),
+/// ));
+/// server.listen();
+/// ```
+// ----- Cluster struct
+#[derive(Clone)]
+pub struct Cluster {
+    inner: Arc<Inner>,
+}
+
+impl fmt::Debug for Cluster {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Cluster")
+            .field("local_addr", &self.inner.local_addr)
+            .field("advertised", &*lock(&self.inner.advertised))
+            .field("gossip_interval", &*lock(&self.inner.gossip_interval))
+            .field("peers", &self.peers())
+            .finish()
+    }
+}
+
+impl Cluster {
+    /// Starts a cluster member listening for the messages of it's peers on the given address.
+    ///
+    /// The member advertises the address it is bound to, call `Cluster::advertise` when binding
+    /// to an unspecified address like `0.0.0.0`.
+    ///
+    /// # Arguments
+    ///
+    /// - `address` - The address to listen on, like `0.0.0.0:7946`.
+    /// - `secret` - The secret shared by all members, messages signed with another one are
+    ///   ignored.
+    ///
+    /// # Errors
+    ///
+    /// Returns an I/O error if the address couldn't be bound.
+    pub fn bind<A: ToSocketAddrs>(address: A, secret: &[u8]) -> io::Result<Cluster> {
+        let listener = TcpListener::bind(address)?;
+        let local_addr = listener.local_addr()?;
+        let inner = Arc::new(Inner {
+            secret: secret.to_vec(),
+            local_addr,
+            advertised: Mutex::new(local_addr.to_string()),
+            gossip_interval: Mutex::new(DEFAULT_GOSSIP_INTERVAL),
+            peers: Mutex::new(HashMap::new()),
+            outbox: Mutex::new(Outbox::default()),
+            buckets: MemoryRateLimitStore::new(),
+            invalidation_hooks: Mutex::new(vec![]),
+            seen_nonces: Mutex::new(HashMap::new()),
+            receiving: AtomicUsize::new(0),
+            invalid_messages: Mutex::new(InvalidMessages::default()),
+        });
+
+        let receiver = Arc::downgrade(&inner);
+        thread::spawn(move || run_receiver(listener, receiver));
+        let sender = Arc::downgrade(&inner);
+        thread::spawn(move || run_sender(sender));
+        return Ok(Cluster { inner });
+    }
+
+    /// Returns the address the member listens on.
+    pub fn local_addr(&self) -> SocketAddr {
+        return self.inner.local_addr;
+    }
+
+    /// Sets the address the peers reach this member at, like the private IP address of the host.
+    pub fn advertise(&self, address: &str) {
+        *lock(&self.inner.advertised) = address.to_string();
+    }
+
+    /// Sets how often changes are sent to the peers, `DEFAULT_GOSSIP_INTERVAL` by default.
+    pub fn set_gossip_interval(&self, interval: Duration) {
+        *lock(&self.inner.gossip_interval) = interval;
+    }
+
+    /// Adds a seed, a peer which is never forgotten even while it can't be reached. The other
+    /// members are learned from the seeds.
+    pub fn join(&self, address: &str) {
+        if address == *lock(&self.inner.advertised) {
+            return;
+        }
+        lock(&self.inner.peers)
+            .entry(address.to_string())
+            .or_insert(Peer {
+                seed: true,
+                failures: 0,
+                last_sent: None,
+            })
+            .seed = true;
+    }
+
+    /// Returns the addresses of the peers currently known, sorted.
+    pub fn peers(&self) -> Vec<String> {
+        let mut peers: Vec<String> = lock(&self.inner.peers).keys().cloned().collect();
+        peers.sort();
+        return peers;
+    }
+
+    /// Returns a `RateLimitStore` whose buckets are shared with the peers, see the module
+    /// documentation.
+    ///
+    /// All stores returned by a member share the same buckets, like the ones of the rate limits
+    /// of different servers in the same process.
+    pub fn rate_limit_store(&self) -> ClusterRateLimitStore {
+        return ClusterRateLimitStore {
+            cluster: self.clone(),
+        };
+    }
+
+    /// Wraps a `SessionStore`, so that sessions removed from it are removed from the stores of
+    /// the peers too.
+    pub fn session_store<S>(&self, store: S) -> ClusterSessionStore<S>
+    where
+        S: SessionStore + 'static,
+    {
+        let store = Arc::new(store);
+        let invalidated = Arc::clone(&store);
+        self.on_session_removed(move |id| invalidated.remove(id));
+        return ClusterSessionStore {
+            cluster: self.clone(),
+            store,
+        };
+    }
+
+    /// Calls a hook with the id of every session removed on a peer, like to remove it from a
+    /// cache of sessions.
+    pub fn on_session_removed<F>(&self, hook: F)
+    where
+        F: Fn(&str) + 'static + Send + Sync,
+    {
+        lock(&self.inner.invalidation_hooks).push(Box::new(hook));
+    }
+
+    /// Tells the peers that a session was removed.
+    pub fn remove_session(&self, id: &str) {
+        lock(&self.inner.outbox)
+            .removed_sessions
+            .push(id.to_string());
+    }
+}
+
+/// A `RateLimitStore` shared by the members of a cluster, see `Cluster::rate_limit_store`.
+// ----- ClusterRateLimitStore struct
+#[derive(Debug, Clone)]
+pub struct ClusterRateLimitStore {
+    cluster: Cluster,
+}
+
+impl RateLimitStore for ClusterRateLimitStore {
+    fn acquire(&self, key: &str, quota: &Quota) -> RateLimitDecision {
+        let decision = self.cluster.inner.buckets.acquire(key, quota);
+        if let RateLimitDecision::Allowed { .. } = decision {
+            let hit = HitKey {
+                key: key.to_string(),
+                requests: quota.requests,
+                window_ms: quota.window.as_millis() as u64,
+            };
+            *lock(&self.cluster.inner.outbox)
+                .hits
+                .entry(hit)
+                .or_insert(0) += 1;
+        }
+        return decision;
+    }
+}
+
+/// A `SessionStore` telling the members of a cluster about removed sessions, see
+/// `Cluster::session_store`.
+// ----- ClusterSessionStore struct
+pub struct ClusterSessionStore<S: SessionStore> {
+    cluster: Cluster,
+    store: Arc<S>,
+}
+
+impl<S: SessionStore> fmt::Debug for ClusterSessionStore<S> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ClusterSessionStore")
+            .field("cluster", &self.cluster)
+            .field("store", &"Arc<dyn SessionStore>")
+            .finish()
+    }
+}
+
+impl<S: SessionStore> ClusterSessionStore<S> {
+    /// Returns the wrapped store.
+    pub fn inner(&self) -> &S {
+        return &self.store;
+    }
+}
+
+impl<S: SessionStore> SessionStore for ClusterSessionStore<S> {
+    fn load(&self, id: &str) -> Option<SessionData> {
+        return self.store.load(id);
+    }
+
+    fn save(&self, id: &str, data: SessionData, ttl: Duration) {
+        self.store.save(id, data, ttl);
+    }
+
+    fn remove(&self, id: &str) {
+        self.store.remove(id);
+        self.cluster.remove_session(id);
+    }
+}
+
+impl Inner {
+    // applies a message of a peer, after learning about the peers it knows
+    fn apply(&self, message: Message) {
+        let advertised = lock(&self.advertised).clone();
+        let mut peers = lock(&self.peers);
+        for address in std::iter::once(&message.from).chain(message.peers.iter()) {
+            if *address == advertised {
+                continue;
+            }
+            peers.entry(address.clone()).or_insert(Peer {
+                seed: false,
+                failures: 0,
+                last_sent: None,
+            });
+        }
+        // the sender can be reached after all
+        if let Some(peer) = peers.get_mut(&message.from) {
+            peer.failures = 0;
+        }
+        drop(peers);
+
+        for hit in message.hits {
+            let quota = Quota {
+                requests: hit.requests,
+                window: Duration::from_millis(hit.window_ms),
+            };
+            self.buckets.take(&hit.key, &quota, hit.count);
+        }
+        if !message.removed_sessions.is_empty() {
+            let hooks = lock(&self.invalidation_hooks);
+            for id in &message.removed_sessions {
+                for hook in hooks.iter() {
+                    (hook)(id);
+                }
+            }
+        }
+    }
+
+    // sends the changes since the last round to every peer, and a heartbeat to the peers which
+    // weren't sent anything for a while
+    fn gossip(&self) {
+        let outbox = std::mem::take(&mut *lock(&self.outbox));
+        let changed = !outbox.hits.is_empty() || !outbox.removed_sessions.is_empty();
+        let peers: Vec<(String, Peer)> = lock(&self.peers)
+            .iter()
+            .map(|(address, peer)| (address.clone(), *peer))
+            .collect();
+        let now = Instant::now();
+        let message = Message {
+            from: lock(&self.advertised).clone(),
+            sent_at: chrono::Utc::now().timestamp(),
+            nonce: uuid::Uuid::new_v4().simple().to_string(),
+            peers: peers.iter().map(|(address, _)| address.clone()).collect(),
+            hits: outbox
+                .hits
+                .into_iter()
+                .map(|(hit, count)| Hit {
+                    key: hit.key,
+                    requests: hit.requests,
+                    window_ms: hit.window_ms,
+                    count,
+                })
+                .collect(),
+            removed_sessions: outbox.removed_sessions,
+        };
+        let line = self.encode(&message);
+
+        for (address, peer) in peers {
+            let due = match peer.last_sent {
+                Some(last_sent) => now.saturating_duration_since(last_sent) >= HEARTBEAT_INTERVAL,
+                None => true,
+            };
+            if !changed && !due {
+                continue;
+            }
+            let sent = send(&address, &line);
+            let mut peers = lock(&self.peers);
+            let forget = match peers.get_mut(&address) {
+                Some(peer) => match sent {
+                    Ok(_) => {
+                        peer.failures = 0;
+                        peer.last_sent = Some(now);
+                        false
+                    }
+                    Err(_) => {
+                        peer.failures += 1;
+                        peer.last_sent = Some(now);
+                        !peer.seed && peer.failures >= MAX_FAILURES
+                    }
+                },
+                None => false,
+            };
+            if forget {
+                peers.remove(&address);
+            }
+        }
+    }
+
+    // signs a message, returning the line sent to the peers
+    fn encode(&self, message: &Message) -> String {
+        let body = serde_json::to_string(message).unwrap_or_default();
+        let mac = digest::hmac_sha256(&self.secret, body.as_bytes());
+        return format!("v1,{} {}\n", utils::base64_encode(&mac), body);
+    }
+
+    // verifies the signature, age and nonce of a line received from a peer
+    fn decode(&self, line: &str) -> Option<Message> {
+        let (signature, body) = line.trim_end().split_once(' ')?;
+        let signature = utils::base64_decode(signature.strip_prefix("v1,")?)?;
+        let mac = digest::hmac_sha256(&self.secret, body.as_bytes());
+        if !utils::constant_time_eq(&signature, &mac) {
+            return None;
+        }
+        let message: Message = serde_json::from_str(body).ok()?;
+        let now = chrono::Utc::now().timestamp();
+        if (now - message.sent_at).abs() > MAX_MESSAGE_AGE || message.nonce.is_empty() {
+            return None;
+        }
+        // nonces only need to be remembered for as long as their messages are accepted
+        let mut seen_nonces = lock(&self.seen_nonces);
+        seen_nonces.retain(|_, sent_at| (now - *sent_at).abs() <= MAX_MESSAGE_AGE);
+        if seen_nonces
+            .insert(message.nonce.clone(), message.sent_at)
+            .is_some()
+        {
+            return None;
+        }
+        return Some(message);
+    }
+
+    // reads and applies the message sent on a connection of a peer
+    fn receive(&self, stream: TcpStream) {
+        let reader = DeadlineReader {
+            stream,
+            deadline: Instant::now() + PEER_TIMEOUT,
+        };
+        let mut line = String::new();
+        if BufReader::new(reader.take(MAX_MESSAGE_LENGTH))
+            .read_line(&mut line)
+            .is_err()
+        {
+            return;
+        }
+        match self.decode(&line) {
+            Some(message) => self.apply(message),
+            None => self.report_invalid_message(),
+        }
+    }
+
+    // counts an ignored message, reporting them at most once per interval so that a flood of
+    // invalid messages doesn't flood the logs too
+    fn report_invalid_message(&self) {
+        let mut invalid = lock(&self.invalid_messages);
+        invalid.count += 1;
+        if invalid
+            .reported_at
+            .is_none_or(|reported_at| reported_at.elapsed() >= INVALID_MESSAGE_REPORT_INTERVAL)
+        {
+            eprintln!("Ignored {} invalid cluster message(s)", invalid.count);
+            invalid.count = 0;
+            invalid.reported_at = Some(Instant::now());
+        }
+    }
+}
+
+// a connection of a peer which fails reads once the deadline of it's message passed, rather than
+// only when a single read takes too long
+struct DeadlineReader {
+    stream: TcpStream,
+    deadline: Instant,
+}
+
+impl Read for DeadlineReader {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let remaining = self.deadline.saturating_duration_since(Instant::now());
+        if remaining.is_zero() {
+            return Err(io::Error::new(
+                io::ErrorKind::TimedOut,
+                "the message took too long",
+            ));
+        }
+        self.stream.set_read_timeout(Some(remaining))?;
+        return self.stream.read(buf);
+    }
+}
+
+// releases a slot of the concurrent receives when dropped, even if applying the message panicked
+struct ReceiveSlot(Arc<Inner>);
+
+impl Drop for ReceiveSlot {
+    fn drop(&mut self) {
+        self.0.receiving.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+// sends a line to a peer on a new connection
+fn send(address: &str, line: &str) -> io::Result<()> {
+    let address = address
+        .to_socket_addrs()?
+        .next()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "invalid peer address"))?;
+    let mut stream = TcpStream::connect_timeout(&address, PEER_TIMEOUT)?;
+    stream.set_write_timeout(Some(PEER_TIMEOUT))?;
+    stream.write_all(line.as_bytes())?;
+    return stream.flush();
+}
+
+// accepts the connections of the peers until the cluster was dropped, which is noticed with the
+// next connection, reading each of them on a thread of it's own
+fn run_receiver(listener: TcpListener, inner: Weak<Inner>) {
+    for stream in listener.incoming() {
+        let inner = match inner.upgrade() {
+            Some(inner) => inner,
+            None => return,
+        };
+        let stream = match stream {
+            Ok(stream) => stream,
+            Err(_) => continue,
+        };
+        // dropping the connection when too many are read already, the peer retries next round
+        if inner.receiving.fetch_add(1, Ordering::SeqCst) >= MAX_CONCURRENT_RECEIVES {
+            inner.receiving.fetch_sub(1, Ordering::SeqCst);
+            continue;
+        }
+        let slot = ReceiveSlot(inner);
+        thread::spawn(move || slot.0.receive(stream));
+    }
+}
+
+// gossips with the peers every interval until the cluster was dropped
+fn run_sender(inner: Weak<Inner>) {
+    loop {
+        let interval = match inner.upgrade() {
+            Some(inner) => *lock(&inner.gossip_interval),
+            None => return,
+        };
+        thread::sleep(interval);
+        match inner.upgrade() {
+            Some(inner) => inner.gossip(),
+            None => return,
+        }
+    }
+}
+
+// locks a mutex, recovering it if a thread panicked while holding it
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    match mutex.lock() {
+        Ok(guard) => return guard,
+        Err(poisoned) => return poisoned.into_inner(),
+    }
+}
@@ -0,0 +1,326 @@
+//! This module provides response compression, and the configuration deciding which responses are
+//! eligible for it.
+//!
+//! Compressing every response hurts more than it helps: tiny bodies grow because of the encoding
+//! overhead, already compressed formats(images, archives) don't shrink, and streams like server-sent
+//! events get held back by the encoder's buffering. `CompressionConfig` captures these rules, and
+//! routes can opt out entirely using `RouteBuilder::no_compress`.
+//!
+//! The encoders themselves require the `compression` feature(gzip and deflate) or the `brotli`
+//! feature(brotli as well), see `WebServer::compression`.
+
+// internal crate imports
+#[cfg(feature = "compression")]
+use crate::stream;
+use crate::{response, router};
+
+// external crate imports
+#[cfg(feature = "compression")]
+use flate2::{
+    write::{GzEncoder, ZlibEncoder},
+    Compression,
+};
+
+// standard library imports
+#[cfg(feature = "compression")]
+use std::io::{self, Write};
+
+/// Configuration of the response compression.
+///
+/// # Fields
+///
+/// - `min_size` - The minimum body size, in bytes, for a response to be compressed(defaults to
+///   1024 bytes).
+/// - `content_types` - An allowlist of compressible content types, entries ending with `/*` match a
+///   whole media type family like `text/*`.
+///
+/// # Examples
+///
+/// ```rust
+/// use browzer_web::{compression::CompressionConfig, response::Response, router::RouteOptions, utils::HttpStatusCode};
+///
+/// let config = CompressionConfig::default().min_size(16);
+///
+/// let mut response = Response::new(HttpStatusCode::OK, "{\"message\": \"Hello, World!\"}".to_string());
+/// response.headers.insert("Content-Type".to_string(), "application/json".to_string());
+/// assert!(config.should_compress(&RouteOptions::default(), &response));
+///
+/// response.headers.insert("Content-Type".to_string(), "image/png".to_string());
+/// assert!(!config.should_compress(&RouteOptions::default(), &response));
+/// ```
+// ----- CompressionConfig struct
+#[derive(Debug, Clone)]
+pub struct CompressionConfig {
+    pub min_size: usize,
+    pub content_types: Vec<String>,
+}
+
+// default implementation for CompressionConfig struct
+impl Default for CompressionConfig {
+    fn default() -> Self {
+        return CompressionConfig {
+            min_size: 1024,
+            content_types: vec![
+                "text/*".to_string(),
+                "application/json".to_string(),
+                "application/javascript".to_string(),
+                "application/xml".to_string(),
+                "application/wasm".to_string(),
+                "image/svg+xml".to_string(),
+            ],
+        };
+    }
+}
+
+impl CompressionConfig {
+    /// Sets the minimum body size, in bytes, for a response to be compressed.
+    pub fn min_size(mut self, min_size: usize) -> CompressionConfig {
+        self.min_size = min_size;
+        return self;
+    }
+
+    /// Replaces the allowlist of compressible content types.
+    pub fn content_types(mut self, content_types: &[&str]) -> CompressionConfig {
+        self.content_types = content_types
+            .iter()
+            .map(|content_type| content_type.to_string())
+            .collect();
+        return self;
+    }
+
+    /// Decides whether a response generated by a route should be compressed.
+    ///
+    /// A response is compressed only if the route didn't opt out, the response isn't already
+    /// encoded, it's body is at least `min_size` bytes long and it's `Content-Type` is in the
+    /// allowlist. Server-sent event streams(`text/event-stream`) are never compressed.
+    ///
+    /// # Arguments
+    ///
+    /// - `route_options` - The `RouteOptions` of the route which generated the response.
+    /// - `response` - The generated `Response`.
+    pub fn should_compress(
+        &self,
+        route_options: &router::RouteOptions,
+        response: &response::Response,
+    ) -> bool {
+        // streaming bodies aren't known up front, and are sent as they are
+        if !route_options.compress
+            || response.stream.is_some()
+            || response.body.len() < self.min_size
+        {
+            return false;
+        }
+        let mut content_type = None;
+        for (key, value) in &response.headers {
+            if key.eq_ignore_ascii_case("Content-Encoding") {
+                return false;
+            }
+            if key.eq_ignore_ascii_case("Content-Type") {
+                content_type = Some(value);
+            }
+        }
+        let content_type = match content_type {
+            Some(content_type) => content_type
+                .split(';')
+                .next()
+                .unwrap_or("")
+                .trim()
+                .to_ascii_lowercase(),
+            None => return false,
+        };
+        if content_type == "text/event-stream" {
+            return false;
+        }
+        return self
+            .content_types
+            .iter()
+            .any(|allowed| match allowed.strip_suffix("/*") {
+                Some(family) => content_type.starts_with(&format!("{}/", family)),
+                None => content_type == allowed.to_ascii_lowercase(),
+            });
+    }
+
+    /// Compresses a response with the best content coding the client accepts, if the response is
+    /// eligible for compression(see `CompressionConfig::should_compress`).
+    ///
+    /// The compressed body replaces the body of the response(it's `Content-Length` follows the
+    /// compressed body), and `Content-Encoding` is set. Eligible responses always get
+    /// `Accept-Encoding` added to their `Vary` header, since caches have to keep the encoded and
+    /// the plain variants apart. Only available with the `compression` feature.
+    ///
+    /// # Arguments
+    ///
+    /// - `route_options` - The `RouteOptions` of the route which generated the response.
+    /// - `accept_encoding` - The `Accept-Encoding` header of the request, if it has one.
+    /// - `response` - The generated `Response`.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use browzer_web::{compression::CompressionConfig, response::Response, router::RouteOptions, utils::HttpStatusCode};
+    ///
+    /// let config = CompressionConfig::default();
+    /// let mut response = Response::new(HttpStatusCode::OK, "Hello, World! ".repeat(100));
+    /// response.headers.insert("Content-Type".to_string(), "text/plain".to_string());
+    ///
+    /// let response = config.apply(&RouteOptions::default(), Some("gzip, deflate"), response);
+    ///
+    /// assert_eq!(response.headers.get("Content-Encoding").unwrap(), "gzip");
+    /// assert_eq!(response.headers.get("Vary").unwrap(), "Accept-Encoding");
+    /// assert!(response.stream.unwrap().length().unwrap() < 1400);
+    /// ```
+    #[cfg(feature = "compression")]
+    pub fn apply(
+        &self,
+        route_options: &router::RouteOptions,
+        accept_encoding: Option<&str>,
+        mut response: response::Response,
+    ) -> response::Response {
+        if !self.should_compress(route_options, &response) {
+            return response;
+        }
+        response.add_vary("Accept-Encoding");
+        let encoding = match accept_encoding.and_then(ContentEncoding::negotiate) {
+            Some(encoding) => encoding,
+            None => return response,
+        };
+        match encoding.encode(response.body.as_bytes()) {
+            Ok(encoded) => {
+                response.body = String::new();
+                response.stream = Some(stream::StreamBody::from_bytes(encoded));
+                response.headers.insert(
+                    "Content-Encoding".to_string(),
+                    encoding.as_str().to_string(),
+                );
+            }
+            // the response is still fine without compression
+            Err(e) => eprintln!("Failed to compress response: {}", e),
+        }
+        return response;
+    }
+}
+
+/// The content codings responses can be compressed with, only available with the `compression`
+/// feature.
+///
+/// # Variants
+///
+/// - `Brotli` - `br`, compresses best, only available with the `brotli` feature.
+/// - `Gzip` - `gzip`, supported by every client.
+/// - `Deflate` - `deflate`, the zlib format.
+#[cfg(feature = "compression")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ContentEncoding {
+    #[cfg(feature = "brotli")]
+    Brotli,
+    Gzip,
+    Deflate,
+}
+
+#[cfg(feature = "compression")]
+impl ContentEncoding {
+    // the supported codings, in the order they are preferred in if the client likes them equally
+    const PREFERENCE: &'static [ContentEncoding] = &[
+        #[cfg(feature = "brotli")]
+        ContentEncoding::Brotli,
+        ContentEncoding::Gzip,
+        ContentEncoding::Deflate,
+    ];
+
+    /// Returns the name of the content coding, as used in the `Content-Encoding` header.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            #[cfg(feature = "brotli")]
+            ContentEncoding::Brotli => return "br",
+            ContentEncoding::Gzip => return "gzip",
+            ContentEncoding::Deflate => return "deflate",
+        }
+    }
+
+    /// Picks the content coding to compress a response with, from the `Accept-Encoding` header of
+    /// the request.
+    ///
+    /// The supported coding with the highest quality value is picked, codings the client likes
+    /// equally are picked in the order brotli, gzip, deflate. A `*` entry applies to all codings
+    /// not listed otherwise, and a quality value of `0` rules a coding out.
+    ///
+    /// # Returns
+    ///
+    /// - `Option<ContentEncoding>` - The content coding, or `None` if the client accepts none of
+    ///   the supported ones.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use browzer_web::compression::ContentEncoding;
+    ///
+    /// assert_eq!(ContentEncoding::negotiate("gzip, deflate"), Some(ContentEncoding::Gzip));
+    /// assert_eq!(ContentEncoding::negotiate("gzip;q=0.5, deflate"), Some(ContentEncoding::Deflate));
+    /// assert_eq!(ContentEncoding::negotiate("*;q=0, identity"), None);
+    /// ```
+    pub fn negotiate(accept_encoding: &str) -> Option<ContentEncoding> {
+        let mut qualities: Vec<(String, f32)> = vec![];
+        for entry in accept_encoding.split(',') {
+            let mut parts = entry.split(';');
+            let coding = parts.next().unwrap_or("").trim().to_ascii_lowercase();
+            if coding.is_empty() {
+                continue;
+            }
+            let mut quality = Some(1.0);
+            for parameter in parts {
+                if let Some((name, value)) = parameter.split_once('=') {
+                    if name.trim().eq_ignore_ascii_case("q") {
+                        quality = value.trim().parse::<f32>().ok();
+                    }
+                }
+            }
+            // entries with an invalid quality value are ignored
+            if let Some(quality) = quality {
+                qualities.push((coding, quality));
+            }
+        }
+
+        let quality_of = |coding: &str| -> Option<f32> {
+            return qualities
+                .iter()
+                .find(|(name, _)| name == coding || (coding == "gzip" && name == "x-gzip"))
+                .map(|(_, quality)| *quality);
+        };
+        let wildcard = quality_of("*").unwrap_or(0.0);
+        let mut best: Option<(ContentEncoding, f32)> = None;
+        for encoding in ContentEncoding::PREFERENCE {
+            let quality = quality_of(encoding.as_str()).unwrap_or(wildcard);
+            if quality > 0.0 && best.map(|(_, best)| quality > best).unwrap_or(true) {
+                best = Some((*encoding, quality));
+            }
+        }
+        return best.map(|(encoding, _)| encoding);
+    }
+
+    /// Compresses a body with the content coding.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the encoder failed.
+    pub fn encode(&self, body: &[u8]) -> io::Result<Vec<u8>> {
+        match self {
+            #[cfg(feature = "brotli")]
+            ContentEncoding::Brotli => {
+                let mut encoder = brotli::CompressorWriter::new(Vec::new(), 4096, 5, 22);
+                encoder.write_all(body)?;
+                encoder.flush()?;
+                return Ok(encoder.into_inner());
+            }
+            ContentEncoding::Gzip => {
+                let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+                encoder.write_all(body)?;
+                return encoder.finish();
+            }
+            ContentEncoding::Deflate => {
+                let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+                encoder.write_all(body)?;
+                return encoder.finish();
+            }
+        }
+    }
+}
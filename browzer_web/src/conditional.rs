@@ -0,0 +1,265 @@
+//! This module provides conditional request support (RFC 9110 preconditions) based on entity tags.
+//!
+//! Handlers declare the current `EntityTag` of the resource they are about to read or modify and
+//! the `If-Match` / `If-None-Match` request headers are evaluated against it, so concurrent editors
+//! can't silently overwrite each other's changes (optimistic concurrency). Representations which
+//! only know when they were last modified, like static files, are checked against the
+//! `If-Modified-Since` header with `not_modified_since`.
+
+// internal crate imports
+use crate::{request, utils};
+
+// standard library imports
+use std::{
+    fmt,
+    time::{Duration, SystemTime},
+};
+
+/// Represents an HTTP entity tag, as used in the `ETag`, `If-Match` and `If-None-Match` headers.
+///
+/// # Fields
+///
+/// - `tag` - The opaque tag value, without the surrounding quotes.
+/// - `weak` - Whether the tag is a weak validator(prefixed with `W/`).
+///
+/// # Examples
+///
+/// ```rust
+/// use browzer_web::conditional::EntityTag;
+///
+/// let etag = EntityTag::parse("W/\"v2\"").unwrap();
+/// assert_eq!(etag, EntityTag::weak("v2"));
+/// assert_eq!(etag.to_string(), "W/\"v2\"");
+/// assert_eq!(EntityTag::strong("v2").to_string(), "\"v2\"");
+/// ```
+// ----- EntityTag struct
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EntityTag {
+    pub tag: String,
+    pub weak: bool,
+}
+
+impl EntityTag {
+    /// Creates a new strong `EntityTag`.
+    pub fn strong(tag: &str) -> EntityTag {
+        return EntityTag {
+            tag: tag.to_string(),
+            weak: false,
+        };
+    }
+
+    /// Creates a new weak `EntityTag`.
+    pub fn weak(tag: &str) -> EntityTag {
+        return EntityTag {
+            tag: tag.to_string(),
+            weak: true,
+        };
+    }
+
+    /// Parses a single entity tag like `"abc"` or `W/"abc"`.
+    ///
+    /// # Returns
+    ///
+    /// - `Option<EntityTag>` - The parsed entity tag, or `None` if the input isn't a valid quoted
+    ///   entity tag.
+    pub fn parse(input: &str) -> Option<EntityTag> {
+        let input = input.trim();
+        let (weak, quoted) = match input.strip_prefix("W/") {
+            Some(rest) => (true, rest),
+            None => (false, input),
+        };
+        if quoted.len() < 2 || !quoted.starts_with('"') || !quoted.ends_with('"') {
+            return None;
+        }
+        let tag = &quoted[1..quoted.len() - 1];
+        if tag.contains('"') {
+            return None;
+        }
+        return Some(EntityTag {
+            tag: tag.to_string(),
+            weak,
+        });
+    }
+
+    /// Strong comparison: both tags must be strong and have the same value.
+    pub fn strong_eq(&self, other: &EntityTag) -> bool {
+        return !self.weak && !other.weak && self.tag == other.tag;
+    }
+
+    /// Weak comparison: the tag values must be the same, the weakness is ignored.
+    pub fn weak_eq(&self, other: &EntityTag) -> bool {
+        return self.tag == other.tag;
+    }
+}
+
+impl fmt::Display for EntityTag {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.weak {
+            return write!(f, "W/\"{}\"", self.tag);
+        }
+        return write!(f, "\"{}\"", self.tag);
+    }
+}
+
+/// The value of an `If-Match` or `If-None-Match` header.
+///
+/// # Variants
+///
+/// - `Any` - The header value was `*`, which matches any current representation.
+/// - `Tags` - A list of entity tags, invalid entries are skipped.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum EntityTagCondition {
+    Any,
+    Tags(Vec<EntityTag>),
+}
+
+impl EntityTagCondition {
+    /// Parses the value of an `If-Match` or `If-None-Match` header.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use browzer_web::conditional::{EntityTag, EntityTagCondition};
+    ///
+    /// assert_eq!(EntityTagCondition::parse("*"), EntityTagCondition::Any);
+    /// assert_eq!(
+    ///     EntityTagCondition::parse("\"a\", W/\"b\""),
+    ///     EntityTagCondition::Tags(vec![EntityTag::strong("a"), EntityTag::weak("b")])
+    /// );
+    /// ```
+    pub fn parse(input: &str) -> EntityTagCondition {
+        if input.trim() == "*" {
+            return EntityTagCondition::Any;
+        }
+        return EntityTagCondition::Tags(
+            input
+                .split(',')
+                .filter_map(EntityTag::parse)
+                .collect::<Vec<_>>(),
+        );
+    }
+}
+
+/// Evaluates the `If-Match` and `If-None-Match` preconditions of a request.
+///
+/// `If-Match` uses strong comparison and fails with `412 Precondition Failed` when no listed tag
+/// matches the current one(or the resource doesn't exist). `If-None-Match` uses weak comparison and,
+/// when a listed tag matches(or the resource exists for `*`), fails with `304 Not Modified` for
+/// `GET` and `HEAD` requests and `412 Precondition Failed` for every other method.
+///
+/// # Arguments
+///
+/// - `request` - The incoming `Request`.
+/// - `current` - The current `EntityTag` of the target resource, or `None` if it doesn't exist yet.
+///
+/// # Returns
+///
+/// - `Option<HttpStatusCode>` - The status code to respond with if a precondition failed, or `None`
+///   if the request may proceed.
+///
+/// # Examples
+///
+/// ```rust
+/// use browzer_web::{conditional::{evaluate_preconditions, EntityTag}, request::Request, utils::{HttpMethod, HttpStatusCode}};
+///
+/// let mut request = Request::default();
+/// request.method = HttpMethod::PATCH;
+/// request.headers.insert("If-Match".to_string(), "\"v1\"".to_string());
+///
+/// assert_eq!(evaluate_preconditions(&request, Some(&EntityTag::strong("v1"))), None);
+/// assert_eq!(
+///     evaluate_preconditions(&request, Some(&EntityTag::strong("v2"))),
+///     Some(HttpStatusCode::PreconditionFailed)
+/// );
+/// ```
+pub fn evaluate_preconditions(
+    request: &request::Request,
+    current: Option<&EntityTag>,
+) -> Option<utils::HttpStatusCode> {
+    if let Some(if_match) = request.header("If-Match") {
+        let matched = match (EntityTagCondition::parse(if_match), current) {
+            (_, None) => false,
+            (EntityTagCondition::Any, Some(_)) => true,
+            (EntityTagCondition::Tags(tags), Some(current)) => {
+                tags.iter().any(|tag| tag.strong_eq(current))
+            }
+        };
+        if !matched {
+            return Some(utils::HttpStatusCode::PreconditionFailed);
+        }
+    }
+
+    if let Some(if_none_match) = request.header("If-None-Match") {
+        let matched = match (EntityTagCondition::parse(if_none_match), current) {
+            (_, None) => false,
+            (EntityTagCondition::Any, Some(_)) => true,
+            (EntityTagCondition::Tags(tags), Some(current)) => {
+                tags.iter().any(|tag| tag.weak_eq(current))
+            }
+        };
+        if matched {
+            return match request.method {
+                utils::HttpMethod::GET | utils::HttpMethod::HEAD => {
+                    Some(utils::HttpStatusCode::NotModified)
+                }
+                _ => Some(utils::HttpStatusCode::PreconditionFailed),
+            };
+        }
+    }
+
+    return None;
+}
+
+/// Evaluates the `If-Modified-Since` precondition of a `GET` or `HEAD` request.
+///
+/// The header is ignored when the request has an `If-None-Match` header(which takes precedence),
+/// for other methods and when it isn't a valid HTTP date. Modification times are compared with a
+/// precision of seconds, the precision of HTTP dates.
+///
+/// # Arguments
+///
+/// - `request` - The incoming `Request`.
+/// - `last_modified` - The time the target resource was last modified.
+///
+/// # Returns
+///
+/// - `bool` - Whether the request should be answered with `304 Not Modified`.
+///
+/// # Examples
+///
+/// ```rust
+/// use browzer_web::{conditional::not_modified_since, request::Request, utils::parse_http_date};
+///
+/// let mut request = Request::default();
+/// request.headers.insert(
+///     "If-Modified-Since".to_string(),
+///     "Sun, 06 Nov 1994 08:49:37 GMT".to_string(),
+/// );
+///
+/// let modified = parse_http_date("Sun, 06 Nov 1994 08:49:37 GMT").unwrap();
+/// assert!(not_modified_since(&request, modified));
+/// let modified = parse_http_date("Mon, 07 Nov 1994 08:49:37 GMT").unwrap();
+/// assert!(!not_modified_since(&request, modified));
+/// ```
+pub fn not_modified_since(request: &request::Request, last_modified: SystemTime) -> bool {
+    match request.method {
+        utils::HttpMethod::GET | utils::HttpMethod::HEAD => {}
+        _ => return false,
+    }
+    if request.header("If-None-Match").is_some() {
+        return false;
+    }
+    let since = match request
+        .header("If-Modified-Since")
+        .and_then(|value| utils::parse_http_date(value))
+    {
+        Some(since) => since,
+        None => return false,
+    };
+    // the header can't express fractions of a second, so they are dropped from the file's time
+    let last_modified = match last_modified.duration_since(SystemTime::UNIX_EPOCH) {
+        Ok(elapsed) => SystemTime::UNIX_EPOCH + Duration::from_secs(elapsed.as_secs()),
+        Err(_) => last_modified,
+    };
+    return last_modified <= since;
+}
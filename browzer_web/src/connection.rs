@@ -0,0 +1,187 @@
+//! This module provides the state of the connection a request arrived on, shared by all the
+//! requests of a persistent(keep-alive) connection.
+//!
+//! Every request carries the `Connection` it was read from, with metadata about the connection
+//! (like the negotiated TLS version and cipher suite, and how many requests it carried so far)
+//! and a storage of typed values which outlives the request, so handlers can cache what they
+//! learned about a connection(like a verified client certificate or a slow lookup) for it's next
+//! requests. See `Context::connection`.
+
+// standard library imports
+use std::{
+    any::{Any, TypeId},
+    collections::HashMap,
+    fmt,
+    net::SocketAddr,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex, MutexGuard, OnceLock,
+    },
+    time::Instant,
+};
+
+/// What was negotiated in the TLS handshake of a connection.
+///
+/// # Fields
+///
+/// - `version` - The TLS version, like `TLSv1_3`.
+/// - `cipher_suite` - The cipher suite, like `TLS13_AES_256_GCM_SHA384`.
+/// - `alpn_protocol` - The application protocol chosen using ALPN, like `http/1.1`, if the client
+///   offered one.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TlsInfo {
+    pub version: Option<String>,
+    pub cipher_suite: Option<String>,
+    pub alpn_protocol: Option<String>,
+}
+
+// the state shared by the clones of a connection, what the TLS handshake negotiated is only known
+// once the first request was read
+struct ConnectionState {
+    remote_addr: Option<SocketAddr>,
+    tls: OnceLock<TlsInfo>,
+    established_at: Instant,
+    requests: AtomicU64,
+    values: Mutex<HashMap<TypeId, Box<dyn Any + Send + Sync>>>,
+}
+
+/// The connection a request arrived on, see the module documentation.
+///
+/// Connections are cheap to clone, and all clones share the same state. A request which wasn't
+/// read from a connection(like one built by hand in a test) gets a connection of it's own.
+///
+/// # Examples
+///
+/// ```rust,no_run
+/// # use browzer_web::{utils::HttpStatusCode, WebServer};
+/// #[derive(Clone)]
+/// struct Greeted;
+///
+/// let mut server = WebServer::new("127.0.0.1:8080".to_string(), 4);
+///
+/// server.get("/", |mut c| {
+///     let connection = c.connection().clone();
+///     // greet only once per connection
+///     if connection.set(Greeted).is_some() {
+///         return c.send_string(HttpStatusCode::OK, "Hello again!");
+///     }
+///     return c.send_string(HttpStatusCode::OK, "Hello!");
+/// });
+/// ```
+// ----- Connection struct
+#[derive(Clone)]
+pub struct Connection {
+    state: Arc<ConnectionState>,
+}
+
+// default implementation for Connection struct
+impl Default for Connection {
+    fn default() -> Self {
+        return Connection::new(None);
+    }
+}
+
+impl fmt::Debug for Connection {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Connection")
+            .field("remote_addr", &self.state.remote_addr)
+            .field("tls", &self.state.tls.get())
+            .field("requests", &self.request_count())
+            .finish()
+    }
+}
+
+impl Connection {
+    // creates the state of a newly accepted connection
+    pub(crate) fn new(remote_addr: Option<SocketAddr>) -> Connection {
+        return Connection {
+            state: Arc::new(ConnectionState {
+                remote_addr,
+                tls: OnceLock::new(),
+                established_at: Instant::now(),
+                requests: AtomicU64::new(0),
+                values: Mutex::new(HashMap::new()),
+            }),
+        };
+    }
+
+    // records what the TLS handshake negotiated, once it was completed
+    pub(crate) fn set_tls(&self, tls: TlsInfo) {
+        let _ = self.state.tls.set(tls);
+    }
+
+    // counts another request read from the connection
+    pub(crate) fn count_request(&self) {
+        self.state.requests.fetch_add(1, Ordering::SeqCst);
+    }
+
+    /// Returns the address of the client, the peer address of the connection or the one from it's
+    /// PROXY protocol header.
+    pub fn remote_addr(&self) -> Option<SocketAddr> {
+        return self.state.remote_addr;
+    }
+
+    /// Returns what was negotiated in the TLS handshake, `None` for plain HTTP connections.
+    pub fn tls(&self) -> Option<&TlsInfo> {
+        return self.state.tls.get();
+    }
+
+    /// Returns the point in time the connection was accepted at.
+    pub fn established_at(&self) -> Instant {
+        return self.state.established_at;
+    }
+
+    /// Returns the number of requests read from the connection so far, the current one included.
+    pub fn request_count(&self) -> u64 {
+        return self.state.requests.load(Ordering::SeqCst);
+    }
+
+    /// Returns a clone of the value of the given type stored on the connection.
+    ///
+    /// # Returns
+    ///
+    /// - `Option<T>` - The value, `None` if no value of the type was stored.
+    pub fn get<T: Any + Send + Sync + Clone>(&self) -> Option<T> {
+        return self
+            .values()
+            .get(&TypeId::of::<T>())
+            .and_then(|value| value.downcast_ref::<T>())
+            .cloned();
+    }
+
+    /// Stores a value on the connection for the requests after this one, replacing the value of
+    /// the same type stored before.
+    ///
+    /// # Returns
+    ///
+    /// - `Option<T>` - The replaced value, `None` if no value of the type was stored.
+    pub fn set<T: Any + Send + Sync>(&self, value: T) -> Option<T> {
+        return self
+            .values()
+            .insert(TypeId::of::<T>(), Box::new(value))
+            .and_then(|value| value.downcast::<T>().ok())
+            .map(|value| *value);
+    }
+
+    /// Removes the value of the given type from the connection.
+    ///
+    /// # Returns
+    ///
+    /// - `Option<T>` - The removed value, `None` if no value of the type was stored.
+    pub fn remove<T: Any + Send + Sync>(&self) -> Option<T> {
+        return self
+            .values()
+            .remove(&TypeId::of::<T>())
+            .and_then(|value| value.downcast::<T>().ok())
+            .map(|value| *value);
+    }
+
+    // locks the stored values, recovering them if another thread panicked while holding the lock
+    fn values(&self) -> MutexGuard<'_, HashMap<TypeId, Box<dyn Any + Send + Sync>>> {
+        return self
+            .state
+            .values
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+    }
+}
@@ -1,13 +1,27 @@
 //! This module defines the `Context` struct, which represents the context of a web request.
 
 // external crate imports
+use serde::{de::DeserializeOwned, Serialize};
+use serde_json;
 use serde_urlencoded;
 
 // internal crate imports
-use crate::{request, response, utils};
+use crate::{
+    auth, cancel, client, conditional, connection, digest, error, forwarded, jobs, links, problem,
+    range, request, request_id, response, router, sessions, stream, tasks, templates, utils,
+};
 
 // standard library imports
-use std::collections::HashMap;
+use std::{
+    any::{Any, TypeId},
+    collections::HashMap,
+    fmt, fs,
+    io::{self, Read, Seek, SeekFrom},
+    net::{IpAddr, SocketAddr},
+    path::Path,
+    str::FromStr,
+    sync::{Arc, Weak},
+};
 
 /// Represents the context of a web request.
 ///
@@ -19,13 +33,19 @@ use std::collections::HashMap;
 ///
 /// - `request` - The incoming request provided via the `Request` struct.
 /// - `response` - The response to be sent back using the `Response` struct.
-/// - `params` - A `HashMap` representing parameters extracted from the request path.
-/// - `query_params` - A `HashMap` representing query parameters extracted from the request path.
+/// - `params` - A `HashMap` representing parameters extracted from the request path, percent-decoded
+///   (`/users/John%20Doe` gives `John Doe`).
+/// - `query_params` - A `HashMap` representing query parameters extracted from the request path,
+///   percent-decoded with `+` standing for a space. Only the first value of a repeated key is
+///   kept, see `Context::query_all` for all of them.
+/// - `raw_params` - The parameters extracted from the request path as they appear in it.
+/// - `raw_query_params` - The query parameters as they appear in the request path.
 ///
 /// # Examples
 ///
 /// ```rust
-/// let mut context = Context::new(Request::new());
+/// # use browzer_web::{context::Context, request::Request, utils::HttpStatusCode};
+/// let mut context = Context::new(Request::default());
 /// let response = context.send_string(HttpStatusCode::OK, "Hello, World!");
 /// ```
 // ----- Context struct
@@ -35,6 +55,18 @@ pub struct Context {
     pub response: response::Response,
     pub params: HashMap<String, String>,
     pub query_params: HashMap<String, String>,
+    pub raw_params: HashMap<String, String>,
+    pub raw_query_params: HashMap<String, String>,
+    pub(crate) session: sessions::Session,
+    pub(crate) background_pool: Arc<tasks::BackgroundPool>,
+    pub(crate) jobs: Option<Arc<jobs::Jobs>>,
+    pub(crate) trusted_proxies: Option<Arc<forwarded::TrustedProxies>>,
+    pub(crate) identity: Option<auth::Identity>,
+    pub(crate) digests: Option<Arc<digest::DigestSet>>,
+    pub(crate) templates: Option<Arc<templates::Templates>>,
+    pub(crate) extensions: HashMap<TypeId, Box<dyn Any + Send + Sync>>,
+    pub(crate) aborted: Option<response::Response>,
+    pub(crate) router: Weak<router::WebRouter>,
 }
 
 impl Context {
@@ -51,7 +83,8 @@ impl Context {
     /// # Examples
     ///
     /// ```rust
-    /// let request = Request::new();
+    /// # use browzer_web::{context::Context, request::Request, utils::HttpStatusCode};
+    /// let request = Request::default();
     /// let context = Context::new(request);
     /// ```
     pub fn new(request: request::Request) -> Context {
@@ -60,6 +93,18 @@ impl Context {
             response: response::Response::default(),
             params: HashMap::new(),
             query_params: HashMap::new(),
+            raw_params: HashMap::new(),
+            raw_query_params: HashMap::new(),
+            session: sessions::Session::default(),
+            background_pool: Arc::new(tasks::BackgroundPool::default()),
+            jobs: None,
+            trusted_proxies: None,
+            identity: None,
+            digests: None,
+            templates: None,
+            extensions: HashMap::new(),
+            aborted: None,
+            router: Weak::new(),
         };
     }
 
@@ -77,7 +122,8 @@ impl Context {
     /// # Examples
     ///
     /// ```rust
-    /// let mut context = Context::new(Request::new());
+    /// # use browzer_web::{context::Context, request::Request, utils::HttpStatusCode};
+    /// let mut context = Context::new(Request::default());
     /// let response = context.send_string(HttpStatusCode::OK, "Hello, World!");
     /// ```
     pub fn send_string(
@@ -91,6 +137,564 @@ impl Context {
         res.clone()
     }
 
+    /// Constructs the response for an error like the router does for the errors it detects
+    /// itself, with the configured error handlers, problem details or page templates(see
+    /// `WebRouter::error_response`).
+    ///
+    /// Headers and cookies already set on the response of the context are kept, unless the error
+    /// response sets them too. Outside of a running server the body is the plain-text reason
+    /// phrase of the status code.
+    ///
+    /// # Arguments
+    ///
+    /// - `status_code` - The `HttpStatusCode` of the error.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// # use browzer_web::{utils::HttpStatusCode, WebServer};
+    /// let mut server = WebServer::new("127.0.0.1:8080".to_string(), 4);
+    /// server.get("/admin", |mut c| {
+    ///     if c.request.header("X-Admin").is_none() {
+    ///         return c.error_response(HttpStatusCode::Forbidden);
+    ///     }
+    ///     return c.send_string(HttpStatusCode::OK, "Welcome");
+    /// });
+    /// ```
+    pub fn error_response(&mut self, status_code: utils::HttpStatusCode) -> response::Response {
+        let error = match self.router.upgrade() {
+            Some(router) => router.error_response(status_code, &self.request.path),
+            None => {
+                let body = status_code.code().0.to_string();
+                response::Response::new(status_code, body)
+            }
+        };
+        let mut response = std::mem::take(&mut self.response);
+        response.status_code = error.status_code;
+        response.headers.extend(error.headers);
+        response.cookies.extend(error.cookies);
+        response.body = error.body;
+        response.stream = error.stream;
+        return response;
+    }
+
+    /// Constructs a streaming response with the given status code, whose body is written by the
+    /// given function once the head of the response was sent.
+    ///
+    /// The body is sent using chunked transfer encoding, see the `stream` module for how the
+    /// `ResponseWriter` buffers it. Set the `Content-Type` header before calling this, like
+    /// `text/event-stream` for server-sent events.
+    ///
+    /// # Arguments
+    ///
+    /// - `status_code` - A `HTTPStatusCode` specifying the status code of the response.
+    /// - `func` - The function writing the body, returning an error from it aborts the response.
+    ///
+    /// # Returns
+    ///
+    /// A streaming `Response` with the specified status code.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # use browzer_web::{context::Context, request::Request, stream::FlushMode, utils::HttpStatusCode};
+    /// use std::io::Write;
+    ///
+    /// let mut context = Context::new(Request::default());
+    /// context.response.headers.insert("Content-Type".to_string(), "text/event-stream".to_string());
+    /// let response = context.send_stream(HttpStatusCode::OK, |writer| {
+    ///     writer.set_flush_mode(FlushMode::Immediate);
+    ///     writer.write_all(b"data: hello\n\n")?;
+    ///     return Ok(());
+    /// });
+    /// ```
+    pub fn send_stream<F>(
+        &mut self,
+        status_code: utils::HttpStatusCode,
+        func: F,
+    ) -> response::Response
+    where
+        F: Fn(&mut stream::ResponseWriter) -> io::Result<()> + 'static + Send + Sync,
+    {
+        let res = &mut self.response;
+        res.status_code = status_code;
+        res.body = String::new();
+        res.stream = Some(stream::StreamBody::new(func));
+        return res.clone();
+    }
+
+    /// Constructs a response streaming the contents of a file, without reading the whole file
+    /// into memory first.
+    ///
+    /// The file is sent with it's `Content-Length`, and a `Content-Type` guessed from it's
+    /// extension unless the header was set already. Binary files are sent as is.
+    ///
+    /// `GET` requests with a `Range` header get only the requested part of the file, as a
+    /// `206 Partial Content` response(see the `range` module). An `If-Range` header is compared
+    /// against the `ETag` and `Last-Modified` headers of the response, so set those before calling
+    /// this if the file has them.
+    ///
+    /// With `WebServer::content_digests` enabled the response carries the digests of the whole
+    /// file(see the `digest` module), `Content-MD5` is left out of partial responses since it
+    /// covers the body and not the whole file.
+    ///
+    /// # Arguments
+    ///
+    /// - `path` - The path of the file to send, it isn't checked against any directory so never
+    ///   pass it unchecked user input(see `utils::resolve_static_path` for that).
+    ///
+    /// # Returns
+    ///
+    /// A streaming `Response` with the status code `200 OK`(or `206 Partial Content` for a range),
+    /// or an error response with the status code `404 Not Found` if there is no such file,
+    /// `403 Forbidden` if it can't be read, `416 Range Not Satisfiable` if the requested range lies
+    /// outside of the file and `500 Internal Server Error` if opening it failed otherwise.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// # use browzer_web::WebServer;
+    /// let mut server = WebServer::new("127.0.0.1:8080".to_string(), 4);
+    ///
+    /// server.get("/download", |mut c| {
+    ///     return c.send_file("./files/archive.zip");
+    /// });
+    /// ```
+    pub fn send_file<P: AsRef<Path>>(&mut self, path: P) -> response::Response {
+        let path = path.as_ref();
+        let opened = fs::File::open(path).and_then(|file| {
+            let metadata = file.metadata()?;
+            return Ok((file, metadata));
+        });
+        let (file, metadata) = match opened {
+            Ok((file, metadata)) => match metadata.is_file() {
+                true => (file, metadata),
+                false => {
+                    return self.error_response(utils::HttpStatusCode::NotFound);
+                }
+            },
+            Err(e) => {
+                let status_code = match e.kind() {
+                    io::ErrorKind::NotFound => utils::HttpStatusCode::NotFound,
+                    io::ErrorKind::PermissionDenied => utils::HttpStatusCode::Forbidden,
+                    _ => utils::HttpStatusCode::InternalServerError,
+                };
+                return self.error_response(status_code);
+            }
+        };
+
+        let digests = self
+            .digests
+            .as_ref()
+            .map(|digests| digests.digest_file(path));
+        return self.send_seekable(file, metadata.len(), path, digests);
+    }
+
+    /// Constructs a response with binary content held in memory, like an asset compiled into the
+    /// binary with `include_bytes!`.
+    ///
+    /// The content is sent like `Context::send_file` sends a file: with a `Content-Type` guessed
+    /// from the extension of the given name unless the header was set already, answering `Range`
+    /// requests and carrying the content digests if enabled.
+    ///
+    /// # Arguments
+    ///
+    /// - `name` - The file name of the content, only used for guessing it's `Content-Type`.
+    /// - `bytes` - The content, like a `&'static [u8]` or a `Vec<u8>`.
+    ///
+    /// # Returns
+    ///
+    /// A streaming `Response` with the status code `200 OK`(or `206 Partial Content` for a range),
+    /// or `416 Range Not Satisfiable` if the requested range lies outside of the content.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// # use browzer_web::WebServer;
+    /// let mut server = WebServer::new("127.0.0.1:8080".to_string(), 4);
+    ///
+    /// server.get("/favicon.ico", |mut c| {
+    ///     return c.send_bytes("favicon.ico", &b"\x00\x00\x01\x00"[..]);
+    /// });
+    /// ```
+    pub fn send_bytes<B: AsRef<[u8]> + Send + 'static>(
+        &mut self,
+        name: &str,
+        bytes: B,
+    ) -> response::Response {
+        let length = bytes.as_ref().len() as u64;
+        let digests = self
+            .digests
+            .as_ref()
+            .map(|digests| Ok(digests.digest(bytes.as_ref())));
+        return self.send_seekable(io::Cursor::new(bytes), length, Path::new(name), digests);
+    }
+
+    // streams a body which can be read from any offset, answering `Range` requests for it
+    fn send_seekable<R: Read + Seek + Send + 'static>(
+        &mut self,
+        mut reader: R,
+        length: u64,
+        path: &Path,
+        digests: Option<io::Result<digest::Digests>>,
+    ) -> response::Response {
+        self.response
+            .headers
+            .insert("Accept-Ranges".to_string(), "bytes".to_string());
+        let range = match self.request.header("Range") {
+            Some(value) if self.request.method == utils::HttpMethod::GET => {
+                let etag = self
+                    .response_header("ETag")
+                    .and_then(|etag| conditional::EntityTag::parse(&etag));
+                let last_modified = self.response_header("Last-Modified");
+                match range::if_range_matches(
+                    &self.request,
+                    etag.as_ref(),
+                    last_modified.as_deref(),
+                ) {
+                    // a range which can't be parsed or isn't supported is ignored
+                    true => match range::ByteRange::parse(value, length) {
+                        Ok(range) => Some(range),
+                        Err(error::RangeError::Unsatisfiable(_)) => {
+                            self.response.headers.insert(
+                                "Content-Range".to_string(),
+                                range::unsatisfied_content_range(length),
+                            );
+                            return self.error_response(utils::HttpStatusCode::RangeNotSatisfiable);
+                        }
+                        Err(_) => None,
+                    },
+                    false => None,
+                }
+            }
+            _ => None,
+        };
+        let (status_code, body_length) = match range {
+            Some(range) => {
+                if let Err(e) = reader.seek(SeekFrom::Start(range.start)) {
+                    eprintln!("Error while seeking in {:?}: {}", path, e);
+                    return self.error_response(utils::HttpStatusCode::InternalServerError);
+                }
+                self.response
+                    .headers
+                    .insert("Content-Range".to_string(), range.content_range(length));
+                (utils::HttpStatusCode::PartialContent, range.length())
+            }
+            None => (utils::HttpStatusCode::OK, length),
+        };
+
+        if let Some(digests) = digests {
+            match digests {
+                Ok(digests) => {
+                    for (name, value) in digests.headers() {
+                        if name == "Content-MD5"
+                            && status_code == utils::HttpStatusCode::PartialContent
+                        {
+                            continue;
+                        }
+                        self.response.headers.insert(name, value);
+                    }
+                }
+                Err(e) => eprintln!("Error while computing the digests of {:?}: {}", path, e),
+            }
+        }
+
+        let res = &mut self.response;
+        if !res
+            .headers
+            .keys()
+            .any(|name| name.eq_ignore_ascii_case("Content-Type"))
+        {
+            res.headers.insert(
+                "Content-Type".to_string(),
+                utils::mime::from_path(path).to_string(),
+            );
+        }
+        res.status_code = status_code;
+        res.body = String::new();
+        res.stream = Some(stream::StreamBody::from_reader(
+            reader.take(body_length),
+            Some(body_length),
+        ));
+        return res.clone();
+    }
+
+    // returns the value of a header already set on the response, matching the name
+    // case-insensitively
+    fn response_header(&self, name: &str) -> Option<String> {
+        return self
+            .response
+            .headers
+            .iter()
+            .find(|(key, _)| key.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.clone());
+    }
+
+    /// Returns the point in time the request was received at.
+    ///
+    /// Use it whenever something refers to the start of the request(like logging, timings or
+    /// timeouts), so that all of them agree on it.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # use browzer_web::{context::Context, request::Request};
+    /// let context = Context::new(Request::default());
+    ///
+    /// println!("handled in {:?}", context.received_at().elapsed());
+    /// ```
+    pub fn received_at(&self) -> request::ReceivedAt {
+        return self.request.received_at;
+    }
+
+    /// Returns the cancellation token of the request, which becomes cancelled once the client
+    /// disconnected.
+    ///
+    /// Long-running handlers should check it between units of work, and streaming handlers can
+    /// move it into their stream, see the `cancel` module for how disconnects are detected.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # use browzer_web::{context::Context, request::Request};
+    /// let context = Context::new(Request::default());
+    ///
+    /// assert!(!context.cancellation_token().is_cancelled());
+    /// ```
+    pub fn cancellation_token(&self) -> cancel::CancellationToken {
+        return self.request.cancellation.clone();
+    }
+
+    /// Returns the `Connection` the request arrived on, with metadata about the connection and a
+    /// storage of values shared with the other requests of the connection.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # use browzer_web::{context::Context, request::Request};
+    /// let context = Context::new(Request::default());
+    ///
+    /// assert!(context.connection().tls().is_none());
+    /// assert_eq!(context.connection().set(42u32), None);
+    /// assert_eq!(context.connection().get::<u32>(), Some(42));
+    /// ```
+    pub fn connection(&self) -> &connection::Connection {
+        return &self.request.connection;
+    }
+
+    /// Spawns a task on the background pool of the server, tied to this request.
+    ///
+    /// Use it instead of `thread::spawn` for work a handler fans out or fires off. The returned
+    /// `ScopedTask` can be joined for it's result, cancelled, or detached to let it finish on it's
+    /// own. A task which is dropped without being joined or detached(like when the handler returns
+    /// early) is cancelled and waited for before the response is sent, so it never leaks. The task
+    /// receives a cancellation token which is cancelled in that case, when the task is cancelled
+    /// or when the client of the request disconnects. See `WebServer::background_workers` for the
+    /// size of the pool.
+    ///
+    /// # Arguments
+    ///
+    /// - `task` - The task, receiving the cancellation token it should check between units of work.
+    ///
+    /// # Returns
+    ///
+    /// - `ScopedTask<T>` - The handle of the task.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # use browzer_web::{context::Context, request::Request, utils::HttpStatusCode};
+    /// let mut context = Context::new(Request::default());
+    ///
+    /// // fetch two things at the same time
+    /// let users = context.spawn_scoped(|_| vec!["alice", "bob"]);
+    /// let posts = context.spawn_scoped(|_| 42);
+    /// let body = format!("{:?} wrote {} posts", users.join().unwrap(), posts.join().unwrap());
+    /// let response = context.send_string(HttpStatusCode::OK, &body);
+    /// # assert_eq!(response.body, "[\"alice\", \"bob\"] wrote 42 posts");
+    /// ```
+    pub fn spawn_scoped<F, T>(&self, task: F) -> tasks::ScopedTask<T>
+    where
+        F: FnOnce(cancel::CancellationToken) -> T + Send + 'static,
+        T: Send + 'static,
+    {
+        return self
+            .background_pool
+            .spawn_scoped(&self.request.cancellation, task);
+    }
+
+    /// Starts a long-running job in the background and constructs a `202 Accepted` response
+    /// pointing the client to the status of the job, see `WebServer::jobs`.
+    ///
+    /// The response carries the URL path of the job status in it's `Location` header and a JSON
+    /// body like `{"id":"...","status":"pending","status_url":"/jobs/..."}`.
+    ///
+    /// # Arguments
+    ///
+    /// - `pool` - The name of the pool the job runs on, like `jobs::DEFAULT_POOL`.
+    /// - `job` - The job, returning it's result or an error message. A panic fails the job.
+    ///
+    /// # Returns
+    ///
+    /// A `Response` with the `202 Accepted` status code, or `500 Internal Server Error` if jobs
+    /// aren't enabled or there is no pool with that name, which is also printed using `eprintln!`.
+    pub fn accept_async<F>(&mut self, pool: &str, job: F) -> response::Response
+    where
+        F: FnOnce() -> Result<String, String> + Send + 'static,
+    {
+        let enqueued = match self.jobs {
+            Some(ref jobs) => jobs.enqueue(pool, job).map(|id| (jobs.status_url(&id), id)),
+            None => {
+                eprintln!("Background jobs aren't enabled, see `WebServer::jobs`");
+                return self.send_string(
+                    utils::HttpStatusCode::InternalServerError,
+                    "Internal Server Error",
+                );
+            }
+        };
+        let (status_url, id) = match enqueued {
+            Some(enqueued) => enqueued,
+            None => {
+                eprintln!("There is no background job pool named \"{}\"", pool);
+                return self.send_string(
+                    utils::HttpStatusCode::InternalServerError,
+                    "Internal Server Error",
+                );
+            }
+        };
+
+        #[derive(Serialize)]
+        struct Accepted<'a> {
+            id: &'a str,
+            status: &'a str,
+            status_url: &'a str,
+        }
+        let body = serde_json::to_string(&Accepted {
+            id: &id,
+            status: jobs::JobStatus::Pending.as_str(),
+            status_url: &status_url,
+        })
+        .unwrap_or_default();
+        self.response
+            .headers
+            .insert("Location".to_string(), status_url);
+        self.response
+            .headers
+            .insert("Content-Type".to_string(), "application/json".to_string());
+        return self.send_string(utils::HttpStatusCode::Accepted, &body);
+    }
+
+    /// Renders an HTML template with the given data, see `WebServer::templates`.
+    ///
+    /// The response gets the `Content-Type: text/html; charset=utf-8` header, unless the header
+    /// was set already.
+    ///
+    /// # Arguments
+    ///
+    /// - `name` - The name of the template, like `posts/show.html`.
+    /// - `data` - The data the template is rendered with, usually a struct or map serializing into
+    ///   a JSON object.
+    ///
+    /// # Returns
+    ///
+    /// A `Response` with the `200 OK` status code and the rendered template, or the `500 Internal
+    /// Server Error` response of `Context::error_response` if templates aren't enabled or rendering
+    /// failed, which is also printed using `eprintln!`.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// # use browzer_web::{templates::Templates, WebServer};
+    /// # use serde::Serialize;
+    /// #[derive(Serialize)]
+    /// struct Profile {
+    ///     name: String,
+    /// }
+    ///
+    /// let mut server = WebServer::new("127.0.0.1:8080".to_string(), 4);
+    /// server.templates(Templates::from_dir("./templates").unwrap());
+    /// server.get("/users/:name", |mut c| {
+    ///     let profile = Profile { name: c.params["name"].clone() };
+    ///     return c.render("profile.html", &profile);
+    /// });
+    /// ```
+    pub fn render<T: Serialize>(&mut self, name: &str, data: &T) -> response::Response {
+        let rendered = match self.templates {
+            Some(ref templates) => templates.render(name, data),
+            None => {
+                eprintln!("Templates aren't enabled, see `WebServer::templates`");
+                return self.error_response(utils::HttpStatusCode::InternalServerError);
+            }
+        };
+        match rendered {
+            Ok(body) => {
+                if self.response_header("Content-Type").is_none() {
+                    self.response.headers.insert(
+                        "Content-Type".to_string(),
+                        "text/html; charset=utf-8".to_string(),
+                    );
+                }
+                return self.send_string(utils::HttpStatusCode::OK, &body);
+            }
+            Err(e) => {
+                eprintln!("Error while rendering the template {}: {}", name, e);
+                return self.error_response(utils::HttpStatusCode::InternalServerError);
+            }
+        }
+    }
+
+    /// Summarizes the client of the request, it's browser, platform and whether it looks like a
+    /// bot, from the `User-Agent` and client hint headers.
+    ///
+    /// The summary is a heuristic(see the `client` module), good for analytics and for adapting
+    /// responses like serving lighter pages when `Save-Data` is on, but never for access control.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # use browzer_web::{client::BrowserFamily, context::Context, request::Request};
+    /// let mut request = Request::default();
+    /// request.headers.insert("User-Agent".to_string(), "curl/8.5.0".to_string());
+    /// request.headers.insert("Save-Data".to_string(), "on".to_string());
+    /// let context = Context::new(request);
+    ///
+    /// let client = context.client();
+    /// assert_eq!(client.browser, BrowserFamily::Other);
+    /// assert!(client.bot);
+    /// assert!(client.hints.save_data);
+    /// ```
+    pub fn client(&self) -> client::ClientInfo {
+        return client::ClientInfo::from_request(&self.request);
+    }
+
+    /// Constructs an RFC 7807 `application/problem+json` response from the given problem details.
+    ///
+    /// # Arguments
+    ///
+    /// - `problem` - A `ProblemDetails` describing the error.
+    ///
+    /// # Returns
+    ///
+    /// A `Response` with the problem's status code and JSON body.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # use browzer_web::{context::Context, problem::ProblemDetails, request::Request, utils::HttpStatusCode};
+    /// let mut context = Context::new(Request::default());
+    /// let response = context.send_problem(
+    ///     ProblemDetails::new(HttpStatusCode::Conflict).with_detail("Username is already taken"),
+    /// );
+    /// ```
+    pub fn send_problem(&mut self, problem: problem::ProblemDetails) -> response::Response {
+        let problem_response = problem.to_response();
+        let res = &mut self.response;
+        res.status_code = problem_response.status_code;
+        res.headers.extend(problem_response.headers);
+        res.body = problem_response.body;
+        res.clone()
+    }
+
     /// Constructs a redirect response with the given status code and target route.
     ///
     /// # Arguments
@@ -105,7 +709,8 @@ impl Context {
     /// # Examples
     ///
     /// ```rust
-    /// let mut context = Context::new(Request::new());
+    /// # use browzer_web::{context::Context, request::Request, utils::HttpStatusCode};
+    /// let mut context = Context::new(Request::default());
     /// let response = context.redirect(HttpStatusCode::SeeOther, "/home");
     /// ```
     pub fn redirect(
@@ -120,6 +725,528 @@ impl Context {
         res.clone()
     }
 
+    /// This method allows the user to send a JSON response back to the client
+    ///
+    /// The value is serialized using `serde_json` and the `Content-Type` header of the response is
+    /// set to `application/json`.
+    ///
+    /// # Arguments
+    /// - `status_code` - A `HttpStatusCode` representing the status code of the response
+    /// - `value` - Any value implementing `serde::Serialize` which is sent as the response body
+    ///
+    /// # Returns
+    /// - A `Response` with the serialized value as it's body, or a `500 Internal Server Error`
+    ///   response if the value couldn't be serialized
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # use browzer_web::{context::Context, request::Request, utils::HttpStatusCode};
+    /// use serde::Serialize;
+    ///
+    /// #[derive(Serialize)]
+    /// struct User {
+    ///     id: u64,
+    ///     name: String,
+    /// }
+    ///
+    /// let mut context = Context::new(Request::default());
+    /// let response = context.send_json(HttpStatusCode::OK, &User { id: 1, name: "axew".to_string() });
+    ///
+    /// assert_eq!(response.body, r#"{"id":1,"name":"axew"}"#);
+    /// assert_eq!(response.headers.get("Content-Type").unwrap(), "application/json");
+    /// ```
+    pub fn send_json<T: Serialize>(
+        &mut self,
+        status_code: utils::HttpStatusCode,
+        value: &T,
+    ) -> response::Response {
+        match serde_json::to_string(value) {
+            Ok(body) => {
+                self.response
+                    .headers
+                    .insert("Content-Type".to_string(), "application/json".to_string());
+                return self.send_string(status_code, &body);
+            }
+            Err(e) => {
+                eprintln!("Failed to serialize JSON response: {}", e);
+                let status_code = utils::HttpStatusCode::InternalServerError;
+                let body = status_code.code().0.to_string();
+                return self.send_string(status_code, &body);
+            }
+        }
+    }
+
+    /// This method allows the user to read the JSON body of the request as a typed value
+    ///
+    /// The `Content-Type` of the request has to be `application/json`(or a `+json` suffixed media
+    /// type like `application/merge-patch+json`) before the body is deserialized using
+    /// `serde_json`.
+    ///
+    /// # Returns
+    /// - `Result<T, BindError>` - The deserialized value, or a `BindError` describing why the body
+    ///   couldn't be read, whose `status_code` method returns the status code to respond with
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # use browzer_web::{context::Context, problem::ProblemDetails, request::Request, utils::HttpStatusCode};
+    /// use serde::Deserialize;
+    ///
+    /// #[derive(Deserialize)]
+    /// struct NewUser {
+    ///     name: String,
+    /// }
+    ///
+    /// let mut request = Request::default();
+    /// request.headers.insert("Content-Type".to_string(), "application/json".to_string());
+    /// request.body = Some(r#"{"name": "axew"}"#.to_string());
+    /// let mut context = Context::new(request);
+    ///
+    /// let response = match context.bind_json::<NewUser>() {
+    ///     Ok(user) => context.send_string(HttpStatusCode::Created, &user.name),
+    ///     Err(e) => context.send_problem(ProblemDetails::new(e.status_code()).with_detail(&e.to_string())),
+    /// };
+    /// assert_eq!(response.status_code, HttpStatusCode::Created);
+    /// ```
+    pub fn bind_json<T: DeserializeOwned>(&self) -> Result<T, error::BindError> {
+        let content_type = match self.request.header("Content-Type") {
+            Some(content_type) => content_type,
+            None => return Err(error::BindError::MissingContentType),
+        };
+        let media_type = content_type
+            .split(';')
+            .next()
+            .unwrap_or("")
+            .trim()
+            .to_ascii_lowercase();
+        if media_type != "application/json" && !media_type.ends_with("+json") {
+            return Err(error::BindError::UnsupportedContentType(
+                content_type.to_string(),
+            ));
+        }
+        let body = match self.request.body {
+            Some(ref body) if !body.trim().is_empty() => body,
+            _ => return Err(error::BindError::MissingBody),
+        };
+        match serde_json::from_str(body) {
+            Ok(value) => return Ok(value),
+            Err(e) => return Err(error::BindError::InvalidJson(e)),
+        }
+    }
+
+    /// Returns the query string of the request, parsed.
+    ///
+    /// Unlike `query_params`, which only keeps the first value of every key, the parsed query
+    /// string keeps all of them. A query string which can't be parsed(which the router answers
+    /// with `400 Bad Request` before the handler runs) results in an empty one. It is parsed only
+    /// once per request, see `Request::query`.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # use browzer_web::{context::Context, request::Request};
+    /// let mut request = Request::default();
+    /// request.path = "/posts?tag=rust&tag=web".to_string();
+    /// let context = Context::new(request);
+    ///
+    /// assert_eq!(context.query_string().get_all("tag"), vec!["rust", "web"]);
+    /// ```
+    pub fn query_string(&self) -> &request::Query {
+        static EMPTY: request::Query = request::Query::new();
+        return self.request.query().unwrap_or(&EMPTY);
+    }
+
+    /// Returns the first value of a query parameter, percent-decoded.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # use browzer_web::{context::Context, request::Request};
+    /// let mut request = Request::default();
+    /// request.path = "/search?q=hello+world".to_string();
+    /// let context = Context::new(request);
+    ///
+    /// assert_eq!(context.query("q"), Some("hello world".to_string()));
+    /// assert_eq!(context.query("page"), None);
+    /// ```
+    pub fn query(&self, key: &str) -> Option<String> {
+        return self.query_string().get(key).map(|value| value.to_string());
+    }
+
+    /// Returns all values of a query parameter which appears more than once, like `tag` in
+    /// `?tag=rust&tag=web`, in the order they appear in.
+    pub fn query_all(&self, key: &str) -> Vec<String> {
+        return self
+            .query_string()
+            .get_all(key)
+            .into_iter()
+            .map(|value| value.to_string())
+            .collect();
+    }
+
+    /// Parses the first value of a query parameter into a typed value, using it's `FromStr`
+    /// implementation.
+    ///
+    /// # Returns
+    ///
+    /// - `Result<T, QueryError>` - The parsed value, or a `QueryError` whose `status_code` method
+    ///   returns the status code to respond with. Handlers returning a `Result` can use `?` on it.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `QueryError::Missing` error if the query parameter is missing, and an
+    /// `InvalidValue` one if it can't be parsed.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # use browzer_web::{context::Context, error::QueryError, request::Request};
+    /// let mut request = Request::default();
+    /// request.path = "/posts?page=2&per_page=many".to_string();
+    /// let context = Context::new(request);
+    ///
+    /// assert_eq!(context.query_parse::<u32>("page"), Ok(2));
+    /// assert!(matches!(
+    ///     context.query_parse::<u32>("per_page"),
+    ///     Err(QueryError::InvalidValue(..))
+    /// ));
+    /// // optional parameters fall back to a default
+    /// assert_eq!(context.query_parse::<u32>("limit").unwrap_or(20), 20);
+    /// ```
+    pub fn query_parse<T>(&self, key: &str) -> Result<T, error::QueryError>
+    where
+        T: FromStr,
+        T::Err: fmt::Display,
+    {
+        let value = match self.query(key) {
+            Some(value) => value,
+            None => return Err(error::QueryError::Missing(key.to_string())),
+        };
+        match value.parse() {
+            Ok(value) => return Ok(value),
+            Err(e) => {
+                return Err(error::QueryError::InvalidValue(
+                    key.to_string(),
+                    e.to_string(),
+                ))
+            }
+        }
+    }
+
+    /// Deserializes the query string of the request into a typed value using `serde`.
+    ///
+    /// Fields wrapped in `Option` may be missing from the query string. Repeated keys aren't
+    /// supported, use `Context::query_all` for them.
+    ///
+    /// # Returns
+    ///
+    /// - `Result<T, QueryError>` - The deserialized value, or a `QueryError::InvalidQuery` error
+    ///   describing why the query string doesn't match the expected type.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # use browzer_web::{context::Context, request::Request};
+    /// use serde::Deserialize;
+    ///
+    /// #[derive(Deserialize)]
+    /// struct Search {
+    ///     q: String,
+    ///     page: Option<u32>,
+    /// }
+    ///
+    /// let mut request = Request::default();
+    /// request.path = "/search?q=hello+world&page=3".to_string();
+    /// let context = Context::new(request);
+    ///
+    /// let search = context.bind_query::<Search>().unwrap();
+    /// assert_eq!(search.q, "hello world");
+    /// assert_eq!(search.page, Some(3));
+    /// ```
+    pub fn bind_query<T: DeserializeOwned>(&self) -> Result<T, error::QueryError> {
+        match serde_urlencoded::from_str(self.request.query_string()) {
+            Ok(value) => return Ok(value),
+            Err(e) => return Err(error::QueryError::InvalidQuery(e.to_string())),
+        }
+    }
+
+    /// Advertises related resources by setting the `Link` header of the response.
+    ///
+    /// If a `Link` header was already set, the new links are appended to it.
+    ///
+    /// # Arguments
+    ///
+    /// - `links` - The `Links` to advertise.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # use browzer_web::{context::Context, links::Pagination, request::Request, utils::HttpStatusCode};
+    /// let mut context = Context::new(Request::default());
+    /// let pagination = Pagination::from_query(&context.query_params, 20).with_total(100);
+    /// context.set_links(&pagination.links("/users"));
+    /// let response = context.send_string(HttpStatusCode::OK, "[]");
+    /// ```
+    pub fn set_links(&mut self, links: &links::Links) {
+        if links.is_empty() {
+            return;
+        }
+        let value = match self.response.headers.get("Link") {
+            Some(existing) => format!("{}, {}", existing, links),
+            None => links.to_string(),
+        };
+        self.response.headers.insert("Link".to_string(), value);
+    }
+
+    /// Returns the address of the client of the request.
+    ///
+    /// Behind a TCP load balancer the address of the connection is the one of the load balancer,
+    /// enable `WebServer::proxy_protocol` to get the one of the client instead. Behind HTTP
+    /// reverse proxies use `Context::client_ip`.
+    ///
+    /// # Returns
+    ///
+    /// - `Option<SocketAddr>` - The address, `None` if the request wasn't read from a connection.
+    pub fn remote_addr(&self) -> Option<SocketAddr> {
+        return self.request.remote_addr;
+    }
+
+    /// Returns the IP address of the client of the request, taking the `Forwarded` or
+    /// `X-Forwarded-For` header of trusted reverse proxies into account.
+    ///
+    /// Without `WebServer::trusted_proxies` the headers are ignored and this is the IP address of
+    /// `Context::remote_addr`, see `forwarded::TrustedProxies::client_ip` for how the headers are
+    /// evaluated otherwise. Use this rather than reading the headers directly for rate limiting
+    /// and logging, since clients can send them too.
+    ///
+    /// # Returns
+    ///
+    /// - `Option<IpAddr>` - The address, `None` if the request wasn't read from a connection.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # use browzer_web::{context::Context, request::Request};
+    /// let mut request = Request::default();
+    /// request.remote_addr = Some("10.0.0.1:52100".parse().unwrap());
+    /// request.headers.insert("X-Forwarded-For".to_string(), "203.0.113.7".to_string());
+    /// let context = Context::new(request);
+    ///
+    /// // no proxy is trusted by default
+    /// assert_eq!(context.client_ip(), Some("10.0.0.1".parse().unwrap()));
+    /// ```
+    pub fn client_ip(&self) -> Option<IpAddr> {
+        match self.trusted_proxies {
+            Some(ref proxies) => return proxies.client_ip(&self.request),
+            None => return self.request.remote_addr.map(|addr| addr.ip()),
+        }
+    }
+
+    /// Returns the authenticated client of the request.
+    ///
+    /// The identity is set by `auth::BasicAuth`, `auth::BearerAuth` and `auth::JwtAuth` for the
+    /// handlers they wrap.
+    ///
+    /// # Returns
+    ///
+    /// - `Option<&Identity>` - The identity, `None` if the request wasn't authenticated.
+    pub fn identity(&self) -> Option<&auth::Identity> {
+        return self.identity.as_ref();
+    }
+
+    /// Answers the request with the given response, skipping the remaining middlewares and the
+    /// route handler.
+    ///
+    /// Middlewares can't return a response themselves, so this is how they turn away a request,
+    /// like an authentication middleware rejecting an invalid token.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// # use browzer_web::{utils::HttpStatusCode, WebServer};
+    /// let mut server = WebServer::new("127.0.0.1:8080".to_string(), 4);
+    /// server.middleware(|mut ctx| {
+    ///     if ctx.request.header("X-Api-Key").is_none() {
+    ///         let response = ctx.send_string(HttpStatusCode::Unauthorized, "Unauthorized");
+    ///         ctx.abort(response);
+    ///     }
+    ///     return ctx;
+    /// });
+    /// ```
+    pub fn abort(&mut self, response: response::Response) {
+        self.aborted = Some(response);
+    }
+
+    /// Returns the ID of the request, if the server assigns them(see `WebServer::request_ids`).
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # use browzer_web::{context::Context, request::Request, request_id::RequestId};
+    /// let mut context = Context::new(Request::default());
+    /// assert_eq!(context.request_id(), None);
+    ///
+    /// context.set(RequestId("abc-123".to_string()));
+    /// assert_eq!(context.request_id(), Some("abc-123"));
+    /// ```
+    pub fn request_id(&self) -> Option<&str> {
+        return self
+            .get::<request_id::RequestId>()
+            .map(|request_id| request_id.0.as_str());
+    }
+
+    /// Returns the value of the given type attached to the request with `Context::set`, like the
+    /// `auth::jwt::Claims` of a token verified by `auth::JwtAuth`.
+    ///
+    /// # Returns
+    ///
+    /// - `Option<&T>` - The value, `None` if no value of the type was attached.
+    pub fn get<T: Any + Send + Sync>(&self) -> Option<&T> {
+        return self
+            .extensions
+            .get(&TypeId::of::<T>())
+            .and_then(|value| value.downcast_ref::<T>());
+    }
+
+    /// Returns a mutable reference to the value of the given type attached to the request.
+    ///
+    /// # Returns
+    ///
+    /// - `Option<&mut T>` - The value, `None` if no value of the type was attached.
+    pub fn get_mut<T: Any + Send + Sync>(&mut self) -> Option<&mut T> {
+        return self
+            .extensions
+            .get_mut(&TypeId::of::<T>())
+            .and_then(|value| value.downcast_mut::<T>());
+    }
+
+    /// Attaches a value to the request, replacing the one of the same type.
+    ///
+    /// Values are keyed by their type, so middlewares can pass data(like the current user or a
+    /// database handle) to the handlers after them. Wrap plain types like `String` in a type of
+    /// your own to keep them from clashing with the values of other middlewares.
+    ///
+    /// # Returns
+    ///
+    /// - `Option<T>` - The replaced value, `None` if no value of the type was attached.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// # use browzer_web::{utils::HttpStatusCode, WebServer};
+    /// struct CurrentUser(String);
+    ///
+    /// let mut server = WebServer::new("127.0.0.1:8080".to_string(), 4);
+    /// server.middleware(|mut ctx| {
+    ///     if let Some(user) = ctx.request.header("X-User").cloned() {
+    ///         ctx.set(CurrentUser(user));
+    ///     }
+    ///     return ctx;
+    /// });
+    /// server.get("/me", |mut c| match c.get::<CurrentUser>() {
+    ///     Some(CurrentUser(user)) => {
+    ///         let body = format!("Hello, {}!", user);
+    ///         return c.send_string(HttpStatusCode::OK, &body);
+    ///     }
+    ///     None => return c.send_string(HttpStatusCode::Unauthorized, "Unauthorized"),
+    /// });
+    /// ```
+    pub fn set<T: Any + Send + Sync>(&mut self, value: T) -> Option<T> {
+        return self
+            .extensions
+            .insert(TypeId::of::<T>(), Box::new(value))
+            .and_then(|replaced| replaced.downcast::<T>().ok())
+            .map(|replaced| *replaced);
+    }
+
+    /// Detaches the value of the given type from the request.
+    ///
+    /// # Returns
+    ///
+    /// - `Option<T>` - The value, `None` if no value of the type was attached.
+    pub fn remove<T: Any + Send + Sync>(&mut self) -> Option<T> {
+        return self
+            .extensions
+            .remove(&TypeId::of::<T>())
+            .and_then(|value| value.downcast::<T>().ok())
+            .map(|value| *value);
+    }
+
+    /// Returns the session of the request.
+    ///
+    /// Sessions have to be enabled with `WebServer::sessions`, otherwise every request gets a new,
+    /// empty session and changes to it are never stored.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # use browzer_web::{context::Context, request::Request, utils::HttpStatusCode};
+    /// let mut context = Context::new(Request::default());
+    /// let visits = match context.session().get("visits") {
+    ///     Some(visits) => visits.parse::<u64>().unwrap_or(0) + 1,
+    ///     None => 1,
+    /// };
+    /// context.session().set("visits", &visits.to_string());
+    /// let response = context.send_string(HttpStatusCode::OK, &format!("Visit number {}", visits));
+    /// ```
+    pub fn session(&self) -> &sessions::Session {
+        return &self.session;
+    }
+
+    /// Sets a cookie on the response, replacing a cookie of the same name set before.
+    ///
+    /// # Arguments
+    ///
+    /// - `cookie` - The `Cookie` to send with the response, see `Cookie::builder`.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # use browzer_web::{context::Context, request::Request, utils::{Cookie, HttpStatusCode, SameSite}};
+    /// let mut context = Context::new(Request::default());
+    /// context.set_cookie(
+    ///     Cookie::builder("session", "abc123")
+    ///         .path("/")
+    ///         .http_only(true)
+    ///         .same_site(SameSite::Strict)
+    ///         .build(),
+    /// );
+    /// let response = context.send_string(HttpStatusCode::OK, "Logged in");
+    ///
+    /// assert!(response
+    ///     .to_string()
+    ///     .contains("Set-Cookie: session=abc123; Path=/; HttpOnly; SameSite=Strict\r\n"));
+    /// ```
+    pub fn set_cookie(&mut self, cookie: utils::Cookie) {
+        self.response.cookies.insert(cookie.name.clone(), cookie);
+    }
+
+    /// Returns a cookie sent with the request.
+    ///
+    /// # Arguments
+    ///
+    /// - `name` - The name of the cookie.
+    ///
+    /// # Returns
+    ///
+    /// - `Option<&Cookie>` - The cookie, `None` if the request has no cookie of that name.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # use browzer_web::{context::Context, request::Request};
+    /// let context = Context::new(Request::default());
+    ///
+    /// match context.get_cookie("session") {
+    ///     Some(cookie) => println!("session {}", cookie.value),
+    ///     None => println!("not logged in"),
+    /// }
+    /// ```
+    pub fn get_cookie(&self, name: &str) -> Option<&utils::Cookie> {
+        return self.request.cookies.get(name);
+    }
+
     /// This method allows the user to read the form data from the request
     ///
     /// # Arguments
@@ -131,7 +1258,8 @@ impl Context {
     /// # Examples
     ///
     /// ```rust
-    /// let mut context = Context::new(Request::new());
+    /// # use browzer_web::{context::Context, request::Request, utils::HttpStatusCode};
+    /// let mut context = Context::new(Request::default());
     /// let form_value = context.form_value("form_value_key");
     /// ```
     pub fn form_value(&mut self, key: &str) -> String {
@@ -159,4 +1287,53 @@ impl Context {
             Err(_) => return String::from(""),
         };
     }
+
+    /// Declares the current entity tag of the requested resource and evaluates the request's
+    /// `If-Match` / `If-None-Match` preconditions against it.
+    ///
+    /// The `ETag` header is set on the context's response, so every response sent afterwards
+    /// advertises the current tag. When a precondition fails, a ready-made `412 Precondition Failed`
+    /// (or `304 Not Modified` for `GET` and `HEAD`) response is returned, which the handler should
+    /// send back instead of performing the write.
+    ///
+    /// # Arguments
+    ///
+    /// - `etag` - The current `EntityTag` of the resource, or `None` if the resource doesn't exist
+    ///   yet(so that `If-None-Match: *` can guard against creating it twice).
+    ///
+    /// # Returns
+    ///
+    /// - `Option<Response>` - The response to send if a precondition failed, `None` otherwise.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # use browzer_web::{conditional::EntityTag, context::Context, request::Request, utils::HttpStatusCode};
+    /// let mut context = Context::new(Request::default());
+    /// let response = match context.check_preconditions(Some(&EntityTag::strong("v1"))) {
+    ///     Some(precondition_failed) => precondition_failed,
+    ///     None => context.send_string(HttpStatusCode::OK, "Updated!"),
+    /// };
+    /// ```
+    pub fn check_preconditions(
+        &mut self,
+        etag: Option<&conditional::EntityTag>,
+    ) -> Option<response::Response> {
+        if let Some(etag) = etag {
+            self.response
+                .headers
+                .insert("ETag".to_string(), etag.to_string());
+        }
+        match conditional::evaluate_preconditions(&self.request, etag) {
+            Some(status_code) => {
+                let body = match status_code {
+                    utils::HttpStatusCode::NotModified => "",
+                    _ => status_code.code().0,
+                }
+                .to_string();
+                return Some(self.send_string(status_code, &body));
+            }
+            None => return None,
+        }
+    }
 }
@@ -0,0 +1,412 @@
+//! This module implements cross-origin resource sharing(CORS), which lets web pages served from
+//! other origins call the server from the browser.
+//!
+//! A `Cors` policy is enabled with `WebServer::cors`, after which the server answers the preflight
+//! requests browsers send before cross-origin requests on it's own, and adds the
+//! `Access-Control-*` headers to the responses of requests from allowed origins. Routes and route
+//! groups can override the policy of the server, see `RouteBuilder::cors`.
+//!
+//! The CORS headers are added after the response cache(see `WebServer::response_cache`) stored
+//! the response, so a cached response never carries the headers meant for another origin, and
+//! every response whose headers depend on the origin gets a `Vary: Origin` header for the caches
+//! in front of the server.
+
+// internal crate imports
+use crate::{request, response, utils};
+
+// standard library imports
+use std::{fmt, sync::Arc, time::Duration};
+
+// a list of allowed values, or all of them
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum AllowList {
+    Any,
+    Only(Vec<String>),
+}
+
+impl AllowList {
+    fn allows(&self, value: &str) -> bool {
+        match self {
+            AllowList::Any => return true,
+            AllowList::Only(values) => {
+                return values
+                    .iter()
+                    .any(|allowed| allowed.eq_ignore_ascii_case(value))
+            }
+        }
+    }
+
+    fn push(&mut self, value: &str) {
+        match self {
+            AllowList::Any => {}
+            AllowList::Only(values) => values.push(value.to_string()),
+        }
+    }
+}
+
+// a closure deciding whether an origin is allowed, compared by identity so that policies can
+// still be compared
+#[derive(Clone)]
+struct OriginCheck(Arc<dyn Fn(&str) -> bool + Send + Sync>);
+
+impl fmt::Debug for OriginCheck {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        return f.write_str("OriginCheck(<closure>)");
+    }
+}
+
+impl PartialEq for OriginCheck {
+    fn eq(&self, other: &Self) -> bool {
+        return Arc::ptr_eq(&self.0, &other.0);
+    }
+}
+
+impl Eq for OriginCheck {}
+
+/// A cross-origin resource sharing policy, see `WebServer::cors`.
+///
+/// No origin is allowed by a new policy, origins are allowed with `Cors::allow_origin`,
+/// `Cors::allow_any_origin` or a closure passed to `Cors::allow_origin_fn`. Cross-origin requests
+/// may use the `GET`, `HEAD` and `POST` methods and the headers browsers always allow(like `Accept`
+/// or `Content-Type` with a form type) by default.
+///
+/// Preflight requests(`OPTIONS` requests with an `Access-Control-Request-Method` header) are
+/// answered with a `204 No Content` response before any middleware runs, so that authentication
+/// middlewares don't reject them. A preflight for an origin, method or header which isn't allowed
+/// is answered without `Access-Control-*` headers, which makes the browser block the actual
+/// request.
+///
+/// # Examples
+///
+/// ```rust
+/// use browzer_web::{cors::Cors, request::Request, utils::HttpMethod};
+/// use std::time::Duration;
+///
+/// let cors = Cors::new()
+///     .allow_origin("https://app.example.com")
+///     .allow_methods(&[HttpMethod::GET, HttpMethod::POST, HttpMethod::DELETE])
+///     .allow_headers(&["Authorization", "Content-Type"])
+///     .allow_credentials(true)
+///     .max_age(Duration::from_secs(600));
+///
+/// let mut request = Request::default();
+/// request.method = HttpMethod::OPTIONS;
+/// request.headers.insert("Origin".to_string(), "https://app.example.com".to_string());
+/// request.headers.insert("Access-Control-Request-Method".to_string(), "DELETE".to_string());
+///
+/// let response = cors.preflight_response(&request).unwrap();
+/// assert_eq!(
+///     response.headers.get("Access-Control-Allow-Origin").unwrap(),
+///     "https://app.example.com"
+/// );
+/// assert_eq!(
+///     response.headers.get("Access-Control-Allow-Methods").unwrap(),
+///     "GET, POST, DELETE"
+/// );
+/// assert_eq!(response.headers.get("Access-Control-Max-Age").unwrap(), "600");
+/// ```
+// ----- Cors struct
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Cors {
+    origins: AllowList,
+    methods: Vec<utils::HttpMethod>,
+    headers: AllowList,
+    exposed_headers: Vec<String>,
+    credentials: bool,
+    max_age: Option<Duration>,
+    origin_check: Option<OriginCheck>,
+}
+
+// default implementation for Cors struct
+impl Default for Cors {
+    fn default() -> Self {
+        return Cors::new();
+    }
+}
+
+impl Cors {
+    /// Creates a new `Cors` policy which doesn't allow any origin yet.
+    pub fn new() -> Cors {
+        return Cors {
+            origins: AllowList::Only(Vec::new()),
+            methods: vec![
+                utils::HttpMethod::GET,
+                utils::HttpMethod::HEAD,
+                utils::HttpMethod::POST,
+            ],
+            headers: AllowList::Only(Vec::new()),
+            exposed_headers: Vec::new(),
+            credentials: false,
+            max_age: None,
+            origin_check: None,
+        };
+    }
+
+    /// Allows requests from an origin.
+    ///
+    /// # Arguments
+    ///
+    /// - `origin` - The origin, a scheme, host and optional port without a path, like
+    ///   `https://app.example.com`.
+    pub fn allow_origin(mut self, origin: &str) -> Cors {
+        self.origins.push(origin.trim_end_matches('/'));
+        return self;
+    }
+
+    /// Allows requests from the origins a closure accepts, besides the ones allowed with
+    /// `Cors::allow_origin`, like origins looked up in a database.
+    ///
+    /// The closure is called for every cross-origin request, so it should answer from memory
+    /// rather than query a database every time.
+    ///
+    /// # Arguments
+    ///
+    /// - `check` - A closure which receives the `Origin` header of the request and returns
+    ///   whether the origin is allowed.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use browzer_web::{cors::Cors, request::Request, response::Response};
+    /// use browzer_web::utils::HttpStatusCode;
+    ///
+    /// let cors = Cors::new().allow_origin_fn(|origin| origin.ends_with(".tenant.example.com"));
+    ///
+    /// let mut request = Request::default();
+    /// request.headers.insert("Origin".to_string(), "https://acme.tenant.example.com".to_string());
+    /// let mut response = Response::new(HttpStatusCode::OK, String::new());
+    /// cors.apply(&request, &mut response);
+    ///
+    /// assert_eq!(
+    ///     response.headers.get("Access-Control-Allow-Origin").unwrap(),
+    ///     "https://acme.tenant.example.com"
+    /// );
+    /// assert_eq!(response.headers.get("Vary").unwrap(), "Origin");
+    /// ```
+    pub fn allow_origin_fn<F>(mut self, check: F) -> Cors
+    where
+        F: Fn(&str) -> bool + 'static + Send + Sync,
+    {
+        self.origin_check = Some(OriginCheck(Arc::new(check)));
+        return self;
+    }
+
+    /// Allows requests from any origin.
+    ///
+    /// Combined with `Cors::allow_credentials` this lets every website make requests with the
+    /// cookies of it's visitors, so only do that for servers which don't authenticate with
+    /// cookies.
+    pub fn allow_any_origin(mut self) -> Cors {
+        self.origins = AllowList::Any;
+        return self;
+    }
+
+    /// Sets the methods cross-origin requests may use, `GET`, `HEAD` and `POST` by default.
+    pub fn allow_methods(mut self, methods: &[utils::HttpMethod]) -> Cors {
+        self.methods = methods.to_vec();
+        return self;
+    }
+
+    /// Allows cross-origin requests to send the given request headers.
+    pub fn allow_headers(mut self, headers: &[&str]) -> Cors {
+        for header in headers {
+            self.headers.push(header);
+        }
+        return self;
+    }
+
+    /// Allows cross-origin requests to send any request header.
+    pub fn allow_any_header(mut self) -> Cors {
+        self.headers = AllowList::Any;
+        return self;
+    }
+
+    /// Lets the scripts making cross-origin requests read the given response headers, besides the
+    /// ones browsers always expose(like `Content-Type`).
+    pub fn expose_headers(mut self, headers: &[&str]) -> Cors {
+        self.exposed_headers
+            .extend(headers.iter().map(|header| header.to_string()));
+        return self;
+    }
+
+    /// Sets whether cross-origin requests may include credentials(cookies and HTTP authentication),
+    /// `false` by default.
+    pub fn allow_credentials(mut self, credentials: bool) -> Cors {
+        self.credentials = credentials;
+        return self;
+    }
+
+    /// Sets how long browsers may cache the result of a preflight request, which saves a preflight
+    /// for every request. Browsers cap this at a couple of hours at most.
+    pub fn max_age(mut self, max_age: Duration) -> Cors {
+        self.max_age = Some(max_age);
+        return self;
+    }
+
+    /// Returns whether a request is a CORS preflight request.
+    pub fn is_preflight(request: &request::Request) -> bool {
+        return request.method == utils::HttpMethod::OPTIONS
+            && request.header("Origin").is_some()
+            && request.header("Access-Control-Request-Method").is_some();
+    }
+
+    /// Answers a preflight request.
+    ///
+    /// # Arguments
+    ///
+    /// - `request` - The request.
+    ///
+    /// # Returns
+    ///
+    /// - `Option<Response>` - The `204 No Content` response to the preflight, with the
+    ///   `Access-Control-*` headers if the request is allowed, or `None` if the request isn't a
+    ///   preflight request.
+    pub fn preflight_response(&self, request: &request::Request) -> Option<response::Response> {
+        if !Cors::is_preflight(request) {
+            return None;
+        }
+        let mut response = response::Response::new(utils::HttpStatusCode::NoContent, String::new());
+        self.add_vary(&mut response);
+        response.add_vary("Access-Control-Request-Method");
+        response.add_vary("Access-Control-Request-Headers");
+
+        let origin = match self.allowed_origin(request) {
+            Some(origin) => origin,
+            None => return Some(response),
+        };
+        let method = request
+            .header("Access-Control-Request-Method")
+            .map(|method| method.trim())
+            .unwrap_or_default();
+        if !self
+            .methods
+            .iter()
+            .any(|allowed| allowed.to_string() == method)
+        {
+            return Some(response);
+        }
+        let requested_headers: Vec<&str> = request
+            .header("Access-Control-Request-Headers")
+            .map(|headers| {
+                headers
+                    .split(',')
+                    .map(|header| header.trim())
+                    .filter(|header| !header.is_empty())
+                    .collect()
+            })
+            .unwrap_or_default();
+        if !requested_headers
+            .iter()
+            .all(|header| self.headers.allows(header))
+        {
+            return Some(response);
+        }
+
+        self.add_origin_headers(&mut response, origin);
+        response.headers.insert(
+            "Access-Control-Allow-Methods".to_string(),
+            self.methods
+                .iter()
+                .map(|method| method.to_string())
+                .collect::<Vec<_>>()
+                .join(", "),
+        );
+        if !requested_headers.is_empty() {
+            // the requested headers were all checked above, so echoing them allows exactly those
+            response.headers.insert(
+                "Access-Control-Allow-Headers".to_string(),
+                requested_headers.join(", "),
+            );
+        }
+        if let Some(max_age) = self.max_age {
+            response.headers.insert(
+                "Access-Control-Max-Age".to_string(),
+                max_age.as_secs().to_string(),
+            );
+        }
+        return Some(response);
+    }
+
+    /// Adds the `Access-Control-*` headers to the response of a request which isn't a preflight
+    /// request, if it's origin is allowed.
+    ///
+    /// # Arguments
+    ///
+    /// - `request` - The request, only it's `Origin` header is used.
+    /// - `response` - The response to the request.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use browzer_web::{cors::Cors, request::Request, response::Response};
+    /// use browzer_web::utils::HttpStatusCode;
+    ///
+    /// let cors = Cors::new().allow_any_origin().expose_headers(&["X-Request-Id"]);
+    ///
+    /// let mut request = Request::default();
+    /// request.headers.insert("Origin".to_string(), "https://example.com".to_string());
+    /// let mut response = Response::new(HttpStatusCode::OK, String::new());
+    /// cors.apply(&request, &mut response);
+    ///
+    /// assert_eq!(response.headers.get("Access-Control-Allow-Origin").unwrap(), "*");
+    /// assert_eq!(
+    ///     response.headers.get("Access-Control-Expose-Headers").unwrap(),
+    ///     "X-Request-Id"
+    /// );
+    /// ```
+    pub fn apply(&self, request: &request::Request, response: &mut response::Response) {
+        self.add_vary(response);
+        let origin = match self.allowed_origin(request) {
+            Some(origin) => origin,
+            None => return,
+        };
+        self.add_origin_headers(response, origin);
+        if !self.exposed_headers.is_empty() {
+            response.headers.insert(
+                "Access-Control-Expose-Headers".to_string(),
+                self.exposed_headers.join(", "),
+            );
+        }
+    }
+
+    // returns the origin of a request if it is allowed
+    fn allowed_origin<'a>(&self, request: &'a request::Request) -> Option<&'a str> {
+        let origin = request.header("Origin")?.trim();
+        let allowed = self.origins.allows(origin)
+            || self
+                .origin_check
+                .as_ref()
+                .is_some_and(|check| (check.0)(origin));
+        match !origin.is_empty() && allowed {
+            true => return Some(origin),
+            false => return None,
+        }
+    }
+
+    // adds the headers telling the browser that the origin may read the response
+    fn add_origin_headers(&self, response: &mut response::Response, origin: &str) {
+        // a wildcard isn't accepted by browsers for requests with credentials, so the origin is
+        // echoed back for those
+        let allowed_origin = match self.origins == AllowList::Any && !self.credentials {
+            true => "*",
+            false => origin,
+        };
+        response.headers.insert(
+            "Access-Control-Allow-Origin".to_string(),
+            allowed_origin.to_string(),
+        );
+        if self.credentials {
+            response.headers.insert(
+                "Access-Control-Allow-Credentials".to_string(),
+                "true".to_string(),
+            );
+        }
+    }
+
+    // responses differ by origin unless every origin gets the same wildcard response
+    fn add_vary(&self, response: &mut response::Response) {
+        if self.origins != AllowList::Any || self.credentials {
+            response.add_vary("Origin");
+        }
+    }
+}
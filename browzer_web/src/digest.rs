@@ -0,0 +1,486 @@
+//! This module computes and verifies content digests, checksums of bodies which let clients and
+//! servers detect corrupted or tampered transfers.
+//!
+//! A `DigestSet` is enabled with `WebServer::content_digests`, after which files sent with
+//! `Context::send_file`(like the ones of `serve_static`) carry the digests of their contents, and
+//! the `upload::UploadReceiver` reports the digests of the files it stored and verifies the digests
+//! clients send along with their uploads. Digests are exchanged in three headers:
+//!
+//! - `Repr-Digest`(RFC 9530), like `Repr-Digest: sha-256=:LCa0a2j/xo/5m0U8HTBBNBNCLXBkg7+g+YpeiGJm564=:`
+//! - `Digest`(RFC 3230, obsoleted by `Repr-Digest` but still widely used), like
+//!   `Digest: SHA-256=LCa0a2j/xo/5m0U8HTBBNBNCLXBkg7+g+YpeiGJm564=`
+//! - `Content-MD5`(RFC 1864), only if the set contains `Md5`(see the `md5` feature)
+//!
+//! The hash functions are the ones of the RustCrypto `sha2`, `sha1` and `md-5` crates, `Md5` is only
+//! available with the `md5` feature. Other algorithms can be plugged in by implementing
+//! `DigestAlgorithm`.
+
+// internal crate imports
+use crate::utils;
+
+// standard library imports
+use std::{
+    collections::HashMap,
+    fmt, fs,
+    io::{self, Read, Write},
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+    time::SystemTime,
+};
+
+// the number of files whose digests a `DigestSet` remembers, the cache is emptied once it is full
+const MAX_CACHED_FILES: usize = 1024;
+
+/// An incremental computation of a digest.
+pub trait DigestHasher: Send {
+    /// Feeds the next bytes of the message into the hasher.
+    fn update(&mut self, data: &[u8]);
+
+    /// Returns the digest of all bytes fed into the hasher.
+    fn finish(self: Box<Self>) -> Vec<u8>;
+}
+
+/// A hash algorithm content digests can be computed with.
+///
+/// Implement this trait to plug in algorithms which aren't built in, like `sha-512`.
+pub trait DigestAlgorithm: Send + Sync {
+    /// Returns the name of the algorithm as registered for `Repr-Digest`(RFC 9530), like
+    /// `sha-256`. The name is upper-cased for the `Digest` header.
+    fn name(&self) -> &str;
+
+    /// Returns a new hasher computing the digest of a message.
+    fn hasher(&self) -> Box<dyn DigestHasher>;
+}
+
+/// The SHA-256 algorithm(FIPS 180-4), named `sha-256`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Sha256;
+
+/// The SHA-1 algorithm(RFC 3174), named `sha`. It is deprecated for digests, prefer `Sha256`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Sha1;
+
+/// The MD5 algorithm(RFC 1321), named `md5`. It is deprecated for digests and only useful for
+/// clients which still send or check `Content-MD5`, prefer `Sha256`.
+///
+/// Only available with the `md5` feature.
+#[cfg(feature = "md5")]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Md5;
+
+// a `DigestHasher` computing the digest with one of the RustCrypto hash functions
+struct Hasher<D>(D);
+
+impl<D: sha2::Digest + Send> DigestHasher for Hasher<D> {
+    fn update(&mut self, data: &[u8]) {
+        sha2::Digest::update(&mut self.0, data);
+    }
+
+    fn finish(self: Box<Self>) -> Vec<u8> {
+        return self.0.finalize().to_vec();
+    }
+}
+
+impl DigestAlgorithm for Sha256 {
+    fn name(&self) -> &str {
+        return "sha-256";
+    }
+
+    fn hasher(&self) -> Box<dyn DigestHasher> {
+        return Box::new(Hasher(<sha2::Sha256 as sha2::Digest>::new()));
+    }
+}
+
+impl DigestAlgorithm for Sha1 {
+    fn name(&self) -> &str {
+        return "sha";
+    }
+
+    fn hasher(&self) -> Box<dyn DigestHasher> {
+        return Box::new(Hasher(<sha1::Sha1 as sha2::Digest>::new()));
+    }
+}
+
+#[cfg(feature = "md5")]
+impl DigestAlgorithm for Md5 {
+    fn name(&self) -> &str {
+        return "md5";
+    }
+
+    fn hasher(&self) -> Box<dyn DigestHasher> {
+        return Box::new(Hasher(<md5::Md5 as sha2::Digest>::new()));
+    }
+}
+
+// computes the SHA-256 digest of a message
+pub(crate) fn sha256(message: &[u8]) -> Vec<u8> {
+    return <sha2::Sha256 as sha2::Digest>::digest(message).to_vec();
+}
+
+// computes the HMAC-SHA-1(RFC 2104) of a message
+pub(crate) fn hmac_sha1(key: &[u8], message: &[u8]) -> Vec<u8> {
+    // an HMAC accepts keys of any length
+    let mut mac = <hmac::Hmac<sha1::Sha1> as hmac::Mac>::new_from_slice(key)
+        .expect("HMAC keys may have any length");
+    hmac::Mac::update(&mut mac, message);
+    return hmac::Mac::finalize(mac).into_bytes().to_vec();
+}
+
+// computes the HMAC-SHA-256(RFC 2104) of a message
+pub(crate) fn hmac_sha256(key: &[u8], message: &[u8]) -> Vec<u8> {
+    let mut mac = <hmac::Hmac<sha2::Sha256> as hmac::Mac>::new_from_slice(key)
+        .expect("HMAC keys may have any length");
+    hmac::Mac::update(&mut mac, message);
+    return hmac::Mac::finalize(mac).into_bytes().to_vec();
+}
+
+/// The digests of a message, by the names of their algorithms.
+///
+/// # Examples
+///
+/// ```rust
+/// use browzer_web::digest::{DigestSet, Sha1, Sha256};
+///
+/// let digests = DigestSet::new().with(Sha256).with(Sha1).digest(b"hello");
+///
+/// assert_eq!(
+///     digests.headers(),
+///     vec![
+///         (
+///             "Repr-Digest".to_string(),
+///             "sha-256=:LPJNul+wow4m6DsqxbninhsWHlwfp0JecwQzYpOLmCQ=:, sha=:qvTGHdzF6KLavt4PO0gs2a6pQ00=:".to_string()
+///         ),
+///         (
+///             "Digest".to_string(),
+///             "SHA-256=LPJNul+wow4m6DsqxbninhsWHlwfp0JecwQzYpOLmCQ=,SHA=qvTGHdzF6KLavt4PO0gs2a6pQ00=".to_string()
+///         ),
+///     ]
+/// );
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Digests {
+    values: Vec<(String, Vec<u8>)>,
+}
+
+impl Digests {
+    /// Parses the digests a client sent in the `Repr-Digest`, `Content-Digest`, `Digest` and
+    /// `Content-MD5` headers, ignoring malformed ones.
+    ///
+    /// # Arguments
+    ///
+    /// - `header` - Looks up the value of a header by it's name, case-insensitively.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use browzer_web::{digest::Digests, request::Request};
+    ///
+    /// let mut request = Request::default();
+    /// request.headers.insert("Content-MD5".to_string(), "XUFAKrxLKna5cZ2REBfFkg==".to_string());
+    ///
+    /// let digests = Digests::from_headers(|name| request.header(name).map(String::as_str));
+    /// assert_eq!(digests.get("md5").unwrap().len(), 16);
+    /// ```
+    pub fn from_headers<'a, F>(header: F) -> Digests
+    where
+        F: Fn(&str) -> Option<&'a str>,
+    {
+        let mut digests = Digests::default();
+        // structured field dictionaries(RFC 9530), like `sha-256=:<base64>:, md5=:<base64>:`
+        for name in ["Repr-Digest", "Content-Digest"] {
+            for member in header(name).unwrap_or_default().split(',') {
+                if let Some((algorithm, value)) = member.split_once('=') {
+                    let value = value
+                        .trim()
+                        .strip_prefix(':')
+                        .and_then(|v| v.strip_suffix(':'));
+                    if let Some(value) = value.and_then(utils::base64_decode) {
+                        digests.insert(algorithm.trim(), value);
+                    }
+                }
+            }
+        }
+        // instance digests(RFC 3230), like `SHA-256=<base64>,MD5=<base64>`
+        for member in header("Digest").unwrap_or_default().split(',') {
+            if let Some((algorithm, value)) = member.split_once('=') {
+                if let Some(value) = utils::base64_decode(value.trim()) {
+                    digests.insert(algorithm.trim(), value);
+                }
+            }
+        }
+        if let Some(value) = header("Content-MD5").and_then(|v| utils::base64_decode(v.trim())) {
+            digests.insert("md5", value);
+        }
+        return digests;
+    }
+
+    /// Returns the digest computed with the algorithm of the given name, compared
+    /// case-insensitively.
+    pub fn get(&self, algorithm: &str) -> Option<&[u8]> {
+        return self
+            .values
+            .iter()
+            .find(|(name, _)| name.eq_ignore_ascii_case(algorithm))
+            .map(|(_, value)| value.as_slice());
+    }
+
+    /// Returns the names of the algorithms and the digests computed with them.
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &[u8])> {
+        return self
+            .values
+            .iter()
+            .map(|(name, value)| (name.as_str(), value.as_slice()));
+    }
+
+    /// Returns whether there are no digests.
+    pub fn is_empty(&self) -> bool {
+        return self.values.is_empty();
+    }
+
+    /// Compares the digests computed with the algorithms both sets have in common.
+    ///
+    /// # Returns
+    ///
+    /// - `Option<bool>` - Whether all of them match, `None` if there is no algorithm in common.
+    pub fn matches(&self, other: &Digests) -> Option<bool> {
+        let mut compared = false;
+        for (name, value) in self.iter() {
+            if let Some(other_value) = other.get(name) {
+                if !utils::constant_time_eq(value, other_value) {
+                    return Some(false);
+                }
+                compared = true;
+            }
+        }
+        return compared.then_some(true);
+    }
+
+    /// Returns the digests base64 encoded, by the names of their algorithms.
+    pub fn to_base64(&self) -> Vec<(String, String)> {
+        return self
+            .iter()
+            .map(|(name, value)| (name.to_string(), utils::base64_encode(value)))
+            .collect();
+    }
+
+    /// Returns the `Repr-Digest`, `Digest` and(if an MD5 digest is included) `Content-MD5` headers
+    /// carrying the digests, empty if there are no digests.
+    pub fn headers(&self) -> Vec<(String, String)> {
+        if self.is_empty() {
+            return vec![];
+        }
+        let encoded = self.to_base64();
+        let repr_digest = encoded
+            .iter()
+            .map(|(name, value)| format!("{}=:{}:", name, value))
+            .collect::<Vec<_>>()
+            .join(", ");
+        let digest = encoded
+            .iter()
+            .map(|(name, value)| format!("{}={}", name.to_ascii_uppercase(), value))
+            .collect::<Vec<_>>()
+            .join(",");
+        let mut headers = vec![
+            ("Repr-Digest".to_string(), repr_digest),
+            ("Digest".to_string(), digest),
+        ];
+        if let Some((_, md5)) = encoded.iter().find(|(name, _)| name == "md5") {
+            headers.push(("Content-MD5".to_string(), md5.to_string()));
+        }
+        return headers;
+    }
+
+    // adds a digest, the first one of an algorithm wins
+    fn insert(&mut self, algorithm: &str, value: Vec<u8>) {
+        let algorithm = algorithm.to_ascii_lowercase();
+        if self.get(&algorithm).is_none() {
+            self.values.push((algorithm, value));
+        }
+    }
+}
+
+/// Computes the digests of a `DigestSet` over a message written to it in pieces.
+pub struct MultiHasher {
+    hashers: Vec<(String, Box<dyn DigestHasher>)>,
+}
+
+impl fmt::Debug for MultiHasher {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("MultiHasher")
+            .field(
+                "hashers",
+                &self
+                    .hashers
+                    .iter()
+                    .map(|(name, _)| name.as_str())
+                    .collect::<Vec<_>>(),
+            )
+            .finish()
+    }
+}
+
+impl MultiHasher {
+    /// Returns the digests of everything written to the hasher.
+    pub fn finish(self) -> Digests {
+        let mut digests = Digests::default();
+        for (name, hasher) in self.hashers {
+            digests.insert(&name, hasher.finish());
+        }
+        return digests;
+    }
+}
+
+impl Write for MultiHasher {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        for (_, hasher) in self.hashers.iter_mut() {
+            hasher.update(buf);
+        }
+        return Ok(buf.len());
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        return Ok(());
+    }
+}
+
+// the digests of a file, valid for as long as it's length and modification time don't change
+#[derive(Debug, Clone)]
+struct CachedFile {
+    length: u64,
+    modified: Option<SystemTime>,
+    digests: Digests,
+}
+
+/// The algorithms content digests are computed with, see `WebServer::content_digests`.
+///
+/// A new set is empty, the default set only contains `Sha256`. The digests of files are cached
+/// until their length or modification time changes, so a file is only read twice the first time
+/// it is sent.
+///
+/// # Examples
+///
+/// ```rust
+/// use browzer_web::digest::{DigestSet, Sha1, Sha256};
+///
+/// let digests = DigestSet::new().with(Sha256).with(Sha1);
+/// assert_eq!(digests.algorithms(), vec!["sha-256", "sha"]);
+/// ```
+// ----- DigestSet struct
+#[derive(Clone)]
+pub struct DigestSet {
+    algorithms: Vec<Arc<dyn DigestAlgorithm>>,
+    files: Arc<Mutex<HashMap<PathBuf, CachedFile>>>,
+}
+
+impl fmt::Debug for DigestSet {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("DigestSet")
+            .field("algorithms", &self.algorithms())
+            .finish()
+    }
+}
+
+// default implementation for DigestSet struct
+impl Default for DigestSet {
+    fn default() -> Self {
+        return DigestSet::new().with(Sha256);
+    }
+}
+
+impl DigestSet {
+    /// Creates a new, empty `DigestSet`.
+    pub fn new() -> DigestSet {
+        return DigestSet {
+            algorithms: vec![],
+            files: Arc::new(Mutex::new(HashMap::new())),
+        };
+    }
+
+    /// Adds an algorithm to the set, digests are listed in the order their algorithms were added.
+    pub fn with<A>(mut self, algorithm: A) -> DigestSet
+    where
+        A: DigestAlgorithm + 'static,
+    {
+        self.algorithms.push(Arc::new(algorithm));
+        return self;
+    }
+
+    /// Returns the names of the algorithms in the set.
+    pub fn algorithms(&self) -> Vec<&str> {
+        return self
+            .algorithms
+            .iter()
+            .map(|algorithm| algorithm.name())
+            .collect();
+    }
+
+    /// Returns a hasher computing the digests of all algorithms in the set at once.
+    pub fn hasher(&self) -> MultiHasher {
+        return MultiHasher {
+            hashers: self
+                .algorithms
+                .iter()
+                .map(|algorithm| (algorithm.name().to_ascii_lowercase(), algorithm.hasher()))
+                .collect(),
+        };
+    }
+
+    /// Computes the digests of a message.
+    pub fn digest(&self, data: &[u8]) -> Digests {
+        let mut hasher = self.hasher();
+        for (_, hasher) in hasher.hashers.iter_mut() {
+            hasher.update(data);
+        }
+        return hasher.finish();
+    }
+
+    /// Computes the digests of the contents of a file, reading it in pieces.
+    ///
+    /// # Errors
+    ///
+    /// Returns an I/O error if the file couldn't be read.
+    pub fn digest_file<P: AsRef<Path>>(&self, path: P) -> io::Result<Digests> {
+        let path = path.as_ref();
+        let mut file = fs::File::open(path)?;
+        let metadata = file.metadata()?;
+        let modified = metadata.modified().ok();
+        let files = match self.files.lock() {
+            Ok(files) => files,
+            Err(poisoned) => poisoned.into_inner(),
+        };
+        if let Some(cached) = files.get(path) {
+            if cached.length == metadata.len() && cached.modified == modified {
+                return Ok(cached.digests.clone());
+            }
+        }
+        // the lock isn't held while the file is read, so other files can be looked up meanwhile
+        drop(files);
+
+        let mut hasher = self.hasher();
+        let mut buffer = [0; 64 * 1024];
+        loop {
+            match file.read(&mut buffer)? {
+                0 => break,
+                read => hasher.write_all(&buffer[..read])?,
+            }
+        }
+        let digests = hasher.finish();
+
+        let mut files = match self.files.lock() {
+            Ok(files) => files,
+            Err(poisoned) => poisoned.into_inner(),
+        };
+        if files.len() >= MAX_CACHED_FILES {
+            files.clear();
+        }
+        files.insert(
+            path.to_path_buf(),
+            CachedFile {
+                length: metadata.len(),
+                modified,
+                digests: digests.clone(),
+            },
+        );
+        return Ok(digests);
+    }
+}
@@ -0,0 +1,123 @@
+//! This module reflects requests back to their client as JSON, showing how the server parsed them.
+//!
+//! Behind reverse proxies, load balancers and CDNs it's hard to tell what actually reaches the
+//! application: which headers a proxy added or stripped, which address the request seems to come
+//! from, how the path and query string were decoded. `DebugEcho` answers every request with a
+//! structured description of it, see `WebServer::debug_echo`.
+
+// internal crate imports
+use crate::{context, response, utils};
+
+// standard library imports
+use std::collections::BTreeMap;
+
+/// How many bytes of the request body are echoed by default.
+pub const DEFAULT_BODY_PREVIEW: usize = 1024;
+
+/// Answers requests with a JSON description of the parsed request, see `WebServer::debug_echo`.
+///
+/// The description has the following fields:
+///
+/// - `method`, `path` and `version` - The request line, the path with it's query string.
+/// - `params` - The decoded route parameters.
+/// - `query` - The decoded query parameters, with all values of repeated keys.
+/// - `headers` - The request headers, with their names as the client sent them.
+/// - `cookies` - The names and values of the request cookies.
+/// - `remote_addr` - The address of the connection(or the one from it's PROXY protocol header).
+/// - `client_ip` - The address of the client, taking trusted reverse proxies into account(see
+///   `Context::client_ip`).
+/// - `tls` - Whether the request arrived over TLS.
+/// - `body` - The length of the body and it's first bytes(lossily decoded as UTF-8), and whether
+///   the preview was truncated.
+///
+/// # Examples
+///
+/// ```rust
+/// use browzer_web::{context::Context, echo::DebugEcho, request::Request};
+///
+/// let mut request = Request::default();
+/// request.path = "/echo?tag=rust&tag=web".to_string();
+/// request.headers.insert("X-Forwarded-For".to_string(), "203.0.113.7".to_string());
+///
+/// let response = DebugEcho::new().handle(Context::new(request));
+/// let echo: serde_json::Value = serde_json::from_str(&response.body).unwrap();
+/// assert_eq!(echo["query"]["tag"], serde_json::json!(["rust", "web"]));
+/// assert_eq!(echo["headers"]["X-Forwarded-For"], "203.0.113.7");
+/// ```
+// ----- DebugEcho struct
+#[derive(Debug, Clone)]
+pub struct DebugEcho {
+    body_preview: usize,
+}
+
+// default implementation for DebugEcho struct
+impl Default for DebugEcho {
+    fn default() -> Self {
+        return DebugEcho::new();
+    }
+}
+
+impl DebugEcho {
+    /// Creates a new `DebugEcho` previewing the first `DEFAULT_BODY_PREVIEW` bytes of bodies.
+    pub fn new() -> DebugEcho {
+        return DebugEcho {
+            body_preview: DEFAULT_BODY_PREVIEW,
+        };
+    }
+
+    /// Sets how many bytes of the request body are echoed, `0` echoes only it's length.
+    pub fn body_preview(mut self, bytes: usize) -> DebugEcho {
+        self.body_preview = bytes;
+        return self;
+    }
+
+    /// Describes a request as JSON, see the fields in the `DebugEcho` documentation.
+    pub fn describe(&self, c: &context::Context) -> serde_json::Value {
+        let request = &c.request;
+
+        let mut query: BTreeMap<&str, Vec<&str>> = BTreeMap::new();
+        let query_string = c.query_string();
+        for (key, value) in query_string.iter() {
+            query.entry(key).or_default().push(value);
+        }
+        let headers: BTreeMap<&String, &String> = request.headers.iter().collect();
+        let cookies: BTreeMap<&String, &String> = request
+            .cookies
+            .iter()
+            .map(|(name, cookie)| (name, &cookie.value))
+            .collect();
+        let body = request.body_bytes();
+        let preview = &body[..body.len().min(self.body_preview)];
+
+        return serde_json::json!({
+            "method": request.method.to_string(),
+            "path": request.path,
+            "version": request.version,
+            "params": c.params.iter().collect::<BTreeMap<_, _>>(),
+            "query": query,
+            "headers": headers,
+            "cookies": cookies,
+            "remote_addr": c.remote_addr().map(|addr| addr.to_string()),
+            "client_ip": c.client_ip().map(|ip| ip.to_string()),
+            "tls": request.tls,
+            "body": {
+                "length": body.len(),
+                "preview": String::from_utf8_lossy(preview),
+                "truncated": preview.len() < body.len(),
+            },
+        });
+    }
+
+    /// Answers a request with it's JSON description.
+    pub fn handle(&self, mut c: context::Context) -> response::Response {
+        let body = serde_json::to_string_pretty(&self.describe(&c)).unwrap_or_default();
+        c.response
+            .headers
+            .insert("Content-Type".to_string(), "application/json".to_string());
+        // the echo differs for every request, so no cache may reuse it
+        c.response
+            .headers
+            .insert("Cache-Control".to_string(), "no-store".to_string());
+        return c.send_string(utils::HttpStatusCode::OK, &body);
+    }
+}
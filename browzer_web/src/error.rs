@@ -3,6 +3,9 @@
 // External crate imports
 use thiserror::Error;
 
+// internal crate imports
+use crate::utils;
+
 // Standard library imports
 use std::{
     io,
@@ -24,6 +27,10 @@ pub enum ThreadPoolError {
     /// Error when sending a message through the channel.
     #[error("Send error: {0}")]
     SendError(String),
+
+    /// Error when the queue of jobs is full, only returned by `ThreadPool::try_execute`.
+    #[error("Job queue is full")]
+    QueueFull,
 }
 
 /// Implement conversion from `PoisonError` to `ThreadPoolError::ReceiverLockError`.
@@ -43,6 +50,420 @@ pub enum RequestError {
     /// Error for an empty HTTP request.
     #[error("Empty HTTP request")]
     EmptyRequestError,
+
+    /// Error for a line which isn't terminated by CRLF, only raised in strict mode.
+    #[error("Invalid line ending: {0}")]
+    InvalidLineEndingError(String),
+
+    /// Error for a malformed header line, only raised in strict mode.
+    #[error("Malformed header: {0}")]
+    MalformedHeaderError(String),
+
+    /// Error for a request whose body length can't be determined unambiguously, like conflicting
+    /// or invalid `Content-Length` headers or an unsupported `Transfer-Encoding`.
+    #[error("Ambiguous body length: {0}")]
+    AmbiguousBodyLengthError(String),
+
+    /// Error for a request with more than one `Host` header.
+    #[error("Request has more than one Host header")]
+    DuplicateHostError,
+
+    /// Error for a request line longer than the limit(in bytes), see `limits::HeadLimits`.
+    #[error("Request line exceeds the limit of {0} bytes")]
+    RequestLineTooLongError(usize),
+
+    /// Error for a request with more headers than the limit, see `limits::HeadLimits`.
+    #[error("Request has more than {0} headers")]
+    TooManyHeadersError(usize),
+
+    /// Error for a request head larger than the limit(in bytes), see `limits::HeadLimits`.
+    #[error("Request head exceeds the limit of {0} bytes")]
+    HeadTooLargeError(usize),
+
+    /// Error for a request method which isn't one of `HttpMethod`, like `TRACE` or `CONNECT`.
+    #[error("Unsupported method: {0}")]
+    UnsupportedMethodError(String),
+
+    /// Error for an HTTP version other than `HTTP/1.0` and `HTTP/1.1`.
+    #[error("Unsupported HTTP version: {0}")]
+    UnsupportedVersionError(String),
+}
+
+impl RequestError {
+    /// Returns the status code of the response which a malformed request should be answered with.
+    ///
+    /// # Returns
+    ///
+    /// - `HttpStatusCode` - `431 Request Header Fields Too Large` for too many or too large
+    ///   headers, `501 Not Implemented` for unsupported methods, `505 HTTP Version Not Supported`
+    ///   for unsupported HTTP versions and `400 Bad Request` otherwise.
+    pub fn status_code(&self) -> utils::HttpStatusCode {
+        match self {
+            RequestError::TooManyHeadersError(_) | RequestError::HeadTooLargeError(_) => {
+                return utils::HttpStatusCode::RequestHeaderFieldsTooLarge;
+            }
+            RequestError::UnsupportedMethodError(_) => {
+                return utils::HttpStatusCode::NotImplemented;
+            }
+            RequestError::UnsupportedVersionError(_) => {
+                return utils::HttpStatusCode::HttpVersionNotSupported;
+            }
+            _ => return utils::HttpStatusCode::BadRequest,
+        }
+    }
+}
+
+/// Custom error type for `Context::bind_json`.
+#[derive(Debug, Error)]
+pub enum BindError {
+    /// Error when the request has no `Content-Type` header.
+    #[error("Missing Content-Type header, expected application/json")]
+    MissingContentType,
+
+    /// Error when the request body isn't JSON according to it's `Content-Type` header.
+    #[error("Unsupported Content-Type: {0}, expected application/json")]
+    UnsupportedContentType(String),
+
+    /// Error when the request has no body.
+    #[error("Missing request body")]
+    MissingBody,
+
+    /// Error when the request body isn't valid JSON or doesn't match the expected type.
+    #[error("Invalid JSON body: {0}")]
+    InvalidJson(#[from] serde_json::Error),
+}
+
+impl BindError {
+    /// Returns the status code of the response which a failed binding should be answered with.
+    ///
+    /// # Returns
+    ///
+    /// - `HttpStatusCode` - `415 Unsupported Media Type` for `Content-Type` errors, `400 Bad
+    ///   Request` otherwise.
+    pub fn status_code(&self) -> utils::HttpStatusCode {
+        match self {
+            BindError::MissingContentType | BindError::UnsupportedContentType(_) => {
+                return utils::HttpStatusCode::UnsupportedMediaType;
+            }
+            BindError::MissingBody | BindError::InvalidJson(_) => {
+                return utils::HttpStatusCode::BadRequest;
+            }
+        }
+    }
+}
+
+/// Custom error type for the typed query string access of `Context`, like `Context::query_parse`.
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum QueryError {
+    /// Error when a required query parameter is missing.
+    #[error("Missing query parameter: {0}")]
+    Missing(String),
+
+    /// Error when a query parameter can't be parsed into the expected type.
+    #[error("Invalid query parameter {0}: {1}")]
+    InvalidValue(String, String),
+
+    /// Error when the query string can't be deserialized into the expected type.
+    #[error("Invalid query string: {0}")]
+    InvalidQuery(String),
+}
+
+impl QueryError {
+    /// Returns the status code of the response which a failed query string access should be
+    /// answered with, always `400 Bad Request`.
+    pub fn status_code(&self) -> utils::HttpStatusCode {
+        return utils::HttpStatusCode::BadRequest;
+    }
+}
+
+/// Custom error type for the `multipart` module.
+#[derive(Debug, Error)]
+pub enum MultipartError {
+    /// Error when the request isn't a `multipart/form-data` request with a boundary.
+    #[error("Missing multipart/form-data boundary")]
+    MissingBoundary,
+
+    /// Error for a malformed multipart body.
+    #[error("Malformed multipart body: {0}")]
+    Malformed(String),
+
+    /// Error when a part which is kept in memory exceeds the size limit(in bytes).
+    #[error("Multipart part exceeds the limit of {0} bytes")]
+    PartTooLarge(usize),
+
+    /// I/O error while reading the body or writing to a sink.
+    #[error("I/O error: {0}")]
+    IO(#[from] io::Error),
+}
+
+impl MultipartError {
+    /// Returns the status code of the response which a failed multipart parsing should be
+    /// answered with.
+    ///
+    /// # Returns
+    ///
+    /// - `HttpStatusCode` - `413 Payload Too Large` for oversized parts, `500 Internal Server
+    ///   Error` for I/O errors(like a failing sink) and `400 Bad Request` otherwise.
+    pub fn status_code(&self) -> utils::HttpStatusCode {
+        match self {
+            MultipartError::MissingBoundary | MultipartError::Malformed(_) => {
+                return utils::HttpStatusCode::BadRequest;
+            }
+            MultipartError::PartTooLarge(_) => return utils::HttpStatusCode::PayloadTooLarge,
+            MultipartError::IO(_) => return utils::HttpStatusCode::InternalServerError,
+        }
+    }
+}
+
+/// Custom error type for human-friendly configuration values, like `10MB`, `30s` or
+/// `https://example.com`.
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum ConfigValueError {
+    /// Error for a value which isn't a valid byte size.
+    #[error("Invalid size: {0}")]
+    InvalidSize(String),
+
+    /// Error for a value which isn't a valid duration.
+    #[error("Invalid duration: {0}")]
+    InvalidDuration(String),
+
+    /// Error for a value which isn't a valid origin(scheme, host and optional port).
+    #[error("Invalid origin: {0}")]
+    InvalidOrigin(String),
+
+    /// Error for a value which isn't a valid IP address or address range(like `10.0.0.0/8`).
+    #[error("Invalid address: {0}")]
+    InvalidAddress(String),
+
+    /// Error for a value which isn't a supported URL, like an `https://` upstream of a
+    /// `ReverseProxy`.
+    #[error("Invalid URL: {0}")]
+    InvalidUrl(String),
+
+    /// Error for the name of a setting which doesn't exist, see `WebServer::configure`.
+    #[error("Unknown setting: {0}")]
+    UnknownSetting(String),
+}
+
+/// Custom error type for resumable uploads.
+#[derive(Debug, Error)]
+pub enum UploadError {
+    /// Error when no upload with the given id exists.
+    #[error("Upload not found")]
+    NotFound,
+
+    /// Error when the `Upload-Offset` of a request doesn't match the current offset of the
+    /// upload(carried by the variant).
+    #[error("Upload offset mismatch, the current offset is {0}")]
+    OffsetMismatch(u64),
+
+    /// Error when the uploaded data would exceed the announced length(or the size limit) of the
+    /// upload, in bytes.
+    #[error("Upload exceeds the limit of {0} bytes")]
+    TooLarge(u64),
+
+    /// I/O error of the upload storage.
+    #[error("I/O error: {0}")]
+    IO(#[from] io::Error),
+}
+
+impl UploadError {
+    /// Returns the status code of the response which a failed upload request should be answered
+    /// with.
+    ///
+    /// # Returns
+    ///
+    /// - `HttpStatusCode` - `404 Not Found` for unknown uploads, `409 Conflict` for offset
+    ///   mismatches, `413 Payload Too Large` for oversized uploads and `500 Internal Server Error`
+    ///   for I/O errors.
+    pub fn status_code(&self) -> utils::HttpStatusCode {
+        match self {
+            UploadError::NotFound => return utils::HttpStatusCode::NotFound,
+            UploadError::OffsetMismatch(_) => return utils::HttpStatusCode::Conflict,
+            UploadError::TooLarge(_) => return utils::HttpStatusCode::PayloadTooLarge,
+            UploadError::IO(_) => return utils::HttpStatusCode::InternalServerError,
+        }
+    }
+}
+
+/// Custom error type for resolving the file a static file request refers to.
+#[derive(Debug, Error)]
+pub enum StaticFileError {
+    /// Error when the requested path would resolve to a file outside the served directory, like
+    /// `../../etc/passwd`.
+    #[error("Requested path escapes the served directory")]
+    Forbidden,
+
+    /// Error when the requested file doesn't exist, or the requested path isn't a valid path.
+    #[error("Requested file not found")]
+    NotFound,
+}
+
+impl StaticFileError {
+    /// Returns the status code of the response which a failed static file request should be
+    /// answered with.
+    ///
+    /// # Returns
+    ///
+    /// - `HttpStatusCode` - `403 Forbidden` for paths escaping the served directory and `404 Not
+    ///   Found` otherwise.
+    pub fn status_code(&self) -> utils::HttpStatusCode {
+        match self {
+            StaticFileError::Forbidden => return utils::HttpStatusCode::Forbidden,
+            StaticFileError::NotFound => return utils::HttpStatusCode::NotFound,
+        }
+    }
+}
+
+/// Custom error type for the `Range` header of a request.
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum RangeError {
+    /// Error for a header which isn't a valid byte range, carrying the header value. Such headers
+    /// are ignored, and the full representation is sent.
+    #[error("Malformed range: {0}")]
+    Malformed(String),
+
+    /// Error for a header requesting something which isn't supported, like a unit other than
+    /// `bytes` or several ranges at once, the full representation is sent instead.
+    #[error("Unsupported range: {0}")]
+    Unsupported(String),
+
+    /// Error when the requested range lies completely outside of the representation, carrying
+    /// it's length in bytes.
+    #[error("Range not satisfiable for a length of {0} bytes")]
+    Unsatisfiable(u64),
+}
+
+/// Custom error type for slugs and short ids.
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum SlugError {
+    /// Error for an alphabet which can't be used for short ids, carrying the reason.
+    #[error("Invalid alphabet: {0}")]
+    InvalidAlphabet(String),
+
+    /// Error when no unused id was found within the given number of attempts.
+    #[error("No unused id found after {0} attempts")]
+    Exhausted(usize),
+}
+
+/// Custom error type for reading recorded traffic, see the `replay` module.
+#[derive(Debug, Error)]
+pub enum ReplayError {
+    /// Error for a line of a recording which isn't a valid record, carrying the line number and
+    /// the reason.
+    #[error("Invalid record on line {0}: {1}")]
+    InvalidRecord(usize, String),
+
+    /// I/O error while reading a recording.
+    #[error("I/O error: {0}")]
+    IO(#[from] io::Error),
+}
+
+/// Custom error type for time-based one-time passwords.
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum TotpError {
+    /// Error for a secret which isn't valid base32, or is empty.
+    #[error("Invalid TOTP secret: {0}")]
+    InvalidSecret(String),
+}
+
+/// Custom error type for JSON Web Tokens.
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum JwtError {
+    /// Error for a token which isn't made of three base64url encoded parts, whose header or
+    /// claims aren't JSON objects, or whose `exp` or `nbf` claim isn't a number.
+    #[error("Malformed token: {0}")]
+    Malformed(String),
+
+    /// Error for a token signed with another algorithm than the one of the key.
+    #[error("Unexpected token algorithm: {0}")]
+    UnexpectedAlgorithm(String),
+
+    /// Error for a token whose signature doesn't match.
+    #[error("Invalid token signature")]
+    InvalidSignature,
+
+    /// Error for a token whose `exp` claim lies in the past.
+    #[error("Token expired")]
+    Expired,
+
+    /// Error for a token whose `nbf` claim lies in the future.
+    #[error("Token not valid yet")]
+    NotYetValid,
+
+    /// Error for a token issued by someone else than the expected issuer.
+    #[error("Invalid token issuer")]
+    InvalidIssuer,
+
+    /// Error for a token issued for someone else than the expected audience.
+    #[error("Invalid token audience")]
+    InvalidAudience,
+
+    /// Error for a key which couldn't be parsed.
+    #[error("Invalid key: {0}")]
+    InvalidKey(String),
+}
+
+/// Custom error type for background tasks.
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum TaskError {
+    /// Error when a task panicked, carrying the panic message if it was a string.
+    #[error("Task panicked: {0}")]
+    Panicked(String),
+}
+
+/// Custom error type for the `proxy` module.
+#[derive(Debug, Error)]
+pub enum ProxyProtocolError {
+    /// Error when a connection doesn't start with a PROXY protocol header.
+    #[error("Missing PROXY protocol header")]
+    InvalidSignature,
+
+    /// Error for an invalid PROXY protocol header.
+    #[error("Malformed PROXY protocol header: {0}")]
+    Malformed(String),
+
+    /// I/O error while reading the header.
+    #[error("I/O error: {0}")]
+    IO(#[from] io::Error),
+}
+
+/// Custom error type for HTML templates.
+#[derive(Debug, Error)]
+pub enum TemplateError {
+    /// Error when there is no template with the given name.
+    #[error("Template not found: {0}")]
+    NotFound(String),
+
+    /// Error for a template which isn't valid, carrying it's name, the line of the error and the
+    /// reason.
+    #[error("Syntax error in {0} on line {1}: {2}")]
+    Syntax(String, usize, String),
+
+    /// Error when a template couldn't be rendered, carrying it's name and the reason.
+    #[error("Failed to render {0}: {1}")]
+    Render(String, String),
+
+    /// I/O error while reading a template file.
+    #[error("I/O error: {0}")]
+    IO(#[from] io::Error),
+}
+
+/// Custom error type for the commands sent to the control socket of a server, see the `upgrade`
+/// module.
+#[derive(Debug, Error)]
+pub enum ControlError {
+    /// Error when the server answered the command with an error, carrying the reason.
+    #[error("Command rejected: {0}")]
+    Rejected(String),
+
+    /// Error when the answer of the server couldn't be understood, carrying the answer.
+    #[error("Invalid answer: {0}")]
+    InvalidAnswer(String),
+
+    /// I/O error while talking to the control socket.
+    #[error("I/O error: {0}")]
+    IO(#[from] io::Error),
 }
 
 /// Custom error type for the `WebServer`.
@@ -63,6 +484,14 @@ pub enum WebServerError {
     /// Internal server error.
     #[error("Internal server error: {0}")]
     InternalServerError(String),
+
+    /// Error while setting up TLS, like an unreadable certificate or private key.
+    #[error("TLS error: {0}")]
+    TlsError(String),
+
+    /// Error when a connection doesn't start with a valid PROXY protocol header.
+    #[error("PROXY protocol error: {0}")]
+    ProxyProtocolError(ProxyProtocolError),
 }
 
 /// Implement conversion from `ParseIntError` to `WebServerError::IO`.
@@ -78,4 +507,13 @@ pub enum WebRouterError {
     /// Error while formatting a path
     #[error("Error while formatting a path: {0}")]
     PathFormatError(String),
+    /// A route which is being used by a request can't be changed
+    #[error("The route {0} is in use and can't be changed")]
+    RouteInUse(String),
+    /// Routes can only be registered through a `RouteRegistry` while the server is listening
+    #[error("The server isn't listening, register routes with the WebServer instead")]
+    NotListening,
+    /// Routes can only be registered with the `WebServer` itself before it's listening
+    #[error("The server is listening, register routes through it's RouteRegistry instead")]
+    Listening,
 }
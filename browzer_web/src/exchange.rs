@@ -0,0 +1,402 @@
+//! This module holds the steps every request goes through on a connection, from the moment it's
+//! head was parsed until it's response was written. They are shared by the worker threads of
+//! `WebServer::listen` and the tasks of `WebServer::listen_async`, which only differ in how they
+//! read the body of a request, run it's handler and write the response.
+
+// internal crate imports
+use crate::{
+    error, limits, metrics, overload, panics, request, response, router, sampling, stream, utils,
+    ConnectionSettings,
+};
+
+// standard library imports
+use std::{
+    any::Any,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+// how the router answered a request, as reported by the connection which ran it
+pub(crate) enum Handled {
+    Response(Result<response::Response, error::WebRouterError>),
+    // the request ran out of time before the router answered it
+    TimedOut,
+    // a middleware or handler panicked with the payload, `None` if the task running it was
+    // cancelled instead
+    Panicked(Option<Box<dyn Any + Send>>),
+}
+
+// the response to write to the connection, see `Exchange::respond`
+pub(crate) struct Reply {
+    // the head of the response, followed by it's body unless it's streamed
+    pub(crate) head: String,
+    pub(crate) stream: Option<stream::StreamBody>,
+    // whether a streamed body is sent in chunks, instead of being ended by closing the connection
+    pub(crate) chunked: bool,
+    pub(crate) keep_alive: bool,
+    pub(crate) result: Result<(), error::WebServerError>,
+}
+
+// a request being served on a connection, created once it's head was parsed
+pub(crate) struct Exchange<'a> {
+    router: &'a router::WebRouter,
+    settings: &'a ConnectionSettings,
+    request_id: Option<String>,
+    // the route the request resolved to, for the post-processing of the responses generated here
+    route: Option<Arc<router::Route>>,
+    metrics_label: Option<Arc<str>>,
+    method: utils::HttpMethod,
+    path: String,
+    version: String,
+    is_head: bool,
+    received_at: request::ReceivedAt,
+    keep_alive: bool,
+    // the options of the matched route, which override the server defaults
+    body_limit: Option<usize>,
+    content_length: usize,
+    // whether the body is passed to the handler while it arrives, see `RouteBuilder::stream_body`
+    streams_body: bool,
+    timeout: Option<Duration>,
+    memory_budget: Option<usize>,
+    memory_usage: limits::MemoryUsage,
+    // the phases of a sampled request, see `WebServer::sample_requests`
+    marks: Option<sampling::HandlerMarks>,
+    queued: Duration,
+    parsed_at: Option<Instant>,
+    handled_at: Option<Instant>,
+    serialized_at: Option<Instant>,
+    status: u16,
+}
+
+impl<'a> Exchange<'a> {
+    // answers a head which couldn't be parsed and asks for the connection to be closed, the
+    // incoming ID can't be trusted from such a head so the response gets a new one
+    pub(crate) fn head_error(
+        router: &router::WebRouter,
+        error: &error::RequestError,
+        reading_since: Instant,
+    ) -> response::Response {
+        let mut response = router.error_response(error.status_code(), "");
+        response
+            .headers
+            .insert("Connection".to_string(), "close".to_string());
+        let request_id = router.request_ids.as_ref().map(|ids| ids.generate());
+        router.post_process(None, request_id.as_deref(), &mut response);
+        router.record_metrics(
+            None,
+            metrics::UNKNOWN_METHOD,
+            response.status_code.code().1,
+            reading_since.elapsed(),
+        );
+        return response;
+    }
+
+    // starts serving a request whose head was parsed, `queued` is the time the connection waited
+    // to be served and only counts towards it's first request
+    pub(crate) fn begin(
+        router: &'a router::WebRouter,
+        settings: &'a ConnectionSettings,
+        request: &mut request::Request,
+        queued: Duration,
+    ) -> Exchange<'a> {
+        // the ID is assigned right away, so the responses generated here carry it as well
+        let request_id = router
+            .request_ids
+            .as_ref()
+            .map(|request_ids| request_ids.assign(request));
+
+        // the route is resolved once, it's options override the server defaults below and the
+        // router dispatches the request to it
+        let resolution = router.resolve(request);
+        let route = resolution.route.clone();
+        let metrics_label = resolution.label.clone();
+        request.resolution = Some(resolution);
+
+        // a sampled request is timed phase by phase
+        let marks = match router.sampler.as_ref().filter(|sampler| sampler.sample()) {
+            Some(_) => {
+                let marks = sampling::HandlerMarks::default();
+                request.handler_marks = Some(marks.clone());
+                Some(marks)
+            }
+            None => None,
+        };
+
+        let route_options = route.as_ref().map(|route| &route.options);
+        let body_limit = route_options
+            .and_then(|options| options.body_limit)
+            .or(settings.body_limit);
+        let timeout = route_options
+            .and_then(|options| options.timeout)
+            .or(settings.request_timeout);
+        let memory_budget = route_options
+            .and_then(|options| options.memory_budget)
+            .or(settings.memory_budget);
+        let content_length = request.content_length();
+        let streams_body =
+            content_length > 0 && route_options.is_some_and(|options| options.stream_body);
+        // a streamed body isn't held in memory
+        let mut memory_usage = limits::MemoryUsage::of_request(request);
+        if streams_body {
+            memory_usage.body = 0;
+        }
+        return Exchange {
+            router,
+            settings,
+            request_id,
+            route,
+            metrics_label,
+            method: request.method.clone(),
+            path: request.path.clone(),
+            version: request.version.clone(),
+            is_head: request.method == utils::HttpMethod::HEAD,
+            received_at: request.received_at,
+            // a stopping server closes the connection after the response
+            keep_alive: settings.keep_alive_timeout.is_some()
+                && request.is_keep_alive()
+                && !settings.shutdown.is_shutting_down(),
+            body_limit,
+            content_length,
+            streams_body,
+            timeout,
+            memory_budget,
+            memory_usage,
+            marks,
+            queued,
+            parsed_at: None,
+            handled_at: None,
+            serialized_at: None,
+            status: 0,
+        };
+    }
+
+    // checks whether the request may be served before it's body is read, returning the response
+    // turning it away otherwise. The body of a turned away request is never read, so the
+    // connection can't be reused afterwards
+    pub(crate) fn admit(&self) -> Option<response::Response> {
+        // a server in maintenance mode turns every request away
+        if self.settings.overload.maintenance.is_enabled() {
+            let mut response = self.reject(utils::HttpStatusCode::ServiceUnavailable);
+            self.settings.overload.signal(
+                &mut response,
+                overload::OverloadReason::Maintenance,
+                self.settings.max_connections,
+            );
+            return Some(response);
+        }
+        if let Some(body_limit) = self.body_limit {
+            if self.content_length > body_limit {
+                return Some(self.reject(utils::HttpStatusCode::PayloadTooLarge));
+            }
+        }
+        if let Some(memory_budget) = self.memory_budget {
+            if self.memory_usage.total() > memory_budget {
+                log_over_budget(&self.path, self.memory_usage, memory_budget);
+                return Some(self.reject(utils::HttpStatusCode::PayloadTooLarge));
+            }
+        }
+        return None;
+    }
+
+    // answers the request with an error response without handling it, and asks for the connection
+    // to be closed
+    pub(crate) fn reject(&self, status_code: utils::HttpStatusCode) -> response::Response {
+        let mut response = self.router.error_response(status_code, "");
+        self.post_process(&mut response);
+        response
+            .headers
+            .insert("Connection".to_string(), "close".to_string());
+        self.record_metrics(response.status_code.code().1);
+        return response;
+    }
+
+    // the time left for reading the body and handling the request, `None` without a timeout
+    pub(crate) fn remaining(&self) -> Option<Duration> {
+        return self
+            .timeout
+            .map(|timeout| timeout.saturating_sub(self.received_at.elapsed()));
+    }
+
+    // whether the body of the request is passed to it's handler while it arrives, instead of being
+    // read before the request is handled
+    pub(crate) fn streams_body(&self) -> bool {
+        return self.streams_body;
+    }
+
+    // marks the body of the request as read, or as handed to the handler if it's streamed
+    pub(crate) fn body_read(&mut self) {
+        self.parsed_at = Some(Instant::now());
+    }
+
+    // marks a streamed body as not read completely, what's left of it is still on the connection
+    // so the connection can't be reused
+    pub(crate) fn body_left_unread(&mut self) {
+        self.keep_alive = false;
+    }
+
+    // turns the outcome of handling the request into the response to write, counting it in the
+    // metrics
+    pub(crate) fn respond(&mut self, handled: Handled, panic_log: &panics::PanicLog) -> Reply {
+        self.handled_at = Some(Instant::now());
+        // the router post-processed the responses it generated already
+        let mut post_processed = true;
+        let (mut response, result) = match handled {
+            Handled::Response(Ok(response)) => (response, Ok(())),
+            Handled::TimedOut => {
+                let mut response = self
+                    .router
+                    .error_response(utils::HttpStatusCode::ServiceUnavailable, &self.path);
+                self.settings.overload.signal(
+                    &mut response,
+                    overload::OverloadReason::RequestTimeout,
+                    self.settings.max_connections,
+                );
+                post_processed = false;
+                (response, Ok(()))
+            }
+            Handled::Response(Err(e)) => {
+                post_processed = false;
+                (
+                    self.router
+                        .error_response(utils::HttpStatusCode::InternalServerError, &self.path),
+                    Err(error::WebServerError::InternalServerError(e.to_string())),
+                )
+            }
+            Handled::Panicked(payload) => {
+                // the state shared with other requests may be left half updated by the panic, so
+                // the connection isn't reused
+                if let Some(payload) = payload {
+                    panic_log.record(panics::PanicPhase::Handling, payload.as_ref());
+                }
+                self.keep_alive = false;
+                post_processed = false;
+                (
+                    self.router
+                        .error_response(utils::HttpStatusCode::InternalServerError, &self.path),
+                    Ok(()),
+                )
+            }
+        };
+        // a response pushing the request over it's memory budget is replaced, the request body
+        // was read completely so the connection can still be reused
+        if let Some(memory_budget) = self.memory_budget {
+            self.memory_usage.add_response(&response);
+            if self.memory_usage.total() > memory_budget {
+                log_over_budget(&self.path, self.memory_usage, memory_budget);
+                response = self
+                    .router
+                    .error_response(utils::HttpStatusCode::InternalServerError, &self.path);
+                post_processed = false;
+            }
+        }
+        if !post_processed {
+            self.post_process(&mut response);
+        }
+        self.status = response.status_code.code().1;
+        self.record_metrics(self.status);
+
+        // a handler can also ask for the connection to be closed by itself
+        match response.headers.get("Connection") {
+            Some(connection) if connection.eq_ignore_ascii_case("close") => self.keep_alive = false,
+            _ => {}
+        }
+        // the body of a stream of unknown length is ended by closing the connection for
+        // `HTTP/1.0` clients, see `Response::head_for_version`
+        let close_delimited = self.version == "HTTP/1.0"
+            && response
+                .stream
+                .as_ref()
+                .is_some_and(|stream| stream.length().is_none());
+        if result.is_err() || close_delimited {
+            self.keep_alive = false;
+        }
+        response.headers.insert(
+            "Connection".to_string(),
+            match self.keep_alive {
+                true => "keep-alive",
+                false => "close",
+            }
+            .to_string(),
+        );
+
+        // responses to `HEAD` requests carry the headers of the equivalent `GET` response, but
+        // never a body(neither do `204` and `304` responses), and the body of a streaming response
+        // is written by it's stream
+        let mut head = response.head_for_request(&self.version, self.is_head);
+        if !self.is_head && response.stream.is_none() && response.allows_body() {
+            head.push_str(&response.body);
+        }
+        let stream = match self.is_head || !response.allows_body() {
+            true => None,
+            false => response.stream.take(),
+        };
+        self.serialized_at = Some(Instant::now());
+        return Reply {
+            head,
+            stream,
+            chunked: !close_delimited,
+            keep_alive: self.keep_alive,
+            result,
+        };
+    }
+
+    // ends the request once it's response was written, passing it's profile to the sampler if the
+    // request was sampled
+    pub(crate) fn complete(self) {
+        self.settings.overload.request_completed();
+        let (sampler, marks) = match (self.router.sampler.as_ref(), self.marks) {
+            (Some(sampler), Some(marks)) => (sampler, marks),
+            _ => return,
+        };
+        let received_at = self.received_at.instant;
+        let parsed_at = self.parsed_at.unwrap_or(received_at);
+        let handled_at = self.handled_at.unwrap_or(parsed_at);
+        let serialized_at = self.serialized_at.unwrap_or(handled_at);
+        let handler = marks.elapsed();
+        sampler.record(&sampling::RequestProfile {
+            timestamp: self.received_at.system_time,
+            method: self.method,
+            path: self.path.split('?').next().unwrap_or_default().to_string(),
+            status: self.status,
+            queue: self.queued,
+            parse: parsed_at.saturating_duration_since(received_at),
+            middleware: handled_at
+                .saturating_duration_since(parsed_at)
+                .saturating_sub(handler),
+            handler,
+            serialize: serialized_at.saturating_duration_since(handled_at),
+            write: serialized_at.elapsed(),
+        });
+    }
+
+    // post-processes a response generated here instead of by the router, like the router does with
+    // the ones it generates(see `WebRouter::post_process`)
+    fn post_process(&self, response: &mut response::Response) {
+        self.router
+            .post_process(self.route.as_deref(), self.request_id.as_deref(), response);
+    }
+
+    // counts a response to the request in the metrics
+    fn record_metrics(&self, status: u16) {
+        self.router.record_metrics(
+            self.metrics_label.as_deref(),
+            self.method.as_str(),
+            status,
+            self.received_at.elapsed(),
+        );
+    }
+}
+
+// logs a request which exceeded it's memory budget, without the query string which may hold
+// sensitive data
+fn log_over_budget(path: &str, usage: limits::MemoryUsage, budget: usize) {
+    eprintln!(
+        "Request to {} exceeded it's memory budget of {} bytes: {} bytes(head: {}, body: {}, response: {})",
+        path.split('?').next().unwrap_or_default(),
+        budget,
+        usage.total(),
+        usage.head,
+        usage.body,
+        usage.response
+    );
+}
@@ -0,0 +1,202 @@
+//! This module determines the address of the client of a request which came through reverse
+//! proxies(like nginx or a cloud load balancer), from the `Forwarded` or `X-Forwarded-For` header
+//! they set, see `WebServer::trusted_proxies` and `Context::client_ip`.
+//!
+//! Clients can send these headers themselves, so they are only believed for the hops added by
+//! trusted proxies: the list of addresses is walked from the right(the hop closest to the
+//! server), and the first address which isn't a trusted proxy is the client.
+
+// internal crate imports
+use crate::{error, request};
+
+// standard library imports
+use std::net::{IpAddr, SocketAddr};
+
+// an address range, an address and the number of leading bits which have to match
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct AddressRange {
+    address: IpAddr,
+    prefix: u32,
+}
+
+impl AddressRange {
+    fn parse(value: &str) -> Option<AddressRange> {
+        let (address, prefix) = match value.trim().split_once('/') {
+            Some((address, prefix)) => (address, Some(prefix)),
+            None => (value.trim(), None),
+        };
+        let address: IpAddr = address.parse().ok()?;
+        let bits = match address {
+            IpAddr::V4(_) => 32,
+            IpAddr::V6(_) => 128,
+        };
+        let prefix = match prefix {
+            Some(prefix) => prefix
+                .parse::<u32>()
+                .ok()
+                .filter(|prefix| *prefix <= bits)?,
+            None => bits,
+        };
+        return Some(AddressRange { address, prefix });
+    }
+
+    fn contains(&self, ip: &IpAddr) -> bool {
+        match (self.address, normalize(*ip)) {
+            (IpAddr::V4(range), IpAddr::V4(ip)) => {
+                let mask = u32::MAX.checked_shl(32 - self.prefix).unwrap_or(0);
+                return u32::from(range) & mask == u32::from(ip) & mask;
+            }
+            (IpAddr::V6(range), IpAddr::V6(ip)) => {
+                let mask = u128::MAX.checked_shl(128 - self.prefix).unwrap_or(0);
+                return u128::from(range) & mask == u128::from(ip) & mask;
+            }
+            _ => return false,
+        }
+    }
+}
+
+/// The reverse proxies whose forwarding headers are trusted, see `WebServer::trusted_proxies`.
+///
+/// # Examples
+///
+/// ```rust
+/// use browzer_web::{forwarded::TrustedProxies, request::Request};
+///
+/// let proxies = TrustedProxies::parse(&["10.0.0.0/8", "::1"]).unwrap();
+///
+/// let mut request = Request::default();
+/// request.remote_addr = Some("10.0.0.2:52100".parse().unwrap());
+/// request.headers.insert(
+///     "X-Forwarded-For".to_string(),
+///     "198.51.100.1, 203.0.113.7, 10.0.0.1".to_string(),
+/// );
+///
+/// // the address before the trusted hops is the client, the ones before it can't be verified
+/// assert_eq!(proxies.client_ip(&request), Some("203.0.113.7".parse().unwrap()));
+///
+/// // headers of clients connecting directly are ignored
+/// request.remote_addr = Some("192.0.2.4:52100".parse().unwrap());
+/// assert_eq!(proxies.client_ip(&request), Some("192.0.2.4".parse().unwrap()));
+/// ```
+// ----- TrustedProxies struct
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TrustedProxies {
+    ranges: Vec<AddressRange>,
+}
+
+impl TrustedProxies {
+    /// Parses a list of trusted proxies.
+    ///
+    /// # Arguments
+    ///
+    /// - `proxies` - The addresses of the proxies, single addresses like `10.0.0.1` or `::1` and
+    ///   ranges in CIDR notation like `10.0.0.0/8` or `fd00::/8`.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `ConfigValueError::InvalidAddress` error for the first entry which is neither an
+    /// address nor a range.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use browzer_web::forwarded::TrustedProxies;
+    ///
+    /// assert!(TrustedProxies::parse(&["127.0.0.1", "172.16.0.0/12", "fd00::/8"]).is_ok());
+    /// assert!(TrustedProxies::parse(&["10.0.0.0/33"]).is_err());
+    /// assert!(TrustedProxies::parse(&["proxy.internal"]).is_err());
+    /// ```
+    pub fn parse(proxies: &[&str]) -> Result<TrustedProxies, error::ConfigValueError> {
+        let mut ranges = Vec::with_capacity(proxies.len());
+        for proxy in proxies {
+            match AddressRange::parse(proxy) {
+                Some(range) => ranges.push(range),
+                None => return Err(error::ConfigValueError::InvalidAddress(proxy.to_string())),
+            }
+        }
+        return Ok(TrustedProxies { ranges });
+    }
+
+    /// Returns whether an address belongs to a trusted proxy.
+    pub fn contains(&self, ip: &IpAddr) -> bool {
+        return self.ranges.iter().any(|range| range.contains(ip));
+    }
+
+    /// Determines the address of the client of a request.
+    ///
+    /// If the request came from a trusted proxy, the addresses of the `Forwarded` header(or the
+    /// `X-Forwarded-For` header, if there is no `Forwarded` header) are walked from the right,
+    /// skipping trusted proxies. The walk stops at the first address which isn't a trusted proxy,
+    /// or at a hop without a usable address(like `for=unknown`), in which case the last trusted
+    /// proxy is the best known address.
+    ///
+    /// # Arguments
+    ///
+    /// - `request` - The request.
+    ///
+    /// # Returns
+    ///
+    /// - `Option<IpAddr>` - The address of the client, `None` if the request wasn't read from a
+    ///   connection.
+    pub fn client_ip(&self, request: &request::Request) -> Option<IpAddr> {
+        let mut client = normalize(request.remote_addr?.ip());
+        let hops: Vec<Option<IpAddr>> = match request.header("Forwarded") {
+            Some(forwarded) => forwarded.split(',').map(forwarded_for).collect(),
+            None => match request.header("X-Forwarded-For") {
+                Some(forwarded_for) => forwarded_for.split(',').map(parse_node).collect(),
+                None => return Some(client),
+            },
+        };
+        for hop in hops.into_iter().rev() {
+            if !self.contains(&client) {
+                break;
+            }
+            match hop {
+                Some(ip) => client = normalize(ip),
+                None => break,
+            }
+        }
+        return Some(client);
+    }
+}
+
+// returns the `for` address of an element of a `Forwarded` header, like
+// `for="[2001:db8::1]:4711";proto=https`
+fn forwarded_for(element: &str) -> Option<IpAddr> {
+    let node = element.split(';').find_map(|pair| {
+        let (name, value) = pair.split_once('=')?;
+        match name.trim().eq_ignore_ascii_case("for") {
+            true => return Some(value),
+            false => return None,
+        }
+    })?;
+    return parse_node(node);
+}
+
+// parses a node of a forwarding header, an address with an optional port, IPv6 addresses with a
+// port in brackets
+fn parse_node(node: &str) -> Option<IpAddr> {
+    let node = node.trim().trim_matches('"');
+    if let Ok(ip) = node.parse::<IpAddr>() {
+        return Some(ip);
+    }
+    if let Ok(address) = node.parse::<SocketAddr>() {
+        return Some(address.ip());
+    }
+    // IPv6 addresses in brackets without a port
+    return node
+        .strip_prefix('[')
+        .and_then(|node| node.strip_suffix(']'))
+        .and_then(|node| node.parse().ok());
+}
+
+// IPv4 clients of a dual-stack socket show up as IPv4-mapped IPv6 addresses
+fn normalize(ip: IpAddr) -> IpAddr {
+    match ip {
+        IpAddr::V6(v6) => match v6.to_ipv4_mapped() {
+            Some(v4) => return IpAddr::V4(v4),
+            None => return ip,
+        },
+        IpAddr::V4(_) => return ip,
+    }
+}
@@ -0,0 +1,211 @@
+//! This module answers the health checks of load balancers and orchestrators, see
+//! `WebServer::health_check` and `WebServer::readiness_check`.
+//!
+//! A liveness check tells whether the process works at all, failing it gets the process restarted.
+//! A readiness check tells whether the process should get traffic right now, failing it takes the
+//! instance out of rotation until it passes again. Readiness checks therefore include the state of
+//! the server itself: an instance which is stopping or whose workers are all busy with
+//! connections waiting for them isn't ready, while it's still alive.
+//!
+//! Both answer with a JSON body listing the result of every check, with `200 OK` if all of them
+//! passed(or are only degraded) and `503 Service Unavailable` otherwise.
+
+// internal crate imports
+use crate::{context, response, upgrade, utils};
+
+// standard library imports
+use std::{fmt, sync::Arc};
+
+/// A boxed health check function, reporting the status of a part of the application.
+pub type CheckFn = Box<dyn Fn() -> HealthStatus + 'static + Send + Sync>;
+
+/// The result of a health check.
+///
+/// # Variants
+///
+/// - `Healthy` - The check passed.
+/// - `Degraded` - The check passed, but with a problem worth reporting(like a fallback in use).
+/// - `Unhealthy` - The check failed, with the reason.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum HealthStatus {
+    Healthy,
+    Degraded(String),
+    Unhealthy(String),
+}
+
+impl HealthStatus {
+    /// Returns whether the check passed, which a degraded check did.
+    pub fn is_healthy(&self) -> bool {
+        return !matches!(self, HealthStatus::Unhealthy(_));
+    }
+
+    /// Returns the name of the status in the JSON body.
+    pub fn name(&self) -> &'static str {
+        match self {
+            HealthStatus::Healthy => return "ok",
+            HealthStatus::Degraded(_) => return "degraded",
+            HealthStatus::Unhealthy(_) => return "unavailable",
+        }
+    }
+
+    // returns the reason of a degraded or failed check
+    fn detail(&self) -> Option<&str> {
+        match self {
+            HealthStatus::Healthy => return None,
+            HealthStatus::Degraded(detail) | HealthStatus::Unhealthy(detail) => {
+                return Some(detail)
+            }
+        }
+    }
+}
+
+// the parts of the server which tell whether it's ready for traffic
+#[derive(Debug, Clone)]
+struct ServerState {
+    queue: Arc<utils::thread_pool::JobQueue>,
+    shutdown: upgrade::ShutdownHandle,
+}
+
+/// A set of named health checks, answering requests with their results, see
+/// `WebServer::health_check`.
+///
+/// # Examples
+///
+/// ```rust
+/// use browzer_web::{context::Context, health::{HealthCheck, HealthStatus}, request::Request};
+///
+/// let health = HealthCheck::new()
+///     .check("database", || HealthStatus::Healthy)
+///     .check("cache", || HealthStatus::Degraded("using the fallback".to_string()));
+///
+/// let response = health.handle(Context::new(Request::default()));
+/// assert_eq!(response.status_code.code().1, 200);
+/// let body: serde_json::Value = serde_json::from_str(&response.body).unwrap();
+/// assert_eq!(body["status"], "degraded");
+/// assert_eq!(body["checks"]["cache"]["detail"], "using the fallback");
+/// ```
+// ----- HealthCheck struct
+#[derive(Default)]
+pub struct HealthCheck {
+    checks: Vec<(String, CheckFn)>,
+    server_checks: bool,
+    server: Option<ServerState>,
+}
+
+impl fmt::Debug for HealthCheck {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("HealthCheck")
+            .field(
+                "checks",
+                &self
+                    .checks
+                    .iter()
+                    .map(|(name, _)| name.as_str())
+                    .collect::<Vec<_>>(),
+            )
+            .field("server_checks", &self.server_checks)
+            .finish()
+    }
+}
+
+impl HealthCheck {
+    /// Creates a new `HealthCheck` without any checks, which always passes.
+    pub fn new() -> HealthCheck {
+        return HealthCheck::default();
+    }
+
+    /// Adds a named check, run for every request.
+    pub fn check<F>(mut self, name: &str, check: F) -> HealthCheck
+    where
+        F: Fn() -> HealthStatus + 'static + Send + Sync,
+    {
+        self.checks.push((name.to_string(), Box::new(check)));
+        return self;
+    }
+
+    /// Adds the checks of the server's readiness for traffic once the `HealthCheck` is served:
+    ///
+    /// - `shutdown` - Fails while the server is stopping(see `WebServer::shutdown_handle`).
+    /// - `workers` - Fails while every worker is busy and connections wait for one, or the queue
+    ///   of waiting connections is full(see `WebServer::queue_limit`).
+    pub fn server_checks(mut self) -> HealthCheck {
+        self.server_checks = true;
+        return self;
+    }
+
+    // reports the state of a server, if the server checks were asked for
+    pub(crate) fn attach(
+        &mut self,
+        queue: Arc<utils::thread_pool::JobQueue>,
+        shutdown: upgrade::ShutdownHandle,
+    ) {
+        if self.server_checks {
+            self.server = Some(ServerState { queue, shutdown });
+        }
+    }
+
+    /// Runs every check, returning their results by name.
+    pub fn run(&self) -> Vec<(String, HealthStatus)> {
+        let mut results = vec![];
+        if let Some(ref server) = self.server {
+            let shutdown = match server.shutdown.is_shutting_down() {
+                true => HealthStatus::Unhealthy("the server is stopping".to_string()),
+                false => HealthStatus::Healthy,
+            };
+            results.push(("shutdown".to_string(), shutdown));
+            let queue = &server.queue;
+            let workers = match queue.is_full() || queue.is_backlogged() {
+                true => HealthStatus::Unhealthy(format!(
+                    "{} connections are waiting for one of {} busy workers",
+                    queue.queued(),
+                    queue.workers()
+                )),
+                false => HealthStatus::Healthy,
+            };
+            results.push(("workers".to_string(), workers));
+        }
+        for (name, check) in self.checks.iter() {
+            results.push((name.clone(), (check)()));
+        }
+        return results;
+    }
+
+    /// Answers a request with the results of the checks as JSON, with `503 Service Unavailable`
+    /// if any of them failed.
+    pub fn handle(&self, mut c: context::Context) -> response::Response {
+        let results = self.run();
+        let status = match results.iter().find(|(_, status)| !status.is_healthy()) {
+            Some((_, status)) => status.name(),
+            None => match results
+                .iter()
+                .any(|(_, status)| matches!(status, HealthStatus::Degraded(_)))
+            {
+                true => "degraded",
+                false => "ok",
+            },
+        };
+        let mut checks = serde_json::Map::new();
+        for (name, result) in results.iter() {
+            let mut check = serde_json::Map::new();
+            check.insert("status".to_string(), result.name().into());
+            if let Some(detail) = result.detail() {
+                check.insert("detail".to_string(), detail.into());
+            }
+            checks.insert(name.clone(), check.into());
+        }
+        let body = serde_json::json!({ "status": status, "checks": checks });
+
+        c.response
+            .headers
+            .insert("Content-Type".to_string(), "application/json".to_string());
+        // load balancers have to see the current state, not a cached one
+        c.response
+            .headers
+            .insert("Cache-Control".to_string(), "no-store".to_string());
+        let status_code = match results.iter().all(|(_, status)| status.is_healthy()) {
+            true => utils::HttpStatusCode::OK,
+            false => utils::HttpStatusCode::ServiceUnavailable,
+        };
+        return c.send_string(status_code, &body.to_string());
+    }
+}
@@ -0,0 +1,450 @@
+//! This module implements the HTTP/1.1 client used to talk to other servers, shared by
+//! `reverse_proxy::ReverseProxy` and `webhooks::HttpTransport`.
+//!
+//! A `Client` opens connections to the servers of `http://` and, with the `tls` feature,
+//! `https://` URLs, and keeps idle connections per origin so they can be reused by later requests.
+//! Without the `tls` feature `https://` URLs are refused, so nothing meant for a TLS server is ever
+//! sent in plaintext. The certificates of HTTPS servers are verified against the Mozilla root
+//! certificates by default, see `Client::tls_config` to trust others.
+//!
+//! URLs are validated by `Url::parse` before anything is sent, a URL containing whitespace or
+//! control characters(like a line break smuggling a header into the request) is rejected.
+
+// internal crate imports
+use crate::error;
+
+// standard library imports
+use std::{
+    collections::HashMap,
+    fmt,
+    io::{self, Read, Write},
+    net::{TcpStream, ToSocketAddrs},
+    sync::{Arc, Mutex, MutexGuard},
+    time::{Duration, Instant},
+};
+
+/// How long connecting to a server, sending a request and every read of the response may take by
+/// default.
+pub const DEFAULT_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// How many idle connections to a single origin are kept open by default.
+pub const DEFAULT_MAX_IDLE: usize = 16;
+
+/// How long an idle connection is reused by default, shorter than the keep-alive timeout of most
+/// servers(5 seconds for a `WebServer`) so the server doesn't close it first.
+pub const DEFAULT_IDLE_TIMEOUT: Duration = Duration::from_secs(4);
+
+/// A validated `http://` or `https://` URL a `Client` sends requests to.
+///
+/// # Examples
+///
+/// ```rust
+/// use browzer_web::http_client::Url;
+///
+/// let url = Url::parse("http://[::1]:9000/hooks?source=billing").unwrap();
+/// assert_eq!(url.authority(), "[::1]:9000");
+/// assert_eq!(url.host(), "::1");
+/// assert_eq!(url.port(), 9000);
+/// assert_eq!(url.target(), "/hooks?source=billing");
+/// assert_eq!(Url::parse("http://example.com").unwrap().target(), "/");
+///
+/// // a line break would end the request line and start a header of the URL's choosing
+/// assert!(Url::parse("http://example.com/\r\nX-Injected: 1").is_err());
+/// assert!(Url::parse("ftp://example.com").is_err());
+/// assert!(Url::parse("http://user@example.com").is_err());
+/// ```
+// ----- Url struct
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Url {
+    tls: bool,
+    authority: String,
+    host: String,
+    port: u16,
+    path: String,
+}
+
+impl Url {
+    /// Parses and validates a URL.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `InvalidUrl` error if the URL isn't an `http://` or `https://` URL with a host
+    /// name(and optionally a port, a path and a query string) made of visible ASCII characters,
+    /// or if it has user information or a fragment. `https://` URLs are only valid with the `tls`
+    /// feature.
+    pub fn parse(url: &str) -> Result<Url, error::ConfigValueError> {
+        let invalid = || error::ConfigValueError::InvalidUrl(url.to_string());
+        // only visible characters, so the URL can't carry whitespace or line breaks into the head
+        // of a request
+        if !url.bytes().all(|byte| byte.is_ascii_graphic()) || url.contains('#') {
+            return Err(invalid());
+        }
+        let (tls, rest) = match (url.strip_prefix("http://"), url.strip_prefix("https://")) {
+            (Some(rest), _) => (false, rest),
+            (None, Some(rest)) => (true, rest),
+            (None, None) => return Err(invalid()),
+        };
+        if tls && cfg!(not(feature = "tls")) {
+            return Err(invalid());
+        }
+        let (authority, path) = match rest.find(['/', '?']) {
+            Some(index) => (&rest[..index], &rest[index..]),
+            None => (rest, ""),
+        };
+        if authority.contains('@') {
+            return Err(invalid());
+        }
+        // IPv6 addresses are written in brackets, which end the authority unless a port follows
+        let (host, port) = match authority.rsplit_once(':') {
+            Some((host, port)) if !authority.ends_with(']') => {
+                (host, port.parse::<u16>().map_err(|_| invalid())?)
+            }
+            _ => match tls {
+                true => (authority, 443),
+                false => (authority, 80),
+            },
+        };
+        let host = host.trim_start_matches('[').trim_end_matches(']');
+        if host.is_empty() {
+            return Err(invalid());
+        }
+        return Ok(Url {
+            tls,
+            authority: authority.to_string(),
+            host: host.to_string(),
+            port,
+            path: path.to_string(),
+        });
+    }
+
+    /// Returns whether the URL is an `https://` URL.
+    pub fn is_tls(&self) -> bool {
+        return self.tls;
+    }
+
+    /// Returns the authority of the URL(the host and the port, as written), which is what the
+    /// `Host` header of a request to it is set to.
+    pub fn authority(&self) -> &str {
+        return &self.authority;
+    }
+
+    /// Returns the host name or IP address of the URL, without the brackets of IPv6 addresses.
+    pub fn host(&self) -> &str {
+        return &self.host;
+    }
+
+    /// Returns the port of the URL, `80` or `443` if it doesn't name one.
+    pub fn port(&self) -> u16 {
+        return self.port;
+    }
+
+    /// Returns the path and query string of the URL as written, which may be empty.
+    pub fn path(&self) -> &str {
+        return &self.path;
+    }
+
+    /// Returns the request target of a request to the URL, it's path and query string with an
+    /// empty path standing for `/`.
+    pub fn target(&self) -> String {
+        match self.path.starts_with('/') {
+            true => return self.path.clone(),
+            false => return format!("/{}", self.path),
+        }
+    }
+
+    // the scheme and authority of the URL, idle connections are kept per origin
+    fn origin(&self) -> String {
+        return format!("{}://{}", self.scheme(), self.authority);
+    }
+
+    fn scheme(&self) -> &'static str {
+        match self.tls {
+            true => return "https",
+            false => return "http",
+        }
+    }
+}
+
+impl fmt::Display for Url {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}://{}{}", self.scheme(), self.authority, self.path)
+    }
+}
+
+/// A connection a `Client` opened, over plain TCP or TLS.
+///
+/// # Variants
+///
+/// - `Plain` - A plain TCP connection, to the server of an `http://` URL.
+/// - `Tls` - A TLS connection, to the server of an `https://` URL(only with the `tls` feature).
+pub enum Connection {
+    Plain(TcpStream),
+    #[cfg(feature = "tls")]
+    Tls(Box<rustls::StreamOwned<rustls::ClientConnection, TcpStream>>),
+}
+
+impl fmt::Debug for Connection {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Connection::Plain(stream) => f.debug_tuple("Plain").field(stream).finish(),
+            #[cfg(feature = "tls")]
+            Connection::Tls(stream) => f.debug_tuple("Tls").field(&stream.sock).finish(),
+        }
+    }
+}
+
+impl Connection {
+    /// Returns the TCP connection underneath, like to set it's timeouts.
+    pub fn tcp(&self) -> &TcpStream {
+        match self {
+            Connection::Plain(stream) => return stream,
+            #[cfg(feature = "tls")]
+            Connection::Tls(stream) => return &stream.sock,
+        }
+    }
+
+    // whether an idle connection is still open, a server closing it makes it readable
+    fn is_open(&self) -> bool {
+        let stream = self.tcp();
+        if stream.set_nonblocking(true).is_err() {
+            return false;
+        }
+        let open =
+            matches!(stream.peek(&mut [0]), Err(ref e) if e.kind() == io::ErrorKind::WouldBlock);
+        return open && stream.set_nonblocking(false).is_ok();
+    }
+}
+
+impl Read for Connection {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match self {
+            Connection::Plain(stream) => return stream.read(buf),
+            #[cfg(feature = "tls")]
+            Connection::Tls(stream) => return stream.read(buf),
+        }
+    }
+}
+
+impl Write for Connection {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self {
+            Connection::Plain(stream) => return stream.write(buf),
+            #[cfg(feature = "tls")]
+            Connection::Tls(stream) => return stream.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self {
+            Connection::Plain(stream) => return stream.flush(),
+            #[cfg(feature = "tls")]
+            Connection::Tls(stream) => return stream.flush(),
+        }
+    }
+}
+
+// the idle connections of a client by origin, kept open for the next requests
+#[derive(Debug)]
+struct ConnectionPool {
+    idle: Mutex<HashMap<String, Vec<(Connection, Instant)>>>,
+    max_idle: usize,
+    idle_timeout: Duration,
+}
+
+impl ConnectionPool {
+    fn new(max_idle: usize, idle_timeout: Duration) -> ConnectionPool {
+        return ConnectionPool {
+            idle: Mutex::new(HashMap::new()),
+            max_idle,
+            idle_timeout,
+        };
+    }
+}
+
+/// An HTTP/1.1 client, which opens connections and keeps the idle ones for reuse.
+///
+/// Clones share their idle connections, so a single client can serve a whole application.
+///
+/// # Examples
+///
+/// ```rust
+/// use browzer_web::http_client::Client;
+/// use std::time::Duration;
+///
+/// let client = Client::new()
+///     .timeout(Duration::from_secs(5))
+///     .max_idle(64);
+/// ```
+// ----- Client struct
+#[derive(Clone)]
+pub struct Client {
+    timeout: Duration,
+    pool: Arc<ConnectionPool>,
+    #[cfg(feature = "tls")]
+    tls_config: Option<Arc<rustls::ClientConfig>>,
+}
+
+impl fmt::Debug for Client {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Client")
+            .field("timeout", &self.timeout)
+            .field("max_idle", &self.pool.max_idle)
+            .field("idle_timeout", &self.pool.idle_timeout)
+            .finish()
+    }
+}
+
+// default implementation for Client struct
+impl Default for Client {
+    fn default() -> Self {
+        return Client {
+            timeout: DEFAULT_TIMEOUT,
+            pool: Arc::new(ConnectionPool::new(DEFAULT_MAX_IDLE, DEFAULT_IDLE_TIMEOUT)),
+            #[cfg(feature = "tls")]
+            tls_config: None,
+        };
+    }
+}
+
+impl Client {
+    /// Creates a new `Client` with the default timeout and pool settings.
+    pub fn new() -> Client {
+        return Client::default();
+    }
+
+    /// Sets how long connecting to a server, sending a request and every read of the response may
+    /// take, `DEFAULT_TIMEOUT` by default.
+    pub fn timeout(mut self, timeout: Duration) -> Client {
+        self.timeout = timeout;
+        return self;
+    }
+
+    /// Sets how many idle connections per origin are kept open, `DEFAULT_MAX_IDLE` by default.
+    /// Zero opens a new connection for every request.
+    pub fn max_idle(mut self, max_idle: usize) -> Client {
+        self.pool = Arc::new(ConnectionPool::new(max_idle, self.pool.idle_timeout));
+        return self;
+    }
+
+    /// Sets how long an idle connection is reused, `DEFAULT_IDLE_TIMEOUT` by default. Keep it
+    /// shorter than the keep-alive timeout of the servers.
+    pub fn idle_timeout(mut self, idle_timeout: Duration) -> Client {
+        self.pool = Arc::new(ConnectionPool::new(self.pool.max_idle, idle_timeout));
+        return self;
+    }
+
+    /// Sets the `rustls` configuration connections to `https://` URLs are made with, like one
+    /// trusting a private certificate authority. By default the certificates of servers are
+    /// verified against the Mozilla root certificates. Only available with the `tls` feature.
+    #[cfg(feature = "tls")]
+    pub fn tls_config(mut self, config: Arc<rustls::ClientConfig>) -> Client {
+        self.tls_config = Some(config);
+        return self;
+    }
+
+    /// Opens a new connection to the server of a URL, trying every address it's host name
+    /// resolves to. The read and write timeouts of the connection are set to the timeout of the
+    /// client.
+    ///
+    /// # Errors
+    ///
+    /// Returns an I/O error if the host name couldn't be resolved or no address accepted the
+    /// connection.
+    pub fn connect(&self, url: &Url) -> io::Result<Connection> {
+        let mut last_error = io::Error::new(
+            io::ErrorKind::NotFound,
+            format!("{} didn't resolve to any address", url.authority),
+        );
+        let mut connected = None;
+        for address in (url.host.as_str(), url.port).to_socket_addrs()? {
+            match TcpStream::connect_timeout(&address, self.timeout) {
+                Ok(stream) => {
+                    connected = Some(stream);
+                    break;
+                }
+                Err(e) => last_error = e,
+            }
+        }
+        let stream = match connected {
+            Some(stream) => stream,
+            None => return Err(last_error),
+        };
+        stream.set_nodelay(true)?;
+        stream.set_read_timeout(Some(self.timeout))?;
+        stream.set_write_timeout(Some(self.timeout))?;
+        if !url.tls {
+            return Ok(Connection::Plain(stream));
+        }
+        #[cfg(feature = "tls")]
+        {
+            let name = rustls::pki_types::ServerName::try_from(url.host.clone()).map_err(|e| {
+                return io::Error::new(io::ErrorKind::InvalidInput, e.to_string());
+            })?;
+            let config = match self.tls_config {
+                Some(ref config) => Arc::clone(config),
+                None => default_tls_config(),
+            };
+            let connection =
+                rustls::ClientConnection::new(config, name).map_err(io::Error::other)?;
+            return Ok(Connection::Tls(Box::new(rustls::StreamOwned::new(
+                connection, stream,
+            ))));
+        }
+        #[cfg(not(feature = "tls"))]
+        return Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            format!("{} requires the tls feature", url),
+        ));
+    }
+
+    /// Takes the most recently used idle connection to the server of a URL, which is the least
+    /// likely to have been closed by the server, skipping the ones it did close already.
+    pub fn take_idle(&self, url: &Url) -> Option<Connection> {
+        let mut idle = lock(&self.pool.idle);
+        let connections = idle.get_mut(&url.origin())?;
+        let idle_timeout = self.pool.idle_timeout;
+        connections.retain(|(_, since)| since.elapsed() < idle_timeout);
+        while let Some((connection, _)) = connections.pop() {
+            if connection.is_open() {
+                return Some(connection);
+            }
+        }
+        return None;
+    }
+
+    /// Keeps a connection to the server of a URL for the next request to it. Only hand back
+    /// connections whose last response was read completely.
+    pub fn put_idle(&self, url: &Url, connection: Connection) {
+        let mut idle = lock(&self.pool.idle);
+        let connections = idle.entry(url.origin()).or_default();
+        if connections.len() < self.pool.max_idle {
+            connections.push((connection, Instant::now()));
+        }
+    }
+}
+
+// the configuration of TLS connections verifying servers against the Mozilla root certificates,
+// built once since loading the roots isn't free
+#[cfg(feature = "tls")]
+fn default_tls_config() -> Arc<rustls::ClientConfig> {
+    static CONFIG: std::sync::OnceLock<Arc<rustls::ClientConfig>> = std::sync::OnceLock::new();
+    return Arc::clone(CONFIG.get_or_init(|| {
+        let roots = rustls::RootCertStore {
+            roots: webpki_roots::TLS_SERVER_ROOTS.to_vec(),
+        };
+        let config = rustls::ClientConfig::builder_with_provider(Arc::new(
+            rustls::crypto::ring::default_provider(),
+        ))
+        .with_safe_default_protocol_versions()
+        .expect("the ring provider supports the default protocol versions")
+        .with_root_certificates(roots)
+        .with_no_client_auth();
+        return Arc::new(config);
+    }));
+}
+
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    return mutex
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner());
+}
@@ -0,0 +1,392 @@
+//! This module provides `Idempotency-Key` support for non-idempotent routes.
+//!
+//! Clients retrying a `POST` (or `PATCH`) request after a network failure can't know whether the
+//! first attempt reached the server. By sending the same `Idempotency-Key` header with every
+//! attempt, the first response is stored and replayed for the retries instead of running the
+//! route handler (and it's side effects, like charging a card) again.
+//!
+//! Keys are scoped to the caller, so a client reusing(or guessing) the key of another one never
+//! gets the other client's response replayed.
+
+// internal crate imports
+use crate::{context, response, utils};
+
+// external crate imports
+use sha2::{Digest, Sha256};
+
+// standard library imports
+use std::{
+    collections::HashMap,
+    fmt,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+/// A boxed closure which extracts the caller a request is scoped to, see `Idempotency::scope_by`.
+pub type ScopeExtractor = Box<dyn Fn(&context::Context) -> Option<String> + 'static + Send + Sync>;
+
+/// The state stored for an idempotency key.
+///
+/// # Variants
+///
+/// - `InFlight` - The first request with this key is still being handled.
+/// - `Completed` - The first request with this key finished and it's response was stored.
+///
+/// Both variants carry a `fingerprint` of the request which used the key first, so that the same
+/// key can't be reused for a different payload.
+#[derive(Debug, Clone)]
+pub enum IdempotencyRecord {
+    InFlight {
+        fingerprint: String,
+    },
+    Completed {
+        fingerprint: String,
+        response: response::Response,
+    },
+}
+
+/// A storage backend for idempotency records.
+///
+/// Implement this trait to keep idempotency records somewhere other than the memory of the current
+/// process, like a database shared by multiple server instances.
+pub trait IdempotencyStore: Send + Sync {
+    /// Returns the record stored for `key`, or `None` if there is no record or it has expired.
+    fn get(&self, key: &str) -> Option<IdempotencyRecord>;
+
+    /// Stores `record` for `key` only if there is no live record for `key` yet.
+    ///
+    /// This must be atomic, as it is what prevents two concurrent requests with the same key from
+    /// both running the route handler.
+    ///
+    /// # Returns
+    ///
+    /// - `bool` - `true` if the record was stored, `false` if a live record already existed.
+    fn insert_if_absent(&self, key: &str, record: IdempotencyRecord, ttl: Duration) -> bool;
+
+    /// Stores `record` for `key`, replacing any existing record.
+    fn set(&self, key: &str, record: IdempotencyRecord, ttl: Duration);
+
+    /// Removes the record stored for `key`.
+    fn remove(&self, key: &str);
+}
+
+/// An in-memory `IdempotencyStore` implementation.
+///
+/// Expired records are purged lazily whenever a new record is inserted.
+///
+/// # Examples
+///
+/// ```rust
+/// use browzer_web::idempotency::{IdempotencyRecord, IdempotencyStore, MemoryIdempotencyStore};
+/// use std::time::Duration;
+///
+/// let store = MemoryIdempotencyStore::new();
+/// let record = IdempotencyRecord::InFlight { fingerprint: "abc".to_string() };
+///
+/// assert!(store.insert_if_absent("key", record.clone(), Duration::from_secs(60)));
+/// assert!(!store.insert_if_absent("key", record, Duration::from_secs(60)));
+/// ```
+// ----- MemoryIdempotencyStore struct
+#[derive(Debug, Default)]
+pub struct MemoryIdempotencyStore {
+    records: Mutex<HashMap<String, (IdempotencyRecord, Instant)>>,
+}
+
+impl MemoryIdempotencyStore {
+    /// Creates a new, empty `MemoryIdempotencyStore`.
+    pub fn new() -> MemoryIdempotencyStore {
+        return MemoryIdempotencyStore::default();
+    }
+}
+
+impl IdempotencyStore for MemoryIdempotencyStore {
+    fn get(&self, key: &str) -> Option<IdempotencyRecord> {
+        let records = match self.records.lock() {
+            Ok(records) => records,
+            Err(poisoned) => poisoned.into_inner(),
+        };
+        match records.get(key) {
+            Some((record, expires_at)) if *expires_at > Instant::now() => {
+                return Some(record.clone());
+            }
+            _ => return None,
+        }
+    }
+
+    fn insert_if_absent(&self, key: &str, record: IdempotencyRecord, ttl: Duration) -> bool {
+        let mut records = match self.records.lock() {
+            Ok(records) => records,
+            Err(poisoned) => poisoned.into_inner(),
+        };
+        let now = Instant::now();
+        records.retain(|_, (_, expires_at)| *expires_at > now);
+        if records.contains_key(key) {
+            return false;
+        }
+        records.insert(key.to_string(), (record, now + ttl));
+        return true;
+    }
+
+    fn set(&self, key: &str, record: IdempotencyRecord, ttl: Duration) {
+        let mut records = match self.records.lock() {
+            Ok(records) => records,
+            Err(poisoned) => poisoned.into_inner(),
+        };
+        records.insert(key.to_string(), (record, Instant::now() + ttl));
+    }
+
+    fn remove(&self, key: &str) {
+        let mut records = match self.records.lock() {
+            Ok(records) => records,
+            Err(poisoned) => poisoned.into_inner(),
+        };
+        records.remove(key);
+    }
+}
+
+/// Middleware which makes route handlers safe to retry using the `Idempotency-Key` header.
+///
+/// `Idempotency` wraps a route handler. For `POST` and `PATCH` requests which carry an
+/// `Idempotency-Key` header, the first response generated for a key is stored for `ttl` and
+/// replayed (with an `Idempotent-Replayed: true` header) for every later request with the same key.
+/// Requests of other methods, or without the header, are passed to the handler untouched.
+///
+/// - A retry arriving while the first request is still being handled gets `409 Conflict`.
+/// - Reusing a key with a different request body gets `422 Unprocessable Entity`.
+/// - `5xx` responses are not stored, so the client can retry them with the same key.
+/// - Streamed responses(see `Response::stream`) are not stored either, since their body can only
+///   be written once. A retry runs the handler again.
+/// - A handler which panics leaves no record behind either.
+///
+/// Keys are scoped to the caller: by default the credentials in it's `Authorization` header(as a
+/// hash), or it's IP address for requests without one. Apps authenticating their callers some
+/// other way(like with sessions) should scope the keys with `Idempotency::scope_by`.
+///
+/// # Fields
+///
+/// - `store` - The `IdempotencyStore` in which records are kept.
+/// - `ttl` - A `Duration` for which a stored response is replayed.
+/// - `scope` - An optional closure extracting the caller a request is scoped to.
+///
+/// # Examples
+///
+/// ```rust,no_run
+/// use browzer_web::{idempotency::{Idempotency, MemoryIdempotencyStore}, WebServer};
+/// use std::time::Duration;
+///
+/// let mut server = WebServer::new("127.0.0.1:8080".to_string(), 4);
+/// let idempotency = Idempotency::new(MemoryIdempotencyStore::new(), Duration::from_secs(24 * 60 * 60));
+///
+/// server.post("/payments", idempotency.wrap(|mut c| {
+///     // charge the card exactly once
+///     return c.send_string(browzer_web::utils::HttpStatusCode::Created, "Payment created!");
+/// }));
+/// ```
+// ----- Idempotency struct
+#[derive(Clone)]
+pub struct Idempotency {
+    store: Arc<dyn IdempotencyStore>,
+    ttl: Duration,
+    scope: Option<Arc<ScopeExtractor>>,
+}
+
+impl fmt::Debug for Idempotency {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Idempotency")
+            .field("store", &"Arc<dyn IdempotencyStore>")
+            .field("ttl", &self.ttl)
+            .field(
+                "scope",
+                &self.scope.as_ref().map(|_| {
+                    "Box<dyn Fn(&context::Context) -> Option<String> + 'static + Send + Sync>"
+                }),
+            )
+            .finish()
+    }
+}
+
+impl Idempotency {
+    /// Creates a new `Idempotency` middleware backed by the given store.
+    ///
+    /// # Arguments
+    ///
+    /// - `store` - The `IdempotencyStore` in which records are kept.
+    /// - `ttl` - A `Duration` for which a stored response is replayed.
+    pub fn new<S>(store: S, ttl: Duration) -> Idempotency
+    where
+        S: IdempotencyStore + 'static,
+    {
+        return Idempotency {
+            store: Arc::new(store),
+            ttl,
+            scope: None,
+        };
+    }
+
+    /// Scopes the idempotency keys to the caller the closure extracts from a request, like the id
+    /// of the logged in user, instead of it's credentials or IP address. Requests the closure
+    /// returns `None` for are passed to the handler without storing or replaying anything.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use browzer_web::idempotency::{Idempotency, MemoryIdempotencyStore};
+    /// use std::time::Duration;
+    ///
+    /// let idempotency = Idempotency::new(MemoryIdempotencyStore::new(), Duration::from_secs(60))
+    ///     .scope_by(|c| c.session().get("user"));
+    /// ```
+    pub fn scope_by<F>(mut self, scope: F) -> Idempotency
+    where
+        F: Fn(&context::Context) -> Option<String> + 'static + Send + Sync,
+    {
+        self.scope = Some(Arc::new(Box::new(scope)));
+        return self;
+    }
+
+    /// Wraps a route handler with idempotency key handling.
+    ///
+    /// # Arguments
+    ///
+    /// - `handler` - The route handler to protect.
+    ///
+    /// # Returns
+    ///
+    /// - A route handler which can be registered with `WebServer::post` and friends.
+    pub fn wrap<F>(
+        &self,
+        handler: F,
+    ) -> impl Fn(context::Context) -> response::Response + 'static + Send + Sync
+    where
+        F: Fn(context::Context) -> response::Response + 'static + Send + Sync,
+    {
+        let idempotency = self.clone();
+        return move |c: context::Context| idempotency.handle(c, &handler);
+    }
+
+    // runs the route handler at most once for each idempotency key
+    fn handle<F>(&self, c: context::Context, handler: &F) -> response::Response
+    where
+        F: Fn(context::Context) -> response::Response,
+    {
+        match c.request.method {
+            utils::HttpMethod::POST | utils::HttpMethod::PATCH => {}
+            _ => return handler(c),
+        }
+        let key = match c.request.header("Idempotency-Key") {
+            Some(key) if !key.trim().is_empty() => key.trim().to_string(),
+            _ => return handler(c),
+        };
+        let scope = match self.scope(&c) {
+            Some(scope) => scope,
+            None => return handler(c),
+        };
+        let key = format!(
+            "{} {} {} {}",
+            scope,
+            c.request.method.to_string(),
+            c.request.path,
+            key
+        );
+        let fingerprint = Self::fingerprint(&c);
+
+        let in_flight = IdempotencyRecord::InFlight {
+            fingerprint: fingerprint.clone(),
+        };
+        if self.store.insert_if_absent(&key, in_flight, self.ttl) {
+            // first request with this key, run the handler and remember it's response. The guard
+            // removes the in-flight record unless the response is stored, so a handler which
+            // panics doesn't lock the key until the record expires
+            let mut guard = InFlightGuard {
+                store: self.store.as_ref(),
+                key: &key,
+                completed: false,
+            };
+            let response = handler(c);
+            if response.status_code.code().1 < 500 && response.stream.is_none() {
+                guard.completed = true;
+                self.store.set(
+                    &key,
+                    IdempotencyRecord::Completed {
+                        fingerprint,
+                        response: response.clone(),
+                    },
+                    self.ttl,
+                );
+            }
+            return response;
+        }
+
+        match self.store.get(&key) {
+            Some(IdempotencyRecord::Completed {
+                fingerprint: stored_fingerprint,
+                response,
+            }) => {
+                if stored_fingerprint != fingerprint {
+                    return Self::error_response(utils::HttpStatusCode::UnprocessableEntity);
+                }
+                let mut response = response;
+                response
+                    .headers
+                    .insert("Idempotent-Replayed".to_string(), "true".to_string());
+                return response;
+            }
+            Some(IdempotencyRecord::InFlight {
+                fingerprint: stored_fingerprint,
+            }) => {
+                if stored_fingerprint != fingerprint {
+                    return Self::error_response(utils::HttpStatusCode::UnprocessableEntity);
+                }
+                return Self::error_response(utils::HttpStatusCode::Conflict);
+            }
+            // the record expired between the two store calls, so treat this as a fresh request
+            None => return self.handle(c, handler),
+        }
+    }
+
+    // returns the caller the key of a request is scoped to, the credentials are hashed so they
+    // aren't kept in the store
+    fn scope(&self, c: &context::Context) -> Option<String> {
+        if let Some(ref scope) = self.scope {
+            return (scope)(c);
+        }
+        if let Some(authorization) = c.request.header("Authorization") {
+            return Some(format!("auth:{}", Self::hash(authorization.as_bytes())));
+        }
+        return c.client_ip().map(|ip| format!("ip:{}", ip));
+    }
+
+    // computes a fingerprint of the request payload, so that a key can't be reused for a
+    // different request
+    fn fingerprint(c: &context::Context) -> String {
+        return Self::hash(c.request.body_bytes());
+    }
+
+    // returns the hex encoded SHA-256 hash of some bytes
+    fn hash(bytes: &[u8]) -> String {
+        return Sha256::digest(bytes)
+            .iter()
+            .map(|byte| format!("{:02x}", byte))
+            .collect();
+    }
+
+    fn error_response(status_code: utils::HttpStatusCode) -> response::Response {
+        let body = status_code.code().0.to_string();
+        return response::Response::new(status_code, body);
+    }
+}
+
+// removes the in-flight record of a key when dropped, unless the request completed
+struct InFlightGuard<'a> {
+    store: &'a dyn IdempotencyStore,
+    key: &'a str,
+    completed: bool,
+}
+
+impl Drop for InFlightGuard<'_> {
+    fn drop(&mut self) {
+        if !self.completed {
+            self.store.remove(self.key);
+        }
+    }
+}
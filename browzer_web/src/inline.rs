@@ -0,0 +1,439 @@
+//! This module inlines small static assets into HTML responses, which saves the browser the
+//! extra round trips for them before it can render a page(like a landing page).
+//!
+//! An `AssetInliner` is enabled with `WebServer::inline_assets` and runs as an after-response
+//! middleware. It replaces stylesheet links with `<style>` elements holding the stylesheet and the
+//! sources of images with `data:` URIs, for the assets served with `WebServer::serve_static` which
+//! match it's patterns and don't exceed it's size caps. The assets are cached in memory until
+//! their file changes.
+
+// internal crate imports
+use crate::{response, utils};
+
+// standard library imports
+use std::{
+    collections::HashMap,
+    fs,
+    path::PathBuf,
+    sync::{Arc, Mutex},
+    time::SystemTime,
+};
+
+/// The default size cap of inlined stylesheets in bytes.
+pub const DEFAULT_MAX_STYLESHEET_SIZE: u64 = 8 * 1024;
+
+/// The default size cap of inlined images in bytes, data URIs are a third larger than the image.
+pub const DEFAULT_MAX_IMAGE_SIZE: u64 = 4 * 1024;
+
+// the contents of an asset together with the modification time of it's file
+#[derive(Debug)]
+struct CachedAsset {
+    modified: SystemTime,
+    contents: Arc<Vec<u8>>,
+}
+
+// an attribute of an HTML tag, with the byte range of it's value within the tag
+#[derive(Debug)]
+struct Attribute {
+    name: String,
+    value: Option<(usize, usize)>,
+}
+
+/// Inlines the small static assets referenced by HTML responses, see `WebServer::inline_assets`.
+///
+/// Only `200 OK` responses with an HTML `Content-Type` and a body which isn't streamed are
+/// rewritten. An asset is inlined if it's URL is under the route path the static directory is
+/// served at, it's file name matches one of the patterns(`*`, matching everything, by default)
+/// and it's file isn't larger than the size cap of it's kind:
+///
+/// - `<link rel="stylesheet" href="...">` tags become `<style>` elements, keeping their `media`
+///   and `nonce` attributes. Stylesheets referencing other files with relative URLs aren't
+///   inlined, since those URLs would resolve against the page instead of the stylesheet.
+/// - the `src` of `<img>` tags becomes a `data:` URI, for files with an image type.
+///
+/// Assets which can't be inlined are left as they are, so the browser fetches them as usual.
+/// Tags inside comments, scripts and style elements aren't touched.
+///
+/// # Examples
+///
+/// ```rust
+/// use browzer_web::{inline::AssetInliner, response::Response, utils::HttpStatusCode};
+/// use std::fs;
+///
+/// let dir = std::env::temp_dir().join("browzer_inline_example");
+/// fs::create_dir_all(&dir).unwrap();
+/// fs::write(dir.join("critical.css"), "body{margin:0}").unwrap();
+/// fs::write(dir.join("dot.gif"), b"GIF89a").unwrap();
+///
+/// let inliner = AssetInliner::new(dir.to_str().unwrap(), "/static/get")
+///     .patterns(&["critical.css", "*.gif"]);
+///
+/// let mut response = Response::new(
+///     HttpStatusCode::OK,
+///     concat!(
+///         r#"<link rel="stylesheet" href="/static/get/critical.css">"#,
+///         r#"<link rel="stylesheet" href="/static/get/other.css">"#,
+///         r#"<img alt="" src="/static/get/dot.gif">"#,
+///     )
+///     .to_string(),
+/// );
+/// response.headers.insert("Content-Type".to_string(), "text/html".to_string());
+///
+/// let response = inliner.apply(response);
+/// assert_eq!(
+///     response.body,
+///     concat!(
+///         "<style>body{margin:0}</style>",
+///         r#"<link rel="stylesheet" href="/static/get/other.css">"#,
+///         r#"<img alt="" src="data:image/gif;base64,R0lGODlh">"#,
+///     )
+/// );
+/// ```
+// ----- AssetInliner struct
+#[derive(Debug)]
+pub struct AssetInliner {
+    root: PathBuf,
+    route_path: String,
+    patterns: Vec<String>,
+    max_stylesheet_size: u64,
+    max_image_size: u64,
+    cache: Mutex<HashMap<PathBuf, CachedAsset>>,
+}
+
+impl AssetInliner {
+    /// Creates a new `AssetInliner` for the static files of a directory.
+    ///
+    /// # Arguments
+    ///
+    /// - `dir_path` - The directory the static files are served from.
+    /// - `route_path` - The route path the directory is served at, the same as passed to
+    ///   `WebServer::serve_static`.
+    pub fn new(dir_path: &str, route_path: &str) -> AssetInliner {
+        return AssetInliner {
+            root: PathBuf::from(dir_path),
+            route_path: format!("/{}", route_path.trim_matches('/')),
+            patterns: vec!["*".to_string()],
+            max_stylesheet_size: DEFAULT_MAX_STYLESHEET_SIZE,
+            max_image_size: DEFAULT_MAX_IMAGE_SIZE,
+            cache: Mutex::new(HashMap::new()),
+        };
+    }
+
+    /// Sets the patterns the file names of inlined assets have to match, replacing the default
+    /// `*`. A `*` in a pattern matches any number of characters and a `?` matches one character.
+    pub fn patterns(mut self, patterns: &[&str]) -> AssetInliner {
+        self.patterns = patterns.iter().map(|pattern| pattern.to_string()).collect();
+        return self;
+    }
+
+    /// Sets the size cap of inlined stylesheets in bytes, `DEFAULT_MAX_STYLESHEET_SIZE` by
+    /// default.
+    pub fn max_stylesheet_size(mut self, bytes: u64) -> AssetInliner {
+        self.max_stylesheet_size = bytes;
+        return self;
+    }
+
+    /// Sets the size cap of inlined images in bytes, `DEFAULT_MAX_IMAGE_SIZE` by default.
+    pub fn max_image_size(mut self, bytes: u64) -> AssetInliner {
+        self.max_image_size = bytes;
+        return self;
+    }
+
+    /// Inlines the assets referenced by an HTML response, leaving other responses untouched.
+    ///
+    /// The `ETag` header of a rewritten response is removed, since it doesn't describe the new
+    /// body.
+    pub fn apply(&self, mut response: response::Response) -> response::Response {
+        if response.status_code != utils::HttpStatusCode::OK || response.stream.is_some() {
+            return response;
+        }
+        let is_html = response.headers.iter().any(|(key, value)| {
+            key.eq_ignore_ascii_case("Content-Type")
+                && value
+                    .trim_start()
+                    .to_ascii_lowercase()
+                    .starts_with("text/html")
+        });
+        if !is_html {
+            return response;
+        }
+        let html = self.inline(&response.body);
+        if html != response.body {
+            response.body = html;
+            response
+                .headers
+                .retain(|key, _| !key.eq_ignore_ascii_case("ETag"));
+        }
+        return response;
+    }
+
+    /// Inlines the assets referenced by an HTML document.
+    pub fn inline(&self, html: &str) -> String {
+        let mut output = String::with_capacity(html.len());
+        let mut rest = html;
+        while let Some(start) = rest.find('<') {
+            output.push_str(&rest[..start]);
+            rest = &rest[start..];
+
+            // comments and the contents of raw text elements are copied as they are
+            if rest.starts_with("<!--") {
+                let end = rest.find("-->").map(|end| end + 3).unwrap_or(rest.len());
+                output.push_str(&rest[..end]);
+                rest = &rest[end..];
+                continue;
+            }
+            let name_length = rest[1..]
+                .find(|c: char| !c.is_ascii_alphanumeric())
+                .unwrap_or(rest.len() - 1);
+            let name = rest[1..1 + name_length].to_ascii_lowercase();
+            let (tag_length, attributes) = match parse_tag(rest, 1 + name_length) {
+                Some(tag) => tag,
+                None => break,
+            };
+            let tag = &rest[..tag_length];
+            match name.as_str() {
+                "link" => output.push_str(&self.inline_stylesheet(tag, &attributes)),
+                "img" => output.push_str(&self.inline_image(tag, &attributes)),
+                "script" | "style" => {
+                    let closing = format!("</{}", name);
+                    let end = find_ignore_case(&rest[tag_length..], &closing)
+                        .map(|end| tag_length + end)
+                        .unwrap_or(rest.len());
+                    output.push_str(&rest[..end]);
+                    rest = &rest[end..];
+                    continue;
+                }
+                _ => output.push_str(tag),
+            }
+            rest = &rest[tag_length..];
+        }
+        output.push_str(rest);
+        return output;
+    }
+
+    // replaces a stylesheet link with a style element holding the stylesheet, if it's eligible
+    fn inline_stylesheet(&self, tag: &str, attributes: &[Attribute]) -> String {
+        let value = |name: &str| attribute_value(tag, attributes, name);
+        let is_stylesheet = value("rel").is_some_and(|rel| {
+            rel.split_ascii_whitespace()
+                .any(|rel| rel.eq_ignore_ascii_case("stylesheet"))
+        });
+        let href = match value("href") {
+            Some(href) if is_stylesheet => href,
+            _ => return tag.to_string(),
+        };
+        let (contents, mime_type) = match self.asset(href, self.max_stylesheet_size) {
+            Some(asset) => asset,
+            None => return tag.to_string(),
+        };
+        let css = match String::from_utf8(contents.to_vec()) {
+            Ok(css) if mime_type.starts_with("text/css") && can_inline_css(&css) => css,
+            _ => return tag.to_string(),
+        };
+        let mut style = String::from("<style");
+        for name in ["media", "nonce"] {
+            if let Some(value) = value(name) {
+                style.push_str(&format!(" {}=\"{}\"", name, value));
+            }
+        }
+        style.push('>');
+        style.push_str(&css);
+        style.push_str("</style>");
+        return style;
+    }
+
+    // replaces the source of an image with a data URI, if it's eligible
+    fn inline_image(&self, tag: &str, attributes: &[Attribute]) -> String {
+        let (start, end) = match attributes
+            .iter()
+            .find(|attribute| attribute.name == "src")
+            .and_then(|attribute| attribute.value)
+        {
+            Some(range) => range,
+            None => return tag.to_string(),
+        };
+        let (contents, mime_type) = match self.asset(&tag[start..end], self.max_image_size) {
+            Some(asset) if asset.1.starts_with("image/") => asset,
+            _ => return tag.to_string(),
+        };
+        return format!(
+            "{}data:{};base64,{}{}",
+            &tag[..start],
+            mime_type,
+            utils::base64_encode(&contents),
+            &tag[end..]
+        );
+    }
+
+    // loads the asset a URL refers to, if it is served from the static directory, matches the
+    // patterns and isn't larger than `max_size`
+    fn asset(&self, url: &str, max_size: u64) -> Option<(Arc<Vec<u8>>, &'static str)> {
+        let url = url.trim().replace("&amp;", "&");
+        let url = url.split(['?', '#']).next().unwrap_or_default();
+        let requested = url
+            .strip_prefix(self.route_path.trim_end_matches('/'))?
+            .strip_prefix('/')?;
+        let file_name = requested.rsplit('/').next().unwrap_or_default();
+        if !self
+            .patterns
+            .iter()
+            .any(|pattern| glob_match(pattern, file_name))
+        {
+            return None;
+        }
+        let path = utils::resolve_static_path(&self.root, requested).ok()?;
+        let mime_type = utils::mime::from_path(&path);
+
+        let metadata = fs::metadata(&path).ok()?;
+        if metadata.len() > max_size {
+            return None;
+        }
+        let modified = metadata.modified().ok()?;
+        if let Ok(cache) = self.cache.lock() {
+            if let Some(cached) = cache.get(&path) {
+                if cached.modified == modified {
+                    return Some((Arc::clone(&cached.contents), mime_type));
+                }
+            }
+        }
+        let contents = Arc::new(fs::read(&path).ok()?);
+        // the file might have grown since it's size was checked
+        if contents.len() as u64 > max_size {
+            return None;
+        }
+        if let Ok(mut cache) = self.cache.lock() {
+            cache.insert(
+                path,
+                CachedAsset {
+                    modified,
+                    contents: Arc::clone(&contents),
+                },
+            );
+        }
+        return Some((contents, mime_type));
+    }
+}
+
+// parses the attributes of the tag at the start of `html`, starting after it's name
+//
+// returns the length of the tag including it's closing `>` and it's attributes, `None` if the
+// tag isn't closed
+fn parse_tag(html: &str, mut position: usize) -> Option<(usize, Vec<Attribute>)> {
+    let bytes = html.as_bytes();
+    let mut attributes = Vec::new();
+    loop {
+        while position < bytes.len()
+            && (bytes[position].is_ascii_whitespace() || bytes[position] == b'/')
+        {
+            position += 1;
+        }
+        match bytes.get(position)? {
+            b'>' => return Some((position + 1, attributes)),
+            _ => {}
+        }
+        let name_start = position;
+        while position < bytes.len()
+            && !bytes[position].is_ascii_whitespace()
+            && !matches!(bytes[position], b'=' | b'>' | b'/')
+        {
+            position += 1;
+        }
+        let name = html[name_start..position].to_ascii_lowercase();
+        if name.is_empty() {
+            // a stray `=`
+            position += 1;
+            continue;
+        }
+        while position < bytes.len() && bytes[position].is_ascii_whitespace() {
+            position += 1;
+        }
+        let mut value = None;
+        if bytes.get(position) == Some(&b'=') {
+            position += 1;
+            while position < bytes.len() && bytes[position].is_ascii_whitespace() {
+                position += 1;
+            }
+            match bytes.get(position)? {
+                quote @ (b'"' | b'\'') => {
+                    let length = html[position + 1..].find(*quote as char)?;
+                    value = Some((position + 1, position + 1 + length));
+                    position += length + 2;
+                }
+                _ => {
+                    let start = position;
+                    while position < bytes.len()
+                        && !bytes[position].is_ascii_whitespace()
+                        && bytes[position] != b'>'
+                    {
+                        position += 1;
+                    }
+                    value = Some((start, position));
+                }
+            }
+        }
+        attributes.push(Attribute { name, value });
+    }
+}
+
+// returns the value of an attribute of a tag
+fn attribute_value<'a>(tag: &'a str, attributes: &[Attribute], name: &str) -> Option<&'a str> {
+    return attributes
+        .iter()
+        .find(|attribute| attribute.name == name)
+        .and_then(|attribute| attribute.value)
+        .map(|(start, end)| &tag[start..end]);
+}
+
+// returns whether a stylesheet still works when moved into the page, which it doesn't if it
+// references other files relative to it's own URL or could end the style element early
+fn can_inline_css(css: &str) -> bool {
+    let lowercase = css.to_ascii_lowercase();
+    if lowercase.contains("</style") || lowercase.contains("@import") {
+        return false;
+    }
+    return lowercase.split("url(").skip(1).all(|reference| {
+        let target = reference.trim_start().trim_start_matches(['"', '\'']);
+        return ["/", "#", "data:", "http:", "https:"]
+            .iter()
+            .any(|prefix| target.starts_with(prefix));
+    });
+}
+
+// returns the position of the first occurrence of an ASCII needle, ignoring case
+fn find_ignore_case(haystack: &str, needle: &str) -> Option<usize> {
+    return haystack
+        .as_bytes()
+        .windows(needle.len())
+        .position(|window| window.eq_ignore_ascii_case(needle.as_bytes()));
+}
+
+// matches a file name against a pattern where `*` matches any number of characters and `?`
+// matches one character
+fn glob_match(pattern: &str, name: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let name: Vec<char> = name.chars().collect();
+    let (mut p, mut n) = (0, 0);
+    // the position of the last `*` and the position in the name it was tried at
+    let mut backtrack = None;
+    while n < name.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                backtrack = Some((p, n));
+                p += 1;
+            }
+            Some(c) if *c == '?' || *c == name[n] => {
+                p += 1;
+                n += 1;
+            }
+            _ => match backtrack {
+                Some((star, tried)) => {
+                    p = star + 1;
+                    n = tried + 1;
+                    backtrack = Some((star, tried + 1));
+                }
+                None => return false,
+            },
+        }
+    }
+    return pattern[p..].iter().all(|c| *c == '*');
+}
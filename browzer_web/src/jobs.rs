@@ -0,0 +1,349 @@
+//! This module runs long-running operations as jobs in the background, answering the request which
+//! started one with `202 Accepted` and a URL the client polls for it's outcome, see
+//! `Context::accept_async` and `WebServer::jobs`.
+//!
+//! The status of every job is kept in a pluggable `JobStore`, an in-memory one is provided by
+//! `MemoryJobStore`.
+
+// internal crate imports
+use crate::{response, tasks, utils};
+
+// external crate imports
+use uuid::Uuid;
+
+// standard library imports
+use std::{
+    collections::HashMap,
+    fmt,
+    panic::{self, AssertUnwindSafe},
+    sync::{Arc, Mutex, MutexGuard},
+    time::{Duration, Instant},
+};
+
+/// The name of the pool jobs run on unless another one is picked.
+pub const DEFAULT_POOL: &str = "default";
+
+/// The path under which the status of jobs is served by default, followed by their id.
+pub const DEFAULT_ROUTE_PATH: &str = "/jobs";
+
+/// The status of a job.
+///
+/// # Variants
+///
+/// - `Pending` - The job waits for a thread of it's pool.
+/// - `Running` - The job is running.
+/// - `Succeeded` - The job finished, carrying it's result.
+/// - `Failed` - The job failed, carrying it's error message.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum JobStatus {
+    Pending,
+    Running,
+    Succeeded(String),
+    Failed(String),
+}
+
+impl JobStatus {
+    /// Returns the name of the status, like `pending`.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            JobStatus::Pending => return "pending",
+            JobStatus::Running => return "running",
+            JobStatus::Succeeded(_) => return "succeeded",
+            JobStatus::Failed(_) => return "failed",
+        }
+    }
+
+    /// Returns whether the job finished, successfully or not.
+    pub fn is_finished(&self) -> bool {
+        return matches!(self, JobStatus::Succeeded(_) | JobStatus::Failed(_));
+    }
+}
+
+/// A storage backend for the status of jobs.
+///
+/// Implement this trait to keep the status somewhere other than the memory of the current
+/// process, like a database which clients polling another server instance can read too.
+pub trait JobStore: Send + Sync {
+    /// Returns the status of the job `id`, or `None` if there is no such job or it has expired.
+    fn load(&self, id: &str) -> Option<JobStatus>;
+
+    /// Stores the status of the job `id`, replacing any existing status. With a `ttl` the status
+    /// expires after it without being stored again, without one it is kept until it is replaced,
+    /// which is how the status of a job that hasn't finished yet is stored.
+    fn save(&self, id: &str, status: JobStatus, ttl: Option<Duration>);
+}
+
+/// An in-memory `JobStore` implementation.
+///
+/// Expired jobs are purged lazily whenever the status of a job is saved.
+///
+/// # Examples
+///
+/// ```rust
+/// use browzer_web::jobs::{JobStatus, JobStore, MemoryJobStore};
+/// use std::time::Duration;
+///
+/// let store = MemoryJobStore::new();
+///
+/// store.save("id", JobStatus::Running, None);
+/// assert_eq!(store.load("id"), Some(JobStatus::Running));
+///
+/// store.save("id", JobStatus::Failed("disk full".to_string()), Some(Duration::ZERO));
+/// assert_eq!(store.load("id"), None);
+/// ```
+// ----- MemoryJobStore struct
+#[derive(Debug, Default)]
+pub struct MemoryJobStore {
+    jobs: Mutex<HashMap<String, (JobStatus, Option<Instant>)>>,
+}
+
+impl MemoryJobStore {
+    /// Creates a new, empty `MemoryJobStore`.
+    pub fn new() -> MemoryJobStore {
+        return MemoryJobStore::default();
+    }
+
+    /// Returns the number of jobs in the store, expired ones which weren't purged yet included.
+    pub fn len(&self) -> usize {
+        return self.lock().len();
+    }
+
+    /// Returns whether the store holds no jobs.
+    pub fn is_empty(&self) -> bool {
+        return self.len() == 0;
+    }
+
+    fn lock(&self) -> MutexGuard<'_, HashMap<String, (JobStatus, Option<Instant>)>> {
+        match self.jobs.lock() {
+            Ok(jobs) => return jobs,
+            Err(poisoned) => return poisoned.into_inner(),
+        }
+    }
+}
+
+impl JobStore for MemoryJobStore {
+    fn load(&self, id: &str) -> Option<JobStatus> {
+        match self.lock().get(id) {
+            Some((status, None)) => return Some(status.clone()),
+            Some((status, Some(expires_at))) if *expires_at > Instant::now() => {
+                return Some(status.clone())
+            }
+            _ => return None,
+        }
+    }
+
+    fn save(&self, id: &str, status: JobStatus, ttl: Option<Duration>) {
+        let mut jobs = self.lock();
+        let now = Instant::now();
+        jobs.retain(|_, (_, expires_at)| expires_at.is_none_or(|expires_at| expires_at > now));
+        jobs.insert(id.to_string(), (status, ttl.map(|ttl| now + ttl)));
+    }
+}
+
+/// The configuration of background jobs, enabled with `WebServer::jobs`.
+///
+/// Jobs run on named pools of threads, separate from the workers serving connections and from the
+/// pool of `Context::spawn_scoped`, so a burst of slow jobs can't starve either of them. The
+/// `default` pool(`DEFAULT_POOL`) has as many threads as there are CPU cores unless it is
+/// configured with `Jobs::pool`. Their status is served as JSON under `Jobs::route_path`, like
+/// `{"id":"...","status":"succeeded","result":"..."}`, with a `Retry-After` header while the job
+/// hasn't finished yet.
+///
+/// # Examples
+///
+/// ```rust,no_run
+/// use browzer_web::{jobs::{Jobs, MemoryJobStore}, WebServer};
+/// use std::time::Duration;
+///
+/// let mut server = WebServer::new("127.0.0.1:8080".to_string(), 4);
+///
+/// // keep the outcome of a job for an hour
+/// server.jobs(Jobs::new(MemoryJobStore::new(), Duration::from_secs(3600)).pool("reports", 2));
+/// server.post("/reports", |mut c| {
+///     return c.accept_async("reports", || {
+///         // generate the report
+///         return Ok("https://example.com/reports/1.pdf".to_string());
+///     });
+/// });
+/// ```
+// ----- Jobs struct
+#[derive(Clone)]
+pub struct Jobs {
+    store: Arc<dyn JobStore>,
+    ttl: Duration,
+    route_path: String,
+    pools: HashMap<String, Arc<tasks::BackgroundPool>>,
+}
+
+impl fmt::Debug for Jobs {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Jobs")
+            .field("store", &"Arc<dyn JobStore>")
+            .field("ttl", &self.ttl)
+            .field("route_path", &self.route_path)
+            .field("pools", &self.pools)
+            .finish()
+    }
+}
+
+impl Jobs {
+    /// Creates a new `Jobs` configuration backed by the given store.
+    ///
+    /// # Arguments
+    ///
+    /// - `store` - The `JobStore` in which the status of jobs is kept.
+    /// - `ttl` - A `Duration` after which the status of a job expires once it finished. Jobs which
+    ///   are still pending or running never expire, however long they take.
+    pub fn new<S>(store: S, ttl: Duration) -> Jobs
+    where
+        S: JobStore + 'static,
+    {
+        let mut pools = HashMap::new();
+        pools.insert(
+            DEFAULT_POOL.to_string(),
+            Arc::new(tasks::BackgroundPool::default()),
+        );
+        return Jobs {
+            store: Arc::new(store),
+            ttl,
+            route_path: DEFAULT_ROUTE_PATH.to_string(),
+            pools,
+        };
+    }
+
+    /// Sets the path under which the status of jobs is served, followed by their id,
+    /// `DEFAULT_ROUTE_PATH` by default.
+    pub fn route_path(mut self, route_path: &str) -> Jobs {
+        self.route_path = route_path.trim_end_matches('/').to_string();
+        return self;
+    }
+
+    /// Adds a pool jobs can run on, or resizes an existing one.
+    ///
+    /// # Arguments
+    ///
+    /// - `name` - The name of the pool, passed to `Context::accept_async`.
+    /// - `size` - The number of threads of the pool, which are only started once the first job
+    ///   runs on it.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `size` is 0.
+    pub fn pool(mut self, name: &str, size: usize) -> Jobs {
+        self.pools
+            .insert(name.to_string(), Arc::new(tasks::BackgroundPool::new(size)));
+        return self;
+    }
+
+    /// Returns the path under which the status of jobs is served.
+    pub fn path(&self) -> &str {
+        return &self.route_path;
+    }
+
+    /// Returns the URL path of the status of a job.
+    pub fn status_url(&self, id: &str) -> String {
+        return format!("{}/{}", self.route_path, id);
+    }
+
+    /// Runs a job on a pool.
+    ///
+    /// # Arguments
+    ///
+    /// - `pool` - The name of the pool.
+    /// - `job` - The job, returning it's result or an error message. A panic fails the job.
+    ///
+    /// # Returns
+    ///
+    /// - `Option<String>` - The id of the job, `None` if there is no pool with that name.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use browzer_web::jobs::{JobStatus, Jobs, MemoryJobStore, DEFAULT_POOL};
+    /// use std::time::Duration;
+    ///
+    /// let jobs = Jobs::new(MemoryJobStore::new(), Duration::from_secs(60));
+    /// let id = jobs.enqueue(DEFAULT_POOL, || Ok("done".to_string())).unwrap();
+    ///
+    /// while !jobs.status(&id).unwrap().is_finished() {
+    ///     std::thread::sleep(Duration::from_millis(1));
+    /// }
+    /// assert_eq!(jobs.status(&id), Some(JobStatus::Succeeded("done".to_string())));
+    /// assert!(jobs.enqueue("missing", || Ok(String::new())).is_none());
+    /// ```
+    pub fn enqueue<F>(&self, pool: &str, job: F) -> Option<String>
+    where
+        F: FnOnce() -> Result<String, String> + Send + 'static,
+    {
+        let pool = self.pools.get(pool)?;
+        let id = Uuid::new_v4().simple().to_string();
+        self.store.save(&id, JobStatus::Pending, None);
+
+        let store = Arc::clone(&self.store);
+        let ttl = self.ttl;
+        let job_id = id.clone();
+        pool.execute(move || {
+            store.save(&job_id, JobStatus::Running, None);
+            let status = match panic::catch_unwind(AssertUnwindSafe(job)) {
+                Ok(Ok(result)) => JobStatus::Succeeded(result),
+                Ok(Err(error)) => JobStatus::Failed(error),
+                Err(_) => JobStatus::Failed("The job panicked".to_string()),
+            };
+            store.save(&job_id, status, Some(ttl));
+        });
+        return Some(id);
+    }
+
+    /// Returns the status of a job, `None` if there is no such job or it has expired.
+    pub fn status(&self, id: &str) -> Option<JobStatus> {
+        return self.store.load(id);
+    }
+
+    /// Generates the response of the status route for a job.
+    ///
+    /// # Returns
+    ///
+    /// - `Option<Response>` - A JSON description of the status, `None` if there is no such job or
+    ///   it has expired.
+    pub fn status_response(&self, id: &str) -> Option<response::Response> {
+        let status = self.status(id)?;
+        // a struct keeps the fields in a fixed order, unlike a JSON map
+        #[derive(serde::Serialize)]
+        struct JsonStatus<'a> {
+            id: &'a str,
+            status: &'a str,
+            #[serde(skip_serializing_if = "Option::is_none")]
+            result: Option<&'a str>,
+            #[serde(skip_serializing_if = "Option::is_none")]
+            error: Option<&'a str>,
+        }
+        let json = JsonStatus {
+            id,
+            status: status.as_str(),
+            result: match status {
+                JobStatus::Succeeded(ref result) => Some(result),
+                _ => None,
+            },
+            error: match status {
+                JobStatus::Failed(ref error) => Some(error),
+                _ => None,
+            },
+        };
+        let mut response = response::Response::new(
+            utils::HttpStatusCode::OK,
+            serde_json::to_string(&json).unwrap_or_default(),
+        );
+        response
+            .headers
+            .insert("Content-Type".to_string(), "application/json".to_string());
+        response
+            .headers
+            .insert("Cache-Control".to_string(), "no-store".to_string());
+        if !status.is_finished() {
+            response
+                .headers
+                .insert("Retry-After".to_string(), "1".to_string());
+        }
+        return Some(response);
+    }
+}
@@ -4,43 +4,173 @@
 //!
 //! ## Examples
 //!
-//! ```rust
-//! use browzer_web;
+//! ```rust,no_run
+//! # const PORT: &str = "3000";
+//! use browzer_web::prelude::*;
 //!
 //! fn main() {
-//!     let mut server = browzer_web::WebServer::new(format!("0.0.0.0:{}", PORT), 5);
+//!     let mut server = WebServer::new(format!("0.0.0.0:{}", PORT), 5);
 //!     server.get("/", |mut c| {
-//!         return c.send_string(browzer_web::utils::HttpStatusCode::OK, "Hello, World!");
+//!         return c.send_string(HttpStatusCode::OK, "Hello, World!");
 //!     });
 //!     server.listen();
 //! }
 //! ```
 //!
+//! ## Features
+//!
+//! - `tls` - serve HTTPS using `rustls`, see `WebServer::new_tls`
+//! - `markdown` - serve Markdown files rendered to HTML pages, see `WebServer::serve_markdown`
+//! - `compression` - gzip and deflate response compression, see `WebServer::compression`
+//! - `brotli` - brotli response compression, on top of the `compression` feature
+//! - `serde` - `Serialize` and `Deserialize` implementations for `Request`, `Response`, `Cookie`,
+//!   `HttpMethod` and `HttpStatusCode`, for recording, queueing or auditing requests
+//! - `jwt` - JSON Web Token(HS256 and RS256) authentication using `ring`, see `auth::jwt`
+//! - `tracing` - a `tracing` span for every request, see `router::WebRouter::handle_request`
+//! - `tokio` - an async mode on the tokio runtime with non-blocking I/O and async handlers, see
+//!   `WebServer::listen_async`
+//! - `md5` - `Content-MD5` and `md5` content digests using `md-5`, see `digest::Md5`
+//!
 //! ## Modules
 //!
+//! - `accept` - rate-limited reporting of failed and dropped connections in the accept loop
+//! - `affinity` - pinning of the server threads to CPU cores
+//! - `auth` - `Basic`, `Bearer` and JSON Web Token authentication and time-based one-time
+//!   passwords(TOTP)
+//! - `cache` - in-memory caching of responses with purging and warm-up
+//! - `cancel` - cancellation tokens telling handlers that their client disconnected
+//! - `canonical` - redirects to the canonical scheme and host name of a site
+//! - `cluster` - rate limit state and session invalidations shared by the instances of a server
+//! - `client` - user agent and client hint summaries of the client of a request
+//! - `compression` - response compression and the rules deciding which responses are eligible
+//! - `conditional` - conditional request(`If-Match`/`If-None-Match`) evaluation using entity tags
+//! - `connection` - per-connection state and metadata shared by the requests of a connection
 //! - `context` - route context which helps to easily work with router handlers
+//! - `cors` - cross-origin resource sharing(CORS) policies and preflight handling
+//! - `digest` - content digests(`Repr-Digest`/`Digest`/`Content-MD5`) of files and uploads
+//! - `echo` - JSON reflections of parsed requests, for debugging proxies and clients
 //! - `error` - custom errors
+//! - `forwarded` - the client address behind trusted reverse proxies(`Forwarded`/`X-Forwarded-For`)
+//! - `health` - liveness and readiness checks for load balancers, answered as JSON
+//! - `http_client` - the HTTP/1.1 client of the reverse proxy and the webhooks, over TCP or TLS
+//! - `idempotency` - `Idempotency-Key` handling for safely retrying non-idempotent requests
+//! - `inline` - inlining of small stylesheets and images into HTML responses
+//! - `jobs` - long-running background jobs answered with `202 Accepted` and a status URL
+//! - `limits` - file descriptor limit awareness and the concurrent connections soft cap
+//! - `links` - RFC 8288 `Link` header builder and pagination links
+//! - `logger` - structured logging of the requests a server answers
+//! - `markdown` - Markdown rendering with front matter and templates(requires the `markdown`
+//!   feature)
+//! - `metrics` - request counts, latency histograms and server gauges in the Prometheus format
+//! - `multipart` - streaming `multipart/form-data` parser with per-field sinks
+//! - `pages` - themable templates of the HTML pages generated by the framework, like error pages
+//! - `overload` - `Retry-After` signaling on the responses of requests turned away under overload
+//! - `panics` - recording of worker panics by the phase they happened in
+//! - `policy` - named access control policies required by routes
+//! - `prelude` - re-exports of the types most applications need, `use browzer_web::prelude::*;`
+//! - `problem` - RFC 7807 problem details error responses
+//! - `proxy` - PROXY protocol(version 1 and 2) headers sent by TCP load balancers
+//! - `rate_limit` - per-client request rate limiting with token buckets
+//! - `range` - byte range requests(`Range` header) and partial responses
+//! - `replay` - recording of sampled requests as NDJSON and replaying them through a router
 //! - `request` - handle HTTP requests related functionality
+//! - `request_id` - IDs tying the log lines and responses of a request together(`X-Request-Id`)
 //! - `response` - handle HTTP response related functionality
+//! - `reverse_proxy` - forwarding of requests to upstream HTTP servers, for gateways and BFFs
 //! - `router` - deals with routing and other aspects of routing like middlewares, registered routes
+//! - `runtime` - serving on the tokio runtime with non-blocking I/O(requires the `tokio` feature)
+//! - `sampling` - phase by phase latency profiles of a sample of the requests
+//! - `sessions` - server-side sessions with pluggable stores, identified by a cookie
+//! - `static_files` - static file serving with `ETag`/`Last-Modified` validation and caching
+//!   directives
+//! - `storage` - embedded key-value store persisted in an append-only log
+//! - `stream` - streaming response bodies with buffering and flush control
+//! - `tasks` - background tasks spawned by handlers and tied to the lifecycle of their request
+//! - `templates` - HTML templates rendered with the data of a request
+//! - `tls` - HTTPS support using `rustls`(requires the `tls` feature)
+//! - `upgrade` - graceful shutdown and listening socket handoff for zero-downtime restarts
+//! - `upload` - resumable(tus protocol) and `multipart/form-data` upload handling
 //! - `utils` - utilities used by the framework
+//! - `webhooks` - signed outbound webhook deliveries with retries and dead letters
 
+pub mod accept;
+pub mod affinity;
+pub mod auth;
+pub mod cache;
+pub mod cancel;
+pub mod canonical;
+pub mod client;
+pub mod cluster;
+pub mod compression;
+pub mod conditional;
+pub mod connection;
 pub mod context;
+pub mod cors;
+pub mod digest;
+pub mod echo;
 pub mod error;
+mod exchange;
+pub mod forwarded;
+pub mod health;
+pub mod http_client;
+pub mod idempotency;
+pub mod inline;
+pub mod jobs;
+pub mod limits;
+pub mod links;
+pub mod logger;
+#[cfg(feature = "markdown")]
+pub mod markdown;
+pub mod metrics;
+pub mod multipart;
+pub mod overload;
+pub mod pages;
+pub mod panics;
+pub mod policy;
+pub mod prelude;
+pub mod problem;
+pub mod proxy;
+pub mod range;
+pub mod rate_limit;
+pub mod replay;
 pub mod request;
+pub mod request_id;
 pub mod response;
+pub mod reverse_proxy;
 pub mod router;
+#[cfg(feature = "tokio")]
+pub mod runtime;
+pub mod sampling;
+pub mod sessions;
+pub mod static_files;
+pub mod storage;
+pub mod stream;
+pub mod tasks;
+pub mod templates;
+#[cfg(feature = "tls")]
+pub mod tls;
+pub mod upgrade;
+pub mod upload;
 pub mod utils;
+pub mod webhooks;
 
 // standard library imports
 use std::{
-    fs,
-    io::{BufRead, BufReader, Read, Write},
-    net::{TcpListener, TcpStream},
-    path::Path,
-    sync::Arc,
+    cell::Cell,
+    io::{self, BufRead, BufReader, Read, Write},
+    net::{SocketAddr, TcpListener, TcpStream},
+    panic::{self, AssertUnwindSafe},
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, Mutex,
+    },
+    thread,
+    time::{Duration, Instant},
 };
 
+#[cfg(unix)]
+use std::os::unix::io::AsRawFd;
+
 /// Represents a web server.
 ///
 /// The `WebServer` struct is responsible for creating the main server which binds all the
@@ -50,14 +180,68 @@ use std::{
 /// # Fields
 ///
 /// - `listener` - A `TcpListener` that listens for incoming requests streams.
+/// - `additional_listeners` - The listeners of the other addresses the server listens on, see
+///   `WebServer::bind`
 /// - `request_pool`- A custom `ThreadPool` implementation which handles request distribution to various worker threads
 /// - `hide_banner` - A boolean flag to control whether the server banner should be displayed(logged to the console) or not
 /// - `address` - The address to which the WebServer binds the TcpListener
 /// - `router` - An `Arc` wrapped `WebRouter` which is responsible for routing logic of the server
+/// - `keep_alive_timeout` - How long a persistent(keep-alive) connection may stay idle between two
+///   requests before it is closed, `None` disables persistent connections(defaults to 5 seconds)
+/// - `workers` - The number of worker threads the `request_pool` starts with, and keeps when idle
+/// - `max_connections` - The maximum number of concurrent connections, connections over it are
+///   answered with `503 Service Unavailable`(defaults to a value derived from the file descriptor
+///   limit of the process, see `WebServer::limits`)
+/// - `queue_policy` - What happens to new connections while the queue of connections waiting for a
+///   worker is full(defaults to `QueuePolicy::Block`), see `WebServer::queue_limit`
+/// - `active_connections` - The number of currently open connections
+/// - `accept_error_log` - Configuration of the rate-limited reporting of failed and dropped
+///   connections, see `WebServer::on_accept_error`
+/// - `panic_log` - The counts of worker panics and the hook reporting them, see
+///   `WebServer::on_panic`
+/// - `overload` - The drain rate of connections and the retry policy of `503 Service Unavailable`
+///   responses, see `WebServer::retry_after`
+/// - `tls_config` - The `rustls` configuration used to serve HTTPS, only available with the `tls`
+///   feature(see `WebServer::new_tls`)
+/// - `accept_plaintext` - Whether connections which don't start with a TLS handshake are served as
+///   plain HTTP on the same port, only available with the `tls` feature(see
+///   `WebServer::accept_plaintext`)
+/// - `strict_http` - Whether requests are parsed strictly according to RFC 7230(defaults to
+///   `false`), see `Request::read_from` for the differences
+/// - `request_timeout` - How long reading the body of a request and handling it may take, measured
+///   from when the request was received(defaults to `None`, no timeout). A client sending it's
+///   body too slowly gets a `408 Request Timeout` response, and a request whose handler didn't
+///   finish in time a `503 Service Unavailable` one. The handler keeps it's worker thread until it
+///   returns, it learns about the timeout through `Context::cancellation_token` and should stop
+///   once the token is cancelled. Routes can override it with `RouteBuilder::timeout`
+/// - `body_limit` - The maximum size of a request body in bytes(defaults to
+///   `limits::DEFAULT_MAX_BODY_SIZE`, `None` disables the limit), requests announcing a larger
+///   body get a `413 Payload Too Large` response without their body being read, see
+///   `WebServer::set_max_body_size`. Routes can override it with `RouteBuilder::body_limit`
+/// - `head_limits` - The limits of the request line, the number of headers and the size of a
+///   request head(defaults to `limits::HeadLimits::default()`), requests exceeding them get a `400
+///   Bad Request` or `431 Request Header Fields Too Large` response, see
+///   `WebServer::set_head_limits`
+/// - `memory_budget` - The approximate number of bytes a single request may hold in memory, it's
+///   head, body and response together(defaults to `None`, no budget), see
+///   `WebServer::set_memory_budget`. Routes can override it with `RouteBuilder::memory_budget`
+/// - `core_map` - The CPU cores the worker and acceptor threads are pinned to once the server
+///   starts listening(defaults to `None`, no pinning), see `WebServer::pin_workers`
+/// - `proxy_protocol` - Whether every connection starts with a PROXY protocol header carrying the
+///   address of the client(defaults to `false`), see `WebServer::proxy_protocol`
+/// - `proxy_protocol_listeners` - The addresses of the listeners whose connections start with a
+///   PROXY protocol header even if `proxy_protocol` is `false`, see
+///   `WebServer::proxy_protocol_on`
+/// - `drain_timeout` - How long the connections open when the server stops may take to finish
+///   before `listen` returns anyway(defaults to `upgrade::DEFAULT_DRAIN_TIMEOUT`)
+/// - `shutdown` - The `ShutdownHandle` stopping the server, see `WebServer::shutdown_handle`
+/// - `upgrade_socket` - The path of the control socket, see `WebServer::upgrade_socket`
+/// - `upgrade_command` - The command line starting the new process of an upgrade, see
+///   `WebServer::upgrade_command`
 ///
 /// # Examples
 ///
-/// ```rust
+/// ```rust,no_run
 /// use browzer_web::WebServer;
 ///
 /// let server = WebServer::new("127.0.0.1:8080".to_string(), 4);
@@ -67,10 +251,38 @@ use std::{
 #[derive(Debug)]
 pub struct WebServer {
     pub listener: TcpListener,
+    additional_listeners: Vec<TcpListener>,
     request_pool: utils::thread_pool::ThreadPool,
     pub hide_banner: bool,
     pub address: String,
     router: Arc<router::WebRouter>,
+    pub keep_alive_timeout: Option<Duration>,
+    pub strict_http: bool,
+    pub request_timeout: Option<Duration>,
+    pub body_limit: Option<usize>,
+    pub head_limits: limits::HeadLimits,
+    pub memory_budget: Option<usize>,
+    pub core_map: Option<affinity::CoreMap>,
+    pub proxy_protocol: bool,
+    proxy_protocol_listeners: Vec<SocketAddr>,
+    pub drain_timeout: Duration,
+    shutdown: upgrade::ShutdownHandle,
+    route_registry: router::RouteRegistry,
+    #[cfg(unix)]
+    upgrade_socket: Option<std::path::PathBuf>,
+    #[cfg(unix)]
+    upgrade_command: Option<upgrade::UpgradeCommand>,
+    workers: usize,
+    pub max_connections: usize,
+    pub queue_policy: limits::QueuePolicy,
+    active_connections: Arc<AtomicUsize>,
+    pub accept_error_log: accept::AcceptErrorLog,
+    panic_log: Arc<panics::PanicLog>,
+    overload: Arc<overload::OverloadSignal>,
+    #[cfg(feature = "tls")]
+    tls_config: Option<Arc<rustls::ServerConfig>>,
+    #[cfg(feature = "tls")]
+    accept_plaintext: bool,
 }
 
 impl WebServer {
@@ -83,9 +295,9 @@ impl WebServer {
     /// # Arguments
     ///
     /// - `address` - A `String` representing the address on which the server will listen for
-    /// incoming requests.
+    ///   incoming requests.
     /// - `workers` - A `usize` specifying the  number of worker threads that will be created in
-    /// the thread pool, to which the incoming requets will be distributed.
+    ///   the thread pool, to which the incoming requets will be distributed.
     ///
     /// # Returns
     ///
@@ -97,32 +309,129 @@ impl WebServer {
     ///
     /// # Examples
     ///
-    /// ```rust
+    /// ```rust,no_run
     /// use browzer_web::WebServer;
     ///
     /// let server = WebServer::new("127.0.0.1:8080".to_string(), 4);
     /// server.listen();
     /// ```
     pub fn new(address: String, workers: usize) -> WebServer {
-        let listener = match TcpListener::bind(&address) {
+        // the process this one replaces hands it's listening socket over, see the `upgrade` module
+        #[cfg(unix)]
+        let inherited = upgrade::inherited_listener();
+        #[cfg(not(unix))]
+        let inherited = None;
+        let listener = match inherited.map_or_else(|| TcpListener::bind(&address), Ok) {
             Ok(listener) => listener,
             Err(listener_create_err) => {
                 panic!(
                     "Failed to create listener for the WebServer, Error: {}",
-                    listener_create_err.to_string()
+                    listener_create_err
                 );
             }
         };
+        return WebServer::with_listener(address, listener, workers);
+    }
+
+    /// Creates a new `WebServer` instance listening on a socket which is already bound.
+    ///
+    /// This serves sockets created by someone else, like the ones systemd passes to socket
+    /// activated services or `listenfd` during development, and lets tests bind to port `0`
+    /// before the server is created. Use `WebServer::local_addr` to find out which port was
+    /// picked.
+    ///
+    /// # Arguments
+    ///
+    /// - `listener` - The bound `TcpListener`, it's put into blocking mode if it isn't.
+    /// - `workers` - The number of worker threads, like for `WebServer::new`.
+    ///
+    /// # Returns
+    ///
+    /// - `WebServer` - A new instance of `WebServer`, whose `address` is the local address of the
+    ///   listener.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use browzer_web::WebServer;
+    /// use std::net::TcpListener;
+    ///
+    /// let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    /// let server = WebServer::from_listener(listener, 2);
+    /// let address = server.local_addr().unwrap();
+    /// assert_ne!(address.port(), 0);
+    /// assert_eq!(server.address, address.to_string());
+    /// ```
+    pub fn from_listener(listener: TcpListener, workers: usize) -> WebServer {
+        let address = match listener.local_addr() {
+            Ok(address) => address.to_string(),
+            Err(_) => String::new(),
+        };
+        return WebServer::with_listener(address, listener, workers);
+    }
+
+    // creates the server around it's bound listener
+    fn with_listener(address: String, listener: TcpListener, workers: usize) -> WebServer {
+        // the accept loop waits for connections itself, handed over sockets may be non-blocking
+        if let Err(e) = listener.set_nonblocking(false) {
+            eprintln!(
+                "Warning: the listener couldn't be put into blocking mode, Error: {}",
+                e
+            );
+        }
 
         let request_pool = utils::thread_pool::ThreadPool::new(workers);
 
+        // derive the connections soft cap from the file descriptor limit, and warn if even the
+        // connections handled by the workers at the same time could exhaust it
+        let (fd_soft_limit, _) = limits::fd_limits();
+        if let Some(fd_soft_limit) = fd_soft_limit {
+            let required_fds = workers as u64 * limits::FDS_PER_CONNECTION + limits::RESERVED_FDS;
+            if required_fds > fd_soft_limit {
+                eprintln!(
+                    "Warning: {} workers may need up to {} file descriptors, but the limit of the process is {}, consider raising it(`ulimit -n`) or using fewer workers",
+                    workers, required_fds, fd_soft_limit
+                );
+            }
+        }
+
+        let active_connections = Arc::new(AtomicUsize::new(0));
+
         // return the WebServer struct
         return WebServer {
             listener,
+            additional_listeners: vec![],
             request_pool,
             hide_banner: false,
             address,
             router: Arc::new(router::WebRouter::new()),
+            keep_alive_timeout: Some(Duration::from_secs(5)),
+            strict_http: false,
+            request_timeout: None,
+            body_limit: Some(limits::DEFAULT_MAX_BODY_SIZE),
+            head_limits: limits::HeadLimits::default(),
+            memory_budget: None,
+            core_map: None,
+            proxy_protocol: false,
+            proxy_protocol_listeners: vec![],
+            drain_timeout: upgrade::DEFAULT_DRAIN_TIMEOUT,
+            shutdown: upgrade::ShutdownHandle::default(),
+            route_registry: router::RouteRegistry::new(),
+            #[cfg(unix)]
+            upgrade_socket: None,
+            #[cfg(unix)]
+            upgrade_command: None,
+            workers,
+            max_connections: limits::max_connections_for(fd_soft_limit),
+            queue_policy: limits::QueuePolicy::Block,
+            active_connections: Arc::clone(&active_connections),
+            accept_error_log: accept::AcceptErrorLog::default(),
+            panic_log: Arc::new(panics::PanicLog::default()),
+            overload: Arc::new(overload::OverloadSignal::new(active_connections, workers)),
+            #[cfg(feature = "tls")]
+            tls_config: None,
+            #[cfg(feature = "tls")]
+            accept_plaintext: false,
         };
     }
 
@@ -132,14 +441,18 @@ impl WebServer {
     /// vector, which applies all your registered middlewares to incoming requests one-by-one in
     /// exact order in which you defined those middleware functions
     ///
+    /// Middlewares registered here run for every request, to run a middleware only for some routes
+    /// attach it to those routes using `RouteBuilder::middleware` or to a `RouteGroup` instead.
+    ///
     /// # Arguments
     ///
     /// - `middleware_func` - A closure function containing the functionality of the middleware
-    /// defined by the user
+    ///   defined by the user
     ///
     /// # Examples
     ///
-    /// ```rust
+    /// ```rust,no_run
+    /// # use browzer_web::WebServer;
     /// let mut server = WebServer::new("127.0.0.1:8080".to_string(), 4);
     ///
     /// server.middleware(|mut ctx| {
@@ -171,102 +484,144 @@ impl WebServer {
         };
     }
 
-    /// Registers a new route for handling HTTP GET requests.
+    /// Register a new after-response middleware
     ///
-    /// This method allows you to define a route and associate it with a handler function that
-    /// will be called when a GET request is made to the specified path. The handler function
-    /// should accept a `Context` object and return a `Response` object.
+    /// After-response middlewares run after the route handler, in the order they were registered,
+    /// and transform the final response of every request, including the errors generated by the
+    /// framework itself(like `404 Not Found`). They receive the request without it's body, which
+    /// makes them the place for adding headers(like security headers), logging status codes or
+    /// post-processing bodies.
     ///
     /// # Arguments
     ///
-    /// - `path` - A string slice that holds the path for the route. This is the URL path that will be
-    ///   matched against incoming GET requests.
-    /// - `handler` - A closure or function that takes a `Context` as input and returns a `Response`.
+    /// - `middleware_func` - A closure receiving the request and the response, returning the
+    ///   transformed response
     ///
     /// # Examples
     ///
-    /// ```rust
+    /// ```rust,no_run
+    /// # use browzer_web::WebServer;
     /// let mut server = WebServer::new("127.0.0.1:8080".to_string(), 4);
     ///
-    /// server.get("/hello", |mut ctx| {
-    ///     return ctx.send_string(browzer_web::utils::HttpStatusCode::OK, "Hello, World!");
+    /// server.after_middleware(|req, mut res| {
+    ///     res.headers.insert("X-Content-Type-Options".to_string(), "nosniff".to_string());
+    ///     println!("{} {} -> {}", req.method.to_string(), req.path, res.status_code.code().1);
+    ///     return res;
     /// });
     /// ```
     ///
     /// # Errors
     ///
     /// If the router is not initialized, this method will print an error message using `eprintln!`.
-    ///
-    /// # Panics
-    ///
-    /// This function will not panic under normal conditions. However, if the router is not properly
-    /// initialized, it will log an error.
-    // ----- GET request
-    pub fn get<F>(&mut self, path: &str, handler: F)
+    pub fn after_middleware<F>(&mut self, middleware_func: F)
     where
-        F: Fn(context::Context) -> response::Response + 'static + Send + Sync,
+        F: Fn(&request::Request, response::Response) -> response::Response + 'static + Send + Sync,
     {
-        match Arc::get_mut(&mut self.router) {
-            Some(router) => {
-                match router.add(path.to_string(), utils::HttpMethod::GET, Box::new(handler)) {
-                    Ok(_) => {}
-                    Err(e) => {
-                        eprintln!("{}", e.to_string());
-                    }
-                }
-            }
-            None => eprintln!(
-                "{}",
-                error::WebServerError::InternalServerError(
-                    "WebRouter is not innitialized".to_string()
-                )
-            ),
-        };
+        if let Some(router) = self.router_mut() {
+            router.add_after_middleware(middleware_func);
+        }
     }
-    /// Registers a new route for handling HTTP POST requests.
+
+    /// Add a default response header
     ///
-    /// This method allows you to define a route and associate it with a handler function that
-    /// will be called when a POST request is made to the specified path. The handler function
-    /// should accept a `Context` object and return a `Response` object.
+    /// The header is added to every response which doesn't set it itself, including the errors
+    /// generated by the framework(like `404 Not Found`) and the responses the server sends without
+    /// consulting the router(like `400 Bad Request` for a malformed request, or `503 Service
+    /// Unavailable` for a request which timed out), so common headers like security headers, the
+    /// server name or cache policies don't have to be set in every handler. Default headers are
+    /// added after the after-response middlewares ran, so a handler or middleware setting the
+    /// header(in any case) wins. Routes and route groups can add defaults of their own with
+    /// `RouteBuilder::header` and `RouteGroup::header`, which take precedence over the server's.
     ///
     /// # Arguments
     ///
-    /// - `path` - A string slice that holds the path for the route. This is the URL path that will be
-    ///   matched against incoming POST requests.
-    /// - `handler` - A closure or function that takes a `Context` as input and returns a `Response`.
+    /// - `name` - The name of the header.
+    /// - `value` - The value of the header.
     ///
     /// # Examples
     ///
-    /// ```rust
+    /// ```rust,no_run
+    /// # use browzer_web::WebServer;
     /// let mut server = WebServer::new("127.0.0.1:8080".to_string(), 4);
     ///
-    /// server.post("/submit", |mut ctx| {
-    ///     return ctx.send_string(browzer_web::utils::HttpStatusCode::OK, "Resource submitted!");
-    /// });
+    /// server.default_header("X-Frame-Options", "DENY");
+    /// server.default_header("X-Content-Type-Options", "nosniff");
+    ///
+    /// let mut assets = server.group("/assets");
+    /// assets.header("Cache-Control", "public, max-age=86400");
     /// ```
     ///
     /// # Errors
     ///
-    /// If the router is not initialized or it it fails to register the route using `WebRouter`,
-    /// this method will print an error message using `eprintln!`.
+    /// If the router is not initialized, this method will print an error message using `eprintln!`.
+    pub fn default_header(&mut self, name: &str, value: &str) {
+        if let Some(router) = self.router_mut() {
+            router
+                .default_headers
+                .push((name.to_string(), value.to_string()));
+        }
+    }
+
+    /// Enable response compression
     ///
-    /// # Panics
+    /// Eligible responses(see `CompressionConfig`) are compressed with the best content coding the
+    /// client accepts in it's `Accept-Encoding` header, after all after-response middlewares ran.
+    /// Routes can opt out using `RouteBuilder::no_compress`. Only available with the `compression`
+    /// feature, brotli additionally requires the `brotli` feature.
     ///
-    /// This function will not panic under normal conditions. However, if the router is not properly
-    /// initialized, it will log an error.
-    // ----- POST request
-    pub fn post<F>(&mut self, path: &str, handler: F)
-    where
-        F: Fn(context::Context) -> response::Response + 'static + Send + Sync,
-    {
+    /// # Arguments
+    ///
+    /// - `config` - The `CompressionConfig` deciding which responses are compressed.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// # use browzer_web::{compression::CompressionConfig, WebServer};
+    /// let mut server = WebServer::new("127.0.0.1:8080".to_string(), 4);
+    ///
+    /// server.compression(CompressionConfig::default().min_size(512));
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// If the router is not initialized, this method will print an error message using `eprintln!`.
+    #[cfg(feature = "compression")]
+    pub fn compression(&mut self, config: compression::CompressionConfig) {
+        if let Some(router) = self.router_mut() {
+            router.compression = Some(config);
+        }
+    }
+
+    /// Enable RFC 7807 problem details for framework-generated errors
+    ///
+    /// After calling this method, every error response generated by the framework itself(like `404
+    /// Not Found`, `405 Method Not Allowed`, `400 Bad Request` or `500 Internal Server Error`) is
+    /// sent as an `application/problem+json` body instead of plain text.
+    ///
+    /// # Arguments
+    ///
+    /// - `base_uri` - An optional base URI from which the problem `type` URIs are derived, like
+    ///   `https://example.com/problems` which results in types like
+    ///   `https://example.com/problems/not-found`. If it is `None`, `about:blank` is used.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// # use browzer_web::WebServer;
+    /// let mut server = WebServer::new("127.0.0.1:8080".to_string(), 4);
+    ///
+    /// server.problem_details(Some("https://example.com/problems"));
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// If the router is not initialized, this method will print an error message using `eprintln!`.
+    pub fn problem_details(&mut self, base_uri: Option<&str>) {
         match Arc::get_mut(&mut self.router) {
             Some(router) => {
-                match router.add(path.to_string(), utils::HttpMethod::POST, Box::new(handler)) {
-                    Ok(_) => {}
-                    Err(e) => {
-                        eprintln!("{}", e.to_string());
-                    }
-                }
+                router.problem_details = Some(problem::ProblemConfig {
+                    base_uri: base_uri.map(|uri| uri.to_string()),
+                })
             }
             None => eprintln!(
                 "{}",
@@ -276,320 +631,3459 @@ impl WebServer {
             ),
         };
     }
-    /// Registers a new route for handling HTTP PATCH requests.
+
+    /// Register a handler generating the `404 Not Found` responses
     ///
-    /// This method allows you to define a route and associate it with a handler function that
-    /// will be called when a PATCH request is made to the specified path. The handler function
-    /// should accept a `Context` object and return a `Response` object.
+    /// The handler replaces the plain-text(or problem details) body the framework sends when a
+    /// request matches no registered route, like for serving a branded error page. Responses a
+    /// route handler generates itself are left alone. Whatever status code the handler sets, the
+    /// response is sent as `404 Not Found`.
     ///
     /// # Arguments
     ///
-    /// - `path` - A string slice that holds the path for the route. This is the URL path that will be
-    ///   matched against incoming PATCH requests.
-    /// - `handler` - A closure or function that takes a `Context` as input and returns a `Response`.
+    /// - `handler` - A closure receiving the status code and the request path, which returns the
+    ///   `Response` to send.
     ///
     /// # Examples
     ///
-    /// ```rust
+    /// ```rust,no_run
+    /// # use browzer_web::{response::Response, WebServer};
     /// let mut server = WebServer::new("127.0.0.1:8080".to_string(), 4);
     ///
-    /// server.patch("/update", |mut ctx| {
-    ///     return ctx.send_string(browzer_web::utils::HttpStatusCode::OK, "Resource patched!");
+    /// server.not_found(|status_code, path| {
+    ///     let mut response = Response::new(status_code, format!("<h1>Nothing at {}</h1>", path));
+    ///     response.headers.insert("Content-Type".to_string(), "text/html".to_string());
+    ///     return response;
     /// });
     /// ```
     ///
     /// # Errors
     ///
-    /// If the router is not initialized or it it fails to register the route using `WebRouter`,
-    /// this method will print an error message using `eprintln!`.
-    ///
-    /// # Panics
-    ///
-    /// This function will not panic under normal conditions. However, if the router is not properly
-    /// initialized, it will log an error.
-    // ----- PATCH request
-    pub fn patch<F>(&mut self, path: &str, handler: F)
+    /// If the router is not initialized, this method will print an error message using `eprintln!`.
+    pub fn not_found<F>(&mut self, handler: F)
     where
-        F: Fn(context::Context) -> response::Response + 'static + Send + Sync,
+        F: Fn(utils::HttpStatusCode, &str) -> response::Response + 'static + Send + Sync,
     {
-        match Arc::get_mut(&mut self.router) {
-            Some(router) => {
-                match router.add(
-                    path.to_string(),
-                    utils::HttpMethod::PATCH,
-                    Box::new(handler),
-                ) {
-                    Ok(_) => {}
-                    Err(e) => {
-                        eprintln!("{}", e.to_string());
-                    }
-                }
-            }
-            None => eprintln!(
-                "{}",
-                error::WebServerError::InternalServerError(
-                    "WebRouter is not innitialized".to_string()
-                )
-            ),
-        };
+        if let Some(router) = self.router_mut() {
+            router.not_found_handler = Some(Box::new(handler));
+        }
     }
-    /// Registers a new route for handling HTTP DELETE requests.
+
+    /// Register a handler generating the `405 Method Not Allowed` responses
     ///
-    /// This method allows you to define a route and associate it with a handler function that
-    /// will be called when a DELETE request is made to the specified path. The handler function
-    /// should accept a `Context` object and return a `Response` object.
+    /// The handler is used when a request path matches a registered route path which has no route
+    /// for the request method. The `Allow` header listing the methods it does have is still added
+    /// to the response, and it is sent as `405 Method Not Allowed` whatever status code the handler
+    /// sets.
     ///
     /// # Arguments
     ///
-    /// - `path` - A string slice that holds the path for the route. This is the URL path that will be
-    ///   matched against incoming DELETE requests.
-    /// - `handler` - A closure or function that takes a `Context` as input and returns a `Response`.
+    /// - `handler` - A closure receiving the status code and the request path, which returns the
+    ///   `Response` to send.
     ///
     /// # Examples
     ///
-    /// ```rust
+    /// ```rust,no_run
+    /// # use browzer_web::{response::Response, WebServer};
     /// let mut server = WebServer::new("127.0.0.1:8080".to_string(), 4);
     ///
-    /// server.delete("/remove", |mut ctx|{
-    ///     return ctx.send_string(browzer_web::utils::HttpStatusCode::OK, "Resource deleted!");
+    /// server.method_not_allowed(|status_code, _| {
+    ///     return Response::new(status_code, "This method isn't supported here".to_string());
     /// });
     /// ```
     ///
     /// # Errors
     ///
-    /// If the router is not initialized or it it fails to register the route using `WebRouter`,
-    /// this method will print an error message using `eprintln!`.
-    ///
-    /// # Panics
-    ///
-    /// This function will not panic under normal conditions. However, if the router is not properly
-    /// initialized, it will log an error.
-    // ----- DELETE request
-    pub fn delete<F>(&mut self, path: &str, handler: F)
+    /// If the router is not initialized, this method will print an error message using `eprintln!`.
+    pub fn method_not_allowed<F>(&mut self, handler: F)
     where
-        F: Fn(context::Context) -> response::Response + 'static + Send + Sync,
+        F: Fn(utils::HttpStatusCode, &str) -> response::Response + 'static + Send + Sync,
     {
-        match Arc::get_mut(&mut self.router) {
-            Some(router) => {
-                match router.add(
-                    path.to_string(),
-                    utils::HttpMethod::DELETE,
-                    Box::new(handler),
-                ) {
-                    Ok(_) => {}
-                    Err(e) => {
-                        eprintln!("{}", e.to_string());
-                    }
-                }
-            }
-            None => eprintln!(
-                "{}",
-                error::WebServerError::InternalServerError(
-                    "WebRouter is not innitialized".to_string()
-                )
-            ),
-        };
+        if let Some(router) = self.router_mut() {
+            router.method_not_allowed_handler = Some(Box::new(handler));
+        }
     }
 
-    /// This method serves and maps static files from directory path to a route path
+    /// Register a handler generating the responses for errors detected by the framework
     ///
-    /// This method does it's function by registering a dynamic GET method route to the
-    /// `route_path`, that route's handler function gets the filename of the file that is requested
-    /// from the dynamic route params and then check if a file with that name exists under the
-    /// `dir_path`, if it does then the handler will return a `String` response with that file's
-    /// content as body, it not then it returns a `NotFound`
+    /// The handler is used for every error response the framework generates itself(like `400 Bad
+    /// Request`, `403 Forbidden` for a denied policy, `500 Internal Server Error` or `503 Service
+    /// Unavailable`), and for `404 Not Found` and `405 Method Not Allowed` unless a dedicated
+    /// handler was registered with `WebServer::not_found` or `WebServer::method_not_allowed`. It
+    /// takes precedence over `WebServer::problem_details`, and the response is always sent with the
+    /// status code of the error.
     ///
     /// # Arguments
     ///
-    /// - `dir_path` - A string representing the directory on the machine which the user wants to
-    /// by served on the web app.
-    /// - `route_path` - A string representing the path to which the user wants to map the
-    /// static file directory
+    /// - `handler` - A closure receiving the status code and the request path(empty if the error
+    ///   occurred before the request was parsed), which returns the `Response` to send.
     ///
     /// # Examples
     ///
-    /// ```rust
+    /// ```rust,no_run
+    /// # use browzer_web::{response::Response, WebServer};
     /// let mut server = WebServer::new("127.0.0.1:8080".to_string(), 4);
     ///
-    /// server.serve_static("static","/static/get")
+    /// // a JSON error envelope
+    /// server.error_handler(|status_code, _| {
+    ///     let (reason, code) = status_code.code();
+    ///     let body = format!("{{\"error\":{{\"code\":{},\"message\":\"{}\"}}}}", code, reason);
+    ///     let mut response = Response::new(status_code, body);
+    ///     response.headers.insert("Content-Type".to_string(), "application/json".to_string());
+    ///     return response;
+    /// });
     /// ```
-    pub fn serve_static(&mut self, dir_path: &str, route_path: &str) {
-        let dir_path = Arc::new(dir_path.to_string());
-        let dir_path_clone = Arc::clone(&dir_path);
-        let route = format!("{}/:filename", route_path);
-
-        self.get(&route, move |mut c| {
-            let filename = match c.params.get("filename") {
-                Some(filename) => filename,
-                None => {
-                    // Couldn't get the filename param
-                    return c.send_string(
-                        utils::HttpStatusCode::InternalServerError,
-                        utils::HttpStatusCode::InternalServerError.code().0,
-                    );
-                }
-            };
-            let path = Path::new(&*dir_path_clone).join(filename); // NOTE: I have NO idea what is happening here
-            match path.exists() {
-                true => {
-                    return c.send_string(
-                        utils::HttpStatusCode::OK,
-                        &match fs::read_to_string(path) {
-                            Ok(res) => res,
-                            Err(_) => {
-                                // Couldn't prase the path to string
-                                return c.send_string(
-                                    utils::HttpStatusCode::InternalServerError,
-                                    utils::HttpStatusCode::InternalServerError.code().0,
-                                );
-                            }
-                        },
-                    );
-                }
-                false => {
-                    // filename doesn't exist under the dir_path
-                    return c.send_string(
-                        utils::HttpStatusCode::NotFound,
-                        utils::HttpStatusCode::NotFound.code().0,
-                    );
-                }
-            }
-        });
+    ///
+    /// # Errors
+    ///
+    /// If the router is not initialized, this method will print an error message using `eprintln!`.
+    pub fn error_handler<F>(&mut self, handler: F)
+    where
+        F: Fn(utils::HttpStatusCode, &str) -> response::Response + 'static + Send + Sync,
+    {
+        if let Some(router) = self.router_mut() {
+            router.error_handler = Some(Box::new(handler));
+        }
     }
 
-    /// Listens for incoming TCP connections and execute various functionality on those connections.
+    /// Render the pages generated by the framework from the given templates
     ///
-    /// This method starts the web server, accepting incoming connections and distributing
-    /// them to worker threads for handling. It uses the `request_pool` to manage a pool of
-    /// worker threads and assigns incoming requests to these workers. The function will
-    /// continue to listen for connections indefinitely.
+    /// Error responses the framework generates itself(like `404 Not Found`, `405 Method Not
+    /// Allowed` or `500 Internal Server Error`) are sent as HTML pages rendered from the templates
+    /// instead of plain-text bodies, and the HTML route listing of `WebServer::enable_route_help`
+    /// uses their layout, so all of them carry the branding of the application. Handlers registered
+    /// with `WebServer::not_found`, `WebServer::method_not_allowed` or `WebServer::error_handler`
+    /// and `WebServer::problem_details` take precedence over the error page.
     ///
-    /// # Panics
+    /// # Arguments
     ///
-    /// This function will not panic under normal conditions. However, it will print error
-    /// messages to the standard error output if it encounters issues with establishing connections
-    /// or assigning worker threads.
+    /// - `templates` - The `PageTemplates` to render the pages from.
     ///
     /// # Examples
     ///
-    /// ```rust
+    /// ```rust,no_run
+    /// # use browzer_web::{pages::PageTemplates, WebServer};
     /// let mut server = WebServer::new("127.0.0.1:8080".to_string(), 4);
-    /// server.listen();
+    ///
+    /// server.page_templates(
+    ///     PageTemplates::new()
+    ///         .layout("<!DOCTYPE html>\n<html>\n<head><title>{{title}} | Acme</title></head>\n<body>{{content}}</body>\n</html>\n")
+    ///         .error_page("<h1>{{reason}}</h1>\n<p><a href=\"/\">Back to the homepage</a></p>\n"),
+    /// );
     /// ```
     ///
-    pub fn listen(&self) {
-        // print the server banner( a simple log message ) accoding to the `address` field boolean variable
-        if !self.hide_banner {
-            println!("-----> HTTP server running on {}", self.address);
+    /// # Errors
+    ///
+    /// If the router is not initialized, this method will print an error message using `eprintln!`.
+    pub fn page_templates(&mut self, templates: pages::PageTemplates) {
+        if let Some(router) = self.router_mut() {
+            router.page_templates = Some(templates);
         }
+    }
 
-        // loop over incoming requests and send those request as jobs to the `request_pool` in
-        // order to be distributed to the worker threads
-        for stream in self.listener.incoming() {
-            let router = Arc::clone(&self.router);
-            match stream {
-                Ok(stream) => {
-                    match self.request_pool.execute(|| {
-                        match Self::handle_request(router, stream) {
-                            Ok(_) => {}
-                            Err(e) => {
-                                eprintln!("Failed to handle incoming request, Error: {}", e);
-                            }
-                        };
-                    }) {
-                        Ok(_) => {}
-                        Err(e) => eprintln!(
-                            "Failed to assign Worker thread to incoming request, Error: {}",
-                            e.to_string()
-                        ),
-                    };
-                }
-                Err(e) => {
-                    eprintln!("Failed to establish a connection, Error: {}", e.to_string());
-                }
-            }
+    /// Cache the request paths which matched no route
+    ///
+    /// Scanners probe servers for well-known paths(like `/wp-login.php` or `/.env`) over and over,
+    /// with the cache enabled repeated requests for such a path are answered with `404 Not Found`
+    /// without walking the route tree again. Middlewares still run before the cache is checked,
+    /// so paths they rewrite are cached as rewritten. See `router::NotFoundCache`.
+    ///
+    /// # Arguments
+    ///
+    /// - `capacity` - The maximum number of paths to remember, the oldest path is forgotten once
+    ///   it is reached.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// # use browzer_web::WebServer;
+    /// let mut server = WebServer::new("127.0.0.1:8080".to_string(), 4);
+    ///
+    /// server.cache_not_found(10_000);
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// If the router is not initialized, this method will print an error message using `eprintln!`.
+    pub fn cache_not_found(&mut self, capacity: usize) {
+        if let Some(router) = self.router_mut() {
+            router.not_found_cache = Some(router::NotFoundCache::new(capacity));
         }
     }
 
-    // handles various operations related to incoming requests.
-    fn handle_request(
+    /// Record a sample of the requests for replaying them later
+    ///
+    /// The requests picked by the `replay::Recorder` are written as lines of JSON(NDJSON) with the
+    /// status code they were answered with, after redacting credentials like the `Authorization`
+    /// header. A `replay::Replay` feeds such a recording back through a `WebRouter`, to test new
+    /// code against real traffic or to simulate load. Requests rejected before reaching the
+    /// router, like ones with a malformed request line, aren't recorded.
+    ///
+    /// # Arguments
+    ///
+    /// - `recorder` - The `Recorder`, with the output, sample rate and redaction rules.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// # use browzer_web::{replay::Recorder, WebServer};
+    /// let mut server = WebServer::new("127.0.0.1:8080".to_string(), 4);
+    ///
+    /// // record one in twenty requests, without the API keys of the clients
+    /// let recorder = Recorder::file("traffic.ndjson").unwrap();
+    /// server.record_requests(recorder.sample_rate(0.05).redact_header("X-Api-Key"));
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// If the router is not initialized, this method will print an error message using `eprintln!`.
+    pub fn record_requests(&mut self, recorder: replay::Recorder) {
+        if let Some(router) = self.router_mut() {
+            router.recorder = Some(recorder);
+        }
+    }
+
+    /// Log every request the server answers
+    ///
+    /// Every request is logged with it's method, path, status code, response size and latency,
+    /// once it's final response(compressed, if it is compressed) was generated. Requests rejected
+    /// before reaching the router, like ones with a malformed request line or a body over the
+    /// limit, aren't logged. See `logger::RequestLogger` for the formats and outputs.
+    ///
+    /// # Arguments
+    ///
+    /// - `logger` - The `RequestLogger`.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// # use browzer_web::{logger::{LogFormat, RequestLogger}, WebServer};
+    /// let mut server = WebServer::new("127.0.0.1:8080".to_string(), 4);
+    ///
+    /// server.request_logger(RequestLogger::new(LogFormat::Text).skip_path("/health"));
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// If the router is not initialized, this method will print an error message using `eprintln!`.
+    pub fn request_logger(&mut self, logger: logger::RequestLogger) {
+        if let Some(router) = self.router_mut() {
+            router.request_logger = Some(logger);
+        }
+    }
+
+    /// Enable request IDs
+    ///
+    /// Every request gets an ID before the middlewares run: the one it arrived with in the
+    /// `X-Request-Id` header(set by a reverse proxy or the calling service), or a new random UUID.
+    /// Middlewares and handlers read it with `Context::request_id` to pass it on to the services
+    /// they call, the request logger includes it in every log line, and the final response carries
+    /// it in the same header. See `request_id::RequestIds` for the header and which incoming IDs are
+    /// kept.
+    ///
+    /// # Arguments
+    ///
+    /// - `request_ids` - The `RequestIds` settings.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// # use browzer_web::{request_id::RequestIds, utils::HttpStatusCode, WebServer};
+    /// let mut server = WebServer::new("127.0.0.1:8080".to_string(), 4);
+    ///
+    /// server.request_ids(RequestIds::new());
+    /// server.get("/", |mut c| {
+    ///     let body = format!("request {}", c.request_id().unwrap_or_default());
+    ///     return c.send_string(HttpStatusCode::OK, &body);
+    /// });
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// If the router is not initialized, this method will print an error message using `eprintln!`.
+    pub fn request_ids(&mut self, request_ids: request_id::RequestIds) {
+        if let Some(router) = self.router_mut() {
+            router.request_ids = Some(request_ids);
+        }
+    }
+
+    /// Cache the responses of selected routes in memory
+    ///
+    /// Repeated `GET` and `HEAD` requests for the paths of the cache are answered without running
+    /// their route handler, once the middlewares and policies of the route ran. If the cache has a
+    /// purge endpoint(see `cache::ResponseCache::purge_endpoint`) it is registered as a `POST`
+    /// route, and the URLs to warm up are requested when the server starts listening. See
+    /// `cache::ResponseCache` for which responses are cached.
+    ///
+    /// # Arguments
+    ///
+    /// - `cache` - The `ResponseCache`, a clone of which can be kept for purging entries.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// # use browzer_web::{cache::ResponseCache, WebServer};
+    /// # use std::time::Duration;
+    /// let mut server = WebServer::new("127.0.0.1:8080".to_string(), 4);
+    ///
+    /// let cache = ResponseCache::new(Duration::from_secs(300))
+    ///     .path("/blog/*")
+    ///     .warm_up(&["/blog"])
+    ///     .purge_endpoint("/_cache/purge", "a-long-random-token");
+    /// server.response_cache(cache.clone());
+    ///
+    /// // after publishing a post
+    /// cache.purge("/blog/*");
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// If the router is not initialized, this method will print an error message using `eprintln!`.
+    pub fn response_cache(&mut self, cache: cache::ResponseCache) {
+        if let Some(path) = cache.purge_endpoint_path() {
+            let endpoint = cache.clone();
+            self.post(path, move |c| {
+                return endpoint.handle_purge(&c);
+            });
+        }
+        if let Some(router) = self.router_mut() {
+            router.response_cache = Some(cache);
+        }
+    }
+
+    /// Redirect requests to the canonical origin of the site
+    ///
+    /// Requests arriving with another scheme or host name(like `http://www.example.com` for a
+    /// canonical origin of `https://example.com`) are redirected to the same path and query on the
+    /// canonical origin, before any middleware runs. See `canonical::CanonicalOrigin` for the
+    /// status codes used and running behind a reverse proxy.
+    ///
+    /// # Arguments
+    ///
+    /// - `origin` - The `CanonicalOrigin` of the site.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// # use browzer_web::{canonical::CanonicalOrigin, WebServer};
+    /// let mut server = WebServer::new("127.0.0.1:8080".to_string(), 4);
+    ///
+    /// // behind a reverse proxy terminating TLS
+    /// server.canonical_origin(
+    ///     CanonicalOrigin::parse("https://example.com")
+    ///         .unwrap()
+    ///         .trust_forwarded_proto(true),
+    /// );
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// If the router is not initialized, this method will print an error message using `eprintln!`.
+    pub fn canonical_origin(&mut self, origin: canonical::CanonicalOrigin) {
+        if let Some(router) = self.router_mut() {
+            router.canonical_origin = Some(origin);
+        }
+    }
+
+    /// Enable cross-origin resource sharing(CORS)
+    ///
+    /// Preflight requests are answered according to the policy before any middleware runs, and
+    /// the responses to all other requests from allowed origins get the `Access-Control-*`
+    /// headers, before the after-response middlewares run. See `cors::Cors` for the policy
+    /// settings.
+    ///
+    /// # Arguments
+    ///
+    /// - `policy` - The `Cors` policy.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// # use browzer_web::{cors::Cors, utils::HttpMethod, WebServer};
+    /// let mut server = WebServer::new("127.0.0.1:8080".to_string(), 4);
+    ///
+    /// server.cors(
+    ///     Cors::new()
+    ///         .allow_origin("https://app.example.com")
+    ///         .allow_methods(&[HttpMethod::GET, HttpMethod::PUT])
+    ///         .allow_headers(&["Content-Type"]),
+    /// );
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// If the router is not initialized, this method will print an error message using `eprintln!`.
+    pub fn cors(&mut self, policy: cors::Cors) {
+        if let Some(router) = self.router_mut() {
+            router.cors = Some(policy);
+        }
+    }
+
+    /// Enable sessions
+    ///
+    /// Every request gets it's session loaded from the store before the middlewares run, handlers
+    /// and middlewares access it with `Context::session`. Changes are stored once the response was
+    /// generated, before the after-response middlewares run. See `sessions::Sessions` for the
+    /// session cookie and expiry settings.
+    ///
+    /// # Arguments
+    ///
+    /// - `config` - The `Sessions` configuration, including the `SessionStore` sessions are kept
+    ///   in.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// # use browzer_web::{sessions::{MemorySessionStore, Sessions}, WebServer};
+    /// # use std::time::Duration;
+    /// let mut server = WebServer::new("127.0.0.1:8080".to_string(), 4);
+    ///
+    /// server.sessions(Sessions::new(MemorySessionStore::new(), Duration::from_secs(30 * 60)));
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// If the router is not initialized, this method will print an error message using `eprintln!`.
+    pub fn sessions(&mut self, config: sessions::Sessions) {
+        if let Some(router) = self.router_mut() {
+            router.sessions = Some(config);
+        }
+    }
+
+    /// Serve a listing of all registered routes
+    ///
+    /// The listing contains the method, path, parameters and description(see
+    /// `RouteBuilder::doc`) of every route, as an HTML page or as JSON for clients asking for it in
+    /// their `Accept` header or with a `format=json` query parameter. Routes registered after
+    /// calling this method are listed too. The listing reveals the whole API surface, so only
+    /// enable it where that's fine, like on internal or development servers.
+    ///
+    /// # Arguments
+    ///
+    /// - `path` - The path to serve the listing under, like `/_routes`.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// # use browzer_web::WebServer;
+    /// let mut server = WebServer::new("127.0.0.1:8080".to_string(), 4);
+    ///
+    /// server.enable_route_help("/_routes");
+    /// server
+    ///     .get("/users/:id", |mut c| {
+    ///         return c.send_string(browzer_web::utils::HttpStatusCode::OK, "user");
+    ///     })
+    ///     .doc("Returns the user with the given id");
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// If the router is not initialized or the path is invalid, this method will print an error
+    /// message using `eprintln!`.
+    pub fn enable_route_help(&mut self, path: &str) {
+        let path = match utils::format_path_by_slashes(path.to_string()) {
+            Ok(path) => path,
+            Err(e) => {
+                eprintln!("Failed to enable the route help, Error: {}", e);
+                return;
+            }
+        };
+        if let Some(router) = self.router_mut() {
+            router.route_help = Some(path);
+        }
+    }
+
+    /// Serve a liveness check for load balancers and orchestrators
+    ///
+    /// `GET` requests to the path run the check and are answered with a JSON body like
+    /// `{"status":"ok","checks":{"app":{"status":"ok"}}}`, with `200 OK` if the check passed(or
+    /// is only degraded) and `503 Service Unavailable` if it failed. A failing liveness check
+    /// usually gets the process restarted, so it should only fail if the process can't recover on
+    /// it's own. See `readiness_check` for taking the instance out of rotation instead.
+    ///
+    /// # Arguments
+    ///
+    /// - `path` - The path of the check, like `/healthz`.
+    /// - `check` - Reports the status of the application, as the `app` check.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// # use browzer_web::{health::HealthStatus, WebServer};
+    /// let mut server = WebServer::new("127.0.0.1:8080".to_string(), 4);
+    ///
+    /// server.health_check("/healthz", || HealthStatus::Healthy);
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// If the router is not initialized or it fails to register the route, this method will
+    /// print an error message using `eprintln!`.
+    pub fn health_check<F>(&mut self, path: &str, check: F) -> router::RouteBuilder<'_>
+    where
+        F: Fn() -> health::HealthStatus + 'static + Send + Sync,
+    {
+        return self.serve_health_check(path, health::HealthCheck::new().check("app", check));
+    }
+
+    /// Serve a readiness check for load balancers and orchestrators
+    ///
+    /// Like `health_check`, but the check also fails while the server is stopping, or while
+    /// every worker is busy and connections are waiting for one(see
+    /// `health::HealthCheck::server_checks`). Load balancers stop sending new requests to the
+    /// instance until it's ready again, without restarting it.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// # use browzer_web::{health::HealthStatus, WebServer};
+    /// # fn database_reachable() -> bool { true }
+    /// let mut server = WebServer::new("127.0.0.1:8080".to_string(), 4);
+    ///
+    /// server.readiness_check("/readyz", || match database_reachable() {
+    ///     true => HealthStatus::Healthy,
+    ///     false => HealthStatus::Unhealthy("the database is unreachable".to_string()),
+    /// });
+    /// ```
+    pub fn readiness_check<F>(&mut self, path: &str, check: F) -> router::RouteBuilder<'_>
+    where
+        F: Fn() -> health::HealthStatus + 'static + Send + Sync,
+    {
+        let health = health::HealthCheck::new()
+            .server_checks()
+            .check("app", check);
+        return self.serve_health_check(path, health);
+    }
+
+    /// Serve the results of a `HealthCheck` with several named checks, like `health_check`.
+    pub fn serve_health_check(
+        &mut self,
+        path: &str,
+        mut health: health::HealthCheck,
+    ) -> router::RouteBuilder<'_> {
+        health.attach(self.request_pool.queue(), self.shutdown.clone());
+        return self.get(path, move |c| health.handle(c));
+    }
+
+    /// Serve metrics of the server in the Prometheus text format
+    ///
+    /// Every request the server answers is counted by it's route path pattern(like
+    /// `/users/:id`), method and status code, and it's latency is recorded in a histogram. `GET`
+    /// requests to the path are answered with these metrics, along with the number of open
+    /// connections, the connections waiting for a worker thread, the worker threads and the
+    /// worker panics, see `metrics::Metrics` for the names. Like other routes, the path can be
+    /// protected with a policy through the returned `RouteBuilder`.
+    ///
+    /// # Arguments
+    ///
+    /// - `path` - The path to serve the metrics under, like `/metrics`.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// # use browzer_web::WebServer;
+    /// let mut server = WebServer::new("127.0.0.1:8080".to_string(), 4);
+    ///
+    /// server.enable_metrics("/metrics");
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// If the router is not initialized or it fails to register the route, this method will
+    /// print an error message using `eprintln!`.
+    pub fn enable_metrics(&mut self, path: &str) -> router::RouteBuilder<'_> {
+        return self.serve_metrics(path, metrics::Metrics::new());
+    }
+
+    /// Serve metrics of the server in the Prometheus text format, like `enable_metrics` but with
+    /// the given `Metrics`(like one with other latency histogram buckets).
+    pub fn serve_metrics(
+        &mut self,
+        path: &str,
+        mut metrics: metrics::Metrics,
+    ) -> router::RouteBuilder<'_> {
+        metrics.attach(
+            Arc::clone(&self.active_connections),
+            self.request_pool.queue(),
+            Arc::clone(&self.panic_log),
+        );
+        let metrics = Arc::new(metrics);
+        if let Some(router) = self.router_mut() {
+            router.metrics = Some(Arc::clone(&metrics));
+        }
+        return self.get(path, move |mut c| {
+            c.response.headers.insert(
+                "Content-Type".to_string(),
+                metrics::CONTENT_TYPE.to_string(),
+            );
+            c.response
+                .headers
+                .insert("Cache-Control".to_string(), "no-store".to_string());
+            return c.send_string(utils::HttpStatusCode::OK, &metrics.render());
+        });
+    }
+
+    /// Serve a JSON reflection of every request sent to a path
+    ///
+    /// Requests of any method to the path, or to a path below it, are answered with a JSON
+    /// description of how the server parsed them: the method, path, decoded parameters, headers,
+    /// cookies, client address and a preview of the body(see `echo::DebugEcho`). It's useful for
+    /// verifying what a reverse proxy forwards and what a client actually sends. The reflection
+    /// reveals credentials like cookies and `Authorization` headers to whoever sends them, and
+    /// the headers added by proxies in front of the server, so only enable it during development.
+    ///
+    /// # Arguments
+    ///
+    /// - `path` - The path to serve the reflection under, like `/debug/echo`.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// # use browzer_web::WebServer;
+    /// let mut server = WebServer::new("127.0.0.1:8080".to_string(), 4);
+    ///
+    /// // `curl -d 'hello' 'localhost:8080/debug/echo/a/b?page=2'`
+    /// server.debug_echo("/debug/echo");
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// If the router is not initialized or it fails to register the routes, this method will
+    /// print an error message using `eprintln!`.
+    pub fn debug_echo(&mut self, path: &str) {
+        self.serve_debug_echo(path, echo::DebugEcho::new());
+    }
+
+    /// Serve a JSON reflection of every request sent to a path, like `debug_echo` but with the
+    /// given `DebugEcho`(like one previewing more of the request body).
+    pub fn serve_debug_echo(&mut self, path: &str, echo: echo::DebugEcho) {
+        let echo = Arc::new(echo);
+        let rest_path = format!("{}/*rest", path.trim_end_matches('/'));
+        let methods = [
+            utils::HttpMethod::GET,
+            utils::HttpMethod::POST,
+            utils::HttpMethod::PUT,
+            utils::HttpMethod::PATCH,
+            utils::HttpMethod::DELETE,
+            utils::HttpMethod::HEAD,
+            utils::HttpMethod::OPTIONS,
+        ];
+        for route_path in [path, &rest_path] {
+            for method in methods.iter() {
+                let echo = Arc::clone(&echo);
+                let registered =
+                    self.register_route(route_path, method.clone(), move |c| echo.handle(c));
+                router::RouteBuilder::or_log(registered);
+            }
+        }
+    }
+
+    /// Forward requests sent to a path to an upstream HTTP server
+    ///
+    /// Requests of any method to the path are forwarded with their headers and body to the
+    /// upstream, and answered with it's response. Both bodies are streamed while they arrive(see
+    /// `RouteBuilder::stream_body`), so neither is held in memory as a whole. The
+    /// part of the path matched by a trailing wildcard segment(like `*path` in `/api/*path`) is
+    /// appended to the path of the upstream, without one the whole request path is. The upstream
+    /// is told about the client with the `X-Forwarded-For`, `X-Forwarded-Proto` and
+    /// `X-Forwarded-Host` headers, and connections to it are reused across requests(see
+    /// `reverse_proxy::ReverseProxy`).
+    ///
+    /// # Arguments
+    ///
+    /// - `path` - The path to forward requests from, like `/api/*path`.
+    /// - `upstream` - The URL of the upstream server, like `http://127.0.0.1:9000`.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// # use browzer_web::WebServer;
+    /// let mut server = WebServer::new("127.0.0.1:8080".to_string(), 4);
+    ///
+    /// // `GET /api/users?page=2` is forwarded as `GET /v1/users?page=2`
+    /// server.proxy("/api/*path", "http://127.0.0.1:9000/v1");
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// If the upstream isn't an `http://` URL(or an `https://` one with the `tls` feature), the
+    /// router is not initialized or it fails to register the routes, this method will print an
+    /// error message using `eprintln!`.
+    pub fn proxy(&mut self, path: &str, upstream: &str) {
+        match reverse_proxy::ReverseProxy::new(upstream) {
+            Ok(proxy) => self.serve_proxy(path, proxy),
+            Err(e) => eprintln!("{}", e),
+        }
+    }
+
+    /// Forward requests sent to a path to an upstream HTTP server, like `proxy` but with the
+    /// given `ReverseProxy`(like one with a shorter timeout).
+    pub fn serve_proxy(&mut self, path: &str, mut proxy: reverse_proxy::ReverseProxy) {
+        if let Some(name) = path
+            .rsplit('/')
+            .next()
+            .and_then(|segment| segment.strip_prefix('*'))
+        {
+            proxy.default_path_param(name);
+        }
+        let proxy = Arc::new(proxy);
+        let methods = [
+            utils::HttpMethod::GET,
+            utils::HttpMethod::POST,
+            utils::HttpMethod::PUT,
+            utils::HttpMethod::PATCH,
+            utils::HttpMethod::DELETE,
+            utils::HttpMethod::HEAD,
+            utils::HttpMethod::OPTIONS,
+        ];
+        for method in methods.iter() {
+            let proxy = Arc::clone(&proxy);
+            // the body is forwarded while it arrives, instead of after it was read completely
+            router::RouteBuilder::or_log(
+                self.register_route(path, method.clone(), move |c| proxy.handle(c)),
+            )
+            .stream_body();
+        }
+    }
+
+    /// Restrict the host names the server answers requests for
+    ///
+    /// A browser visiting a malicious site can be made to send requests to a server listening on
+    /// `localhost` or a private network by rebinding the site's DNS name to that address(DNS
+    /// rebinding). Such requests still carry the attacker's host name in their `Host` header, so
+    /// only accepting known host names protects development servers and internal APIs from them.
+    ///
+    /// Requests without a `Host` header get a `400 Bad Request` response, requests for any other
+    /// host get a `421 Misdirected Request` response. An entry starting with a dot, like
+    /// `.example.com`, matches the domain itself and all of it's subdomains. Ports are ignored.
+    ///
+    /// # Arguments
+    ///
+    /// - `hosts` - The host names the server answers requests for.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// # use browzer_web::WebServer;
+    /// let mut server = WebServer::new("127.0.0.1:8080".to_string(), 4);
+    ///
+    /// server.allowed_hosts(&["localhost", "127.0.0.1", ".example.com"]);
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// If the router is not initialized, this method will print an error message using `eprintln!`.
+    pub fn allowed_hosts(&mut self, hosts: &[&str]) {
+        if let Some(router) = self.router_mut() {
+            router.allowed_hosts = Some(hosts.iter().map(|host| host.to_string()).collect());
+        }
+    }
+
+    /// Expect a PROXY protocol header at the start of every connection
+    ///
+    /// TCP(layer 4) load balancers like HAProxy or AWS NLB hide the address of the client from the
+    /// server, unless they announce it with a PROXY protocol(version 1 or 2) header before
+    /// forwarding the connection. With this enabled the header is read first and it's source
+    /// address is the one `Context::remote_addr` returns, so rate limiting and logs see the real
+    /// client. Connections without a valid header are closed without a response.
+    ///
+    /// Only enable it if all connections come through a load balancer sending the header,
+    /// otherwise clients connecting directly could claim any address. For a server which is also
+    /// reached directly, use `WebServer::proxy_protocol_on` for the listener of the load balancer
+    /// only.
+    ///
+    /// # Arguments
+    ///
+    /// - `enabled` - Whether connections start with a PROXY protocol header.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// # use browzer_web::{utils::HttpStatusCode, WebServer};
+    /// let mut server = WebServer::new("127.0.0.1:8080".to_string(), 4);
+    ///
+    /// server.proxy_protocol(true);
+    /// server.get("/ip", |mut c| {
+    ///     let ip = c.remote_addr().map(|addr| addr.ip().to_string()).unwrap_or_default();
+    ///     return c.send_string(HttpStatusCode::OK, &ip);
+    /// });
+    /// server.listen();
+    /// ```
+    pub fn proxy_protocol(&mut self, enabled: bool) {
+        self.proxy_protocol = enabled;
+    }
+
+    /// Expect a PROXY protocol header at the start of the connections of one listener
+    ///
+    /// Works like `WebServer::proxy_protocol`, but only for the connections accepted by the
+    /// listener bound to `address`, so a server can take connections from a load balancer on one
+    /// port and from clients connecting directly on another.
+    ///
+    /// # Arguments
+    ///
+    /// - `address` - The local address of the listener, as returned by `WebServer::bind` or
+    ///   `WebServer::local_addr`.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// # use browzer_web::WebServer;
+    /// let mut server = WebServer::new("0.0.0.0:8080".to_string(), 4);
+    ///
+    /// // the load balancer forwards to port 8081, internal clients connect to port 8080
+    /// let balanced = server.bind("0.0.0.0:8081").unwrap();
+    /// server.proxy_protocol_on(balanced);
+    /// server.listen();
+    /// ```
+    pub fn proxy_protocol_on(&mut self, address: SocketAddr) {
+        self.proxy_protocol_listeners.push(address);
+    }
+
+    /// Trust the forwarding headers of reverse proxies
+    ///
+    /// Behind reverse proxies like nginx the address of the connection is the one of the nearest
+    /// proxy, which passes the address of the client on in a `Forwarded` or `X-Forwarded-For`
+    /// header. Those headers are only believed for requests coming from the given proxies, and
+    /// `Context::client_ip` returns the address of the client they name, see
+    /// `forwarded::TrustedProxies`.
+    ///
+    /// # Arguments
+    ///
+    /// - `proxies` - The `TrustedProxies`.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// # use browzer_web::{forwarded::TrustedProxies, utils::HttpStatusCode, WebServer};
+    /// let mut server = WebServer::new("127.0.0.1:8080".to_string(), 4);
+    ///
+    /// server.trusted_proxies(TrustedProxies::parse(&["10.0.0.0/8"]).unwrap());
+    /// server.get("/ip", |mut c| {
+    ///     let ip = c.client_ip().map(|ip| ip.to_string()).unwrap_or_default();
+    ///     return c.send_string(HttpStatusCode::OK, &ip);
+    /// });
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// If the router is not initialized, this method will print an error message using `eprintln!`.
+    pub fn trusted_proxies(&mut self, proxies: forwarded::TrustedProxies) {
+        if let Some(router) = self.router_mut() {
+            router.trusted_proxies = Some(Arc::new(proxies));
+        }
+    }
+
+    /// Limit the rate of requests per client
+    ///
+    /// Every request takes a token from the bucket of it's client, right after the host allowlist
+    /// and canonical origin redirects were checked and before any middleware runs. A client whose
+    /// bucket is empty gets a `429 Too Many Requests` response with a `Retry-After` header instead.
+    /// Clients are told apart by their IP address unless the `RateLimit` extracts a custom key,
+    /// behind reverse proxies set `WebServer::trusted_proxies` so the address of the client is used
+    /// rather than the one of the proxy. See `rate_limit::RateLimit` for the quota and the store
+    /// the buckets are kept in.
+    ///
+    /// # Arguments
+    ///
+    /// - `rate_limit` - The `RateLimit`.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// # use browzer_web::{rate_limit::RateLimit, utils::HttpStatusCode, WebServer};
+    /// # use std::time::Duration;
+    /// let mut server = WebServer::new("127.0.0.1:8080".to_string(), 4);
+    ///
+    /// // 60 requests a minute, in bursts of up to 60 requests
+    /// server.rate_limit(RateLimit::new(60, Duration::from_secs(60)));
+    /// server.get("/", |mut c| c.send_string(HttpStatusCode::OK, "Hello"));
+    /// server.listen();
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// If the router is not initialized, this method will print an error message using `eprintln!`.
+    pub fn rate_limit(&mut self, rate_limit: rate_limit::RateLimit) {
+        if let Some(router) = self.router_mut() {
+            router.rate_limit = Some(rate_limit);
+        }
+    }
+
+    /// Profile a sample of the requests phase by phase
+    ///
+    /// For the fraction of requests picked by the `sampling::Sampler`, the time spent waiting for a
+    /// worker, parsing, in middlewares, in the route handler, serializing and writing the response
+    /// is measured and passed to the sink of the sampler as a `sampling::RequestProfile`. The
+    /// requests which aren't sampled aren't timed, so the overhead stays low in production.
+    ///
+    /// # Arguments
+    ///
+    /// - `sampler` - The `Sampler`, with the sample rate and the sink of the profiles.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// # use browzer_web::{sampling::Sampler, utils::HttpStatusCode, WebServer};
+    /// let mut server = WebServer::new("127.0.0.1:8080".to_string(), 4);
+    ///
+    /// // profile one in a hundred requests
+    /// server.sample_requests(Sampler::new(0.01, |profile| {
+    ///     eprintln!("{} took {:?}: {:?}", profile.path, profile.total(), profile.phases());
+    /// }));
+    /// server.get("/", |mut c| c.send_string(HttpStatusCode::OK, "Hello"));
+    /// server.listen();
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// If the router is not initialized, this method will print an error message using `eprintln!`.
+    pub fn sample_requests(&mut self, sampler: sampling::Sampler) {
+        if let Some(router) = self.router_mut() {
+            router.sampler = Some(sampler);
+        }
+    }
+
+    /// Compute and verify content digests of files and uploads
+    ///
+    /// Files sent with `Context::send_file`(including the ones served by `serve_static`) carry
+    /// `Repr-Digest` and `Digest` headers(and `Content-MD5` if the set contains `digest::Md5`, see
+    /// the `md5` feature) with the digests of their contents, computed once per version of a file.
+    /// The `upload::UploadReceiver` verifies the digests clients send with an upload, answering
+    /// `422 Unprocessable Entity` on a mismatch, and reports the digests of the files it stored.
+    /// See the `digest` module for the headers and how to plug in other algorithms.
+    ///
+    /// # Arguments
+    ///
+    /// - `digests` - The `DigestSet` with the algorithms to use, `DigestSet::default()` for
+    ///   `sha-256`.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// # use browzer_web::{digest::{DigestSet, Sha1, Sha256}, WebServer};
+    /// let mut server = WebServer::new("127.0.0.1:8080".to_string(), 4);
+    ///
+    /// // older download managers only check SHA-1 digests
+    /// server.content_digests(DigestSet::new().with(Sha256).with(Sha1));
+    /// server.serve_static("./public", "/files");
+    /// server.listen();
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// If the router is not initialized, this method will print an error message using `eprintln!`.
+    pub fn content_digests(&mut self, digests: digest::DigestSet) {
+        if let Some(router) = self.router_mut() {
+            router.content_digests = Some(Arc::new(digests));
+        }
+    }
+
+    /// Register the HTML templates handlers render
+    ///
+    /// Handlers render a template with `Context::render`, see the `templates` module for their
+    /// syntax.
+    ///
+    /// # Arguments
+    ///
+    /// - `templates` - The `Templates`, usually loaded from a directory with `Templates::from_dir`.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// # use browzer_web::{templates::Templates, WebServer};
+    /// use serde_json::json;
+    ///
+    /// let mut server = WebServer::new("127.0.0.1:8080".to_string(), 4);
+    ///
+    /// server.templates(Templates::from_dir("./templates").unwrap());
+    /// server.get("/", |mut c| {
+    ///     return c.render("index.html", &json!({ "title": "Home" }));
+    /// });
+    /// server.listen();
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// If the router is not initialized, this method will print an error message using `eprintln!`.
+    pub fn templates(&mut self, templates: templates::Templates) {
+        if let Some(router) = self.router_mut() {
+            router.templates = Some(Arc::new(templates));
+        }
+    }
+
+    /// Creates a new `WebServer` instance which serves HTTPS.
+    ///
+    /// Works exactly like `WebServer::new`, except that every accepted connection is wrapped into a
+    /// TLS stream using the given certificate chain and private key, so the same routes and
+    /// handlers serve HTTPS. Only available with the `tls` feature.
+    ///
+    /// # Arguments
+    ///
+    /// - `address` - A `String` representing the address on which the server will listen for
+    ///   incoming requests.
+    /// - `workers` - A `usize` specifying the number of worker threads.
+    /// - `cert_path` - The path to the PEM file containing the certificate chain.
+    /// - `key_path` - The path to the PEM file containing the private key.
+    ///
+    /// # Returns
+    ///
+    /// - `WebServer` - A new instance of `WebServer`.
+    ///
+    /// # Panics
+    ///
+    /// This function will panic if it fails to bind the `TcpListener` to the provided address, or
+    /// if the certificate chain or private key can't be loaded.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// use browzer_web::WebServer;
+    ///
+    /// let server = WebServer::new_tls("127.0.0.1:8443".to_string(), 4, "cert.pem", "key.pem");
+    /// server.listen();
+    /// ```
+    #[cfg(feature = "tls")]
+    pub fn new_tls(address: String, workers: usize, cert_path: &str, key_path: &str) -> WebServer {
+        let tls_config = match tls::load_server_config(cert_path, key_path) {
+            Ok(tls_config) => tls_config,
+            Err(e) => panic!("Failed to load the TLS configuration, Error: {}", e),
+        };
+        let mut server = WebServer::new(address, workers);
+        server.tls_config = Some(tls_config);
+        if let Some(router) = server.router_mut() {
+            router.tls = true;
+        }
+        return server;
+    }
+
+    /// Serve plain HTTP and HTTPS on the same port
+    ///
+    /// The first bytes of every connection are inspected before anything is read, a connection
+    /// starting with a TLS handshake(a `ClientHello` record) is served over HTTPS and any other one
+    /// as plain HTTP, so clients which got the scheme wrong(like internal tools configured with
+    /// `http://` for an HTTPS server) still reach the same routes. The scheme a request arrived
+    /// with is available as `Request::tls`. Only has an effect on servers created with
+    /// `WebServer::new_tls`, and only available with the `tls` feature.
+    ///
+    /// Requests arriving as plain HTTP are not encrypted, combine it with
+    /// `WebServer::canonical_origin` to redirect them to HTTPS instead of serving them.
+    ///
+    /// # Arguments
+    ///
+    /// - `enabled` - Whether plaintext connections are accepted.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// use browzer_web::WebServer;
+    ///
+    /// let mut server = WebServer::new_tls("127.0.0.1:8443".to_string(), 4, "cert.pem", "key.pem");
+    /// server.accept_plaintext(true);
+    /// server.listen();
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// If the router is not initialized, this method will print an error message using `eprintln!`.
+    #[cfg(feature = "tls")]
+    pub fn accept_plaintext(&mut self, enabled: bool) {
+        self.accept_plaintext = enabled;
+        let tls = self.tls_config.is_some() && !enabled;
+        if let Some(router) = self.router_mut() {
+            // the scheme is decided per connection, see `Request::tls`
+            router.tls = tls;
+        }
+    }
+
+    /// Returns the resource limits the server runs with
+    ///
+    /// The file descriptor limits are queried again on every call, the connection numbers are
+    /// the ones the server currently uses.
+    ///
+    /// # Returns
+    ///
+    /// - `ServerLimits` - The file descriptor limits, the number of workers, the connections soft
+    ///   cap and the number of currently open connections.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// # use browzer_web::WebServer;
+    /// let server = WebServer::new("127.0.0.1:8080".to_string(), 4);
+    ///
+    /// println!("{:?}", server.limits());
+    /// ```
+    pub fn limits(&self) -> limits::ServerLimits {
+        let (fd_soft_limit, fd_hard_limit) = limits::fd_limits();
+        return limits::ServerLimits {
+            fd_soft_limit,
+            fd_hard_limit,
+            workers: self.request_pool.size(),
+            max_workers: self.request_pool.max_workers(),
+            max_connections: self.max_connections,
+            active_connections: self.active_connections.load(Ordering::SeqCst),
+            queued_connections: self.request_pool.queued(),
+            queue_capacity: self.request_pool.queue_capacity(),
+        };
+    }
+
+    /// Set the maximum size of request bodies
+    ///
+    /// A request announcing a larger body in it's `Content-Length` header is answered with `413
+    /// Payload Too Large` and the connection is closed, before any of the body is read or memory
+    /// is allocated for it. The limit defaults to `limits::DEFAULT_MAX_BODY_SIZE`, routes which
+    /// need a different one(like uploads) can override it with `RouteBuilder::body_limit`.
+    ///
+    /// # Arguments
+    ///
+    /// - `bytes` - The maximum size of a request body in bytes.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// # use browzer_web::WebServer;
+    /// let mut server = WebServer::new("127.0.0.1:8080".to_string(), 4);
+    ///
+    /// server.set_max_body_size(64 * 1024);
+    /// ```
+    pub fn set_max_body_size(&mut self, bytes: usize) {
+        self.body_limit = Some(bytes);
+    }
+
+    /// Set the limits of request heads
+    ///
+    /// The head of a request is read until it exceeds a limit at most, a request whose request
+    /// line is too long is answered with `400 Bad Request`, one with too many or too large headers
+    /// with `431 Request Header Fields Too Large`, and the connection is closed. The defaults(see
+    /// `limits::HeadLimits`) fit any regular client, applications behind a proxy adding lots of
+    /// headers or accepting huge cookies may need to raise them.
+    ///
+    /// # Arguments
+    ///
+    /// - `head_limits` - The limits of the request line, the number of headers and the whole head.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// # use browzer_web::{WebServer, limits::HeadLimits};
+    /// let mut server = WebServer::new("127.0.0.1:8080".to_string(), 4);
+    ///
+    /// server.set_head_limits(HeadLimits {
+    ///     max_head_size: 16 * 1024,
+    ///     ..HeadLimits::default()
+    /// });
+    /// ```
+    pub fn set_head_limits(&mut self, head_limits: limits::HeadLimits) {
+        self.head_limits = head_limits;
+    }
+
+    /// Set the memory budget of requests
+    ///
+    /// The memory attributable to a request is tracked approximately, as the bytes of it's parsed
+    /// head, it's body and it's buffered response(see `limits::MemoryUsage`). A request whose head
+    /// and announced body already exceed the budget is answered with `413 Payload Too Large`
+    /// before it's body is read, and one whose response pushes it over the budget with `500
+    /// Internal Server Error` instead of that response. Both are logged using `eprintln!`. This
+    /// protects small instances from pathological payloads, like a body just under the body limit
+    /// whose handler echoes it many times over. Routes can override the budget with
+    /// `RouteBuilder::memory_budget`.
+    ///
+    /// # Arguments
+    ///
+    /// - `bytes` - The memory budget of a request in bytes.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// # use browzer_web::WebServer;
+    /// let mut server = WebServer::new("127.0.0.1:8080".to_string(), 4);
+    ///
+    /// server.set_memory_budget(256 * 1024);
+    /// ```
+    pub fn set_memory_budget(&mut self, bytes: usize) {
+        self.memory_budget = Some(bytes);
+    }
+
+    /// Set a setting of the server from a human-friendly configuration value
+    ///
+    /// This is the loader of configuration files and environment variables(see
+    /// `WebServer::configure_from_env`), sizes are parsed with `utils::parse_size` and durations
+    /// with `utils::parse_duration`. The settings which can be disabled(all but `drain_timeout`)
+    /// are disabled with `none`.
+    ///
+    /// | Name | Value | Setting |
+    /// |------|-------|---------|
+    /// | `body_limit` | size | `body_limit`, see `WebServer::set_max_body_size` |
+    /// | `memory_budget` | size | `memory_budget`, see `WebServer::set_memory_budget` |
+    /// | `keep_alive_timeout` | duration | `keep_alive_timeout` |
+    /// | `request_timeout` | duration | `request_timeout` |
+    /// | `drain_timeout` | duration | `drain_timeout` |
+    ///
+    /// # Arguments
+    ///
+    /// - `name` - The name of the setting, case-insensitive.
+    /// - `value` - The value of the setting, like `10MB` or `30s`.
+    ///
+    /// # Returns
+    ///
+    /// - `Result<(), ConfigValueError>` - An `UnknownSetting` error for a name which isn't listed
+    ///   above, or an `InvalidSize`/`InvalidDuration` error for a value which can't be parsed. The
+    ///   setting is left unchanged on error.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// # use browzer_web::WebServer;
+    /// # use std::time::Duration;
+    /// let mut server = WebServer::new("127.0.0.1:8080".to_string(), 4);
+    ///
+    /// server.configure("body_limit", "10MB").unwrap();
+    /// server.configure("request_timeout", "1m30s").unwrap();
+    /// server.configure("keep_alive_timeout", "none").unwrap();
+    ///
+    /// assert_eq!(server.body_limit, Some(10_000_000));
+    /// assert_eq!(server.request_timeout, Some(Duration::from_secs(90)));
+    /// assert_eq!(server.keep_alive_timeout, None);
+    /// ```
+    pub fn configure(&mut self, name: &str, value: &str) -> Result<(), error::ConfigValueError> {
+        let size = |value: &str| -> Result<Option<usize>, error::ConfigValueError> {
+            if value.trim().eq_ignore_ascii_case("none") {
+                return Ok(None);
+            }
+            let size = utils::parse_size(value)?;
+            return usize::try_from(size)
+                .map(Some)
+                .map_err(|_| error::ConfigValueError::InvalidSize(value.to_string()));
+        };
+        let duration = |value: &str| -> Result<Option<Duration>, error::ConfigValueError> {
+            if value.trim().eq_ignore_ascii_case("none") {
+                return Ok(None);
+            }
+            return utils::parse_duration(value).map(Some);
+        };
+        match name.to_ascii_lowercase().as_str() {
+            "body_limit" => self.body_limit = size(value)?,
+            "memory_budget" => self.memory_budget = size(value)?,
+            "keep_alive_timeout" => self.keep_alive_timeout = duration(value)?,
+            "request_timeout" => self.request_timeout = duration(value)?,
+            "drain_timeout" => self.drain_timeout = utils::parse_duration(value)?,
+            _ => return Err(error::ConfigValueError::UnknownSetting(name.to_string())),
+        }
+        return Ok(());
+    }
+
+    /// Set the settings of the server from environment variables
+    ///
+    /// Every setting of `WebServer::configure` is read from the environment variable named after
+    /// it in upper case, behind the prefix(like `APP_BODY_LIMIT` for the `APP_` prefix). Settings
+    /// without an environment variable are left unchanged.
+    ///
+    /// # Arguments
+    ///
+    /// - `prefix` - The prefix of the environment variables.
+    ///
+    /// # Returns
+    ///
+    /// - `Result<(), ConfigValueError>` - The error of the first variable whose value couldn't be
+    ///   parsed.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// # use browzer_web::WebServer;
+    /// let mut server = WebServer::new("127.0.0.1:8080".to_string(), 4);
+    ///
+    /// // APP_BODY_LIMIT=1MiB APP_REQUEST_TIMEOUT=30s ./server
+    /// server.configure_from_env("APP_").expect("invalid configuration");
+    /// ```
+    pub fn configure_from_env(&mut self, prefix: &str) -> Result<(), error::ConfigValueError> {
+        for name in [
+            "body_limit",
+            "memory_budget",
+            "keep_alive_timeout",
+            "request_timeout",
+            "drain_timeout",
+        ] {
+            let variable = format!("{}{}", prefix, name.to_ascii_uppercase());
+            if let Ok(value) = std::env::var(&variable) {
+                self.configure(name, &value)?;
+            }
+        }
+        return Ok(());
+    }
+
+    /// Pin the worker threads and the acceptor threads to CPU cores
+    ///
+    /// The workers are spread over the cores the process is allowed to run on, with the threads
+    /// accepting connections on the first of them(one per listener), see
+    /// `affinity::CoreMap::round_robin`. This
+    /// reduces cache thrashing and improves tail latency on hosts dedicated to the server, but
+    /// can hurt when other busy processes share the cores. Threads are pinned once the server
+    /// starts listening, on platforms other than Linux this does nothing.
+    ///
+    /// # Arguments
+    ///
+    /// - `enabled` - Whether the threads should be pinned.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// # use browzer_web::WebServer;
+    /// let mut server = WebServer::new("127.0.0.1:8080".to_string(), 4);
+    ///
+    /// server.pin_workers(true);
+    /// server.listen();
+    /// ```
+    pub fn pin_workers(&mut self, enabled: bool) {
+        self.core_map = match enabled {
+            true => Some(affinity::CoreMap::round_robin(self.workers)),
+            false => None,
+        };
+    }
+
+    /// Pin the worker threads and the acceptor threads to explicitly chosen CPU cores
+    ///
+    /// Like `WebServer::pin_workers`, but with the cores picked by the caller, for example to keep
+    /// the server off the cores handling network interrupts.
+    ///
+    /// # Arguments
+    ///
+    /// - `core_map` - The cores of the worker threads and the acceptor threads.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// use browzer_web::{affinity::CoreMap, WebServer};
+    ///
+    /// let mut server = WebServer::new("127.0.0.1:8080".to_string(), 4);
+    ///
+    /// server.pin_workers_to(CoreMap::new(vec![2, 3, 4, 5]).acceptor(1));
+    /// server.listen();
+    /// ```
+    pub fn pin_workers_to(&mut self, core_map: affinity::CoreMap) {
+        self.core_map = Some(core_map);
+    }
+
+    /// Register a hook for failed and dropped connections
+    ///
+    /// By default, failed `accept` calls, connections which couldn't be handed to a worker thread
+    /// and connections which failed while being served(like with a malformed request head) are
+    /// printed to the console. Reports are rate-limited to one per
+    /// `accept_error_log.interval` and count the errors suppressed in between, so an error storm
+    /// (like running out of file descriptors) doesn't flood the console. The hook receives these
+    /// reports instead of them being printed.
+    ///
+    /// # Arguments
+    ///
+    /// - `hook` - A closure which receives every `AcceptErrorReport`.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// # use browzer_web::WebServer;
+    /// let mut server = WebServer::new("127.0.0.1:8080".to_string(), 4);
+    ///
+    /// server.on_accept_error(|report| {
+    ///     eprintln!("[accept] {} ({} errors so far)", report, report.total);
+    /// });
+    /// ```
+    pub fn on_accept_error<F>(&mut self, hook: F)
+    where
+        F: Fn(&accept::AcceptErrorReport) + 'static + Send + Sync,
+    {
+        self.accept_error_log.hook = Some(Arc::new(hook));
+    }
+
+    /// Enable background jobs
+    ///
+    /// Handlers can then start long-running operations with `Context::accept_async`, which
+    /// answers with `202 Accepted` right away, and clients poll the status of the job under the
+    /// route path of the `Jobs`(`/jobs/:id` by default), registered as a `GET` route by this
+    /// method. See `jobs::Jobs` for the pools jobs run on.
+    ///
+    /// # Arguments
+    ///
+    /// - `jobs` - The `Jobs` configuration.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// # use browzer_web::{jobs::{Jobs, MemoryJobStore}, WebServer};
+    /// # use std::time::Duration;
+    /// let mut server = WebServer::new("127.0.0.1:8080".to_string(), 4);
+    ///
+    /// server.jobs(Jobs::new(MemoryJobStore::new(), Duration::from_secs(3600)));
+    /// server.post("/exports", |mut c| {
+    ///     return c.accept_async("default", || Ok("exported".to_string()));
+    /// });
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// If the router is not initialized, this method will print an error message using `eprintln!`.
+    pub fn jobs(&mut self, jobs: jobs::Jobs) {
+        let status_jobs = jobs.clone();
+        self.get(&format!("{}/:id", jobs.path()), move |mut c| {
+            let id = c.params.get("id").cloned().unwrap_or_default();
+            match status_jobs.status_response(&id) {
+                Some(response) => return response,
+                None => return c.send_string(utils::HttpStatusCode::NotFound, "Not Found"),
+            }
+        });
+        if let Some(router) = self.router_mut() {
+            router.jobs = Some(Arc::new(jobs));
+        }
+    }
+
+    /// Set the number of threads running the tasks handlers spawn
+    ///
+    /// Tasks spawned with `Context::spawn_scoped` run on a background pool separate from the
+    /// workers serving connections, which has as many threads as there are CPU cores by default.
+    /// The threads are only started once the first task is spawned.
+    ///
+    /// # Arguments
+    ///
+    /// - `workers` - The number of threads.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// # use browzer_web::WebServer;
+    /// let mut server = WebServer::new("127.0.0.1:8080".to_string(), 4);
+    ///
+    /// server.background_workers(8);
+    /// ```
+    ///
+    /// # Panics
+    ///
+    /// Panics if `workers` is 0.
+    ///
+    /// # Errors
+    ///
+    /// If the router is not initialized, this method will print an error message using `eprintln!`.
+    pub fn background_workers(&mut self, workers: usize) {
+        let background_pool = Arc::new(tasks::BackgroundPool::new(workers));
+        if let Some(router) = self.router_mut() {
+            router.background_pool = background_pool;
+        }
+    }
+
+    /// Register a hook for worker panics
+    ///
+    /// A panic while serving a connection(like in a route handler) is caught by the worker thread,
+    /// which closes the connection and keeps serving other connections. Panics are printed to the
+    /// console by default, the hook receives a `PanicReport` for every panic instead, telling
+    /// whether it happened before or while a response was written.
+    ///
+    /// A panic while generating a response(in a middleware or route handler) is answered with a
+    /// `500 Internal Server Error` response first, generated like any other framework error(see
+    /// `WebServer::error_handler`).
+    ///
+    /// # Arguments
+    ///
+    /// - `hook` - A closure which receives every `PanicReport`.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// # use browzer_web::{panics::PanicPhase, WebServer};
+    /// let mut server = WebServer::new("127.0.0.1:8080".to_string(), 4);
+    ///
+    /// server.on_panic(|report| {
+    ///     if report.phase == PanicPhase::Writing {
+    ///         eprintln!("[panic] a response was cut off: {}", report);
+    ///     }
+    /// });
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// If the server is already listening, this method will print an error message using
+    /// `eprintln!`.
+    pub fn on_panic<F>(&mut self, hook: F)
+    where
+        F: Fn(&panics::PanicReport) + 'static + Send + Sync,
+    {
+        match Arc::get_mut(&mut self.panic_log) {
+            Some(panic_log) => panic_log.hook = Some(Box::new(hook)),
+            None => eprintln!(
+                "{}",
+                error::WebServerError::InternalServerError(
+                    "The panic hook can't be changed while the server is listening".to_string()
+                )
+            ),
+        }
+    }
+
+    /// Returns the number of worker panics since the server was created, by the phase they
+    /// happened in
+    ///
+    /// The worker threads recover from every one of them, `PanicCounts::total` tells how many
+    /// panics they recovered from.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// # use browzer_web::WebServer;
+    /// let server = WebServer::new("127.0.0.1:8080".to_string(), 4);
+    ///
+    /// let counts = server.panic_counts();
+    /// println!("{} panics while writing responses", counts.writing);
+    /// ```
+    pub fn panic_counts(&self) -> panics::PanicCounts {
+        return self.panic_log.counts();
+    }
+
+    /// Set the retry policy of `503 Service Unavailable` responses
+    ///
+    /// Connections over `max_connections`(or over the queue limit, see `WebServer::queue_limit`),
+    /// requests whose handler didn't finish within the request timeout and requests arriving in
+    /// maintenance mode are answered with `503 Service Unavailable`, and a `Retry-After` header
+    /// telling the client how long to back off. The delay is computed from the `OverloadState` of
+    /// the server by `overload::queue_drain_retry_after` by default, the policy computes it
+    /// instead, returning `None` leaves the header out.
+    ///
+    /// # Arguments
+    ///
+    /// - `policy` - A closure computing the `Retry-After` delay from the `OverloadState`.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// # use browzer_web::{overload::{queue_drain_retry_after, OverloadReason}, WebServer};
+    /// # use std::time::Duration;
+    /// let mut server = WebServer::new("127.0.0.1:8080".to_string(), 4);
+    ///
+    /// server.retry_after(|state| match state.reason {
+    ///     // a slow handler is likely to be slow again, give it more room
+    ///     OverloadReason::RequestTimeout => Some(Duration::from_secs(30)),
+    ///     _ => queue_drain_retry_after(state),
+    /// });
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// If the server is already listening, this method will print an error message using
+    /// `eprintln!`.
+    pub fn retry_after<F>(&mut self, policy: F)
+    where
+        F: Fn(&overload::OverloadState) -> Option<Duration> + 'static + Send + Sync,
+    {
+        match Arc::get_mut(&mut self.overload) {
+            Some(overload) => overload.policy = Some(Box::new(policy)),
+            None => eprintln!(
+                "{}",
+                error::WebServerError::InternalServerError(
+                    "The retry policy can't be changed while the server is listening".to_string()
+                )
+            ),
+        }
+    }
+
+    /// Let the number of worker threads grow under load
+    ///
+    /// The server starts with the number of workers given to `WebServer::new`, which it keeps
+    /// when idle. With a maximum above it, another worker is started whenever a connection has to
+    /// wait while every worker is busy, up to `max` workers. The extra workers stop again once
+    /// they had nothing to do for `idle_timeout`, so a server doesn't have to run with enough
+    /// threads for it's busiest minute all day. See `WebServer::limits` for the current number.
+    ///
+    /// # Arguments
+    ///
+    /// - `max` - The number of workers the server may grow to.
+    /// - `idle_timeout` - How long an extra worker waits for a connection before it stops.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// # use browzer_web::WebServer;
+    /// # use std::time::Duration;
+    /// let mut server = WebServer::new("127.0.0.1:8080".to_string(), 4);
+    ///
+    /// server.max_workers(64, Duration::from_secs(30));
+    /// ```
+    pub fn max_workers(&mut self, max: usize, idle_timeout: Duration) {
+        self.request_pool.set_max_workers(max, idle_timeout);
+    }
+
+    /// Bound the queue of connections waiting for a worker thread
+    ///
+    /// Connections accepted while every worker is busy wait in a queue, which is unbounded by
+    /// default and only kept in check by `max_connections`. Under a traffic spike it can hold far
+    /// more connections than the workers get through before their clients give up. With a limit,
+    /// the policy decides what happens to new connections once the queue is full:
+    ///
+    /// - `QueuePolicy::Block` - The accept loop waits for a worker, so new connections stay in the
+    ///   backlog of the listening socket(and the kernel refuses them once it's full).
+    /// - `QueuePolicy::Reject` - New connections are answered with `503 Service Unavailable`(with
+    ///   a `Retry-After` header, see `WebServer::retry_after`) right away.
+    ///
+    /// # Arguments
+    ///
+    /// - `capacity` - How many connections may wait for a worker.
+    /// - `policy` - What happens to new connections while the queue is full.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// # use browzer_web::{limits::QueuePolicy, WebServer};
+    /// let mut server = WebServer::new("127.0.0.1:8080".to_string(), 4);
+    ///
+    /// // 4 connections are handled and up to 64 wait, the ones after them are turned away
+    /// server.queue_limit(64, QueuePolicy::Reject);
+    /// ```
+    pub fn queue_limit(&mut self, capacity: usize, policy: limits::QueuePolicy) {
+        self.request_pool.set_queue_capacity(Some(capacity));
+        self.queue_policy = policy;
+    }
+
+    /// Listens on another address as well
+    ///
+    /// Every address gets an accept loop of it's own, while all of them share the router and the
+    /// worker threads. This serves IPv4 and IPv6 clients(with separate sockets for both address
+    /// families) or several ports from one server. The settings of the server apply to all
+    /// addresses alike, so a TLS server serves HTTPS on all of them.
+    ///
+    /// Every listener is handed over to the new process of an upgrade(see
+    /// `WebServer::upgrade_socket`), which picks the one bound to the same address up here
+    /// instead of binding a new one.
+    ///
+    /// # Arguments
+    ///
+    /// - `address` - The address to listen on, like `[::1]:8080`.
+    ///
+    /// # Returns
+    ///
+    /// - `io::Result<SocketAddr>` - The address the listener was bound to, which tells the port
+    ///   picked for port `0`.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// # use browzer_web::WebServer;
+    /// let mut server = WebServer::new("0.0.0.0:8080".to_string(), 4);
+    /// server.bind("[::]:8080").unwrap();
+    /// server.bind("0.0.0.0:8081").unwrap();
+    /// server.listen();
+    /// ```
+    pub fn bind(&mut self, address: &str) -> io::Result<SocketAddr> {
+        // the process this one replaces hands it's listening sockets over, see the `upgrade` module
+        #[cfg(unix)]
+        let inherited = upgrade::inherited_listener_for(address);
+        #[cfg(not(unix))]
+        let inherited = None;
+        let listener = inherited.map_or_else(|| TcpListener::bind(address), Ok)?;
+        let local_addr = listener.local_addr()?;
+        self.additional_listeners.push(listener);
+        return Ok(local_addr);
+    }
+
+    /// Listens on a socket which is already bound as well, like `WebServer::bind` does for an
+    /// address.
+    ///
+    /// Use it for the sockets after the first one of a socket activated service, see
+    /// `WebServer::from_listener`.
+    pub fn add_listener(&mut self, listener: TcpListener) -> io::Result<SocketAddr> {
+        listener.set_nonblocking(false)?;
+        let local_addr = listener.local_addr()?;
+        self.additional_listeners.push(listener);
+        return Ok(local_addr);
+    }
+
+    /// Returns the address the server listens on, which tells the port picked for port `0`.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # use browzer_web::WebServer;
+    /// let server = WebServer::new("127.0.0.1:0".to_string(), 2);
+    /// assert_ne!(server.local_addr().unwrap().port(), 0);
+    /// ```
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        return self.listener.local_addr();
+    }
+
+    /// Returns the addresses the server listens on, the one given to `WebServer::new` first.
+    pub fn local_addrs(&self) -> Vec<SocketAddr> {
+        return std::iter::once(&self.listener)
+            .chain(self.additional_listeners.iter())
+            .filter_map(|listener| listener.local_addr().ok())
+            .collect();
+    }
+
+    /// Returns a handle stopping the server gracefully
+    ///
+    /// Once the handle is triggered, the server stops accepting connections, closes keep-alive
+    /// connections after their current response and `listen` returns once every connection was
+    /// closed, or `drain_timeout` passed. See the `upgrade` module.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// # use browzer_web::WebServer;
+    /// let server = WebServer::new("127.0.0.1:8080".to_string(), 4);
+    ///
+    /// let shutdown = server.shutdown_handle();
+    /// std::thread::spawn(move || {
+    ///     // wait for a deployment, a signal, ...
+    ///     shutdown.shutdown();
+    /// });
+    /// server.listen();
+    /// println!("all connections closed");
+    /// ```
+    pub fn shutdown_handle(&self) -> upgrade::ShutdownHandle {
+        return self.shutdown.clone();
+    }
+
+    /// Returns a handle putting the server in and out of maintenance mode
+    ///
+    /// While maintenance mode is enabled, every request is answered with `503 Service
+    /// Unavailable`(and a `Retry-After` header, see `retry_after`) instead of being handled, and
+    /// it's connection is closed. Requests already being handled when it's enabled are finished.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// # use browzer_web::WebServer;
+    /// let server = WebServer::new("127.0.0.1:8080".to_string(), 4);
+    ///
+    /// let maintenance = server.maintenance_handle();
+    /// std::thread::spawn(move || {
+    ///     maintenance.enable();
+    ///     // migrate the database, ...
+    ///     maintenance.disable();
+    /// });
+    /// server.listen();
+    /// ```
+    pub fn maintenance_handle(&self) -> overload::MaintenanceHandle {
+        return self.overload.maintenance.clone();
+    }
+
+    /// Returns a handle registering routes while the server is listening
+    ///
+    /// The router is shared with the connections once `listen` is called, so `get`, `post`, ...
+    /// can't register routes anymore. The returned `RouteRegistry` registers them instead, from
+    /// any thread(or route handler) for as long as the server is listening, and returns
+    /// `WebRouterError::NotListening` before and after that. See `router::RouteRegistry`.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// # use browzer_web::{utils::HttpStatusCode, WebServer};
+    /// let server = WebServer::new("127.0.0.1:8080".to_string(), 4);
+    ///
+    /// let registry = server.route_registry();
+    /// std::thread::spawn(move || {
+    ///     // wait for a plugin to be loaded, ...
+    ///     registry
+    ///         .get("/plugin", |mut c| c.send_string(HttpStatusCode::OK, "loaded"))
+    ///         .unwrap();
+    /// });
+    /// server.listen();
+    /// ```
+    pub fn route_registry(&self) -> router::RouteRegistry {
+        return self.route_registry.clone();
+    }
+
+    /// Listen for control commands on a unix socket
+    ///
+    /// The control socket is created once the server starts listening, and answers the `status`,
+    /// `stop` and `upgrade` commands. An upgrade starts a new process which inherits the listening
+    /// socket of this one and takes the control socket over, while this one stops gracefully, so
+    /// an application can be restarted(like for deploying a new build) without refusing a single
+    /// connection. See the `upgrade` module.
+    ///
+    /// # Arguments
+    ///
+    /// - `path` - The path of the control socket, an existing file at it is replaced.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// # use browzer_web::{upgrade, WebServer};
+    /// if std::env::args().nth(1).as_deref() == Some("upgrade") {
+    ///     // `app upgrade` restarts the running `app`
+    ///     println!("{:?}", upgrade::send_command("/tmp/app.sock", "upgrade"));
+    ///     return;
+    /// }
+    ///
+    /// let mut server = WebServer::new("127.0.0.1:8080".to_string(), 4);
+    /// server.upgrade_socket("/tmp/app.sock");
+    /// server.listen();
+    /// ```
+    #[cfg(unix)]
+    pub fn upgrade_socket(&mut self, path: &str) {
+        self.upgrade_socket = Some(std::path::PathBuf::from(path));
+    }
+
+    /// Set the command line starting the new process of an upgrade
+    ///
+    /// The current executable is started with the same arguments by default, see
+    /// `UpgradeCommand::current`. The new process has to create it's `WebServer` like this one,
+    /// with the same `upgrade_socket`, so it picks the listening socket up and reports back.
+    ///
+    /// # Arguments
+    ///
+    /// - `command` - The `UpgradeCommand` starting the new process.
+    #[cfg(unix)]
+    pub fn upgrade_command(&mut self, command: upgrade::UpgradeCommand) {
+        self.upgrade_command = Some(command);
+    }
+
+    /// Register a named access control policy
+    ///
+    /// Policies decide whether a request may reach a route handler, routes opt into them using
+    /// `RouteBuilder::require_policy`. They are evaluated after all middlewares, so an
+    /// authentication middleware can populate the context first. A request which is denied by any
+    /// required policy gets a `403 Forbidden` response.
+    ///
+    /// # Arguments
+    ///
+    /// - `name` - The name of the policy, as used by `RouteBuilder::require_policy`.
+    /// - `policy_func` - A closure which receives the request `Context` and returns whether the
+    ///   request is allowed.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// # use browzer_web::WebServer;
+    /// let mut server = WebServer::new("127.0.0.1:8080".to_string(), 4);
+    ///
+    /// server.policy("admin", |ctx| {
+    ///     return ctx.request.headers.get("X-Role").map(|role| role == "admin").unwrap_or(false);
+    /// });
+    /// server
+    ///     .delete("/users/:id", |mut ctx| {
+    ///         return ctx.send_string(browzer_web::utils::HttpStatusCode::OK, "User deleted!");
+    ///     })
+    ///     .require_policy("admin");
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// If the router is not initialized, this method will print an error message using `eprintln!`.
+    pub fn policy<F>(&mut self, name: &str, policy_func: F)
+    where
+        F: Fn(&context::Context) -> bool + 'static + Send + Sync,
+    {
+        if let Some(router) = self.router_mut() {
+            router
+                .policies
+                .insert(name.to_string(), Arc::new(Box::new(policy_func)));
+        }
+    }
+
+    /// Register an audit hook for access control decisions
+    ///
+    /// The hook is called with a `PolicyDecision` every time a policy required by a route is
+    /// evaluated, whether it allowed the request or not, which makes it a good place to record
+    /// denied access attempts.
+    ///
+    /// # Arguments
+    ///
+    /// - `audit_func` - A closure which receives every `PolicyDecision`.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// # use browzer_web::WebServer;
+    /// let mut server = WebServer::new("127.0.0.1:8080".to_string(), 4);
+    ///
+    /// server.policy_audit(|decision| {
+    ///     if !decision.allowed {
+    ///         eprintln!("Denied {:?} {} by policy {}", decision.method, decision.path, decision.policy);
+    ///     }
+    /// });
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// If the router is not initialized, this method will print an error message using `eprintln!`.
+    pub fn policy_audit<F>(&mut self, audit_func: F)
+    where
+        F: Fn(&policy::PolicyDecision) + 'static + Send + Sync,
+    {
+        if let Some(router) = self.router_mut() {
+            router.policy_audit = Some(Box::new(audit_func));
+        }
+    }
+
+    /// Creates a group of routes sharing a common path prefix
+    ///
+    /// Routes registered through the returned `RouteGroup` are registered under the prefix, and
+    /// middlewares or access control policies attached to the group only apply to it's routes.
+    ///
+    /// # Arguments
+    ///
+    /// - `prefix` - The path prefix of every route in the group, like `/api/v1`.
+    ///
+    /// # Returns
+    ///
+    /// - `RouteGroup` - The group, which borrows the server until it is dropped.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// # use browzer_web::WebServer;
+    /// let mut server = WebServer::new("127.0.0.1:8080".to_string(), 4);
+    ///
+    /// let mut api = server.group("/api/v1");
+    /// api.middleware(|mut ctx| {
+    ///     // some functionality only for `/api/v1` routes
+    ///     return ctx;
+    /// });
+    /// api.get("/users", |mut ctx| {
+    ///     return ctx.send_string(browzer_web::utils::HttpStatusCode::OK, "[]");
+    /// });
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// If the router is not initialized, this method will print an error message using `eprintln!`
+    /// and return a group whose methods are no-ops.
+    pub fn group(&mut self, prefix: &str) -> router::RouteGroup<'_> {
+        return router::RouteGroup::new(self.router_mut(), prefix);
+    }
+
+    /// Registers a new route for handling HTTP GET requests.
+    ///
+    /// This method allows you to define a route and associate it with a handler function that
+    /// will be called when a GET request is made to the specified path. The handler function
+    /// should accept a `Context` object and return a `Response` object.
+    ///
+    /// # Arguments
+    ///
+    /// - `path` - A string slice that holds the path for the route. This is the URL path that will be
+    ///   matched against incoming GET requests.
+    /// - `handler` - A closure or function that takes a `Context` as input and returns a `Response`.
+    ///
+    /// # Returns
+    ///
+    /// - `RouteBuilder` - A builder which can be used to customize the newly registered route.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// # use browzer_web::WebServer;
+    /// let mut server = WebServer::new("127.0.0.1:8080".to_string(), 4);
+    ///
+    /// server.get("/hello", |mut ctx| {
+    ///     return ctx.send_string(browzer_web::utils::HttpStatusCode::OK, "Hello, World!");
+    /// });
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// If the router is not initialized, this method will print an error message using `eprintln!`.
+    ///
+    /// # Panics
+    ///
+    /// This function will not panic under normal conditions. However, if the router is not properly
+    /// initialized, it will log an error.
+    // ----- GET request
+    pub fn get<F>(&mut self, path: &str, handler: F) -> router::RouteBuilder<'_>
+    where
+        F: Fn(context::Context) -> response::Response + 'static + Send + Sync,
+    {
+        return router::RouteBuilder::or_log(self.register_route(
+            path,
+            utils::HttpMethod::GET,
+            handler,
+        ));
+    }
+    /// Registers a new route for handling HTTP POST requests.
+    ///
+    /// This method allows you to define a route and associate it with a handler function that
+    /// will be called when a POST request is made to the specified path. The handler function
+    /// should accept a `Context` object and return a `Response` object.
+    ///
+    /// # Arguments
+    ///
+    /// - `path` - A string slice that holds the path for the route. This is the URL path that will be
+    ///   matched against incoming POST requests.
+    /// - `handler` - A closure or function that takes a `Context` as input and returns a `Response`.
+    ///
+    /// # Returns
+    ///
+    /// - `RouteBuilder` - A builder which can be used to customize the newly registered route.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// # use browzer_web::WebServer;
+    /// let mut server = WebServer::new("127.0.0.1:8080".to_string(), 4);
+    ///
+    /// server.post("/submit", |mut ctx| {
+    ///     return ctx.send_string(browzer_web::utils::HttpStatusCode::OK, "Resource submitted!");
+    /// });
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// If the router is not initialized or it it fails to register the route using `WebRouter`,
+    /// this method will print an error message using `eprintln!`.
+    ///
+    /// # Panics
+    ///
+    /// This function will not panic under normal conditions. However, if the router is not properly
+    /// initialized, it will log an error.
+    // ----- POST request
+    pub fn post<F>(&mut self, path: &str, handler: F) -> router::RouteBuilder<'_>
+    where
+        F: Fn(context::Context) -> response::Response + 'static + Send + Sync,
+    {
+        return router::RouteBuilder::or_log(self.register_route(
+            path,
+            utils::HttpMethod::POST,
+            handler,
+        ));
+    }
+    /// Registers a new route for handling HTTP PUT requests.
+    ///
+    /// This method allows you to define a route and associate it with a handler function that
+    /// will be called when a PUT request is made to the specified path. The handler function
+    /// should accept a `Context` object and return a `Response` object.
+    ///
+    /// # Arguments
+    ///
+    /// - `path` - A string slice that holds the path for the route. This is the URL path that will be
+    ///   matched against incoming PUT requests.
+    /// - `handler` - A closure or function that takes a `Context` as input and returns a `Response`.
+    ///
+    /// # Returns
+    ///
+    /// - `RouteBuilder` - A builder which can be used to customize the newly registered route.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// # use browzer_web::WebServer;
+    /// let mut server = WebServer::new("127.0.0.1:8080".to_string(), 4);
+    ///
+    /// server.put("/replace", |mut ctx| {
+    ///     return ctx.send_string(browzer_web::utils::HttpStatusCode::OK, "Resource replaced!");
+    /// });
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// If the router is not initialized or it it fails to register the route using `WebRouter`,
+    /// this method will print an error message using `eprintln!`.
+    ///
+    /// # Panics
+    ///
+    /// This function will not panic under normal conditions. However, if the router is not properly
+    /// initialized, it will log an error.
+    // ----- PUT request
+    pub fn put<F>(&mut self, path: &str, handler: F) -> router::RouteBuilder<'_>
+    where
+        F: Fn(context::Context) -> response::Response + 'static + Send + Sync,
+    {
+        return router::RouteBuilder::or_log(self.register_route(
+            path,
+            utils::HttpMethod::PUT,
+            handler,
+        ));
+    }
+    /// Registers a new route for handling HTTP PATCH requests.
+    ///
+    /// This method allows you to define a route and associate it with a handler function that
+    /// will be called when a PATCH request is made to the specified path. The handler function
+    /// should accept a `Context` object and return a `Response` object.
+    ///
+    /// # Arguments
+    ///
+    /// - `path` - A string slice that holds the path for the route. This is the URL path that will be
+    ///   matched against incoming PATCH requests.
+    /// - `handler` - A closure or function that takes a `Context` as input and returns a `Response`.
+    ///
+    /// # Returns
+    ///
+    /// - `RouteBuilder` - A builder which can be used to customize the newly registered route.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// # use browzer_web::WebServer;
+    /// let mut server = WebServer::new("127.0.0.1:8080".to_string(), 4);
+    ///
+    /// server.patch("/update", |mut ctx| {
+    ///     return ctx.send_string(browzer_web::utils::HttpStatusCode::OK, "Resource patched!");
+    /// });
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// If the router is not initialized or it it fails to register the route using `WebRouter`,
+    /// this method will print an error message using `eprintln!`.
+    ///
+    /// # Panics
+    ///
+    /// This function will not panic under normal conditions. However, if the router is not properly
+    /// initialized, it will log an error.
+    // ----- PATCH request
+    pub fn patch<F>(&mut self, path: &str, handler: F) -> router::RouteBuilder<'_>
+    where
+        F: Fn(context::Context) -> response::Response + 'static + Send + Sync,
+    {
+        return router::RouteBuilder::or_log(self.register_route(
+            path,
+            utils::HttpMethod::PATCH,
+            handler,
+        ));
+    }
+    /// Registers a new route for handling HTTP DELETE requests.
+    ///
+    /// This method allows you to define a route and associate it with a handler function that
+    /// will be called when a DELETE request is made to the specified path. The handler function
+    /// should accept a `Context` object and return a `Response` object.
+    ///
+    /// # Arguments
+    ///
+    /// - `path` - A string slice that holds the path for the route. This is the URL path that will be
+    ///   matched against incoming DELETE requests.
+    /// - `handler` - A closure or function that takes a `Context` as input and returns a `Response`.
+    ///
+    /// # Returns
+    ///
+    /// - `RouteBuilder` - A builder which can be used to customize the newly registered route.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// # use browzer_web::WebServer;
+    /// let mut server = WebServer::new("127.0.0.1:8080".to_string(), 4);
+    ///
+    /// server.delete("/remove", |mut ctx|{
+    ///     return ctx.send_string(browzer_web::utils::HttpStatusCode::OK, "Resource deleted!");
+    /// });
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// If the router is not initialized or it it fails to register the route using `WebRouter`,
+    /// this method will print an error message using `eprintln!`.
+    ///
+    /// # Panics
+    ///
+    /// This function will not panic under normal conditions. However, if the router is not properly
+    /// initialized, it will log an error.
+    // ----- DELETE request
+    pub fn delete<F>(&mut self, path: &str, handler: F) -> router::RouteBuilder<'_>
+    where
+        F: Fn(context::Context) -> response::Response + 'static + Send + Sync,
+    {
+        return router::RouteBuilder::or_log(self.register_route(
+            path,
+            utils::HttpMethod::DELETE,
+            handler,
+        ));
+    }
+
+    /// Registers a new route with an async handler(requires the `tokio` feature)
+    ///
+    /// The handler returns a future instead of a response, so it can await other async code(like a
+    /// database client). Middlewares still run before it like for any other route. A server started
+    /// with `WebServer::listen_async` awaits the future on it's runtime, so a handler waiting on
+    /// something doesn't hold a thread. With `WebServer::listen` the future is driven to completion
+    /// by the worker thread handling the request instead(see `runtime::block_on`), on a runtime
+    /// shared by all worker threads.
+    ///
+    /// # Arguments
+    ///
+    /// - `path` - The path of the route.
+    /// - `method` - The method of the route.
+    /// - `handler` - A closure or function that takes a `Context` as input and returns a future of
+    ///   a `Response`, or of a `Result` whose error converts into an `ErrorResponse`(see
+    ///   `response::IntoResponse`).
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// # use browzer_web::{utils::{HttpMethod, HttpStatusCode}, WebServer};
+    /// # use std::time::Duration;
+    /// let mut server = WebServer::new("127.0.0.1:8080".to_string(), 4);
+    ///
+    /// server
+    ///     .register_async_route("/slow", HttpMethod::GET, |mut c| async move {
+    ///         tokio::time::sleep(Duration::from_millis(100)).await;
+    ///         return c.send_string(HttpStatusCode::OK, "done");
+    ///     })
+    ///     .expect("the route is registered before the server is listening");
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// The errors of `WebServer::register_route`.
+    #[cfg(feature = "tokio")]
+    pub fn register_async_route<F, Fut, R>(
+        &mut self,
+        path: &str,
+        method: utils::HttpMethod,
+        handler: F,
+    ) -> Result<router::RouteBuilder<'_>, error::WebRouterError>
+    where
+        F: Fn(context::Context) -> Fut + 'static + Send + Sync,
+        Fut: std::future::Future<Output = R> + 'static + Send,
+        R: response::IntoResponse,
+    {
+        let router = match Arc::get_mut(&mut self.router) {
+            Some(router) => router,
+            None => return Err(error::WebRouterError::Listening),
+        };
+        let handler = Arc::new(handler);
+        let blocking_handler = Arc::clone(&handler);
+        let route = router.add(path.to_string(), method, move |c| {
+            return runtime::block_on(blocking_handler(c));
+        })?;
+        route.async_handler = Some(Box::new(move |c| {
+            let future = handler(c);
+            return Box::pin(async move { future.await.into_result() });
+        }));
+        return Ok(router::RouteBuilder::new(Some(route)));
+    }
+
+    /// Registers a new route with an async handler for HTTP GET requests, see
+    /// `WebServer::register_async_route`.
+    #[cfg(feature = "tokio")]
+    pub fn get_async<F, Fut, R>(&mut self, path: &str, handler: F) -> router::RouteBuilder<'_>
+    where
+        F: Fn(context::Context) -> Fut + 'static + Send + Sync,
+        Fut: std::future::Future<Output = R> + 'static + Send,
+        R: response::IntoResponse,
+    {
+        return router::RouteBuilder::or_log(self.register_async_route(
+            path,
+            utils::HttpMethod::GET,
+            handler,
+        ));
+    }
+
+    /// Registers a new route with an async handler for HTTP POST requests, see
+    /// `WebServer::register_async_route`.
+    #[cfg(feature = "tokio")]
+    pub fn post_async<F, Fut, R>(&mut self, path: &str, handler: F) -> router::RouteBuilder<'_>
+    where
+        F: Fn(context::Context) -> Fut + 'static + Send + Sync,
+        Fut: std::future::Future<Output = R> + 'static + Send,
+        R: response::IntoResponse,
+    {
+        return router::RouteBuilder::or_log(self.register_async_route(
+            path,
+            utils::HttpMethod::POST,
+            handler,
+        ));
+    }
+
+    /// Registers a new route with an async handler for HTTP PUT requests, see
+    /// `WebServer::register_async_route`.
+    #[cfg(feature = "tokio")]
+    pub fn put_async<F, Fut, R>(&mut self, path: &str, handler: F) -> router::RouteBuilder<'_>
+    where
+        F: Fn(context::Context) -> Fut + 'static + Send + Sync,
+        Fut: std::future::Future<Output = R> + 'static + Send,
+        R: response::IntoResponse,
+    {
+        return router::RouteBuilder::or_log(self.register_async_route(
+            path,
+            utils::HttpMethod::PUT,
+            handler,
+        ));
+    }
+
+    /// Registers a new route with an async handler for HTTP PATCH requests, see
+    /// `WebServer::register_async_route`.
+    #[cfg(feature = "tokio")]
+    pub fn patch_async<F, Fut, R>(&mut self, path: &str, handler: F) -> router::RouteBuilder<'_>
+    where
+        F: Fn(context::Context) -> Fut + 'static + Send + Sync,
+        Fut: std::future::Future<Output = R> + 'static + Send,
+        R: response::IntoResponse,
+    {
+        return router::RouteBuilder::or_log(self.register_async_route(
+            path,
+            utils::HttpMethod::PATCH,
+            handler,
+        ));
+    }
+
+    /// Registers a new route with an async handler for HTTP DELETE requests, see
+    /// `WebServer::register_async_route`.
+    #[cfg(feature = "tokio")]
+    pub fn delete_async<F, Fut, R>(&mut self, path: &str, handler: F) -> router::RouteBuilder<'_>
+    where
+        F: Fn(context::Context) -> Fut + 'static + Send + Sync,
+        Fut: std::future::Future<Output = R> + 'static + Send,
+        R: response::IntoResponse,
+    {
+        return router::RouteBuilder::or_log(self.register_async_route(
+            path,
+            utils::HttpMethod::DELETE,
+            handler,
+        ));
+    }
+
+    // returns mutable access to the router, which is only possible while the server isn't
+    // listening, printing an error message otherwise
+    fn router_mut(&mut self) -> Option<&mut router::WebRouter> {
+        match Arc::get_mut(&mut self.router) {
+            Some(router) => return Some(router),
+            None => {
+                eprintln!(
+                    "{}",
+                    error::WebServerError::InternalServerError(
+                        "The WebRouter is shared with connections still in flight, register routes through `WebServer::route_registry` instead".to_string()
+                    )
+                );
+                return None;
+            }
+        }
+    }
+
+    /// Registers a new route with a handler which can fail
+    ///
+    /// The handler returns a `Response`, or a `Result` whose error converts into an
+    /// `ErrorResponse`(see `response::IntoResponse`), which lets it bail out early with the `?`
+    /// operator. A returned error is answered like any other framework error(see
+    /// `WebServer::error_handler`). Handlers which always return a `Response` can be registered
+    /// with `WebServer::get` and the other methods named after HTTP methods too.
+    ///
+    /// # Arguments
+    ///
+    /// - `path` - The path of the route.
+    /// - `method` - The method of the route.
+    /// - `handler` - A closure or function that takes a `Context` as input and returns anything
+    ///   implementing `IntoResponse`.
+    ///
+    /// # Returns
+    ///
+    /// - `Result<RouteBuilder, WebRouterError>` - A builder which can be used to customize the
+    ///   newly registered route, or an error if the route couldn't be registered.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// # use browzer_web::{response::{ErrorResponse, Response}, WebServer};
+    /// # use browzer_web::utils::{HttpMethod, HttpStatusCode};
+    /// let mut server = WebServer::new("127.0.0.1:8080".to_string(), 4);
+    ///
+    /// server
+    ///     .register_route("/notes", HttpMethod::GET, |mut c| -> Result<Response, ErrorResponse> {
+    ///         let notes = std::fs::read_to_string("notes.txt")?;
+    ///         return Ok(c.send_string(HttpStatusCode::OK, &notes));
+    ///     })
+    ///     .expect("the route is registered before the server is listening");
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// - `WebRouterError::Listening` - The server is listening already, routes are registered
+    ///   through `WebServer::route_registry` from then on.
+    /// - `WebRouterError::PathFormatError` - The path couldn't be formatted.
+    /// - `WebRouterError::RouteInUse` - The route registered for the path and method before is
+    ///   still in use.
+    pub fn register_route<F, R>(
+        &mut self,
+        path: &str,
+        method: utils::HttpMethod,
+        handler: F,
+    ) -> Result<router::RouteBuilder<'_>, error::WebRouterError>
+    where
+        F: Fn(context::Context) -> R + 'static + Send + Sync,
+        R: response::IntoResponse,
+    {
+        let router = match Arc::get_mut(&mut self.router) {
+            Some(router) => router,
+            None => return Err(error::WebRouterError::Listening),
+        };
+        let route = router.add(path.to_string(), method, Box::new(handler))?;
+        return Ok(router::RouteBuilder::new(Some(route)));
+    }
+
+    /// Serves resumable uploads following the tus protocol under a path
+    ///
+    /// This method registers the routes of the upload creation URL(`path`) and of the individual
+    /// uploads(`path/:id`), all handled by the given `ResumableUploads`. See
+    /// `upload::ResumableUploads` for the supported requests.
+    ///
+    /// Every `PATCH` request is subject to the server's `body_limit`, so clients have to send the
+    /// upload in chunks no larger than it(which tus clients do when configured with a chunk size).
+    ///
+    /// # Arguments
+    ///
+    /// - `path` - The upload creation URL, like `/files`.
+    /// - `uploads` - The `ResumableUploads` handler, which holds the upload storage.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// use browzer_web::{upload::{FileUploadStore, ResumableUploads}, WebServer};
+    ///
+    /// let mut server = WebServer::new("127.0.0.1:8080".to_string(), 4);
+    ///
+    /// let uploads = ResumableUploads::new(FileUploadStore::new("uploads").unwrap());
+    /// server.resumable_uploads("/files", uploads);
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// If the router is not initialized or it fails to register the routes, this method will
+    /// print an error message using `eprintln!`.
+    pub fn resumable_uploads(&mut self, path: &str, uploads: upload::ResumableUploads) {
+        let upload_path = format!("{}/:id", path.trim_end_matches('/'));
+        let routes = [
+            (path, utils::HttpMethod::POST),
+            (path, utils::HttpMethod::OPTIONS),
+            (&upload_path, utils::HttpMethod::HEAD),
+            (&upload_path, utils::HttpMethod::PATCH),
+            (&upload_path, utils::HttpMethod::OPTIONS),
+        ];
+        for (route_path, method) in routes {
+            let uploads = uploads.clone();
+            router::RouteBuilder::or_log(
+                self.register_route(route_path, method, move |c| uploads.handle(c)),
+            );
+        }
+    }
+
+    /// Receives `multipart/form-data` file uploads into a directory
+    ///
+    /// This method registers a POST route to `route_path` which stores every file of the request
+    /// in `dir_path` using an `upload::UploadReceiver`, the counterpart to `serve_static`. File
+    /// names are sanitized, existing files are never overwritten and the response is a JSON
+    /// description of the stored files.
+    ///
+    /// The body limit of the route is raised to fit `limits.max_files` files of
+    /// `limits.max_file_size` bytes, instead of the server's `body_limit`.
+    ///
+    /// # Arguments
+    ///
+    /// - `route_path` - The path of the upload route, like `/upload`.
+    /// - `dir_path` - The directory the files are stored in, which is created if it doesn't exist.
+    /// - `limits` - The `UploadLimits` for the size, number and types of files.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// use browzer_web::{upload::UploadLimits, WebServer};
+    ///
+    /// let mut server = WebServer::new("127.0.0.1:8080".to_string(), 4);
+    ///
+    /// server.accept_uploads("/upload", "uploads", UploadLimits::default());
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// If the directory couldn't be created or the route couldn't be registered, this method will
+    /// print an error message using `eprintln!`.
+    pub fn accept_uploads(
+        &mut self,
+        route_path: &str,
+        dir_path: &str,
+        limits: upload::UploadLimits,
+    ) -> router::RouteBuilder<'_> {
+        // room for the largest allowed files, and the multipart framing around them
+        let body_limit = (limits.max_file_size as usize)
+            .saturating_mul(limits.max_files)
+            .saturating_add(64 * 1024);
+        let receiver = match upload::UploadReceiver::new(dir_path, limits) {
+            Ok(receiver) => receiver,
+            Err(e) => {
+                eprintln!(
+                    "Failed to create the upload directory {}, Error: {}",
+                    dir_path, e
+                );
+                return router::RouteBuilder::new(None);
+            }
+        };
+        let registered = self.register_route(route_path, utils::HttpMethod::POST, move |c| {
+            return receiver.handle(c);
+        });
+        return router::RouteBuilder::or_log(registered).body_limit(body_limit);
+    }
+
+    /// This method serves and maps static files from directory path to a route path
+    ///
+    /// This method does it's function by registering a dynamic GET method route to the
+    /// `route_path`, that route's handler function gets the filename of the file that is requested
+    /// from the dynamic route params and then check if a file with that name exists under the
+    /// `dir_path`, if it does then the handler will stream that file's content as the body of the
+    /// response(see `Context::send_file`), it not then it returns a `NotFound`
+    ///
+    /// The filename is percent-decoded and resolved using `utils::resolve_static_path`, requests
+    /// for paths outside of `dir_path`(like `..%2F..%2Fetc%2Fpasswd`) are answered with a
+    /// `Forbidden`
+    ///
+    /// The `Content-Type` header of the response is set from the extension of the file(see
+    /// `utils::mime`), files of an unknown type are served as `application/octet-stream`
+    ///
+    /// Files are sent with `ETag` and `Last-Modified` headers, and requests for a file the client
+    /// already has a current copy of(see `If-None-Match` and `If-Modified-Since`) are answered
+    /// with `304 Not Modified`. Use `serve_static_files` to send `Cache-Control` directives too.
+    ///
+    /// Files in subdirectories are served under their path relative to `dir_path`, and a request
+    /// for a directory(or for `route_path` itself) gets the `index.html` file of the directory.
+    /// `serve_static_files` can answer directories without one with a listing of their entries.
+    ///
+    /// # Arguments
+    ///
+    /// - `dir_path` - A string representing the directory on the machine which the user wants to
+    ///   by served on the web app.
+    /// - `route_path` - A string representing the path to which the user wants to map the
+    ///   static file directory
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// # use browzer_web::WebServer;
+    /// let mut server = WebServer::new("127.0.0.1:8080".to_string(), 4);
+    ///
+    /// server.serve_static("static","/static/get")
+    /// ```
+    pub fn serve_static(&mut self, dir_path: &str, route_path: &str) {
+        self.serve_static_files(route_path, static_files::StaticFiles::new(dir_path));
+    }
+
+    /// Serves the files of a directory under a route path, like `serve_static` but with the
+    /// caching behaviour configured by the `StaticFiles`(like it's `Cache-Control` directives).
+    /// The files can be compiled into the binary as well, see `StaticFiles::embedded`.
+    ///
+    /// # Arguments
+    ///
+    /// - `route_path` - The route path to serve the files under.
+    /// - `files` - The `StaticFiles` serving the directory.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// # use browzer_web::{static_files::StaticFiles, WebServer};
+    /// let mut server = WebServer::new("127.0.0.1:8080".to_string(), 4);
+    ///
+    /// server.serve_static_files(
+    ///     "/static/get",
+    ///     StaticFiles::new("static").cache_control("public, max-age=3600"),
+    /// );
+    /// ```
+    pub fn serve_static_files(&mut self, route_path: &str, files: static_files::StaticFiles) {
+        let files = Arc::new(files);
+        let index_files = Arc::clone(&files);
+        let route_path = route_path.trim_end_matches('/');
+        self.get(&format!("{}/", route_path), move |c| index_files.handle(c));
+        self.get(&format!("{}/*filename", route_path), move |c| {
+            files.handle(c)
+        });
+    }
+
+    /// Inline small static assets into HTML responses
+    ///
+    /// Registers the `AssetInliner` as an after-response middleware, which replaces the links to
+    /// small stylesheets and the sources of small images served with `serve_static` in HTML
+    /// responses with the assets themselves, saving the browser the round trips for them. See
+    /// `inline::AssetInliner` for which assets are inlined.
+    ///
+    /// # Arguments
+    ///
+    /// - `inliner` - The `AssetInliner` for the static directory.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// # use browzer_web::{inline::AssetInliner, WebServer};
+    /// let mut server = WebServer::new("127.0.0.1:8080".to_string(), 4);
+    ///
+    /// server.serve_static("static", "/assets");
+    /// server.inline_assets(
+    ///     AssetInliner::new("static", "/assets")
+    ///         .patterns(&["critical*.css", "*.svg"])
+    ///         .max_image_size(2 * 1024),
+    /// );
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// If the router is not initialized, this method will print an error message using `eprintln!`.
+    pub fn inline_assets(&mut self, inliner: inline::AssetInliner) {
+        self.after_middleware(move |_, response| inliner.apply(response));
+    }
+
+    /// Serves the Markdown files of a directory as HTML pages under a route path.
+    ///
+    /// A request for `route_path/page` is answered with `dir_path/page.md`(or
+    /// `dir_path/page/index.md`) rendered to HTML and inserted into the template, and a request for
+    /// `route_path` itself with `dir_path/index.md`. Rendered pages are cached until their file
+    /// changes, see `markdown::MarkdownSite`. Only available with the `markdown` feature.
+    ///
+    /// # Arguments
+    ///
+    /// - `dir_path` - The directory containing the Markdown files.
+    /// - `route_path` - The route path to serve the pages under.
+    /// - `template` - The `MarkdownTemplate` layout the pages are inserted into.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// # use browzer_web::{markdown::MarkdownTemplate, WebServer};
+    /// let mut server = WebServer::new("127.0.0.1:8080".to_string(), 4);
+    ///
+    /// let layout = std::fs::read_to_string("templates/docs.html").unwrap();
+    /// server.serve_markdown("docs", "/docs", MarkdownTemplate::new(&layout));
+    /// server.listen();
+    /// ```
+    #[cfg(feature = "markdown")]
+    pub fn serve_markdown(
+        &mut self,
+        dir_path: &str,
+        route_path: &str,
+        template: markdown::MarkdownTemplate,
+    ) {
+        let site = Arc::new(markdown::MarkdownSite::new(dir_path, template));
+        let index_site = Arc::clone(&site);
+        self.get(route_path, move |c| index_site.handle(c));
+        let pages_path = format!("{}/*page", route_path.trim_end_matches('/'));
+        self.get(&pages_path, move |c| site.handle(c));
+    }
+
+    /// Listens for incoming TCP connections on the tokio runtime(requires the `tokio` feature)
+    ///
+    /// Like `WebServer::listen`, but connections are served by tasks of the current tokio runtime
+    /// with non-blocking I/O instead of by the worker threads, so idle keep-alive connections and
+    /// slow clients don't occupy a thread. Middlewares and blocking handlers run on the blocking
+    /// thread pool of the runtime once their request was read completely, while async handlers are
+    /// awaited on the runtime itself, see the `runtime` module. HTTPS and the PROXY protocol are
+    /// served like by `WebServer::listen`. The returned future completes once the server was
+    /// stopped(see `WebServer::shutdown_handle`) and the open connections were drained.
+    ///
+    /// # Errors
+    ///
+    /// Returns the error of converting the listening sockets for the runtime if that fails.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// # use browzer_web::{utils::HttpStatusCode, WebServer};
+    /// #[tokio::main]
+    /// async fn main() {
+    ///     let mut server = WebServer::new("127.0.0.1:8080".to_string(), 4);
+    ///     server.get_async("/", |mut c| async move {
+    ///         return c.send_string(HttpStatusCode::OK, "Hello, World!");
+    ///     });
+    ///     server.listen_async().await.unwrap();
+    /// }
+    /// ```
+    #[cfg(feature = "tokio")]
+    pub async fn listen_async(&self) -> io::Result<()> {
+        return runtime::listen(self).await;
+    }
+
+    /// Listens for incoming TCP connections and execute various functionality on those connections.
+    ///
+    /// This method starts the web server, accepting incoming connections and distributing
+    /// them to worker threads for handling. It uses the `request_pool` to manage a pool of
+    /// worker threads and assigns incoming requests to these workers. The function will
+    /// continue to listen for connections until the server is stopped(see
+    /// `WebServer::shutdown_handle`), and returns once the open connections were drained.
+    ///
+    /// # Panics
+    ///
+    /// This function will not panic under normal conditions. However, it will print error
+    /// messages to the standard error output if it encounters issues with establishing connections
+    /// or assigning worker threads.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// # use browzer_web::WebServer;
+    /// let mut server = WebServer::new("127.0.0.1:8080".to_string(), 4);
+    /// server.listen();
+    /// ```
+    ///
+    pub fn listen(&self) {
+        self.print_banner();
+
+        // pin the threads to their cores before the first connection is accepted
+        if let Some(core_map) = &self.core_map {
+            if affinity::is_supported() {
+                let expected = match core_map.workers.is_empty() {
+                    true => 0,
+                    false => self.workers,
+                };
+                let pinned = self.request_pool.pin_workers(&core_map.workers);
+                if pinned < expected {
+                    eprintln!(
+                        "Warning: only {} of {} workers could be pinned to the cores {:?}",
+                        pinned, expected, core_map.workers
+                    );
+                }
+                if let Some(core) = core_map.acceptor_core(0) {
+                    if !affinity::pin_current_thread(core) {
+                        eprintln!(
+                            "Warning: the acceptor thread couldn't be pinned to core {}",
+                            core
+                        );
+                    }
+                }
+            }
+        }
+
+        self.prepare_router();
+
+        // the worker threads report the jobs they recovered from along with the panics they
+        // caught while serving a connection, so `ThreadPool::recovered_panics` counts both
+        self.request_pool.set_panic_log(Arc::clone(&self.panic_log));
+
+        // the process this one replaces is told that this one is ready only now
+        #[cfg(unix)]
+        let control = self.upgrade_socket.as_ref().and_then(|path| {
+            match upgrade::ControlSocket::start(
+                path,
+                self.upgrade_command.clone(),
+                &std::iter::once(&self.listener)
+                    .chain(self.additional_listeners.iter())
+                    .collect::<Vec<_>>(),
+                Arc::clone(&self.active_connections),
+                self.shutdown.clone(),
+            ) {
+                Ok(control) => Some(control),
+                Err(e) => {
+                    eprintln!(
+                        "Failed to create the control socket {:?}, Error: {}",
+                        path, e
+                    );
+                    None
+                }
+            }
+        });
+
+        // every listener gets an accept loop of it's own, all of them sending the connections they
+        // accept as jobs to the `request_pool` in order to be distributed to the worker threads
+        // the listeners after the first one are accepted on the acceptor cores after the first one
+        let acceptor_core = |listener: usize| {
+            return self
+                .core_map
+                .as_ref()
+                .and_then(|core_map| core_map.acceptor_core(listener))
+                .filter(|_| affinity::is_supported());
+        };
+        thread::scope(|scope| {
+            for (i, listener) in self.additional_listeners.iter().enumerate() {
+                let core = acceptor_core(i + 1);
+                scope.spawn(move || {
+                    if let Some(core) = core {
+                        if !affinity::pin_current_thread(core) {
+                            eprintln!(
+                                "Warning: the acceptor thread couldn't be pinned to core {}",
+                                core
+                            );
+                        }
+                    }
+                    self.accept_loop(listener);
+                });
+            }
+            self.accept_loop(&self.listener);
+        });
+        self.route_registry.attach(None);
+
+        // let the connections in flight finish, keep-alive connections are closed after their
+        // current response
+        let deadline = Instant::now() + self.drain_timeout;
+        while self.active_connections.load(Ordering::SeqCst) > 0 && Instant::now() < deadline {
+            thread::sleep(Duration::from_millis(10));
+        }
+        #[cfg(unix)]
+        if let Some(control) = control {
+            control.close();
+        }
+        if !self.hide_banner {
+            println!("-----> server on {} stopped", self.address);
+        }
+    }
+
+    // prints the server banner( a simple log message ) accoding to the `hide_banner` field
+    fn print_banner(&self) {
+        if self.hide_banner {
+            return;
+        }
+        let scheme = match self.is_tls() {
+            true if self.accepts_plaintext() => "HTTP and HTTPS",
+            true => "HTTPS",
+            false => "HTTP",
+        };
+        let mut addresses = vec![self.address.clone()];
+        for listener in self.additional_listeners.iter() {
+            if let Ok(address) = listener.local_addr() {
+                addresses.push(address.to_string());
+            }
+        }
+        println!(
+            "-----> {} server running on {}",
+            scheme,
+            addresses.join(", ")
+        );
+    }
+
+    // gets the router ready for the first request
+    fn prepare_router(&self) {
+        // resolve the middlewares and policies of every route once, instead of for every request
+        self.router.compile();
+
+        // handlers can generate error responses like the router from now on
+        router::WebRouter::share(&self.router);
+
+        // fill the response cache before the first client asks for it's URLs
+        self.router.warm_response_cache();
+
+        // routes can be registered through the registry from now on, see `route_registry`
+        self.route_registry.attach(Some(Arc::clone(&self.router)));
+    }
+
+    // the settings every connection is served with
+    fn connection_settings(&self) -> ConnectionSettings {
+        return ConnectionSettings {
+            keep_alive_timeout: self.keep_alive_timeout,
+            max_connections: self.max_connections,
+            overload: Arc::clone(&self.overload),
+            shutdown: self.shutdown.clone(),
+            strict_http: self.strict_http,
+            request_timeout: self.request_timeout,
+            body_limit: self.body_limit,
+            head_limits: self.head_limits,
+            memory_budget: self.memory_budget,
+            proxy_protocol: self.proxy_protocol,
+            #[cfg(feature = "tls")]
+            accept_plaintext: self.accept_plaintext,
+        };
+    }
+
+    // accepts connections from a listener and hands them to the `request_pool` until the server is
+    // stopping
+    fn accept_loop(&self, listener: &TcpListener) {
+        let mut accept_errors = self.accept_error_log.tracker();
+        let proxy_protocol = self.proxy_protocol
+            || listener
+                .local_addr()
+                .is_ok_and(|address| self.proxy_protocol_listeners.contains(&address));
+        while let Some(stream) = self.accept(listener) {
+            let router = Arc::clone(&self.router);
+            let settings = ConnectionSettings {
+                proxy_protocol,
+                ..self.connection_settings()
+            };
+            let panic_log = Arc::clone(&self.panic_log);
+            let error_reporter = accept_errors.reporter();
+            #[cfg(feature = "tls")]
+            let tls_config = self.tls_config.clone();
+            match stream {
+                Ok(stream) => {
+                    accept_errors.accepted();
+                    let accepted_at = Instant::now();
+                    let connection_guard = match limits::ConnectionGuard::acquire(
+                        &self.active_connections,
+                        self.max_connections,
+                    ) {
+                        Some(connection_guard) => connection_guard,
+                        None => {
+                            // shed the connection right away instead of letting it wait for a
+                            // worker while holding file descriptors
+                            self.shed(stream, overload::OverloadReason::ConnectionLimit);
+                            continue;
+                        }
+                    };
+                    // the job owns the stream, a second handle to it answers the connection if
+                    // the queue turns out to be full
+                    let rejected = match self.queue_policy {
+                        limits::QueuePolicy::Reject => stream.try_clone().ok(),
+                        limits::QueuePolicy::Block => None,
+                    };
+                    let job = move || {
+                        let _connection_guard = connection_guard;
+                        let queued = accepted_at.elapsed();
+                        // a panic drops the connection stream while unwinding, so the connection
+                        // is closed and never reused, whatever was written to it so far
+                        let phase = Cell::new(panics::PanicPhase::Handling);
+                        let result = panic::catch_unwind(AssertUnwindSafe(|| {
+                            Self::handle_connection(
+                                router,
+                                stream,
+                                settings,
+                                queued,
+                                &phase,
+                                &panic_log,
+                                #[cfg(feature = "tls")]
+                                tls_config,
+                            )
+                        }));
+                        match result {
+                            Ok(Ok(_)) => {}
+                            Ok(Err(e)) => error_reporter.connection_failed(&e),
+                            Err(payload) => panic_log.record(phase.get(), payload.as_ref()),
+                        };
+                    };
+                    // the queue is checked and the job queued at once, so the queue can't fill up
+                    // in between
+                    let result = match rejected {
+                        Some(_) => self.request_pool.try_execute(job),
+                        None => self.request_pool.execute(job),
+                    };
+                    match (result, rejected) {
+                        (Ok(_), _) => {}
+                        (Err(error::ThreadPoolError::QueueFull), Some(rejected)) => {
+                            self.shed(rejected, overload::OverloadReason::QueueFull);
+                        }
+                        (Err(e), _) => accept_errors.connection_dropped(&e),
+                    };
+                }
+                Err(e) => match accept_errors.accept_failed(&e) {
+                    Some(backoff) => thread::sleep(backoff),
+                    None => {}
+                },
+            }
+        }
+    }
+
+    // answers a connection the server has no room for with `503 Service Unavailable`, TLS clients
+    // can't read a plaintext response so their connection is just closed
+    fn shed(&self, mut stream: TcpStream, reason: overload::OverloadReason) {
+        if self.is_tls() && !self.accepts_plaintext() {
+            return;
+        }
+        let mut response = self
+            .router
+            .error_response(utils::HttpStatusCode::ServiceUnavailable, "");
+        self.router.post_process(None, None, &mut response);
+        self.overload
+            .signal(&mut response, reason, self.max_connections);
+        response
+            .headers
+            .insert("Connection".to_string(), "close".to_string());
+        let _ = stream.write_all(response.to_string().as_bytes());
+    }
+
+    // accepts the next connection of a listener, `None` once the server is stopping
+    fn accept(&self, listener: &TcpListener) -> Option<io::Result<TcpStream>> {
+        loop {
+            if self.shutdown.is_shutting_down() {
+                return None;
+            }
+            // the stop is noticed while waiting, instead of only once the next connection arrives
+            #[cfg(unix)]
+            if !upgrade::wait_acceptable(listener) {
+                continue;
+            }
+            return Some(listener.accept().map(|(stream, _)| stream));
+        }
+    }
+
+    // whether the server serves HTTPS instead of plain HTTP
+    fn is_tls(&self) -> bool {
+        #[cfg(feature = "tls")]
+        return self.tls_config.is_some();
+        #[cfg(not(feature = "tls"))]
+        return false;
+    }
+
+    // whether the server serves plain HTTP next to HTTPS on the same port
+    fn accepts_plaintext(&self) -> bool {
+        #[cfg(feature = "tls")]
+        return self.accept_plaintext;
+        #[cfg(not(feature = "tls"))]
+        return false;
+    }
+
+    // handles a client connection by serving requests on it one after another, until either the
+    // client or the server asks for the connection to be closed
+    fn handle_connection(
         router: Arc<router::WebRouter>,
         mut stream: TcpStream,
+        settings: ConnectionSettings,
+        queued: Duration,
+        phase: &Cell<panics::PanicPhase>,
+        panic_log: &panics::PanicLog,
+        #[cfg(feature = "tls")] tls_config: Option<Arc<rustls::ServerConfig>>,
     ) -> Result<(), error::WebServerError> {
-        let mut buf_reader = BufReader::new(&mut stream);
-
-        // parse the request string into a `Request` struct by first parsing the string to a string
-        // vector containling the lines of requests as elements by following cases:-
-        //
-        // - if the headers contain the `Content-Length` header and it's value is more than 0, then
-        //   we properly parse the body too
-        // - if the headers do not contain the `Content-Length` then we stop after parsing
-        //
-        // and then passing that vector onto the `new` function of the `Request` string as input
-        let request = match request::Request::new(&{
-            let mut request_vector = Vec::new();
-            let mut content_length = 0;
-
-            for line in buf_reader.by_ref().lines() {
-                let line = match line {
-                    Ok(ln) => ln,
+        // an idle persistent connection occupies a worker thread, so it is only kept open for
+        // `keep_alive_timeout` while waiting for the next request
+        if settings.keep_alive_timeout.is_some() {
+            match stream.set_read_timeout(settings.keep_alive_timeout) {
+                Ok(_) => {}
+                Err(e) => return Err(error::WebServerError::IO(e)),
+            }
+        }
+
+        // behind a TCP load balancer the client address comes from the PROXY protocol header,
+        // which precedes everything else(the TLS handshake included), a connection without a
+        // valid header is dropped
+        let remote_addr = match settings.proxy_protocol {
+            true => match proxy::read_header(&mut stream) {
+                Ok(header) => header.source.or(stream.peer_addr().ok()),
+                Err(e) => return Err(error::WebServerError::ProxyProtocolError(e)),
+            },
+            false => stream.peer_addr().ok(),
+        };
+
+        #[cfg(feature = "tls")]
+        if let Some(tls_config) = tls_config {
+            // sniff the first bytes of the connection to serve clients which don't speak TLS as
+            // plain HTTP, a connection closed before sending anything is simply done
+            let handshake = match settings.accept_plaintext {
+                true => match tls::starts_with_handshake(&stream) {
+                    Ok(Some(handshake)) => handshake,
+                    Ok(None) => return Ok(()),
                     Err(e) => return Err(error::WebServerError::IO(e)),
-                };
-                match line.strip_prefix("Content-Length: ") {
-                    Some(c_l) => {
-                        content_length = match c_l.trim().parse() {
-                            Ok(safe_c_l) => safe_c_l,
-                            Err(e) => return Err(error::WebServerError::from(e)),
+                },
+                false => true,
+            };
+            if !handshake {
+                return Self::serve_requests(
+                    router,
+                    stream,
+                    remote_addr,
+                    settings,
+                    queued,
+                    phase,
+                    panic_log,
+                );
+            }
+            let tls_stream = tls::accept(tls_config, stream)?;
+            return Self::serve_requests(
+                router,
+                tls_stream,
+                remote_addr,
+                settings,
+                queued,
+                phase,
+                panic_log,
+            );
+        }
+        return Self::serve_requests(
+            router,
+            stream,
+            remote_addr,
+            settings,
+            queued,
+            phase,
+            panic_log,
+        );
+    }
+
+    // reads requests from a connection stream and writes the responses generated by the router back
+    // to it, for as long as the connection is kept alive
+    fn serve_requests<S: Transport>(
+        router: Arc<router::WebRouter>,
+        stream: S,
+        remote_addr: Option<SocketAddr>,
+        settings: ConnectionSettings,
+        mut queued: Duration,
+        phase: &Cell<panics::PanicPhase>,
+        panic_log: &panics::PanicLog,
+    ) -> Result<(), error::WebServerError> {
+        // the state shared by all requests of the connection
+        let connection = connection::Connection::new(remote_addr);
+        let mut buf_reader = BufReader::new(stream);
+
+        loop {
+            // an idle keep-alive connection is closed as soon as the server is stopping, instead
+            // of once it timed out
+            if connection.request_count() > 0
+                && !Self::wait_for_request(&mut buf_reader, &settings)?
+            {
+                return Ok(());
+            }
+            let reading_since = Instant::now();
+            let mut request = match request::Request::read_head_with_limits(
+                &mut buf_reader,
+                settings.strict_http,
+                &settings.head_limits,
+            ) {
+                Ok(Some(request)) => request,
+                Ok(None) => {
+                    // the client closed the connection, or left it idle for too long, between
+                    // two requests
+                    return Ok(());
+                }
+                Err(error::WebServerError::RequestParseError(e)) => {
+                    // let the client know that it's request was malformed(or too large)
+                    let response = exchange::Exchange::head_error(&router, &e, reading_since);
+                    Self::reject(&mut buf_reader, response);
+                    return Err(error::WebServerError::RequestParseError(e));
+                }
+                Err(e) => return Err(e),
+            };
+            // bind the cancellation token of the request to the connection, until the request was
+            // served
+            request.cancellation = buf_reader.get_ref().cancellation_token();
+            request.tls = buf_reader.get_ref().is_tls();
+            request.remote_addr = remote_addr;
+            // the TLS handshake is completed lazily by the first read, so what it negotiated is
+            // only known once the first request arrived
+            if connection.request_count() == 0 {
+                if let Some(tls) = buf_reader.get_ref().tls_info() {
+                    connection.set_tls(tls);
+                }
+            }
+            connection.count_request();
+            request.connection = connection.clone();
+            let cancellation = request.cancellation.clone();
+            let _detach_guard = cancel::DetachGuard(request.cancellation.clone());
+
+            // the time the connection waited for a worker only counts towards it's first request
+            let mut exchange = exchange::Exchange::begin(
+                &router,
+                &settings,
+                &mut request,
+                std::mem::take(&mut queued),
+            );
+            if let Some(response) = exchange.admit() {
+                Self::reject(&mut buf_reader, response);
+                return Ok(());
+            }
+
+            // utilize user registered routes from `routes` hashmap in the `WebRouter` to handle
+            // requests, generate responses and then send those responses to the request agent
+            // throught the TCP connection stream, if the router fails to generate a response a `500
+            // Internal Server Error` response is sent instead and the connection is closed, the same
+            // goes for a middleware or handler which panics
+            let handled = match exchange.streams_body() {
+                true => {
+                    // the handler reads the body from the connection while it arrives, the
+                    // connection is lent to it's body stream until the handler returned
+                    exchange.body_read();
+                    let deadline = exchange
+                        .remaining()
+                        .map(|remaining| Instant::now() + remaining);
+                    let body = Arc::new(Mutex::new(StreamedBody {
+                        reader: Some(buf_reader),
+                        left: request.content_length() as u64,
+                        failed: false,
+                    }));
+                    let source = Arc::clone(&body);
+                    request.body_stream = Some(request::BodyStream::new(
+                        request.content_length() as u64,
+                        move || Self::read_body_chunk(&source, deadline),
+                    ));
+                    let handled = panic::catch_unwind(AssertUnwindSafe(|| {
+                        return Self::handle_request_until(&router, request, deadline);
+                    }));
+                    // a body stream the handler kept can't read from the connection anymore
+                    let mut body = body.lock().unwrap_or_else(|e| e.into_inner());
+                    buf_reader = match body.reader.take() {
+                        Some(reader) => reader,
+                        None => return Ok(()),
+                    };
+                    if body.left > 0
+                        || body.failed
+                        || buf_reader
+                            .get_ref()
+                            .set_read_timeout(settings.keep_alive_timeout)
+                            .is_err()
+                    {
+                        exchange.body_left_unread();
+                    }
+                    handled
+                }
+                false => {
+                    // the body has to arrive before the deadline of the request, the keep-alive
+                    // timeout is restored for waiting on the next request afterwards
+                    let body_result = match exchange.remaining() {
+                        Some(remaining) => {
+                            // a zero read timeout is rejected by the socket, so wait at least a
+                            // millisecond
+                            let read_timeout = remaining.max(Duration::from_millis(1));
+                            buf_reader.get_ref().set_read_timeout(Some(read_timeout))?;
+                            let body_result = request.read_body(&mut buf_reader);
+                            buf_reader
+                                .get_ref()
+                                .set_read_timeout(settings.keep_alive_timeout)?;
+                            body_result
+                        }
+                        None => request.read_body(&mut buf_reader),
+                    };
+                    match body_result {
+                        Ok(_) => {}
+                        Err(error::WebServerError::IO(e))
+                            if matches!(
+                                e.kind(),
+                                io::ErrorKind::TimedOut | io::ErrorKind::WouldBlock
+                            ) =>
+                        {
+                            let response = exchange.reject(utils::HttpStatusCode::RequestTimeout);
+                            Self::reject(&mut buf_reader, response);
+                            return Ok(());
                         }
+                        Err(e) => return Err(e),
                     }
-                    None => {}
+                    exchange.body_read();
+
+                    let deadline = exchange
+                        .remaining()
+                        .map(|remaining| Instant::now() + remaining);
+                    panic::catch_unwind(AssertUnwindSafe(|| {
+                        return Self::handle_request_until(&router, request, deadline);
+                    }))
+                }
+            };
+            let handled = match handled {
+                Ok(Some(result)) => exchange::Handled::Response(result),
+                Ok(None) => exchange::Handled::TimedOut,
+                Err(payload) => exchange::Handled::Panicked(Some(payload)),
+            };
+            let reply = exchange.respond(handled, panic_log);
+
+            // from here on a panic may leave a partially written response behind
+            phase.set(panics::PanicPhase::Writing);
+            let stream = buf_reader.get_mut();
+            match stream.write_all(reply.head.as_bytes()) {
+                Ok(_) => {}
+                Err(e) => {
+                    return Err(error::WebServerError::IO(e));
                 }
-                if line.is_empty() {
-                    request_vector.push(line);
-                    break;
+            };
+            if let Some(stream_body) = reply.stream {
+                // a stream failing halfway leaves the body without it's last chunk, and the
+                // connection is closed so the client can tell that the body is incomplete
+                let mut writer = stream::ResponseWriter::new(
+                    stream,
+                    cancellation.clone(),
+                    stream_body.length(),
+                    reply.chunked,
+                );
+                match stream_body.write_to(&mut writer) {
+                    Ok(_) => writer.finish()?,
+                    Err(e) => return Err(error::WebServerError::IO(e)),
                 }
-                request_vector.push(line);
             }
-            let mut body = Vec::new();
-            if content_length > 0 {
-                body.resize(content_length, 0);
-                match buf_reader.take(content_length as u64).read_exact(&mut body) {
-                    Ok(_) => {}
-                    Err(e) => return Err(error::WebServerError::IO(e)),
+            match stream.flush() {
+                Ok(_) => {}
+                Err(e) => {
+                    return Err(error::WebServerError::StreamFlushError(e.to_string()));
                 }
-                request_vector.push(String::from_utf8_lossy(&body).to_string());
             }
-            request_vector // return the request_vector to Request::new() function
-        }) {
-            Ok(safe) => safe,
-            Err(e) => {
-                return Err(error::WebServerError::RequestParseError(e));
+            phase.set(panics::PanicPhase::Handling);
+            exchange.complete();
+
+            if !reply.keep_alive {
+                return reply.result;
             }
-        };
+        }
+    }
 
-        // utilize user registered routes from `routes` hashmap in the `WebRouter` to handle
-        // requests, generate responses and then send those responses to the request agent throught
-        // the TCP connection stream
-        match stream.write_all(
-            match router.handle_request(request) {
-                Ok(res) => res.to_string(),
-                Err(e) => {
-                    return Err(error::WebServerError::InternalServerError(e.to_string()));
-                }
+    // waits for the next request of a keep-alive connection, checking whether the server is
+    // stopping every `IDLE_POLL_INTERVAL`. `false` if the connection was closed, timed out or the
+    // server is stopping before the request arrived
+    fn wait_for_request<S: Transport>(
+        buf_reader: &mut BufReader<S>,
+        settings: &ConnectionSettings,
+    ) -> io::Result<bool> {
+        let deadline = settings
+            .keep_alive_timeout
+            .map(|keep_alive_timeout| Instant::now() + keep_alive_timeout);
+        // a pipelined request may be buffered already
+        while buf_reader.buffer().is_empty() {
+            if settings.shutdown.is_shutting_down() {
+                return Ok(false);
             }
-            .as_bytes(),
-        ) {
-            Ok(_) => {}
-            Err(e) => {
-                return Err(error::WebServerError::IO(e));
+            let poll_interval = match deadline {
+                Some(deadline) => deadline
+                    .saturating_duration_since(Instant::now())
+                    .min(upgrade::IDLE_POLL_INTERVAL),
+                None => upgrade::IDLE_POLL_INTERVAL,
+            };
+            if poll_interval.is_zero() {
+                return Ok(false);
             }
-        };
+            buf_reader.get_ref().set_read_timeout(Some(poll_interval))?;
+            match buf_reader.fill_buf() {
+                Ok([]) => return Ok(false),
+                Ok(_) => {}
+                Err(e)
+                    if matches!(
+                        e.kind(),
+                        io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut
+                    ) => {}
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+                Err(e) => return Err(e),
+            }
+        }
+        // the rest of the request has to arrive within the keep-alive timeout, like the first one
+        buf_reader
+            .get_ref()
+            .set_read_timeout(settings.keep_alive_timeout)?;
+        return Ok(true);
+    }
 
-        match stream.flush() {
-            Ok(_) => Ok({}),
-            Err(e) => {
-                return Err(error::WebServerError::StreamFlushError(e.to_string()));
+    // handles a request on the current worker. The handler learns about the deadline through the
+    // cancellation token of the request, which is cancelled once it passed, and a response
+    // generated after the deadline is thrown away
+    fn handle_request_until(
+        router: &router::WebRouter,
+        request: request::Request,
+        deadline: Option<Instant>,
+    ) -> Option<Result<response::Response, error::WebRouterError>> {
+        let cancellation = request.cancellation.clone();
+        if let Some(deadline) = deadline {
+            cancellation.set_deadline(deadline);
+        }
+        let result = router.handle_request(request);
+        match deadline {
+            Some(deadline) if Instant::now() >= deadline => {
+                cancellation.cancel();
+                return None;
+            }
+            _ => return Some(result),
+        }
+    }
+
+    // reads the next part of a streamed body from the connection for it's handler, the body has to
+    // arrive before the deadline of the request. `None` once the whole body was read, after a
+    // failed read the body ends early
+    fn read_body_chunk<S: Transport>(
+        body: &Mutex<StreamedBody<S>>,
+        deadline: Option<Instant>,
+    ) -> Option<io::Result<Vec<u8>>> {
+        let mut body = body.lock().unwrap_or_else(|e| e.into_inner());
+        if body.left == 0 || body.failed {
+            return None;
+        }
+        let StreamedBody {
+            ref mut reader,
+            ref mut left,
+            ref mut failed,
+        } = *body;
+        let reader = match reader {
+            Some(reader) => reader,
+            None => {
+                return Some(Err(io::Error::other(
+                    "The request body can't be read after the request was handled",
+                )));
+            }
+        };
+        let timed_out = || io::Error::new(io::ErrorKind::TimedOut, "Request body timed out");
+        let mut chunk = vec![0u8; (*left).min(request::BODY_CHUNK_SIZE as u64) as usize];
+        loop {
+            // a zero read timeout is rejected by the socket, so wait at least a millisecond
+            if let Some(deadline) = deadline {
+                let rest = deadline.saturating_duration_since(Instant::now());
+                if rest.is_zero() {
+                    *failed = true;
+                    return Some(Err(timed_out()));
+                }
+                let read_timeout = rest.max(Duration::from_millis(1));
+                if let Err(e) = reader.get_ref().set_read_timeout(Some(read_timeout)) {
+                    *failed = true;
+                    return Some(Err(e));
+                }
+            }
+            match reader.read(&mut chunk) {
+                Ok(0) => {
+                    *failed = true;
+                    return Some(Err(io::Error::new(
+                        io::ErrorKind::UnexpectedEof,
+                        "Connection closed before the whole request body was received",
+                    )));
+                }
+                Ok(read) => {
+                    chunk.truncate(read);
+                    *left -= read as u64;
+                    return Some(Ok(chunk));
+                }
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+                // the socket reports a read timeout as `WouldBlock` on some platforms
+                Err(e)
+                    if matches!(
+                        e.kind(),
+                        io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut
+                    ) =>
+                {
+                    *failed = true;
+                    return Some(Err(timed_out()));
+                }
+                Err(e) => {
+                    *failed = true;
+                    return Some(Err(e));
+                }
             }
         }
     }
+
+    // writes the response to a request which won't be handled, the connection is being dropped
+    // anyway so a failed write doesn't matter here
+    fn reject<S: Transport>(buf_reader: &mut BufReader<S>, response: response::Response) {
+        let stream = buf_reader.get_mut();
+        let _ = stream.write_all(response.to_string().as_bytes());
+        let _ = stream.flush();
+    }
+}
+
+// the connection a streamed request body is read from, lent to the body stream of the request
+// while it's handler runs
+struct StreamedBody<S> {
+    reader: Option<BufReader<S>>,
+    // the number of bytes of the body which weren't read yet
+    left: u64,
+    failed: bool,
+}
+
+// the per-connection settings of the server, copied into every worker job
+#[derive(Debug, Clone)]
+struct ConnectionSettings {
+    keep_alive_timeout: Option<Duration>,
+    max_connections: usize,
+    overload: Arc<overload::OverloadSignal>,
+    shutdown: upgrade::ShutdownHandle,
+    strict_http: bool,
+    request_timeout: Option<Duration>,
+    body_limit: Option<usize>,
+    head_limits: limits::HeadLimits,
+    memory_budget: Option<usize>,
+    proxy_protocol: bool,
+    #[cfg(feature = "tls")]
+    accept_plaintext: bool,
+}
+
+// a connection stream whose reads can time out and whose hangups can be detected, implemented for
+// plaintext and TLS streams
+trait Transport: Read + Write + Send + 'static {
+    fn set_read_timeout(&self, timeout: Option<Duration>) -> io::Result<()>;
+    fn cancellation_token(&self) -> cancel::CancellationToken;
+    fn is_tls(&self) -> bool;
+    fn tls_info(&self) -> Option<connection::TlsInfo>;
+}
+
+impl Transport for TcpStream {
+    fn set_read_timeout(&self, timeout: Option<Duration>) -> io::Result<()> {
+        return TcpStream::set_read_timeout(self, timeout);
+    }
+
+    fn cancellation_token(&self) -> cancel::CancellationToken {
+        #[cfg(unix)]
+        return cancel::CancellationToken::for_socket(self.as_raw_fd());
+        #[cfg(not(unix))]
+        return cancel::CancellationToken::new();
+    }
+
+    fn is_tls(&self) -> bool {
+        return false;
+    }
+
+    fn tls_info(&self) -> Option<connection::TlsInfo> {
+        return None;
+    }
+}
+
+#[cfg(feature = "tls")]
+impl Transport for tls::TlsStream {
+    fn set_read_timeout(&self, timeout: Option<Duration>) -> io::Result<()> {
+        return self.sock.set_read_timeout(timeout);
+    }
+
+    fn cancellation_token(&self) -> cancel::CancellationToken {
+        return self.sock.cancellation_token();
+    }
+
+    fn is_tls(&self) -> bool {
+        return true;
+    }
+
+    fn tls_info(&self) -> Option<connection::TlsInfo> {
+        return Some(tls::tls_info(&self.conn));
+    }
 }
@@ -0,0 +1,304 @@
+//! This module makes the `WebServer` aware of the file descriptor limit of the process.
+//!
+//! Every connection costs file descriptors, and once the process runs out of them, `accept` starts
+//! failing and handlers can't even open files anymore. The limit(`RLIMIT_NOFILE`) is queried at
+//! startup to derive a safe maximum number of concurrent connections, which the server enforces as
+//! a soft cap by answering connections over it with `503 Service Unavailable` right away.
+//!
+//! It also holds the default limit of the size of request bodies, which keeps a client announcing
+//! a huge body from making the server allocate memory for it, and the accounting behind memory
+//! budgets(see `WebServer::set_memory_budget`), as well as the limits of request heads, which
+//! keep a client sending an endless request line or endless headers from doing the same.
+
+// internal crate imports
+use crate::{request, response};
+
+// standard library imports
+use std::sync::{
+    atomic::{AtomicUsize, Ordering},
+    Arc,
+};
+
+/// The number of file descriptors reserved for everything but connections, like the standard
+/// streams, the listener and files opened by the application.
+pub const RESERVED_FDS: u64 = 32;
+
+/// The number of file descriptors a single connection may need: the socket and one file opened by
+/// a handler(like `serve_static` does).
+pub const FDS_PER_CONNECTION: u64 = 2;
+
+/// The default maximum size of a request body in bytes(10 MiB), see `WebServer::set_max_body_size`.
+pub const DEFAULT_MAX_BODY_SIZE: usize = 10 * 1024 * 1024;
+
+/// The default maximum length of a request line in bytes(8 KiB), see `HeadLimits`.
+pub const DEFAULT_MAX_REQUEST_LINE: usize = 8 * 1024;
+
+/// The default maximum number of headers of a request, see `HeadLimits`.
+pub const DEFAULT_MAX_HEADERS: usize = 100;
+
+/// The default maximum size of a request head in bytes(64 KiB), see `HeadLimits`.
+pub const DEFAULT_MAX_HEAD_SIZE: usize = 64 * 1024;
+
+// the maximum number of concurrent connections if the file descriptor limit can't be determined
+const FALLBACK_MAX_CONNECTIONS: usize = 1024;
+
+/// The resource limits the server runs with.
+///
+/// # Fields
+///
+/// - `fd_soft_limit` - The soft `RLIMIT_NOFILE` limit of the process, `None` if it is unlimited or
+///   couldn't be determined.
+/// - `fd_hard_limit` - The hard `RLIMIT_NOFILE` limit of the process, `None` if it is unlimited or
+///   couldn't be determined.
+/// - `workers` - The current number of worker threads handling connections.
+/// - `max_workers` - The number of worker threads the server may grow to under load(see
+///   `WebServer::max_workers`).
+/// - `max_connections` - The maximum number of concurrent connections, connections over it are
+///   answered with `503 Service Unavailable`.
+/// - `active_connections` - The number of connections accepted and not closed yet, including the
+///   ones waiting for a worker thread.
+/// - `queued_connections` - The number of connections waiting for a worker thread.
+/// - `queue_capacity` - How many connections may wait for a worker thread, `None` if there is no
+///   bound(see `WebServer::queue_limit`).
+///
+/// # Examples
+///
+/// ```rust,no_run
+/// # use browzer_web::WebServer;
+/// let server = WebServer::new("127.0.0.1:8080".to_string(), 4);
+/// let limits = server.limits();
+///
+/// println!(
+///     "{}/{} connections, fd limit: {:?}",
+///     limits.active_connections, limits.max_connections, limits.fd_soft_limit
+/// );
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ServerLimits {
+    pub fd_soft_limit: Option<u64>,
+    pub fd_hard_limit: Option<u64>,
+    pub workers: usize,
+    pub max_workers: usize,
+    pub max_connections: usize,
+    pub active_connections: usize,
+    pub queued_connections: usize,
+    pub queue_capacity: Option<usize>,
+}
+
+/// What the server does with a new connection while the queue of connections waiting for a worker
+/// thread is full, see `WebServer::queue_limit`.
+///
+/// # Variants
+///
+/// - `Block` - The accept loop waits until a worker took a queued connection, leaving new
+///   connections in the backlog of the listening socket.
+/// - `Reject` - The connection is answered with `503 Service Unavailable` right away.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum QueuePolicy {
+    #[default]
+    Block,
+    Reject,
+}
+
+/// The limits of request heads, see `WebServer::set_head_limits`.
+///
+/// The head of a request is read line by line, and reading stops as soon as a limit is exceeded,
+/// so a hostile client can't make the server buffer more than `max_head_size` bytes of it. A
+/// request line over it's limit is answered with `400 Bad Request`, too many or too large headers
+/// with `431 Request Header Fields Too Large`, and the connection is closed in both cases.
+///
+/// # Fields
+///
+/// - `max_request_line` - The maximum length of the request line in bytes, including it's line
+///   ending(defaults to `DEFAULT_MAX_REQUEST_LINE`).
+/// - `max_headers` - The maximum number of header lines(defaults to `DEFAULT_MAX_HEADERS`).
+/// - `max_head_size` - The maximum size of the whole head in bytes, the request line and the
+///   headers with their line endings(defaults to `DEFAULT_MAX_HEAD_SIZE`).
+///
+/// # Examples
+///
+/// ```rust
+/// use browzer_web::{error::RequestError, error::WebServerError, limits::HeadLimits, request::Request};
+///
+/// let limits = HeadLimits {
+///     max_headers: 2,
+///     ..HeadLimits::default()
+/// };
+///
+/// let mut input = "GET / HTTP/1.1\r\nHost: localhost\r\nAccept: */*\r\n\r\n".as_bytes();
+/// assert!(Request::read_head_with_limits(&mut input, false, &limits).is_ok());
+///
+/// let mut input = "GET / HTTP/1.1\r\nHost: localhost\r\nAccept: */*\r\nX-A: 1\r\n\r\n".as_bytes();
+/// assert!(matches!(
+///     Request::read_head_with_limits(&mut input, false, &limits),
+///     Err(WebServerError::RequestParseError(RequestError::TooManyHeadersError(2)))
+/// ));
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct HeadLimits {
+    pub max_request_line: usize,
+    pub max_headers: usize,
+    pub max_head_size: usize,
+}
+
+// default implementation for HeadLimits struct
+impl Default for HeadLimits {
+    fn default() -> Self {
+        return HeadLimits {
+            max_request_line: DEFAULT_MAX_REQUEST_LINE,
+            max_headers: DEFAULT_MAX_HEADERS,
+            max_head_size: DEFAULT_MAX_HEAD_SIZE,
+        };
+    }
+}
+
+/// Queries the soft and hard file descriptor limits(`RLIMIT_NOFILE`) of the process.
+///
+/// # Returns
+///
+/// - `(Option<u64>, Option<u64>)` - The soft and hard limit, each `None` if it is unlimited or
+///   couldn't be determined(like on platforms without `getrlimit`).
+pub fn fd_limits() -> (Option<u64>, Option<u64>) {
+    #[cfg(unix)]
+    {
+        let mut rlimit = libc::rlimit {
+            rlim_cur: 0,
+            rlim_max: 0,
+        };
+        // SAFETY: `getrlimit` only writes to the `rlimit` struct passed to it, which is valid for
+        // the duration of the call
+        if unsafe { libc::getrlimit(libc::RLIMIT_NOFILE, &mut rlimit) } != 0 {
+            return (None, None);
+        }
+        // `rlim_t` isn't `u64` on every platform
+        #[allow(clippy::unnecessary_cast)]
+        let limit = |value: libc::rlim_t| match value == libc::RLIM_INFINITY {
+            true => None,
+            false => Some(value as u64),
+        };
+        return (limit(rlimit.rlim_cur), limit(rlimit.rlim_max));
+    }
+    #[cfg(not(unix))]
+    return (None, None);
+}
+
+/// Derives a safe maximum number of concurrent connections from a file descriptor limit.
+///
+/// # Examples
+///
+/// ```rust
+/// use browzer_web::limits::max_connections_for;
+///
+/// assert_eq!(max_connections_for(Some(1024)), 496);
+/// assert_eq!(max_connections_for(Some(16)), 1);
+/// ```
+pub fn max_connections_for(fd_soft_limit: Option<u64>) -> usize {
+    match fd_soft_limit {
+        Some(limit) => {
+            let max_connections = limit.saturating_sub(RESERVED_FDS) / FDS_PER_CONNECTION;
+            return max_connections.max(1) as usize;
+        }
+        None => return FALLBACK_MAX_CONNECTIONS,
+    }
+}
+
+/// The approximate memory attributable to a request, compared against it's memory budget(see
+/// `WebServer::set_memory_budget`).
+///
+/// Only the data the server buffers for the request is counted, not the allocations of the
+/// handler itself or the body of a streaming response, which is never held in memory as a whole.
+///
+/// # Fields
+///
+/// - `head` - The bytes of the parsed request head: the path, the version and the headers.
+/// - `body` - The bytes of the request body, as announced by it's `Content-Length` header.
+/// - `response` - The bytes of the buffered response: it's headers, cookies and body.
+///
+/// # Examples
+///
+/// ```rust
+/// use browzer_web::{limits::MemoryUsage, request::Request, response::Response};
+///
+/// let mut request = Request::default();
+/// request.path = "/users".to_string();
+/// request.version = "HTTP/1.1".to_string();
+/// request.headers.insert("Content-Length".to_string(), "512".to_string());
+///
+/// let mut usage = MemoryUsage::of_request(&request);
+/// assert_eq!(usage.head, 31);
+/// assert_eq!(usage.body, 512);
+///
+/// usage.add_response(&Response::default());
+/// assert_eq!(usage.total(), 543);
+/// ```
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct MemoryUsage {
+    pub head: usize,
+    pub body: usize,
+    pub response: usize,
+}
+
+impl MemoryUsage {
+    /// Measures the head and the announced body of a request, before it's body is read.
+    pub fn of_request(request: &request::Request) -> MemoryUsage {
+        let headers: usize = request
+            .headers
+            .iter()
+            .map(|(key, value)| key.len() + value.len())
+            .sum();
+        return MemoryUsage {
+            head: request.path.len() + request.version.len() + headers,
+            body: request.content_length(),
+            response: 0,
+        };
+    }
+
+    /// Adds the buffered parts of a response to the usage.
+    pub fn add_response(&mut self, response: &response::Response) {
+        let headers: usize = response
+            .headers
+            .iter()
+            .map(|(key, value)| key.len() + value.len())
+            .sum();
+        let cookies: usize = response
+            .cookies
+            .values()
+            .map(|cookie| cookie.to_header_value().len())
+            .sum();
+        self.response += headers + cookies + response.body.len();
+    }
+
+    /// Returns the sum of the head, body and response bytes.
+    pub fn total(&self) -> usize {
+        return self.head + self.body + self.response;
+    }
+}
+
+// counts a connection as active for as long as it is alive, which also covers connections that
+// are dropped because a handler panicked
+pub(crate) struct ConnectionGuard {
+    active_connections: Arc<AtomicUsize>,
+}
+
+impl ConnectionGuard {
+    // counts a new connection as active, unless `max_connections` connections are active already
+    pub(crate) fn acquire(
+        active_connections: &Arc<AtomicUsize>,
+        max_connections: usize,
+    ) -> Option<ConnectionGuard> {
+        let previous = active_connections.fetch_add(1, Ordering::SeqCst);
+        if previous >= max_connections {
+            active_connections.fetch_sub(1, Ordering::SeqCst);
+            return None;
+        }
+        return Some(ConnectionGuard {
+            active_connections: Arc::clone(active_connections),
+        });
+    }
+}
+
+impl Drop for ConnectionGuard {
+    fn drop(&mut self) {
+        self.active_connections.fetch_sub(1, Ordering::SeqCst);
+    }
+}
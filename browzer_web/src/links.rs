@@ -0,0 +1,235 @@
+//! This module provides utilities for building RFC 8288 `Link` headers, which API responses use to
+//! advertise related resources(HATEOAS), along with a pagination helper which generates the usual
+//! `first`/`prev`/`next`/`last` links.
+
+// standard library imports
+use std::{collections::HashMap, fmt};
+
+/// Represents a single web link of a `Link` header.
+///
+/// # Fields
+///
+/// - `uri` - The target URI of the link.
+/// - `rel` - The relation type of the link, like `next` or `self`.
+/// - `params` - Additional target attributes of the link, like `title` or `type`.
+// ----- Link struct
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Link {
+    pub uri: String,
+    pub rel: String,
+    pub params: Vec<(String, String)>,
+}
+
+impl Link {
+    /// Creates a new `Link` with the given target URI and relation type.
+    pub fn new(uri: &str, rel: &str) -> Link {
+        return Link {
+            uri: uri.to_string(),
+            rel: rel.to_string(),
+            params: vec![],
+        };
+    }
+
+    /// Adds a target attribute to the link.
+    pub fn param(mut self, name: &str, value: &str) -> Link {
+        self.params.push((name.to_string(), value.to_string()));
+        return self;
+    }
+}
+
+impl fmt::Display for Link {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "<{}>; rel=\"{}\"", self.uri, quote(&self.rel))?;
+        for (name, value) in &self.params {
+            write!(f, "; {}=\"{}\"", name, quote(value))?;
+        }
+        return Ok(());
+    }
+}
+
+/// A builder for the value of a `Link` header containing multiple links.
+///
+/// # Examples
+///
+/// ```rust
+/// use browzer_web::links::Links;
+///
+/// let links = Links::new()
+///     .add("/users?page=3", "next")
+///     .add_with("/users/schema", "describedby", &[("type", "application/schema+json")]);
+///
+/// assert_eq!(
+///     links.to_string(),
+///     "</users?page=3>; rel=\"next\", </users/schema>; rel=\"describedby\"; type=\"application/schema+json\""
+/// );
+/// ```
+// ----- Links struct
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Links {
+    pub links: Vec<Link>,
+}
+
+impl Links {
+    /// Creates a new, empty `Links` builder.
+    pub fn new() -> Links {
+        return Links::default();
+    }
+
+    /// Adds a link with the given target URI and relation type.
+    pub fn add(self, uri: &str, rel: &str) -> Links {
+        return self.link(Link::new(uri, rel));
+    }
+
+    /// Adds a link with the given target URI, relation type and target attributes.
+    pub fn add_with(self, uri: &str, rel: &str, params: &[(&str, &str)]) -> Links {
+        let mut link = Link::new(uri, rel);
+        for (name, value) in params {
+            link = link.param(name, value);
+        }
+        return self.link(link);
+    }
+
+    /// Adds an already constructed `Link`.
+    pub fn link(mut self, link: Link) -> Links {
+        self.links.push(link);
+        return self;
+    }
+
+    /// Returns the first link with the given relation type.
+    pub fn get(&self, rel: &str) -> Option<&Link> {
+        return self.links.iter().find(|link| link.rel == rel);
+    }
+
+    /// Whether the builder doesn't contain any links.
+    pub fn is_empty(&self) -> bool {
+        return self.links.is_empty();
+    }
+}
+
+impl fmt::Display for Links {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let links = self
+            .links
+            .iter()
+            .map(|link| link.to_string())
+            .collect::<Vec<_>>();
+        return write!(f, "{}", links.join(", "));
+    }
+}
+
+/// A pagination helper for collection endpoints.
+///
+/// Reads the current page from the `page` and `per_page` query parameters and generates the
+/// matching pagination `Links`.
+///
+/// # Fields
+///
+/// - `page` - The current page, starting at 1.
+/// - `per_page` - The number of items on each page.
+/// - `total` - The total number of items in the collection, if it is known. Without it, no `last`
+///   link can be generated and a `next` link is always generated.
+///
+/// # Examples
+///
+/// ```rust
+/// use browzer_web::links::Pagination;
+/// use std::collections::HashMap;
+///
+/// let query = HashMap::from([("page".to_string(), "2".to_string())]);
+/// let pagination = Pagination::from_query(&query, 10).with_total(35);
+///
+/// assert_eq!(pagination.offset(), 10);
+/// assert_eq!(
+///     pagination.links("/users").to_string(),
+///     "</users?page=1&per_page=10>; rel=\"first\", </users?page=1&per_page=10>; rel=\"prev\", \
+///      </users?page=3&per_page=10>; rel=\"next\", </users?page=4&per_page=10>; rel=\"last\""
+/// );
+/// ```
+// ----- Pagination struct
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Pagination {
+    pub page: u64,
+    pub per_page: u64,
+    pub total: Option<u64>,
+}
+
+impl Pagination {
+    /// Creates a new `Pagination` for the given page and page size.
+    pub fn new(page: u64, per_page: u64) -> Pagination {
+        return Pagination {
+            page: page.max(1),
+            per_page: per_page.max(1),
+            total: None,
+        };
+    }
+
+    /// Creates a new `Pagination` from the `page` and `per_page` query parameters of a request,
+    /// falling back to the first page and `default_per_page` for missing or invalid values.
+    pub fn from_query(query_params: &HashMap<String, String>, default_per_page: u64) -> Pagination {
+        let page = match query_params.get("page") {
+            Some(page) => page.parse().unwrap_or(1),
+            None => 1,
+        };
+        let per_page = match query_params.get("per_page") {
+            Some(per_page) => per_page.parse().unwrap_or(default_per_page),
+            None => default_per_page,
+        };
+        return Pagination::new(page, per_page);
+    }
+
+    /// Sets the total number of items in the collection.
+    pub fn with_total(mut self, total: u64) -> Pagination {
+        self.total = Some(total);
+        return self;
+    }
+
+    /// The number of items to skip to get to the current page.
+    pub fn offset(&self) -> u64 {
+        return (self.page - 1) * self.per_page;
+    }
+
+    /// The number of the last page, if the total number of items is known.
+    pub fn last_page(&self) -> Option<u64> {
+        return self.total.map(|total| total.div_ceil(self.per_page).max(1));
+    }
+
+    /// Generates the `first`, `prev`, `next` and `last` links for this page of the collection.
+    ///
+    /// # Arguments
+    ///
+    /// - `base_uri` - The URI of the collection, it may already contain other query parameters.
+    pub fn links(&self, base_uri: &str) -> Links {
+        let page_uri = |page: u64| {
+            let separator = match base_uri.contains('?') {
+                true => '&',
+                false => '?',
+            };
+            format!(
+                "{}{}page={}&per_page={}",
+                base_uri, separator, page, self.per_page
+            )
+        };
+
+        let mut links = Links::new().add(&page_uri(1), "first");
+        if self.page > 1 {
+            links = links.add(&page_uri(self.page - 1), "prev");
+        }
+        match self.last_page() {
+            Some(last_page) => {
+                if self.page < last_page {
+                    links = links.add(&page_uri(self.page + 1), "next");
+                }
+                links = links.add(&page_uri(last_page), "last");
+            }
+            None => {
+                links = links.add(&page_uri(self.page + 1), "next");
+            }
+        }
+        return links;
+    }
+}
+
+// escapes a link parameter value so that it can be used inside a quoted string
+fn quote(value: &str) -> String {
+    return value.replace('\\', "\\\\").replace('"', "\\\"");
+}
@@ -0,0 +1,294 @@
+//! This module logs every request a `WebServer` answers, with it's method, path, status code,
+//! response size and latency, see `WebServer::request_logger`.
+//!
+//! Log lines are written to stdout as text or JSON lines by default, any other destination can be
+//! plugged in with `RequestLogger::writer` or `RequestLogger::custom`.
+
+// internal crate imports
+use crate::utils;
+
+// external crate imports
+use chrono::{DateTime, SecondsFormat, Utc};
+
+// standard library imports
+use std::{
+    fmt,
+    io::{self, Write},
+    sync::Mutex,
+    time::{Duration, SystemTime},
+};
+
+/// A boxed hook which receives the log entry of every request.
+pub type LogHook = Box<dyn Fn(&RequestLog) + 'static + Send + Sync>;
+
+/// The format of the log lines written by a `RequestLogger`.
+///
+/// # Variants
+///
+/// - `Text` - A line of space separated fields, like
+///   `2024-05-01T12:00:00.000Z GET /users/1 200 512B 1.250ms`, followed by the request ID if the
+///   server assigns them.
+/// - `Json` - A JSON object per line, with the `timestamp`, `method`, `path`, `status`,
+///   `size`(`null` for streamed responses of unknown length) and `latency_ms` fields, and the
+///   `request_id` field if the server assigns request IDs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LogFormat {
+    Text,
+    Json,
+}
+
+/// The log entry of a request.
+///
+/// # Fields
+///
+/// - `timestamp` - When the request was received.
+/// - `method` - The method of the request.
+/// - `path` - The path of the request, without the query string(which may carry tokens).
+/// - `status` - The status code of the response.
+/// - `size` - The size of the response body in bytes, compressed if the response was compressed.
+///   `None` for a streamed body of unknown length.
+/// - `latency` - The time from receiving the request until it's response was generated.
+/// - `request_id` - The ID of the request, if the server assigns them(see
+///   `WebServer::request_ids`).
+///
+/// New fields may be added in later versions, so entries are created with `RequestLog::new`
+/// instead of a struct literal.
+///
+/// # Examples
+///
+/// ```rust
+/// use browzer_web::{logger::RequestLog, utils::HttpMethod};
+/// use std::time::{Duration, UNIX_EPOCH};
+///
+/// let log = RequestLog::new(
+///     UNIX_EPOCH + Duration::from_secs(1_700_000_000),
+///     HttpMethod::GET,
+///     "/users/1",
+///     200,
+///     Some(512),
+///     Duration::from_micros(1250),
+/// );
+///
+/// assert_eq!(log.to_text(), "2023-11-14T22:13:20.000Z GET /users/1 200 512B 1.250ms");
+/// assert_eq!(
+///     log.to_json(),
+///     r#"{"timestamp":"2023-11-14T22:13:20.000Z","method":"GET","path":"/users/1","status":200,"size":512,"latency_ms":1.25}"#
+/// );
+///
+/// let log = log.with_request_id("abc-123");
+/// assert_eq!(log.to_text(), "2023-11-14T22:13:20.000Z GET /users/1 200 512B 1.250ms abc-123");
+/// assert!(log.to_json().ends_with(r#""latency_ms":1.25,"request_id":"abc-123"}"#));
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+#[non_exhaustive]
+pub struct RequestLog {
+    pub timestamp: SystemTime,
+    pub method: utils::HttpMethod,
+    pub path: String,
+    pub status: u16,
+    pub size: Option<u64>,
+    pub latency: Duration,
+    pub request_id: Option<String>,
+}
+
+impl RequestLog {
+    /// Creates a new `RequestLog` entry without a request ID, see the fields of `RequestLog`.
+    pub fn new(
+        timestamp: SystemTime,
+        method: utils::HttpMethod,
+        path: &str,
+        status: u16,
+        size: Option<u64>,
+        latency: Duration,
+    ) -> RequestLog {
+        return RequestLog {
+            timestamp,
+            method,
+            path: path.to_string(),
+            status,
+            size,
+            latency,
+            request_id: None,
+        };
+    }
+
+    /// Sets the ID of the request the entry belongs to.
+    pub fn with_request_id(mut self, request_id: &str) -> RequestLog {
+        self.request_id = Some(request_id.to_string());
+        return self;
+    }
+
+    /// Formats the entry as a line of text, see `LogFormat::Text`.
+    pub fn to_text(&self) -> String {
+        let size = match self.size {
+            Some(size) => format!("{}B", size),
+            None => "-".to_string(),
+        };
+        let line = format!(
+            "{} {} {} {} {} {:.3}ms",
+            self.timestamp_string(),
+            self.method.to_string(),
+            self.path,
+            self.status,
+            size,
+            self.latency.as_secs_f64() * 1000.0
+        );
+        match self.request_id {
+            Some(ref request_id) => return format!("{} {}", line, request_id),
+            None => return line,
+        }
+    }
+
+    /// Formats the entry as a JSON object, see `LogFormat::Json`.
+    pub fn to_json(&self) -> String {
+        // a struct keeps the fields in a fixed order, unlike a JSON map
+        #[derive(serde::Serialize)]
+        struct JsonLog<'a> {
+            timestamp: String,
+            method: String,
+            path: &'a str,
+            status: u16,
+            size: Option<u64>,
+            latency_ms: f64,
+            #[serde(skip_serializing_if = "Option::is_none")]
+            request_id: Option<&'a str>,
+        }
+        let log = JsonLog {
+            timestamp: self.timestamp_string(),
+            method: self.method.to_string(),
+            path: &self.path,
+            status: self.status,
+            size: self.size,
+            latency_ms: self.latency.as_secs_f64() * 1000.0,
+            request_id: self.request_id.as_deref(),
+        };
+        return serde_json::to_string(&log).unwrap_or_default();
+    }
+
+    // formats the timestamp as RFC 3339 in UTC, with milliseconds
+    fn timestamp_string(&self) -> String {
+        return DateTime::<Utc>::from(self.timestamp).to_rfc3339_opts(SecondsFormat::Millis, true);
+    }
+}
+
+// where a `RequestLogger` sends the log entries
+enum LogOutput {
+    Writer(LogFormat, Mutex<Box<dyn Write + Send>>),
+    Hook(LogHook),
+}
+
+/// Logs the requests answered by a `WebServer`, see `WebServer::request_logger`.
+///
+/// Requests for the paths passed to `RequestLogger::skip_path`(like health checks polled every
+/// few seconds) aren't logged. A failure to write a log line is ignored, so logging never breaks
+/// serving requests.
+///
+/// # Examples
+///
+/// ```rust
+/// use browzer_web::logger::{LogFormat, RequestLogger};
+///
+/// // JSON lines on stdout, without the health checks and static files
+/// let logger = RequestLogger::new(LogFormat::Json)
+///     .skip_path("/health")
+///     .skip_path("/static/*");
+///
+/// assert!(logger.skips("/health"));
+/// assert!(logger.skips("/static/app.css"));
+/// assert!(!logger.skips("/healthz"));
+/// ```
+// ----- RequestLogger struct
+pub struct RequestLogger {
+    output: LogOutput,
+    skip_paths: Vec<String>,
+}
+
+impl fmt::Debug for RequestLogger {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let output = match self.output {
+            LogOutput::Writer(format, _) => format!("Writer({:?})", format),
+            LogOutput::Hook(_) => "Box<dyn Fn(&RequestLog) + 'static + Send + Sync>".to_string(),
+        };
+        f.debug_struct("RequestLogger")
+            .field("output", &output)
+            .field("skip_paths", &self.skip_paths)
+            .finish()
+    }
+}
+
+impl RequestLogger {
+    /// Creates a new `RequestLogger` writing log lines of the given format to stdout.
+    pub fn new(format: LogFormat) -> RequestLogger {
+        return RequestLogger {
+            output: LogOutput::Writer(format, Mutex::new(Box::new(io::stdout()))),
+            skip_paths: Vec::new(),
+        };
+    }
+
+    /// Creates a new `RequestLogger` passing every log entry to a hook, like for sending them to
+    /// a log collector or collecting metrics.
+    ///
+    /// # Arguments
+    ///
+    /// - `hook` - A closure receiving the `RequestLog` of every logged request.
+    pub fn custom<F>(hook: F) -> RequestLogger
+    where
+        F: Fn(&RequestLog) + 'static + Send + Sync,
+    {
+        return RequestLogger {
+            output: LogOutput::Hook(Box::new(hook)),
+            skip_paths: Vec::new(),
+        };
+    }
+
+    /// Writes the log lines to a writer(like a file) instead of stdout, keeping the format.
+    ///
+    /// Has no effect on a logger created with `RequestLogger::custom`.
+    pub fn writer<W>(mut self, writer: W) -> RequestLogger
+    where
+        W: Write + Send + 'static,
+    {
+        if let LogOutput::Writer(format, _) = self.output {
+            self.output = LogOutput::Writer(format, Mutex::new(Box::new(writer)));
+        }
+        return self;
+    }
+
+    /// Stops requests for a path from being logged.
+    ///
+    /// # Arguments
+    ///
+    /// - `path` - The path, compared without the query string. A path ending in `*` skips every
+    ///   path starting with the part before it.
+    pub fn skip_path(mut self, path: &str) -> RequestLogger {
+        self.skip_paths.push(path.to_string());
+        return self;
+    }
+
+    /// Returns whether requests for a path aren't logged.
+    pub fn skips(&self, path: &str) -> bool {
+        return utils::matches_path_pattern(&self.skip_paths, path);
+    }
+
+    /// Logs the entry of a request, unless it's path is skipped.
+    pub fn log(&self, log: &RequestLog) {
+        if self.skips(&log.path) {
+            return;
+        }
+        match self.output {
+            LogOutput::Writer(format, ref writer) => {
+                let line = match format {
+                    LogFormat::Text => log.to_text(),
+                    LogFormat::Json => log.to_json(),
+                };
+                let mut writer = match writer.lock() {
+                    Ok(writer) => writer,
+                    Err(poisoned) => poisoned.into_inner(),
+                };
+                let _ = writeln!(writer, "{}", line);
+                let _ = writer.flush();
+            }
+            LogOutput::Hook(ref hook) => hook(log),
+        }
+    }
+}
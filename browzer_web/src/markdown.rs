@@ -0,0 +1,325 @@
+//! This module renders Markdown files to HTML pages, for serving simple documentation or blog
+//! sites with `WebServer::serve_markdown`.
+//!
+//! It is only available with the `markdown` feature enabled. A Markdown file may start with a
+//! front matter block of `key: value` lines between two `---` lines, whose values are available to
+//! the template. Rendered pages are cached and only rendered again once their file changes.
+
+// internal crate imports
+use crate::{context, error, response, utils};
+
+// external crate imports
+use pulldown_cmark::{html, Options, Parser};
+
+// standard library imports
+use std::{
+    collections::HashMap,
+    fs,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+    time::SystemTime,
+};
+
+/// A Markdown document split into it's front matter metadata and rendered HTML content.
+///
+/// # Fields
+///
+/// - `metadata` - The `key: value` pairs of the front matter, empty if the document has none.
+/// - `html` - The Markdown content(without the front matter) rendered to HTML.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MarkdownPage {
+    pub metadata: HashMap<String, String>,
+    pub html: String,
+}
+
+impl MarkdownPage {
+    /// Parses the front matter of a Markdown document and renders the rest of it to HTML.
+    ///
+    /// Tables, footnotes, strikethrough and task lists are enabled on top of CommonMark.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # use browzer_web::markdown::MarkdownPage;
+    /// let page = MarkdownPage::render("---\ntitle: Hello\n---\n# Hello, *World*!\n");
+    ///
+    /// assert_eq!(page.metadata.get("title").unwrap(), "Hello");
+    /// assert_eq!(page.html, "<h1>Hello, <em>World</em>!</h1>\n");
+    /// ```
+    pub fn render(source: &str) -> MarkdownPage {
+        let (metadata, content) = parse_front_matter(source);
+        let mut options = Options::empty();
+        options.insert(Options::ENABLE_TABLES);
+        options.insert(Options::ENABLE_FOOTNOTES);
+        options.insert(Options::ENABLE_STRIKETHROUGH);
+        options.insert(Options::ENABLE_TASKLISTS);
+
+        let mut html = String::new();
+        html::push_html(&mut html, Parser::new_ext(content, options));
+        return MarkdownPage { metadata, html };
+    }
+
+    /// Returns the title of the page, the `title` of the front matter if set.
+    pub fn title(&self) -> Option<&str> {
+        return self.metadata.get("title").map(|title| title.as_str());
+    }
+}
+
+/// Splits a Markdown document into it's front matter metadata and the remaining content.
+///
+/// The front matter has to start at the very first line with `---` and end with another `---`
+/// line, every line in between of the form `key: value` becomes an entry of the metadata(with
+/// surrounding quotes removed from the value), other lines are ignored.
+///
+/// # Returns
+///
+/// - `(HashMap<String, String>, &str)` - The metadata and the content after the front matter, or
+///   no metadata and the whole document if it doesn't start with a front matter block.
+///
+/// # Examples
+///
+/// ```rust
+/// # use browzer_web::markdown::parse_front_matter;
+/// let (metadata, content) = parse_front_matter("---\ntitle: \"Hello\"\ndate: 2024-05-01\n---\nBody");
+///
+/// assert_eq!(metadata.get("title").unwrap(), "Hello");
+/// assert_eq!(metadata.get("date").unwrap(), "2024-05-01");
+/// assert_eq!(content, "Body");
+/// ```
+pub fn parse_front_matter(source: &str) -> (HashMap<String, String>, &str) {
+    let mut metadata = HashMap::new();
+    let rest = match source
+        .strip_prefix("---\n")
+        .or_else(|| source.strip_prefix("---\r\n"))
+    {
+        Some(rest) => rest,
+        None => return (metadata, source),
+    };
+
+    let mut offset = 0;
+    for line in rest.split_inclusive('\n') {
+        offset += line.len();
+        let line = line.trim_end_matches(['\r', '\n']);
+        if line == "---" {
+            for (key, value) in rest[..offset - line.len()]
+                .lines()
+                .filter_map(|line| line.split_once(':'))
+            {
+                let value = value.trim();
+                let value = match value.len() >= 2
+                    && ((value.starts_with('"') && value.ends_with('"'))
+                        || (value.starts_with('\'') && value.ends_with('\'')))
+                {
+                    true => &value[1..value.len() - 1],
+                    false => value,
+                };
+                metadata.insert(key.trim().to_string(), value.to_string());
+            }
+            return (metadata, &rest[offset..]);
+        }
+    }
+    // an unterminated front matter block is just content
+    return (metadata, source);
+}
+
+/// A layout which rendered Markdown pages are inserted into.
+///
+/// The layout is HTML containing placeholders, which are replaced with the values of the page:
+/// - `{{content}}` - The rendered HTML of the page.
+/// - `{{title}}` - The `title` of the front matter, or the file name of the page without it's
+///   extension.
+/// - `{{meta.key}}` - The value of `key` in the front matter, empty if it isn't set.
+///
+/// Titles and metadata values are HTML-escaped, the content is inserted as is.
+///
+/// # Examples
+///
+/// ```rust
+/// # use browzer_web::markdown::{MarkdownPage, MarkdownTemplate};
+/// let template = MarkdownTemplate::new(
+///     "<title>{{title}}</title><p>{{meta.author}}</p><main>{{content}}</main>",
+/// );
+/// let page = MarkdownPage::render("---\ntitle: Tips & Tricks\nauthor: Axew\n---\nHi");
+///
+/// assert_eq!(
+///     template.render(&page, "tips"),
+///     "<title>Tips &amp; Tricks</title><p>Axew</p><main><p>Hi</p>\n</main>"
+/// );
+/// ```
+// ----- MarkdownTemplate struct
+#[derive(Debug, Clone)]
+pub struct MarkdownTemplate {
+    layout: String,
+}
+
+// default implementation for MarkdownTemplate struct
+impl Default for MarkdownTemplate {
+    fn default() -> Self {
+        return MarkdownTemplate::new(
+            "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>{{title}}</title>\n</head>\n<body>\n{{content}}</body>\n</html>\n",
+        );
+    }
+}
+
+impl MarkdownTemplate {
+    /// Creates a new `MarkdownTemplate` from a layout.
+    pub fn new(layout: &str) -> MarkdownTemplate {
+        return MarkdownTemplate {
+            layout: layout.to_string(),
+        };
+    }
+
+    /// Inserts a page into the layout.
+    ///
+    /// # Arguments
+    ///
+    /// - `page` - The rendered page.
+    /// - `fallback_title` - The title used if the page has no `title` in it's front matter.
+    pub fn render(&self, page: &MarkdownPage, fallback_title: &str) -> String {
+        let mut output = String::with_capacity(self.layout.len() + page.html.len());
+        let mut rest = self.layout.as_str();
+        while let Some(start) = rest.find("{{") {
+            let end = match rest[start..].find("}}") {
+                Some(end) => start + end,
+                None => break,
+            };
+            output.push_str(&rest[..start]);
+            let placeholder = rest[start + 2..end].trim();
+            match placeholder {
+                "content" => output.push_str(&page.html),
+                "title" => {
+                    output.push_str(&utils::escape_html(page.title().unwrap_or(fallback_title)))
+                }
+                placeholder => match placeholder.strip_prefix("meta.") {
+                    Some(key) => {
+                        if let Some(value) = page.metadata.get(key) {
+                            output.push_str(&utils::escape_html(value));
+                        }
+                    }
+                    // unknown placeholders are kept, so layouts can contain other `{{ }}` syntax
+                    None => output.push_str(&rest[start..end + 2]),
+                },
+            }
+            rest = &rest[end + 2..];
+        }
+        output.push_str(rest);
+        return output;
+    }
+}
+
+// a rendered page together with the modification time of the file it was rendered from
+#[derive(Debug)]
+struct CachedPage {
+    modified: SystemTime,
+    html: Arc<String>,
+}
+
+/// Serves the Markdown files of a directory as HTML pages, see `WebServer::serve_markdown`.
+///
+/// A request for `page` is answered with the file `page.md`, or `page/index.md` if that doesn't
+/// exist, resolved like `utils::resolve_static_path` so requests can't escape the directory.
+/// Rendered pages are cached, and rendered again once the modification time of their file changes.
+// ----- MarkdownSite struct
+#[derive(Debug)]
+pub struct MarkdownSite {
+    root: PathBuf,
+    template: MarkdownTemplate,
+    cache: Mutex<HashMap<PathBuf, CachedPage>>,
+}
+
+impl MarkdownSite {
+    /// Creates a new `MarkdownSite` serving the Markdown files of a directory through a template.
+    pub fn new(dir_path: &str, template: MarkdownTemplate) -> MarkdownSite {
+        return MarkdownSite {
+            root: PathBuf::from(dir_path),
+            template,
+            cache: Mutex::new(HashMap::new()),
+        };
+    }
+
+    /// Renders the page for a requested path(relative to the directory, `""` for the index page),
+    /// using the cached page if it's file didn't change.
+    ///
+    /// # Returns
+    ///
+    /// - `Result<Arc<String>, StaticFileError>` - The HTML of the page, a `Forbidden` error if the
+    ///   requested path would escape the directory or a `NotFound` error if there is no such page.
+    pub fn page(&self, requested: &str) -> Result<Arc<String>, error::StaticFileError> {
+        let path = self.resolve(requested)?;
+        let modified = match fs::metadata(&path).and_then(|metadata| metadata.modified()) {
+            Ok(modified) => modified,
+            Err(_) => return Err(error::StaticFileError::NotFound),
+        };
+        if let Ok(cache) = self.cache.lock() {
+            if let Some(cached) = cache.get(&path) {
+                if cached.modified == modified {
+                    return Ok(Arc::clone(&cached.html));
+                }
+            }
+        }
+
+        let source = match fs::read_to_string(&path) {
+            Ok(source) => source,
+            Err(_) => return Err(error::StaticFileError::NotFound),
+        };
+        let fallback_title = path
+            .file_stem()
+            .map(|stem| stem.to_string_lossy().to_string())
+            .unwrap_or_default();
+        let html = Arc::new(
+            self.template
+                .render(&MarkdownPage::render(&source), &fallback_title),
+        );
+        if let Ok(mut cache) = self.cache.lock() {
+            cache.insert(
+                path,
+                CachedPage {
+                    modified,
+                    html: Arc::clone(&html),
+                },
+            );
+        }
+        return Ok(html);
+    }
+
+    /// Answers a request for a page, the requested path is taken from the `page` route parameter.
+    pub fn handle(&self, mut c: context::Context) -> response::Response {
+        // the requested path is decoded while resolving it, so the raw value is used
+        let requested = c.raw_params.get("page").cloned().unwrap_or_default();
+        match self.page(&requested) {
+            Ok(html) => {
+                c.response.headers.insert(
+                    "Content-Type".to_string(),
+                    "text/html; charset=utf-8".to_string(),
+                );
+                return c.send_string(utils::HttpStatusCode::OK, &html);
+            }
+            Err(e) => {
+                return c.error_response(e.status_code());
+            }
+        }
+    }
+
+    // resolves a requested page to it's Markdown file, trying `page.md` before `page/index.md`
+    fn resolve(&self, requested: &str) -> Result<PathBuf, error::StaticFileError> {
+        let requested = requested.trim_end_matches('/');
+        let mut candidates = vec![];
+        if !requested.is_empty() {
+            candidates.push(format!("{}.md", requested));
+        }
+        candidates.push(match requested.is_empty() {
+            true => "index.md".to_string(),
+            false => format!("{}/index.md", requested),
+        });
+
+        let mut result = Err(error::StaticFileError::NotFound);
+        for candidate in candidates {
+            result = utils::resolve_static_path(Path::new(&self.root), &candidate);
+            match result {
+                Ok(_) | Err(error::StaticFileError::Forbidden) => return result,
+                Err(error::StaticFileError::NotFound) => {}
+            }
+        }
+        return result;
+    }
+}
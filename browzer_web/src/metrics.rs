@@ -0,0 +1,344 @@
+//! This module counts the requests a `WebServer` answers and exposes the counts in the Prometheus
+//! text format, see `WebServer::enable_metrics`.
+//!
+//! Requests are counted by route, method and status code, and their latency is recorded in a
+//! histogram by route and method. Routes are labeled with their route path pattern(like
+//! `/users/:id`) instead of the request path, so the number of series stays bounded however many
+//! different paths clients request. Requests no route path matches share the `unmatched` label.
+//! Every response the server sends is counted, including the ones it generates before a request
+//! reaches the router(like `413 Payload Too Large` or `408 Request Timeout`).
+//! Next to the request metrics, the server reports it's open connections, the connections waiting
+//! for a worker, the worker threads and the worker panics at the time of the scrape.
+
+// internal crate imports
+use crate::{panics, utils::thread_pool};
+
+// standard library imports
+use std::{
+    collections::BTreeMap,
+    fmt::{self, Write},
+    sync::{
+        atomic::{AtomicU64, AtomicUsize, Ordering},
+        Arc, RwLock,
+    },
+    time::Duration,
+};
+
+/// The upper bounds of the latency histogram buckets by default, in seconds(the bucket bounds of
+/// the Prometheus client libraries).
+pub const DEFAULT_BUCKETS: &[f64] = &[
+    0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0,
+];
+
+/// The route label of requests no route path matches.
+pub const UNMATCHED_ROUTE: &str = "unmatched";
+
+/// The method label of requests whose head couldn't be parsed, which are answered with `400 Bad
+/// Request`(or `413`/`431` for a head over the limits) before their method is known.
+pub const UNKNOWN_METHOD: &str = "unknown";
+
+/// The content type of the Prometheus text format.
+pub const CONTENT_TYPE: &str = "text/plain; version=0.0.4; charset=utf-8";
+
+// the requests of a route and method
+#[derive(Debug)]
+struct Series {
+    statuses: RwLock<BTreeMap<u16, AtomicU64>>,
+    // the number of requests by the first bucket whose bound their latency doesn't exceed, the
+    // last one counts the requests slower than every bound
+    buckets: Vec<AtomicU64>,
+    count: AtomicU64,
+    sum_nanos: AtomicU64,
+}
+
+impl Series {
+    fn new(buckets: usize) -> Series {
+        return Series {
+            statuses: RwLock::new(BTreeMap::new()),
+            buckets: (0..=buckets).map(|_| AtomicU64::new(0)).collect(),
+            count: AtomicU64::new(0),
+            sum_nanos: AtomicU64::new(0),
+        };
+    }
+}
+
+// the sources of the gauges, which belong to the server
+#[derive(Debug, Clone)]
+struct ServerGauges {
+    active_connections: Arc<AtomicUsize>,
+    queue: Arc<thread_pool::JobQueue>,
+    panic_log: Arc<panics::PanicLog>,
+}
+
+/// Counts the requests of a server and renders them in the Prometheus text format, see
+/// `WebServer::enable_metrics`.
+///
+/// The counters are shared atomics, so recording a request only takes a write lock the first time
+/// it's route, method and status code are seen.
+///
+/// The metrics are:
+///
+/// - `browzer_requests_total{route, method, status}` - The number of requests answered.
+/// - `browzer_request_duration_seconds{route, method}` - A histogram of the time from receiving a
+///   request until it's response was generated.
+/// - `browzer_active_connections` - The number of open connections.
+/// - `browzer_queued_connections` - The number of connections waiting for a worker thread.
+/// - `browzer_workers` - The number of worker threads.
+/// - `browzer_worker_panics_total{phase}` - The number of worker panics, by the phase they
+///   happened in(see the `panics` module). Every one of them was recovered from, their sum is the
+///   `ThreadPool::recovered_panics` of the server.
+///
+/// # Examples
+///
+/// ```rust
+/// use browzer_web::metrics::Metrics;
+/// use std::time::Duration;
+///
+/// let metrics = Metrics::new();
+/// metrics.record("/users/:id", "GET", 200, Duration::from_millis(20));
+///
+/// let text = metrics.render();
+/// assert!(text.contains(r#"browzer_requests_total{route="/users/:id",method="GET",status="200"} 1"#));
+/// assert!(text.contains(
+///     r#"browzer_request_duration_seconds_bucket{route="/users/:id",method="GET",le="0.025"} 1"#
+/// ));
+/// ```
+// ----- Metrics struct
+#[derive(Debug)]
+pub struct Metrics {
+    buckets: Vec<f64>,
+    series: RwLock<BTreeMap<(String, String), Arc<Series>>>,
+    gauges: Option<ServerGauges>,
+}
+
+// default implementation for Metrics struct
+impl Default for Metrics {
+    fn default() -> Self {
+        return Metrics::new();
+    }
+}
+
+impl Metrics {
+    /// Creates a new `Metrics` with the `DEFAULT_BUCKETS` latency histogram buckets.
+    pub fn new() -> Metrics {
+        return Metrics {
+            buckets: DEFAULT_BUCKETS.to_vec(),
+            series: RwLock::new(BTreeMap::new()),
+            gauges: None,
+        };
+    }
+
+    /// Sets the upper bounds of the latency histogram buckets, which are sorted and deduplicated.
+    pub fn buckets(mut self, bounds: &[Duration]) -> Metrics {
+        let mut buckets: Vec<f64> = bounds.iter().map(|bound| bound.as_secs_f64()).collect();
+        buckets.sort_by(|a, b| a.total_cmp(b));
+        buckets.dedup();
+        self.buckets = buckets;
+        return self;
+    }
+
+    // reports the connections, queue, workers and panics of a server along with the requests
+    pub(crate) fn attach(
+        &mut self,
+        active_connections: Arc<AtomicUsize>,
+        queue: Arc<thread_pool::JobQueue>,
+        panic_log: Arc<panics::PanicLog>,
+    ) {
+        self.gauges = Some(ServerGauges {
+            active_connections,
+            queue,
+            panic_log,
+        });
+    }
+
+    /// Records an answered request.
+    ///
+    /// # Arguments
+    ///
+    /// - `route` - The route path pattern the request matched, `UNMATCHED_ROUTE` if none did.
+    /// - `method` - The method of the request.
+    /// - `status` - The status code of the response.
+    /// - `latency` - The time from receiving the request until it's response was generated.
+    pub fn record(&self, route: &str, method: &str, status: u16, latency: Duration) {
+        let key = (route.to_string(), method.to_string());
+        let series = self
+            .series
+            .read()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .get(&key)
+            .cloned();
+        let series = match series {
+            Some(series) => series,
+            None => Arc::clone(
+                self.series
+                    .write()
+                    .unwrap_or_else(|poisoned| poisoned.into_inner())
+                    .entry(key)
+                    .or_insert_with(|| Arc::new(Series::new(self.buckets.len()))),
+            ),
+        };
+
+        let counted = series
+            .statuses
+            .read()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .get(&status)
+            .map(|count| count.fetch_add(1, Ordering::Relaxed))
+            .is_some();
+        if !counted {
+            series
+                .statuses
+                .write()
+                .unwrap_or_else(|poisoned| poisoned.into_inner())
+                .entry(status)
+                .or_insert_with(|| AtomicU64::new(0))
+                .fetch_add(1, Ordering::Relaxed);
+        }
+
+        let seconds = latency.as_secs_f64();
+        let bucket = self
+            .buckets
+            .iter()
+            .position(|bound| seconds <= *bound)
+            .unwrap_or(self.buckets.len());
+        series.buckets[bucket].fetch_add(1, Ordering::Relaxed);
+        series.count.fetch_add(1, Ordering::Relaxed);
+        series.sum_nanos.fetch_add(
+            latency.as_nanos().min(u64::MAX as u128) as u64,
+            Ordering::Relaxed,
+        );
+    }
+
+    /// Renders the metrics in the Prometheus text format(see `CONTENT_TYPE`).
+    pub fn render(&self) -> String {
+        let mut text = String::new();
+        // writing to a `String` can't fail
+        let _ = self.write_to(&mut text);
+        return text;
+    }
+
+    fn write_to(&self, text: &mut String) -> fmt::Result {
+        let series = self
+            .series
+            .read()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+
+        writeln!(
+            text,
+            "# HELP browzer_requests_total The number of requests answered."
+        )?;
+        writeln!(text, "# TYPE browzer_requests_total counter")?;
+        for ((route, method), series) in series.iter() {
+            let statuses = series
+                .statuses
+                .read()
+                .unwrap_or_else(|poisoned| poisoned.into_inner());
+            for (status, count) in statuses.iter() {
+                writeln!(
+                    text,
+                    "browzer_requests_total{{route=\"{}\",method=\"{}\",status=\"{}\"}} {}",
+                    escape_label(route),
+                    escape_label(method),
+                    status,
+                    count.load(Ordering::Relaxed)
+                )?;
+            }
+        }
+
+        writeln!(
+            text,
+            "# HELP browzer_request_duration_seconds The time from receiving a request until it's response was generated."
+        )?;
+        writeln!(text, "# TYPE browzer_request_duration_seconds histogram")?;
+        for ((route, method), series) in series.iter() {
+            let labels = format!(
+                "route=\"{}\",method=\"{}\"",
+                escape_label(route),
+                escape_label(method)
+            );
+            // the buckets of the text format are cumulative
+            let mut cumulative = 0;
+            for (i, bound) in self.buckets.iter().enumerate() {
+                cumulative += series.buckets[i].load(Ordering::Relaxed);
+                writeln!(
+                    text,
+                    "browzer_request_duration_seconds_bucket{{{},le=\"{}\"}} {}",
+                    labels, bound, cumulative
+                )?;
+            }
+            let count = series.count.load(Ordering::Relaxed);
+            writeln!(
+                text,
+                "browzer_request_duration_seconds_bucket{{{},le=\"+Inf\"}} {}",
+                labels, count
+            )?;
+            writeln!(
+                text,
+                "browzer_request_duration_seconds_sum{{{}}} {}",
+                labels,
+                Duration::from_nanos(series.sum_nanos.load(Ordering::Relaxed)).as_secs_f64()
+            )?;
+            writeln!(
+                text,
+                "browzer_request_duration_seconds_count{{{}}} {}",
+                labels, count
+            )?;
+        }
+
+        if let Some(ref gauges) = self.gauges {
+            let gauge_values = [
+                (
+                    "browzer_active_connections",
+                    "The number of open connections.",
+                    gauges.active_connections.load(Ordering::SeqCst),
+                ),
+                (
+                    "browzer_queued_connections",
+                    "The number of connections waiting for a worker thread.",
+                    gauges.queue.queued(),
+                ),
+                (
+                    "browzer_workers",
+                    "The number of worker threads.",
+                    gauges.queue.workers(),
+                ),
+            ];
+            for (name, help, value) in gauge_values {
+                writeln!(text, "# HELP {} {}", name, help)?;
+                writeln!(text, "# TYPE {} gauge", name)?;
+                writeln!(text, "{} {}", name, value)?;
+            }
+
+            let panics = gauges.panic_log.counts();
+            writeln!(
+                text,
+                "# HELP browzer_worker_panics_total The number of worker panics."
+            )?;
+            writeln!(text, "# TYPE browzer_worker_panics_total counter")?;
+            writeln!(
+                text,
+                "browzer_worker_panics_total{{phase=\"handling\"}} {}",
+                panics.handling
+            )?;
+            writeln!(
+                text,
+                "browzer_worker_panics_total{{phase=\"writing\"}} {}",
+                panics.writing
+            )?;
+            writeln!(
+                text,
+                "browzer_worker_panics_total{{phase=\"job\"}} {}",
+                panics.jobs
+            )?;
+        }
+        return Ok(());
+    }
+}
+
+// escapes a label value of the text format
+fn escape_label(value: &str) -> String {
+    return value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n");
+}
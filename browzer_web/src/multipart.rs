@@ -0,0 +1,467 @@
+//! This module provides a streaming `multipart/form-data` parser.
+//!
+//! The parser reads the multipart body in small chunks and hands the content of every part to a
+//! sink(any `std::io::Write` implementation) as soon as it arrives, instead of collecting whole
+//! parts in memory first. A sink factory can be registered per field, so file parts can be written
+//! directly to temporary files or object storage writers while plain form fields are kept in memory.
+
+// internal crate imports
+use crate::{error, request};
+
+// standard library imports
+use std::{
+    collections::HashMap,
+    fmt, fs,
+    io::{self, Read, Write},
+};
+
+// the size of the chunks the multipart body is read in
+const CHUNK_SIZE: usize = 8 * 1024;
+// the maximum size of the headers of a single part
+const MAX_PART_HEADERS_SIZE: usize = 8 * 1024;
+
+/// A boxed sink factory, which creates the writer the content of a part is streamed to.
+pub type SinkFactory = Box<dyn Fn(&PartHeaders) -> io::Result<Box<dyn PartSink>> + Send + Sync>;
+
+/// A writer the content of a part is streamed to.
+///
+/// Once the whole content of the part was written, the parser calls `PartSink::finish`, which
+/// flushes the writer by default. Sinks which have to do more at the end of a part, like verifying
+/// a checksum, override it. `flush` may be called at any time and never means the part is complete.
+pub trait PartSink: Write {
+    /// Completes the part, after it's whole content was written to the sink. An error fails the
+    /// parsing of the body.
+    fn finish(&mut self) -> io::Result<()> {
+        return self.flush();
+    }
+}
+
+impl PartSink for Vec<u8> {}
+
+impl PartSink for io::Sink {}
+
+impl PartSink for fs::File {}
+
+impl<W: Write> PartSink for io::BufWriter<W> {}
+
+/// The headers of a single part of a multipart body.
+///
+/// # Fields
+///
+/// - `name` - The field name, from the `name` parameter of the `Content-Disposition` header.
+/// - `filename` - The file name, from the `filename` parameter of the `Content-Disposition` header,
+///   only present for file parts.
+/// - `content_type` - The `Content-Type` header of the part, if any.
+/// - `headers` - All headers of the part.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PartHeaders {
+    pub name: String,
+    pub filename: Option<String>,
+    pub content_type: Option<String>,
+    pub headers: HashMap<String, String>,
+}
+
+/// A file part of a parsed multipart body.
+///
+/// # Fields
+///
+/// - `headers` - The headers of the part.
+/// - `size` - The size of the part's content in bytes.
+/// - `data` - The content of the part if it was kept in memory, `None` if it was streamed to a
+///   sink.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FilePart {
+    pub headers: PartHeaders,
+    pub size: u64,
+    pub data: Option<Vec<u8>>,
+}
+
+/// The result of parsing a multipart body.
+///
+/// # Fields
+///
+/// - `fields` - The plain form fields(parts without a file name and without a sink), by name.
+/// - `files` - The file parts and the parts streamed to sinks, in the order they were received.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct MultipartForm {
+    pub fields: HashMap<String, String>,
+    pub files: Vec<FilePart>,
+}
+
+/// A configurable `multipart/form-data` parser.
+///
+/// # Examples
+///
+/// ```rust
+/// use browzer_web::multipart::{Multipart, PartSink};
+/// use std::{io, sync::{Arc, Mutex}};
+///
+/// // a sink writing into a shared buffer, in practice this would be a file or an upload stream
+/// struct SharedSink(Arc<Mutex<Vec<u8>>>);
+/// impl io::Write for SharedSink {
+///     fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+///         self.0.lock().unwrap().extend_from_slice(buf);
+///         return Ok(buf.len());
+///     }
+///     fn flush(&mut self) -> io::Result<()> {
+///         return Ok(());
+///     }
+/// }
+/// impl PartSink for SharedSink {}
+///
+/// let body = "--XyZ\r\n\
+///     Content-Disposition: form-data; name=\"title\"\r\n\r\n\
+///     Holiday\r\n\
+///     --XyZ\r\n\
+///     Content-Disposition: form-data; name=\"video\"; filename=\"beach.mp4\"\r\n\
+///     Content-Type: video/mp4\r\n\r\n\
+///     not really a video\r\n\
+///     --XyZ--\r\n";
+///
+/// let stored = Arc::new(Mutex::new(Vec::new()));
+/// let sink_buffer = Arc::clone(&stored);
+/// let form = Multipart::new("XyZ")
+///     .sink("video", move |_part| Ok(Box::new(SharedSink(Arc::clone(&sink_buffer)))))
+///     .parse(body.as_bytes())
+///     .unwrap();
+///
+/// assert_eq!(form.fields.get("title").unwrap(), "Holiday");
+/// assert_eq!(form.files[0].headers.filename.as_deref(), Some("beach.mp4"));
+/// assert_eq!(form.files[0].size, 18);
+/// assert_eq!(form.files[0].data, None);
+/// assert_eq!(stored.lock().unwrap().as_slice(), b"not really a video");
+/// ```
+// ----- Multipart struct
+pub struct Multipart {
+    boundary: String,
+    sinks: HashMap<String, SinkFactory>,
+    file_sink: Option<SinkFactory>,
+    max_field_size: usize,
+}
+
+impl fmt::Debug for Multipart {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Multipart")
+            .field("boundary", &self.boundary)
+            .field("sinks", &self.sinks.keys().collect::<Vec<_>>())
+            .field(
+                "file_sink",
+                &"Option<Box<dyn Fn(&PartHeaders) -> io::Result<Box<dyn PartSink>> + Send + Sync>>",
+            )
+            .field("max_field_size", &self.max_field_size)
+            .finish()
+    }
+}
+
+impl Multipart {
+    /// Creates a new `Multipart` parser for the given boundary.
+    ///
+    /// Parts without a sink are kept in memory, each of them limited to 1 MiB(see
+    /// `Multipart::max_field_size`).
+    pub fn new(boundary: &str) -> Multipart {
+        return Multipart {
+            boundary: boundary.to_string(),
+            sinks: HashMap::new(),
+            file_sink: None,
+            max_field_size: 1024 * 1024,
+        };
+    }
+
+    /// Creates a new `Multipart` parser using the boundary from the `Content-Type` header of a
+    /// request.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `MissingBoundary` error if the request isn't a `multipart/form-data` request or
+    /// it's `Content-Type` header has no boundary.
+    pub fn from_request(request: &request::Request) -> Result<Multipart, error::MultipartError> {
+        let content_type = match request.header("Content-Type") {
+            Some(content_type) => content_type,
+            None => return Err(error::MultipartError::MissingBoundary),
+        };
+        let mut params = content_type.split(';');
+        let media_type = params.next().unwrap_or("").trim();
+        if !media_type.eq_ignore_ascii_case("multipart/form-data") {
+            return Err(error::MultipartError::MissingBoundary);
+        }
+        for param in params {
+            match param.trim().split_once('=') {
+                Some((name, value)) if name.trim().eq_ignore_ascii_case("boundary") => {
+                    let boundary = value.trim().trim_matches('"');
+                    if boundary.is_empty() {
+                        break;
+                    }
+                    return Ok(Multipart::new(boundary));
+                }
+                _ => {}
+            }
+        }
+        return Err(error::MultipartError::MissingBoundary);
+    }
+
+    /// Streams the content of the parts of a field to the writers created by a sink factory,
+    /// which is called once for every part with that field name.
+    pub fn sink<F>(mut self, field: &str, factory: F) -> Multipart
+    where
+        F: Fn(&PartHeaders) -> io::Result<Box<dyn PartSink>> + Send + Sync + 'static,
+    {
+        self.sinks.insert(field.to_string(), Box::new(factory));
+        return self;
+    }
+
+    /// Streams the content of every file part without a field specific sink to the writers
+    /// created by a sink factory.
+    pub fn file_sink<F>(mut self, factory: F) -> Multipart
+    where
+        F: Fn(&PartHeaders) -> io::Result<Box<dyn PartSink>> + Send + Sync + 'static,
+    {
+        self.file_sink = Some(Box::new(factory));
+        return self;
+    }
+
+    /// Sets the maximum size, in bytes, of a single part which is kept in memory.
+    pub fn max_field_size(mut self, max_field_size: usize) -> Multipart {
+        self.max_field_size = max_field_size;
+        return self;
+    }
+
+    /// Parses a multipart body, streaming the parts with sinks to them and keeping the rest in
+    /// memory.
+    ///
+    /// # Arguments
+    ///
+    /// - `reader` - The multipart body, like `Request::body_bytes`.
+    ///
+    /// # Returns
+    ///
+    /// - `Result<MultipartForm, MultipartError>` - The parsed form, or an error if the body is
+    ///   malformed, a part kept in memory exceeds the size limit or a sink failed.
+    pub fn parse<R: Read>(&self, reader: R) -> Result<MultipartForm, error::MultipartError> {
+        let mut stream = ChunkReader::new(reader);
+        let mut form = MultipartForm::default();
+        let first_delimiter = format!("--{}", self.boundary).into_bytes();
+        let delimiter = format!("\r\n--{}", self.boundary).into_bytes();
+
+        // skip the preamble, up to and including the first delimiter
+        if !stream.skip_past(&first_delimiter)? {
+            return Err(malformed("missing the first boundary"));
+        }
+
+        loop {
+            // a delimiter is either followed by CRLF and the next part, or by `--` which ends the
+            // multipart body
+            let suffix = stream.take(2)?;
+            match suffix.as_slice() {
+                b"--" => return Ok(form),
+                b"\r\n" => {}
+                _ => return Err(malformed("invalid boundary delimiter")),
+            }
+
+            let headers =
+                parse_part_headers(&stream.take_until(b"\r\n\r\n", MAX_PART_HEADERS_SIZE)?)?;
+            let sink_factory = match self.sinks.get(&headers.name) {
+                Some(factory) => Some(factory),
+                None => match headers.filename {
+                    Some(_) => self.file_sink.as_ref(),
+                    None => None,
+                },
+            };
+
+            match sink_factory {
+                Some(factory) => {
+                    let mut sink = factory(&headers)?;
+                    let size = stream.copy_until(&delimiter, &mut *sink, None)?;
+                    sink.finish()?;
+                    form.files.push(FilePart {
+                        headers,
+                        size,
+                        data: None,
+                    });
+                }
+                None => {
+                    let mut data = Vec::new();
+                    let size =
+                        stream.copy_until(&delimiter, &mut data, Some(self.max_field_size))?;
+                    match headers.filename {
+                        Some(_) => form.files.push(FilePart {
+                            headers,
+                            size,
+                            data: Some(data),
+                        }),
+                        None => {
+                            let value = String::from_utf8_lossy(&data).to_string();
+                            form.fields.insert(headers.name, value);
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+// a minimal buffered reader which supports searching for delimiters across chunk borders
+struct ChunkReader<R: Read> {
+    reader: R,
+    buffer: Vec<u8>,
+    eof: bool,
+}
+
+impl<R: Read> ChunkReader<R> {
+    fn new(reader: R) -> ChunkReader<R> {
+        return ChunkReader {
+            reader,
+            buffer: Vec::with_capacity(CHUNK_SIZE),
+            eof: false,
+        };
+    }
+
+    // reads the next chunk into the buffer, returning `false` at the end of the input
+    fn fill(&mut self) -> io::Result<bool> {
+        if self.eof {
+            return Ok(false);
+        }
+        let mut chunk = [0; CHUNK_SIZE];
+        loop {
+            match self.reader.read(&mut chunk) {
+                Ok(0) => {
+                    self.eof = true;
+                    return Ok(false);
+                }
+                Ok(n) => {
+                    self.buffer.extend_from_slice(&chunk[..n]);
+                    return Ok(true);
+                }
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                Err(e) => return Err(e),
+            }
+        }
+    }
+
+    // takes exactly `n` bytes from the input
+    fn take(&mut self, n: usize) -> Result<Vec<u8>, error::MultipartError> {
+        while self.buffer.len() < n {
+            if !self.fill()? {
+                return Err(malformed("unexpected end of the body"));
+            }
+        }
+        return Ok(self.buffer.drain(..n).collect());
+    }
+
+    // discards the input up to and including `delimiter`, returning `false` if it never occurs
+    fn skip_past(&mut self, delimiter: &[u8]) -> Result<bool, error::MultipartError> {
+        loop {
+            if let Some(index) = find(&self.buffer, delimiter) {
+                self.buffer.drain(..index + delimiter.len());
+                return Ok(true);
+            }
+            // keep the bytes which could be the start of a delimiter split across two chunks
+            let keep = delimiter.len().saturating_sub(1).min(self.buffer.len());
+            self.buffer.drain(..self.buffer.len() - keep);
+            if !self.fill()? {
+                return Ok(false);
+            }
+        }
+    }
+
+    // takes the input up to `delimiter`, discarding the delimiter itself
+    fn take_until(
+        &mut self,
+        delimiter: &[u8],
+        limit: usize,
+    ) -> Result<Vec<u8>, error::MultipartError> {
+        loop {
+            if let Some(index) = find(&self.buffer, delimiter) {
+                let data = self.buffer.drain(..index).collect();
+                self.buffer.drain(..delimiter.len());
+                return Ok(data);
+            }
+            if self.buffer.len() > limit {
+                return Err(malformed("part headers are too large"));
+            }
+            if !self.fill()? {
+                return Err(malformed("unexpected end of the body"));
+            }
+        }
+    }
+
+    // streams the input up to `delimiter` to a writer, discarding the delimiter itself, and
+    // returns the number of bytes written
+    fn copy_until<W: Write + ?Sized>(
+        &mut self,
+        delimiter: &[u8],
+        writer: &mut W,
+        limit: Option<usize>,
+    ) -> Result<u64, error::MultipartError> {
+        let mut written = 0;
+        loop {
+            let (end, found) = match find(&self.buffer, delimiter) {
+                Some(index) => (index, true),
+                // everything but a possible partial delimiter at the end can be written out
+                None => (self.buffer.len().saturating_sub(delimiter.len() - 1), false),
+            };
+            if let Some(limit) = limit {
+                if written as usize + end > limit {
+                    return Err(error::MultipartError::PartTooLarge(limit));
+                }
+            }
+            writer.write_all(&self.buffer[..end])?;
+            written += end as u64;
+            self.buffer.drain(..end);
+            if found {
+                self.buffer.drain(..delimiter.len());
+                return Ok(written);
+            }
+            if !self.fill()? {
+                return Err(malformed("unexpected end of the body"));
+            }
+        }
+    }
+}
+
+// parses the headers of a single part
+fn parse_part_headers(raw_headers: &[u8]) -> Result<PartHeaders, error::MultipartError> {
+    let mut part = PartHeaders::default();
+    let raw_headers = String::from_utf8_lossy(raw_headers);
+    for line in raw_headers.split("\r\n") {
+        let (name, value) = match line.split_once(':') {
+            Some((name, value)) => (name.trim(), value.trim()),
+            None => return Err(malformed("invalid part header")),
+        };
+        if name.eq_ignore_ascii_case("Content-Disposition") {
+            for param in value.split(';').skip(1) {
+                match param.trim().split_once('=') {
+                    Some((key, value)) => {
+                        let value = value.trim().trim_matches('"').to_string();
+                        match key.trim().to_ascii_lowercase().as_str() {
+                            "name" => part.name = value,
+                            "filename" => part.filename = Some(value),
+                            _ => {}
+                        }
+                    }
+                    None => {}
+                }
+            }
+        } else if name.eq_ignore_ascii_case("Content-Type") {
+            part.content_type = Some(value.to_string());
+        }
+        part.headers.insert(name.to_string(), value.to_string());
+    }
+    if part.name.is_empty() {
+        return Err(malformed("part without a field name"));
+    }
+    return Ok(part);
+}
+
+// returns the index of the first occurrence of `needle` in `haystack`
+fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    if needle.is_empty() || haystack.len() < needle.len() {
+        return None;
+    }
+    return haystack
+        .windows(needle.len())
+        .position(|window| window == needle);
+}
+
+fn malformed(message: &str) -> error::MultipartError {
+    return error::MultipartError::Malformed(message.to_string());
+}
@@ -0,0 +1,275 @@
+//! This module tells clients when to retry the requests a `WebServer` turned away under overload.
+//!
+//! The server answers with `503 Service Unavailable` when it has more connections than it can take
+//! (see `WebServer::max_connections`), when the queue of connections waiting for a worker is full
+//! (see `WebServer::queue_limit`), when a handler doesn't finish within the request timeout and
+//! while it's in maintenance mode (see `WebServer::maintenance_handle`). Those responses carry a
+//! `Retry-After` header, computed by a retry policy from the current `OverloadState`, so
+//! well-behaved clients back off for about as long as the server needs to work through it's
+//! backlog instead of retrying right away. The default policy, `queue_drain_retry_after`, divides
+//! the connections waiting for a worker by the rate requests were completed at recently, see
+//! `WebServer::retry_after` for replacing it.
+
+// internal crate imports
+use crate::response;
+
+// standard library imports
+use std::{
+    fmt,
+    sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        Arc, Mutex, MutexGuard,
+    },
+    time::{Duration, Instant},
+};
+
+/// The shortest `Retry-After` delay the default policy asks for.
+pub const MIN_RETRY_AFTER: Duration = Duration::from_secs(1);
+
+/// The longest `Retry-After` delay the default policy asks for, also used in maintenance mode and
+/// while no request was completed recently and the backlog doesn't drain at all.
+pub const MAX_RETRY_AFTER: Duration = Duration::from_secs(60);
+
+// the number of seconds the completed requests are counted for in the drain rate, older ones are
+// forgotten
+const DRAIN_WINDOW_SECONDS: usize = 10;
+
+/// A boxed retry policy, computing the `Retry-After` delay of a `503 Service Unavailable`
+/// response, `None` leaves the header out.
+pub type RetryAfterPolicy = Box<dyn Fn(&OverloadState) -> Option<Duration> + 'static + Send + Sync>;
+
+/// Why a request was answered with `503 Service Unavailable`.
+///
+/// # Variants
+///
+/// - `ConnectionLimit` - The connection was over the `max_connections` soft cap and shed right
+///   after it was accepted.
+/// - `RequestTimeout` - The handler of the request didn't finish within the request timeout.
+/// - `QueueFull` - Every worker was busy and the queue of connections waiting for one was full,
+///   see `WebServer::queue_limit`.
+/// - `Maintenance` - The server is in maintenance mode, see `WebServer::maintenance_handle`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OverloadReason {
+    ConnectionLimit,
+    RequestTimeout,
+    QueueFull,
+    Maintenance,
+}
+
+/// The state of the server when it turned a request away, as passed to the retry policy.
+///
+/// # Fields
+///
+/// - `reason` - The `OverloadReason` the request was turned away for.
+/// - `active_connections` - The number of connections accepted and not closed yet, including the
+///   ones waiting for a worker thread.
+/// - `max_connections` - The maximum number of concurrent connections.
+/// - `workers` - The number of worker threads handling connections.
+/// - `drain_rate` - The number of requests completed per second recently, `0.0` if none was.
+#[derive(Debug, Clone, PartialEq)]
+pub struct OverloadState {
+    pub reason: OverloadReason,
+    pub active_connections: usize,
+    pub max_connections: usize,
+    pub workers: usize,
+    pub drain_rate: f64,
+}
+
+impl OverloadState {
+    /// Returns the number of connections waiting for a worker thread, assuming every worker is
+    /// busy with a connection.
+    pub fn queued(&self) -> usize {
+        return self.active_connections.saturating_sub(self.workers);
+    }
+}
+
+/// The default retry policy: the time the server needs to drain the connections waiting for a
+/// worker(and the one being turned away) at the recent drain rate, between `MIN_RETRY_AFTER` and
+/// `MAX_RETRY_AFTER`. Clients turned away in maintenance mode are asked to wait `MAX_RETRY_AFTER`,
+/// as the backlog says nothing about when the maintenance is over.
+///
+/// # Examples
+///
+/// ```rust
+/// use browzer_web::overload::{queue_drain_retry_after, OverloadReason, OverloadState};
+/// use std::time::Duration;
+///
+/// let state = OverloadState {
+///     reason: OverloadReason::ConnectionLimit,
+///     active_connections: 43,
+///     max_connections: 43,
+///     workers: 4,
+///     drain_rate: 8.0,
+/// };
+/// // 39 queued connections and the rejected one, at 8 requests per second
+/// assert_eq!(queue_drain_retry_after(&state), Some(Duration::from_secs(5)));
+/// ```
+pub fn queue_drain_retry_after(state: &OverloadState) -> Option<Duration> {
+    if state.reason == OverloadReason::Maintenance || state.drain_rate <= 0.0 {
+        return Some(MAX_RETRY_AFTER);
+    }
+    let backlog = (state.queued() + 1) as f64;
+    let seconds = (backlog / state.drain_rate).min(MAX_RETRY_AFTER.as_secs_f64());
+    return Some(Duration::from_secs_f64(seconds).max(MIN_RETRY_AFTER));
+}
+
+/// Puts a listening `WebServer` in and out of maintenance mode, see
+/// `WebServer::maintenance_handle`.
+///
+/// Handles are cheap to clone, and all clones switch the same server.
+///
+/// # Examples
+///
+/// ```rust,no_run
+/// # use browzer_web::WebServer;
+/// let server = WebServer::new("127.0.0.1:8080".to_string(), 4);
+/// let maintenance = server.maintenance_handle();
+///
+/// std::thread::spawn(move || {
+///     maintenance.enable();
+///     // migrate the database, every request is answered with `503 Service Unavailable` meanwhile
+///     maintenance.disable();
+/// });
+/// server.listen();
+/// ```
+// ----- MaintenanceHandle struct
+#[derive(Debug, Clone, Default)]
+pub struct MaintenanceHandle {
+    enabled: Arc<AtomicBool>,
+}
+
+impl MaintenanceHandle {
+    /// Answers every request from now on with `503 Service Unavailable`.
+    pub fn enable(&self) {
+        self.enabled.store(true, Ordering::SeqCst);
+    }
+
+    /// Handles requests again.
+    pub fn disable(&self) {
+        self.enabled.store(false, Ordering::SeqCst);
+    }
+
+    /// Returns whether the server is in maintenance mode.
+    pub fn is_enabled(&self) -> bool {
+        return self.enabled.load(Ordering::SeqCst);
+    }
+}
+
+// the requests completed during each of the last `DRAIN_WINDOW_SECONDS` seconds, a slot holds the
+// second(counted from `started`) it's count belongs to, so stale slots are told apart
+#[derive(Debug)]
+struct DrainWindow {
+    started: Instant,
+    completed: [(u64, u64); DRAIN_WINDOW_SECONDS],
+}
+
+/// Measures how fast requests are completed and signals when to retry on `503 Service
+/// Unavailable` responses, see `WebServer::retry_after`.
+///
+/// # Fields
+///
+/// - `policy` - An optional retry policy used instead of `queue_drain_retry_after`.
+// ----- OverloadSignal struct
+pub struct OverloadSignal {
+    pub policy: Option<RetryAfterPolicy>,
+    pub(crate) maintenance: MaintenanceHandle,
+    active_connections: Arc<AtomicUsize>,
+    workers: usize,
+    window: Mutex<DrainWindow>,
+}
+
+impl fmt::Debug for OverloadSignal {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("OverloadSignal")
+            .field(
+                "policy",
+                &"Option<Box<dyn Fn(&OverloadState) -> Option<Duration> + 'static + Send + Sync>>",
+            )
+            .field("maintenance", &self.maintenance.is_enabled())
+            .field("workers", &self.workers)
+            .field("drain_rate", &self.drain_rate())
+            .finish()
+    }
+}
+
+impl OverloadSignal {
+    // creates a signal for a server, sharing it's count of active connections
+    pub(crate) fn new(active_connections: Arc<AtomicUsize>, workers: usize) -> OverloadSignal {
+        return OverloadSignal {
+            policy: None,
+            maintenance: MaintenanceHandle::default(),
+            active_connections,
+            workers,
+            window: Mutex::new(DrainWindow {
+                started: Instant::now(),
+                completed: [(0, 0); DRAIN_WINDOW_SECONDS],
+            }),
+        };
+    }
+
+    /// Returns the number of requests completed per second recently.
+    ///
+    /// Only the last ten seconds are counted, from the second the oldest of those requests was
+    /// completed in, so a server which was idle before a burst of requests isn't taken for a slow
+    /// one.
+    pub fn drain_rate(&self) -> f64 {
+        let window = self.window();
+        let elapsed = window.started.elapsed().as_secs_f64();
+        let now = elapsed as u64;
+        let recent = window.completed.iter().filter(|(second, count)| {
+            return *count > 0 && *second + DRAIN_WINDOW_SECONDS as u64 > now;
+        });
+        let (oldest, completed) = recent.fold((now, 0), |(oldest, completed), (second, count)| {
+            return (oldest.min(*second), completed + count);
+        });
+        // the current second counts as a whole one, so the first requests don't make for a burst
+        let span = (elapsed - oldest as f64).max(1.0);
+        return completed as f64 / span;
+    }
+
+    // counts a response written to a client towards the drain rate
+    pub(crate) fn request_completed(&self) {
+        let mut window = self.window();
+        let now = window.started.elapsed().as_secs();
+        let slot = &mut window.completed[now as usize % DRAIN_WINDOW_SECONDS];
+        if slot.0 != now {
+            *slot = (now, 0);
+        }
+        slot.1 += 1;
+    }
+    // adds the `Retry-After` header the retry policy asks for to a `503 Service Unavailable`
+    // response
+    pub(crate) fn signal(
+        &self,
+        response: &mut response::Response,
+        reason: OverloadReason,
+        max_connections: usize,
+    ) {
+        let state = OverloadState {
+            reason,
+            active_connections: self.active_connections.load(Ordering::SeqCst),
+            max_connections,
+            workers: self.workers,
+            drain_rate: self.drain_rate(),
+        };
+        let retry_after = match self.policy {
+            Some(ref policy) => (policy)(&state),
+            None => queue_drain_retry_after(&state),
+        };
+        if let Some(retry_after) = retry_after {
+            // the header has a precision of seconds, and `0` would invite an immediate retry
+            let seconds = retry_after.as_secs() + (retry_after.subsec_nanos() > 0) as u64;
+            response
+                .headers
+                .insert("Retry-After".to_string(), seconds.max(1).to_string());
+        }
+    }
+
+    // locks the drain window, recovering it if another thread panicked while holding the lock
+    fn window(&self) -> MutexGuard<'_, DrainWindow> {
+        return self
+            .window
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+    }
+}
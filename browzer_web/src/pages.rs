@@ -0,0 +1,187 @@
+//! This module renders the HTML pages the framework generates itself, like error pages and the
+//! route listing of `WebServer::enable_route_help`, from one set of templates which applications
+//! can replace with their own branding, see `WebServer::page_templates`.
+//!
+//! The templates are rendered by the engine of the `templates` module, so besides `{{name}}`
+//! placeholders they can use it's conditions and filters, like `{% if path %}`. Values inserted
+//! into a template are never rendered as templates themselves.
+
+// external crate imports
+use serde_json::json;
+
+// internal crate imports
+use crate::{error::TemplateError, response, templates, utils};
+
+// standard library imports
+use std::sync::Arc;
+
+// the names the layout and the error page are rendered under
+const LAYOUT: &str = "layout";
+const ERROR_PAGE: &str = "error_page";
+
+/// The default layout every page is inserted into.
+pub const DEFAULT_LAYOUT: &str = "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>{{title}}</title>\n</head>\n<body>\n{{content}}</body>\n</html>\n";
+
+/// The default content of error pages.
+pub const DEFAULT_ERROR_PAGE: &str = "<h1>{{status}} {{reason}}</h1>\n";
+
+/// The templates of the pages generated by the framework.
+///
+/// - The layout is the whole document, with the `{{title}}` and `{{content}}` placeholders.
+/// - The error page is the content of error pages, with the `{{status}}`(like `404`),
+///   `{{reason}}`(like `Not Found`) and `{{path}}` placeholders. Error pages are titled
+///   `{{status}} {{reason}}`.
+///
+/// All values are HTML-escaped before they are inserted, except for the content of the layout
+/// which already is HTML. Placeholders without a value are rendered as empty.
+///
+/// # Examples
+///
+/// ```rust
+/// use browzer_web::{pages::PageTemplates, utils::HttpStatusCode};
+///
+/// let templates = PageTemplates::new()
+///     .layout("<html><title>{{title}} - Acme</title><main>{{content}}</main></html>")
+///     .error_page("<h1>{{reason}}</h1><p>Nothing at <code>{{path}}</code></p>");
+///
+/// let response = templates.error_response(HttpStatusCode::NotFound, "/<missing>");
+/// assert_eq!(response.status_code, HttpStatusCode::NotFound);
+/// assert_eq!(
+///     response.body,
+///     "<html><title>404 Not Found - Acme</title><main><h1>Not Found</h1><p>Nothing at <code>/&lt;missing&gt;</code></p></main></html>"
+/// );
+/// ```
+// ----- PageTemplates struct
+#[derive(Debug, Clone)]
+pub struct PageTemplates {
+    layout: String,
+    error_page: String,
+    // the layout and the error page, parsed once
+    templates: Arc<templates::Templates>,
+}
+
+impl PartialEq for PageTemplates {
+    fn eq(&self, other: &Self) -> bool {
+        return self.layout == other.layout && self.error_page == other.error_page;
+    }
+}
+
+impl Eq for PageTemplates {}
+
+// default implementation for PageTemplates struct
+impl Default for PageTemplates {
+    fn default() -> Self {
+        return PageTemplates::new();
+    }
+}
+
+impl PageTemplates {
+    /// Creates a new `PageTemplates` with the default layout and error page.
+    pub fn new() -> PageTemplates {
+        return PageTemplates::parse(DEFAULT_LAYOUT, DEFAULT_ERROR_PAGE)
+            .expect("the default page templates are valid");
+    }
+
+    /// Sets the layout every page is inserted into, `DEFAULT_LAYOUT` by default.
+    ///
+    /// # Errors
+    ///
+    /// If the layout isn't a valid template, the current one is kept and an error message is
+    /// printed using `eprintln!`.
+    pub fn layout(self, layout: &str) -> PageTemplates {
+        match PageTemplates::parse(layout, &self.error_page) {
+            Ok(templates) => return templates,
+            Err(e) => {
+                eprintln!("{}", e);
+                return self;
+            }
+        }
+    }
+
+    /// Sets the content of error pages, `DEFAULT_ERROR_PAGE` by default.
+    ///
+    /// # Errors
+    ///
+    /// If the error page isn't a valid template, the current one is kept and an error message is
+    /// printed using `eprintln!`.
+    pub fn error_page(self, error_page: &str) -> PageTemplates {
+        match PageTemplates::parse(&self.layout, error_page) {
+            Ok(templates) => return templates,
+            Err(e) => {
+                eprintln!("{}", e);
+                return self;
+            }
+        }
+    }
+
+    /// Renders a page, inserting it's content into the layout.
+    ///
+    /// # Arguments
+    ///
+    /// - `title` - The title of the page, which is HTML-escaped.
+    /// - `content` - The HTML content of the page, which is inserted as it is.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use browzer_web::pages::PageTemplates;
+    ///
+    /// let templates = PageTemplates::new().layout("<title>{{title}}</title>{{content}}");
+    /// assert_eq!(
+    ///     templates.render("Q&A", "<p>{{title}}</p>"),
+    ///     "<title>Q&amp;A</title><p>{{title}}</p>"
+    /// );
+    /// ```
+    pub fn render(&self, title: &str, content: &str) -> String {
+        let data = json!({ "title": title, "content": content });
+        match self.templates.render_value(LAYOUT, &data, &["content"]) {
+            Ok(page) => return page,
+            Err(e) => {
+                // like a layout including a template which doesn't exist
+                eprintln!("{}", e);
+                return content.to_string();
+            }
+        }
+    }
+
+    /// Renders the error page for a status code as a `text/html` response.
+    ///
+    /// # Arguments
+    ///
+    /// - `status_code` - The `HttpStatusCode` of the error.
+    /// - `path` - The request path for which the error occurred.
+    pub fn error_response(
+        &self,
+        status_code: utils::HttpStatusCode,
+        path: &str,
+    ) -> response::Response {
+        let (reason, code) = status_code.code();
+        let data = json!({ "status": code, "reason": reason, "path": path });
+        let content = match self.templates.render_value(ERROR_PAGE, &data, &[]) {
+            Ok(content) => content,
+            Err(e) => {
+                eprintln!("{}", e);
+                utils::escape_html(reason)
+            }
+        };
+        let body = self.render(&format!("{} {}", code, reason), &content);
+        let mut response = response::Response::new(status_code, body);
+        response.headers.insert(
+            "Content-Type".to_string(),
+            "text/html; charset=utf-8".to_string(),
+        );
+        return response;
+    }
+
+    // parses the layout and the error page
+    fn parse(layout: &str, error_page: &str) -> Result<PageTemplates, TemplateError> {
+        let mut templates = templates::Templates::new();
+        templates.add(LAYOUT, layout)?;
+        templates.add(ERROR_PAGE, error_page)?;
+        return Ok(PageTemplates {
+            layout: layout.to_string(),
+            error_page: error_page.to_string(),
+            templates: Arc::new(templates),
+        });
+    }
+}
@@ -0,0 +1,155 @@
+//! This module records panics of the worker threads while they serve a connection.
+//!
+//! A panic is recorded together with the phase it happened in. A panic while a response is being
+//! written is the dangerous kind: part of the response may already be on the wire, so reusing the
+//! connection(keep-alive) would splice the rest of the broken response into the next one. The
+//! connection is therefore always closed after a panic, and write-phase panics are counted
+//! separately from panics which happened before anything was written. Panics of the other jobs a
+//! `ThreadPool` runs are recorded as well, see `ThreadPool::set_panic_log`.
+
+// standard library imports
+use std::{
+    any::Any,
+    fmt,
+    sync::atomic::{AtomicU64, Ordering},
+};
+
+/// A boxed hook which receives the reports of worker panics.
+pub type PanicHook = Box<dyn Fn(&PanicReport) + 'static + Send + Sync>;
+
+/// The phase of serving a connection in which a panic happened.
+///
+/// # Variants
+///
+/// - `Handling` - While reading a request or generating a response(like in a middleware or route
+///   handler), nothing of the response was written yet. A panic while generating a response is
+///   answered with a `500 Internal Server Error` response before the connection is closed.
+/// - `Writing` - While writing a response, which may have been partially sent to the client.
+/// - `Job` - In a job of the `ThreadPool` outside of serving a connection, which the worker
+///   running it recovered from(see `ThreadPool::recovered_panics`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PanicPhase {
+    Handling,
+    Writing,
+    Job,
+}
+
+/// A report of a panic while serving a connection, as passed to the panic hook.
+///
+/// # Fields
+///
+/// - `phase` - The `PanicPhase` the panic happened in.
+/// - `message` - The panic message, if the panic payload was a string.
+#[derive(Debug)]
+pub struct PanicReport<'a> {
+    pub phase: PanicPhase,
+    pub message: Option<&'a str>,
+}
+
+impl fmt::Display for PanicReport<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.phase {
+            PanicPhase::Handling => write!(f, "Worker panicked while handling a request")?,
+            PanicPhase::Writing => write!(
+                f,
+                "Worker panicked while writing a response, the partially written response was cut off"
+            )?,
+            PanicPhase::Job => write!(f, "Worker recovered from a panicking job")?,
+        }
+        if let Some(message) = self.message {
+            write!(f, ", Error: {}", message)?;
+        }
+        if self.phase == PanicPhase::Job {
+            return Ok(());
+        }
+        return write!(f, " (connection closed)");
+    }
+}
+
+/// The number of worker panics since the server was created, by phase.
+///
+/// # Fields
+///
+/// - `handling` - Panics before anything of a response was written.
+/// - `writing` - Panics while a response was being written.
+/// - `jobs` - Panics of jobs outside of serving a connection.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct PanicCounts {
+    pub handling: u64,
+    pub writing: u64,
+    pub jobs: u64,
+}
+
+impl PanicCounts {
+    /// Returns the number of panics of all phases, each of which a worker recovered from.
+    pub fn total(&self) -> u64 {
+        return self.handling + self.writing + self.jobs;
+    }
+}
+
+/// Counts the worker panics and hands them to the panic hook.
+///
+/// # Fields
+///
+/// - `hook` - An optional hook which receives the reports instead of them being printed to the
+///   console.
+// ----- PanicLog struct
+#[derive(Default)]
+pub struct PanicLog {
+    pub hook: Option<PanicHook>,
+    handling: AtomicU64,
+    writing: AtomicU64,
+    jobs: AtomicU64,
+}
+
+impl fmt::Debug for PanicLog {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("PanicLog")
+            .field(
+                "hook",
+                &"Option<Box<dyn Fn(&PanicReport) + 'static + Send + Sync>>",
+            )
+            .field("counts", &self.counts())
+            .finish()
+    }
+}
+
+impl PanicLog {
+    /// Returns the number of panics recorded so far.
+    pub fn counts(&self) -> PanicCounts {
+        return PanicCounts {
+            handling: self.handling.load(Ordering::SeqCst),
+            writing: self.writing.load(Ordering::SeqCst),
+            jobs: self.jobs.load(Ordering::SeqCst),
+        };
+    }
+
+    // records a panic caught by a worker thread
+    pub(crate) fn record(&self, phase: PanicPhase, payload: &(dyn Any + Send)) {
+        match phase {
+            PanicPhase::Handling => self.handling.fetch_add(1, Ordering::SeqCst),
+            PanicPhase::Writing => self.writing.fetch_add(1, Ordering::SeqCst),
+            PanicPhase::Job => self.jobs.fetch_add(1, Ordering::SeqCst),
+        };
+        let report = PanicReport {
+            phase,
+            message: panic_message(payload),
+        };
+        match self.hook {
+            Some(ref hook) => (hook)(&report),
+            None => eprintln!("{}", report),
+        }
+    }
+}
+
+// returns the message of a panic, if it's payload is a string
+pub(crate) fn panic_message(payload: &(dyn Any + Send)) -> Option<&str> {
+    match payload.downcast_ref::<&str>() {
+        Some(message) => return Some(*message),
+        None => {
+            return payload
+                .downcast_ref::<String>()
+                .map(|message| message.as_str());
+        }
+    }
+}
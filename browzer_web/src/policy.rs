@@ -0,0 +1,36 @@
+//! This module defines the types of the access control policy layer.
+//!
+//! Policies are named predicates over the request `Context`, registered once on the server with
+//! `WebServer::policy` and required by routes with `RouteBuilder::require_policy`. They are
+//! evaluated after all middlewares ran(so authentication middlewares already populated the
+//! context), right before the route handler. If any required policy denies the request, the handler
+//! is skipped and a `403 Forbidden` response is sent instead.
+
+// internal crate imports
+use crate::{context, utils};
+
+/// A boxed policy function which decides whether a request is allowed.
+pub type Policy = Box<dyn Fn(&context::Context) -> bool + 'static + Send + Sync>;
+
+/// A boxed audit hook which is called with every policy decision.
+pub type AuditHook = Box<dyn Fn(&PolicyDecision) + 'static + Send + Sync>;
+
+/// Represents the outcome of evaluating a policy for a request, as reported to the audit hook.
+///
+/// # Fields
+///
+/// - `policy` - The name of the evaluated policy.
+/// - `method` - The HTTP method of the request.
+/// - `path` - The path of the request.
+/// - `allowed` - Whether the policy allowed the request.
+/// - `registered` - Whether a policy with this name was registered at all, unknown policies always
+///   deny requests.
+// ----- PolicyDecision struct
+#[derive(Debug, Clone)]
+pub struct PolicyDecision {
+    pub policy: String,
+    pub method: utils::HttpMethod,
+    pub path: String,
+    pub allowed: bool,
+    pub registered: bool,
+}
@@ -0,0 +1,30 @@
+//! This module re-exports the types most handlers and middlewares need, so that an application
+//! file only needs a single import instead of long paths like `browzer_web::utils::HttpStatusCode`.
+//!
+//! # Examples
+//!
+//! ```rust,no_run
+//! use browzer_web::prelude::*;
+//!
+//! let mut server = WebServer::new("127.0.0.1:8080".to_string(), 4);
+//! server.middleware(|mut c: Context| {
+//!     c.response.headers.insert("X-Powered-By".to_string(), "browzer".to_string());
+//!     return c;
+//! });
+//! server.get("/", |mut c: Context| {
+//!     return c.send_string(HttpStatusCode::OK, "Hello, World!");
+//! });
+//! server.listen();
+//! ```
+
+pub use crate::{
+    context::Context,
+    error::{BindError, QueryError},
+    problem::ProblemDetails,
+    request::{Query, Request},
+    response::{ErrorResponse, IntoResponse, Response},
+    router::{AfterMiddleware, Middleware, RouteHandler},
+    stream::ResponseWriter,
+    utils::{Cookie, HttpMethod, HttpStatusCode, SameSite},
+    WebServer,
+};
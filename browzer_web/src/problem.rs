@@ -0,0 +1,157 @@
+//! This module defines the `ProblemDetails` struct, which represents an RFC 7807 problem details
+//! error response(`application/problem+json`).
+
+// internal crate imports
+use crate::{response, utils};
+
+/// Represents an RFC 7807 problem details object.
+///
+/// # Fields
+///
+/// - `problem_type` - A URI reference identifying the problem type(the `type` member), defaults to
+///   `about:blank`.
+/// - `title` - A short, human-readable summary of the problem type.
+/// - `status` - The `HttpStatusCode` generated for this occurrence of the problem.
+/// - `detail` - An optional human-readable explanation specific to this occurrence of the problem.
+/// - `instance` - An optional URI reference identifying this occurrence of the problem.
+///
+/// # Examples
+///
+/// ```rust
+/// use browzer_web::{problem::ProblemDetails, utils::HttpStatusCode};
+///
+/// let problem = ProblemDetails::new(HttpStatusCode::NotFound)
+///     .with_detail("No user with id 42")
+///     .with_instance("/users/42");
+/// let response = problem.to_response();
+///
+/// assert_eq!(response.status_code, HttpStatusCode::NotFound);
+/// assert_eq!(response.headers.get("Content-Type").unwrap(), "application/problem+json");
+/// assert_eq!(
+///     response.body,
+///     r#"{"type":"about:blank","title":"Not Found","status":404,"detail":"No user with id 42","instance":"/users/42"}"#
+/// );
+/// ```
+// ----- ProblemDetails struct
+#[derive(Debug, Clone)]
+pub struct ProblemDetails {
+    pub problem_type: String,
+    pub title: String,
+    pub status: utils::HttpStatusCode,
+    pub detail: Option<String>,
+    pub instance: Option<String>,
+}
+
+impl ProblemDetails {
+    /// Creates a new `ProblemDetails` for the given status code, with the status code's reason
+    /// phrase as the title and `about:blank` as the problem type.
+    pub fn new(status: utils::HttpStatusCode) -> ProblemDetails {
+        return ProblemDetails {
+            problem_type: String::from("about:blank"),
+            title: status.code().0.to_string(),
+            status,
+            detail: None,
+            instance: None,
+        };
+    }
+
+    /// Creates a new `ProblemDetails` for the given status code, deriving the problem type from a
+    /// base URI and the status code's reason phrase.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use browzer_web::{problem::ProblemDetails, utils::HttpStatusCode};
+    ///
+    /// let problem = ProblemDetails::with_base_uri(HttpStatusCode::MethodNotAllowed, "https://example.com/problems/");
+    /// assert_eq!(problem.problem_type, "https://example.com/problems/method-not-allowed");
+    /// ```
+    pub fn with_base_uri(status: utils::HttpStatusCode, base_uri: &str) -> ProblemDetails {
+        let slug = status.code().0.to_lowercase().replace(' ', "-");
+        let mut problem = ProblemDetails::new(status);
+        problem.problem_type = format!("{}/{}", base_uri.trim_end_matches('/'), slug);
+        return problem;
+    }
+
+    /// Sets the `detail` member of the problem.
+    pub fn with_detail(mut self, detail: &str) -> ProblemDetails {
+        self.detail = Some(detail.to_string());
+        return self;
+    }
+
+    /// Sets the `instance` member of the problem.
+    pub fn with_instance(mut self, instance: &str) -> ProblemDetails {
+        self.instance = Some(instance.to_string());
+        return self;
+    }
+
+    /// Serializes the problem into a JSON object string.
+    pub fn to_json(&self) -> String {
+        let mut json = format!(
+            "{{\"type\":\"{}\",\"title\":\"{}\",\"status\":{}",
+            escape_json(&self.problem_type),
+            escape_json(&self.title),
+            self.status.code().1
+        );
+        if let Some(ref detail) = self.detail {
+            json.push_str(&format!(",\"detail\":\"{}\"", escape_json(detail)));
+        }
+        if let Some(ref instance) = self.instance {
+            json.push_str(&format!(",\"instance\":\"{}\"", escape_json(instance)));
+        }
+        json.push('}');
+        return json;
+    }
+
+    /// Converts the problem into a `Response` with an `application/problem+json` body.
+    pub fn to_response(&self) -> response::Response {
+        let mut response = response::Response::new(self.status.clone(), self.to_json());
+        response.headers.insert(
+            "Content-Type".to_string(),
+            "application/problem+json".to_string(),
+        );
+        return response;
+    }
+}
+
+/// Configuration for framework-generated problem details responses.
+///
+/// When set on the `WebRouter`, every error response generated by the framework itself(like `404
+/// Not Found` or `405 Method Not Allowed`) is sent as an `application/problem+json` body instead of
+/// plain text.
+///
+/// # Fields
+///
+/// - `base_uri` - An optional base URI from which problem types are derived(see
+///   `ProblemDetails::with_base_uri`), `about:blank` is used as the type if it is `None`.
+#[derive(Debug, Clone, Default)]
+pub struct ProblemConfig {
+    pub base_uri: Option<String>,
+}
+
+impl ProblemConfig {
+    /// Creates the `ProblemDetails` for a framework-generated error according to this config.
+    pub fn problem(&self, status: utils::HttpStatusCode) -> ProblemDetails {
+        match self.base_uri {
+            Some(ref base_uri) => return ProblemDetails::with_base_uri(status, base_uri),
+            None => return ProblemDetails::new(status),
+        }
+    }
+}
+
+// escapes a string so that it can be embedded inside a JSON string literal
+fn escape_json(input: &str) -> String {
+    let mut escaped = String::with_capacity(input.len());
+    for c in input.chars() {
+        match c {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            '\n' => escaped.push_str("\\n"),
+            '\r' => escaped.push_str("\\r"),
+            '\t' => escaped.push_str("\\t"),
+            c if (c as u32) < 0x20 => escaped.push_str(&format!("\\u{:04x}", c as u32)),
+            c => escaped.push(c),
+        }
+    }
+    return escaped;
+}
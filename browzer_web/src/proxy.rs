@@ -0,0 +1,210 @@
+//! This module parses the PROXY protocol(version 1 and 2) header which TCP load balancers like
+//! HAProxy send at the start of a connection, carrying the address of the client they forward,
+//! see `WebServer::proxy_protocol`.
+//!
+//! The header is read before anything else, including the TLS handshake, so the load balancer can
+//! pass TLS connections through untouched.
+
+// internal crate imports
+use crate::error;
+
+// standard library imports
+use std::{
+    io::Read,
+    net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr},
+};
+
+// the signature every version 2 header starts with
+pub(crate) const V2_SIGNATURE: [u8; 12] = [
+    0x0D, 0x0A, 0x0D, 0x0A, 0x00, 0x0D, 0x0A, 0x51, 0x55, 0x49, 0x54, 0x0A,
+];
+
+// the maximum length of a version 1 header, line ending included
+pub(crate) const V1_MAX_LENGTH: usize = 107;
+
+/// The addresses carried by a PROXY protocol header.
+///
+/// Both are `None` if the header doesn't describe a proxied TCP connection, like the health checks
+/// of a load balancer(`PROXY UNKNOWN` or a version 2 `LOCAL` command), in which case the address
+/// of the connection itself is the one of the client.
+///
+/// # Fields
+///
+/// - `source` - The address of the client.
+/// - `destination` - The address the client connected to on the load balancer.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ProxyHeader {
+    pub source: Option<SocketAddr>,
+    pub destination: Option<SocketAddr>,
+}
+
+/// Reads a PROXY protocol header from the start of a connection, consuming exactly the bytes of
+/// the header.
+///
+/// # Arguments
+///
+/// - `reader` - The connection, positioned at it's first byte.
+///
+/// # Returns
+///
+/// - `Result<ProxyHeader, ProxyProtocolError>` - The addresses carried by the header.
+///
+/// # Errors
+///
+/// Returns a `ProxyProtocolError::InvalidSignature` error if the connection doesn't start with a
+/// PROXY protocol header, a `Malformed` one if the header is invalid and an `IO` one if the
+/// connection couldn't be read.
+///
+/// # Examples
+///
+/// ```rust
+/// use browzer_web::proxy::read_header;
+/// use std::io::{Cursor, Read};
+///
+/// let mut connection = Cursor::new(
+///     b"PROXY TCP4 203.0.113.7 192.0.2.1 56324 443\r\nGET / HTTP/1.1\r\n\r\n".to_vec(),
+/// );
+/// let header = read_header(&mut connection).unwrap();
+/// assert_eq!(header.source, Some("203.0.113.7:56324".parse().unwrap()));
+///
+/// // the request itself is left unread
+/// let mut rest = String::new();
+/// connection.read_to_string(&mut rest).unwrap();
+/// assert_eq!(rest, "GET / HTTP/1.1\r\n\r\n");
+///
+/// assert!(read_header(&mut Cursor::new(b"GET / HTTP/1.1\r\n\r\n".to_vec())).is_err());
+/// ```
+pub fn read_header<R: Read>(reader: &mut R) -> Result<ProxyHeader, error::ProxyProtocolError> {
+    // both versions are at least as long as the signature of version 2
+    let mut start = [0u8; 12];
+    reader.read_exact(&mut start)?;
+    if start == V2_SIGNATURE {
+        return read_v2(reader);
+    }
+    if start.starts_with(b"PROXY ") {
+        return read_v1(reader, &start);
+    }
+    return Err(error::ProxyProtocolError::InvalidSignature);
+}
+
+// reads the rest of a version 1 header, a single line like
+// `PROXY TCP4 203.0.113.7 192.0.2.1 56324 443\r\n`
+fn read_v1<R: Read>(
+    reader: &mut R,
+    start: &[u8],
+) -> Result<ProxyHeader, error::ProxyProtocolError> {
+    let malformed = |message: &str| error::ProxyProtocolError::Malformed(message.to_string());
+
+    // the line is read byte by byte, so nothing after it is consumed
+    let mut line = start.to_vec();
+    while !line.ends_with(b"\r\n") {
+        if line.len() >= V1_MAX_LENGTH {
+            return Err(malformed("header line too long"));
+        }
+        let mut byte = [0u8; 1];
+        reader.read_exact(&mut byte)?;
+        line.push(byte[0]);
+    }
+    let line = std::str::from_utf8(&line[..line.len() - 2])
+        .map_err(|_| malformed("header line isn't valid UTF-8"))?;
+
+    let parts: Vec<&str> = line.split(' ').collect();
+    match parts.get(1) {
+        Some(&"UNKNOWN") => {
+            return Ok(ProxyHeader {
+                source: None,
+                destination: None,
+            })
+        }
+        Some(&"TCP4") | Some(&"TCP6") => {}
+        _ => return Err(malformed("unknown protocol")),
+    }
+    if parts.len() != 6 {
+        return Err(malformed("wrong number of fields"));
+    }
+    let ip = |value: &str| -> Result<IpAddr, error::ProxyProtocolError> {
+        let ip: IpAddr = value.parse().map_err(|_| malformed("invalid address"))?;
+        match (parts[1], ip) {
+            ("TCP4", IpAddr::V4(_)) | ("TCP6", IpAddr::V6(_)) => return Ok(ip),
+            _ => return Err(malformed("address doesn't match the protocol")),
+        }
+    };
+    let port = |value: &str| -> Result<u16, error::ProxyProtocolError> {
+        // ports are written without leading zeros
+        if value.len() > 1 && value.starts_with('0') {
+            return Err(malformed("invalid port"));
+        }
+        return value.parse().map_err(|_| malformed("invalid port"));
+    };
+    return Ok(ProxyHeader {
+        source: Some(SocketAddr::new(ip(parts[2])?, port(parts[4])?)),
+        destination: Some(SocketAddr::new(ip(parts[3])?, port(parts[5])?)),
+    });
+}
+
+// reads the rest of a version 2 header, after it's signature
+fn read_v2<R: Read>(reader: &mut R) -> Result<ProxyHeader, error::ProxyProtocolError> {
+    let malformed = |message: &str| error::ProxyProtocolError::Malformed(message.to_string());
+
+    let mut fixed = [0u8; 4];
+    reader.read_exact(&mut fixed)?;
+    let (version_command, family) = (fixed[0], fixed[1]);
+    let mut addresses = vec![0u8; u16::from_be_bytes([fixed[2], fixed[3]]) as usize];
+    reader.read_exact(&mut addresses)?;
+
+    if version_command >> 4 != 2 {
+        return Err(malformed("unsupported version"));
+    }
+    let local = ProxyHeader {
+        source: None,
+        destination: None,
+    };
+    match version_command & 0x0F {
+        // a connection opened by the load balancer itself, like a health check
+        0x0 => return Ok(local),
+        0x1 => {}
+        _ => return Err(malformed("unknown command")),
+    }
+
+    let port = |bytes: &[u8]| u16::from_be_bytes([bytes[0], bytes[1]]);
+    match family {
+        // TCP over IPv4
+        0x11 => {
+            if addresses.len() < 12 {
+                return Err(malformed("address block too short"));
+            }
+            let source = Ipv4Addr::new(addresses[0], addresses[1], addresses[2], addresses[3]);
+            let destination = Ipv4Addr::new(addresses[4], addresses[5], addresses[6], addresses[7]);
+            return Ok(ProxyHeader {
+                source: Some(SocketAddr::new(source.into(), port(&addresses[8..10]))),
+                destination: Some(SocketAddr::new(
+                    destination.into(),
+                    port(&addresses[10..12]),
+                )),
+            });
+        }
+        // TCP over IPv6
+        0x21 => {
+            if addresses.len() < 36 {
+                return Err(malformed("address block too short"));
+            }
+            let ip = |bytes: &[u8]| {
+                let mut octets = [0u8; 16];
+                octets.copy_from_slice(bytes);
+                return Ipv6Addr::from(octets);
+            };
+            return Ok(ProxyHeader {
+                source: Some(SocketAddr::new(
+                    ip(&addresses[0..16]).into(),
+                    port(&addresses[32..34]),
+                )),
+                destination: Some(SocketAddr::new(
+                    ip(&addresses[16..32]).into(),
+                    port(&addresses[34..36]),
+                )),
+            });
+        }
+        // unspecified, UDP or unix socket addresses don't describe a TCP client
+        _ => return Ok(local),
+    }
+}
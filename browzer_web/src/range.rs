@@ -0,0 +1,209 @@
+//! This module provides byte range requests (RFC 9110), which let clients fetch only a part of a
+//! representation, like a media player seeking in a video or a download resuming after the
+//! connection dropped.
+//!
+//! Only a single range per request is supported, requests for several ranges at once are answered
+//! with the full representation, which RFC 9110 allows. `Context::send_file` handles ranges on
+//! it's own, the types here are for handlers serving partial content from other sources.
+
+// internal crate imports
+use crate::{conditional, error, request};
+
+/// A range of bytes of a representation, resolved against it's length.
+///
+/// # Fields
+///
+/// - `start` - The offset of the first byte of the range.
+/// - `end` - The offset of the last byte of the range, inclusive like in the `Content-Range`
+///   header.
+///
+/// # Examples
+///
+/// ```rust
+/// use browzer_web::range::ByteRange;
+///
+/// // the last 500 bytes of a 10000 byte file
+/// let range = ByteRange::parse("bytes=-500", 10000).unwrap();
+///
+/// assert_eq!(range, ByteRange { start: 9500, end: 9999 });
+/// assert_eq!(range.length(), 500);
+/// assert_eq!(range.content_range(10000), "bytes 9500-9999/10000");
+/// ```
+// ----- ByteRange struct
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ByteRange {
+    pub start: u64,
+    pub end: u64,
+}
+
+impl ByteRange {
+    /// Parses the value of a `Range` header, for a representation of the given length.
+    ///
+    /// The forms `bytes=start-end`, `bytes=start-`(up to the end) and `bytes=-suffix`(the last
+    /// `suffix` bytes) are supported, an `end` past the end of the representation is cut off.
+    ///
+    /// # Arguments
+    ///
+    /// - `value` - The value of the `Range` header.
+    /// - `length` - The length of the full representation, in bytes.
+    ///
+    /// # Errors
+    ///
+    /// - `RangeError::Malformed` - The value isn't a valid range, it should be ignored.
+    /// - `RangeError::Unsupported` - The value uses another unit than `bytes` or requests several
+    ///   ranges, it should be ignored.
+    /// - `RangeError::Unsatisfiable` - The range starts past the end of the representation(or is
+    ///   an empty suffix), it should be answered with `416 Range Not Satisfiable`.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use browzer_web::{error::RangeError, range::ByteRange};
+    ///
+    /// assert_eq!(ByteRange::parse("bytes=0-99", 1000), Ok(ByteRange { start: 0, end: 99 }));
+    /// assert_eq!(ByteRange::parse("bytes=900-", 1000), Ok(ByteRange { start: 900, end: 999 }));
+    /// assert_eq!(ByteRange::parse("bytes=900-5000", 1000), Ok(ByteRange { start: 900, end: 999 }));
+    /// assert_eq!(ByteRange::parse("bytes=1000-", 1000), Err(RangeError::Unsatisfiable(1000)));
+    /// assert!(matches!(ByteRange::parse("bytes=5-1", 1000), Err(RangeError::Malformed(_))));
+    /// assert!(matches!(ByteRange::parse("bytes=0-1,5-9", 1000), Err(RangeError::Unsupported(_))));
+    /// ```
+    pub fn parse(value: &str, length: u64) -> Result<ByteRange, error::RangeError> {
+        let malformed = || error::RangeError::Malformed(value.to_string());
+        let (unit, set) = match value.trim().split_once('=') {
+            Some(parts) => parts,
+            None => return Err(malformed()),
+        };
+        if !unit.trim().eq_ignore_ascii_case("bytes") {
+            return Err(error::RangeError::Unsupported(value.to_string()));
+        }
+        let specs: Vec<&str> = set
+            .split(',')
+            .map(|spec| spec.trim())
+            .filter(|spec| !spec.is_empty())
+            .collect();
+        let spec = match specs.as_slice() {
+            [spec] => *spec,
+            [] => return Err(malformed()),
+            _ => return Err(error::RangeError::Unsupported(value.to_string())),
+        };
+        let (first, last) = match spec.split_once('-') {
+            Some(parts) => parts,
+            None => return Err(malformed()),
+        };
+
+        if first.is_empty() {
+            // a suffix range, the last `suffix` bytes
+            let suffix = match parse_position(last) {
+                Some(suffix) => suffix,
+                None => return Err(malformed()),
+            };
+            if suffix == 0 || length == 0 {
+                return Err(error::RangeError::Unsatisfiable(length));
+            }
+            return Ok(ByteRange {
+                start: length.saturating_sub(suffix),
+                end: length - 1,
+            });
+        }
+
+        let start = match parse_position(first) {
+            Some(start) => start,
+            None => return Err(malformed()),
+        };
+        let end = match last.is_empty() {
+            true => u64::MAX,
+            false => match parse_position(last) {
+                Some(end) if end >= start => end,
+                _ => return Err(malformed()),
+            },
+        };
+        if start >= length {
+            return Err(error::RangeError::Unsatisfiable(length));
+        }
+        return Ok(ByteRange {
+            start,
+            end: end.min(length - 1),
+        });
+    }
+
+    /// Returns the number of bytes in the range.
+    pub fn length(&self) -> u64 {
+        return self.end - self.start + 1;
+    }
+
+    /// Returns the value of the `Content-Range` header of a partial response with this range.
+    ///
+    /// # Arguments
+    ///
+    /// - `length` - The length of the full representation, in bytes.
+    pub fn content_range(&self, length: u64) -> String {
+        return format!("bytes {}-{}/{}", self.start, self.end, length);
+    }
+}
+
+/// Returns the value of the `Content-Range` header of a `416 Range Not Satisfiable` response, like
+/// `bytes */1000`.
+///
+/// # Arguments
+///
+/// - `length` - The length of the full representation, in bytes.
+pub fn unsatisfied_content_range(length: u64) -> String {
+    return format!("bytes */{}", length);
+}
+
+/// Evaluates the `If-Range` header of a request, which makes a `Range` header conditional on the
+/// representation not having changed since the client got the first part of it.
+///
+/// An entity tag in the header has to strongly match the current `ETag`, a date has to be exactly
+/// the current `Last-Modified` value.
+///
+/// # Arguments
+///
+/// - `request` - The incoming `Request`.
+/// - `etag` - The current `EntityTag` of the representation, if it has one.
+/// - `last_modified` - The current `Last-Modified` value of the representation, if it has one.
+///
+/// # Returns
+///
+/// - `bool` - `true` if the `Range` header should be honoured(including when there is no
+///   `If-Range` header), `false` if the full representation should be sent instead.
+///
+/// # Examples
+///
+/// ```rust
+/// use browzer_web::{conditional::EntityTag, range::if_range_matches, request::Request};
+///
+/// let mut request = Request::default();
+/// assert!(if_range_matches(&request, None, None));
+///
+/// request.headers.insert("If-Range".to_string(), "\"v1\"".to_string());
+/// assert!(if_range_matches(&request, Some(&EntityTag::strong("v1")), None));
+/// assert!(!if_range_matches(&request, Some(&EntityTag::strong("v2")), None));
+/// ```
+pub fn if_range_matches(
+    request: &request::Request,
+    etag: Option<&conditional::EntityTag>,
+    last_modified: Option<&str>,
+) -> bool {
+    let if_range = match request.header("If-Range") {
+        Some(if_range) => if_range.trim(),
+        None => return true,
+    };
+    match conditional::EntityTag::parse(if_range) {
+        Some(tag) => return etag.map(|etag| etag.strong_eq(&tag)).unwrap_or(false),
+        None => return last_modified == Some(if_range),
+    }
+}
+
+// parses a byte position, which can't be signed or empty, positions too large for a `u64` are
+// saturated since they are past the end of any representation anyway
+fn parse_position(input: &str) -> Option<u64> {
+    if input.is_empty() || !input.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    return Some(input.bytes().fold(0u64, |position, b| {
+        position
+            .saturating_mul(10)
+            .saturating_add((b - b'0') as u64)
+    }));
+}
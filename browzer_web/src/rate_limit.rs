@@ -0,0 +1,309 @@
+//! This module limits how many requests a single client can make, using a token bucket per client.
+//!
+//! A `RateLimit` is enabled with `WebServer::rate_limit`, after which every request takes a token
+//! from the bucket of it's client before any middleware runs. Requests finding the bucket empty
+//! are answered with `429 Too Many Requests` and a `Retry-After` header telling the client when the
+//! next token will be available. Clients are told apart by their IP address(see
+//! `Context::client_ip`) or by a custom key, like an API key header.
+//!
+//! The buckets are kept in a `RateLimitStore` shared by all worker threads, an in-memory one by
+//! default. `cluster::Cluster::rate_limit_store` shares them with other instances of the server.
+
+// internal crate imports
+use crate::{forwarded, request};
+
+// standard library imports
+use std::{
+    collections::HashMap,
+    fmt,
+    sync::{Arc, Mutex, MutexGuard},
+    time::{Duration, Instant},
+};
+
+/// A boxed closure extracting the key a request is rate limited by, `None` exempts the request.
+pub type KeyExtractor = Box<dyn Fn(&request::Request) -> Option<String> + 'static + Send + Sync>;
+
+/// How many requests a client may make in a window of time.
+///
+/// A client may make `requests` requests in a burst, after which it gets another request every
+/// `window / requests`, so the bucket of a client is full again after it stayed idle for `window`.
+/// A quota of 0 requests limits every request, which is told to retry after `window`.
+///
+/// # Fields
+///
+/// - `requests` - The number of requests allowed per window, which is also the size of a burst.
+/// - `window` - The length of the window.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Quota {
+    pub requests: u32,
+    pub window: Duration,
+}
+
+impl Quota {
+    /// Returns the time it takes to refill a single token of a bucket.
+    pub fn refill_interval(&self) -> Duration {
+        return self.window / self.requests.max(1);
+    }
+}
+
+/// The decision about a single request, made by a `RateLimitStore`.
+///
+/// # Variants
+///
+/// - `Allowed` - The request may be served, `remaining` more requests are allowed right now.
+/// - `Limited` - The request exceeds the quota, the next one is allowed after `retry_after`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RateLimitDecision {
+    Allowed { remaining: u32 },
+    Limited { retry_after: Duration },
+}
+
+/// A storage backend for the token buckets of a `RateLimit`.
+///
+/// Implement this trait to share the buckets between multiple server instances, like in a
+/// database all of them use.
+pub trait RateLimitStore: Send + Sync {
+    /// Takes a token from the bucket of `key`, which starts out full, after refilling the tokens
+    /// earned since it was last used.
+    ///
+    /// This must be atomic, as concurrent requests of the same client could both take the last
+    /// token otherwise.
+    fn acquire(&self, key: &str, quota: &Quota) -> RateLimitDecision;
+}
+
+// the token bucket of a single client
+#[derive(Debug, Clone, Copy)]
+struct Bucket {
+    tokens: f64,
+    updated_at: Instant,
+}
+
+/// An in-memory `RateLimitStore` implementation.
+///
+/// A bucket expires once it is full again, since a full bucket is what a new client gets anyway.
+/// Expired buckets are purged lazily when a bucket for a new client is created, at most once per
+/// window of the quota, so a flood of new clients doesn't sweep the whole store on every request.
+///
+/// # Examples
+///
+/// ```rust
+/// use browzer_web::rate_limit::{MemoryRateLimitStore, Quota, RateLimitDecision, RateLimitStore};
+/// use std::time::Duration;
+///
+/// let store = MemoryRateLimitStore::new();
+/// let quota = Quota { requests: 2, window: Duration::from_secs(60) };
+///
+/// assert_eq!(store.acquire("10.0.0.1", &quota), RateLimitDecision::Allowed { remaining: 1 });
+/// assert_eq!(store.acquire("10.0.0.1", &quota), RateLimitDecision::Allowed { remaining: 0 });
+/// assert!(matches!(store.acquire("10.0.0.1", &quota), RateLimitDecision::Limited { .. }));
+/// // every client has a bucket of it's own
+/// assert_eq!(store.acquire("10.0.0.2", &quota), RateLimitDecision::Allowed { remaining: 1 });
+/// ```
+// ----- MemoryRateLimitStore struct
+#[derive(Debug, Default)]
+pub struct MemoryRateLimitStore {
+    buckets: Mutex<Buckets>,
+}
+
+// the buckets of a `MemoryRateLimitStore`, and when the expired ones were purged last
+#[derive(Debug, Default)]
+struct Buckets {
+    map: HashMap<String, Bucket>,
+    purged_at: Option<Instant>,
+}
+
+impl MemoryRateLimitStore {
+    /// Creates a new, empty `MemoryRateLimitStore`.
+    pub fn new() -> MemoryRateLimitStore {
+        return MemoryRateLimitStore::default();
+    }
+
+    /// Returns the number of buckets in the store, expired ones which weren't purged yet included.
+    pub fn len(&self) -> usize {
+        return self.lock().map.len();
+    }
+
+    /// Returns whether the store holds no buckets.
+    pub fn is_empty(&self) -> bool {
+        return self.len() == 0;
+    }
+
+    fn lock(&self) -> MutexGuard<'_, Buckets> {
+        match self.buckets.lock() {
+            Ok(buckets) => return buckets,
+            Err(poisoned) => return poisoned.into_inner(),
+        }
+    }
+}
+
+impl RateLimitStore for MemoryRateLimitStore {
+    fn acquire(&self, key: &str, quota: &Quota) -> RateLimitDecision {
+        let mut buckets = self.lock();
+        let now = Instant::now();
+        let bucket = bucket(&mut buckets, key, quota, now);
+        let tokens = refill(bucket, quota, now);
+        bucket.updated_at = now;
+        if tokens >= 1.0 {
+            bucket.tokens = tokens - 1.0;
+            return RateLimitDecision::Allowed {
+                remaining: bucket.tokens.floor() as u32,
+            };
+        }
+        bucket.tokens = tokens;
+        // a quota of 0 requests never refills a token, the client may try again after the window
+        return RateLimitDecision::Limited {
+            retry_after: Duration::try_from_secs_f64((1.0 - tokens) / rate(quota))
+                .unwrap_or(quota.window),
+        };
+    }
+}
+
+impl MemoryRateLimitStore {
+    // takes tokens from the bucket of `key` without deciding about a request, like for the
+    // requests another server instance served. The bucket doesn't go below empty
+    pub(crate) fn take(&self, key: &str, quota: &Quota, tokens: u32) {
+        let mut buckets = self.lock();
+        let now = Instant::now();
+        let bucket = bucket(&mut buckets, key, quota, now);
+        bucket.tokens = (refill(bucket, quota, now) - tokens as f64).max(0.0);
+        bucket.updated_at = now;
+    }
+}
+
+// returns the bucket of `key`, creating a full one if there is none. The expired buckets are purged
+// along with it, unless they already were within the last window
+fn bucket<'a>(buckets: &'a mut Buckets, key: &str, quota: &Quota, now: Instant) -> &'a mut Bucket {
+    let purge_due = buckets
+        .purged_at
+        .is_none_or(|purged_at| now.saturating_duration_since(purged_at) >= quota.window);
+    if purge_due && !buckets.map.contains_key(key) {
+        buckets
+            .map
+            .retain(|_, bucket| refill(bucket, quota, now) < quota.requests as f64);
+        buckets.purged_at = Some(now);
+    }
+    return buckets.map.entry(key.to_string()).or_insert(Bucket {
+        tokens: quota.requests as f64,
+        updated_at: now,
+    });
+}
+
+// returns the tokens in a bucket after refilling the ones earned since it was last used
+fn refill(bucket: &Bucket, quota: &Quota, now: Instant) -> f64 {
+    let earned = now
+        .saturating_duration_since(bucket.updated_at)
+        .as_secs_f64()
+        * rate(quota);
+    return (bucket.tokens + earned).min(quota.requests as f64);
+}
+
+// returns the tokens earned per second
+fn rate(quota: &Quota) -> f64 {
+    return quota.requests as f64 / quota.window.as_secs_f64().max(f64::EPSILON);
+}
+
+/// Limits the rate of requests per client, see `WebServer::rate_limit`.
+///
+/// By default clients are told apart by their IP address, taking the forwarding headers of
+/// trusted proxies into account(see `WebServer::trusted_proxies`). Requests whose client address
+/// isn't known, like the ones not read from a connection, aren't limited.
+///
+/// # Examples
+///
+/// ```rust
+/// use browzer_web::rate_limit::{RateLimit, RateLimitDecision};
+/// use std::time::Duration;
+///
+/// // 100 requests a minute per API key, requests without a key aren't limited
+/// let rate_limit = RateLimit::new(100, Duration::from_secs(60))
+///     .key_by(|request| request.header("X-Api-Key").cloned());
+///
+/// assert_eq!(rate_limit.check("key"), RateLimitDecision::Allowed { remaining: 99 });
+/// ```
+// ----- RateLimit struct
+#[derive(Clone)]
+pub struct RateLimit {
+    quota: Quota,
+    store: Arc<dyn RateLimitStore>,
+    key: Option<Arc<KeyExtractor>>,
+}
+
+impl fmt::Debug for RateLimit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("RateLimit")
+            .field("quota", &self.quota)
+            .field("store", &"Arc<dyn RateLimitStore>")
+            .field(
+                "key",
+                &self.key.as_ref().map(|_| {
+                    "Box<dyn Fn(&request::Request) -> Option<String> + 'static + Send + Sync>"
+                }),
+            )
+            .finish()
+    }
+}
+
+impl RateLimit {
+    /// Creates a new `RateLimit` keeping it's buckets in a `MemoryRateLimitStore`.
+    ///
+    /// # Arguments
+    ///
+    /// - `requests` - The number of requests a client may make per window, see `Quota`.
+    /// - `window` - The length of the window.
+    pub fn new(requests: u32, window: Duration) -> RateLimit {
+        return RateLimit {
+            quota: Quota { requests, window },
+            store: Arc::new(MemoryRateLimitStore::new()),
+            key: None,
+        };
+    }
+
+    /// Keeps the buckets in the given store instead of the memory of the current process.
+    pub fn store<S>(mut self, store: S) -> RateLimit
+    where
+        S: RateLimitStore + 'static,
+    {
+        self.store = Arc::new(store);
+        return self;
+    }
+
+    /// Tells clients apart by the key the closure extracts from their requests instead of their
+    /// IP address, requests the closure returns `None` for aren't limited.
+    pub fn key_by<F>(mut self, key: F) -> RateLimit
+    where
+        F: Fn(&request::Request) -> Option<String> + 'static + Send + Sync,
+    {
+        self.key = Some(Arc::new(Box::new(key)));
+        return self;
+    }
+
+    /// Returns the quota of the rate limit.
+    pub fn quota(&self) -> Quota {
+        return self.quota;
+    }
+
+    /// Takes a token from the bucket of the given key.
+    pub fn check(&self, key: &str) -> RateLimitDecision {
+        return self.store.acquire(key, &self.quota);
+    }
+
+    // returns how long the client of a request has to wait before it may make the next request,
+    // `None` if the request may be served
+    pub(crate) fn limit(
+        &self,
+        request: &request::Request,
+        trusted_proxies: Option<&forwarded::TrustedProxies>,
+    ) -> Option<Duration> {
+        let key = match self.key {
+            Some(ref key) => (key)(request),
+            None => match trusted_proxies {
+                Some(proxies) => proxies.client_ip(request).map(|ip| ip.to_string()),
+                None => request.remote_addr.map(|addr| addr.ip().to_string()),
+            },
+        };
+        match self.check(&key?) {
+            RateLimitDecision::Allowed { .. } => return None,
+            RateLimitDecision::Limited { retry_after } => return Some(retry_after),
+        }
+    }
+}
@@ -0,0 +1,578 @@
+//! This module records a sample of the requests a server answers and replays them later, see
+//! `WebServer::record_requests`.
+//!
+//! A `Recorder` writes every sampled request as a line of JSON(NDJSON) to a file or any other
+//! writer, together with the status code it was answered with. Credentials are redacted before a
+//! request is written: the `Authorization`, `Cookie` and `Proxy-Authorization` headers by default,
+//! and any other header or query parameter passed to `Recorder::redact_header` or
+//! `Recorder::redact_query`.
+//!
+//! A `Replay` reads such a recording and feeds the requests back through
+//! `WebRouter::handle_request`, without a network in between. Comparing the status codes with the
+//! recorded ones catches regressions of new code against real traffic, and replaying the
+//! recording several times on several threads simulates load. Redacted values are replayed as
+//! `REDACTED`, so routes which need the redacted credentials answer differently than they did when
+//! they were recorded.
+
+// internal crate imports
+use crate::{error, request, router, utils};
+
+// external crate imports
+use chrono::{DateTime, SecondsFormat, Utc};
+use serde::{Deserialize, Serialize};
+
+// standard library imports
+use std::{
+    collections::BTreeMap,
+    fmt, fs,
+    io::{self, BufRead, BufReader, Write},
+    path::Path,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Mutex,
+    },
+    thread,
+    time::{Duration, Instant},
+};
+
+/// The value redacted headers and query parameters are recorded with.
+pub const REDACTED: &str = "[REDACTED]";
+
+/// The headers a `Recorder` redacts by default.
+pub const DEFAULT_REDACTED_HEADERS: &[&str] = &["Authorization", "Cookie", "Proxy-Authorization"];
+
+/// A request as it's written to a recording, one per line.
+///
+/// # Fields
+///
+/// - `timestamp` - When the request was received, in RFC 3339 format.
+/// - `method` - The method of the request.
+/// - `path` - The path of the request, with it's query string.
+/// - `version` - The HTTP version of the request.
+/// - `headers` - The headers of the request, sorted by name.
+/// - `body` - The body of the request, as text or(if `body_base64` is set) as base64. Empty if
+///   the request had no body or the recorder doesn't record bodies.
+/// - `body_base64` - Whether the body wasn't valid UTF-8 and is encoded as base64.
+/// - `tls` - Whether the request arrived over HTTPS.
+/// - `remote_addr` - The address of the client, if the request was read from a connection.
+/// - `status` - The status code the request was answered with, if it was recorded by a server.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RecordedRequest {
+    pub timestamp: String,
+    pub method: String,
+    pub path: String,
+    pub version: String,
+    pub headers: BTreeMap<String, String>,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub body: String,
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub body_base64: bool,
+    #[serde(default)]
+    pub tls: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub remote_addr: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub status: Option<u16>,
+}
+
+impl RecordedRequest {
+    /// Records a request as it is, without redacting anything.
+    pub fn from_request(request: &request::Request) -> RecordedRequest {
+        let body = request.body_bytes();
+        let (body, body_base64) = match std::str::from_utf8(body) {
+            Ok(text) => (text.to_string(), false),
+            Err(_) => (utils::base64_encode(body), true),
+        };
+        return RecordedRequest {
+            timestamp: DateTime::<Utc>::from(request.received_at.system_time)
+                .to_rfc3339_opts(SecondsFormat::Millis, true),
+            method: request.method.to_string(),
+            path: request.path.clone(),
+            version: request.version.clone(),
+            headers: request
+                .headers
+                .iter()
+                .map(|(name, value)| (name.clone(), value.clone()))
+                .collect(),
+            body,
+            body_base64,
+            tls: request.tls,
+            remote_addr: request.remote_addr.map(|address| address.to_string()),
+            status: None,
+        };
+    }
+
+    /// Turns the record back into a `Request`, parsed like a request read from a connection.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `RequestParseError` if the recorded request isn't a valid HTTP request(like one
+    /// with an unsupported method), or an `InternalServerError` if it's base64 body is invalid.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use browzer_web::{replay::RecordedRequest, request::Request};
+    ///
+    /// let mut input = "POST /users?page=2 HTTP/1.1\r\nContent-Length: 4\r\n\r\naxew".as_bytes();
+    /// let request = Request::read_from(&mut input, false).unwrap().unwrap();
+    ///
+    /// let record = RecordedRequest::from_request(&request);
+    /// assert_eq!(record.body, "axew");
+    /// let replayed = record.to_request().unwrap();
+    /// assert_eq!(replayed.path, "/users?page=2");
+    /// assert_eq!(replayed.body, Some("axew".to_string()));
+    /// ```
+    pub fn to_request(&self) -> Result<request::Request, error::WebServerError> {
+        let body = match self.body_base64 {
+            true => utils::base64_decode(&self.body).ok_or_else(|| {
+                error::WebServerError::InternalServerError(
+                    "Invalid base64 body in the recorded request".to_string(),
+                )
+            })?,
+            false => self.body.clone().into_bytes(),
+        };
+        let mut raw = format!("{} {} {}\r\n", self.method, self.path, self.version);
+        for (name, value) in self.headers.iter() {
+            // the body is framed by it's recorded length
+            if name.eq_ignore_ascii_case("Content-Length")
+                || name.eq_ignore_ascii_case("Transfer-Encoding")
+            {
+                continue;
+            }
+            raw.push_str(&format!("{}: {}\r\n", name, value));
+        }
+        if !body.is_empty() {
+            raw.push_str(&format!("Content-Length: {}\r\n", body.len()));
+        }
+        raw.push_str("\r\n");
+        let mut raw = raw.into_bytes();
+        raw.extend_from_slice(&body);
+
+        let mut request = match request::Request::read_from(&mut raw.as_slice(), false)? {
+            Some(request) => request,
+            None => {
+                return Err(error::WebServerError::InternalServerError(
+                    "Empty recorded request".to_string(),
+                ))
+            }
+        };
+        request.tls = self.tls;
+        request.remote_addr = self
+            .remote_addr
+            .as_ref()
+            .and_then(|address| address.parse().ok());
+        return Ok(request);
+    }
+}
+
+/// Records a sample of the requests a server answers as NDJSON, see `WebServer::record_requests`.
+///
+/// Requests are sampled evenly rather than randomly, like with a `sampling::Sampler`. Requests for
+/// the paths passed to `Recorder::skip_path` aren't recorded, and a failure to write a record is
+/// ignored, so recording never breaks serving requests.
+///
+/// # Examples
+///
+/// ```rust
+/// use browzer_web::{replay::Recorder, request::Request};
+///
+/// let recorder = Recorder::new(Vec::new())
+///     .sample_rate(0.1)
+///     .redact_header("X-Api-Key")
+///     .redact_query("token")
+///     .skip_path("/health");
+///
+/// let mut request = Request::default();
+/// request.path = "/search?q=rust&token=secret".to_string();
+/// request.headers.insert("x-api-key".to_string(), "secret".to_string());
+/// request.headers.insert("Authorization".to_string(), "Bearer secret".to_string());
+///
+/// let record = recorder.redact(&request);
+/// assert_eq!(record.path, "/search?q=rust&token=[REDACTED]");
+/// assert_eq!(record.headers["x-api-key"], "[REDACTED]");
+/// assert_eq!(record.headers["Authorization"], "[REDACTED]");
+/// ```
+// ----- Recorder struct
+pub struct Recorder {
+    output: Mutex<Box<dyn Write + Send>>,
+    rate: f64,
+    requests: AtomicU64,
+    redacted_headers: Vec<String>,
+    redacted_query: Vec<String>,
+    skip_paths: Vec<String>,
+    record_bodies: bool,
+}
+
+impl fmt::Debug for Recorder {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Recorder")
+            .field("output", &"Box<dyn Write + Send>")
+            .field("rate", &self.rate)
+            .field("requests", &self.requests)
+            .field("redacted_headers", &self.redacted_headers)
+            .field("redacted_query", &self.redacted_query)
+            .field("skip_paths", &self.skip_paths)
+            .field("record_bodies", &self.record_bodies)
+            .finish()
+    }
+}
+
+impl Recorder {
+    /// Creates a new `Recorder` writing every request(and it's body) to a writer, redacting the
+    /// `DEFAULT_REDACTED_HEADERS`.
+    pub fn new<W>(writer: W) -> Recorder
+    where
+        W: Write + Send + 'static,
+    {
+        return Recorder {
+            output: Mutex::new(Box::new(writer)),
+            rate: 1.0,
+            requests: AtomicU64::new(0),
+            redacted_headers: DEFAULT_REDACTED_HEADERS
+                .iter()
+                .map(|header| header.to_string())
+                .collect(),
+            redacted_query: Vec::new(),
+            skip_paths: Vec::new(),
+            record_bodies: true,
+        };
+    }
+
+    /// Creates a new `Recorder` appending to a file, which is created if it doesn't exist.
+    ///
+    /// # Errors
+    ///
+    /// Returns an I/O error if the file can't be opened for writing.
+    pub fn file<P: AsRef<Path>>(path: P) -> io::Result<Recorder> {
+        let file = fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)?;
+        return Ok(Recorder::new(io::LineWriter::new(file)));
+    }
+
+    /// Sets the fraction of requests to record, from `0.0`(none) to `1.0`(all, the default).
+    /// Values outside of that range are clamped to it.
+    pub fn sample_rate(mut self, rate: f64) -> Recorder {
+        self.rate = match rate.is_nan() {
+            true => 0.0,
+            false => rate.clamp(0.0, 1.0),
+        };
+        return self;
+    }
+
+    /// Records a header with the value `REDACTED`, matching it's name case-insensitively.
+    pub fn redact_header(mut self, name: &str) -> Recorder {
+        self.redacted_headers.push(name.to_string());
+        return self;
+    }
+
+    /// Records a query parameter with the value `REDACTED`, matching it's decoded name.
+    pub fn redact_query(mut self, name: &str) -> Recorder {
+        self.redacted_query.push(name.to_string());
+        return self;
+    }
+
+    /// Stops requests for a path from being recorded.
+    ///
+    /// # Arguments
+    ///
+    /// - `path` - The path, compared without the query string. A path ending in `*` skips every
+    ///   path starting with the part before it.
+    pub fn skip_path(mut self, path: &str) -> Recorder {
+        self.skip_paths.push(path.to_string());
+        return self;
+    }
+
+    /// Sets whether the bodies of requests are recorded, enabled by default. Without them requests
+    /// are replayed without a body.
+    pub fn record_bodies(mut self, record: bool) -> Recorder {
+        self.record_bodies = record;
+        return self;
+    }
+
+    /// Returns whether requests for a path aren't recorded.
+    pub fn skips(&self, path: &str) -> bool {
+        return utils::matches_path_pattern(&self.skip_paths, path);
+    }
+
+    /// Counts a request and returns whether it is recorded, which a request for a skipped path
+    /// never is.
+    pub fn sample(&self, request: &request::Request) -> bool {
+        if self.skips(&request.path) {
+            return false;
+        }
+        let count = self.requests.fetch_add(1, Ordering::Relaxed) as f64;
+        // a request is sampled whenever the expected number of samples passes a whole number
+        return ((count + 1.0) * self.rate).floor() > (count * self.rate).floor();
+    }
+
+    /// Records a request with the redacted headers and query parameters replaced, and without
+    /// it's body if bodies aren't recorded.
+    pub fn redact(&self, request: &request::Request) -> RecordedRequest {
+        let mut record = RecordedRequest::from_request(request);
+        for (name, value) in record.headers.iter_mut() {
+            if self
+                .redacted_headers
+                .iter()
+                .any(|redacted| name.eq_ignore_ascii_case(redacted))
+            {
+                *value = REDACTED.to_string();
+            }
+        }
+        if let Some((path, query)) = record.path.split_once('?') {
+            let query = query
+                .split('&')
+                .map(|pair| {
+                    let key = pair.split('=').next().unwrap_or_default();
+                    let decoded = utils::query_decode(key).unwrap_or_default();
+                    match self.redacted_query.contains(&decoded) {
+                        true => format!("{}={}", key, REDACTED),
+                        false => pair.to_string(),
+                    }
+                })
+                .collect::<Vec<_>>()
+                .join("&");
+            record.path = format!("{}?{}", path, query);
+        }
+        if !self.record_bodies {
+            record.body = String::new();
+            record.body_base64 = false;
+        }
+        return record;
+    }
+
+    /// Writes a record as a line of JSON.
+    pub fn write(&self, record: &RecordedRequest) {
+        let line = match serde_json::to_string(record) {
+            Ok(line) => line,
+            Err(_) => return,
+        };
+        let mut output = match self.output.lock() {
+            Ok(output) => output,
+            Err(poisoned) => poisoned.into_inner(),
+        };
+        let _ = writeln!(output, "{}", line);
+        let _ = output.flush();
+    }
+
+    // samples and redacts a request before the router consumes it
+    pub(crate) fn capture(&self, request: &request::Request) -> Option<RecordedRequest> {
+        match self.sample(request) {
+            true => return Some(self.redact(request)),
+            false => return None,
+        }
+    }
+}
+
+/// The outcome of a replayed request.
+///
+/// # Fields
+///
+/// - `index` - The position of the request in the recording, starting at `0`.
+/// - `recorded_status` - The status code the request was answered with when it was recorded.
+/// - `status` - The status code the request was answered with now, `None` if it failed.
+/// - `error` - Why the request failed, like a record which isn't a valid request.
+/// - `latency` - How long the router took to answer the request.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ReplayResult {
+    pub index: usize,
+    pub recorded_status: Option<u16>,
+    pub status: Option<u16>,
+    pub error: Option<String>,
+    pub latency: Duration,
+}
+
+impl ReplayResult {
+    /// Returns whether the request was answered differently than when it was recorded, or failed.
+    pub fn is_regression(&self) -> bool {
+        return self.error.is_some()
+            || (self.recorded_status.is_some() && self.status != self.recorded_status);
+    }
+}
+
+/// The outcome of a `Replay`.
+///
+/// # Fields
+///
+/// - `results` - The outcome of every replayed request, in the order they were replayed in on
+///   each thread.
+/// - `elapsed` - How long the whole replay took.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ReplayReport {
+    pub results: Vec<ReplayResult>,
+    pub elapsed: Duration,
+}
+
+impl ReplayReport {
+    /// Returns the requests which were answered differently than when they were recorded.
+    pub fn regressions(&self) -> Vec<&ReplayResult> {
+        return self
+            .results
+            .iter()
+            .filter(|result| result.is_regression())
+            .collect();
+    }
+
+    /// Returns how many requests were answered per second.
+    pub fn requests_per_second(&self) -> f64 {
+        return match self.elapsed.is_zero() {
+            true => 0.0,
+            false => self.results.len() as f64 / self.elapsed.as_secs_f64(),
+        };
+    }
+
+    /// Returns the latency below which the given fraction(like `0.99`) of the requests were
+    /// answered, zero without any requests.
+    pub fn latency_percentile(&self, fraction: f64) -> Duration {
+        let mut latencies: Vec<Duration> =
+            self.results.iter().map(|result| result.latency).collect();
+        if latencies.is_empty() {
+            return Duration::ZERO;
+        }
+        latencies.sort();
+        let index = (fraction.clamp(0.0, 1.0) * (latencies.len() - 1) as f64).round() as usize;
+        return latencies[index];
+    }
+}
+
+/// Replays recorded requests through a `WebRouter`, for regression tests and load simulations.
+///
+/// # Examples
+///
+/// ```rust
+/// use browzer_web::{
+///     replay::Replay,
+///     router::WebRouter,
+///     utils::{HttpMethod, HttpStatusCode},
+/// };
+///
+/// let recording = r#"{"timestamp":"2024-05-01T12:00:00.000Z","method":"GET","path":"/","version":"HTTP/1.1","headers":{},"status":200}
+/// {"timestamp":"2024-05-01T12:00:01.000Z","method":"GET","path":"/gone","version":"HTTP/1.1","headers":{},"status":200}"#;
+///
+/// let mut router = WebRouter::new();
+/// router
+///     .add("/".to_string(), HttpMethod::GET, |mut c| {
+///         c.send_string(HttpStatusCode::OK, "home")
+///     })
+///     .unwrap();
+///
+/// let report = Replay::from_reader(recording.as_bytes()).unwrap().run(&router);
+/// assert_eq!(report.results.len(), 2);
+/// let regressions = report.regressions();
+/// assert_eq!(regressions.len(), 1);
+/// assert_eq!(regressions[0].status, Some(404));
+/// ```
+// ----- Replay struct
+#[derive(Debug, Clone)]
+pub struct Replay {
+    requests: Vec<RecordedRequest>,
+    repeat: usize,
+    threads: usize,
+}
+
+impl Replay {
+    /// Creates a new `Replay` of the given requests, replaying them once on the calling thread.
+    pub fn new(requests: Vec<RecordedRequest>) -> Replay {
+        return Replay {
+            requests,
+            repeat: 1,
+            threads: 1,
+        };
+    }
+
+    /// Reads a recording from a reader, one record per line. Empty lines are skipped.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `InvalidRecord` error for the first line which isn't a valid record, or an I/O
+    /// error if reading failed.
+    pub fn from_reader<R: BufRead>(reader: R) -> Result<Replay, error::ReplayError> {
+        let mut requests = vec![];
+        for (index, line) in reader.lines().enumerate() {
+            let line = line?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            let record = serde_json::from_str(&line)
+                .map_err(|e| error::ReplayError::InvalidRecord(index + 1, e.to_string()))?;
+            requests.push(record);
+        }
+        return Ok(Replay::new(requests));
+    }
+
+    /// Reads a recording from a file written by a `Recorder`, see `Replay::from_reader`.
+    pub fn file<P: AsRef<Path>>(path: P) -> Result<Replay, error::ReplayError> {
+        return Replay::from_reader(BufReader::new(fs::File::open(path)?));
+    }
+
+    /// Sets how many times the recording is replayed, once by default.
+    pub fn repeat(mut self, times: usize) -> Replay {
+        self.repeat = times;
+        return self;
+    }
+
+    /// Sets on how many threads the recording is replayed at the same time, each replaying it
+    /// `repeat` times. One by default.
+    pub fn threads(mut self, threads: usize) -> Replay {
+        self.threads = threads.max(1);
+        return self;
+    }
+
+    /// Returns the recorded requests.
+    pub fn requests(&self) -> &[RecordedRequest] {
+        return &self.requests;
+    }
+
+    /// Replays the requests through a router and reports how each of them was answered. The
+    /// bodies of the responses aren't read, so streamed responses are only started.
+    pub fn run(&self, router: &router::WebRouter) -> ReplayReport {
+        let started = Instant::now();
+        let results = thread::scope(|scope| {
+            let workers: Vec<_> = (0..self.threads)
+                .map(|_| scope.spawn(|| self.replay_all(router)))
+                .collect();
+            return workers
+                .into_iter()
+                .flat_map(|worker| worker.join().unwrap_or_default())
+                .collect();
+        });
+        return ReplayReport {
+            results,
+            elapsed: started.elapsed(),
+        };
+    }
+
+    // replays the recording `repeat` times on the current thread
+    fn replay_all(&self, router: &router::WebRouter) -> Vec<ReplayResult> {
+        let mut results = Vec::with_capacity(self.requests.len() * self.repeat);
+        for _ in 0..self.repeat {
+            for (index, record) in self.requests.iter().enumerate() {
+                results.push(replay_one(router, index, record));
+            }
+        }
+        return results;
+    }
+}
+
+// replays a single request through a router
+fn replay_one(router: &router::WebRouter, index: usize, record: &RecordedRequest) -> ReplayResult {
+    let started = Instant::now();
+    let outcome = record
+        .to_request()
+        .map_err(|e| e.to_string())
+        .and_then(|request| router.handle_request(request).map_err(|e| e.to_string()));
+    let latency = started.elapsed();
+    let (status, error) = match outcome {
+        Ok(response) => (Some(response.status_code.code().1), None),
+        Err(e) => (None, Some(e)),
+    };
+    return ReplayResult {
+        index,
+        recorded_status: record.status,
+        status,
+        error,
+        latency,
+    };
+}
@@ -1,10 +1,95 @@
 //! This module defines the `Request` struct and functionality related to handling HTTP requests.
 
 // internal crate imports
-use crate::{error, utils};
+use crate::{cancel, connection, error, limits, router, sampling, utils};
 
 // standard library imports
-use std::collections::HashMap;
+use std::{
+    collections::HashMap,
+    fmt,
+    io::{self, BufRead, Read},
+    net::SocketAddr,
+    sync::OnceLock,
+    time::{Duration, Instant, SystemTime},
+};
+
+// the capacity the buffer of a request body starts with, it grows as more of the body arrives
+const INITIAL_BODY_CAPACITY: usize = 64 * 1024;
+
+// the largest part of a streamed request body which is passed to the handler at once
+pub(crate) const BODY_CHUNK_SIZE: usize = 16 * 1024;
+
+// the number of parts of a streamed request body which may wait for the handler to read them, on
+// the tokio runtime
+#[cfg(feature = "tokio")]
+pub(crate) const BODY_CHUNKS: usize = 8;
+
+/// The point in time a request was received at.
+///
+/// It is captured once, when the first line of the request arrives, so that everything measuring
+/// or logging the request(like timeouts, timings or access logs) refers to the same point in time
+/// instead of each of them calling `now()` at a different point.
+///
+/// # Fields
+///
+/// - `instant` - The monotonic clock reading, for measuring durations.
+/// - `system_time` - The wall clock reading, for displaying or logging the time.
+///
+/// # Examples
+///
+/// ```rust
+/// # use browzer_web::request::ReceivedAt;
+/// let received_at = ReceivedAt::now();
+///
+/// assert!(received_at.elapsed() < std::time::Duration::from_secs(1));
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ReceivedAt {
+    pub instant: Instant,
+    pub system_time: SystemTime,
+}
+
+impl ReceivedAt {
+    /// Captures the current point in time.
+    pub fn now() -> ReceivedAt {
+        return ReceivedAt {
+            instant: Instant::now(),
+            system_time: SystemTime::now(),
+        };
+    }
+
+    /// Returns the time passed since the request was received, using the monotonic clock.
+    pub fn elapsed(&self) -> Duration {
+        return self.instant.elapsed();
+    }
+}
+
+// serializes the point in time as the wall clock reading, the monotonic one is meaningless outside
+// of the process
+#[cfg(feature = "serde")]
+impl serde::Serialize for ReceivedAt {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        return self.system_time.serialize(serializer);
+    }
+}
+
+// deserializes the point in time from the wall clock reading, deriving a monotonic clock reading
+// which reports the same elapsed time
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for ReceivedAt {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let system_time = SystemTime::deserialize(deserializer)?;
+        let now = ReceivedAt::now();
+        let elapsed = now
+            .system_time
+            .duration_since(system_time)
+            .unwrap_or_default();
+        return Ok(ReceivedAt {
+            instant: now.instant.checked_sub(elapsed).unwrap_or(now.instant),
+            system_time,
+        });
+    }
+}
 
 /// Represents an HTTP request.
 ///
@@ -17,17 +102,63 @@ use std::collections::HashMap;
 /// - `path` - The path of the request (e.g., "/index.html").
 /// - `version` - The HTTP version used in the request (e.g., "HTTP/1.1").
 /// - `headers` - A `HashMap` containing the request headers as key-value pairs.
-/// - `body` - An optional string containing the body of the request.
+/// - `body` - An optional string containing the body of the request, `None` if the body isn't
+///   valid UTF-8.
+/// - `raw_body` - The body of the request as received, only set if it isn't valid UTF-8(like a
+///   file upload). The body is kept in only one of the two fields, `Request::body_bytes` returns
+///   it's bytes either way.
 /// - `cookies` - A `HashMap` containing cookies from the request
+/// - `received_at` - The point in time the request was received at
+/// - `cancellation` - The `CancellationToken` of the request, cancelled once it's client
+///   disconnected. It isn't serialized with the `serde` feature, a deserialized request gets a
+///   fresh token
+/// - `tls` - Whether the request arrived over HTTPS, see `WebServer::accept_plaintext`
+/// - `remote_addr` - The address of the client, the peer address of the connection or the one
+///   from it's PROXY protocol header(see `WebServer::proxy_protocol`). `None` if the request
+///   wasn't read from a connection
+/// - `connection` - The `Connection` the request was read from, shared with the other requests of
+///   the connection. It isn't serialized with the `serde` feature, a deserialized request gets a
+///   connection of it's own
 // ----- Request struct
 #[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Request {
     pub method: utils::HttpMethod,
     pub path: String,
     pub version: String,
     pub headers: HashMap<String, String>,
     pub body: Option<String>,
+    pub raw_body: Option<Vec<u8>>,
     pub cookies: HashMap<String, utils::Cookie>,
+    pub received_at: ReceivedAt,
+    #[cfg_attr(
+        feature = "serde",
+        serde(skip, default = "cancel::CancellationToken::new")
+    )]
+    pub cancellation: cancel::CancellationToken,
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub tls: bool,
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub remote_addr: Option<SocketAddr>,
+    #[cfg_attr(feature = "serde", serde(skip))]
+    pub connection: connection::Connection,
+    #[cfg_attr(feature = "serde", serde(skip))]
+    pub(crate) handler_marks: Option<sampling::HandlerMarks>,
+    #[cfg_attr(feature = "serde", serde(skip))]
+    pub(crate) query: OnceLock<Option<Query>>,
+    // whether the path ended with a slash before the router removed it, see
+    // `WebRouter::handle_request`
+    #[cfg_attr(feature = "serde", serde(skip))]
+    pub(crate) trailing_slash: bool,
+    // the ID the request was given when it arrived, see `RequestIds::assign`
+    #[cfg_attr(feature = "serde", serde(skip))]
+    pub(crate) request_id: Option<String>,
+    // the route the request was resolved to when it arrived, see `WebRouter::resolve`
+    #[cfg_attr(feature = "serde", serde(skip))]
+    pub(crate) resolution: Option<router::RouteResolution>,
+    // the body of a request to a route streaming it, see `Request::take_body_stream`
+    #[cfg_attr(feature = "serde", serde(skip))]
+    pub(crate) body_stream: Option<BodyStream>,
 }
 // default implementation for Request struct
 impl Default for Request {
@@ -38,7 +169,19 @@ impl Default for Request {
             version: String::from("HTTP/1.1"),
             headers: HashMap::new(),
             body: None,
+            raw_body: None,
             cookies: HashMap::new(),
+            received_at: ReceivedAt::now(),
+            cancellation: cancel::CancellationToken::new(),
+            tls: false,
+            remote_addr: None,
+            connection: connection::Connection::default(),
+            handler_marks: None,
+            query: OnceLock::new(),
+            trailing_slash: false,
+            request_id: None,
+            resolution: None,
+            body_stream: None,
         }
     }
 }
@@ -56,13 +199,15 @@ impl Request {
     /// # Returns
     ///
     /// - `Result<Request, error::RequestError>` - A result containing the `Request` struct if
-    /// parsing is successful, or a `RequestError` if there is an error in parsing.
+    ///   parsing is successful, or a `RequestError` if there is an error in parsing.
     ///
     /// # Errors
     ///
     /// - `RequestError::InvalidRequestLineError` - If the request line is malformed.
+    /// - `RequestError::UnsupportedVersionError` - If the HTTP version isn't 1.0 or 1.1.
+    /// - `RequestError::UnsupportedMethodError` - If the method isn't one of `HttpMethod`.
     /// - `RequestError::EmptyRequestError` - If the request is empty.
-    pub fn new(input: &Vec<String>) -> Result<Request, error::RequestError> {
+    pub fn new(input: &[String]) -> Result<Request, error::RequestError> {
         let method;
         let path;
         let version;
@@ -70,19 +215,46 @@ impl Request {
 
         // parse request method, path, and version from the first line of input string vector by
         // looping over the parts of the line
-        match input.get(0) {
+        match input.first() {
             Some(request_line) => {
                 let parts: Vec<_> = request_line.split_whitespace().collect();
                 if parts.len() >= 3 {
+                    version = parts[2].to_string();
+                    match version.as_str() {
+                        "HTTP/1.0" | "HTTP/1.1" => {}
+                        _ if version.starts_with("HTTP/") => {
+                            return Err(error::RequestError::UnsupportedVersionError(version));
+                        }
+                        _ => {
+                            return Err(error::RequestError::InvalidRequestLineError(
+                                request_line.to_string(),
+                            ));
+                        }
+                    }
+                    // methods are case-sensitive, and unknown ones(like `TRACE` or `CONNECT`) are
+                    // rejected rather than handled like another method
                     method = match parts[0] {
                         "GET" => utils::HttpMethod::GET,
                         "POST" => utils::HttpMethod::POST,
+                        "PUT" => utils::HttpMethod::PUT,
                         "PATCH" => utils::HttpMethod::PATCH,
                         "DELETE" => utils::HttpMethod::DELETE,
-                        _ => utils::HttpMethod::GET,
+                        "HEAD" => utils::HttpMethod::HEAD,
+                        "OPTIONS" => utils::HttpMethod::OPTIONS,
+                        other => {
+                            return Err(error::RequestError::UnsupportedMethodError(
+                                other.to_string(),
+                            ));
+                        }
                     };
+                    // the request-target is made of visible ASCII characters only, anything else
+                    // has to be percent-encoded
+                    if !parts[1].bytes().all(|byte| byte.is_ascii_graphic()) {
+                        return Err(error::RequestError::InvalidRequestLineError(
+                            request_line.to_string(),
+                        ));
+                    }
                     path = parts[1].to_string();
-                    version = parts[2].to_string();
                 } else {
                     return Err(error::RequestError::InvalidRequestLineError(
                         request_line.to_string(),
@@ -127,7 +299,10 @@ impl Request {
             cookie_string.split(";").for_each(|string_cookie| {
                 let mut cookie_parts = string_cookie.splitn(2, '=');
                 if let (Some(name), Some(value)) = (cookie_parts.next(), cookie_parts.next()) {
-                    cookies.insert(name.trim().to_string(), utils::Cookie::new(name, value));
+                    cookies.insert(
+                        name.trim().to_string(),
+                        utils::Cookie::new(name.trim(), value.trim()),
+                    );
                 }
             });
         };
@@ -139,7 +314,787 @@ impl Request {
             version,
             headers,
             body,
+            raw_body: None,
             cookies,
+            received_at: ReceivedAt::now(),
+            cancellation: cancel::CancellationToken::new(),
+            tls: false,
+            remote_addr: None,
+            connection: connection::Connection::default(),
+            handler_marks: None,
+            query: OnceLock::new(),
+            trailing_slash: false,
+            request_id: None,
+            resolution: None,
+            body_stream: None,
         });
     }
+
+    /// Reads the next HTTP request from a buffered reader, like a TCP connection stream.
+    ///
+    /// The request head is read line by line until the empty line ending it, after which the body
+    /// is read according to the `Content-Length` header. Whatever framing problem would make the
+    /// server and an intermediary(like a proxy in front of it) disagree about where a request
+    /// ends is always rejected, because such disagreements allow request smuggling:
+    ///
+    /// - multiple `Content-Length` headers with different values, or a non-numeric value
+    /// - any `Transfer-Encoding` header, since chunked request bodies aren't supported
+    ///
+    /// In strict mode, every other RFC 7230 requirement on the request head is enforced too:
+    ///
+    /// - every line has to end with CRLF, bare LF line endings are rejected
+    /// - the request line has to be exactly `method SP request-target SP HTTP-version`
+    /// - header names have to be tokens, so whitespace before the colon is rejected, and so are
+    ///   obsolete line folds(header lines starting with whitespace)
+    ///
+    /// In the default permissive mode these are tolerated, like most servers do, which is fine as
+    /// long as no intermediary interprets them differently.
+    ///
+    /// # Arguments
+    ///
+    /// - `buf_reader` - The reader to read the request from.
+    /// - `strict` - Whether to enable strict mode.
+    ///
+    /// # Returns
+    ///
+    /// - `Result<Option<Request>, WebServerError>` - The parsed `Request`, `None` if the reader was
+    ///   closed(or timed out) before any part of a request arrived, a `RequestParseError` if the
+    ///   request is malformed or an `IO` error if reading failed.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use browzer_web::request::Request;
+    ///
+    /// let mut input = "POST /users HTTP/1.1\r\nContent-Length: 4\r\n\r\naxew".as_bytes();
+    /// let request = Request::read_from(&mut input, true).unwrap().unwrap();
+    /// assert_eq!(request.body, Some("axew".to_string()));
+    ///
+    /// let mut input = "GET / HTTP/1.1\nHost: example.com\n\n".as_bytes();
+    /// assert!(Request::read_from(&mut input, false).is_ok());
+    /// let mut input = "GET / HTTP/1.1\nHost: example.com\n\n".as_bytes();
+    /// assert!(Request::read_from(&mut input, true).is_err());
+    /// ```
+    pub fn read_from<R: BufRead>(
+        buf_reader: &mut R,
+        strict: bool,
+    ) -> Result<Option<Request>, error::WebServerError> {
+        let mut request = match Request::read_head(buf_reader, strict)? {
+            Some(request) => request,
+            None => return Ok(None),
+        };
+        request.read_body(buf_reader)?;
+        return Ok(Some(request));
+    }
+
+    /// Reads the head of the next HTTP request from a buffered reader, without it's body.
+    ///
+    /// This is the first half of `Request::read_from`, it allows looking at the request(like at
+    /// it's `Content-Length`) before deciding whether to read the body using `Request::read_body`.
+    /// The head is validated exactly like `Request::read_from` does, and it's size is bounded by
+    /// the default `limits::HeadLimits`(see `Request::read_head_with_limits`).
+    ///
+    /// # Arguments
+    ///
+    /// - `buf_reader` - The reader to read the request head from.
+    /// - `strict` - Whether to enable strict mode.
+    ///
+    /// # Returns
+    ///
+    /// - `Result<Option<Request>, WebServerError>` - The parsed `Request` without a body, `None` if
+    ///   the reader was closed(or timed out) before any part of a request arrived, a
+    ///   `RequestParseError` if the request head is malformed or an `IO` error if reading failed.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use browzer_web::request::Request;
+    ///
+    /// let mut input = "POST /users HTTP/1.1\r\nContent-Length: 4\r\n\r\naxew".as_bytes();
+    /// let mut request = Request::read_head(&mut input, false).unwrap().unwrap();
+    /// assert_eq!(request.content_length(), 4);
+    /// assert_eq!(request.body, None);
+    ///
+    /// request.read_body(&mut input).unwrap();
+    /// assert_eq!(request.body, Some("axew".to_string()));
+    /// ```
+    pub fn read_head<R: BufRead>(
+        buf_reader: &mut R,
+        strict: bool,
+    ) -> Result<Option<Request>, error::WebServerError> {
+        return Request::read_head_with_limits(buf_reader, strict, &limits::HeadLimits::default());
+    }
+
+    /// Reads the head of the next HTTP request from a buffered reader, like `Request::read_head`
+    /// but with custom limits of it's size.
+    ///
+    /// Reading stops as soon as a limit is exceeded, so no more than `max_head_size` bytes(plus
+    /// one) are ever buffered. A head which exceeds a limit or isn't valid UTF-8 is reported as a
+    /// `RequestParseError`, whose `RequestError::status_code` is the status code to answer it with.
+    ///
+    /// # Arguments
+    ///
+    /// - `buf_reader` - The reader to read the request head from.
+    /// - `strict` - Whether to enable strict mode.
+    /// - `limits` - The limits of the request line, the number of headers and the whole head.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use browzer_web::{error::WebServerError, limits::HeadLimits, request::Request, utils::HttpStatusCode};
+    ///
+    /// let limits = HeadLimits {
+    ///     max_head_size: 64,
+    ///     ..HeadLimits::default()
+    /// };
+    /// let head = format!("GET / HTTP/1.1\r\nCookie: {}\r\n\r\n", "a".repeat(100));
+    ///
+    /// match Request::read_head_with_limits(&mut head.as_bytes(), false, &limits) {
+    ///     Err(WebServerError::RequestParseError(e)) => {
+    ///         assert_eq!(e.status_code(), HttpStatusCode::RequestHeaderFieldsTooLarge);
+    ///     }
+    ///     _ => panic!("the head is too large"),
+    /// }
+    /// ```
+    pub fn read_head_with_limits<R: BufRead>(
+        buf_reader: &mut R,
+        strict: bool,
+        limits: &limits::HeadLimits,
+    ) -> Result<Option<Request>, error::WebServerError> {
+        let parse_error = |e: error::RequestError| error::WebServerError::RequestParseError(e);
+
+        // read the request head line by line into a string vector, until the empty line which
+        // separates the head from the body
+        let mut request_vector = Vec::new();
+        let mut received_at = None;
+        let mut head_size = 0;
+        loop {
+            let is_request_line = request_vector.is_empty();
+            let limit = match is_request_line {
+                true => limits.max_request_line.min(limits.max_head_size),
+                false => limits.max_head_size.saturating_sub(head_size),
+            };
+            // read at most one byte over the limit, which tells a line at the limit apart from a
+            // longer one without buffering the rest of it
+            let mut bytes = Vec::new();
+            match buf_reader
+                .by_ref()
+                .take((limit as u64).saturating_add(1))
+                .read_until(b'\n', &mut bytes)
+            {
+                Ok(0) => break,
+                Ok(_) => {
+                    if received_at.is_none() {
+                        received_at = Some(ReceivedAt::now());
+                    }
+                }
+                Err(e) => {
+                    if request_vector.is_empty()
+                        && matches!(
+                            e.kind(),
+                            io::ErrorKind::WouldBlock
+                                | io::ErrorKind::TimedOut
+                                | io::ErrorKind::ConnectionReset
+                                | io::ErrorKind::UnexpectedEof
+                        )
+                    {
+                        return Ok(None);
+                    }
+                    return Err(error::WebServerError::IO(e));
+                }
+            }
+            if bytes.len() > limit {
+                return Err(parse_error(match is_request_line {
+                    true => error::RequestError::RequestLineTooLongError(limit),
+                    false => error::RequestError::HeadTooLargeError(limits.max_head_size),
+                }));
+            }
+            head_size += bytes.len();
+            let line = match String::from_utf8(bytes) {
+                Ok(line) => line,
+                Err(e) => {
+                    let line = String::from_utf8_lossy(e.as_bytes()).trim_end().to_string();
+                    return Err(parse_error(match is_request_line {
+                        true => error::RequestError::InvalidRequestLineError(line),
+                        false => error::RequestError::MalformedHeaderError(line),
+                    }));
+                }
+            };
+            let line = match line.strip_suffix("\r\n") {
+                Some(line) => line.to_string(),
+                None => {
+                    if strict {
+                        return Err(parse_error(error::RequestError::InvalidLineEndingError(
+                            line.trim_end().to_string(),
+                        )));
+                    }
+                    line.trim_end_matches(['\r', '\n']).to_string()
+                }
+            };
+            if strict && line.contains('\r') {
+                return Err(parse_error(error::RequestError::InvalidLineEndingError(
+                    line,
+                )));
+            }
+            let is_end_of_head = line.is_empty();
+            request_vector.push(line);
+            if is_end_of_head {
+                break;
+            }
+            // every line but the request line is a header
+            if request_vector.len() - 1 > limits.max_headers {
+                return Err(parse_error(error::RequestError::TooManyHeadersError(
+                    limits.max_headers,
+                )));
+            }
+        }
+        if request_vector.is_empty() {
+            return Ok(None);
+        }
+
+        if strict {
+            Request::validate_strict(&request_vector).map_err(parse_error)?;
+        }
+        Request::parse_content_length(&request_vector).map_err(parse_error)?;
+        Request::check_host_count(&request_vector).map_err(parse_error)?;
+
+        match Request::new(&request_vector) {
+            Ok(mut request) => {
+                if let Some(received_at) = received_at {
+                    request.received_at = received_at;
+                }
+                return Ok(Some(request));
+            }
+            Err(e) => return Err(parse_error(e)),
+        }
+    }
+
+    /// Reads the body of a request, whose head was read using `Request::read_head`, from a
+    /// buffered reader.
+    ///
+    /// Exactly `Content-Length` bytes are read, anything after them belongs to the next request on
+    /// the connection.
+    ///
+    /// # Arguments
+    ///
+    /// - `buf_reader` - The reader the request head was read from.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `IO` error if reading failed, like when the reader was closed before the whole
+    /// body arrived.
+    pub fn read_body<R: BufRead>(
+        &mut self,
+        buf_reader: &mut R,
+    ) -> Result<(), error::WebServerError> {
+        let content_length = self.content_length();
+        if content_length == 0 {
+            return Ok(());
+        }
+        // the buffer grows with the data which actually arrives, rather than trusting the announced
+        // length up front, so a client lying about it can't make the server allocate gigabytes
+        let mut body = Vec::with_capacity(content_length.min(INITIAL_BODY_CAPACITY));
+        match buf_reader
+            .by_ref()
+            .take(content_length as u64)
+            .read_to_end(&mut body)
+        {
+            Ok(read) if read == content_length => {}
+            Ok(_) => {
+                return Err(error::WebServerError::IO(io::Error::new(
+                    io::ErrorKind::UnexpectedEof,
+                    "Connection closed before the whole request body was received",
+                )));
+            }
+            Err(e) => return Err(error::WebServerError::IO(e)),
+        }
+        // the body is kept once, as a string if it's valid UTF-8 and as bytes otherwise, so a binary
+        // body isn't corrupted by a lossy conversion
+        match String::from_utf8(body) {
+            Ok(body) => self.body = Some(body),
+            Err(e) => self.raw_body = Some(e.into_bytes()),
+        }
+        return Ok(());
+    }
+
+    /// Returns the length of the request body announced by the `Content-Length` header, `0` if the
+    /// request has no(or an invalid) `Content-Length` header.
+    pub fn content_length(&self) -> usize {
+        match self.header("Content-Length") {
+            Some(length) => return length.trim().parse::<usize>().unwrap_or(0),
+            None => return 0,
+        }
+    }
+
+    // enforces the RFC 7230 grammar of the request line and header lines of a request head
+    fn validate_strict(head: &[String]) -> Result<(), error::RequestError> {
+        let request_line = match head.first() {
+            Some(request_line) => request_line,
+            None => return Err(error::RequestError::EmptyRequestError),
+        };
+        let parts = request_line.split(' ').collect::<Vec<_>>();
+        if parts.len() != 3
+            || parts
+                .iter()
+                .any(|part| part.is_empty() || part.contains('\t'))
+            || !parts[2].starts_with("HTTP/")
+        {
+            return Err(error::RequestError::InvalidRequestLineError(
+                request_line.to_string(),
+            ));
+        }
+
+        for line in head[1..].iter().take_while(|line| !line.is_empty()) {
+            let name = match line.split_once(':') {
+                Some((name, _)) => name,
+                None => return Err(error::RequestError::MalformedHeaderError(line.to_string())),
+            };
+            let is_token = !name.is_empty()
+                && name
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || "!#$%&'*+-.^_`|~".contains(c));
+            if !is_token {
+                return Err(error::RequestError::MalformedHeaderError(line.to_string()));
+            }
+        }
+        return Ok(());
+    }
+
+    // determines the length of the request body from the headers of a request head, rejecting
+    // every header combination which could be interpreted differently by another HTTP parser
+    fn parse_content_length(head: &[String]) -> Result<usize, error::RequestError> {
+        let mut content_length = None;
+        for line in head[1..].iter().take_while(|line| !line.is_empty()) {
+            let (name, value) = match line.split_once(':') {
+                Some((name, value)) => (name.trim(), value.trim()),
+                None => continue,
+            };
+            if name.eq_ignore_ascii_case("Transfer-Encoding") {
+                return Err(error::RequestError::AmbiguousBodyLengthError(format!(
+                    "unsupported Transfer-Encoding: {}",
+                    value
+                )));
+            }
+            if !name.eq_ignore_ascii_case("Content-Length") {
+                continue;
+            }
+            if value.is_empty() || !value.chars().all(|c| c.is_ascii_digit()) {
+                return Err(error::RequestError::AmbiguousBodyLengthError(format!(
+                    "invalid Content-Length: {}",
+                    value
+                )));
+            }
+            let length = match value.parse::<usize>() {
+                Ok(length) => length,
+                Err(_) => {
+                    return Err(error::RequestError::AmbiguousBodyLengthError(format!(
+                        "invalid Content-Length: {}",
+                        value
+                    )))
+                }
+            };
+            match content_length {
+                Some(previous) if previous != length => {
+                    return Err(error::RequestError::AmbiguousBodyLengthError(format!(
+                        "conflicting Content-Length values: {} and {}",
+                        previous, length
+                    )));
+                }
+                _ => content_length = Some(length),
+            }
+        }
+        return Ok(content_length.unwrap_or(0));
+    }
+
+    // rejects a request head with more than one `Host` header(RFC 9112, section 3.2), since only
+    // one of them would be kept and the host checks could be answered for either of them
+    fn check_host_count(head: &[String]) -> Result<(), error::RequestError> {
+        let hosts = head[1..]
+            .iter()
+            .take_while(|line| !line.is_empty())
+            .filter_map(|line| line.split_once(':'))
+            .filter(|(name, _)| name.trim().eq_ignore_ascii_case("Host"))
+            .count();
+        if hosts > 1 {
+            return Err(error::RequestError::DuplicateHostError);
+        }
+        return Ok(());
+    }
+
+    /// Returns a copy of the request without it's body, like for the after-response middlewares.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # use browzer_web::request::Request;
+    /// let mut request = Request::new(&["POST /users HTTP/1.1".to_string()]).unwrap();
+    /// request.body = Some("axew".to_string());
+    ///
+    /// assert_eq!(request.without_body().path, "/users");
+    /// assert_eq!(request.without_body().body, None);
+    /// ```
+    pub fn without_body(&self) -> Request {
+        return Request {
+            method: self.method.clone(),
+            path: self.path.to_string(),
+            version: self.version.to_string(),
+            headers: self.headers.clone(),
+            body: None,
+            raw_body: None,
+            cookies: self.cookies.clone(),
+            received_at: self.received_at,
+            cancellation: self.cancellation.clone(),
+            tls: self.tls,
+            remote_addr: self.remote_addr,
+            connection: self.connection.clone(),
+            handler_marks: self.handler_marks.clone(),
+            query: self.query.clone(),
+            trailing_slash: self.trailing_slash,
+            request_id: self.request_id.clone(),
+            resolution: None,
+            body_stream: None,
+        };
+    }
+
+    /// Returns the raw body of the request, which is empty if the request has no body.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # use browzer_web::request::Request;
+    /// let mut request = Request::default();
+    /// request.body = Some("axew".to_string());
+    /// assert_eq!(request.body_bytes(), b"axew");
+    ///
+    /// request.body = None;
+    /// request.raw_body = Some(vec![0xff, 0xfe]);
+    /// assert_eq!(request.body_bytes(), [0xff, 0xfe]);
+    /// ```
+    pub fn body_bytes(&self) -> &[u8] {
+        match (&self.raw_body, &self.body) {
+            (Some(raw_body), _) => return raw_body,
+            (None, Some(body)) => return body.as_bytes(),
+            (None, None) => return &[],
+        }
+    }
+
+    /// Takes the body of a request to a route which streams it(see `RouteBuilder::stream_body`).
+    ///
+    /// `None` for requests to other routes, whose body was read before they were handled(see
+    /// `Request::body_bytes`), and once the stream was taken.
+    pub fn take_body_stream(&mut self) -> Option<BodyStream> {
+        return self.body_stream.take();
+    }
+
+    /// Returns the value of a request header, matching the header name case-insensitively.
+    ///
+    /// # Arguments
+    ///
+    /// - `name` - The name of the header.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use browzer_web::request::Request;
+    ///
+    /// let mut request = Request::default();
+    /// request.headers.insert("content-type".to_string(), "text/plain".to_string());
+    /// assert_eq!(request.header("Content-Type"), Some(&"text/plain".to_string()));
+    /// ```
+    pub fn header(&self, name: &str) -> Option<&String> {
+        return self
+            .headers
+            .iter()
+            .find(|(key, _)| key.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value);
+    }
+
+    /// Returns the host name the request was sent to, taken from the `Host` header.
+    ///
+    /// The port is removed and the host name is lowercased, IPv6 literals keep their brackets.
+    ///
+    /// # Returns
+    ///
+    /// - `Option<String>` - The host name, or `None` if the request has no (or an empty) `Host`
+    ///   header.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use browzer_web::request::Request;
+    ///
+    /// let mut request = Request::default();
+    /// assert_eq!(request.host(), None);
+    ///
+    /// request.headers.insert("Host".to_string(), "Example.com:8080".to_string());
+    /// assert_eq!(request.host(), Some("example.com".to_string()));
+    ///
+    /// request.headers.insert("Host".to_string(), "[::1]:3000".to_string());
+    /// assert_eq!(request.host(), Some("[::1]".to_string()));
+    /// ```
+    pub fn host(&self) -> Option<String> {
+        let host = match self.header("Host") {
+            Some(host) => host.trim(),
+            None => return None,
+        };
+        let host = match host.starts_with('[') {
+            true => match host.find(']') {
+                Some(end) => &host[..=end],
+                None => host,
+            },
+            false => host.split(':').next().unwrap_or(""),
+        };
+        let host = host.trim_end_matches('.');
+        if host.is_empty() {
+            return None;
+        }
+        return Some(host.to_ascii_lowercase());
+    }
+
+    /// Returns the query string of the request path, without the `?`, which is empty if the path
+    /// has no query string.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use browzer_web::request::Request;
+    ///
+    /// let mut request = Request::default();
+    /// assert_eq!(request.query_string(), "");
+    ///
+    /// request.path = "/search?q=rust&page=2".to_string();
+    /// assert_eq!(request.query_string(), "q=rust&page=2");
+    /// ```
+    pub fn query_string(&self) -> &str {
+        match self.path.split_once('?') {
+            Some((_, query)) => return query,
+            None => return "",
+        }
+    }
+
+    /// Returns the query string of the request path, parsed.
+    ///
+    /// The query string is parsed the first time it is needed and kept for the rest of the
+    /// request, so the router and every `Context::query` call share one parse. Changing `path`
+    /// after that doesn't change the parsed query string.
+    ///
+    /// # Returns
+    ///
+    /// - `Option<&Query>` - The parsed query string, `None` if it can't be parsed(see
+    ///   `Query::parse`).
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use browzer_web::request::Request;
+    ///
+    /// let mut request = Request::default();
+    /// request.path = "/search?q=rust&q=web".to_string();
+    /// assert_eq!(request.query().unwrap().get_all("q"), vec!["rust", "web"]);
+    /// ```
+    pub fn query(&self) -> Option<&Query> {
+        return self
+            .query
+            .get_or_init(|| Query::parse(self.query_string()))
+            .as_ref();
+    }
+
+    /// Whether the client wants the connection to persist after this request.
+    ///
+    /// `HTTP/1.1` connections are persistent unless the client sends `Connection: close`, while
+    /// older versions are only persistent if the client explicitly sends `Connection: keep-alive`.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use browzer_web::request::Request;
+    ///
+    /// let mut request = Request::default();
+    /// assert!(request.is_keep_alive());
+    ///
+    /// request.headers.insert("Connection".to_string(), "close".to_string());
+    /// assert!(!request.is_keep_alive());
+    /// ```
+    pub fn is_keep_alive(&self) -> bool {
+        let has_token = |token: &str| match self.header("Connection") {
+            Some(connection) => connection
+                .split(',')
+                .any(|option| option.trim().eq_ignore_ascii_case(token)),
+            None => false,
+        };
+        match self.version.as_str() {
+            "HTTP/1.1" => return !has_token("close"),
+            _ => return has_token("keep-alive"),
+        }
+    }
+}
+
+/// The parsed query string of a request.
+///
+/// Keys and values are percent-decoded with `+` standing for a space, and keep the order they
+/// appear in. A key may appear more than once, like `tag=a&tag=b`, in which case `Query::get`
+/// returns it's first value and `Query::get_all` all of them.
+///
+/// # Examples
+///
+/// ```rust
+/// use browzer_web::request::Query;
+///
+/// let query = Query::parse("tag=rust&tag=web&q=hello+world&flag").unwrap();
+/// assert_eq!(query.get("tag"), Some("rust"));
+/// assert_eq!(query.get_all("tag"), vec!["rust", "web"]);
+/// assert_eq!(query.get("q"), Some("hello world"));
+/// assert_eq!(query.get("flag"), Some(""));
+/// assert_eq!(query.get("page"), None);
+///
+/// assert!(Query::parse("=value").is_none());
+/// assert!(Query::parse("q=100%").is_none());
+/// ```
+// ----- Query struct
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Query {
+    pairs: Vec<(String, String)>,
+}
+
+impl Query {
+    /// Creates an empty `Query`.
+    pub const fn new() -> Query {
+        return Query { pairs: Vec::new() };
+    }
+
+    /// Parses a query string(without the `?`), empty segments like in `a=1&&b=2` are skipped.
+    ///
+    /// # Returns
+    ///
+    /// - `Option<Query>` - The parsed query string, `None` if a key is empty or a key or value
+    ///   can't be percent-decoded.
+    pub fn parse(query: &str) -> Option<Query> {
+        let mut pairs = Vec::new();
+        for part in query.split('&').filter(|part| !part.is_empty()) {
+            let (key, value) = part.split_once('=').unwrap_or((part, ""));
+            if key.is_empty() {
+                return None;
+            }
+            pairs.push((utils::query_decode(key)?, utils::query_decode(value)?));
+        }
+        return Some(Query { pairs });
+    }
+
+    /// Returns the first value of a key.
+    pub fn get(&self, key: &str) -> Option<&str> {
+        return self
+            .pairs
+            .iter()
+            .find(|(name, _)| name == key)
+            .map(|(_, value)| value.as_str());
+    }
+
+    /// Returns all values of a key, in the order they appear in.
+    pub fn get_all(&self, key: &str) -> Vec<&str> {
+        return self
+            .pairs
+            .iter()
+            .filter(|(name, _)| name == key)
+            .map(|(_, value)| value.as_str())
+            .collect();
+    }
+
+    /// Returns whether the query string contains a key.
+    pub fn contains_key(&self, key: &str) -> bool {
+        return self.pairs.iter().any(|(name, _)| name == key);
+    }
+
+    /// Returns the key and value pairs, in the order they appear in.
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &str)> {
+        return self
+            .pairs
+            .iter()
+            .map(|(key, value)| (key.as_str(), value.as_str()));
+    }
+
+    /// Returns the number of key and value pairs.
+    pub fn len(&self) -> usize {
+        return self.pairs.len();
+    }
+
+    /// Returns whether the query string has no key and value pairs.
+    pub fn is_empty(&self) -> bool {
+        return self.pairs.is_empty();
+    }
+}
+
+/// The body of a request which is passed to it's handler while it arrives, instead of being read
+/// before the handler runs, see `RouteBuilder::stream_body`.
+///
+/// Reads block until the next part of the body arrived, and fail like reading the connection
+/// failed: with a `TimedOut` error once the request ran out of time, or an `UnexpectedEof` error if
+/// the client closed the connection before the whole body arrived. Async handlers should read it on
+/// a blocking thread(like with `tokio::task::spawn_blocking`). Whatever the handler doesn't read is
+/// never read from the connection, which is closed after the response then.
+// ----- BodyStream struct
+pub struct BodyStream {
+    length: u64,
+    received: u64,
+    // returns the next part of the body, `None` once all of it(or as much of it as arrived) was
+    // passed on
+    next_chunk: Box<dyn FnMut() -> Option<io::Result<Vec<u8>>> + Send>,
+    chunk: Vec<u8>,
+    position: usize,
+}
+
+impl BodyStream {
+    // creates a stream of a body of the given length, which is passed on in parts by `next_chunk`
+    pub(crate) fn new<F>(length: u64, next_chunk: F) -> BodyStream
+    where
+        F: FnMut() -> Option<io::Result<Vec<u8>>> + 'static + Send,
+    {
+        return BodyStream {
+            length,
+            received: 0,
+            next_chunk: Box::new(next_chunk),
+            chunk: Vec::new(),
+            position: 0,
+        };
+    }
+
+    /// Returns the length of the body, as announced by the `Content-Length` header of the request.
+    pub fn length(&self) -> u64 {
+        return self.length;
+    }
+}
+
+impl fmt::Debug for BodyStream {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        return f
+            .debug_struct("BodyStream")
+            .field("length", &self.length)
+            .finish();
+    }
+}
+
+impl Read for BodyStream {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        while self.position == self.chunk.len() {
+            match (self.next_chunk)() {
+                Some(Ok(chunk)) => {
+                    self.received += chunk.len() as u64;
+                    self.chunk = chunk;
+                    self.position = 0;
+                }
+                Some(Err(e)) => return Err(e),
+                // a body which ended early is never mistaken for the whole body
+                None if self.received < self.length => {
+                    return Err(io::Error::new(
+                        io::ErrorKind::UnexpectedEof,
+                        "The request body ended before all of it was received",
+                    ));
+                }
+                None => return Ok(0),
+            }
+        }
+        let read = buf.len().min(self.chunk.len() - self.position);
+        buf[..read].copy_from_slice(&self.chunk[self.position..self.position + read]);
+        self.position += read;
+        return Ok(read);
+    }
 }
@@ -0,0 +1,138 @@
+//! This module gives every request a `WebServer` answers an ID, which ties together the log lines,
+//! the response and the requests to other services made on it's behalf, see
+//! `WebServer::request_ids`.
+//!
+//! A request arriving with an ID(set by a reverse proxy or the calling service) keeps it, all other
+//! requests get a new random UUID. The ID is available to middlewares and handlers with
+//! `Context::request_id`, is part of the request log(see `logger::RequestLog`) and is echoed in the
+//! same header of the response.
+
+// internal crate imports
+use crate::{request, response};
+
+// external crate imports
+use uuid::Uuid;
+
+/// The header carrying the ID of a request by default.
+pub const DEFAULT_HEADER: &str = "X-Request-Id";
+
+/// The longest incoming ID which is kept, longer ones are replaced.
+pub const MAX_LENGTH: usize = 200;
+
+/// The ID of a request, attached to it's `Context`(see `Context::request_id`).
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct RequestId(pub String);
+
+/// The request ID settings of a server, see `WebServer::request_ids`.
+///
+/// An incoming ID is only kept if it's at most `MAX_LENGTH` characters of visible ASCII, so a
+/// client can't inject line breaks or other control characters into the log lines through it.
+///
+/// # Examples
+///
+/// ```rust
+/// use browzer_web::{request::Request, request_id::RequestIds};
+///
+/// let ids = RequestIds::new();
+///
+/// let mut request = Request::default();
+/// request.headers.insert("x-request-id".to_string(), "abc-123".to_string());
+/// assert_eq!(ids.assign(&mut request), "abc-123");
+///
+/// // an ID is only assigned once
+/// assert_eq!(ids.assign(&mut request), "abc-123");
+///
+/// // the line break makes the incoming ID unusable, so a new one is generated
+/// let mut request = Request::default();
+/// request.headers.insert("X-Request-Id".to_string(), "abc\n123".to_string());
+/// let id = ids.assign(&mut request);
+/// assert_eq!(id.len(), 36);
+/// assert_eq!(request.header("X-Request-Id"), Some(&id));
+/// ```
+// ----- RequestIds struct
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RequestIds {
+    header: String,
+    trust_incoming: bool,
+}
+
+// default implementation for RequestIds struct
+impl Default for RequestIds {
+    fn default() -> Self {
+        return RequestIds::new();
+    }
+}
+
+impl RequestIds {
+    /// Creates new `RequestIds` settings, using the `X-Request-Id` header and keeping incoming IDs.
+    pub fn new() -> RequestIds {
+        return RequestIds {
+            header: DEFAULT_HEADER.to_string(),
+            trust_incoming: true,
+        };
+    }
+
+    /// Sets the header carrying the ID, in requests and responses.
+    pub fn header(mut self, name: &str) -> RequestIds {
+        self.header = name.to_string();
+        return self;
+    }
+
+    /// Sets whether incoming IDs are kept, which only makes sense if the clients(or the reverse
+    /// proxy in front of the server) can be trusted to send unique ones. Enabled by default.
+    pub fn trust_incoming(mut self, trust: bool) -> RequestIds {
+        self.trust_incoming = trust;
+        return self;
+    }
+
+    /// Returns the header carrying the ID.
+    pub fn header_name(&self) -> &str {
+        return &self.header;
+    }
+
+    /// Returns the ID of a request, keeping a valid incoming one or generating a new one, and
+    /// replaces the header of the request with it.
+    ///
+    /// The server assigns the ID as soon as the head of a request was parsed, so that even the
+    /// responses it generates itself(like `413 Payload Too Large`) carry it. A request which was
+    /// assigned an ID already keeps it.
+    pub fn assign(&self, request: &mut request::Request) -> String {
+        if let Some(ref id) = request.request_id {
+            return id.clone();
+        }
+        let incoming = match self.trust_incoming {
+            true => request
+                .header(&self.header)
+                .filter(|id| is_valid(id))
+                .cloned(),
+            false => None,
+        };
+        let id = incoming.unwrap_or_else(|| Uuid::new_v4().to_string());
+        request
+            .headers
+            .retain(|name, _| !name.eq_ignore_ascii_case(&self.header));
+        request.headers.insert(self.header.clone(), id.clone());
+        request.request_id = Some(id.clone());
+        return id;
+    }
+
+    /// Generates a new ID, for a request whose head couldn't be parsed.
+    pub fn generate(&self) -> String {
+        return Uuid::new_v4().to_string();
+    }
+
+    /// Sets the header of a response to the ID of it's request.
+    pub fn apply(&self, id: &str, response: &mut response::Response) {
+        response
+            .headers
+            .retain(|name, _| !name.eq_ignore_ascii_case(&self.header));
+        response.headers.insert(self.header.clone(), id.to_string());
+    }
+}
+
+// returns whether an incoming ID can be kept
+fn is_valid(id: &str) -> bool {
+    return !id.is_empty()
+        && id.len() <= MAX_LENGTH
+        && id.bytes().all(|byte| byte.is_ascii_graphic());
+}
@@ -1,14 +1,11 @@
 //! This module defines the `Response` struct used to represent HTTP responses in the web framework.
 //! It includes functionality to create, manipulate, and convert responses to strings for sending over the network
 
-// external crate imports
-use chrono;
-
 // internal crate imports
-use crate::utils;
+use crate::{error, stream, utils};
 
 // standard library imports
-use std::collections::HashMap;
+use std::{collections::HashMap, io};
 
 /// Represents an HTTP response.
 ///
@@ -20,20 +17,25 @@ use std::collections::HashMap;
 /// - `headers` - A `HashMap` containing key-value pairs of header names and values.
 /// - `body` - A `String` containing the body of the response.
 /// - `cookies` - A `HashMap` containing cookies from the request
+/// - `stream` - An optional `StreamBody`, which makes the response a streaming response whose body
+///   is written by the stream(using chunked transfer encoding) instead of being taken from `body`.
+///   It isn't serialized with the `serde` feature, a deserialized response never streams
 ///
 /// # Examples
 ///
 /// ```rust
 /// use browzer_web::response::Response;
 /// use browzer_web::utils::HttpStatusCode;
-/// use maplit::hashmap;
+/// use std::collections::HashMap;
 ///
 /// let response = Response {
 ///     status_code: HttpStatusCode::OK,
-///     headers: hashmap! {
-///         "Content-Type".to_string() => "text/html".to_string()
-///     },
+///     headers: HashMap::from([
+///         ("Content-Type".to_string(), "text/html".to_string())
+///     ]),
 ///     body: "<html><body>Hello, World!</body></html>".to_string(),
+///     cookies: HashMap::new(),
+///     stream: None,
 /// };
 ///
 /// assert_eq!(response.status_code, HttpStatusCode::OK);
@@ -42,11 +44,14 @@ use std::collections::HashMap;
 /// ```
 // ----- Response struct
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Response {
     pub status_code: utils::HttpStatusCode,
     pub headers: HashMap<String, String>,
     pub body: String,
     pub cookies: HashMap<String, utils::Cookie>,
+    #[cfg_attr(feature = "serde", serde(skip))]
+    pub stream: Option<stream::StreamBody>,
 }
 
 // default implementation for Response struct
@@ -57,6 +62,7 @@ impl Default for Response {
             headers: HashMap::new(),
             body: String::from(""),
             cookies: HashMap::new(),
+            stream: None,
         };
     }
 }
@@ -93,9 +99,54 @@ impl Response {
             headers: HashMap::new(),
             body,
             cookies: HashMap::new(),
+            stream: None,
         };
     }
 
+    /// Adds a request header name to the `Vary` header of the response, unless it's already listed.
+    ///
+    /// Responses whose content depends on a request header(like `Accept-Encoding` or `Origin`)
+    /// have to list it in their `Vary` header, so that caches don't serve them to clients sending a
+    /// different value.
+    ///
+    /// # Arguments
+    ///
+    /// - `name` - The name of the request header.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use browzer_web::response::Response;
+    /// use browzer_web::utils::HttpStatusCode;
+    ///
+    /// let mut response = Response::new(HttpStatusCode::OK, String::new());
+    /// response.add_vary("Accept-Encoding");
+    /// response.add_vary("Origin");
+    /// response.add_vary("origin");
+    ///
+    /// assert_eq!(response.headers.get("Vary").unwrap(), "Accept-Encoding, Origin");
+    /// ```
+    pub fn add_vary(&mut self, name: &str) {
+        let existing = self
+            .headers
+            .iter()
+            .find(|(key, _)| key.eq_ignore_ascii_case("Vary"))
+            .map(|(key, value)| (key.clone(), value.clone()));
+        match existing {
+            Some((key, value)) => {
+                let listed = value
+                    .split(',')
+                    .any(|entry| entry.trim() == "*" || entry.trim().eq_ignore_ascii_case(name));
+                if !listed {
+                    self.headers.insert(key, format!("{}, {}", value, name));
+                }
+            }
+            None => {
+                self.headers.insert("Vary".to_string(), name.to_string());
+            }
+        }
+    }
+
     /// Converts the `Response` instance into a string formatted as an HTTP response.
     ///
     /// This function convert the `Response` struct into a string to be sent as bytes by setting the status_code
@@ -104,6 +155,9 @@ impl Response {
     /// over the `cookies` field in the Response struct, and then finally adding a blank line
     /// followed by the body of the response to the response string
     ///
+    /// Responses which can't have a body(`204 No Content` and `304 Not Modified`) are converted
+    /// without it, see `Response::head_to_string` for their headers.
+    ///
     /// # Returns
     ///
     /// - A `String` representation of the HTTP response.
@@ -112,11 +166,12 @@ impl Response {
     ///
     /// ```rust
     /// use browzer_web::response::Response;
-    /// use browzer_web::utils::HttpStatusCode;
-    /// use maplit::hashmap;
+    /// use browzer_web::utils::{Cookie, HttpStatusCode};
+    /// use std::collections::HashMap;
+    /// use std::time::{Duration, SystemTime};
     ///
-    /// let mut cookies = hashmap! {
-    ///     "session".to_string() => Cookie {
+    /// let cookies = HashMap::from([
+    ///     ("session".to_string(), Cookie {
     ///         name: "session".to_string(),
     ///         value: "abc123".to_string(),
     ///         expires: Some(SystemTime::now() + Duration::new(15 * 60, 0)),
@@ -125,15 +180,17 @@ impl Response {
     ///         secure: true,
     ///         http_only: true,
     ///         ..Default::default()
-    ///     }
-    /// };
+    ///     })
+    /// ]);
     ///
     /// let response = Response {
     ///     status_code: HttpStatusCode::OK,
-    ///     headers: hashmap! {
-    ///         "Content-Type".to_string() => "text/html".to_string()
-    ///     },
+    ///     headers: HashMap::from([
+    ///         ("Content-Type".to_string(), "text/html".to_string())
+    ///     ]),
     ///     body: "<html><body>Hello, World!</body></html>".to_string(),
+    ///     cookies,
+    ///     stream: None,
     /// };
     ///
     /// let response_string = response.to_string();
@@ -145,52 +202,382 @@ impl Response {
     /// assert!(response_string.contains("Set-Cookie: session=abc123; Path=/; Domain=example.com; Expires="));
     /// ```
     pub fn to_string(&self) -> String {
+        let mut response = self.head_to_string();
+        if self.allows_body() {
+            response.push_str(&self.body);
+        }
+        return response;
+    }
+
+    /// Whether the status code of the response allows a body, which `204 No Content` and `304 Not
+    /// Modified` don't.
+    pub fn allows_body(&self) -> bool {
+        return !matches!(
+            self.status_code,
+            utils::HttpStatusCode::NoContent | utils::HttpStatusCode::NotModified
+        );
+    }
+
+    /// Converts the `Response` instance into a string formatted as an HTTP response, without the
+    /// body.
+    ///
+    /// The `Content-Length` header still reflects the length of the body in bytes, which is what a
+    /// response to a `HEAD` request has to look like. Streaming responses without a known length
+    /// announce `Transfer-Encoding: chunked` instead, see `Response::head_for_version` for the
+    /// responses to `HTTP/1.0` requests.
+    ///
+    /// The framing headers are generated, so `Content-Length` and `Transfer-Encoding` set in
+    /// `headers` are never sent twice. `204 No Content` responses carry neither, and `304 Not
+    /// Modified` ones only a `Content-Length` which was set explicitly. A `Content-Length` set by
+    /// the handler is only honoured for `304` responses and responses to `HEAD` requests, see
+    /// `Response::head_for_request`.
+    ///
+    /// # Returns
+    ///
+    /// - A `String` containing the status line and headers of the HTTP response, terminated by a
+    ///   blank line.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use browzer_web::response::Response;
+    /// use browzer_web::utils::HttpStatusCode;
+    ///
+    /// let response = Response::new(HttpStatusCode::OK, "Hello, World!".to_string());
+    /// let head_string = response.head_to_string();
+    ///
+    /// assert!(head_string.contains("Content-Length: 13"));
+    /// assert!(head_string.ends_with("\r\n\r\n"));
+    ///
+    /// // the length is counted in bytes, not characters
+    /// let response = Response::new(HttpStatusCode::OK, "Grüße".to_string());
+    /// assert!(response.head_to_string().contains("Content-Length: 7"));
+    ///
+    /// // the length of a body which wasn't generated is only announced to `HEAD` requests
+    /// let mut response = Response::new(HttpStatusCode::OK, String::new());
+    /// response.headers.insert("content-length".to_string(), "1024".to_string());
+    /// assert!(response.head_to_string().contains("Content-Length: 0\r\n"));
+    /// assert!(!response.head_to_string().contains("content-length"));
+    ///
+    /// let response = Response::new(HttpStatusCode::NoContent, String::new());
+    /// assert!(!response.head_to_string().contains("Content-Length"));
+    /// ```
+    pub fn head_to_string(&self) -> String {
+        return self.head_for_version("HTTP/1.1");
+    }
+
+    /// Converts the `Response` instance into the head of a response to a request of the given HTTP
+    /// version, see `Response::head_to_string`.
+    ///
+    /// `HTTP/1.0` clients don't know the chunked transfer encoding, so a response to them has an
+    /// `HTTP/1.0` status line and a streaming response without a known length announces no length
+    /// at all. It's body ends when the connection is closed. Every other version gets an
+    /// `HTTP/1.1` response.
+    ///
+    /// # Arguments
+    ///
+    /// - `version` - The HTTP version of the request, like `HTTP/1.0`.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use browzer_web::{response::Response, stream::StreamBody, utils::HttpStatusCode};
+    ///
+    /// let mut response = Response::new(HttpStatusCode::OK, String::new());
+    /// response.stream = Some(StreamBody::new(|_| Ok(())));
+    ///
+    /// assert!(response.head_for_version("HTTP/1.1").contains("Transfer-Encoding: chunked"));
+    /// let head = response.head_for_version("HTTP/1.0");
+    /// assert!(head.starts_with("HTTP/1.0 200 OK\r\n"));
+    /// assert!(!head.contains("Transfer-Encoding") && !head.contains("Content-Length"));
+    /// ```
+    pub fn head_for_version(&self, version: &str) -> String {
+        return self.head_for_request(version, false);
+    }
+
+    /// Converts the `Response` instance into the head of a response to a request of the given HTTP
+    /// version and method, see `Response::head_for_version`.
+    ///
+    /// A response to a `HEAD` request with an empty body can announce the length of the body it
+    /// stands for by setting the `Content-Length` header itself, like a handler answering `HEAD`
+    /// requests without generating the body. For every other request the length is the one of the
+    /// body, so a stale or wrong header can't desynchronize the connection.
+    ///
+    /// # Arguments
+    ///
+    /// - `version` - The HTTP version of the request, like `HTTP/1.0`.
+    /// - `is_head` - Whether the request is a `HEAD` request.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use browzer_web::{response::Response, utils::HttpStatusCode};
+    ///
+    /// let mut response = Response::new(HttpStatusCode::OK, String::new());
+    /// response.headers.insert("Content-Length".to_string(), "1024".to_string());
+    ///
+    /// assert!(response.head_for_request("HTTP/1.1", true).contains("Content-Length: 1024\r\n"));
+    /// assert!(response.head_for_request("HTTP/1.1", false).contains("Content-Length: 0\r\n"));
+    /// ```
+    pub fn head_for_request(&self, version: &str, is_head: bool) -> String {
         let status_code = &self.status_code.code();
+        let http_1_0 = version == "HTTP/1.0";
         let mut response = format!(
-            "HTTP/1.1 {} {}\r\nContent-Length: {}\r\n",
+            "{} {} {}\r\n",
+            match http_1_0 {
+                true => "HTTP/1.0",
+                false => "HTTP/1.1",
+            },
             status_code.1,
-            status_code.0,
-            &self.body.len(),
+            status_code.0
         );
+        // a length set by the handler stands for a body which wasn't generated
+        let explicit_length = self
+            .headers
+            .iter()
+            .find(|(key, _)| key.eq_ignore_ascii_case("Content-Length"))
+            .and_then(|(_, value)| value.trim().parse::<u64>().ok());
+        let content_length = match self.stream {
+            Some(ref stream) => stream.length(),
+            None if self.body.is_empty() && is_head => Some(explicit_length.unwrap_or(0)),
+            // the length of a `String` is it's length in bytes
+            None => Some(self.body.len() as u64),
+        };
+        match self.status_code {
+            utils::HttpStatusCode::NoContent => {}
+            utils::HttpStatusCode::NotModified => {
+                if let Some(length) = explicit_length {
+                    response.push_str(&format!("Content-Length: {}\r\n", length));
+                }
+            }
+            _ => match content_length {
+                Some(length) => response.push_str(&format!("Content-Length: {}\r\n", length)),
+                // the body of a response to an `HTTP/1.0` request is delimited by closing the
+                // connection
+                None if http_1_0 => {}
+                None => response.push_str("Transfer-Encoding: chunked\r\n"),
+            },
+        }
         for (key, value) in &self.headers {
+            if key.eq_ignore_ascii_case("Content-Length")
+                || key.eq_ignore_ascii_case("Transfer-Encoding")
+            {
+                continue;
+            }
             response.push_str(&format! {"{}: {}\r\n",key,value});
         }
 
-        // parse cookies hashmap and append it to the response string
+        // serialize the cookies hashmap into `Set-Cookie` headers of the response string
         for cookie in self.cookies.values() {
-            let mut cookie_string = format!("{}={}", cookie.name, cookie.value);
+            response.push_str(&format!("Set-Cookie: {}\r\n", cookie.to_header_value()));
+        }
 
-            if let Some(ref path) = cookie.path {
-                cookie_string.push_str(&format!("; Path={}", path));
-            }
+        response.push_str("\r\n");
+        return response;
+    }
+}
 
-            if let Some(ref domain) = cookie.domain {
-                cookie_string.push_str(&format!("; Domain={}", domain));
-            }
+/// Converts the value returned by a route handler into the `Response` sent to the client.
+///
+/// Route handlers can return a bare `Response`, or a `Result<Response, E>` whose error converts
+/// into an `ErrorResponse`, which lets them bail out early with the `?` operator. Handlers
+/// returning a `Result` are registered with `WebServer::register_route`.
+///
+/// # Examples
+///
+/// ```rust,no_run
+/// # use browzer_web::{response::{ErrorResponse, Response}, WebServer};
+/// # use browzer_web::utils::{HttpMethod, HttpStatusCode};
+/// # use serde::Deserialize;
+/// # #[derive(Deserialize)]
+/// # struct NewUser { name: String }
+/// let mut server = WebServer::new("127.0.0.1:8080".to_string(), 4);
+///
+/// server
+///     .register_route("/users", HttpMethod::POST, |mut c| -> Result<Response, ErrorResponse> {
+///         // a body which isn't a valid user is answered with `400 Bad Request` or
+///         // `415 Unsupported Media Type`
+///         let user: NewUser = c.bind_json()?;
+///         if user.name.is_empty() {
+///             return Err(ErrorResponse::new(
+///                 HttpStatusCode::UnprocessableEntity,
+///                 "The name is empty",
+///             ));
+///         }
+///         return Ok(c.send_string(HttpStatusCode::Created, &user.name));
+///     })
+///     .unwrap();
+/// ```
+pub trait IntoResponse {
+    /// Converts the value into a `Response`.
+    fn into_response(self) -> Response;
 
-            if let Some(expires) = cookie.expires {
-                let datetime = chrono::DateTime::<chrono::Utc>::from(expires);
-                let formatted_time = datetime.format("%a, %d %b %Y %H:%M:%S GMT").to_string();
-                cookie_string.push_str(&format!("; Expires={}", formatted_time));
-            }
+    /// Converts the value into a `Response`, or the `ErrorResponse` it carries. The router answers
+    /// an `ErrorResponse` like any other framework error(see `WebServer::error_handler`), this
+    /// method only needs to be implemented by types which can carry one.
+    fn into_result(self) -> Result<Response, ErrorResponse>
+    where
+        Self: Sized,
+    {
+        return Ok(self.into_response());
+    }
+}
 
-            if let Some(max_age) = cookie.max_age {
-                cookie_string.push_str(&format!("; Max-Age={}", max_age));
-            }
+impl IntoResponse for Response {
+    fn into_response(self) -> Response {
+        return self;
+    }
+}
 
-            if cookie.secure {
-                cookie_string.push_str("; Secure");
-            }
+impl<E> IntoResponse for Result<Response, E>
+where
+    E: Into<ErrorResponse>,
+{
+    fn into_response(self) -> Response {
+        match self {
+            Ok(response) => return response,
+            Err(e) => return e.into().into_response(),
+        }
+    }
 
-            if cookie.http_only {
-                cookie_string.push_str("; HttpOnly");
-            }
+    fn into_result(self) -> Result<Response, ErrorResponse> {
+        return self.map_err(|e| e.into());
+    }
+}
+
+/// An error returned by a route handler.
+///
+/// The router answers it like any other framework error with it's status code(see
+/// `WebServer::error_handler`, `WebServer::problem_details` and `WebServer::page_templates`), the
+/// message becomes the `detail` of a problem details response. Without any of them configured the
+/// message is sent as a plain text body.
+///
+/// The errors of this crate, I/O errors, JSON errors and bare status codes convert into an
+/// `ErrorResponse`, so they can be returned from a handler with the `?` operator. Client errors
+/// keep their message, while server errors(and failed static file lookups) are answered with the
+/// reason phrase of their status code only, so no internal details reach the client.
+///
+/// # Fields
+///
+/// - `status_code` - The status code of the response.
+/// - `message` - The message of the error, sent to the client as described above.
+///
+/// # Examples
+///
+/// ```rust
+/// use browzer_web::{error::UploadError, response::ErrorResponse, utils::HttpStatusCode};
+/// use std::io;
+///
+/// let error = ErrorResponse::from(UploadError::OffsetMismatch(42));
+/// assert_eq!(error.status_code, HttpStatusCode::Conflict);
+/// assert_eq!(error.message, "Upload offset mismatch, the current offset is 42");
+///
+/// let error = ErrorResponse::from(io::Error::new(io::ErrorKind::Other, "disk on fire"));
+/// assert_eq!(error.status_code, HttpStatusCode::InternalServerError);
+/// assert_eq!(error.message, "Internal Server Error");
+///
+/// assert_eq!(ErrorResponse::from(HttpStatusCode::NotFound).message, "Not Found");
+/// ```
+// ----- ErrorResponse struct
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ErrorResponse {
+    pub status_code: utils::HttpStatusCode,
+    pub message: String,
+}
 
-            response.push_str(&format!("Set-Cookie: {}\r\n", cookie_string));
+impl ErrorResponse {
+    /// Creates a new `ErrorResponse` with the given status code and message.
+    pub fn new(status_code: utils::HttpStatusCode, message: &str) -> ErrorResponse {
+        return ErrorResponse {
+            status_code,
+            message: message.to_string(),
+        };
+    }
+
+    // keeps the message of client errors, server errors only get their reason phrase
+    fn from_error(status_code: utils::HttpStatusCode, message: String) -> ErrorResponse {
+        match status_code.code().1 >= 500 {
+            true => return ErrorResponse::from(status_code),
+            false => return ErrorResponse::new(status_code, &message),
         }
+    }
+}
 
-        response.push_str("\r\n");
-        response.push_str(&self.body);
+impl IntoResponse for ErrorResponse {
+    fn into_response(self) -> Response {
+        let mut response = Response::new(self.status_code, self.message);
+        response.headers.insert(
+            "Content-Type".to_string(),
+            "text/plain; charset=utf-8".to_string(),
+        );
         return response;
     }
+
+    fn into_result(self) -> Result<Response, ErrorResponse> {
+        return Err(self);
+    }
+}
+
+impl From<utils::HttpStatusCode> for ErrorResponse {
+    fn from(status_code: utils::HttpStatusCode) -> Self {
+        let message = status_code.code().0.to_string();
+        return ErrorResponse {
+            status_code,
+            message,
+        };
+    }
+}
+
+impl From<error::BindError> for ErrorResponse {
+    fn from(err: error::BindError) -> Self {
+        return ErrorResponse::from_error(err.status_code(), err.to_string());
+    }
+}
+
+impl From<error::QueryError> for ErrorResponse {
+    fn from(err: error::QueryError) -> Self {
+        return ErrorResponse::from_error(err.status_code(), err.to_string());
+    }
+}
+
+impl From<error::MultipartError> for ErrorResponse {
+    fn from(err: error::MultipartError) -> Self {
+        return ErrorResponse::from_error(err.status_code(), err.to_string());
+    }
+}
+
+impl From<error::UploadError> for ErrorResponse {
+    fn from(err: error::UploadError) -> Self {
+        return ErrorResponse::from_error(err.status_code(), err.to_string());
+    }
+}
+
+impl From<error::StaticFileError> for ErrorResponse {
+    fn from(err: error::StaticFileError) -> Self {
+        // the reason phrase is all a client needs to know about a file it can't have
+        return ErrorResponse::from(err.status_code());
+    }
+}
+
+impl From<error::WebServerError> for ErrorResponse {
+    fn from(_: error::WebServerError) -> Self {
+        return ErrorResponse::from(utils::HttpStatusCode::InternalServerError);
+    }
+}
+
+impl From<serde_json::Error> for ErrorResponse {
+    fn from(err: serde_json::Error) -> Self {
+        return ErrorResponse::new(utils::HttpStatusCode::BadRequest, &err.to_string());
+    }
+}
+
+impl From<io::Error> for ErrorResponse {
+    fn from(err: io::Error) -> Self {
+        let status_code = match err.kind() {
+            io::ErrorKind::NotFound => utils::HttpStatusCode::NotFound,
+            io::ErrorKind::PermissionDenied => utils::HttpStatusCode::Forbidden,
+            _ => utils::HttpStatusCode::InternalServerError,
+        };
+        return ErrorResponse::from(status_code);
+    }
 }
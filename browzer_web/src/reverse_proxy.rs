@@ -0,0 +1,829 @@
+//! This module forwards requests to an upstream HTTP server and streams it's responses back to the
+//! client, which turns a `WebServer` into a gateway or a backend for a frontend, see
+//! `WebServer::proxy`.
+//!
+//! The method, headers and body of a request are forwarded as they arrived, except for the
+//! hop-by-hop headers(see `HOP_BY_HOP_HEADERS`), which only concern a single connection. The body
+//! is streamed to the upstream while it arrives(see `RouteBuilder::stream_body`), so a large upload
+//! isn't held in memory. The
+//! `Host` header names the upstream, while the standard `X-Forwarded-For`, `X-Forwarded-Proto` and
+//! `X-Forwarded-Host` headers tell it about the client and the host name it asked for.
+//!
+//! Connections to the upstream are kept open after a response was read completely and reused by
+//! the next requests, so a busy gateway doesn't pay for a new TCP connection per request. The body
+//! of an upstream response isn't buffered, it's streamed to the client while it arrives. When the
+//! upstream closes a reused connection without answering, the request is sent again on a new one,
+//! unless it's a `POST` or `PATCH` request without an `Idempotency-Key` header, which the upstream
+//! may have applied already, or a larger part of it's streamed body than `REPLAY_LIMIT` was sent
+//! already.
+//! Upstreams which can't be reached are answered with `502 Bad Gateway`, ones which don't answer
+//! in time with `504 Gateway Timeout`. Upstreams are reached with an `http_client::Client`, so
+//! `https://` upstreams are supported with the `tls` feature.
+
+// internal crate imports
+use crate::{context, error, http_client, limits, request, response, stream, utils};
+
+// standard library imports
+use std::{
+    collections::HashMap,
+    io::{self, BufRead, BufReader, Read, Write},
+    time::Duration,
+};
+
+/// How long connecting to the upstream, sending a request and every read of the response may take
+/// by default.
+pub const DEFAULT_TIMEOUT: Duration = http_client::DEFAULT_TIMEOUT;
+
+/// How many idle connections to the upstream are kept open by default.
+pub const DEFAULT_MAX_IDLE: usize = http_client::DEFAULT_MAX_IDLE;
+
+/// How long an idle connection to the upstream is reused by default, shorter than the keep-alive
+/// timeout of most servers(5 seconds for a `WebServer`) so the upstream doesn't close it first.
+pub const DEFAULT_IDLE_TIMEOUT: Duration = http_client::DEFAULT_IDLE_TIMEOUT;
+
+/// The headers which only concern a single connection, and are never forwarded in either
+/// direction. The headers named in the `Connection` header are treated the same way.
+pub const HOP_BY_HOP_HEADERS: &[&str] = &[
+    "Connection",
+    "Keep-Alive",
+    "Proxy-Authenticate",
+    "Proxy-Authorization",
+    "Proxy-Connection",
+    "TE",
+    "Trailer",
+    "Transfer-Encoding",
+    "Upgrade",
+];
+
+// the headers of a request which the proxy sets itself
+const REPLACED_HEADERS: &[&str] = &[
+    "Host",
+    "Content-Length",
+    "X-Forwarded-For",
+    "X-Forwarded-Proto",
+    "X-Forwarded-Host",
+];
+
+// the longest chunk size line of a chunked upstream response which is read
+const MAX_CHUNK_LINE: u64 = 1024;
+
+/// The number of bytes of a streamed request body which are kept while they are sent to the
+/// upstream, so that a request with a body up to this size can be sent again on a new connection.
+pub const REPLAY_LIMIT: usize = 64 * 1024;
+
+// why sending a request on a connection failed
+enum SendError {
+    // the connection was closed before any of the response arrived, like an idle connection the
+    // upstream closed in the meantime, so a replayable request can be sent again on a new one
+    Closed(io::Error),
+    Failed(io::Error),
+    // the body of the request couldn't be read from the client
+    Body(io::Error),
+}
+
+// the body of a request forwarded to the upstream
+enum RequestBody<'a> {
+    // the body was read before the request was handled
+    Buffered(&'a [u8]),
+    // the body is passed on while it arrives, what was sent of it is kept until it outgrows the
+    // `REPLAY_LIMIT`
+    Streamed {
+        stream: request::BodyStream,
+        sent: Option<Vec<u8>>,
+    },
+}
+
+impl RequestBody<'_> {
+    // whether the body can be sent(again) as a whole
+    fn can_resend(&self) -> bool {
+        match self {
+            RequestBody::Buffered(_) => return true,
+            RequestBody::Streamed { sent, .. } => return sent.is_some(),
+        }
+    }
+}
+
+// how the end of the body of an upstream response is found
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Framing {
+    Empty,
+    Length(u64),
+    Chunked,
+    UntilClose,
+}
+
+// the head of an upstream response
+struct ResponseHead {
+    status_code: utils::HttpStatusCode,
+    headers: HashMap<String, String>,
+    cookies: HashMap<String, utils::Cookie>,
+    framing: Framing,
+    keep_alive: bool,
+}
+
+/// Forwards requests to an upstream HTTP server, see `WebServer::proxy`.
+///
+/// The path a request is forwarded to is the path of the upstream followed by the part of the
+/// request path matched by the wildcard segment of the route(like `*path` in `/api/*path`), and
+/// the query string of the request. Without such a segment(see `ReverseProxy::path_param`), the
+/// whole request path is appended instead. Clones share their idle connections, and so do proxies
+/// given the same `http_client::Client`.
+///
+/// The status code and reason phrase of an upstream response are passed through unchanged, and so
+/// are it's `Set-Cookie` headers(see `utils::Cookie::to_header_value`). They are parsed into the
+/// `cookies` of the response, keyed by name, so middlewares can inspect or replace them. Of
+/// several cookies with the same name only the last one is kept.
+///
+/// # Examples
+///
+/// ```rust
+/// use browzer_web::reverse_proxy::ReverseProxy;
+/// use std::time::Duration;
+///
+/// let proxy = ReverseProxy::new("http://127.0.0.1:9000/v1")
+///     .unwrap()
+///     .timeout(Duration::from_secs(5))
+///     .max_idle(64);
+/// assert_eq!(proxy.upstream(), "http://127.0.0.1:9000/v1");
+///
+/// assert!(ReverseProxy::new("ftp://example.com").is_err());
+/// assert!(ReverseProxy::new("http://example.com:http").is_err());
+/// assert!(ReverseProxy::new("http://example.com/search?q=1").is_err());
+/// ```
+// ----- ReverseProxy struct
+#[derive(Debug, Clone)]
+pub struct ReverseProxy {
+    upstream: http_client::Url,
+    base_path: String,
+    path_param: Option<String>,
+    client: http_client::Client,
+}
+
+impl ReverseProxy {
+    /// Creates a new `ReverseProxy` forwarding requests to the given upstream URL, like
+    /// `http://127.0.0.1:9000` or `http://users.internal/api`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `InvalidUrl` error if the upstream isn't a valid URL(see `http_client::Url`) or
+    /// has a query string.
+    pub fn new(upstream: &str) -> Result<ReverseProxy, error::ConfigValueError> {
+        let url = http_client::Url::parse(upstream)?;
+        if url.path().contains('?') {
+            return Err(error::ConfigValueError::InvalidUrl(upstream.to_string()));
+        }
+        return Ok(ReverseProxy {
+            base_path: url.path().trim_end_matches('/').to_string(),
+            upstream: url,
+            path_param: None,
+            client: http_client::Client::new(),
+        });
+    }
+
+    /// Sets the route parameter holding the part of the request path which is appended to the
+    /// path of the upstream. `WebServer::proxy` sets it to the wildcard segment of the route.
+    pub fn path_param(mut self, name: &str) -> ReverseProxy {
+        self.path_param = Some(name.to_string());
+        return self;
+    }
+
+    /// Sets how long connecting to the upstream, sending a request and every read of the response
+    /// may take, `DEFAULT_TIMEOUT` by default.
+    pub fn timeout(mut self, timeout: Duration) -> ReverseProxy {
+        self.client = self.client.timeout(timeout);
+        return self;
+    }
+
+    /// Sets how many idle connections to the upstream are kept open, `DEFAULT_MAX_IDLE` by
+    /// default. Zero opens a new connection for every request.
+    pub fn max_idle(mut self, max_idle: usize) -> ReverseProxy {
+        self.client = self.client.max_idle(max_idle);
+        return self;
+    }
+
+    /// Sets how long an idle connection to the upstream is reused, `DEFAULT_IDLE_TIMEOUT` by
+    /// default. Keep it shorter than the keep-alive timeout of the upstream.
+    pub fn idle_timeout(mut self, idle_timeout: Duration) -> ReverseProxy {
+        self.client = self.client.idle_timeout(idle_timeout);
+        return self;
+    }
+
+    /// Sends the requests with the given client, like one shared with other proxies or with a
+    /// `webhooks::HttpTransport`, or one trusting a private certificate authority. The client's
+    /// timeout and pool settings replace the ones set on the proxy so far.
+    pub fn client(mut self, client: http_client::Client) -> ReverseProxy {
+        self.client = client;
+        return self;
+    }
+
+    /// Returns the URL of the upstream.
+    pub fn upstream(&self) -> String {
+        let scheme = match self.upstream.is_tls() {
+            true => "https",
+            false => "http",
+        };
+        return format!(
+            "{}://{}{}",
+            scheme,
+            self.upstream.authority(),
+            self.base_path
+        );
+    }
+
+    // sets the route parameter holding the forwarded path, unless one was set already
+    pub(crate) fn default_path_param(&mut self, name: &str) {
+        if self.path_param.is_none() {
+            self.path_param = Some(name.to_string());
+        }
+    }
+
+    /// Forwards a request to the upstream and answers it with the upstream's response, or with
+    /// `502 Bad Gateway`(`504 Gateway Timeout` if the upstream didn't answer in time) if that
+    /// failed. Failures are logged using `eprintln!`. Requests whose path would climb above the
+    /// path of the upstream with `..` segments are answered with `400 Bad Request`, and so are
+    /// requests whose streamed body couldn't be read completely(`408 Request Timeout` if it didn't
+    /// arrive in time).
+    ///
+    /// The body is streamed to the upstream if the route streams it(see
+    /// `RouteBuilder::stream_body`, like the routes of `WebServer::proxy` do), otherwise the body
+    /// which was read already is sent.
+    pub fn handle(&self, mut c: context::Context) -> response::Response {
+        let body_stream = c.request.take_body_stream();
+        let body_length = match body_stream {
+            Some(ref stream) => stream.length(),
+            None => c.request.body_bytes().len() as u64,
+        };
+        let head = match self.request_head(&c, body_length) {
+            Some(head) => head,
+            None => return c.error_response(utils::HttpStatusCode::BadRequest),
+        };
+        let is_head = c.request.method == utils::HttpMethod::HEAD;
+        // a request which may have reached the upstream is only sent again if that's harmless
+        let replayable = !matches!(
+            c.request.method,
+            utils::HttpMethod::POST | utils::HttpMethod::PATCH
+        ) || c.request.header("Idempotency-Key").is_some();
+        let mut body = match body_stream {
+            Some(stream) => RequestBody::Streamed {
+                stream,
+                sent: Some(Vec::new()),
+            },
+            None => RequestBody::Buffered(c.request.body_bytes()),
+        };
+        let result = self.exchange(&head, &mut body, is_head, replayable);
+        drop(body);
+        match result {
+            Ok(response) => return response,
+            Err(SendError::Body(e)) => {
+                eprintln!(
+                    "Failed to read the body of a request proxied to {}: {}",
+                    self.upstream(),
+                    e
+                );
+                let status_code = match e.kind() {
+                    io::ErrorKind::TimedOut | io::ErrorKind::WouldBlock => {
+                        utils::HttpStatusCode::RequestTimeout
+                    }
+                    _ => utils::HttpStatusCode::BadRequest,
+                };
+                return c.error_response(status_code);
+            }
+            Err(SendError::Closed(e)) | Err(SendError::Failed(e)) => {
+                eprintln!("Failed to proxy a request to {}: {}", self.upstream(), e);
+                let status_code = match e.kind() {
+                    io::ErrorKind::TimedOut | io::ErrorKind::WouldBlock => {
+                        utils::HttpStatusCode::GatewayTimeout
+                    }
+                    _ => utils::HttpStatusCode::BadGateway,
+                };
+                return c.error_response(status_code);
+            }
+        }
+    }
+
+    // builds the head of the request sent to the upstream, `None` if the forwarded path climbs
+    // above the path of the upstream
+    fn request_head(&self, c: &context::Context, body_length: u64) -> Option<String> {
+        let request = &c.request;
+        let path = request.path.split('?').next().unwrap_or_default();
+        let forwarded_path = match self
+            .path_param
+            .as_ref()
+            .and_then(|name| c.raw_params.get(name))
+        {
+            Some(rest) => format!("/{}", rest.trim_start_matches('/')),
+            // the router strips the slash of the root path
+            None if path.is_empty() => "/".to_string(),
+            None => path.to_string(),
+        };
+        let mut target = format!(
+            "{}{}",
+            self.base_path,
+            remove_dot_segments(&forwarded_path)?
+        );
+        let query = request.query_string();
+        if !query.is_empty() {
+            target.push('?');
+            target.push_str(query);
+        }
+
+        let mut head = format!(
+            "{} {} HTTP/1.1\r\nHost: {}\r\n",
+            request.method.to_string(),
+            target,
+            self.upstream.authority()
+        );
+        let connection_tokens = connection_tokens(request.header("Connection"));
+        for (name, value) in request.headers.iter() {
+            if is_hop_by_hop(name, &connection_tokens)
+                || REPLACED_HEADERS
+                    .iter()
+                    .any(|replaced| name.eq_ignore_ascii_case(replaced))
+            {
+                continue;
+            }
+            head.push_str(&format!("{}: {}\r\n", name, value));
+        }
+
+        let client_ip = request.remote_addr.map(|address| address.ip().to_string());
+        let forwarded_for = match (request.header("X-Forwarded-For"), client_ip) {
+            (Some(forwarded_for), Some(client_ip)) => {
+                Some(format!("{}, {}", forwarded_for, client_ip))
+            }
+            (Some(forwarded_for), None) => Some(forwarded_for.clone()),
+            (None, client_ip) => client_ip,
+        };
+        if let Some(forwarded_for) = forwarded_for {
+            head.push_str(&format!("X-Forwarded-For: {}\r\n", forwarded_for));
+        }
+        let proto = match request.tls {
+            true => "https",
+            false => "http",
+        };
+        head.push_str(&format!("X-Forwarded-Proto: {}\r\n", proto));
+        if let Some(host) = request.header("Host") {
+            head.push_str(&format!("X-Forwarded-Host: {}\r\n", host));
+        }
+
+        let expects_body = matches!(
+            request.method,
+            utils::HttpMethod::POST | utils::HttpMethod::PUT | utils::HttpMethod::PATCH
+        );
+        if body_length > 0 || expects_body {
+            head.push_str(&format!("Content-Length: {}\r\n", body_length));
+        }
+        head.push_str("\r\n");
+        return Some(head);
+    }
+
+    // sends a request to the upstream, on an idle connection if there is one
+    fn exchange(
+        &self,
+        head: &str,
+        body: &mut RequestBody,
+        is_head: bool,
+        replayable: bool,
+    ) -> Result<response::Response, SendError> {
+        if let Some(connection) = self.client.take_idle(&self.upstream) {
+            match self.send(connection, head, body, is_head) {
+                Ok(response) => return Ok(response),
+                // the request is sent again on a new connection, unless the upstream may have
+                // applied it before closing the connection or the body can't be sent again
+                Err(SendError::Closed(_)) if replayable && body.can_resend() => {}
+                Err(e) => return Err(e),
+            }
+        }
+        let connection = self
+            .client
+            .connect(&self.upstream)
+            .map_err(SendError::Failed)?;
+        return self.send(connection, head, body, is_head);
+    }
+
+    // sends a request on a connection and reads the head of the response, it's body is streamed
+    // from the connection once the response is sent to the client
+    fn send(
+        &self,
+        mut connection: http_client::Connection,
+        head: &str,
+        body: &mut RequestBody,
+        is_head: bool,
+    ) -> Result<response::Response, SendError> {
+        let closed = |e: io::Error| match e.kind() {
+            io::ErrorKind::BrokenPipe
+            | io::ErrorKind::ConnectionReset
+            | io::ErrorKind::ConnectionAborted => SendError::Closed(e),
+            _ => SendError::Failed(e),
+        };
+        connection.write_all(head.as_bytes()).map_err(closed)?;
+        match body {
+            RequestBody::Buffered(bytes) => connection.write_all(bytes).map_err(closed)?,
+            RequestBody::Streamed { stream, sent } => {
+                // a request sent again starts with the part of the body which was sent before
+                if let Some(sent) = sent {
+                    connection.write_all(sent).map_err(closed)?;
+                }
+                let mut chunk = vec![0u8; request::BODY_CHUNK_SIZE];
+                loop {
+                    let read = match stream.read(&mut chunk) {
+                        Ok(0) => break,
+                        Ok(read) => read,
+                        Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                        Err(e) => return Err(SendError::Body(e)),
+                    };
+                    *sent = match sent.take() {
+                        Some(mut kept) if kept.len() + read <= REPLAY_LIMIT => {
+                            kept.extend_from_slice(&chunk[..read]);
+                            Some(kept)
+                        }
+                        _ => None,
+                    };
+                    connection.write_all(&chunk[..read]).map_err(closed)?;
+                }
+            }
+        }
+        connection.flush().map_err(closed)?;
+
+        let mut reader = BufReader::new(connection);
+        let head = read_response_head(&mut reader, is_head)?;
+        let mut response = response::Response {
+            status_code: head.status_code,
+            headers: head.headers,
+            body: String::new(),
+            cookies: head.cookies,
+            stream: None,
+        };
+        match head.framing {
+            Framing::Empty | Framing::Length(0) => {
+                if head.keep_alive && reader.buffer().is_empty() {
+                    self.client.put_idle(&self.upstream, reader.into_inner());
+                }
+            }
+            framing => {
+                let length = match framing {
+                    Framing::Length(length) => Some(length),
+                    _ => None,
+                };
+                let body = UpstreamBody {
+                    reader: Some(reader),
+                    framing,
+                    chunk_remaining: 0,
+                    finished: false,
+                    reusable: head.keep_alive && framing != Framing::UntilClose,
+                    client: self.client.clone(),
+                    upstream: self.upstream.clone(),
+                };
+                response.stream = Some(stream::StreamBody::from_reader(body, length));
+            }
+        }
+        return Ok(response);
+    }
+}
+
+// the body of an upstream response, which hands it's connection back to the pool once it was
+// read completely
+struct UpstreamBody {
+    reader: Option<BufReader<http_client::Connection>>,
+    framing: Framing,
+    chunk_remaining: u64,
+    finished: bool,
+    reusable: bool,
+    client: http_client::Client,
+    upstream: http_client::Url,
+}
+
+impl Read for UpstreamBody {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let reader = match self.reader {
+            Some(ref mut reader) if !self.finished && !buf.is_empty() => reader,
+            _ => return Ok(0),
+        };
+        match self.framing {
+            Framing::Empty => {
+                self.finished = true;
+                return Ok(0);
+            }
+            Framing::Length(ref mut remaining) => {
+                let max = (*remaining).min(buf.len() as u64) as usize;
+                let read = reader.read(&mut buf[..max])?;
+                if read == 0 {
+                    return Err(truncated());
+                }
+                *remaining -= read as u64;
+                self.finished = *remaining == 0;
+                return Ok(read);
+            }
+            Framing::Chunked => {
+                if self.chunk_remaining == 0 {
+                    self.chunk_remaining = read_chunk_size(reader)?;
+                    if self.chunk_remaining == 0 {
+                        // the trailers of the last chunk aren't forwarded
+                        loop {
+                            if read_line(reader)?.is_empty() {
+                                break;
+                            }
+                        }
+                        self.finished = true;
+                        return Ok(0);
+                    }
+                }
+                let max = self.chunk_remaining.min(buf.len() as u64) as usize;
+                let read = reader.read(&mut buf[..max])?;
+                if read == 0 {
+                    return Err(truncated());
+                }
+                self.chunk_remaining -= read as u64;
+                if self.chunk_remaining == 0 && !read_line(reader)?.is_empty() {
+                    return Err(io::Error::new(
+                        io::ErrorKind::InvalidData,
+                        "Malformed chunk in the upstream response",
+                    ));
+                }
+                return Ok(read);
+            }
+            Framing::UntilClose => {
+                let read = reader.read(buf)?;
+                self.finished = read == 0;
+                return Ok(read);
+            }
+        }
+    }
+}
+
+impl Drop for UpstreamBody {
+    fn drop(&mut self) {
+        // only a connection whose response was read completely can carry the next request
+        if let Some(reader) = self.reader.take() {
+            if self.finished && self.reusable && reader.buffer().is_empty() {
+                self.client.put_idle(&self.upstream, reader.into_inner());
+            }
+        }
+    }
+}
+
+// reads the head of an upstream response, skipping interim(`1xx`) responses
+fn read_response_head(
+    reader: &mut BufReader<http_client::Connection>,
+    is_head: bool,
+) -> Result<ResponseHead, SendError> {
+    let max_size = limits::DEFAULT_MAX_HEAD_SIZE;
+    loop {
+        let mut lines: Vec<String> = vec![];
+        let mut size = 0;
+        loop {
+            let mut bytes = vec![];
+            let limit = (max_size - size) as u64 + 1;
+            let read = match reader.by_ref().take(limit).read_until(b'\n', &mut bytes) {
+                Ok(read) => read,
+                Err(e)
+                    if lines.is_empty()
+                        && matches!(
+                            e.kind(),
+                            io::ErrorKind::ConnectionReset | io::ErrorKind::ConnectionAborted
+                        ) =>
+                {
+                    return Err(SendError::Closed(e));
+                }
+                Err(e) => return Err(SendError::Failed(e)),
+            };
+            if read == 0 {
+                let e = truncated();
+                return match lines.is_empty() {
+                    true => Err(SendError::Closed(e)),
+                    false => Err(SendError::Failed(e)),
+                };
+            }
+            size += read;
+            if size > max_size {
+                return Err(SendError::Failed(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    "The head of the upstream response is too large",
+                )));
+            }
+            let line = String::from_utf8_lossy(&bytes)
+                .trim_end_matches(['\r', '\n'])
+                .to_string();
+            if line.is_empty() {
+                break;
+            }
+            lines.push(line);
+        }
+
+        let malformed = || {
+            SendError::Failed(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "Malformed upstream response",
+            ))
+        };
+        let mut status_line = lines[0].splitn(3, ' ');
+        let version = status_line.next().ok_or_else(malformed)?.to_string();
+        let code = status_line
+            .next()
+            .and_then(|code| code.parse::<u16>().ok())
+            .filter(|code| (100..600).contains(code))
+            .ok_or_else(malformed)?;
+        if (100..200).contains(&code) {
+            continue;
+        }
+        // status codes without a variant of their own are passed through unchanged
+        let status_code = match utils::HttpStatusCode::from_code(code) {
+            Some(status_code) => status_code,
+            None => {
+                let reason = status_line.next().unwrap_or_default().trim();
+                utils::HttpStatusCode::Other(code, reason.to_string())
+            }
+        };
+
+        let fields: Vec<(&str, &str)> = lines[1..]
+            .iter()
+            .filter_map(|line| line.split_once(':'))
+            .map(|(name, value)| (name.trim(), value.trim()))
+            .collect();
+        let connection = fields
+            .iter()
+            .filter(|(name, _)| name.eq_ignore_ascii_case("Connection"))
+            .map(|(_, value)| *value)
+            .collect::<Vec<_>>()
+            .join(",");
+        let connection_tokens = connection_tokens(Some(&connection));
+        let mut headers: HashMap<String, String> = HashMap::new();
+        let mut cookies = HashMap::new();
+        let mut content_length = None;
+        let mut chunked = false;
+        for (name, value) in fields {
+            if name.eq_ignore_ascii_case("Transfer-Encoding") {
+                chunked = value
+                    .rsplit(',')
+                    .next()
+                    .is_some_and(|coding| coding.trim().eq_ignore_ascii_case("chunked"));
+            }
+            if name.eq_ignore_ascii_case("Content-Length") {
+                content_length = Some(value.parse::<u64>().map_err(|_| malformed())?);
+            }
+            if is_hop_by_hop(name, &connection_tokens) {
+                continue;
+            }
+            if name.eq_ignore_ascii_case("Set-Cookie") {
+                // the header is passed on as it is, whatever attributes it has. Cookies are keyed
+                // by name like the ones handlers set, so a later cookie of the same name replaces
+                // an earlier one
+                let mut cookie = parse_set_cookie(value).unwrap_or_default();
+                cookie.raw = Some(value.to_string());
+                cookies.insert(cookie.name.clone(), cookie);
+                continue;
+            }
+            // repeated headers are combined into a list
+            match headers.get_mut(name) {
+                Some(existing) => {
+                    existing.push_str(", ");
+                    existing.push_str(value);
+                }
+                None => {
+                    headers.insert(name.to_string(), value.to_string());
+                }
+            }
+        }
+
+        let has_body = !is_head
+            && !matches!(
+                status_code,
+                utils::HttpStatusCode::NoContent | utils::HttpStatusCode::NotModified
+            );
+        let framing = match (has_body, chunked, content_length) {
+            (false, _, _) => Framing::Empty,
+            (true, true, _) => Framing::Chunked,
+            (true, false, Some(length)) => Framing::Length(length),
+            (true, false, None) => Framing::UntilClose,
+        };
+        if framing != Framing::Empty {
+            // the body the client gets is framed by the server
+            headers.retain(|name, _| !name.eq_ignore_ascii_case("Content-Length"));
+        }
+        let keep_alive = version == "HTTP/1.1" && !connection_tokens.iter().any(|t| t == "close");
+        return Ok(ResponseHead {
+            status_code,
+            headers,
+            cookies,
+            framing,
+            keep_alive,
+        });
+    }
+}
+
+// resolves the `.` and `..` segments of a path(including percent-encoded ones like `%2e%2e`), so
+// a forwarded path can't reach outside the path of the upstream, `None` if it climbs above the
+// root
+fn remove_dot_segments(path: &str) -> Option<String> {
+    let mut segments: Vec<&str> = vec![];
+    let mut dot_segment = false;
+    for segment in path.trim_start_matches('/').split('/') {
+        dot_segment = true;
+        match segment.to_ascii_lowercase().replace("%2e", ".").as_str() {
+            "." => {}
+            ".." => {
+                segments.pop()?;
+            }
+            _ => {
+                segments.push(segment);
+                dot_segment = false;
+            }
+        }
+    }
+    // a trailing dot segment leaves the path ending with a slash, like `/a/b/..` resolving to `/a/`
+    if dot_segment {
+        segments.push("");
+    }
+    return Some(format!("/{}", segments.join("/")));
+}
+
+// parses the value of a `Set-Cookie` header, so handlers and middlewares can inspect the cookie
+fn parse_set_cookie(value: &str) -> Option<utils::Cookie> {
+    let mut attributes = value.split(';');
+    let (name, cookie_value) = attributes.next()?.split_once('=')?;
+    let mut cookie = utils::Cookie::new(name.trim(), cookie_value.trim());
+    for attribute in attributes {
+        let (key, value) = match attribute.split_once('=') {
+            Some((key, value)) => (key.trim(), value.trim()),
+            None => (attribute.trim(), ""),
+        };
+        match key.to_ascii_lowercase().as_str() {
+            "path" => cookie.path = Some(value.to_string()),
+            "domain" => cookie.domain = Some(value.to_string()),
+            "expires" => {
+                cookie.expires = utils::parse_http_date(value);
+                cookie.raw_expires = Some(value.to_string());
+            }
+            "max-age" => cookie.max_age = value.parse().ok(),
+            "secure" => cookie.secure = true,
+            "httponly" => cookie.http_only = true,
+            "samesite" => {
+                cookie.same_site = match value.to_ascii_lowercase().as_str() {
+                    "strict" => Some(utils::SameSite::Strict),
+                    "lax" => Some(utils::SameSite::Lax),
+                    "none" => Some(utils::SameSite::None),
+                    _ => None,
+                }
+            }
+            _ => {}
+        }
+    }
+    return Some(cookie);
+}
+
+// returns the lowercase names listed in a `Connection` header
+fn connection_tokens(connection: Option<&String>) -> Vec<String> {
+    return connection
+        .map(|value| {
+            value
+                .split(',')
+                .map(|token| token.trim().to_ascii_lowercase())
+                .filter(|token| !token.is_empty())
+                .collect()
+        })
+        .unwrap_or_default();
+}
+
+// returns whether a header only concerns a single connection
+fn is_hop_by_hop(name: &str, connection_tokens: &[String]) -> bool {
+    return HOP_BY_HOP_HEADERS
+        .iter()
+        .any(|header| name.eq_ignore_ascii_case(header))
+        || connection_tokens
+            .iter()
+            .any(|token| name.eq_ignore_ascii_case(token));
+}
+
+// reads the size line of a chunk
+fn read_chunk_size(reader: &mut BufReader<http_client::Connection>) -> io::Result<u64> {
+    let line = read_line(reader)?;
+    let size = line.split(';').next().unwrap_or_default().trim();
+    return u64::from_str_radix(size, 16).map_err(|_| {
+        io::Error::new(
+            io::ErrorKind::InvalidData,
+            "Malformed chunk size in the upstream response",
+        )
+    });
+}
+
+// reads a line of a chunked body without it's line ending
+fn read_line(reader: &mut BufReader<http_client::Connection>) -> io::Result<String> {
+    let mut bytes = vec![];
+    if reader
+        .by_ref()
+        .take(MAX_CHUNK_LINE)
+        .read_until(b'\n', &mut bytes)?
+        == 0
+    {
+        return Err(truncated());
+    }
+    return Ok(String::from_utf8_lossy(&bytes)
+        .trim_end_matches(['\r', '\n'])
+        .to_string());
+}
+
+fn truncated() -> io::Error {
+    return io::Error::new(
+        io::ErrorKind::UnexpectedEof,
+        "The upstream closed the connection before the whole response was received",
+    );
+}
@@ -1,274 +1,2947 @@
 //! This module provides the routing functionality for the web framework. It defines the `WebRouter` struct, allowing user to handle routing in a web application.
 
 // internal crate imports
-use crate::{context, error, request, response, utils};
+#[cfg(feature = "compression")]
+use crate::compression;
+use crate::{
+    cache, canonical, context, cors, digest, error, forwarded, jobs, logger, metrics, pages,
+    policy, problem, rate_limit, replay, request, request_id, response, sampling, sessions, tasks,
+    templates, utils,
+};
 // standard library imports
-use std::{collections::HashMap, fmt};
+use std::{
+    borrow::Cow,
+    collections::{HashMap, HashSet, VecDeque},
+    fmt,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex, OnceLock, RwLock, RwLockReadGuard, Weak,
+    },
+    time::Duration,
+};
+#[cfg(feature = "tokio")]
+use std::{future::Future, pin::Pin};
 
-/// Manages the routing logic for the web framework.
+/// A boxed route handler function which generates a `Response` from a `Context`, or an
+/// `ErrorResponse` which the router answers like any other framework error.
+pub type RouteHandler = Box<
+    dyn Fn(context::Context) -> Result<response::Response, response::ErrorResponse>
+        + 'static
+        + Send
+        + Sync,
+>;
+
+// the future of the response of an async route handler
+#[cfg(feature = "tokio")]
+pub(crate) type HandlerFuture =
+    Pin<Box<dyn Future<Output = Result<response::Response, response::ErrorResponse>> + Send>>;
+
+// a boxed async route handler function, which returns the future of the response instead
+#[cfg(feature = "tokio")]
+pub(crate) type AsyncRouteHandler =
+    Box<dyn Fn(context::Context) -> HandlerFuture + 'static + Send + Sync>;
+
+/// A boxed middleware function which transforms a `Context` before it reaches a route handler.
+pub type Middleware = Box<dyn Fn(context::Context) -> context::Context + 'static + Send + Sync>;
+
+/// A boxed after-response middleware function which transforms the final `Response` of a request.
 ///
-/// The `WebRouter` struct holds the registered routes and matches incoming requests to the appropriate route handler.
+/// It receives the request without it's body, so the body doesn't have to be kept around until
+/// the response is generated.
+pub type AfterMiddleware = Box<
+    dyn Fn(&request::Request, response::Response) -> response::Response + 'static + Send + Sync,
+>;
+
+/// A boxed error handler function which generates the response for an error detected by the
+/// framework itself, from it's status code and the request path it occurred for.
+pub type ErrorHandler =
+    Box<dyn Fn(utils::HttpStatusCode, &str) -> response::Response + 'static + Send + Sync>;
+
+/// Per-route options which customize how the framework treats a registered route.
 ///
 /// # Fields
 ///
-/// - `routes` - A `HashMap` mapping route paths to another `HashMap` of HTTP methods and their corresponding `RouteHandlerFunction`.
-/// - `middlewares` - A `Vector` representing a list of all the registered middlewares
-// ----- WebRouter struct
-pub struct WebRouter {
-    // HashMap< --path-- ,HashMap< --method-- , RouteHandlerFunction>>
-    pub routes: HashMap<
-        String,
-        HashMap<
-            String,
-            Box<dyn Fn(context::Context) -> response::Response + 'static + Send + Sync>,
-        >,
-    >,
-    pub middlewares: Vec<Box<dyn Fn(context::Context) -> context::Context + 'static + Send + Sync>>,
+/// - `compress` - Whether responses of this route may be compressed by the response compression,
+///   `true` by default. Disable it for streams(like server-sent events) and already compressed
+///   bodies.
+/// - `policies` - Names of the access control policies which must all allow a request before the
+///   route handler is run.
+/// - `timeout` - Overrides the server's `request_timeout` for this route, if set.
+/// - `body_limit` - Overrides the server's `body_limit`(in bytes) for this route, if set.
+/// - `doc` - A human readable description of the route, listed by the route help endpoint(see
+///   `WebServer::enable_route_help`).
+/// - `cors` - Overrides the server's CORS policy(see `WebServer::cors`) for this route, if set.
+/// - `memory_budget` - Overrides the server's `memory_budget`(in bytes) for this route, if set.
+/// - `headers` - Headers added to every response of this route which doesn't set them itself,
+///   taking precedence over the server's default headers(see `WebServer::default_header`).
+/// - `stream_body` - Whether the body of a request is passed to the handler while it arrives,
+///   instead of being read before the handler runs, see `RouteBuilder::stream_body`.
+// ----- RouteOptions struct
+#[derive(Debug, Clone)]
+pub struct RouteOptions {
+    pub compress: bool,
+    pub policies: Vec<String>,
+    pub timeout: Option<Duration>,
+    pub body_limit: Option<usize>,
+    pub doc: Option<String>,
+    pub cors: Option<cors::Cors>,
+    pub memory_budget: Option<usize>,
+    pub headers: Vec<(String, String)>,
+    pub stream_body: bool,
 }
 
-impl fmt::Debug for WebRouter {
+// default implementation for RouteOptions struct
+impl Default for RouteOptions {
+    fn default() -> Self {
+        return RouteOptions {
+            compress: true,
+            policies: vec![],
+            timeout: None,
+            body_limit: None,
+            doc: None,
+            cors: None,
+            memory_budget: None,
+            headers: vec![],
+            stream_body: false,
+        };
+    }
+}
+
+/// Represents a registered route.
+///
+/// # Fields
+///
+/// - `handler` - The `RouteHandler` which generates responses for the route.
+/// - `options` - The `RouteOptions` of the route.
+/// - `middlewares` - Middlewares which only apply to this route, like the ones of the `RouteGroup`
+///   it was registered in. They run after the global middlewares, right before the route handler.
+///
+/// The middlewares and access control policies of a route are compiled into a single chain, along
+/// with the list of all steps a request goes through including the global middlewares. Routes
+/// registered while the server is running are compiled when they are registered, all others are
+/// compiled again by `WebServer::listen` before the first connection is accepted, so that the
+/// middlewares and policies added after them are picked up.
+// ----- Route struct
+pub struct Route {
+    pub handler: RouteHandler,
+    pub options: RouteOptions,
+    pub middlewares: Vec<Arc<Middleware>>,
+    // the handler of a route registered with `WebServer::register_async_route`, awaited on the
+    // runtime by `WebServer::listen_async` instead of blocking a thread on `handler`
+    #[cfg(feature = "tokio")]
+    pub(crate) async_handler: Option<AsyncRouteHandler>,
+    // the number of middlewares at the start of `middlewares` which the route got from the
+    // `RouteGroup` it was registered in
+    group_middlewares: usize,
+    chain: OnceLock<CompiledChain>,
+}
+
+// the compiled chain of a route, see `WebRouter::chain`
+struct CompiledChain {
+    // the steps run after the global middlewares
+    steps: Box<[CompiledStep]>,
+    // all steps a request goes through, as listed by `WebRouter::routes`
+    listing: Box<[ChainStep]>,
+}
+
+// a step of the compiled chain of a route, with the policies already looked up by their names
+enum CompiledStep {
+    Middleware(Arc<Middleware>),
+    Policy(String, Option<Arc<policy::Policy>>),
+}
+
+/// A step a request matching a route goes through, in the order they run, as listed by
+/// `WebRouter::routes`.
+///
+/// # Variants
+///
+/// - `GlobalMiddleware` - The middleware registered with `WebServer::middleware` at the index.
+/// - `GroupMiddleware` - The middleware of the `RouteGroup` of the route at the index.
+/// - `RouteMiddleware` - The middleware attached with `RouteBuilder::middleware` at the index.
+/// - `Policy` - The access control policy with the name, see `RouteBuilder::require_policy`.
+/// - `Handler` - The route handler.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ChainStep {
+    GlobalMiddleware(usize),
+    GroupMiddleware(usize),
+    RouteMiddleware(usize),
+    Policy(String),
+    Handler,
+}
+
+impl fmt::Display for ChainStep {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ChainStep::GlobalMiddleware(index) => write!(f, "global middleware {}", index),
+            ChainStep::GroupMiddleware(index) => write!(f, "group middleware {}", index),
+            ChainStep::RouteMiddleware(index) => write!(f, "route middleware {}", index),
+            ChainStep::Policy(name) => write!(f, "policy {}", name),
+            ChainStep::Handler => write!(f, "handler"),
+        }
+    }
+}
+
+impl Route {
+    /// Creates a new `Route` with the default options and no middlewares, to be configured with a
+    /// `RouteBuilder` before it is registered.
+    pub fn new<F, R>(handler: F) -> Route
+    where
+        F: Fn(context::Context) -> R + 'static + Send + Sync,
+        R: response::IntoResponse,
+    {
+        return Route {
+            handler: Box::new(move |c| handler(c).into_result()),
+            options: RouteOptions::default(),
+            middlewares: vec![],
+            #[cfg(feature = "tokio")]
+            async_handler: None,
+            group_middlewares: 0,
+            chain: OnceLock::new(),
+        };
+    }
+
+    // whether the route has an async handler, see `WebServer::register_async_route`
+    pub(crate) fn is_async(&self) -> bool {
+        #[cfg(feature = "tokio")]
+        return self.async_handler.is_some();
+        #[cfg(not(feature = "tokio"))]
+        return false;
+    }
+
+    // lists the steps of the route after the global middlewares, in the order they run
+    fn steps(&self) -> Vec<ChainStep> {
+        let mut steps: Vec<ChainStep> = (0..self.middlewares.len())
+            .map(|index| match index < self.group_middlewares {
+                true => ChainStep::GroupMiddleware(index),
+                false => ChainStep::RouteMiddleware(index - self.group_middlewares),
+            })
+            .collect();
+        steps.extend(self.options.policies.iter().cloned().map(ChainStep::Policy));
+        steps.push(ChainStep::Handler);
+        return steps;
+    }
+}
+
+impl fmt::Debug for Route {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Route")
+            .field(
+                "handler",
+                &"Box<dyn Fn(context::Context) -> response::Response + Send + Sync + 'static>",
+            )
+            .field("options", &self.options)
+            .field(
+                "middlewares",
+                &format!(
+                    "[Arc<Box<dyn Fn(context::Context) -> context::Context + Send + Sync + 'static>>; {}]",
+                    self.middlewares.len()
+                ),
+            )
+            .field("group_middlewares", &self.group_middlewares)
+            .field("async", &self.is_async())
+            .field("compiled", &self.chain.get().is_some())
+            .finish()
+    }
+}
+
+/// A builder which customizes a route right after it's registration.
+///
+/// It is returned by the route registration methods of `WebServer`(like `WebServer::get`), so route
+/// options can be chained onto the registration. If the registration failed, every method is a
+/// no-op.
+///
+/// # Examples
+///
+/// ```rust,no_run
+/// # use browzer_web::WebServer;
+/// let mut server = WebServer::new("127.0.0.1:8080".to_string(), 4);
+///
+/// server
+///     .get("/events", |mut ctx| {
+///         return ctx.send_string(browzer_web::utils::HttpStatusCode::OK, "data: hello\n\n");
+///     })
+///     .no_compress();
+/// ```
+// ----- RouteBuilder struct
+#[derive(Debug)]
+pub struct RouteBuilder<'a> {
+    route: Option<&'a mut Route>,
+}
+
+impl<'a> RouteBuilder<'a> {
+    /// Creates a new `RouteBuilder` for the given route, `None` creates a no-op builder.
+    pub fn new(route: Option<&'a mut Route>) -> RouteBuilder<'a> {
+        return RouteBuilder { route };
+    }
+
+    // unwraps the builder of a newly registered route for the methods named after HTTP methods,
+    // printing why the route couldn't be registered and returning a no-op builder instead
+    pub(crate) fn or_log(
+        registered: Result<RouteBuilder<'a>, error::WebRouterError>,
+    ) -> RouteBuilder<'a> {
+        match registered {
+            Ok(builder) => return builder,
+            Err(e) => {
+                eprintln!("{}", e);
+                return RouteBuilder::new(None);
+            }
+        }
+    }
+
+    /// Opts the route out of response compression.
+    pub fn no_compress(mut self) -> RouteBuilder<'a> {
+        if let Some(ref mut route) = self.route {
+            route.options.compress = false;
+        }
+        return self;
+    }
+
+    /// Attaches a middleware which only runs for requests matching this route.
+    ///
+    /// Route middlewares run after the global middlewares(and the ones of the `RouteGroup` the route
+    /// was registered in), in the order they were attached, right before the access control
+    /// policies and the route handler.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// # use browzer_web::WebServer;
+    /// let mut server = WebServer::new("127.0.0.1:8080".to_string(), 4);
+    ///
+    /// server
+    ///     .get("/admin", |mut ctx| {
+    ///         return ctx.send_string(browzer_web::utils::HttpStatusCode::OK, "Admin area");
+    ///     })
+    ///     .middleware(|mut ctx| {
+    ///         // authenticate only this route
+    ///         return ctx;
+    ///     })
+    ///     .middleware(|mut ctx| {
+    ///         // runs after the first one
+    ///         return ctx;
+    ///     });
+    /// ```
+    pub fn middleware<F>(mut self, middleware_func: F) -> RouteBuilder<'a>
+    where
+        F: Fn(context::Context) -> context::Context + 'static + Send + Sync,
+    {
+        if let Some(ref mut route) = self.route {
+            route.middlewares.push(Arc::new(Box::new(middleware_func)));
+            route.chain.take();
+        }
+        return self;
+    }
+
+    /// Requires the access control policy with the given name to allow a request before the route
+    /// handler is run, requests denied by the policy get a `403 Forbidden` response.
+    ///
+    /// The policy itself is registered using `WebServer::policy`, requiring a policy which is never
+    /// registered denies every request.
+    pub fn require_policy(mut self, name: &str) -> RouteBuilder<'a> {
+        if let Some(ref mut route) = self.route {
+            route.options.policies.push(name.to_string());
+            route.chain.take();
+        }
+        return self;
+    }
+
+    /// Overrides the server's `request_timeout` for this route, like a tiny timeout for a health
+    /// check.
+    ///
+    /// See `WebServer::request_timeout` for how the timeout is enforced.
+    pub fn timeout(mut self, timeout: Duration) -> RouteBuilder<'a> {
+        if let Some(ref mut route) = self.route {
+            route.options.timeout = Some(timeout);
+        }
+        return self;
+    }
+
+    /// Overrides the server's `body_limit` for this route, like a larger limit for an upload
+    /// endpoint.
+    ///
+    /// Requests with a larger `Content-Length` are answered with `413 Payload Too Large` before
+    /// their body is read.
+    pub fn body_limit(mut self, bytes: usize) -> RouteBuilder<'a> {
+        if let Some(ref mut route) = self.route {
+            route.options.body_limit = Some(bytes);
+        }
+        return self;
+    }
+
+    /// Overrides the server's `memory_budget` for this route, like a tight budget for an endpoint
+    /// exposed to untrusted clients.
+    ///
+    /// See `WebServer::set_memory_budget` for how the budget is enforced.
+    pub fn memory_budget(mut self, bytes: usize) -> RouteBuilder<'a> {
+        if let Some(ref mut route) = self.route {
+            route.options.memory_budget = Some(bytes);
+        }
+        return self;
+    }
+
+    /// Streams the body of requests to the handler while it arrives, instead of reading it before
+    /// the handler runs, like for a handler forwarding large uploads elsewhere.
+    ///
+    /// The handler reads the body from `Request::take_body_stream`, the body is neither in
+    /// `Request::body` nor in `Request::raw_body` then. The `body_limit` still applies to the
+    /// announced length of the body, while the `memory_budget` doesn't count it since it isn't
+    /// buffered. The body stream reads from the connection while the handler runs, it can't be
+    /// read anymore once the handler returned.
+    pub fn stream_body(mut self) -> RouteBuilder<'a> {
+        if let Some(ref mut route) = self.route {
+            route.options.stream_body = true;
+        }
+        return self;
+    }
+
+    /// Overrides the server's CORS policy(see `WebServer::cors`) for this route, like a public
+    /// endpoint which any origin may call on an otherwise locked down server.
+    ///
+    /// The policy of the route replaces the server's policy as a whole, for the actual requests
+    /// as well as for the preflight requests of the route.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// # use browzer_web::WebServer;
+    /// use browzer_web::cors::Cors;
+    ///
+    /// let mut server = WebServer::new("127.0.0.1:8080".to_string(), 4);
+    /// server.cors(Cors::new().allow_origin("https://app.example.com"));
+    ///
+    /// server
+    ///     .get("/public/stats", |mut ctx| {
+    ///         return ctx.send_string(browzer_web::utils::HttpStatusCode::OK, "{}");
+    ///     })
+    ///     .cors(Cors::new().allow_any_origin());
+    /// ```
+    pub fn cors(mut self, policy: cors::Cors) -> RouteBuilder<'a> {
+        if let Some(ref mut route) = self.route {
+            route.options.cors = Some(policy);
+        }
+        return self;
+    }
+
+    /// Adds a header to every response of this route which doesn't set it itself, like a cache
+    /// policy for an endpoint whose responses never change.
+    ///
+    /// Route headers take precedence over the server's default headers, see
+    /// `WebServer::default_header` for when they are added.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// # use browzer_web::WebServer;
+    /// let mut server = WebServer::new("127.0.0.1:8080".to_string(), 4);
+    ///
+    /// server
+    ///     .get("/logo.svg", |mut ctx| {
+    ///         return ctx.send_string(browzer_web::utils::HttpStatusCode::OK, "<svg/>");
+    ///     })
+    ///     .header("Cache-Control", "public, max-age=31536000, immutable");
+    /// ```
+    pub fn header(mut self, name: &str, value: &str) -> RouteBuilder<'a> {
+        if let Some(ref mut route) = self.route {
+            route
+                .options
+                .headers
+                .push((name.to_string(), value.to_string()));
+        }
+        return self;
+    }
+
+    /// Documents the route, the description is listed by the route help endpoint(see
+    /// `WebServer::enable_route_help`).
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// # use browzer_web::WebServer;
+    /// let mut server = WebServer::new("127.0.0.1:8080".to_string(), 4);
+    ///
+    /// server
+    ///     .post("/users", |mut ctx| {
+    ///         return ctx.send_string(browzer_web::utils::HttpStatusCode::Created, "Created");
+    ///     })
+    ///     .doc("Creates a user; body: {\"name\": string}");
+    /// ```
+    pub fn doc(mut self, doc: &str) -> RouteBuilder<'a> {
+        if let Some(ref mut route) = self.route {
+            route.options.doc = Some(doc.to_string());
+        }
+        return self;
+    }
+}
+
+/// A group of routes sharing a common path prefix, middlewares and route options(like access
+/// control policies, timeouts and body limits).
+///
+/// It is returned by `WebServer::group`. Every route registered through the group is registered
+/// under the prefix of the group, and gets the middlewares and options attached to the group so
+/// far, so attach those before registering the routes. Group middlewares run after the global
+/// middlewares and only for requests matching a route of the group.
+///
+/// If the router couldn't be borrowed, every method is a no-op.
+///
+/// # Examples
+///
+/// ```rust,no_run
+/// # use browzer_web::WebServer;
+/// let mut server = WebServer::new("127.0.0.1:8080".to_string(), 4);
+///
+/// let mut api = server.group("/api/v1");
+/// api.middleware(|mut ctx| {
+///     // only runs for routes under `/api/v1`
+///     return ctx;
+/// });
+/// api.get("/users", |mut ctx| {
+///     return ctx.send_string(browzer_web::utils::HttpStatusCode::OK, "[]");
+/// });
+///
+/// let mut admin = api.group("/admin");
+/// admin.require_policy("admin").timeout(std::time::Duration::from_secs(60));
+/// admin.delete("/users/:id", |mut ctx| {
+///     return ctx.send_string(browzer_web::utils::HttpStatusCode::OK, "deleted");
+/// });
+/// ```
+// ----- RouteGroup struct
+pub struct RouteGroup<'a> {
+    router: Option<&'a mut WebRouter>,
+    prefix: String,
+    middlewares: Vec<Arc<Middleware>>,
+    options: RouteOptions,
+}
+
+impl fmt::Debug for RouteGroup<'_> {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        f.debug_struct("WebRouter")
-            .field("routes", &"HashMap<String, HashMap<String, Box<dyn Fn(context::Context) -> response::Response + Send + Sync + 'static>>>")
-            .field("middlewares", &"Vec<Box<dyn Fn(context::Context) -> context::Context + 'static + Send + Sync>>")
+        f.debug_struct("RouteGroup")
+            .field("prefix", &self.prefix)
+            .field(
+                "middlewares",
+                &format!(
+                    "[Arc<Box<dyn Fn(context::Context) -> context::Context + Send + Sync + 'static>>; {}]",
+                    self.middlewares.len()
+                ),
+            )
+            .field("options", &self.options)
             .finish()
     }
-}
+}
+
+impl<'a> RouteGroup<'a> {
+    /// Creates a new `RouteGroup` registering it's routes in the given router under the given
+    /// prefix, `None` creates a no-op group.
+    pub fn new(router: Option<&'a mut WebRouter>, prefix: &str) -> RouteGroup<'a> {
+        return RouteGroup {
+            router,
+            prefix: prefix.trim_end_matches('/').to_string(),
+            middlewares: vec![],
+            options: RouteOptions::default(),
+        };
+    }
+
+    /// Creates a nested group, whose prefix is appended to the prefix of this group and which
+    /// inherits the middlewares and options attached to this group so far.
+    pub fn group(&mut self, prefix: &str) -> RouteGroup<'_> {
+        return RouteGroup {
+            router: self.router.as_deref_mut(),
+            prefix: join_paths(&self.prefix, prefix)
+                .trim_end_matches('/')
+                .to_string(),
+            middlewares: self.middlewares.clone(),
+            options: self.options.clone(),
+        };
+    }
+
+    /// Attaches a middleware to every route registered through the group from now on.
+    pub fn middleware<F>(&mut self, middleware_func: F) -> &mut RouteGroup<'a>
+    where
+        F: Fn(context::Context) -> context::Context + 'static + Send + Sync,
+    {
+        self.middlewares.push(Arc::new(Box::new(middleware_func)));
+        return self;
+    }
+
+    /// Requires the access control policy with the given name for every route registered through
+    /// the group from now on, see `RouteBuilder::require_policy`.
+    pub fn require_policy(&mut self, name: &str) -> &mut RouteGroup<'a> {
+        self.options.policies.push(name.to_string());
+        return self;
+    }
+
+    /// Overrides the server's `request_timeout` for every route registered through the group from
+    /// now on, see `RouteBuilder::timeout`.
+    pub fn timeout(&mut self, timeout: Duration) -> &mut RouteGroup<'a> {
+        self.options.timeout = Some(timeout);
+        return self;
+    }
+
+    /// Overrides the server's `body_limit` for every route registered through the group from now
+    /// on, see `RouteBuilder::body_limit`.
+    pub fn body_limit(&mut self, bytes: usize) -> &mut RouteGroup<'a> {
+        self.options.body_limit = Some(bytes);
+        return self;
+    }
+
+    /// Overrides the server's `memory_budget` for every route registered through the group from
+    /// now on, see `RouteBuilder::memory_budget`.
+    pub fn memory_budget(&mut self, bytes: usize) -> &mut RouteGroup<'a> {
+        self.options.memory_budget = Some(bytes);
+        return self;
+    }
+
+    /// Overrides the server's CORS policy for every route registered through the group from now
+    /// on, see `RouteBuilder::cors`.
+    pub fn cors(&mut self, policy: cors::Cors) -> &mut RouteGroup<'a> {
+        self.options.cors = Some(policy);
+        return self;
+    }
+
+    /// Adds a header to every response of the routes registered through the group from now on,
+    /// see `RouteBuilder::header`.
+    pub fn header(&mut self, name: &str, value: &str) -> &mut RouteGroup<'a> {
+        self.options
+            .headers
+            .push((name.to_string(), value.to_string()));
+        return self;
+    }
+
+    /// Registers a route for HTTP GET requests under the prefix of the group.
+    pub fn get<F>(&mut self, path: &str, handler: F) -> RouteBuilder<'_>
+    where
+        F: Fn(context::Context) -> response::Response + 'static + Send + Sync,
+    {
+        return RouteBuilder::or_log(self.register_route(path, utils::HttpMethod::GET, handler));
+    }
+
+    /// Registers a route for HTTP POST requests under the prefix of the group.
+    pub fn post<F>(&mut self, path: &str, handler: F) -> RouteBuilder<'_>
+    where
+        F: Fn(context::Context) -> response::Response + 'static + Send + Sync,
+    {
+        return RouteBuilder::or_log(self.register_route(path, utils::HttpMethod::POST, handler));
+    }
+
+    /// Registers a route for HTTP PUT requests under the prefix of the group.
+    pub fn put<F>(&mut self, path: &str, handler: F) -> RouteBuilder<'_>
+    where
+        F: Fn(context::Context) -> response::Response + 'static + Send + Sync,
+    {
+        return RouteBuilder::or_log(self.register_route(path, utils::HttpMethod::PUT, handler));
+    }
+
+    /// Registers a route for HTTP PATCH requests under the prefix of the group.
+    pub fn patch<F>(&mut self, path: &str, handler: F) -> RouteBuilder<'_>
+    where
+        F: Fn(context::Context) -> response::Response + 'static + Send + Sync,
+    {
+        return RouteBuilder::or_log(self.register_route(path, utils::HttpMethod::PATCH, handler));
+    }
+
+    /// Registers a route for HTTP DELETE requests under the prefix of the group.
+    pub fn delete<F>(&mut self, path: &str, handler: F) -> RouteBuilder<'_>
+    where
+        F: Fn(context::Context) -> response::Response + 'static + Send + Sync,
+    {
+        return RouteBuilder::or_log(self.register_route(path, utils::HttpMethod::DELETE, handler));
+    }
+
+    /// Registers a route with a handler which can fail under the prefix of the group, see
+    /// `WebServer::register_route`.
+    ///
+    /// # Returns
+    ///
+    /// - `Result<RouteBuilder, WebRouterError>` - A builder customizing the route, or
+    ///   `WebRouterError::Listening` for a no-op group(see `RouteGroup::new`) and an error if the
+    ///   path couldn't be formatted.
+    pub fn register_route<F, R>(
+        &mut self,
+        path: &str,
+        method: utils::HttpMethod,
+        handler: F,
+    ) -> Result<RouteBuilder<'_>, error::WebRouterError>
+    where
+        F: Fn(context::Context) -> R + 'static + Send + Sync,
+        R: response::IntoResponse,
+    {
+        let router = match self.router {
+            Some(ref mut router) => router,
+            None => return Err(error::WebRouterError::Listening),
+        };
+        let route = router.add(join_paths(&self.prefix, path), method, handler)?;
+        route.middlewares = self.middlewares.clone();
+        route.group_middlewares = self.middlewares.len();
+        route.options = self.options.clone();
+        return Ok(RouteBuilder::new(Some(route)));
+    }
+}
+
+// joins a group prefix and a route path with exactly one slash in between
+fn join_paths(prefix: &str, path: &str) -> String {
+    return format!(
+        "{}/{}",
+        prefix.trim_end_matches('/'),
+        path.trim_start_matches('/')
+    );
+}
+
+/// A handle registering routes while the server is listening, see `WebServer::route_registry`.
+///
+/// Routes registered with the `WebServer` itself(`WebServer::get`, ...) have to be registered
+/// before `listen` is called, while the registry works only as long as the server is listening.
+/// It can be cloned and moved to other threads, or into route handlers.
+///
+/// Every method returns `WebRouterError::NotListening` if the server isn't listening.
+///
+/// # Examples
+///
+/// ```rust,no_run
+/// use browzer_web::{utils::HttpStatusCode, WebServer};
+///
+/// let mut server = WebServer::new("127.0.0.1:8080".to_string(), 4);
+/// let registry = server.route_registry();
+/// server.post("/plugins/:name", move |mut c| {
+///     let name = c.params.get("name").cloned().unwrap_or_default();
+///     let path = format!("/plugins/{}/status", name);
+///     return match registry.get(&path, |mut c| c.send_string(HttpStatusCode::OK, "running")) {
+///         Ok(()) => c.send_string(HttpStatusCode::Created, &path),
+///         Err(e) => c.send_string(HttpStatusCode::InternalServerError, &e.to_string()),
+///     };
+/// });
+/// server.listen();
+/// ```
+// ----- RouteRegistry struct
+#[derive(Debug, Clone, Default)]
+pub struct RouteRegistry {
+    router: Arc<Mutex<Option<Arc<WebRouter>>>>,
+}
+
+impl RouteRegistry {
+    /// Creates a new `RouteRegistry`, which has no router to register routes with until it's
+    /// attached to one.
+    pub fn new() -> RouteRegistry {
+        return RouteRegistry::default();
+    }
+
+    /// Attaches the registry(and all it's clones) to a router, or detaches it with `None`.
+    pub fn attach(&self, router: Option<Arc<WebRouter>>) {
+        *self
+            .router
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner()) = router;
+    }
+
+    /// Returns whether the registry is attached to a router.
+    pub fn is_attached(&self) -> bool {
+        return self
+            .router
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .is_some();
+    }
+
+    /// Registers a route, letting `configure` set it up through a `RouteBuilder` before it can be
+    /// requested.
+    ///
+    /// # Arguments
+    ///
+    /// - `path` - The route path, with the same syntax as `WebServer::get`.
+    /// - `method` - The HTTP method of the route.
+    /// - `handler` - The route handler.
+    /// - `configure` - Configures the route, like the `RouteBuilder` returned by `WebServer::get`.
+    ///
+    /// # Returns
+    ///
+    /// - `Result<(), WebRouterError>` - `NotListening` if the registry isn't attached to a router,
+    ///   or an error if the path couldn't be formatted.
+    pub fn route<F, R, C>(
+        &self,
+        path: &str,
+        method: utils::HttpMethod,
+        handler: F,
+        configure: C,
+    ) -> Result<(), error::WebRouterError>
+    where
+        F: Fn(context::Context) -> R + 'static + Send + Sync,
+        R: response::IntoResponse,
+        C: FnOnce(RouteBuilder<'_>) -> RouteBuilder<'_>,
+    {
+        // the router is cloned out of the lock, so other registrations don't wait for this one
+        let router = self
+            .router
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .clone()
+            .ok_or(error::WebRouterError::NotListening)?;
+        let mut route = Route::new(handler);
+        configure(RouteBuilder::new(Some(&mut route)));
+        return router.insert_route(path.to_string(), method, route);
+    }
+
+    /// Registers a GET route, see `RouteRegistry::route`.
+    pub fn get<F>(&self, path: &str, handler: F) -> Result<(), error::WebRouterError>
+    where
+        F: Fn(context::Context) -> response::Response + 'static + Send + Sync,
+    {
+        return self.route(path, utils::HttpMethod::GET, handler, |route| route);
+    }
+
+    /// Registers a POST route, see `RouteRegistry::route`.
+    pub fn post<F>(&self, path: &str, handler: F) -> Result<(), error::WebRouterError>
+    where
+        F: Fn(context::Context) -> response::Response + 'static + Send + Sync,
+    {
+        return self.route(path, utils::HttpMethod::POST, handler, |route| route);
+    }
+
+    /// Registers a PUT route, see `RouteRegistry::route`.
+    pub fn put<F>(&self, path: &str, handler: F) -> Result<(), error::WebRouterError>
+    where
+        F: Fn(context::Context) -> response::Response + 'static + Send + Sync,
+    {
+        return self.route(path, utils::HttpMethod::PUT, handler, |route| route);
+    }
+
+    /// Registers a PATCH route, see `RouteRegistry::route`.
+    pub fn patch<F>(&self, path: &str, handler: F) -> Result<(), error::WebRouterError>
+    where
+        F: Fn(context::Context) -> response::Response + 'static + Send + Sync,
+    {
+        return self.route(path, utils::HttpMethod::PATCH, handler, |route| route);
+    }
+
+    /// Registers a DELETE route, see `RouteRegistry::route`.
+    pub fn delete<F>(&self, path: &str, handler: F) -> Result<(), error::WebRouterError>
+    where
+        F: Fn(context::Context) -> response::Response + 'static + Send + Sync,
+    {
+        return self.route(path, utils::HttpMethod::DELETE, handler, |route| route);
+    }
+}
+
+/// Manages the routing logic for the web framework.
+///
+/// The `WebRouter` struct holds the registered routes and matches incoming requests to the appropriate route handler.
+///
+/// # Fields
+///
+/// - `routes` - A `RouteTree` of the registered route paths, mapping each of them to a `HashMap` of HTTP methods and their corresponding `Route`.
+///   It is behind a lock so routes can be registered while the router serves requests, see
+///   `WebRouter::insert_route`.
+/// - `middlewares` - A `Vector` representing a list of all the registered middlewares
+/// - `after_middlewares` - A `Vector` of the registered after-response middlewares, which
+///   transform every response(including framework-generated errors) after the route handler
+/// - `default_headers` - Headers added to every response which doesn't set them itself, after the
+///   after-response middlewares ran, see `WebServer::default_header`
+/// - `problem_details` - An optional `ProblemConfig`, when set all framework-generated error
+///   responses are sent as `application/problem+json` bodies
+/// - `policies` - A `HashMap` mapping names of access control policies to the policy functions
+/// - `policy_audit` - An optional hook which is called with every access control policy decision
+/// - `allowed_hosts` - An optional allowlist of host names, when set requests for any other host
+///   are rejected before reaching middlewares or handlers
+/// - `compression` - An optional `CompressionConfig`, when set eligible responses are compressed
+///   after the after-response middlewares ran(requires the `compression` feature)
+/// - `not_found_cache` - An optional `NotFoundCache` of request paths which matched no route,
+///   letting repeated requests for them skip the route tree
+/// - `recorder` - An optional `Recorder`, when set a sample of the requests the router answers is
+///   recorded with their status codes, for replaying them later
+/// - `sessions` - An optional `Sessions` configuration, when set every request gets it's session
+///   loaded before the middlewares run and stored once it's response was generated
+/// - `route_help` - An optional path under which a listing of all registered routes is served, see
+///   `WebServer::enable_route_help`
+/// - `cors` - An optional `Cors` policy, when set preflight requests are answered before the
+///   middlewares run and the responses to allowed origins get the `Access-Control-*` headers
+/// - `canonical_origin` - An optional `CanonicalOrigin`, when set requests for any other scheme or
+///   host name are redirected to it before reaching middlewares or handlers
+/// - `tls` - Whether all requests the router serves arrive over HTTPS, otherwise the scheme is the
+///   one of each request(see `Request::tls`)
+/// - `request_logger` - An optional `RequestLogger`, when set every request the router answers is
+///   logged once it's final response was generated
+/// - `request_ids` - Optional `RequestIds` settings, when set every request gets an ID before the
+///   middlewares run, which is echoed in the final response
+/// - `response_cache` - An optional `ResponseCache`, when set the responses for it's paths are
+///   served from the cache once the middlewares and policies of their route ran
+/// - `not_found_handler` - An optional `ErrorHandler` generating the `404 Not Found` responses
+/// - `method_not_allowed_handler` - An optional `ErrorHandler` generating the
+///   `405 Method Not Allowed` responses
+/// - `error_handler` - An optional `ErrorHandler` generating the responses for all other errors
+///   detected by the framework
+/// - `page_templates` - Optional `PageTemplates`, when set error responses are rendered as HTML
+///   pages and the route listing uses them instead of the default layout
+/// - `background_pool` - The `BackgroundPool` running the tasks handlers spawn with
+///   `Context::spawn_scoped`
+/// - `jobs` - An optional `Jobs` configuration, when set handlers can start background jobs with
+///   `Context::accept_async`
+/// - `trusted_proxies` - Optional `TrustedProxies`, whose forwarding headers `Context::client_ip`
+///   believes
+/// - `sampler` - An optional `Sampler`, when set a fraction of the requests is profiled phase by
+///   phase
+/// - `rate_limit` - An optional `RateLimit`, when set clients exceeding it's quota get a `429 Too
+///   Many Requests` response before any middleware runs
+/// - `content_digests` - An optional `DigestSet`, when set files sent with `Context::send_file`
+///   carry digests of their contents and uploads are verified against the digests clients send
+/// - `templates` - Optional `Templates`, which handlers render with `Context::render`
+/// - `metrics` - Optional `Metrics`, when set every request the router answers is counted by it's
+///   route path, method and status code once it's final response was generated
+// ----- WebRouter struct
+pub struct WebRouter {
+    pub routes: RwLock<RouteTree>,
+    pub middlewares: Vec<Middleware>,
+    pub after_middlewares: Vec<AfterMiddleware>,
+    pub default_headers: Vec<(String, String)>,
+    pub problem_details: Option<problem::ProblemConfig>,
+    pub policies: HashMap<String, Arc<policy::Policy>>,
+    pub policy_audit: Option<policy::AuditHook>,
+    pub allowed_hosts: Option<Vec<String>>,
+    #[cfg(feature = "compression")]
+    pub compression: Option<compression::CompressionConfig>,
+    pub not_found_cache: Option<NotFoundCache>,
+    pub recorder: Option<replay::Recorder>,
+    pub sessions: Option<sessions::Sessions>,
+    pub route_help: Option<String>,
+    pub cors: Option<cors::Cors>,
+    pub canonical_origin: Option<canonical::CanonicalOrigin>,
+    pub tls: bool,
+    pub request_logger: Option<logger::RequestLogger>,
+    pub request_ids: Option<request_id::RequestIds>,
+    pub response_cache: Option<cache::ResponseCache>,
+    pub not_found_handler: Option<ErrorHandler>,
+    pub method_not_allowed_handler: Option<ErrorHandler>,
+    pub error_handler: Option<ErrorHandler>,
+    pub page_templates: Option<pages::PageTemplates>,
+    pub background_pool: Arc<tasks::BackgroundPool>,
+    pub jobs: Option<Arc<jobs::Jobs>>,
+    pub trusted_proxies: Option<Arc<forwarded::TrustedProxies>>,
+    pub sampler: Option<sampling::Sampler>,
+    pub rate_limit: Option<rate_limit::RateLimit>,
+    pub content_digests: Option<Arc<digest::DigestSet>>,
+    pub templates: Option<Arc<templates::Templates>>,
+    pub metrics: Option<Arc<metrics::Metrics>>,
+    // the router itself once it is shared with the connections, which handlers reach through
+    // their context to generate error responses(see `Context::error_response`)
+    shared: OnceLock<Weak<WebRouter>>,
+}
+
+impl fmt::Debug for WebRouter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut debug = f.debug_struct("WebRouter");
+        debug
+            .field("routes", &*self.route_table())
+            .field(
+                "middlewares",
+                &"Vec<Box<dyn Fn(context::Context) -> context::Context + 'static + Send + Sync>>",
+            )
+            .field(
+                "after_middlewares",
+                &"Vec<Box<dyn Fn(&request::Request, response::Response) -> response::Response + 'static + Send + Sync>>",
+            )
+            .field("default_headers", &self.default_headers)
+            .field("problem_details", &self.problem_details)
+            .field("policies", &self.policies.keys().collect::<Vec<_>>())
+            .field(
+                "policy_audit",
+                &"Option<Box<dyn Fn(&policy::PolicyDecision) + 'static + Send + Sync>>",
+            )
+            .field("allowed_hosts", &self.allowed_hosts)
+            .field("not_found_cache", &self.not_found_cache)
+            .field("recorder", &self.recorder)
+            .field("sessions", &self.sessions)
+            .field("route_help", &self.route_help)
+            .field("cors", &self.cors)
+            .field("canonical_origin", &self.canonical_origin)
+            .field("tls", &self.tls)
+            .field("request_logger", &self.request_logger)
+            .field("request_ids", &self.request_ids)
+            .field("response_cache", &self.response_cache)
+            .field(
+                "not_found_handler",
+                &"Option<Box<dyn Fn(utils::HttpStatusCode, &str) -> response::Response + 'static + Send + Sync>>",
+            )
+            .field(
+                "method_not_allowed_handler",
+                &"Option<Box<dyn Fn(utils::HttpStatusCode, &str) -> response::Response + 'static + Send + Sync>>",
+            )
+            .field(
+                "error_handler",
+                &"Option<Box<dyn Fn(utils::HttpStatusCode, &str) -> response::Response + 'static + Send + Sync>>",
+            )
+            .field("page_templates", &self.page_templates)
+            .field("background_pool", &self.background_pool)
+            .field("jobs", &self.jobs)
+            .field("trusted_proxies", &self.trusted_proxies)
+            .field("sampler", &self.sampler)
+            .field("metrics", &self.metrics)
+            .field("rate_limit", &self.rate_limit)
+            .field("content_digests", &self.content_digests)
+            .field("templates", &self.templates);
+        #[cfg(feature = "compression")]
+        debug.field("compression", &self.compression);
+        return debug.finish();
+    }
+}
+
+// default implementation for WebRouter struct
+impl Default for WebRouter {
+    fn default() -> Self {
+        return WebRouter::new();
+    }
+}
+
+impl WebRouter {
+    /// Creates a new `WebRouter` with an empty route map.
+    ///
+    /// # Returns
+    ///
+    /// - `WebRouter` - A new instance of `WebRouter`.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use browzer_web::router::WebRouter;
+    ///
+    /// let router = WebRouter::new();
+    ///
+    /// assert!(router.route_table().is_empty());
+    /// ```
+    pub fn new() -> WebRouter {
+        return WebRouter {
+            routes: RwLock::new(RouteTree::new()),
+            middlewares: vec![],
+            after_middlewares: vec![],
+            default_headers: vec![],
+            problem_details: None,
+            policies: HashMap::new(),
+            policy_audit: None,
+            allowed_hosts: None,
+            #[cfg(feature = "compression")]
+            compression: None,
+            not_found_cache: None,
+            recorder: None,
+            sessions: None,
+            route_help: None,
+            cors: None,
+            canonical_origin: None,
+            tls: false,
+            request_logger: None,
+            request_ids: None,
+            response_cache: None,
+            not_found_handler: None,
+            method_not_allowed_handler: None,
+            error_handler: None,
+            page_templates: None,
+            background_pool: Arc::new(tasks::BackgroundPool::default()),
+            jobs: None,
+            trusted_proxies: None,
+            sampler: None,
+            metrics: None,
+            rate_limit: None,
+            content_digests: None,
+            templates: None,
+            shared: OnceLock::new(),
+        };
+    }
+
+    // lets handlers reach the router through their context, see `Context::error_response`. Called
+    // once the router is shared with the connections, it can't be changed through `Arc::get_mut`
+    // anymore afterwards.
+    pub(crate) fn share(router: &Arc<WebRouter>) {
+        let _ = router.shared.set(Arc::downgrade(router));
+    }
+
+    /// Adds a new route to the `routes` tree using route path, method and route handler as input
+    ///
+    /// Route paths may contain `:name` segments matching any single path segment, and end with a
+    /// `*name` segment matching the rest of the path, see `RouteTree` for how they are matched.
+    ///
+    /// # Arguments
+    ///
+    /// - `path` - The route path as a `String`.
+    /// - `method` - The HTTP method for the route as an `HttpMethod`.
+    /// - `handler` - The closure function for the route, returning a `Response` or anything else
+    ///   implementing `IntoResponse`(like a `Result<Response, ErrorResponse>`).
+    ///
+    /// # Returns
+    ///
+    /// - `Result<&mut Route, WebRouterError>` - A Result containing the newly registered `Route`, or
+    ///   a `WebRouterError` if there is any error while formatting the path using
+    ///   `format_path_by_slashes` utility function
+    pub fn add<F, R>(
+        &mut self,
+        mut path: String,
+        method: utils::HttpMethod,
+        handler: F,
+    ) -> Result<&mut Route, error::WebRouterError>
+    where
+        F: Fn(context::Context) -> R + 'static + Send + Sync,
+        R: response::IntoResponse,
+    {
+        path = match utils::format_path_by_slashes(path) {
+            Ok(formatted_path) => formatted_path,
+            Err(e) => {
+                return Err(e);
+            }
+        };
+        let route = self
+            .route_table_mut()
+            .insert(&path)
+            .entry(method.to_string())
+            .insert_entry(Arc::new(Route::new(handler)))
+            .into_mut();
+        // the route was just created, so nothing else holds it
+        return Arc::get_mut(route).ok_or(error::WebRouterError::RouteInUse(path));
+    }
+
+    /// Registers a route while the router may be serving requests, replacing the route registered
+    /// for the same route path and method.
+    ///
+    /// The route is configured(see `Route::new` and `RouteBuilder`) before it's registration, so
+    /// no request can reach it before it's middlewares and policies are in place. Requests already
+    /// dispatched to a replaced route finish with it. The paths remembered by the
+    /// `not_found_cache` are forgotten, since they may match the new route, and so are the
+    /// responses stored in the `response_cache`, which may have been generated by the route it
+    /// replaces(or shadows).
+    ///
+    /// # Arguments
+    ///
+    /// - `path` - The route path as a `String`.
+    /// - `method` - The HTTP method for the route as an `HttpMethod`.
+    /// - `route` - The configured `Route`.
+    ///
+    /// # Returns
+    ///
+    /// - `Result<(), WebRouterError>` - An error if the path couldn't be formatted using
+    ///   `format_path_by_slashes`.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use browzer_web::{request::Request, router::{Route, RouteBuilder, WebRouter}};
+    /// use browzer_web::utils::{HttpMethod, HttpStatusCode};
+    /// use std::sync::Arc;
+    ///
+    /// let router = Arc::new(WebRouter::new());
+    ///
+    /// let mut route = Route::new(|mut c| c.send_string(HttpStatusCode::OK, "late"));
+    /// RouteBuilder::new(Some(&mut route)).doc("Registered while serving");
+    /// router.insert_route("/late".to_string(), HttpMethod::GET, route).unwrap();
+    ///
+    /// let mut request = Request::default();
+    /// request.path = "/late".to_string();
+    /// assert_eq!(router.handle_request(request).unwrap().body, "late");
+    /// ```
+    pub fn insert_route(
+        &self,
+        path: String,
+        method: utils::HttpMethod,
+        route: Route,
+    ) -> Result<(), error::WebRouterError> {
+        let path = utils::format_path_by_slashes(path)?;
+        // the chain is compiled before the route can be requested
+        self.chain(&route);
+        let mut routes = self
+            .routes
+            .write()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        routes
+            .insert(&path)
+            .insert(method.to_string(), Arc::new(route));
+        // cleared while the tree is locked, so no request can remember a path it didn't match(or
+        // the response of the route it matched) before the route was registered
+        if let Some(ref cache) = self.not_found_cache {
+            cache.clear();
+        }
+        if let Some(ref cache) = self.response_cache {
+            cache.purge_all();
+        }
+        return Ok(());
+    }
+
+    /// Returns the route tree, locked for reading. Routes registered with
+    /// `WebRouter::insert_route` wait until the lock is released, so don't hold on to it.
+    pub fn route_table(&self) -> RwLockReadGuard<'_, RouteTree> {
+        return self
+            .routes
+            .read()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+    }
+
+    // returns the route tree for registering routes, which needs no locking while the router is
+    // borrowed mutably
+    fn route_table_mut(&mut self) -> &mut RouteTree {
+        return self
+            .routes
+            .get_mut()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+    }
+
+    /// Appends a new middleware to the `middlewares` vector
+    ///
+    /// # Arguments
+    ///
+    /// - `middleware_func` - A closure function representing the middleware handler
+    pub fn add_middleware<F>(&mut self, middleware_func: F)
+    where
+        F: Fn(context::Context) -> context::Context + 'static + Send + Sync,
+    {
+        self.middlewares.push(Box::new(middleware_func));
+    }
+
+    /// Appends a new after-response middleware to the `after_middlewares` vector
+    ///
+    /// # Arguments
+    ///
+    /// - `middleware_func` - A closure function receiving the request(without it's body) and the
+    ///   response, returning the transformed response
+    pub fn add_after_middleware<F>(&mut self, middleware_func: F)
+    where
+        F: Fn(&request::Request, response::Response) -> response::Response + 'static + Send + Sync,
+    {
+        self.after_middlewares.push(Box::new(middleware_func));
+    }
+
+    /// Handles an incoming request, apply middlewares and generates a response.
+    ///
+    /// This function works in three parts:
+    /// 1. It applies all the middlewares from the `middlewares` vector
+    /// 2. handle response generation from request by first finding the user-registered routes
+    ///    which match the request's path(it will be hashmap) in the `routes` tree, then using that
+    ///    hashmap to get the route which matches request's method and then finaly using that route's
+    ///    handler function to generate the response for the request by providing a new `Context` with
+    ///    the request as input to the handler function
+    /// 3. It applies all the after-response middlewares from the `after_middlewares` vector to the
+    ///    generated response, whether it came from a route handler or from the framework itself
+    /// 4. It post-processes the response like every other response of the server, adding the
+    ///    default headers of the matched route(see `RouteBuilder::header`) and the
+    ///    `default_headers` of the router which the response doesn't have yet
+    ///
+    /// With a `compression` config set, the final response is compressed afterwards if it is
+    /// eligible, so that middlewares always see the plain body.
+    ///
+    /// With the `tracing` feature enabled, all of this runs in a `request` span at the `INFO`
+    /// level, so the events of middlewares and handlers belong to their request. The span has the
+    /// `method`, `path`(without the query string), `remote_addr` and `request_id`(see
+    /// `WebServer::request_ids`) fields, and the `status` field once the response was generated.
+    /// Plug in a subscriber(like the ones of `tracing-subscriber`) to turn them into structured
+    /// logs or distributed traces.
+    ///
+    /// `HEAD` requests are handled by the `GET` route of a path unless it has a `HEAD` route of it's
+    /// own, and `OPTIONS` requests to a path without an `OPTIONS` route are answered with a `204 No
+    /// Content` response listing the registered methods in it's `Allow` header.
+    ///
+    /// # Arguments
+    ///
+    /// - `request` - The incoming `Request`.
+    ///
+    /// # Returns
+    ///
+    /// - `Result<Response, WebRouterError>` - A result containing the `Respnose` struct if
+    ///   response is successfully generated, or a `WebRouterError` if there is an error in generating
+    ///   the response.
+    pub fn handle_request(
+        &self,
+        request: request::Request,
+    ) -> Result<response::Response, error::WebRouterError> {
+        let (finishing, dispatched) = self.start_request(request)?;
+        #[cfg(feature = "tracing")]
+        let span = finishing.span.clone();
+        #[cfg(feature = "tracing")]
+        let _entered = span.enter();
+        let response = self.run_dispatched(dispatched);
+        return Ok(self.finish_request(finishing, response));
+    }
+
+    // does everything `handle_request` does before the route handler runs, taking what the steps
+    // after it need from the request before the request is consumed
+    pub(crate) fn start_request(
+        &self,
+        mut request: request::Request,
+    ) -> Result<(Finishing<'_>, Dispatched), error::WebRouterError> {
+        // a sampled request is recorded as it arrived, before the router changes anything about it
+        let recording = self
+            .recorder
+            .as_ref()
+            .and_then(|recorder| recorder.capture(&request));
+
+        // format request path by slashes, a directory of static files is only served from a path
+        // ending with one though
+        request.trailing_slash = request
+            .path
+            .split('?')
+            .next()
+            .is_some_and(|path| path.ends_with('/'));
+        request.path = match utils::format_path_by_slashes(request.path) {
+            Ok(formatted_path) => formatted_path,
+            Err(e) => {
+                return Err(e);
+            }
+        };
+
+        // the ID is assigned before anything else sees the request, so that every log line and
+        // response carries it. The server assigned it already when the head was parsed, so it's
+        // only new for requests handled by the router directly
+        let request_id = self
+            .request_ids
+            .as_ref()
+            .map(|request_ids| request_ids.assign(&mut request));
+
+        // entered until the request was answered, so it covers the middlewares and the handler
+        #[cfg(feature = "tracing")]
+        let span = tracing::info_span!(
+            "request",
+            method = %request.method.to_string(),
+            // the router strips the slash of the root path
+            path = match request.path.split('?').next().unwrap_or_default() {
+                "" => "/",
+                path => path,
+            },
+            remote_addr = tracing::field::Empty,
+            request_id = tracing::field::Empty,
+            status = tracing::field::Empty,
+        );
+        #[cfg(feature = "tracing")]
+        {
+            if let Some(remote_addr) = request.remote_addr {
+                span.record("remote_addr", tracing::field::display(remote_addr));
+            }
+            if let Some(ref request_id) = request_id {
+                span.record("request_id", request_id.as_str());
+            }
+        }
+        #[cfg(feature = "tracing")]
+        let entered = span.clone().entered();
+
+        // everything the request log needs from the request is taken before it is consumed
+        let request_log = self.request_logger.as_ref().map(|_| {
+            let path = request.path.split('?').next().unwrap_or_default();
+            let path = match path.is_empty() {
+                true => "/".to_string(),
+                false => path.to_string(),
+            };
+            return (request.method.clone(), path, request.received_at);
+        });
+
+        // the route is resolved once, by the server when the request arrived or here for requests
+        // handled by the router directly
+        let mut resolution = match request.resolution.take() {
+            Some(resolution) => resolution,
+            None => self.resolve(&request),
+        };
+
+        // everything the compression needs from the request is taken before it is consumed, the
+        // options of the route come from the route itself
+        #[cfg(feature = "compression")]
+        let accept_encoding = self
+            .compression
+            .as_ref()
+            .and_then(|_| request.header("Accept-Encoding").cloned());
+
+        let session = self
+            .sessions
+            .as_ref()
+            .map(|sessions| sessions.load(&request));
+
+        // the after-response middlewares(and the CORS headers) only get the request without it's
+        // body
+        let cors = self.cors_policy(&request, &resolution);
+        let route = resolution.route.clone();
+        let request_head = match self.after_middlewares.is_empty() && cors.is_none() {
+            true => None,
+            false => Some(request.without_body()),
+        };
+        let dispatched =
+            self.route_request(request, session.as_ref(), cors.as_deref(), &mut resolution)?;
+        #[cfg(feature = "tracing")]
+        drop(entered);
+
+        let finishing = Finishing {
+            recording,
+            request_log,
+            #[cfg(feature = "compression")]
+            accept_encoding,
+            session,
+            cors,
+            route,
+            request_head,
+            request_id,
+            #[cfg(feature = "tracing")]
+            span,
+        };
+        return Ok((finishing, dispatched));
+    }
+
+    // handles a request like `handle_request`, except for requests reaching an async route handler
+    // which are left pending with the future of the handler, for the caller to await it and
+    // finish them with `WebRouter::finish_pending`
+    #[cfg(feature = "tokio")]
+    pub(crate) fn handle_request_or_pend(
+        &self,
+        request: request::Request,
+    ) -> Result<Handling, error::WebRouterError> {
+        let (finishing, dispatched) = self.start_request(request)?;
+        #[cfg(feature = "tracing")]
+        let span = finishing.span.clone();
+        #[cfg(feature = "tracing")]
+        let _entered = span.enter();
+        let (route, context, cache_key) = match dispatched {
+            Dispatched::Handler(route, context, cache_key) if route.is_async() => {
+                (route, context, cache_key)
+            }
+            dispatched => {
+                let response = self.run_dispatched(dispatched);
+                return Ok(Handling::Answered(self.finish_request(finishing, response)));
+            }
+        };
+        let path = context.request.path.clone();
+        let marks = context.request.handler_marks.clone();
+        if let Some(ref marks) = marks {
+            marks.start();
+        }
+        let future = match route.async_handler {
+            Some(ref handler) => (handler)(*context),
+            None => unreachable!("only routes with an async handler are left pending"),
+        };
+        // the events of the handler belong to it's request wherever the future is polled
+        #[cfg(feature = "tracing")]
+        let future: HandlerFuture = Box::pin(tracing::Instrument::instrument(future, span.clone()));
+        let pending = Box::new(PendingRequest {
+            finishing: finishing.into_owned(),
+            path,
+            marks,
+            cache_key,
+        });
+        return Ok(Handling::Pending(future, pending));
+    }
+
+    // finishes a request left pending by `WebRouter::handle_request_or_pend` with the result of it's
+    // async route handler
+    #[cfg(feature = "tokio")]
+    pub(crate) fn finish_pending(
+        &self,
+        pending: Box<PendingRequest>,
+        result: Result<response::Response, response::ErrorResponse>,
+    ) -> response::Response {
+        if let Some(ref marks) = pending.marks {
+            marks.finish();
+        }
+        let response = self.handler_response(result, &pending.path);
+        self.store_response(pending.cache_key, &response);
+        return self.finish_request(pending.finishing, response);
+    }
+
+    // does everything `handle_request` does after the route handler generated the response
+    pub(crate) fn finish_request(
+        &self,
+        finishing: Finishing<'_>,
+        mut response: response::Response,
+    ) -> response::Response {
+        #[cfg(feature = "tracing")]
+        let _entered = finishing.span.enter();
+
+        // the session is stored even if the request was answered before reaching a handler, so
+        // that changes made by middlewares aren't lost
+        if let (Some(sessions), Some(session)) =
+            (self.sessions.as_ref(), finishing.session.as_ref())
+        {
+            sessions.commit(session, &mut response);
+        }
+
+        if let Some(request_head) = finishing.request_head {
+            if let Some(cors) = finishing.cors {
+                // preflight responses already got their headers
+                if !cors::Cors::is_preflight(&request_head) {
+                    cors.apply(&request_head, &mut response);
+                }
+            }
+            for middleware in &self.after_middlewares {
+                response = (middleware)(&request_head, response);
+            }
+        }
+
+        // the post-processing comes after the middlewares, so the default headers only fill in
+        // what neither the handler nor the middlewares set
+        let request_id = finishing.request_id;
+        self.post_process(
+            finishing.route.as_deref(),
+            request_id.as_deref(),
+            &mut response,
+        );
+
+        #[cfg(feature = "compression")]
+        if let Some(ref config) = self.compression {
+            let route_options = match finishing.route {
+                Some(ref route) => Cow::Borrowed(&route.options),
+                None => Cow::Owned(RouteOptions::default()),
+            };
+            response = config.apply(
+                &route_options,
+                finishing.accept_encoding.as_deref(),
+                response,
+            );
+        }
+
+        #[cfg(feature = "tracing")]
+        finishing
+            .span
+            .record("status", response.status_code.code().1);
+
+        if let (Some(logger), Some((method, path, received_at))) =
+            (self.request_logger.as_ref(), finishing.request_log)
+        {
+            let log = logger::RequestLog::new(
+                received_at.system_time,
+                method,
+                &path,
+                response.status_code.code().1,
+                match response.stream {
+                    Some(ref stream) => stream.length(),
+                    None => Some(response.body.len() as u64),
+                },
+                received_at.elapsed(),
+            );
+            logger.log(&match request_id {
+                Some(ref request_id) => log.with_request_id(request_id),
+                None => log,
+            });
+        }
+        if let (Some(recorder), Some(mut recording)) = (self.recorder.as_ref(), finishing.recording)
+        {
+            recording.status = Some(response.status_code.code().1);
+            recorder.write(&recording);
+        }
+        return response;
+    }
+
+    // generates the response for a request with an already formatted path, everything but the
+    // after-response middlewares
+    fn route_request(
+        &self,
+        request: request::Request,
+        session: Option<&sessions::Session>,
+        cors: Option<&cors::Cors>,
+        resolution: &mut RouteResolution,
+    ) -> Result<Dispatched, error::WebRouterError> {
+        // reject requests for hosts which aren't served by this router, before any middleware or
+        // handler gets to see them
+        match self.check_host(&request) {
+            Some(status_code) => {
+                return Ok(Dispatched::Response(
+                    self.error_response(status_code, &request.path),
+                ))
+            }
+            None => {}
+        }
+
+        // requests for other origins of the site are redirected before anything else happens
+        if let Some(ref origin) = self.canonical_origin {
+            if let Some(response) = origin.redirect(&request, self.tls || request.tls) {
+                return Ok(Dispatched::Response(response));
+            }
+        }
+
+        // clients over their quota are turned away before any work is done for their requests
+        if let Some(ref rate_limit) = self.rate_limit {
+            if let Some(retry_after) = rate_limit.limit(&request, self.trusted_proxies.as_deref()) {
+                let mut response =
+                    self.error_response(utils::HttpStatusCode::TooManyRequests, &request.path);
+                // a client retrying after the rounded down number of seconds would be limited again
+                let seconds = retry_after.as_secs() + u64::from(retry_after.subsec_nanos() > 0);
+                response
+                    .headers
+                    .insert("Retry-After".to_string(), seconds.max(1).to_string());
+                return Ok(Dispatched::Response(response));
+            }
+        }
+
+        // preflight requests are answered before the middlewares run, since browsers send them
+        // without credentials and an authentication middleware would reject them
+        if let Some(cors) = cors {
+            if let Some(response) = cors.preflight_response(&request) {
+                return Ok(Dispatched::Response(response));
+            }
+        }
+
+        // apply middlewares
+        let mut context = context::Context::new(request);
+        context.background_pool = Arc::clone(&self.background_pool);
+        context.jobs = self.jobs.clone();
+        context.trusted_proxies = self.trusted_proxies.clone();
+        context.digests = self.content_digests.clone();
+        context.templates = self.templates.clone();
+        if let Some(router) = self.shared.get() {
+            context.router = Weak::clone(router);
+        }
+        if let Some(ref request_ids) = self.request_ids {
+            if let Some(request_id) = context.request.header(request_ids.header_name()).cloned() {
+                context.set(request_id::RequestId(request_id));
+            }
+        }
+        if let Some(session) = session {
+            context.session = session.clone();
+        }
+        for middleware in &self.middlewares {
+            context = (middleware)(context);
+            if let Some(response) = context.aborted.take() {
+                return Ok(Dispatched::Response(response));
+            }
+        }
+
+        // the route help endpoint isn't a registered route, so it lists the routes registered
+        // after it was enabled too
+        if let Some(ref route_help) = self.route_help {
+            let path = context.request.path.split('?').next().unwrap_or("");
+            let method = &context.request.method;
+            if path == route_help
+                && (*method == utils::HttpMethod::GET || *method == utils::HttpMethod::HEAD)
+            {
+                return Ok(Dispatched::Response(
+                    self.route_help_response(&context.request),
+                ));
+            }
+        }
+
+        // paths known to match no registered route path(like the ones scanners keep probing) are
+        // answered right away
+        if let Some(ref cache) = self.not_found_cache {
+            if cache.contains(&context.request.path) {
+                return Ok(Dispatched::Response(self.error_response(
+                    utils::HttpStatusCode::NotFound,
+                    &context.request.path,
+                )));
+            }
+        }
+
+        // the route resolved when the request arrived is dispatched to, unless a middleware changed
+        // the path or the method of the request. Otherwise match the request path against the
+        // registered route paths in a single walk of the route tree, preferring a route path which
+        // can handle the request method. The route is taken out of the tree, so the tree isn't
+        // locked while the request is handled.
+        let resolved = resolution.label.is_some()
+            && resolution.path == context.request.path
+            && resolution.method.as_ref() == Some(&context.request.method);
+        let route_match = match resolved {
+            true => Some((
+                resolution.route.clone(),
+                std::mem::take(&mut resolution.params),
+                std::mem::take(&mut resolution.allowed_methods),
+            )),
+            // a path which matched no route path is walked again, to remember it while the tree is
+            // locked
+            false => {
+                let routes = self.route_table();
+                let route_match = routes
+                    .find(&context.request.path, &context.request.method)
+                    .map(|route_match| {
+                        let route =
+                            WebRouter::find_route(route_match.methods, &context.request.method);
+                        let allowed_methods = match route {
+                            Some(_) => String::new(),
+                            None => WebRouter::allowed_methods(route_match.methods),
+                        };
+                        return (route.cloned(), route_match.params, allowed_methods);
+                    });
+                // the path is remembered while the tree is locked, so a route registered meanwhile
+                // can't be shadowed by it(see `WebRouter::insert_route`)
+                if let (None, Some(cache)) = (&route_match, &self.not_found_cache) {
+                    cache.insert(&context.request.path);
+                }
+                route_match
+            }
+        };
+        let (route, route_params, allowed_methods) = match route_match {
+            Some(route_match) => route_match,
+            None => {
+                // the request path doesn't match any registered route path
+                return Ok(Dispatched::Response(self.error_response(
+                    utils::HttpStatusCode::NotFound,
+                    &context.request.path,
+                )));
+            }
+        };
+        match route {
+            Some(route) => {
+                // process and validate the route and query parameters from request path, the
+                // handler gets them percent-decoded next to the raw values
+                let parsed = WebRouter::parse_query(&context.request.path).and_then(|raw_query| {
+                    let params = WebRouter::decode_params(&route_params, utils::percent_decode)?;
+                    let query = context.request.query()?;
+                    return Some((params, query, raw_query));
+                });
+                let (params, query, raw_query_params) = match parsed {
+                    Some(parsed) => parsed,
+                    None => {
+                        return Ok(Dispatched::Response(self.error_response(
+                            utils::HttpStatusCode::BadRequest,
+                            &context.request.path,
+                        )));
+                    }
+                };
+                context.params.extend(params);
+                for (key, value) in query.iter() {
+                    context
+                        .query_params
+                        .entry(key.to_string())
+                        .or_insert_with(|| value.to_string());
+                }
+                context.raw_params.extend(route_params);
+                context.raw_query_params.extend(raw_query_params);
+                return Ok(self.dispatch(&route, context));
+            }
+            None if context.request.method == utils::HttpMethod::OPTIONS => {
+                // the matched route path doesn't handle `OPTIONS` requests itself
+                return Ok(Dispatched::Response(WebRouter::options_response(
+                    allowed_methods,
+                )));
+            }
+            None => {
+                // the request path matches a registered route path but the method is different
+                let mut response = self.error_response(
+                    utils::HttpStatusCode::MethodNotAllowed,
+                    &context.request.path,
+                );
+                response
+                    .headers
+                    .insert("Allow".to_string(), allowed_methods);
+                return Ok(Dispatched::Response(response));
+            }
+        }
+    }
+
+    // splits the query string of a request path into it's raw parameters, keeping the first value
+    // of a repeated key, `None` if any of them has an empty key
+    fn parse_query(path: &str) -> Option<HashMap<String, String>> {
+        let mut query_params = HashMap::new();
+        if let Some((_, query)) = path.split_once('?') {
+            for part in query.split('&').filter(|part| !part.is_empty()) {
+                let (key, value) = part.split_once('=').unwrap_or((part, ""));
+                if key.is_empty() {
+                    return None;
+                }
+                query_params
+                    .entry(key.to_string())
+                    .or_insert_with(|| value.to_string());
+            }
+        }
+        return Some(query_params);
+    }
+
+    // decodes the keys and values of parameters, `None` if any of them can't be decoded
+    fn decode_params(
+        params: &HashMap<String, String>,
+        decode: fn(&str) -> Option<String>,
+    ) -> Option<HashMap<String, String>> {
+        let mut decoded = HashMap::with_capacity(params.len());
+        for (key, value) in params.iter() {
+            decoded.insert(decode(key)?, decode(value)?);
+        }
+        return Some(decoded);
+    }
+
+    // validates the `Host` header of a request against the `allowed_hosts` allowlist, returning the
+    // status code to reject the request with if it isn't allowed
+    //
+    // an allowlist entry starting with a dot(like `.example.com`) matches the domain itself and all
+    // of it's subdomains, every other entry has to match exactly
+    fn check_host(&self, request: &request::Request) -> Option<utils::HttpStatusCode> {
+        let allowed_hosts = match self.allowed_hosts {
+            Some(ref allowed_hosts) => allowed_hosts,
+            None => return None,
+        };
+        let host = match request.host() {
+            Some(host) => host,
+            None => return Some(utils::HttpStatusCode::BadRequest),
+        };
+        let allowed = allowed_hosts.iter().any(|allowed| {
+            let allowed = allowed.to_ascii_lowercase();
+            match allowed.strip_prefix('.') {
+                Some(domain) => host == domain || host.ends_with(&allowed),
+                None => host == allowed,
+            }
+        });
+        match allowed {
+            true => return None,
+            false => return Some(utils::HttpStatusCode::MisdirectedRequest),
+        }
+    }
+
+    // looks up the route registered for a request method in the method map of a route path, `HEAD`
+    // requests fall back to the `GET` route since the body is stripped before sending anyway
+    fn find_route<'a>(
+        method_map: &'a HashMap<String, Arc<Route>>,
+        method: &utils::HttpMethod,
+    ) -> Option<&'a Arc<Route>> {
+        match method_map.get(&method.to_string()) {
+            Some(route) => return Some(route),
+            None => match method {
+                utils::HttpMethod::HEAD => {
+                    return method_map.get(&utils::HttpMethod::GET.to_string());
+                }
+                _ => return None,
+            },
+        }
+    }
+
+    // lists the methods which a route path can be requested with, as used in the `Allow` header
+    fn allowed_methods(method_map: &HashMap<String, Arc<Route>>) -> String {
+        let registered = |method: utils::HttpMethod| method_map.contains_key(&method.to_string());
+        return [
+            utils::HttpMethod::GET,
+            utils::HttpMethod::HEAD,
+            utils::HttpMethod::POST,
+            utils::HttpMethod::PUT,
+            utils::HttpMethod::PATCH,
+            utils::HttpMethod::DELETE,
+            utils::HttpMethod::OPTIONS,
+        ]
+        .into_iter()
+        .filter(|method| match method {
+            utils::HttpMethod::HEAD => {
+                registered(utils::HttpMethod::HEAD) || registered(utils::HttpMethod::GET)
+            }
+            utils::HttpMethod::OPTIONS => true,
+            method => registered(method.clone()),
+        })
+        .map(|method| method.to_string())
+        .collect::<Vec<_>>()
+        .join(", ");
+    }
+
+    // answers an `OPTIONS` request for a route path which has no `OPTIONS` route of it's own
+    fn options_response(allowed_methods: String) -> response::Response {
+        let mut response = response::Response::new(utils::HttpStatusCode::NoContent, String::new());
+        response
+            .headers
+            .insert("Allow".to_string(), allowed_methods);
+        return response;
+    }
+
+    /// Returns the options of the route a request would be dispatched to, without running it.
+    ///
+    /// This is used to apply per-route settings(like the body limit) before the whole request was
+    /// read.
+    ///
+    /// # Arguments
+    ///
+    /// - `request` - The request, whose body doesn't have to be read yet.
+    ///
+    /// # Returns
+    ///
+    /// - `Option<RouteOptions>` - The options of the matching route, `None` if no route matches
+    ///   the path and method of the request.
+    pub fn route_options(&self, request: &request::Request) -> Option<RouteOptions> {
+        return self
+            .matching_route(request)
+            .map(|route| route.options.clone());
+    }
+
+    /// Returns the route a request would be dispatched to, without running it, see
+    /// `WebRouter::route_options`.
+    ///
+    /// # Returns
+    ///
+    /// - `Option<Arc<Route>>` - The matching route, `None` if no route matches the path and method
+    ///   of the request.
+    pub fn matching_route(&self, request: &request::Request) -> Option<Arc<Route>> {
+        let path = match utils::format_path_by_slashes(request.path.to_string()) {
+            Ok(path) => path,
+            Err(_) => return None,
+        };
+        if let Some(ref cache) = self.not_found_cache {
+            if cache.contains(&path) {
+                return None;
+            }
+        }
+        let routes = self.route_table();
+        let route_match = routes.find(&path, &request.method)?;
+        return WebRouter::find_route(route_match.methods, &request.method).cloned();
+    }
+
+    /// Resolves the route a request is dispatched to, in a single walk of the route tree.
+    ///
+    /// The server resolves the route of every request as it arrives(to look up it's options) and
+    /// hands the resolution to `handle_request` along with the request, so it isn't looked up
+    /// again for the default headers, the metrics label or the dispatch.
+    pub(crate) fn resolve(&self, request: &request::Request) -> RouteResolution {
+        let mut resolution = RouteResolution {
+            method: Some(request.method.clone()),
+            ..RouteResolution::default()
+        };
+        resolution.path = match utils::format_path_by_slashes(request.path.to_string()) {
+            Ok(path) => path,
+            Err(_) => return resolution,
+        };
+        if let Some(ref cache) = self.not_found_cache {
+            if cache.contains(&resolution.path) {
+                return resolution;
+            }
+        }
+        let routes = self.route_table();
+        if let Some(route_match) = routes.find(&resolution.path, &request.method) {
+            resolution.route = WebRouter::find_route(route_match.methods, &request.method).cloned();
+            if resolution.route.is_none() {
+                resolution.allowed_methods = WebRouter::allowed_methods(route_match.methods);
+            }
+            resolution.label = Some(Arc::clone(route_match.label));
+            resolution.params = route_match.params;
+        }
+        return resolution;
+    }
+
+    /// Post-processes a response before it is sent, whether `handle_request` generated it or the
+    /// server itself(like it's `408 Request Timeout` or `503 Service Unavailable` responses).
+    ///
+    /// The default headers of the route(see `RouteBuilder::header`) and the `default_headers` of
+    /// the router fill in the headers the response doesn't have yet. The ones of the route come
+    /// before the ones of the server, and the route's own ones(added last) before the ones it got
+    /// from it's group. The response gets the request ID header if request IDs are enabled.
+    pub(crate) fn post_process(
+        &self,
+        route: Option<&Route>,
+        request_id: Option<&str>,
+        response: &mut response::Response,
+    ) {
+        let route_headers = route.map(|route| route.options.headers.as_slice());
+        for (name, value) in route_headers
+            .unwrap_or_default()
+            .iter()
+            .rev()
+            .chain(self.default_headers.iter())
+        {
+            if !response
+                .headers
+                .keys()
+                .any(|key| key.eq_ignore_ascii_case(name))
+            {
+                response.headers.insert(name.clone(), value.clone());
+            }
+        }
+        if let (Some(request_ids), Some(request_id)) = (self.request_ids.as_ref(), request_id) {
+            request_ids.apply(request_id, response);
+        }
+    }
+
+    /// Records an answered request in the metrics, labeled with the route path pattern it resolved
+    /// to(`metrics::UNMATCHED_ROUTE` without one). Does nothing unless metrics are enabled.
+    ///
+    /// The server records every response it sends this way, the ones of the router as well as the
+    /// ones it generates itself(like `408 Request Timeout` or `503 Service Unavailable`), which is
+    /// why `handle_request` doesn't record them.
+    pub(crate) fn record_metrics(
+        &self,
+        label: Option<&str>,
+        method: &str,
+        status: u16,
+        latency: Duration,
+    ) {
+        if let Some(ref metrics) = self.metrics {
+            metrics.record(
+                label.unwrap_or(metrics::UNMATCHED_ROUTE),
+                method,
+                status,
+                latency,
+            );
+        }
+    }
+
+    // returns the CORS policy for a request, the one of it's route if that overrides the server's
+    // policy. A preflight request is matched against the route of the method it asks for, since
+    // there usually is no `OPTIONS` route.
+    fn cors_policy(
+        &self,
+        request: &request::Request,
+        resolution: &RouteResolution,
+    ) -> Option<Cow<'_, cors::Cors>> {
+        let route_policy = match cors::Cors::is_preflight(request) {
+            true => utils::format_path_by_slashes(request.path.to_string())
+                .ok()
+                .and_then(|path| {
+                    let routes = self.route_table();
+                    let route_match = routes.find(&path, &request.method)?;
+                    let method = request.header("Access-Control-Request-Method")?.trim();
+                    let route = match route_match.methods.get(method) {
+                        Some(route) => route,
+                        None if method == "HEAD" => route_match.methods.get("GET")?,
+                        None => return None,
+                    };
+                    return route.options.cors.clone();
+                }),
+            false => resolution
+                .route
+                .as_ref()
+                .and_then(|route| route.options.cors.clone()),
+        };
+        return route_policy
+            .map(Cow::Owned)
+            .or(self.cors.as_ref().map(Cow::Borrowed));
+    }
+
+    // lists the registered routes, as JSON if the client asks for it(with the `Accept` header or a
+    // `format=json` query parameter) and as an HTML page otherwise
+    fn route_help_response(&self, request: &request::Request) -> response::Response {
+        let routes = self.routes();
+        let wants_json = request
+            .header("Accept")
+            .is_some_and(|accept| accept.contains("application/json"))
+            || request::Query::parse(request.query_string())
+                .is_some_and(|query| query.get("format") == Some("json"));
+
+        let (content_type, body) = match wants_json {
+            true => {
+                let routes: Vec<serde_json::Value> = routes
+                    .iter()
+                    .map(|route| {
+                        serde_json::json!({
+                            "method": route.method,
+                            "path": route.path,
+                            "params": route.params,
+                            "doc": route.doc,
+                            "chain": route
+                                .chain
+                                .iter()
+                                .map(|step| step.to_string())
+                                .collect::<Vec<_>>(),
+                        })
+                    })
+                    .collect();
+                let body = serde_json::to_string_pretty(&routes).unwrap_or_default();
+                ("application/json", body)
+            }
+            false => {
+                let mut rows = String::new();
+                for route in routes.iter() {
+                    rows.push_str(&format!(
+                        "<tr><td><code>{}</code></td><td><code>{}</code></td><td>{}</td><td>{}</td></tr>\n",
+                        route.method,
+                        utils::escape_html(&route.path),
+                        utils::escape_html(&route.params.join(", ")),
+                        utils::escape_html(route.doc.as_deref().unwrap_or(""))
+                    ));
+                }
+                let content = format!(
+                    "<h1>Routes</h1>\n<table>\n<tr><th>Method</th><th>Path</th><th>Parameters</th><th>Description</th></tr>\n{}</table>\n",
+                    rows
+                );
+                let body = match self.page_templates {
+                    Some(ref templates) => templates.render("Routes", &content),
+                    None => pages::PageTemplates::default().render("Routes", &content),
+                };
+                ("text/html; charset=utf-8", body)
+            }
+        };
+        let mut response = response::Response::new(utils::HttpStatusCode::OK, body);
+        response
+            .headers
+            .insert("Content-Type".to_string(), content_type.to_string());
+        response
+            .headers
+            .insert("Vary".to_string(), "Accept".to_string());
+        return response;
+    }
 
-impl WebRouter {
-    /// Creates a new `WebRouter` with an empty route map.
+    /// Compiles the middlewares and access control policies of every route into a single chain,
+    /// looking up the policies by their names once instead of for every request. Called by
+    /// `WebServer::listen` before the first connection is accepted, routes which weren't compiled
+    /// are compiled when they are first requested.
     ///
     /// # Returns
     ///
-    /// - `WebRouter` - A new instance of `WebRouter`.
+    /// - `usize` - The number of routes.
+    pub fn compile(&self) -> usize {
+        let mut compiled = 0;
+        let mut routes = self
+            .routes
+            .write()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        for endpoint in routes.endpoints_mut() {
+            for route in endpoint.methods.values_mut() {
+                // a chain compiled earlier(like for a request sent with `handle_request` while
+                // the server was still being configured) may miss middlewares and policies
+                if let Some(route) = Arc::get_mut(route) {
+                    route.chain.take();
+                }
+                self.chain(route);
+                compiled += 1;
+            }
+        }
+        return compiled;
+    }
+
+    // returns the compiled chain of a route, compiling it if it wasn't yet
+    fn chain<'a>(&self, route: &'a Route) -> &'a CompiledChain {
+        return route.chain.get_or_init(|| {
+            let middlewares = route
+                .middlewares
+                .iter()
+                .map(|middleware| CompiledStep::Middleware(Arc::clone(middleware)));
+            let policies =
+                route.options.policies.iter().map(|name| {
+                    CompiledStep::Policy(name.clone(), self.policies.get(name).cloned())
+                });
+            let global = (0..self.middlewares.len()).map(ChainStep::GlobalMiddleware);
+            return CompiledChain {
+                steps: middlewares.chain(policies).collect(),
+                listing: global.chain(route.steps()).collect(),
+            };
+        });
+    }
+
+    /// Returns the registered routes sorted by route path and method, with all the steps a
+    /// request matching them goes through, global middlewares included.
     ///
     /// # Examples
     ///
     /// ```rust
-    /// use browzer_web::router::WebRouter;
+    /// use browzer_web::{router::{ChainStep, RouteBuilder, WebRouter}, utils::{HttpMethod, HttpStatusCode}};
     ///
-    /// let router = WebRouter::new();
+    /// let mut router = WebRouter::new();
+    /// router.add_middleware(|ctx| ctx);
+    /// let route = router
+    ///     .add("/admin".to_string(), HttpMethod::GET, |mut c| {
+    ///         return c.send_string(HttpStatusCode::OK, "admin");
+    ///     })
+    ///     .ok();
+    /// RouteBuilder::new(route).middleware(|ctx| ctx).require_policy("admin");
     ///
-    /// assert!(router.routes.is_empty());
+    /// assert_eq!(
+    ///     router.routes()[0].chain,
+    ///     vec![
+    ///         ChainStep::GlobalMiddleware(0),
+    ///         ChainStep::RouteMiddleware(0),
+    ///         ChainStep::Policy("admin".to_string()),
+    ///         ChainStep::Handler,
+    ///     ]
+    /// );
     /// ```
-    pub fn new() -> WebRouter {
-        return WebRouter {
-            routes: HashMap::new(),
-            middlewares: vec![],
+    pub fn routes(&self) -> Vec<RouteInfo> {
+        return self
+            .route_table()
+            .route_infos(|route| self.chain(route).listing.to_vec());
+    }
+
+    // runs the chain of a matched route for the context, making sure all access control policies
+    // required by the route allow the request, up to it's handler
+    fn dispatch(&self, route: &Arc<Route>, mut context: context::Context) -> Dispatched {
+        for step in self.chain(route).steps.iter() {
+            let (policy_name, policy) = match step {
+                CompiledStep::Middleware(middleware) => {
+                    context = (middleware)(context);
+                    match context.aborted.take() {
+                        Some(response) => return Dispatched::Response(response),
+                        None => continue,
+                    }
+                }
+                CompiledStep::Policy(name, policy) => (name, policy),
+            };
+            let (allowed, registered) = match policy {
+                Some(policy) => ((policy)(&context), true),
+                None => (false, false),
+            };
+            if let Some(ref audit) = self.policy_audit {
+                (audit)(&policy::PolicyDecision {
+                    policy: policy_name.to_string(),
+                    method: context.request.method.clone(),
+                    path: context.request.path.to_string(),
+                    allowed,
+                    registered,
+                });
+            }
+            if !registered {
+                eprintln!(
+                    "Access control policy \"{}\" is required by a route but was never registered",
+                    policy_name
+                );
+            }
+            if !allowed {
+                return Dispatched::Response(
+                    self.error_response(utils::HttpStatusCode::Forbidden, &context.request.path),
+                );
+            }
+        }
+        if let Some(ref cache) = self.response_cache {
+            if let Some(response) = cache.lookup(&context.request) {
+                return Dispatched::Response(response);
+            }
+            if cache.caches(&context.request.path) {
+                // only what decides whether the response is stored is kept of the request
+                let request = request::Request {
+                    method: context.request.method.clone(),
+                    path: context.request.path.clone(),
+                    headers: context.request.headers.clone(),
+                    ..Default::default()
+                };
+                return Dispatched::Handler(
+                    Arc::clone(route),
+                    Box::new(context),
+                    Some(Box::new(request)),
+                );
+            }
+        }
+        return Dispatched::Handler(Arc::clone(route), Box::new(context), None);
+    }
+
+    // runs the handler of a route, timing it if the request is sampled(see
+    // `WebServer::sample_requests`), and answers an `ErrorResponse` it returns like any other
+    // framework error
+    fn run_handler(&self, route: &Route, context: context::Context) -> response::Response {
+        let marks = context.request.handler_marks.clone();
+        let path = context.request.path.clone();
+        if let Some(ref marks) = marks {
+            marks.start();
+        }
+        let result = (route.handler)(context);
+        if let Some(ref marks) = marks {
+            marks.finish();
+        }
+        return self.handler_response(result, &path);
+    }
+
+    // answers an `ErrorResponse` returned by a route handler like any other framework error
+    pub(crate) fn handler_response(
+        &self,
+        result: Result<response::Response, response::ErrorResponse>,
+        path: &str,
+    ) -> response::Response {
+        match result {
+            Ok(response) => return response,
+            Err(error) => {
+                return self.detailed_error_response(error.status_code, path, Some(&error.message))
+            }
+        }
+    }
+
+    // generates the response of a dispatched request on the current thread
+    fn run_dispatched(&self, dispatched: Dispatched) -> response::Response {
+        match dispatched {
+            Dispatched::Response(response) => return response,
+            Dispatched::Handler(route, context, cache_key) => {
+                let response = self.run_handler(&route, *context);
+                self.store_response(cache_key, &response);
+                return response;
+            }
+        }
+    }
+
+    // stores the response of a handler in the response cache, if the request was eligible for it
+    pub(crate) fn store_response(
+        &self,
+        cache_key: Option<Box<request::Request>>,
+        response: &response::Response,
+    ) {
+        if let (Some(cache), Some(request)) = (self.response_cache.as_ref(), cache_key) {
+            cache.store(&request, response);
+        }
+    }
+
+    /// Requests the URLs configured with `ResponseCache::warm_up`, so that their responses are
+    /// cached. Called by `WebServer::listen` before the first connection is accepted.
+    ///
+    /// # Returns
+    ///
+    /// - `usize` - The number of URLs which were answered with `200 OK`, a warning is printed using
+    ///   `eprintln!` for every other one.
+    pub fn warm_response_cache(&self) -> usize {
+        let requests = match self.response_cache {
+            Some(ref cache) => cache.warm_up_requests(),
+            None => return 0,
         };
+        let mut warmed = 0;
+        for request in requests {
+            let path = request.path.clone();
+            match self.handle_request(request) {
+                Ok(response) if response.status_code == utils::HttpStatusCode::OK => warmed += 1,
+                Ok(response) => eprintln!(
+                    "Warning: warming up the response cache with \"{}\" got {}",
+                    path,
+                    response.status_code.code().1
+                ),
+                Err(err) => eprintln!(
+                    "Warning: warming up the response cache with \"{}\" failed: {}",
+                    path, err
+                ),
+            }
+        }
+        return warmed;
     }
 
-    /// Adds a new route to the `routes` hashmap using route path, method and route handler as input
+    /// Generates the response for an error detected by the framework itself, like a request which
+    /// doesn't match any registered route.
+    ///
+    /// By default the body is the plain-text reason phrase of the status code, if `problem_details`
+    /// is configured an `application/problem+json` body is generated instead, otherwise if
+    /// `page_templates` are configured an HTML error page is rendered from them. A registered
+    /// `not_found_handler`, `method_not_allowed_handler` or `error_handler` takes precedence over
+    /// all of them, the status code of the response it generates is always the one of the error.
     ///
     /// # Arguments
     ///
-    /// - `path` - The route path as a `String`.
-    /// - `method` - The HTTP method for the route as an `HttpMethod`.
-    /// - `handler` - The `RouteHandlerFunction` representing closure function for the route.
+    /// - `status_code` - The `HttpStatusCode` of the error.
+    /// - `instance` - The request path for which the error occurred.
     ///
     /// # Returns
     ///
-    /// - `Result<(), WebRouterError>` - A Result containing a `WebRouterError` if there is
-    /// any error while formatting the path using `format_path_by_slashes` utility function
-    pub fn add<F>(
-        &mut self,
-        mut path: String,
-        method: utils::HttpMethod,
-        handler: F,
-    ) -> Result<(), error::WebRouterError>
-    where
-        F: Fn(context::Context) -> response::Response + 'static + Send + Sync,
-    {
-        path = match utils::format_path_by_slashes(path) {
-            Ok(formatted_path) => formatted_path,
-            Err(e) => {
-                return Err(e);
+    /// - `Response` - The error response.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use browzer_web::{pages::PageTemplates, problem::ProblemConfig, router::WebRouter};
+    /// use browzer_web::{response::Response, utils::HttpStatusCode};
+    ///
+    /// let mut router = WebRouter::new();
+    /// assert_eq!(router.error_response(HttpStatusCode::NotFound, "/missing").body, "Not Found");
+    ///
+    /// router.page_templates = Some(PageTemplates::new());
+    /// let response = router.error_response(HttpStatusCode::NotFound, "/missing");
+    /// assert_eq!(response.headers.get("Content-Type").unwrap(), "text/html; charset=utf-8");
+    ///
+    /// router.problem_details = Some(ProblemConfig::default());
+    /// let response = router.error_response(HttpStatusCode::NotFound, "/missing");
+    /// assert_eq!(response.headers.get("Content-Type").unwrap(), "application/problem+json");
+    ///
+    /// router.not_found_handler = Some(Box::new(|_, path| {
+    ///     return Response::new(HttpStatusCode::OK, format!("Nothing at {}", path));
+    /// }));
+    /// let response = router.error_response(HttpStatusCode::NotFound, "/missing");
+    /// assert_eq!(response.status_code, HttpStatusCode::NotFound);
+    /// assert_eq!(response.body, "Nothing at /missing");
+    /// ```
+    pub fn error_response(
+        &self,
+        status_code: utils::HttpStatusCode,
+        instance: &str,
+    ) -> response::Response {
+        return self.detailed_error_response(status_code, instance, None);
+    }
+
+    // generates an error response like `error_response`, with an optional detail message which
+    // becomes the `detail` of a problem details response, or the body of a plain text one
+    fn detailed_error_response(
+        &self,
+        status_code: utils::HttpStatusCode,
+        instance: &str,
+        detail: Option<&str>,
+    ) -> response::Response {
+        let handler = match status_code {
+            utils::HttpStatusCode::NotFound => self.not_found_handler.as_ref(),
+            utils::HttpStatusCode::MethodNotAllowed => self.method_not_allowed_handler.as_ref(),
+            _ => None,
+        };
+        if let Some(handler) = handler.or(self.error_handler.as_ref()) {
+            let mut response = (handler)(status_code.clone(), instance);
+            response.status_code = status_code;
+            return response;
+        }
+        match self.problem_details {
+            Some(ref config) => {
+                let mut problem = config.problem(status_code).with_instance(instance);
+                // a detail repeating the reason phrase adds nothing to the title
+                if let Some(detail) = detail.filter(|detail| *detail != problem.title) {
+                    problem = problem.with_detail(detail);
+                }
+                return problem.to_response();
             }
+            None => match self.page_templates {
+                Some(ref templates) => return templates.error_response(status_code, instance),
+                None => match detail {
+                    Some(detail) => {
+                        let mut response = response::Response::new(status_code, detail.to_string());
+                        response.headers.insert(
+                            "Content-Type".to_string(),
+                            "text/plain; charset=utf-8".to_string(),
+                        );
+                        return response;
+                    }
+                    None => {
+                        let body = status_code.code().0.to_string();
+                        return response::Response::new(status_code, body);
+                    }
+                },
+            },
+        }
+    }
+}
+
+/// A registered route, as listed by `RouteTree::routes`.
+///
+/// # Fields
+///
+/// - `method` - The HTTP method of the route, like `GET`.
+/// - `path` - The route path pattern, like `/users/:id`.
+/// - `params` - The names of the `:name` and `*name` segments of the route path, in order.
+/// - `doc` - The description of the route, see `RouteBuilder::doc`.
+/// - `chain` - The steps a request matching the route goes through, in the order they run. Only
+///   `WebRouter::routes` lists the global middlewares.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RouteInfo {
+    pub method: String,
+    pub path: String,
+    pub params: Vec<String>,
+    pub doc: Option<String>,
+    pub chain: Vec<ChainStep>,
+}
+
+/// A route path matched by `RouteTree::find`.
+///
+/// # Fields
+///
+/// - `path` - The registered route path pattern, like `/users/:id`.
+/// - `methods` - The routes registered for the route path, by HTTP method.
+/// - `params` - The values of the `:name` and `*name` segments of the route path, by name.
+#[derive(Debug)]
+pub struct RouteMatch<'a> {
+    pub path: &'a str,
+    pub methods: &'a HashMap<String, Arc<Route>>,
+    pub params: HashMap<String, String>,
+    // the route path pattern as it labels the requests of the route in the metrics
+    pub(crate) label: &'a Arc<str>,
+}
+
+// the route a request resolved to, found by a single walk of the route tree when the request
+// arrives and shared by everything which needs it(the options and default headers of the route,
+// the metrics label and the dispatch to it's handler), see `WebRouter::resolve`
+#[derive(Debug, Default)]
+pub(crate) struct RouteResolution {
+    // the formatted path and the method of the request the route was resolved for
+    path: String,
+    method: Option<utils::HttpMethod>,
+    // the route path pattern the request path matched, `None` if it matched none
+    pub(crate) label: Option<Arc<str>>,
+    // the route of the request method, `None` if the route path doesn't handle it
+    pub(crate) route: Option<Arc<Route>>,
+    params: HashMap<String, String>,
+    allowed_methods: String,
+}
+
+// what the router made of a request before it's handler ran, see `WebRouter::start_request`
+pub(crate) enum Dispatched {
+    // the request was answered without reaching a handler
+    Response(response::Response),
+    // the handler of the route generates the response for the context, the request is kept if the
+    // response is stored in the response cache
+    Handler(
+        Arc<Route>,
+        Box<context::Context>,
+        Option<Box<request::Request>>,
+    ),
+}
+
+// the outcome of `WebRouter::handle_request_or_pend`
+#[cfg(feature = "tokio")]
+pub(crate) enum Handling {
+    Answered(response::Response),
+    Pending(HandlerFuture, Box<PendingRequest>),
+}
+
+// a request waiting for the future of it's async route handler, see `WebRouter::finish_pending`
+#[cfg(feature = "tokio")]
+pub(crate) struct PendingRequest {
+    finishing: Finishing<'static>,
+    path: String,
+    marks: Option<sampling::HandlerMarks>,
+    cache_key: Option<Box<request::Request>>,
+}
+
+// the steps of a request left once it's handler generated the response, along with what they need
+// from the request, see `WebRouter::finish_request`
+pub(crate) struct Finishing<'a> {
+    recording: Option<replay::RecordedRequest>,
+    request_log: Option<(utils::HttpMethod, String, request::ReceivedAt)>,
+    #[cfg(feature = "compression")]
+    accept_encoding: Option<String>,
+    session: Option<sessions::Session>,
+    cors: Option<Cow<'a, cors::Cors>>,
+    route: Option<Arc<Route>>,
+    request_head: Option<request::Request>,
+    request_id: Option<String>,
+    #[cfg(feature = "tracing")]
+    pub(crate) span: tracing::Span,
+}
+
+impl Finishing<'_> {
+    // detaches the steps from the router, so they can be finished after the request was handed to
+    // another task
+    #[cfg(feature = "tokio")]
+    pub(crate) fn into_owned(self) -> Finishing<'static> {
+        return Finishing {
+            recording: self.recording,
+            request_log: self.request_log,
+            #[cfg(feature = "compression")]
+            accept_encoding: self.accept_encoding,
+            session: self.session,
+            cors: self.cors.map(|cors| Cow::Owned(cors.into_owned())),
+            route: self.route,
+            request_head: self.request_head,
+            request_id: self.request_id,
+            #[cfg(feature = "tracing")]
+            span: self.span,
         };
-        self.routes
-            .entry(path.to_string())
-            .or_insert_with(HashMap::new)
-            .insert(method.to_string(), Box::new(handler));
-        return Ok(());
     }
+}
 
-    /// Appends a new middleware to the `middlewares` vector
+/// A segment trie of the registered route paths, which matches a request path in a single walk
+/// proportional to it's length instead of trying every registered route path.
+///
+/// Every node of the tree stands for a path segment, and a route path is registered at the node
+/// reached by following it's segments. A segment of a route path can be:
+/// - static, like `users`, which only matches the exact same segment
+/// - a parameter, like `:id`, which matches any single non-empty segment
+/// - a wildcard, like `*path`, which matches the rest of the path(at least one segment) and may
+///   only be the last segment of a route path
+///
+/// When several route paths match a request path, static segments are preferred over parameters,
+/// and parameters over wildcards, segment by segment from the start of the path. So `/users/me` is
+/// matched by `/users/me` rather than `/users/:id`, whichever was registered first.
+///
+/// # Examples
+///
+/// ```rust
+/// use browzer_web::{router::RouteTree, utils::HttpMethod};
+///
+/// let mut routes = RouteTree::new();
+/// routes.insert("/users/:id");
+/// routes.insert("/users/me");
+/// routes.insert("/static/*file");
+///
+/// let route_match = routes.find("/users/axew", &HttpMethod::GET).unwrap();
+/// assert_eq!(route_match.path, "/users/:id");
+/// assert_eq!(route_match.params.get("id").unwrap(), "axew");
+///
+/// assert_eq!(routes.find("/users/me", &HttpMethod::GET).unwrap().path, "/users/me");
+///
+/// let route_match = routes.find("/static/css/main.css", &HttpMethod::GET).unwrap();
+/// assert_eq!(route_match.params.get("file").unwrap(), "css/main.css");
+///
+/// assert!(routes.find("/posts", &HttpMethod::GET).is_none());
+/// ```
+// ----- RouteTree struct
+#[derive(Default)]
+pub struct RouteTree {
+    root: RouteNode,
+    len: usize,
+}
+
+// a node of the route tree, standing for one segment of the route paths passing through it
+#[derive(Default)]
+struct RouteNode {
+    // the route path ending at this node
+    endpoint: Option<Endpoint>,
+    statics: HashMap<String, RouteNode>,
+    // the child for a `:name` segment, the names are kept by the endpoints since different route
+    // paths may name the same parameter differently
+    param: Option<Box<RouteNode>>,
+    // the route path ending with a `*name` segment at this node
+    wildcard: Option<Endpoint>,
+}
+
+// a registered route path with it's routes
+struct Endpoint {
+    path: String,
+    // the route path as it labels requests in the metrics, the root route path with it's slash
+    label: Arc<str>,
+    // the names of the `:name` and `*name` segments of the route path, in order
+    param_names: Vec<String>,
+    methods: HashMap<String, Arc<Route>>,
+}
+
+impl fmt::Debug for RouteTree {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("RouteTree")
+            .field("paths", &self.paths())
+            .finish()
+    }
+}
+
+impl RouteTree {
+    /// Creates a new empty `RouteTree`.
+    pub fn new() -> RouteTree {
+        return RouteTree::default();
+    }
+
+    /// Returns the number of registered route paths.
+    pub fn len(&self) -> usize {
+        return self.len;
+    }
+
+    /// Returns `true` if no route path is registered.
+    pub fn is_empty(&self) -> bool {
+        return self.len == 0;
+    }
+
+    /// Returns the registered route paths, in no particular order.
+    pub fn paths(&self) -> Vec<&str> {
+        return self
+            .endpoints()
+            .into_iter()
+            .map(|endpoint| endpoint.path.as_str())
+            .collect();
+    }
+
+    // returns the registered route paths with their routes for changing them, in no particular
+    // order
+    fn endpoints_mut(&mut self) -> Vec<&mut Endpoint> {
+        let mut endpoints = vec![];
+        let mut nodes = vec![&mut self.root];
+        while let Some(node) = nodes.pop() {
+            endpoints.extend(
+                [&mut node.endpoint, &mut node.wildcard]
+                    .into_iter()
+                    .flatten(),
+            );
+            nodes.extend(node.statics.values_mut());
+            nodes.extend(node.param.as_deref_mut());
+        }
+        return endpoints;
+    }
+
+    // returns the registered route paths with their routes, in no particular order
+    fn endpoints(&self) -> Vec<&Endpoint> {
+        let mut endpoints = vec![];
+        let mut nodes = vec![&self.root];
+        while let Some(node) = nodes.pop() {
+            endpoints.extend([&node.endpoint, &node.wildcard].into_iter().flatten());
+            nodes.extend(node.statics.values());
+            nodes.extend(node.param.as_deref());
+        }
+        return endpoints;
+    }
+
+    /// Returns the registered routes, sorted by route path and method. Their chains start after
+    /// the global middlewares, see `WebRouter::routes` for the complete ones.
     ///
-    /// # Arguments
+    /// # Examples
     ///
-    /// - `middleware_func` - A closure function representing the middleware handler
-    pub fn add_middleware<F>(&mut self, middleware_func: F)
+    /// ```rust
+    /// use browzer_web::{router::{RouteBuilder, WebRouter}, utils::{HttpMethod, HttpStatusCode}};
+    ///
+    /// let mut router = WebRouter::new();
+    /// let route = router
+    ///     .add("/users/:id".to_string(), HttpMethod::GET, |mut c| {
+    ///         return c.send_string(HttpStatusCode::OK, "user");
+    ///     })
+    ///     .ok();
+    /// RouteBuilder::new(route).doc("Returns a user");
+    ///
+    /// let routes = router.route_table().routes();
+    /// assert_eq!(routes[0].method, "GET");
+    /// assert_eq!(routes[0].path, "/users/:id");
+    /// assert_eq!(routes[0].params, vec!["id".to_string()]);
+    /// assert_eq!(routes[0].doc.as_deref(), Some("Returns a user"));
+    /// ```
+    pub fn routes(&self) -> Vec<RouteInfo> {
+        return self.route_infos(Route::steps);
+    }
+
+    // describes the registered routes, sorted by route path and method, with the given chains
+    fn route_infos<F>(&self, chain: F) -> Vec<RouteInfo>
     where
-        F: Fn(context::Context) -> context::Context + 'static + Send + Sync,
+        F: Fn(&Route) -> Vec<ChainStep>,
     {
-        self.middlewares.push(Box::new(middleware_func));
+        let mut routes = vec![];
+        for endpoint in self.endpoints() {
+            for (method, route) in endpoint.methods.iter() {
+                // the root route path is stored without it's slash
+                let path = match endpoint.path.is_empty() {
+                    true => "/".to_string(),
+                    false => endpoint.path.clone(),
+                };
+                routes.push(RouteInfo {
+                    method: method.clone(),
+                    path,
+                    params: endpoint.param_names.clone(),
+                    doc: route.options.doc.clone(),
+                    chain: chain(route),
+                });
+            }
+        }
+        routes.sort_by(|a, b| (&a.path, &a.method).cmp(&(&b.path, &b.method)));
+        return routes;
     }
 
-    /// Handles an incoming request, apply middlewares and generates a response.
+    /// Registers a route path, returning it's routes by HTTP method to add routes to.
     ///
-    /// This function works in two parts:
-    /// 1. It applies all the middlewares from the `middlewares` vector
-    /// 2. handle response generation from request by first getting all the user-registered routes
-    /// which match the request's path(it will be hashmap) from `routes` hashmap, then using that
-    /// hashmap to get the route which matches request's method and then finaly using that route's
-    /// handler function to generate the response for the request by providing a new `Context` with
-    /// the request as input to the handler function
+    /// Route paths only differing in the names of their parameters(like `/users/:id` and
+    /// `/users/:name`) are the same route path, the names of the last registration are used.
     ///
     /// # Arguments
     ///
-    /// - `request` - The incoming `Request`.
+    /// - `path` - The route path, already formatted with `format_path_by_slashes`.
     ///
     /// # Returns
     ///
-    /// - `Result<Response, WebRouterError>` - A result containing the `Respnose` struct if
-    /// response is successfully generated, or a `WebRouterError` if there is an error in generating
-    /// the response.
-    pub fn handle_request(
-        &self,
-        mut request: request::Request,
-    ) -> Result<response::Response, error::WebRouterError> {
-        // format request path by slashes
-        request.path = match utils::format_path_by_slashes(request.path) {
-            Ok(formatted_path) => formatted_path,
-            Err(e) => {
-                return Err(e);
+    /// - `&mut HashMap<String, Arc<Route>>` - The routes of the route path, empty if it is new.
+    pub fn insert(&mut self, path: &str) -> &mut HashMap<String, Arc<Route>> {
+        let mut node = &mut self.root;
+        let mut param_names = vec![];
+        let mut segments = path.split('/').peekable();
+        let mut wildcard = None;
+        while let Some(segment) = segments.next() {
+            if let Some(name) = segment.strip_prefix('*') {
+                if segments.peek().is_none() {
+                    wildcard = Some(name);
+                    break;
+                }
             }
+            node = match segment.strip_prefix(':') {
+                Some(name) => {
+                    param_names.push(name.to_string());
+                    node.param.get_or_insert_with(Box::default)
+                }
+                None => node.statics.entry(segment.to_string()).or_default(),
+            };
+        }
+        let endpoint = match wildcard {
+            Some(name) => {
+                param_names.push(name.to_string());
+                &mut node.wildcard
+            }
+            None => &mut node.endpoint,
         };
-
-        // apply middlewares
-        let mut context = context::Context::new(request);
-        for middleware in &self.middlewares {
-            context = (middleware)(context);
+        if endpoint.is_none() {
+            self.len += 1;
         }
+        let endpoint = endpoint.get_or_insert_with(|| Endpoint {
+            path: String::new(),
+            label: Arc::from(""),
+            param_names: vec![],
+            methods: HashMap::new(),
+        });
+        endpoint.path = path.to_string();
+        endpoint.label = match path.is_empty() {
+            true => Arc::from("/"),
+            false => Arc::from(path),
+        };
+        endpoint.param_names = param_names;
+        return &mut endpoint.methods;
+    }
 
-        // request path pattern matching with registered route paths
-        match self.routes.get(&context.request.path) {
-            Some(path_map) => match path_map.get(&context.request.method.to_string()) {
-                Some(route_handler) => {
-                    // the request path, method `exactly` matches a registered route path, method
-                    return Ok((route_handler)(context));
-                }
-                None => {
-                    // the request path `exactly` matches a registered route path but the method is
-                    // different
-                    return Ok(response::Response::new(
-                        utils::HttpStatusCode::MethodNotAllowed,
-                        format!("{}", utils::HttpStatusCode::MethodNotAllowed.code().0).to_string(),
-                    ));
-                }
-            },
-            // the request path does not `exactly` match a registered route path
-            None => {
-                for (route_path, method_map) in &self.routes {
-                    match WebRouter::match_dynamic_route(
-                        context.request.path.to_string(),
-                        route_path.to_string(),
-                    ) {
-                        Some(params) => match method_map.get(&context.request.method.to_string()) {
-                            Some(route_handler) => {
-                                // process and validate query parameters from request path
-                                let mut query_params = HashMap::new();
-                                match context.request.path.split('?').nth(1) {
-                                    Some(query) => {
-                                        for part in query.split('&') {
-                                            let mut key_value = part.split('=');
-                                            let key = key_value.next().unwrap_or("");
-                                            let value = key_value.next().unwrap_or("");
-                                            if key.is_empty() {
-                                                // If the key is empty, return a bad request response
-                                                return Ok(response::Response::new(
-                                                    utils::HttpStatusCode::BadRequest,
-                                                    format!(
-                                                        "{}",
-                                                        utils::HttpStatusCode::BadRequest.code().0
-                                                    )
-                                                    .to_string(),
-                                                ));
-                                            }
-                                            query_params.insert(key.to_string(), value.to_string());
-                                        }
-                                    }
-                                    None => {}
-                                }
-
-                                context.params = params;
-                                context.query_params = query_params;
-
-                                // the request path matches a registered dynamic route path pattern
-                                // with provided parameters
-                                return Ok((route_handler)(context));
-                            }
-                            None => {}
-                        },
-                        None => {}
-                    }
-                }
-                // the request path neither `exactly` matches any registered route,
-                // nor matches with any registered dynamic route path pattern
-                return Ok(response::Response::new(
-                    utils::HttpStatusCode::NotFound,
-                    format!("{}", utils::HttpStatusCode::NotFound.code().0).to_string(),
-                ));
+    /// Returns the routes of a registered route path by HTTP method.
+    ///
+    /// The route path is looked up as it was registered(like `/users/:id`), use `RouteTree::find`
+    /// to match a request path instead.
+    pub fn get(&self, path: &str) -> Option<&HashMap<String, Arc<Route>>> {
+        let mut node = &self.root;
+        let mut segments = path.split('/').peekable();
+        while let Some(segment) = segments.next() {
+            if segment.starts_with('*') && segments.peek().is_none() {
+                return node.wildcard.as_ref().map(|endpoint| &endpoint.methods);
             }
+            node = match segment.starts_with(':') {
+                true => node.param.as_deref()?,
+                false => node.statics.get(segment)?,
+            };
         }
+        return node.endpoint.as_ref().map(|endpoint| &endpoint.methods);
     }
-    /// Matches a request path to a registered dynamic route path, extracting parameters if available.
-    ///
-    /// This function first removes the query parameters from the request path string, then
-    /// splits both the request path and route path into vectors by splitting at `/` (slashes).
-    /// It ensures the lengths of these vectors are the same. If they are, it zips the vectors
-    /// into one vector with the format `(request_path_part, route_path_part)`.
+
+    /// Matches a request path against the registered route paths.
     ///
-    /// It then loops over this vector and checks if the `route_path_part` of any item starts with `:`.
-    /// If it does, this registered route is identified as a dynamic route, so the corresponding
-    /// `request_path_part` is stored in the `params` `HashMap` which is then returned after the loop ends.
-    /// If the `route_path_part` does not start with `:`, it is treated as a normal route and both parts
-    /// must be equal. If they aren't, the function returns `None`.
+    /// A route path which has a route for the request method(or a `GET` route for a `HEAD`
+    /// request) is preferred, so `GET /users/me` can be handled by `/users/:id` even when
+    /// `/users/me` only has a `POST` route. If no matching route path has such a route, the best
+    /// matching route path is returned anyway, to answer the request with `405 Method Not Allowed`
+    /// or the allowed methods for an `OPTIONS` request.
     ///
     /// # Arguments
     ///
-    /// - `request_path` - A `String` representing the path of the incoming request.
-    /// - `route_path` - A `String` representing a registered route path pattern.
+    /// - `path` - The request path, already formatted with `format_path_by_slashes`, the query
+    ///   string(if any) is ignored.
+    /// - `method` - The method of the request.
     ///
     /// # Returns
     ///
-    /// An `Option<HashMap<String, String>>` containing the extracted parameters if the request path
-    /// matches the registered route path pattern, or `None` if it does not match.
-    ///
-    /// # Examples
-    ///
-    /// ```rust
-    /// let request_path = "/users/123".to_string();
-    /// let route_path = "/users/:id".to_string();
-    /// let params = WebRouter::match_dynamic_route(request_path, route_path).unwrap();
-    ///
-    /// assert_eq!(params.get("id"), Some(&"123".to_string()));
-    /// ```
-    fn match_dynamic_route(
-        request_path: String,
-        route_path: String,
-    ) -> Option<HashMap<String, String>> {
-        let mut params: HashMap<String, String> = HashMap::new();
+    /// - `Option<RouteMatch>` - The matched route path with the values of it's parameters, `None`
+    ///   if no registered route path matches.
+    pub fn find(&self, path: &str, method: &utils::HttpMethod) -> Option<RouteMatch<'_>> {
+        let path = path.split('?').next().unwrap_or("");
+        let mut segments = vec![];
+        let mut start = 0;
+        for segment in path.split('/') {
+            segments.push((start, segment));
+            start += segment.len() + 1;
+        }
+
+        let mut values = vec![];
+        let method = method.to_string();
+        let handles_method = |endpoint: &Endpoint| {
+            endpoint.methods.contains_key(&method)
+                || (method == "HEAD" && endpoint.methods.contains_key("GET"))
+        };
+        let endpoint = match self
+            .root
+            .find(path, &segments, &mut values, &handles_method)
+        {
+            Some(endpoint) => endpoint,
+            None => {
+                values.clear();
+                self.root.find(path, &segments, &mut values, &|_| true)?
+            }
+        };
 
-        let request_path_parts: Vec<&str> = request_path.split('?').collect::<Vec<_>>()[0]
-            .split('/')
+        let params = endpoint
+            .param_names
+            .iter()
+            .zip(values)
+            .map(|(name, value)| (name.to_string(), value.to_string()))
             .collect();
-        let route_path_parts: Vec<&str> = route_path.split('/').collect();
+        return Some(RouteMatch {
+            path: &endpoint.path,
+            methods: &endpoint.methods,
+            params,
+            label: &endpoint.label,
+        });
+    }
+}
 
-        if route_path_parts.len() != request_path_parts.len() {
-            return None;
+impl RouteNode {
+    // finds the best matching endpoint accepted by the filter for the remaining segments(with their
+    // offsets in the path), collecting the values of the parameters along the way
+    fn find<'t, 'p>(
+        &'t self,
+        path: &'p str,
+        segments: &[(usize, &'p str)],
+        values: &mut Vec<&'p str>,
+        accept: &dyn Fn(&Endpoint) -> bool,
+    ) -> Option<&'t Endpoint> {
+        let (start, segment) = match segments.first() {
+            Some(&first) => first,
+            None => return self.endpoint.as_ref().filter(|endpoint| accept(endpoint)),
+        };
+        if let Some(child) = self.statics.get(segment) {
+            if let Some(endpoint) = child.find(path, &segments[1..], values, accept) {
+                return Some(endpoint);
+            }
         }
+        if let Some(ref child) = self.param {
+            if !segment.is_empty() {
+                values.push(segment);
+                if let Some(endpoint) = child.find(path, &segments[1..], values, accept) {
+                    return Some(endpoint);
+                }
+                values.pop();
+            }
+        }
+        if let Some(ref endpoint) = self.wildcard {
+            let rest = &path[start..];
+            if !rest.is_empty() && accept(endpoint) {
+                values.push(rest);
+                return Some(endpoint);
+            }
+        }
+        return None;
+    }
+}
 
-        for (request_path_part, route_path_part) in
-            request_path_parts.iter().zip(route_path_parts.iter())
-        {
-            if route_path_part.starts_with(':') {
-                let param_name = &route_path_part[1..];
-                params.insert(param_name.to_string(), request_path_part.to_string());
-            } else if request_path_part != route_path_part {
-                return None;
+/// A bounded cache of request paths which matched no registered route path, see
+/// `WebServer::cache_not_found`.
+///
+/// A path which matched nothing keeps matching nothing until another route is registered(which
+/// clears the cache), so it can be answered with `404 Not Found` without walking the route tree.
+/// Query strings are ignored, and once the cache is full the oldest path is evicted. Paths longer
+/// than `NotFoundCache::MAX_PATH_LENGTH` are never cached, so the memory used stays bounded.
+///
+/// # Examples
+///
+/// ```rust
+/// use browzer_web::router::NotFoundCache;
+///
+/// let cache = NotFoundCache::new(2);
+/// cache.insert("/wp-login.php?redirect=1");
+/// cache.insert("/.env");
+/// cache.insert("/admin.php");
+///
+/// assert!(!cache.contains("/wp-login.php"));
+/// assert!(cache.contains("/.env?x=1"));
+/// assert_eq!(cache.len(), 2);
+/// assert_eq!(cache.hits(), 1);
+/// ```
+// ----- NotFoundCache struct
+#[derive(Debug)]
+pub struct NotFoundCache {
+    capacity: usize,
+    entries: Mutex<NotFoundEntries>,
+    hits: AtomicU64,
+}
+
+// the cached paths, and the order they were inserted in for evicting the oldest one
+#[derive(Debug, Default)]
+struct NotFoundEntries {
+    paths: HashSet<String>,
+    order: VecDeque<String>,
+}
+
+impl NotFoundCache {
+    /// The maximum length of a path to be cached, in bytes.
+    pub const MAX_PATH_LENGTH: usize = 1024;
+
+    /// Creates a new, empty `NotFoundCache` holding up to `capacity` paths.
+    pub fn new(capacity: usize) -> NotFoundCache {
+        return NotFoundCache {
+            capacity,
+            entries: Mutex::new(NotFoundEntries::default()),
+            hits: AtomicU64::new(0),
+        };
+    }
+
+    /// Returns whether a path is known to match no registered route path, counting a hit if it
+    /// is.
+    pub fn contains(&self, path: &str) -> bool {
+        let path = path.split('?').next().unwrap_or("");
+        let found = match self.entries.lock() {
+            Ok(entries) => entries.paths.contains(path),
+            Err(_) => false,
+        };
+        if found {
+            self.hits.fetch_add(1, Ordering::Relaxed);
+        }
+        return found;
+    }
+
+    /// Remembers that a path matched no registered route path, evicting the oldest path if the
+    /// cache is full.
+    pub fn insert(&self, path: &str) {
+        let path = path.split('?').next().unwrap_or("");
+        if self.capacity == 0 || path.len() > NotFoundCache::MAX_PATH_LENGTH {
+            return;
+        }
+        if let Ok(mut entries) = self.entries.lock() {
+            if entries.paths.contains(path) {
+                return;
+            }
+            while entries.order.len() >= self.capacity {
+                match entries.order.pop_front() {
+                    Some(oldest) => {
+                        entries.paths.remove(&oldest);
+                    }
+                    None => break,
+                }
             }
+            entries.paths.insert(path.to_string());
+            entries.order.push_back(path.to_string());
+        }
+    }
+
+    /// Forgets all cached paths, since a newly registered route may match them.
+    pub fn clear(&self) {
+        if let Ok(mut entries) = self.entries.lock() {
+            entries.paths.clear();
+            entries.order.clear();
+        }
+    }
+
+    /// Returns the number of cached paths.
+    pub fn len(&self) -> usize {
+        match self.entries.lock() {
+            Ok(entries) => return entries.paths.len(),
+            Err(_) => return 0,
         }
-        Some(params)
+    }
+
+    /// Returns whether no paths are cached.
+    pub fn is_empty(&self) -> bool {
+        return self.len() == 0;
+    }
+
+    /// Returns the number of requests answered from the cache so far.
+    pub fn hits(&self) -> u64 {
+        return self.hits.load(Ordering::Relaxed);
     }
 }
@@ -0,0 +1,724 @@
+//! This module serves a `WebServer` on the tokio runtime, see `WebServer::listen_async`(requires
+//! the `tokio` feature).
+//!
+//! Connections are read from and written to with non-blocking I/O, so clients which are slow to
+//! send their requests, or keep their connections open between requests, only cost a task instead
+//! of a worker thread. Middlewares and handlers keep their signature and run on the blocking thread
+//! pool of the runtime, while async handlers(see `WebServer::register_async_route`) are awaited on
+//! the runtime itself, so a handler waiting on other async code(like a database client) doesn't
+//! hold a thread either.
+//!
+//! Requests go through the same steps as the ones served by worker threads(limits, timeouts,
+//! memory budgets, request IDs, metrics and sampling), over plain HTTP or HTTPS and behind the
+//! PROXY protocol alike. The body of a streamed response is generated by a blocking thread, which
+//! passes it's chunks to the connection task.
+
+// internal crate imports
+#[cfg(feature = "tls")]
+use crate::tls;
+use crate::{
+    cancel, connection, error, exchange, limits, overload, panics, proxy, request, response,
+    router, stream, upgrade, utils, ConnectionSettings, WebServer,
+};
+
+// external crate imports
+use tokio::{
+    io::{AsyncBufReadExt, AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt, BufReader},
+    net::{TcpListener, TcpStream},
+    sync::mpsc,
+    task::{JoinError, JoinHandle},
+};
+
+// standard library imports
+use std::{
+    future::{self, Future},
+    io::{self, Write},
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, OnceLock,
+    },
+    task::Poll,
+    time::{Duration, Instant},
+};
+
+// how often the accept loop checks whether the server is stopping
+const SHUTDOWN_POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+// the largest part of a request body reserved up front, the rest grows with the data which arrives
+const INITIAL_BODY_CAPACITY: usize = 64 * 1024;
+
+// the number of chunks of a streamed body which may wait for the connection to take them
+const STREAM_CHUNKS: usize = 8;
+
+/// Runs a future to completion, blocking the current thread until it's done.
+///
+/// The future runs on the tokio runtime of the current thread, like the one of a server started
+/// with `WebServer::listen_async`, or on a runtime shared by all threads without one(like the
+/// worker threads of a server started with `WebServer::listen`). This is how async handlers are
+/// run, so it's meant for blocking code like handlers and must not be called from async code.
+///
+/// # Examples
+///
+/// ```rust
+/// use browzer_web::runtime;
+///
+/// let answer = runtime::block_on(async { 40 + 2 });
+/// assert_eq!(answer, 42);
+/// ```
+pub fn block_on<F: Future>(future: F) -> F::Output {
+    match tokio::runtime::Handle::try_current() {
+        Ok(handle) => return handle.block_on(future),
+        Err(_) => return shared_runtime().block_on(future),
+    }
+}
+
+// the runtime of the threads which don't belong to a runtime, created on first use
+fn shared_runtime() -> &'static tokio::runtime::Runtime {
+    static RUNTIME: OnceLock<tokio::runtime::Runtime> = OnceLock::new();
+    return RUNTIME.get_or_init(|| {
+        return tokio::runtime::Builder::new_multi_thread()
+            .enable_all()
+            .build()
+            .expect("Failed to create the tokio runtime of the async handlers");
+    });
+}
+
+// everything a connection task needs from the server, which the task can't borrow
+#[derive(Clone)]
+struct Acceptor {
+    router: Arc<router::WebRouter>,
+    settings: ConnectionSettings,
+    active_connections: Arc<AtomicUsize>,
+    panic_log: Arc<panics::PanicLog>,
+    #[cfg(feature = "tls")]
+    tls: Option<tokio_rustls::TlsAcceptor>,
+}
+
+// serves a server on the current tokio runtime until it's stopped, see `WebServer::listen_async`
+pub(crate) async fn listen(server: &WebServer) -> io::Result<()> {
+    let std_listeners: Vec<&std::net::TcpListener> = std::iter::once(&server.listener)
+        .chain(server.additional_listeners.iter())
+        .collect();
+    let mut listeners = Vec::with_capacity(std_listeners.len());
+    for listener in std_listeners.iter() {
+        // the connections of a listener start with a PROXY protocol header if the server or the
+        // listener expects one
+        let proxy_protocol = server.proxy_protocol
+            || listener
+                .local_addr()
+                .is_ok_and(|address| server.proxy_protocol_listeners.contains(&address));
+        let listener = listener.try_clone()?;
+        listener.set_nonblocking(true)?;
+        listeners.push((TcpListener::from_std(listener)?, proxy_protocol));
+    }
+
+    server.print_banner();
+    server.prepare_router();
+
+    let acceptor = Acceptor {
+        router: Arc::clone(&server.router),
+        settings: server.connection_settings(),
+        active_connections: Arc::clone(&server.active_connections),
+        panic_log: Arc::clone(&server.panic_log),
+        #[cfg(feature = "tls")]
+        tls: server
+            .tls_config
+            .clone()
+            .map(tokio_rustls::TlsAcceptor::from),
+    };
+    let mut accept_errors = server.accept_error_log.tracker();
+    while !server.shutdown.is_shutting_down() {
+        // the first listener with a pending connection is served, the stop is noticed while
+        // waiting instead of only once the next connection arrives
+        let accepted = future::poll_fn(|cx| {
+            for (listener, proxy_protocol) in listeners.iter() {
+                if let Poll::Ready(result) = listener.poll_accept(cx) {
+                    return Poll::Ready(result.map(|(stream, _)| (stream, *proxy_protocol)));
+                }
+            }
+            return Poll::Pending;
+        });
+        let (stream, proxy_protocol) =
+            match tokio::time::timeout(SHUTDOWN_POLL_INTERVAL, accepted).await {
+                Ok(Ok(accepted)) => accepted,
+                Ok(Err(e)) => {
+                    if let Some(backoff) = accept_errors.accept_failed(&e) {
+                        tokio::time::sleep(backoff).await;
+                    }
+                    continue;
+                }
+                Err(_) => continue,
+            };
+        accept_errors.accepted();
+
+        let acceptor = acceptor.clone();
+        let connection_guard = match limits::ConnectionGuard::acquire(
+            &acceptor.active_connections,
+            acceptor.settings.max_connections,
+        ) {
+            Some(connection_guard) => connection_guard,
+            None => {
+                tokio::spawn(async move { shed(&acceptor, stream).await });
+                continue;
+            }
+        };
+        let error_reporter = accept_errors.reporter();
+        tokio::spawn(async move {
+            let _connection_guard = connection_guard;
+            let result = handle_connection(&acceptor, stream, proxy_protocol).await;
+            if let Err(e) = result {
+                error_reporter.connection_failed(&e);
+            }
+        });
+    }
+    server.route_registry.attach(None);
+
+    // the listeners share their sockets with the ones of the server, which `listen` expects to be
+    // blocking
+    drop(listeners);
+    for listener in std_listeners.iter() {
+        listener.set_nonblocking(false)?;
+    }
+
+    // let the connections in flight finish, keep-alive connections are closed after their
+    // current response
+    let deadline = Instant::now() + server.drain_timeout;
+    while server.active_connections.load(Ordering::SeqCst) > 0 && Instant::now() < deadline {
+        tokio::time::sleep(Duration::from_millis(10)).await;
+    }
+    if !server.hide_banner {
+        println!("-----> server on {} stopped", server.address);
+    }
+    return Ok(());
+}
+
+// answers a connection the server has no room for with `503 Service Unavailable`, TLS clients
+// can't read a plaintext response so their connection is just closed
+async fn shed(acceptor: &Acceptor, mut stream: TcpStream) {
+    #[cfg(feature = "tls")]
+    if acceptor.tls.is_some() && !acceptor.settings.accept_plaintext {
+        return;
+    }
+    let mut response = acceptor
+        .router
+        .error_response(utils::HttpStatusCode::ServiceUnavailable, "");
+    acceptor.router.post_process(None, None, &mut response);
+    acceptor.settings.overload.signal(
+        &mut response,
+        overload::OverloadReason::ConnectionLimit,
+        acceptor.settings.max_connections,
+    );
+    response
+        .headers
+        .insert("Connection".to_string(), "close".to_string());
+    let _ = stream.write_all(response.to_string().as_bytes()).await;
+}
+
+// serves a client connection, after reading it's PROXY protocol header and completing the TLS
+// handshake if the server expects them
+async fn handle_connection(
+    acceptor: &Acceptor,
+    mut stream: TcpStream,
+    proxy_protocol: bool,
+) -> Result<(), error::WebServerError> {
+    let settings = &acceptor.settings;
+    // behind a TCP load balancer the client address comes from the PROXY protocol header, which
+    // precedes everything else(the TLS handshake included), a connection without a valid header
+    // is dropped
+    let remote_addr = match proxy_protocol {
+        true => {
+            let header = within(settings.keep_alive_timeout, read_proxy_header(&mut stream))
+                .await
+                .map_err(error::ProxyProtocolError::from)
+                .and_then(|header| header);
+            match header {
+                Ok(header) => header.source.or(stream.peer_addr().ok()),
+                Err(e) => return Err(error::WebServerError::ProxyProtocolError(e)),
+            }
+        }
+        false => stream.peer_addr().ok(),
+    };
+
+    #[cfg(feature = "tls")]
+    if let Some(ref tls) = acceptor.tls {
+        // sniff the first byte of the connection to serve clients which don't speak TLS as plain
+        // HTTP, a connection closed before sending anything is simply done
+        let handshake = match settings.accept_plaintext {
+            true => {
+                let mut first_byte = [0u8; 1];
+                match within(settings.keep_alive_timeout, stream.peek(&mut first_byte)).await {
+                    Ok(Ok(0)) => return Ok(()),
+                    Ok(Ok(_)) => first_byte[0] == tls::HANDSHAKE_RECORD,
+                    Ok(Err(e)) | Err(e) => return Err(error::WebServerError::IO(e)),
+                }
+            }
+            false => true,
+        };
+        if handshake {
+            let tls_stream = match within(settings.keep_alive_timeout, tls.accept(stream)).await {
+                Ok(Ok(tls_stream)) => tls_stream,
+                Ok(Err(e)) | Err(e) => return Err(error::WebServerError::TlsError(e.to_string())),
+            };
+            let tls_info = tls::tls_info(tls_stream.get_ref().1);
+            return serve_connection(acceptor, tls_stream, remote_addr, Some(tls_info)).await;
+        }
+    }
+    return serve_connection(acceptor, stream, remote_addr, None).await;
+}
+
+// reads requests from a connection and writes the responses generated by the router back to it,
+// for as long as the connection is kept alive
+async fn serve_connection<S: AsyncRead + AsyncWrite + Unpin>(
+    acceptor: &Acceptor,
+    stream: S,
+    remote_addr: Option<std::net::SocketAddr>,
+    tls: Option<connection::TlsInfo>,
+) -> Result<(), error::WebServerError> {
+    let router = &acceptor.router;
+    let settings = &acceptor.settings;
+    // the state shared by all requests of the connection
+    let connection = connection::Connection::new(remote_addr);
+    let is_tls = tls.is_some();
+    if let Some(tls) = tls {
+        connection.set_tls(tls);
+    }
+    let mut reader = BufReader::new(stream);
+
+    loop {
+        // an idle keep-alive connection is closed as soon as the server is stopping, instead of
+        // once it timed out
+        let shutdown = match connection.request_count() {
+            0 => None,
+            _ => Some(&settings.shutdown),
+        };
+        let (head, received_at) = match read_head(
+            &mut reader,
+            settings.keep_alive_timeout,
+            settings.head_limits.max_head_size,
+            shutdown,
+        )
+        .await?
+        {
+            Some(head) => head,
+            None => {
+                // the client closed the connection, or left it idle for too long, between two
+                // requests
+                return Ok(());
+            }
+        };
+        // the head is parsed exactly like the one of a request read by a worker thread
+        let mut request = match request::Request::read_head_with_limits(
+            &mut head.as_slice(),
+            settings.strict_http,
+            &settings.head_limits,
+        ) {
+            Ok(Some(request)) => request,
+            Ok(None) => return Ok(()),
+            Err(error::WebServerError::RequestParseError(e)) => {
+                let response = exchange::Exchange::head_error(router, &e, received_at.instant);
+                reject(&mut reader, response).await;
+                return Err(error::WebServerError::RequestParseError(e));
+            }
+            Err(e) => return Err(e),
+        };
+        request.received_at = received_at;
+        request.remote_addr = remote_addr;
+        request.tls = is_tls;
+        connection.count_request();
+        request.connection = connection.clone();
+        let cancellation = request.cancellation.clone();
+
+        // a connection doesn't wait for a worker thread, so it's first request isn't queued
+        let mut exchange =
+            exchange::Exchange::begin(router, settings, &mut request, Duration::ZERO);
+        if let Some(response) = exchange.admit() {
+            reject(&mut reader, response).await;
+            return Ok(());
+        }
+
+        let handled = match exchange.streams_body() {
+            true => {
+                // the request is handled by a task of it's own, while the body is passed to it
+                // from the connection
+                let length = request.content_length() as u64;
+                let (sender, mut receiver) = mpsc::channel(request::BODY_CHUNKS);
+                request.body_stream = Some(request::BodyStream::new(length, move || {
+                    return receiver.blocking_recv();
+                }));
+                exchange.body_read();
+                let remaining = exchange.remaining();
+                let handling_router = Arc::clone(router);
+                let handling = AbortOnDrop(tokio::spawn(async move {
+                    return handle(&handling_router, request, remaining).await;
+                }));
+                if !pump_body(&mut reader, length, sender, remaining).await {
+                    exchange.body_left_unread();
+                }
+                match handling.join().await {
+                    Ok(handled) => handled,
+                    Err(join_error) => failed(join_error),
+                }
+            }
+            false => {
+                // the body has to arrive before the deadline of the request
+                let content_length = request.content_length();
+                if content_length > 0 {
+                    let mut body = Vec::with_capacity(content_length.min(INITIAL_BODY_CAPACITY));
+                    let limited = (&mut reader).take(content_length as u64);
+                    match within(exchange.remaining(), read_to_end(limited, &mut body)).await {
+                        Ok(read) => {
+                            read?;
+                        }
+                        Err(_) => {
+                            let response = exchange.reject(utils::HttpStatusCode::RequestTimeout);
+                            reject(&mut reader, response).await;
+                            return Ok(());
+                        }
+                    }
+                    // a body cut short by the client is reported like the one read by a worker
+                    // thread
+                    request.read_body(&mut body.as_slice())?;
+                }
+                exchange.body_read();
+                handle(router, request, exchange.remaining()).await
+            }
+        };
+        let reply = exchange.respond(handled, &acceptor.panic_log);
+
+        let stream = reader.get_mut();
+        stream.write_all(reply.head.as_bytes()).await?;
+        if let Some(stream_body) = reply.stream {
+            write_stream(
+                stream,
+                stream_body,
+                cancellation,
+                reply.chunked,
+                &acceptor.panic_log,
+            )
+            .await?;
+        }
+        match stream.flush().await {
+            Ok(_) => {}
+            Err(e) => {
+                return Err(error::WebServerError::StreamFlushError(e.to_string()));
+            }
+        }
+        exchange.complete();
+
+        if !reply.keep_alive {
+            return reply.result;
+        }
+    }
+}
+
+// handles a request, the router runs it's middlewares and blocking handlers on the blocking thread
+// pool of the runtime while async handlers are awaited on the runtime itself. A request which runs
+// out of time has it's async handler dropped, a blocking handler can't be interrupted so it keeps
+// running in the background and it's response is thrown away
+async fn handle(
+    router: &Arc<router::WebRouter>,
+    request: request::Request,
+    timeout: Option<Duration>,
+) -> exchange::Handled {
+    let cancellation = request.cancellation.clone();
+    let handling = async {
+        let handling_router = Arc::clone(router);
+        let handling =
+            tokio::task::spawn_blocking(move || handling_router.handle_request_or_pend(request));
+        let (future, pending) = match handling.await {
+            Ok(Ok(router::Handling::Answered(response))) => {
+                return exchange::Handled::Response(Ok(response));
+            }
+            Ok(Ok(router::Handling::Pending(future, pending))) => (future, pending),
+            Ok(Err(e)) => return exchange::Handled::Response(Err(e)),
+            Err(join_error) => return failed(join_error),
+        };
+        let handler = AbortOnDrop(tokio::spawn(future));
+        let result = match handler.join().await {
+            Ok(result) => result,
+            Err(join_error) => return failed(join_error),
+        };
+        // the after-response middlewares may block like any other middleware
+        let finishing_router = Arc::clone(router);
+        let finishing =
+            tokio::task::spawn_blocking(move || finishing_router.finish_pending(pending, result));
+        match finishing.await {
+            Ok(response) => return exchange::Handled::Response(Ok(response)),
+            Err(join_error) => return failed(join_error),
+        }
+    };
+    match within(timeout, handling).await {
+        Ok(handled) => return handled,
+        Err(_) => {
+            // let the handler know that nobody is waiting for it's response anymore
+            cancellation.cancel();
+            return exchange::Handled::TimedOut;
+        }
+    }
+}
+
+// passes the streamed body of a request from the connection to it's handler, a failed read is
+// passed on too unless the handler has parts of the body left to read(which end early then).
+// `false` if the body wasn't read completely(the handler stopped reading it, or reading it failed),
+// so the connection can't be reused
+async fn pump_body<S: AsyncRead + Unpin>(
+    reader: &mut BufReader<S>,
+    length: u64,
+    sender: mpsc::Sender<io::Result<Vec<u8>>>,
+    timeout: Option<Duration>,
+) -> bool {
+    let pumping = async {
+        let mut remaining = length;
+        while remaining > 0 {
+            let mut chunk = vec![0u8; remaining.min(request::BODY_CHUNK_SIZE as u64) as usize];
+            match reader.read(&mut chunk).await {
+                Ok(0) => {
+                    let _ = sender.try_send(Err(io::Error::new(
+                        io::ErrorKind::UnexpectedEof,
+                        "Connection closed before the whole request body was received",
+                    )));
+                    return false;
+                }
+                Ok(read) => {
+                    chunk.truncate(read);
+                    remaining -= read as u64;
+                    // the handler is done with the body once it dropped it's stream
+                    if sender.send(Ok(chunk)).await.is_err() {
+                        return false;
+                    }
+                }
+                Err(e) => {
+                    let _ = sender.try_send(Err(e));
+                    return false;
+                }
+            }
+        }
+        return true;
+    };
+    // the body has to arrive before the deadline of the request
+    match within(timeout, pumping).await {
+        Ok(complete) => return complete,
+        Err(e) => {
+            let _ = sender.try_send(Err(e));
+            return false;
+        }
+    }
+}
+
+// reports a task of a request which didn't finish, with the payload of it's panic unless it was
+// cancelled
+fn failed(join_error: JoinError) -> exchange::Handled {
+    return exchange::Handled::Panicked(join_error.try_into_panic().ok());
+}
+
+// a task which is aborted once it's handle is dropped, like when the request it handles runs out
+// of time
+struct AbortOnDrop<T>(JoinHandle<T>);
+
+impl<T> AbortOnDrop<T> {
+    async fn join(mut self) -> Result<T, JoinError> {
+        return (&mut self.0).await;
+    }
+}
+
+impl<T> Drop for AbortOnDrop<T> {
+    fn drop(&mut self) {
+        self.0.abort();
+    }
+}
+
+// writes a streamed body to the connection. Streams write to a blocking writer, so the stream runs
+// on a blocking thread which passes it's chunks to the connection through a channel
+async fn write_stream<S: AsyncWrite + Unpin>(
+    stream: &mut S,
+    stream_body: stream::StreamBody,
+    cancellation: cancel::CancellationToken,
+    chunked: bool,
+    panic_log: &panics::PanicLog,
+) -> Result<(), error::WebServerError> {
+    let (sender, mut receiver) = mpsc::channel(STREAM_CHUNKS);
+    let streaming = tokio::task::spawn_blocking(move || {
+        let mut channel = ChannelWriter(sender);
+        let mut writer =
+            stream::ResponseWriter::new(&mut channel, cancellation, stream_body.length(), chunked);
+        stream_body.write_to(&mut writer)?;
+        return writer.finish();
+    });
+    // a failed write drops the receiver, which fails the next write of the stream
+    while let Some(chunk) = receiver.recv().await {
+        stream.write_all(&chunk).await?;
+        stream.flush().await?;
+    }
+    match streaming.await {
+        Ok(result) => return result.map_err(error::WebServerError::IO),
+        Err(join_error) => {
+            if let Ok(payload) = join_error.try_into_panic() {
+                panic_log.record(panics::PanicPhase::Writing, payload.as_ref());
+            }
+            // the body is incomplete, so the connection can't be reused
+            return Err(error::WebServerError::IO(io::Error::other(
+                "The stream of the response failed",
+            )));
+        }
+    }
+}
+
+// a blocking writer passing everything written to it on to the connection task
+struct ChannelWriter(mpsc::Sender<Vec<u8>>);
+
+impl Write for ChannelWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self.0.blocking_send(buf.to_vec()) {
+            Ok(_) => return Ok(buf.len()),
+            Err(_) => {
+                return Err(io::Error::new(
+                    io::ErrorKind::BrokenPipe,
+                    "The connection of the response was closed",
+                ))
+            }
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        return Ok(());
+    }
+}
+
+// awaits a future for at most the timeout, `None` waits for as long as it takes
+async fn within<F: Future>(timeout: Option<Duration>, future: F) -> io::Result<F::Output> {
+    match timeout {
+        Some(timeout) => match tokio::time::timeout(timeout, future).await {
+            Ok(output) => return Ok(output),
+            Err(_) => {
+                return Err(io::Error::new(
+                    io::ErrorKind::TimedOut,
+                    "Timed out while serving the connection",
+                ))
+            }
+        },
+        None => return Ok(future.await),
+    }
+}
+
+// reads a limited part of a connection to the end
+async fn read_to_end<R: AsyncRead + Unpin>(mut reader: R, buf: &mut Vec<u8>) -> io::Result<usize> {
+    return reader.read_to_end(buf).await;
+}
+
+// reads the PROXY protocol header at the start of a connection, collecting exactly the bytes of
+// the header to parse them like a worker thread would(see `proxy::read_header`)
+async fn read_proxy_header(
+    stream: &mut TcpStream,
+) -> Result<proxy::ProxyHeader, error::ProxyProtocolError> {
+    // both versions are at least as long as the signature of version 2
+    let mut header = vec![0u8; proxy::V2_SIGNATURE.len()];
+    stream.read_exact(&mut header).await?;
+    if header == proxy::V2_SIGNATURE {
+        // the version, command and address family, followed by the length of the addresses
+        let mut fixed = [0u8; 4];
+        stream.read_exact(&mut fixed).await?;
+        header.extend_from_slice(&fixed);
+        let mut addresses = vec![0u8; u16::from_be_bytes([fixed[2], fixed[3]]) as usize];
+        stream.read_exact(&mut addresses).await?;
+        header.extend_from_slice(&addresses);
+    } else if header.starts_with(b"PROXY ") {
+        // the line is read byte by byte, so nothing after it is consumed
+        while !header.ends_with(b"\r\n") && header.len() < proxy::V1_MAX_LENGTH {
+            header.push(stream.read_u8().await?);
+        }
+    }
+    return proxy::read_header(&mut header.as_slice());
+}
+
+// reads the head of the next request, up to and including the empty line ending it, along with
+// the time it's first byte arrived. `None` if the connection was closed(or left idle for the
+// timeout) before any part of a request arrived, the whole head has to arrive within the timeout
+// too. With a `shutdown` handle the wait for the first byte also ends once the server is stopping.
+// Reading stops one byte over `max_size`, the parser reports such a head as too large
+async fn read_head<S: AsyncRead + Unpin>(
+    reader: &mut BufReader<S>,
+    timeout: Option<Duration>,
+    max_size: usize,
+    shutdown: Option<&upgrade::ShutdownHandle>,
+) -> Result<Option<(Vec<u8>, request::ReceivedAt)>, error::WebServerError> {
+    let deadline = timeout.map(|timeout| Instant::now() + timeout);
+    let available = loop {
+        let mut wait = deadline.map(|deadline| deadline.saturating_duration_since(Instant::now()));
+        if let Some(shutdown) = shutdown {
+            if shutdown.is_shutting_down() {
+                return Ok(None);
+            }
+            wait = Some(wait.map_or(upgrade::IDLE_POLL_INTERVAL, |wait| {
+                return wait.min(upgrade::IDLE_POLL_INTERVAL);
+            }));
+        }
+        // waiting for the buffer to fill can be given up on without losing any data
+        match wait {
+            Some(wait) => match tokio::time::timeout(wait, reader.fill_buf()).await {
+                Ok(available) => break available.map(|bytes| !bytes.is_empty()),
+                Err(_) if deadline.is_some_and(|deadline| Instant::now() >= deadline) => {
+                    return Ok(None);
+                }
+                Err(_) => continue,
+            },
+            None => break reader.fill_buf().await.map(|bytes| !bytes.is_empty()),
+        }
+    };
+    match available {
+        Ok(true) => {}
+        Ok(false) => return Ok(None),
+        Err(e)
+            if matches!(
+                e.kind(),
+                io::ErrorKind::ConnectionReset | io::ErrorKind::UnexpectedEof
+            ) =>
+        {
+            return Ok(None);
+        }
+        Err(e) => return Err(error::WebServerError::IO(e)),
+    }
+    let received_at = request::ReceivedAt::now();
+
+    // the rest of the head has to arrive within the timeout of it's first byte
+    let reading = async {
+        let mut head = Vec::new();
+        loop {
+            let start = head.len();
+            let remaining = max_size.saturating_add(1).saturating_sub(start);
+            if remaining == 0 {
+                break;
+            }
+            let mut line = (&mut *reader).take(remaining as u64);
+            // a head cut short by the client is reported by the parser
+            if line.read_until(b'\n', &mut head).await? == 0 {
+                break;
+            }
+            let line = &head[start..];
+            if line == b"\r\n" || line == b"\n" {
+                break;
+            }
+        }
+        return Ok::<Vec<u8>, io::Error>(head);
+    };
+    let head = match within(timeout, reading).await {
+        Ok(head) => head?,
+        Err(_) => {
+            return Err(error::WebServerError::IO(io::Error::new(
+                io::ErrorKind::TimedOut,
+                "Timed out while reading the request head",
+            )));
+        }
+    };
+    return Ok(Some((head, received_at)));
+}
+
+// writes the response to a request which won't be handled, the connection is being dropped anyway
+// so a failed write doesn't matter here
+async fn reject<S: AsyncRead + AsyncWrite + Unpin>(
+    reader: &mut BufReader<S>,
+    response: response::Response,
+) {
+    let stream = reader.get_mut();
+    let _ = stream.write_all(response.to_string().as_bytes()).await;
+    let _ = stream.flush().await;
+}
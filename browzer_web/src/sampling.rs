@@ -0,0 +1,200 @@
+//! This module profiles a sample of the requests a server answers, breaking their latency down
+//! into the phases of serving them, see `WebServer::sample_requests`.
+//!
+//! Only the sampled requests are timed beyond what the server does anyway, so a small sample rate
+//! keeps the overhead negligible in production while still showing where the time goes.
+
+// internal crate imports
+use crate::utils;
+
+// standard library imports
+use std::{
+    fmt,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+    time::{Duration, Instant, SystemTime},
+};
+
+/// A boxed sink which receives the profiles of the sampled requests.
+pub type ProfileSink = Box<dyn Fn(&RequestProfile) + 'static + Send + Sync>;
+
+/// The phase breakdown of a sampled request.
+///
+/// # Fields
+///
+/// - `timestamp` - When the request was received.
+/// - `method` - The method of the request.
+/// - `path` - The path of the request, without the query string.
+/// - `status` - The status code of the response.
+/// - `queue` - The time the connection waited for a worker thread after it was accepted, only for
+///   the first request of a connection.
+/// - `parse` - The time from the first byte of the request until it's head and body were read.
+/// - `middleware` - The time spent generating the response outside of the route handler, routing,
+///   middlewares, access control policies and compression included.
+/// - `handler` - The time spent in the route handler, zero if the request didn't reach one.
+/// - `serialize` - The time spent turning the response into it's HTTP representation.
+/// - `write` - The time spent writing the response to the connection.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RequestProfile {
+    pub timestamp: SystemTime,
+    pub method: utils::HttpMethod,
+    pub path: String,
+    pub status: u16,
+    pub queue: Duration,
+    pub parse: Duration,
+    pub middleware: Duration,
+    pub handler: Duration,
+    pub serialize: Duration,
+    pub write: Duration,
+}
+
+impl RequestProfile {
+    /// Returns the phases with their names, in the order they happen in.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use browzer_web::{sampling::RequestProfile, utils::HttpMethod};
+    /// use std::time::{Duration, SystemTime};
+    ///
+    /// let profile = RequestProfile {
+    ///     timestamp: SystemTime::now(),
+    ///     method: HttpMethod::GET,
+    ///     path: "/users".to_string(),
+    ///     status: 200,
+    ///     queue: Duration::ZERO,
+    ///     parse: Duration::from_micros(40),
+    ///     middleware: Duration::from_micros(120),
+    ///     handler: Duration::from_millis(3),
+    ///     serialize: Duration::from_micros(15),
+    ///     write: Duration::from_micros(60),
+    /// };
+    ///
+    /// let slowest = profile.phases().into_iter().max_by_key(|(_, time)| *time).unwrap();
+    /// assert_eq!(slowest.0, "handler");
+    /// assert_eq!(profile.total(), Duration::from_micros(3235));
+    /// ```
+    pub fn phases(&self) -> [(&'static str, Duration); 6] {
+        return [
+            ("queue", self.queue),
+            ("parse", self.parse),
+            ("middleware", self.middleware),
+            ("handler", self.handler),
+            ("serialize", self.serialize),
+            ("write", self.write),
+        ];
+    }
+
+    /// Returns the sum of all phases.
+    pub fn total(&self) -> Duration {
+        return self.phases().iter().map(|(_, time)| *time).sum();
+    }
+}
+
+/// Samples a fraction of the requests and passes their `RequestProfile` to a sink, see
+/// `WebServer::sample_requests`.
+///
+/// Requests are sampled evenly rather than randomly, with a rate of `0.01` every hundredth request
+/// is profiled.
+///
+/// # Examples
+///
+/// ```rust
+/// use browzer_web::sampling::Sampler;
+///
+/// let sampler = Sampler::new(0.25, |profile| {
+///     eprintln!("{} {} took {:?}", profile.method.to_string(), profile.path, profile.total());
+/// });
+///
+/// let sampled = (0..100).filter(|_| sampler.sample()).count();
+/// assert_eq!(sampled, 25);
+/// ```
+// ----- Sampler struct
+pub struct Sampler {
+    rate: f64,
+    sink: ProfileSink,
+    requests: AtomicU64,
+}
+
+impl fmt::Debug for Sampler {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Sampler")
+            .field("rate", &self.rate)
+            .field(
+                "sink",
+                &"Box<dyn Fn(&RequestProfile) + 'static + Send + Sync>",
+            )
+            .field("requests", &self.requests)
+            .finish()
+    }
+}
+
+impl Sampler {
+    /// Creates a new `Sampler`.
+    ///
+    /// # Arguments
+    ///
+    /// - `rate` - The fraction of requests to profile, from `0.0`(none) to `1.0`(all). Values
+    ///   outside of that range are clamped to it.
+    /// - `sink` - A closure receiving the `RequestProfile` of every sampled request. It runs on the
+    ///   worker thread after the response was written, so it should hand the profile off rather
+    ///   than do slow work.
+    pub fn new<F>(rate: f64, sink: F) -> Sampler
+    where
+        F: Fn(&RequestProfile) + 'static + Send + Sync,
+    {
+        let rate = match rate.is_nan() {
+            true => 0.0,
+            false => rate.clamp(0.0, 1.0),
+        };
+        return Sampler {
+            rate,
+            sink: Box::new(sink),
+            requests: AtomicU64::new(0),
+        };
+    }
+
+    /// Counts a request and returns whether it is sampled.
+    pub fn sample(&self) -> bool {
+        let count = self.requests.fetch_add(1, Ordering::Relaxed) as f64;
+        // a request is sampled whenever the expected number of samples passes a whole number
+        return ((count + 1.0) * self.rate).floor() > (count * self.rate).floor();
+    }
+
+    /// Passes the profile of a sampled request to the sink.
+    pub fn record(&self, profile: &RequestProfile) {
+        (self.sink)(profile);
+    }
+}
+
+// when the route handler of a sampled request started and finished, shared with the router
+// because the handler may run on another thread(like a blocking one of the tokio runtime)
+#[derive(Debug, Clone, Default)]
+pub(crate) struct HandlerMarks(Arc<Mutex<(Option<Instant>, Option<Instant>)>>);
+
+impl HandlerMarks {
+    pub(crate) fn start(&self) {
+        if let Ok(mut marks) = self.0.lock() {
+            marks.0 = Some(Instant::now());
+        }
+    }
+
+    pub(crate) fn finish(&self) {
+        if let Ok(mut marks) = self.0.lock() {
+            marks.1 = Some(Instant::now());
+        }
+    }
+
+    // the time spent in the handler, zero if it didn't run or hasn't finished
+    pub(crate) fn elapsed(&self) -> Duration {
+        match self.0.lock() {
+            Ok(marks) => match *marks {
+                (Some(start), Some(finish)) => return finish.saturating_duration_since(start),
+                _ => return Duration::ZERO,
+            },
+            Err(_) => return Duration::ZERO,
+        }
+    }
+}
@@ -0,0 +1,393 @@
+//! This module provides server-side sessions, which keep per-visitor state(like the logged in
+//! user) between requests.
+//!
+//! The data of a session is kept in a `SessionStore`, the client only gets a random session id in
+//! a cookie. Sessions are enabled with `WebServer::sessions`, after which every handler and
+//! middleware can use the session of it's request through `Context::session`. A session is only
+//! stored(and it's cookie only sent) once something was put into it, so anonymous visitors don't
+//! fill up the store.
+
+// external crate imports
+use uuid::Uuid;
+
+// internal crate imports
+use crate::{request, response, utils};
+
+// standard library imports
+use std::{
+    collections::HashMap,
+    fmt,
+    sync::{Arc, Mutex, MutexGuard},
+    time::{Duration, Instant},
+};
+
+/// The name of the cookie carrying the session id by default.
+pub const DEFAULT_COOKIE_NAME: &str = "browzer_session";
+
+// how often a `MemorySessionStore` purges it's expired sessions at most
+const PURGE_INTERVAL: Duration = Duration::from_secs(60);
+
+/// The data of a session, string keys mapped to string values.
+pub type SessionData = HashMap<String, String>;
+
+/// A storage backend for sessions.
+///
+/// Implement this trait to keep sessions somewhere other than the memory of the current process,
+/// like a database shared by multiple server instances or surviving restarts.
+pub trait SessionStore: Send + Sync {
+    /// Returns the data stored for the session `id`, or `None` if there is no such session or it
+    /// has expired.
+    fn load(&self, id: &str) -> Option<SessionData>;
+
+    /// Stores the data of the session `id`, replacing any existing data, and makes it expire after
+    /// `ttl` without being stored again.
+    fn save(&self, id: &str, data: SessionData, ttl: Duration);
+
+    /// Removes the session `id`.
+    fn remove(&self, id: &str);
+}
+
+/// An in-memory `SessionStore` implementation.
+///
+/// Expired sessions are purged lazily when a new session is saved, at most once a minute, so
+/// saving a session doesn't sweep the whole store on every request.
+///
+/// # Examples
+///
+/// ```rust
+/// use browzer_web::sessions::{MemorySessionStore, SessionData, SessionStore};
+/// use std::time::Duration;
+///
+/// let store = MemorySessionStore::new();
+/// let data = SessionData::from([("user".to_string(), "axew".to_string())]);
+///
+/// store.save("id", data.clone(), Duration::from_secs(60));
+/// assert_eq!(store.load("id"), Some(data));
+/// ```
+// ----- MemorySessionStore struct
+#[derive(Debug, Default)]
+pub struct MemorySessionStore {
+    sessions: Mutex<StoredSessions>,
+}
+
+// the sessions of a `MemorySessionStore` with the time they expire at, and when the expired ones
+// were purged last
+#[derive(Debug, Default)]
+struct StoredSessions {
+    map: HashMap<String, (SessionData, Instant)>,
+    purged_at: Option<Instant>,
+}
+
+impl MemorySessionStore {
+    /// Creates a new, empty `MemorySessionStore`.
+    pub fn new() -> MemorySessionStore {
+        return MemorySessionStore::default();
+    }
+
+    /// Returns the number of sessions in the store, expired ones which weren't purged yet included.
+    pub fn len(&self) -> usize {
+        return self.lock().map.len();
+    }
+
+    /// Returns whether the store holds no sessions.
+    pub fn is_empty(&self) -> bool {
+        return self.len() == 0;
+    }
+
+    fn lock(&self) -> MutexGuard<'_, StoredSessions> {
+        match self.sessions.lock() {
+            Ok(sessions) => return sessions,
+            Err(poisoned) => return poisoned.into_inner(),
+        }
+    }
+}
+
+impl SessionStore for MemorySessionStore {
+    fn load(&self, id: &str) -> Option<SessionData> {
+        match self.lock().map.get(id) {
+            Some((data, expires_at)) if *expires_at > Instant::now() => return Some(data.clone()),
+            _ => return None,
+        }
+    }
+
+    fn save(&self, id: &str, data: SessionData, ttl: Duration) {
+        let mut sessions = self.lock();
+        let now = Instant::now();
+        let purge_due = sessions
+            .purged_at
+            .is_none_or(|purged_at| now.saturating_duration_since(purged_at) >= PURGE_INTERVAL);
+        if purge_due && !sessions.map.contains_key(id) {
+            sessions.map.retain(|_, (_, expires_at)| *expires_at > now);
+            sessions.purged_at = Some(now);
+        }
+        sessions.map.insert(id.to_string(), (data, now + ttl));
+    }
+
+    fn remove(&self, id: &str) {
+        self.lock().map.remove(id);
+    }
+}
+
+/// The session of a request, see `Context::session`.
+///
+/// A `Session` is a handle, clones of it share the same data. Changes are written to the
+/// `SessionStore` once the response was generated.
+///
+/// # Examples
+///
+/// ```rust
+/// use browzer_web::sessions::Session;
+///
+/// let session = Session::default();
+/// session.set("user", "axew");
+///
+/// assert_eq!(session.get("user"), Some("axew".to_string()));
+/// assert_eq!(session.remove("user"), Some("axew".to_string()));
+/// assert!(session.is_empty());
+/// ```
+// ----- Session struct
+#[derive(Debug, Clone, Default)]
+pub struct Session {
+    state: Arc<Mutex<SessionState>>,
+}
+
+// the shared state behind a session handle
+#[derive(Debug, Default)]
+struct SessionState {
+    // the id the client sent, `None` for a session which wasn't stored yet
+    id: Option<String>,
+    data: SessionData,
+    changed: bool,
+    regenerate: bool,
+    destroyed: bool,
+}
+
+impl Session {
+    /// Returns the id of the session, `None` if it wasn't stored yet.
+    pub fn id(&self) -> Option<String> {
+        return self.lock().id.clone();
+    }
+
+    /// Returns the value stored under a key.
+    pub fn get(&self, key: &str) -> Option<String> {
+        return self.lock().data.get(key).cloned();
+    }
+
+    /// Stores a value under a key, replacing the previous value.
+    pub fn set(&self, key: &str, value: &str) {
+        let mut state = self.lock();
+        state.data.insert(key.to_string(), value.to_string());
+        state.changed = true;
+    }
+
+    /// Removes the value stored under a key.
+    ///
+    /// # Returns
+    ///
+    /// - `Option<String>` - The removed value, `None` if the key wasn't set.
+    pub fn remove(&self, key: &str) -> Option<String> {
+        let mut state = self.lock();
+        let value = state.data.remove(key);
+        if value.is_some() {
+            state.changed = true;
+        }
+        return value;
+    }
+
+    /// Removes all values of the session.
+    pub fn clear(&self) {
+        let mut state = self.lock();
+        if !state.data.is_empty() {
+            state.data.clear();
+            state.changed = true;
+        }
+    }
+
+    /// Returns whether the session holds no values.
+    pub fn is_empty(&self) -> bool {
+        return self.lock().data.is_empty();
+    }
+
+    /// Gives the session a new id, keeping it's values.
+    ///
+    /// Call this whenever the privileges of the visitor change, most importantly right after they
+    /// logged in, so that a session id an attacker planted in the browser before(session
+    /// fixation) doesn't become a logged in session.
+    pub fn regenerate(&self) {
+        let mut state = self.lock();
+        state.regenerate = true;
+        state.changed = true;
+    }
+
+    /// Ends the session, like when the visitor logs out.
+    ///
+    /// The session is removed from the store and it's cookie is expired.
+    pub fn destroy(&self) {
+        let mut state = self.lock();
+        state.data.clear();
+        state.destroyed = true;
+        state.changed = true;
+    }
+
+    fn lock(&self) -> MutexGuard<'_, SessionState> {
+        match self.state.lock() {
+            Ok(state) => return state,
+            Err(poisoned) => return poisoned.into_inner(),
+        }
+    }
+}
+
+/// Configuration of the sessions of a `WebServer`, see `WebServer::sessions`.
+///
+/// Sessions expire after being unused for `ttl`, every request using a session extends it. The
+/// session cookie is `HttpOnly` and `SameSite=Lax` by default.
+///
+/// # Examples
+///
+/// ```rust,no_run
+/// use browzer_web::{sessions::{MemorySessionStore, Sessions}, utils::HttpStatusCode, WebServer};
+/// use std::time::Duration;
+///
+/// let mut server = WebServer::new("127.0.0.1:8080".to_string(), 4);
+/// server.sessions(Sessions::new(MemorySessionStore::new(), Duration::from_secs(60 * 60)).secure(true));
+///
+/// server.post("/login", |mut c| {
+///     // after checking the credentials
+///     c.session().regenerate();
+///     c.session().set("user", "axew");
+///     return c.send_string(HttpStatusCode::OK, "Logged in");
+/// });
+/// server.get("/me", |mut c| match c.session().get("user") {
+///     Some(user) => return c.send_string(HttpStatusCode::OK, &user),
+///     None => return c.send_string(HttpStatusCode::Unauthorized, "Not logged in"),
+/// });
+/// ```
+// ----- Sessions struct
+#[derive(Clone)]
+pub struct Sessions {
+    store: Arc<dyn SessionStore>,
+    ttl: Duration,
+    cookie_name: String,
+    path: String,
+    secure: bool,
+    same_site: utils::SameSite,
+}
+
+impl fmt::Debug for Sessions {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Sessions")
+            .field("store", &"Arc<dyn SessionStore>")
+            .field("ttl", &self.ttl)
+            .field("cookie_name", &self.cookie_name)
+            .field("path", &self.path)
+            .field("secure", &self.secure)
+            .field("same_site", &self.same_site)
+            .finish()
+    }
+}
+
+impl Sessions {
+    /// Creates a new `Sessions` configuration backed by the given store.
+    ///
+    /// # Arguments
+    ///
+    /// - `store` - The `SessionStore` in which sessions are kept.
+    /// - `ttl` - A `Duration` after which an unused session expires.
+    pub fn new<S>(store: S, ttl: Duration) -> Sessions
+    where
+        S: SessionStore + 'static,
+    {
+        return Sessions {
+            store: Arc::new(store),
+            ttl,
+            cookie_name: DEFAULT_COOKIE_NAME.to_string(),
+            path: "/".to_string(),
+            secure: false,
+            same_site: utils::SameSite::Lax,
+        };
+    }
+
+    /// Sets the name of the cookie carrying the session id, `DEFAULT_COOKIE_NAME` by default.
+    pub fn cookie_name(mut self, cookie_name: &str) -> Sessions {
+        self.cookie_name = cookie_name.to_string();
+        return self;
+    }
+
+    /// Sets the path of the session cookie, `/` by default.
+    pub fn path(mut self, path: &str) -> Sessions {
+        self.path = path.to_string();
+        return self;
+    }
+
+    /// Sets whether the session cookie is only sent over HTTPS, which it should be whenever the
+    /// server is reachable over HTTPS.
+    pub fn secure(mut self, secure: bool) -> Sessions {
+        self.secure = secure;
+        return self;
+    }
+
+    /// Sets the `SameSite` attribute of the session cookie, `SameSite::Lax` by default.
+    pub fn same_site(mut self, same_site: utils::SameSite) -> Sessions {
+        self.same_site = same_site;
+        return self;
+    }
+
+    /// Loads the session of a request from the store, an empty session if the request has no
+    /// session cookie or it's session expired.
+    pub fn load(&self, request: &request::Request) -> Session {
+        let session = Session::default();
+        let id = match request.cookies.get(&self.cookie_name) {
+            Some(cookie) if !cookie.value.is_empty() => cookie.value.clone(),
+            _ => return session,
+        };
+        if let Some(data) = self.store.load(&id) {
+            let mut state = session.lock();
+            state.id = Some(id);
+            state.data = data;
+        }
+        return session;
+    }
+
+    /// Writes the changes of a session to the store and sets the session cookie on the response.
+    pub fn commit(&self, session: &Session, response: &mut response::Response) {
+        let mut state = session.lock();
+        if state.destroyed || (state.changed && state.data.is_empty()) {
+            // an emptied session is ended, so that it's id can't be used anymore
+            if let Some(id) = state.id.take() {
+                self.store.remove(&id);
+                self.set_cookie(response, "", 0);
+            }
+            return;
+        }
+        if state.regenerate {
+            if let Some(id) = state.id.take() {
+                self.store.remove(&id);
+            }
+        }
+        if state.id.is_none() && state.data.is_empty() {
+            return;
+        }
+        let id = match state.id {
+            Some(ref id) => id.clone(),
+            None => Uuid::new_v4().simple().to_string(),
+        };
+
+        // every request using a session extends it, in the store as well as in the browser
+        self.store.save(&id, state.data.clone(), self.ttl);
+        self.set_cookie(response, &id, self.ttl.as_secs() as i64);
+        state.id = Some(id);
+        state.changed = false;
+        state.regenerate = false;
+    }
+
+    fn set_cookie(&self, response: &mut response::Response, value: &str, max_age: i64) {
+        let cookie = utils::Cookie::builder(&self.cookie_name, value)
+            .path(&self.path)
+            .max_age(max_age)
+            .secure(self.secure)
+            .http_only(true)
+            .same_site(self.same_site)
+            .build();
+        response.cookies.insert(self.cookie_name.clone(), cookie);
+    }
+}
@@ -0,0 +1,642 @@
+//! This module serves the files of a directory, with the validators and caching directives which
+//! let browsers and proxies reuse the copies they already have.
+//!
+//! Every file is sent with an `ETag` and a `Last-Modified` header derived from it's metadata, so no
+//! file is read just to answer a revalidation. Requests whose `If-None-Match` or
+//! `If-Modified-Since` header matches the current file get an empty `304 Not Modified` response
+//! instead of the file, see `StaticFiles`.
+//!
+//! A request for a directory is answered with it's index file(`index.html`), or with a generated
+//! listing of the directory if that is enabled.
+//!
+//! The files don't have to be on disk: `EmbeddedAssets` holds files compiled into the binary(like
+//! with `include_bytes!`, or collected from an `include_dir!` directory) which are served the same
+//! way, so single-binary deployments don't need a static directory next to them.
+
+// internal crate imports
+use crate::{conditional, context, digest, error, pages, response, utils};
+
+// standard library imports
+use std::{
+    borrow::Cow,
+    collections::{BTreeMap, HashMap},
+    fmt, fs,
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::SystemTime,
+};
+
+/// The index file served for a directory by default.
+pub const DEFAULT_INDEX: &str = "index.html";
+
+/// Serves the files of a directory, see `WebServer::serve_static_files`.
+///
+/// The requested file is taken from the `filename` route parameter(which may span several path
+/// segments) and resolved using `utils::resolve_static_path`, then streamed using
+/// `Context::send_file`(so range requests work too). Before that:
+///
+/// - the `ETag` header is set to a strong entity tag made of the size and the modification time
+///   of the file, and the `Last-Modified` header to the modification time.
+/// - `If-Match` and `If-None-Match` preconditions are evaluated against the entity tag, see
+///   `conditional::evaluate_preconditions`, and `If-Modified-Since` against the modification time,
+///   see `conditional::not_modified_since`.
+/// - the `Cache-Control` header is set to the configured directives, if any. It is sent with
+///   `304 Not Modified` responses as well, so revalidating a copy extends it's freshness.
+///
+/// A request for a directory(or for the route path itself, the served directory) is answered
+/// with the directory's index file, `DEFAULT_INDEX` by default. Without an index file it gets a
+/// `404 Not Found` response, or a generated HTML page listing the entries of the directory if
+/// listings are enabled. Hidden entries(starting with a `.`) aren't listed. A directory requested
+/// without a trailing slash is redirected to it's path with one(`301 Moved Permanently`), so
+/// relative links in it's index file resolve against the directory itself. An index file which is
+/// a symbolic link out of the served directory gets a `403 Forbidden` response.
+///
+/// Embedded assets(see `StaticFiles::embedded`) are served the same way, with an `ETag` made of a
+/// hash of their content and a `Last-Modified` header only if `EmbeddedAssets::modified` was set.
+///
+/// # Examples
+///
+/// ```rust,no_run
+/// use browzer_web::{static_files::StaticFiles, WebServer};
+///
+/// let mut server = WebServer::new("127.0.0.1:8080".to_string(), 4);
+///
+/// // fingerprinted assets never change, everything else is revalidated on every use
+/// server.serve_static_files(
+///     "/assets",
+///     StaticFiles::new("static/assets").cache_control("public, max-age=31536000, immutable"),
+/// );
+/// server.serve_static_files("/files", StaticFiles::new("static/files").cache_control("no-cache"));
+/// server.listen();
+/// ```
+// ----- StaticFiles struct
+#[derive(Debug, Clone)]
+pub struct StaticFiles {
+    root: PathBuf,
+    cache_control: Option<String>,
+    etag: bool,
+    last_modified: bool,
+    index: Option<String>,
+    listing: bool,
+    page_templates: pages::PageTemplates,
+    embedded: Option<Arc<EmbeddedAssets>>,
+}
+
+impl StaticFiles {
+    /// Creates a new `StaticFiles` serving the files of a directory, with `ETag` and
+    /// `Last-Modified` validators, without a `Cache-Control` header and without directory
+    /// listings.
+    pub fn new(dir_path: &str) -> StaticFiles {
+        return StaticFiles {
+            root: PathBuf::from(dir_path),
+            cache_control: None,
+            etag: true,
+            last_modified: true,
+            index: Some(DEFAULT_INDEX.to_string()),
+            listing: false,
+            page_templates: pages::PageTemplates::default(),
+            embedded: None,
+        };
+    }
+
+    /// Creates a new `StaticFiles` serving files held in memory instead of a directory, configured
+    /// like the one `StaticFiles::new` creates.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// use browzer_web::{
+    ///     static_files::{EmbeddedAssets, StaticFiles},
+    ///     WebServer,
+    /// };
+    ///
+    /// let assets = EmbeddedAssets::new()
+    ///     .file("index.html", &b"<h1>Hello!</h1>"[..])
+    ///     .file("css/site.css", &b"h1 { color: teal; }"[..]);
+    ///
+    /// let mut server = WebServer::new("127.0.0.1:8080".to_string(), 4);
+    /// server.serve_static_files("/", StaticFiles::embedded(assets));
+    /// server.listen();
+    /// ```
+    pub fn embedded(assets: EmbeddedAssets) -> StaticFiles {
+        let mut files = StaticFiles::new("");
+        files.embedded = Some(Arc::new(assets));
+        return files;
+    }
+
+    /// Sets the `Cache-Control` directives sent with every file, like `public, max-age=3600`.
+    pub fn cache_control(mut self, directives: &str) -> StaticFiles {
+        self.cache_control = Some(directives.to_string());
+        return self;
+    }
+
+    /// Sets whether files are sent with an `ETag` header, and `If-Match` / `If-None-Match`
+    /// preconditions are evaluated. Enabled by default.
+    pub fn etag(mut self, enabled: bool) -> StaticFiles {
+        self.etag = enabled;
+        return self;
+    }
+
+    /// Sets whether files are sent with a `Last-Modified` header, and `If-Modified-Since`
+    /// preconditions are evaluated. Enabled by default.
+    pub fn last_modified(mut self, enabled: bool) -> StaticFiles {
+        self.last_modified = enabled;
+        return self;
+    }
+
+    /// Sets the name of the index file served for a directory, `None` serves no index files.
+    pub fn index(mut self, index: Option<&str>) -> StaticFiles {
+        self.index = index.map(|index| index.to_string());
+        return self;
+    }
+
+    /// Sets whether directories without an index file are answered with a generated listing of
+    /// their entries. Disabled by default, since it reveals every file in the directory.
+    pub fn listing(mut self, enabled: bool) -> StaticFiles {
+        self.listing = enabled;
+        return self;
+    }
+
+    /// Sets the `PageTemplates` directory listings are rendered with, the default ones by
+    /// default.
+    pub fn page_templates(mut self, templates: pages::PageTemplates) -> StaticFiles {
+        self.page_templates = templates;
+        return self;
+    }
+
+    /// Returns the directory the files are served from, empty for embedded assets.
+    pub fn root(&self) -> &Path {
+        return &self.root;
+    }
+
+    /// Returns the embedded assets served, `None` if the files are served from a directory.
+    pub fn assets(&self) -> Option<&EmbeddedAssets> {
+        return self.embedded.as_deref();
+    }
+
+    /// Answers a request for a file or directory, the requested path is taken from the
+    /// `filename` route parameter and the served directory itself is requested without it.
+    pub fn handle(&self, mut c: context::Context) -> response::Response {
+        // the filename is decoded while resolving it, so the raw value is used
+        let filename = c.raw_params.get("filename").cloned().unwrap_or_default();
+        if let Some(ref assets) = self.embedded {
+            return self.handle_embedded(c, assets, &filename);
+        }
+        // the filename comes straight from the request, so it is resolved in a way which can't
+        // escape the served directory(like `..%2F..%2Fetc%2Fpasswd` would)
+        let path = match utils::resolve_static_entry(&self.root, &filename) {
+            Ok(path) => path,
+            Err(e) => return c.error_response(e.status_code()),
+        };
+        if path.is_file() {
+            return self.send(&mut c, &path);
+        }
+        if !path.is_dir() {
+            return c.error_response(utils::HttpStatusCode::NotFound);
+        }
+        if !c.request.trailing_slash {
+            return redirect_to_directory(&mut c);
+        }
+        match self.index_file(&path) {
+            Ok(Some(index_path)) => return self.send(&mut c, &index_path),
+            Ok(None) if self.listing => return self.list_dir(&mut c, &path, filename.is_empty()),
+            Ok(None) => return c.error_response(utils::HttpStatusCode::NotFound),
+            Err(e) => return c.error_response(e.status_code()),
+        }
+    }
+
+    /// Sends a file with the validators and caching directives of this `StaticFiles`, answering
+    /// the request with `304 Not Modified`(or `412 Precondition Failed`) if it's preconditions
+    /// say so.
+    ///
+    /// # Arguments
+    ///
+    /// - `c` - The context of the request.
+    /// - `path` - The path of the file to send, it isn't checked against the served directory.
+    pub fn send(&self, c: &mut context::Context, path: &Path) -> response::Response {
+        // files which can't be inspected are left to `send_file`, which answers with the error
+        let metadata = match fs::metadata(path) {
+            Ok(metadata) if metadata.is_file() => metadata,
+            _ => return c.send_file(path),
+        };
+        let modified = metadata.modified().ok();
+        let etag = file_etag(metadata.len(), modified);
+        if let Some(not_modified) = self.revalidate(c, &etag, modified) {
+            return not_modified;
+        }
+        return c.send_file(path);
+    }
+
+    // answers a request for an embedded file or directory, like `handle` does for the files on
+    // disk
+    fn handle_embedded(
+        &self,
+        mut c: context::Context,
+        assets: &EmbeddedAssets,
+        filename: &str,
+    ) -> response::Response {
+        let path = match resolve_asset(filename) {
+            Ok(path) => path,
+            Err(e) => return c.error_response(e.status_code()),
+        };
+        if assets.files.contains_key(&path) {
+            return self.send_embedded(&mut c, assets, &path);
+        }
+        if !assets.is_dir(&path) {
+            return c.error_response(utils::HttpStatusCode::NotFound);
+        }
+        if !c.request.trailing_slash {
+            return redirect_to_directory(&mut c);
+        }
+        if let Some(ref index) = self.index {
+            let index_path = match path.is_empty() {
+                true => index.clone(),
+                false => format!("{}/{}", path, index),
+            };
+            if assets.files.contains_key(&index_path) {
+                return self.send_embedded(&mut c, assets, &index_path);
+            }
+        }
+        match self.listing {
+            true => return self.render_listing(&mut c, assets.entries(&path), filename.is_empty()),
+            false => return c.error_response(utils::HttpStatusCode::NotFound),
+        }
+    }
+
+    // resolves the index file of a directory on disk, `None` if it has none. A symbolic link
+    // pointing the index file out of the served directory gets the request rejected, instead of
+    // falling back to the listing
+    fn index_file(&self, dir: &Path) -> Result<Option<PathBuf>, error::StaticFileError> {
+        let index = match self.index {
+            Some(ref index) => dir.join(index),
+            None => return Ok(None),
+        };
+        if fs::symlink_metadata(&index).is_err() {
+            return Ok(None);
+        }
+        let root = match self.root.canonicalize() {
+            Ok(root) => root,
+            Err(_) => return Err(error::StaticFileError::NotFound),
+        };
+        match index.canonicalize() {
+            Ok(index) if !index.starts_with(&root) => {
+                return Err(error::StaticFileError::Forbidden)
+            }
+            Ok(index) if index.is_file() => return Ok(Some(index)),
+            // a dangling link, or a directory named like the index file
+            _ => return Ok(None),
+        }
+    }
+
+    // sends an embedded file with the validators and caching directives of this `StaticFiles`
+    fn send_embedded(
+        &self,
+        c: &mut context::Context,
+        assets: &EmbeddedAssets,
+        path: &str,
+    ) -> response::Response {
+        let file = match assets.files.get(path) {
+            Some(file) => file,
+            None => return c.error_response(utils::HttpStatusCode::NotFound),
+        };
+        if let Some(not_modified) = self.revalidate(c, &file.etag, assets.modified) {
+            return not_modified;
+        }
+        return c.send_bytes(path, file.bytes.clone());
+    }
+
+    // sets the validators and caching directives of a file on the response, and answers the
+    // request right away if it's preconditions say so
+    fn revalidate(
+        &self,
+        c: &mut context::Context,
+        etag: &conditional::EntityTag,
+        modified: Option<SystemTime>,
+    ) -> Option<response::Response> {
+        if let Some(ref directives) = self.cache_control {
+            c.response
+                .headers
+                .insert("Cache-Control".to_string(), directives.clone());
+        }
+        if let (true, Some(modified)) = (self.last_modified, modified) {
+            c.response
+                .headers
+                .insert("Last-Modified".to_string(), utils::http_date(modified));
+        }
+        if self.etag {
+            if let Some(precondition_failed) = c.check_preconditions(Some(etag)) {
+                return Some(precondition_failed);
+            }
+        }
+        if let (true, Some(modified)) = (self.last_modified, modified) {
+            if conditional::not_modified_since(&c.request, modified) {
+                return Some(c.send_string(utils::HttpStatusCode::NotModified, ""));
+            }
+        }
+        return None;
+    }
+
+    // lists the entries of a directory on disk
+    fn list_dir(&self, c: &mut context::Context, dir: &Path, is_root: bool) -> response::Response {
+        let entries = match fs::read_dir(dir) {
+            Ok(entries) => entries
+                .filter_map(|entry| entry.ok())
+                .filter_map(|entry| {
+                    let name = entry.file_name().into_string().ok()?;
+                    let is_dir = entry.file_type().ok()?.is_dir();
+                    return Some((name, is_dir));
+                })
+                .collect::<Vec<_>>(),
+            Err(e) => {
+                eprintln!("Error while listing the directory {:?}: {}", dir, e);
+                return c.error_response(utils::HttpStatusCode::InternalServerError);
+            }
+        };
+        return self.render_listing(c, entries, is_root);
+    }
+
+    // renders the listing page of a directory, linking the entries relative to the request path
+    fn render_listing(
+        &self,
+        c: &mut context::Context,
+        entries: Vec<(String, bool)>,
+        is_root: bool,
+    ) -> response::Response {
+        let mut entries = entries
+            .into_iter()
+            .filter(|(name, _)| !name.starts_with('.'))
+            .collect::<Vec<_>>();
+        // directories first, then by name
+        entries.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+
+        let path = c.request.path.split('?').next().unwrap_or_default();
+        let path = match path.is_empty() {
+            true => "/",
+            false => path,
+        };
+        let base = path.trim_end_matches('/');
+        let mut content = format!("<h1>Index of {}</h1>\n<ul>\n", utils::escape_html(path));
+        if !is_root {
+            let parent = match base.rfind('/') {
+                Some(0) | None => "/",
+                Some(end) => &base[..end],
+            };
+            content.push_str(&format!(
+                "<li><a href=\"{}/\">../</a></li>\n",
+                utils::escape_html(parent.trim_end_matches('/'))
+            ));
+        }
+        for (name, is_dir) in entries {
+            // directories are linked with their trailing slash, saving the redirect to it
+            let suffix = if is_dir { "/" } else { "" };
+            content.push_str(&format!(
+                "<li><a href=\"{}/{}{}\">{}{}</a></li>\n",
+                utils::escape_html(base),
+                utils::percent_encode(&name),
+                suffix,
+                utils::escape_html(&name),
+                suffix
+            ));
+        }
+        content.push_str("</ul>\n");
+
+        let body = self
+            .page_templates
+            .render(&format!("Index of {}", path), &content);
+        c.response.headers.insert(
+            "Content-Type".to_string(),
+            "text/html; charset=utf-8".to_string(),
+        );
+        return c.send_string(utils::HttpStatusCode::OK, &body);
+    }
+}
+
+// an embedded file, with the entity tag of it's content
+#[derive(Clone)]
+struct EmbeddedFile {
+    bytes: EmbeddedBytes,
+    etag: conditional::EntityTag,
+}
+
+// the shared content of an embedded file, borrowed from the binary or owned
+#[derive(Clone)]
+struct EmbeddedBytes(Arc<Cow<'static, [u8]>>);
+
+impl AsRef<[u8]> for EmbeddedBytes {
+    fn as_ref(&self) -> &[u8] {
+        return &self.0;
+    }
+}
+
+/// Files held in memory, served like the files of a directory, see `StaticFiles::embedded`.
+///
+/// Files are stored under their path relative to the served directory, with `/` separating the
+/// directories(leading slashes and `.` segments are dropped). Directories exist implicitly, as
+/// the parents of the files. The content can be borrowed for the lifetime of the program(like the
+/// output of `include_bytes!` or `include_dir!`) or owned, and isn't copied either way.
+///
+/// # Examples
+///
+/// ```rust
+/// use browzer_web::static_files::EmbeddedAssets;
+/// use std::collections::HashMap;
+///
+/// let assets = EmbeddedAssets::new()
+///     // the files are usually compiled in, like `include_bytes!("../static/app.js")`
+///     .file("app.js", &b"console.log('hello');"[..])
+///     .file("/img/logo.svg", b"<svg></svg>".to_vec());
+/// assert_eq!(assets.get("img/logo.svg"), Some(&b"<svg></svg>"[..]));
+///
+/// // a map of paths to contents(like one generated by a build script) works too
+/// let mut generated: HashMap<String, Vec<u8>> = HashMap::new();
+/// generated.insert("robots.txt".to_string(), b"User-agent: *".to_vec());
+/// let assets: EmbeddedAssets = generated.into_iter().collect();
+/// assert_eq!(assets.len(), 1);
+/// ```
+///
+/// With the `include_dir` crate, the files of a whole directory can be collected:
+///
+/// ```rust,ignore
+/// use include_dir::{include_dir, Dir};
+///
+/// static STATIC: Dir = include_dir!("$CARGO_MANIFEST_DIR/static");
+///
+/// let assets = STATIC
+///     .find("**/*")
+///     .unwrap()
+///     .filter_map(|entry| entry.as_file())
+///     .map(|file| (file.path().to_string_lossy().to_string(), file.contents()))
+///     .collect::<EmbeddedAssets>();
+/// ```
+// ----- EmbeddedAssets struct
+#[derive(Clone, Default)]
+pub struct EmbeddedAssets {
+    files: HashMap<String, EmbeddedFile>,
+    modified: Option<SystemTime>,
+}
+
+impl fmt::Debug for EmbeddedAssets {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("EmbeddedAssets")
+            .field("files", &self.paths())
+            .field("modified", &self.modified)
+            .finish()
+    }
+}
+
+impl EmbeddedAssets {
+    /// Creates a new empty `EmbeddedAssets`.
+    pub fn new() -> EmbeddedAssets {
+        return EmbeddedAssets::default();
+    }
+
+    /// Adds a file, replacing the file at the same path.
+    ///
+    /// # Arguments
+    ///
+    /// - `path` - The path of the file relative to the served directory, like `css/site.css`.
+    /// - `bytes` - The content of the file, like a `&'static [u8]` or a `Vec<u8>`.
+    pub fn file<B: Into<Cow<'static, [u8]>>>(mut self, path: &str, bytes: B) -> EmbeddedAssets {
+        self.insert(path, bytes);
+        return self;
+    }
+
+    /// Adds a file like `EmbeddedAssets::file`, through a mutable reference.
+    pub fn insert<B: Into<Cow<'static, [u8]>>>(&mut self, path: &str, bytes: B) {
+        let path = match normalize_asset_path(path) {
+            Some(path) if !path.is_empty() => path,
+            _ => {
+                eprintln!("Warning: the embedded asset path {:?} is invalid", path);
+                return;
+            }
+        };
+        let bytes = bytes.into();
+        let hash = digest::sha256(&bytes)
+            .iter()
+            .take(8)
+            .map(|byte| format!("{:02x}", byte))
+            .collect::<String>();
+        self.files.insert(
+            path,
+            EmbeddedFile {
+                bytes: EmbeddedBytes(Arc::new(bytes)),
+                etag: conditional::EntityTag::strong(&hash),
+            },
+        );
+    }
+
+    /// Sets the modification time the files are served with, in the `Last-Modified` header. By
+    /// default they are served without one, since the time the binary was built isn't known.
+    pub fn modified(mut self, modified: SystemTime) -> EmbeddedAssets {
+        self.modified = Some(modified);
+        return self;
+    }
+
+    /// Returns the content of the file at a path, `None` if there is no such file.
+    pub fn get(&self, path: &str) -> Option<&[u8]> {
+        let path = normalize_asset_path(path)?;
+        return self.files.get(&path).map(|file| file.bytes.as_ref());
+    }
+
+    /// Returns the paths of all files, sorted.
+    pub fn paths(&self) -> Vec<&str> {
+        let mut paths = self
+            .files
+            .keys()
+            .map(|path| path.as_str())
+            .collect::<Vec<_>>();
+        paths.sort();
+        return paths;
+    }
+
+    /// Returns the number of files.
+    pub fn len(&self) -> usize {
+        return self.files.len();
+    }
+
+    /// Returns whether there are no files.
+    pub fn is_empty(&self) -> bool {
+        return self.files.is_empty();
+    }
+
+    // returns whether a normalized path is a directory, the parent of at least one file
+    fn is_dir(&self, path: &str) -> bool {
+        if path.is_empty() {
+            return true;
+        }
+        let prefix = format!("{}/", path);
+        return self.files.keys().any(|file| file.starts_with(&prefix));
+    }
+
+    // returns the names of the entries of a directory, and whether they are directories
+    fn entries(&self, path: &str) -> Vec<(String, bool)> {
+        let prefix = match path.is_empty() {
+            true => String::new(),
+            false => format!("{}/", path),
+        };
+        let mut entries = BTreeMap::new();
+        for file in self.files.keys() {
+            if let Some(rest) = file.strip_prefix(&prefix) {
+                match rest.split_once('/') {
+                    Some((dir, _)) => entries.insert(dir.to_string(), true),
+                    None => entries.insert(rest.to_string(), false),
+                };
+            }
+        }
+        return entries.into_iter().collect();
+    }
+}
+
+impl<P: AsRef<str>, B: Into<Cow<'static, [u8]>>> FromIterator<(P, B)> for EmbeddedAssets {
+    fn from_iter<I: IntoIterator<Item = (P, B)>>(iter: I) -> EmbeddedAssets {
+        let mut assets = EmbeddedAssets::new();
+        for (path, bytes) in iter {
+            assets.insert(path.as_ref(), bytes);
+        }
+        return assets;
+    }
+}
+
+// redirects a request for a directory to the path of the directory with a trailing slash, so
+// relative links in it's index file resolve against the directory itself
+fn redirect_to_directory(c: &mut context::Context) -> response::Response {
+    let location = match c.request.path.split_once('?') {
+        Some((path, query)) => format!("{}/?{}", path, query),
+        None => format!("{}/", c.request.path),
+    };
+    return c.redirect(utils::HttpStatusCode::MovedPermanently, &location);
+}
+
+// resolves a requested path to the normalized path of an embedded file or directory, like
+// `utils::resolve_static_entry` does for the files on disk
+fn resolve_asset(requested: &str) -> Result<String, error::StaticFileError> {
+    let requested = match utils::percent_decode(requested) {
+        Some(requested) if !requested.contains('\0') => requested,
+        _ => return Err(error::StaticFileError::NotFound),
+    };
+    return normalize_asset_path(&requested).ok_or(error::StaticFileError::Forbidden);
+}
+
+// normalizes a path of an embedded file to it's segments joined with `/`, `None` if it leaves the
+// served directory
+fn normalize_asset_path(path: &str) -> Option<String> {
+    let mut segments = vec![];
+    // backslashes are separators on windows, so they are treated as such everywhere
+    for segment in path.split(['/', '\\']) {
+        match segment {
+            "" | "." => {}
+            ".." => return None,
+            segment => segments.push(segment),
+        }
+    }
+    return Some(segments.join("/"));
+}
+
+// derives the entity tag of a file from it's size and modification time, like most web servers do
+fn file_etag(len: u64, modified: Option<SystemTime>) -> conditional::EntityTag {
+    let modified = modified
+        .and_then(|modified| modified.duration_since(SystemTime::UNIX_EPOCH).ok())
+        .map(|elapsed| elapsed.as_nanos())
+        .unwrap_or(0);
+    return conditional::EntityTag::strong(&format!("{:x}-{:x}", len, modified));
+}
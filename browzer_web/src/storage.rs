@@ -0,0 +1,592 @@
+//! This module provides `KvStore`, an embedded key-value store for small apps which need to keep
+//! a little data(like counters, settings or tokens) across restarts without running a database.
+//!
+//! The store keeps all entries in memory and persists them in a single append-only log file,
+//! every change is appended as a record before it is applied. Once the log consists mostly of
+//! overwritten and removed entries, it is compacted by rewriting the live entries into a new log
+//! which replaces the old one. A record at the end of the log which was only partly written(like
+//! when the process was killed in the middle of a write) is dropped, with a warning, when the store
+//! is opened again. A damaged record anywhere else fails opening the store instead, rather than
+//! silently losing the entries after it.
+//!
+//! The log is made of a header followed by records, each of them being:
+//!
+//! ```text
+//! +-----------+-------------------+---------------------+--------------+-----+-------+
+//! | operation | key length(u32le) | value length(u32le) | CRC-32(u32le)| key | value |
+//! +-----------+-------------------+---------------------+--------------+-----+-------+
+//! ```
+//!
+//! where the operation is `1` for setting and `2` for removing an entry, and the CRC-32 covers the
+//! operation, the lengths, the key and the value. Logs written before records had a checksum are
+//! rewritten in the current format when they are opened.
+
+// external crate imports
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+
+// internal crate imports
+use crate::sessions::{SessionData, SessionStore};
+
+// standard library imports
+use std::{
+    collections::HashMap,
+    fmt, fs,
+    io::{self, BufWriter, Read, Write},
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex, MutexGuard},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+// the header of a log file, identifying it and the version of the format
+const MAGIC: &[u8; 8] = b"BZKV\x00\x00\x00\x02";
+
+// the header of a log file in the first version of the format, with records without a checksum
+const MAGIC_V1: &[u8; 8] = b"BZKV\x00\x00\x00\x01";
+
+// the length of the head of a record, before it's key and value
+const RECORD_HEAD_LENGTH: u64 = 13;
+
+// the length of the head of a record in the first version of the format
+const RECORD_HEAD_LENGTH_V1: usize = 9;
+
+// the operations of records
+const OPERATION_SET: u8 = 1;
+const OPERATION_REMOVE: u8 = 2;
+
+/// The prefix of the keys of the entries holding sessions, when a `KvStore` is used as a
+/// `SessionStore`.
+pub const SESSION_KEY_PREFIX: &str = "session:";
+
+/// The number of stale bytes(of overwritten and removed entries) in a log above which it is
+/// compacted, if they also make up more than half of it.
+pub const DEFAULT_COMPACTION_THRESHOLD: u64 = 1024 * 1024;
+
+// the state of an open store
+struct Inner {
+    path: PathBuf,
+    file: fs::File,
+    entries: HashMap<String, Vec<u8>>,
+    log_length: u64,
+    stale_length: u64,
+    compaction_threshold: u64,
+}
+
+/// An embedded, file-backed key-value store, see the module documentation.
+///
+/// A `KvStore` is cheap to clone, all clones share the same entries and log. Move a clone into
+/// every route handler which needs it. Every change is written to the log before the method
+/// making it returns, call `KvStore::sync` to also make sure it reached the disk.
+///
+/// # Examples
+///
+/// ```rust,no_run
+/// use browzer_web::{storage::KvStore, utils::HttpStatusCode, WebServer};
+///
+/// let store = KvStore::open("data/app.kv").unwrap();
+///
+/// let mut server = WebServer::new("127.0.0.1:8080".to_string(), 4);
+/// let visits = store.clone();
+/// server.get("/", move |mut c| {
+///     let count = visits.increment("visits", 1).unwrap_or_default();
+///     return c.send_string(HttpStatusCode::OK, &format!("Visitor number {}", count));
+/// });
+/// server.listen();
+/// ```
+// ----- KvStore struct
+#[derive(Clone)]
+pub struct KvStore {
+    inner: Arc<Mutex<Inner>>,
+}
+
+impl fmt::Debug for KvStore {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let inner = self.lock();
+        f.debug_struct("KvStore")
+            .field("path", &inner.path)
+            .field("entries", &inner.entries.len())
+            .field("log_length", &inner.log_length)
+            .field("stale_length", &inner.stale_length)
+            .finish()
+    }
+}
+
+impl KvStore {
+    /// Opens the store kept in the given file, creating it if it doesn't exist yet.
+    ///
+    /// # Errors
+    ///
+    /// Returns an I/O error if the file couldn't be read or created, or an error of the kind
+    /// `InvalidData` if it isn't a log of a `KvStore` or a record before the last one is damaged.
+    pub fn open<P: AsRef<Path>>(path: P) -> io::Result<KvStore> {
+        let path = path.as_ref().to_path_buf();
+        let mut file = fs::OpenOptions::new()
+            .read(true)
+            .append(true)
+            .create(true)
+            .open(&path)?;
+        let mut log = vec![];
+        file.read_to_end(&mut log)?;
+        if log.is_empty() {
+            // makes sure the new log, and the directory entry pointing at it, reach the disk
+            file.write_all(MAGIC)?;
+            file.sync_all()?;
+            sync_parent_directory(&path)?;
+            log.extend_from_slice(MAGIC);
+        }
+        let checksummed = match &log[..log.len().min(MAGIC.len())] {
+            header if header == MAGIC => true,
+            header if header == MAGIC_V1 => false,
+            _ => {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    "not a key-value store log",
+                ));
+            }
+        };
+
+        // replays the log, stopping at a record which was only partly written
+        let mut entries: HashMap<String, Vec<u8>> = HashMap::new();
+        let mut stale_length = 0;
+        let mut offset = MAGIC.len();
+        loop {
+            let (operation, key, value, length) = match parse_record(&log[offset..], checksummed) {
+                Record::Complete(operation, key, value, length) => (operation, key, value, length),
+                Record::Torn => break,
+                Record::Damaged(length) => {
+                    // a damaged last record is one whose write was cut short, like the file
+                    // having been extended before the data reached the disk
+                    if offset + length >= log.len() {
+                        break;
+                    }
+                    return Err(io::Error::new(
+                        io::ErrorKind::InvalidData,
+                        format!("damaged record at offset {} of {:?}", offset, path),
+                    ));
+                }
+            };
+            let replaced = match operation {
+                OPERATION_SET => entries.insert(key.clone(), value.to_vec()),
+                OPERATION_REMOVE => {
+                    // the remove record itself is stale right away
+                    stale_length += length as u64;
+                    entries.remove(&key)
+                }
+                _ => {
+                    return Err(io::Error::new(
+                        io::ErrorKind::InvalidData,
+                        format!("unknown operation {} at offset {}", operation, offset),
+                    ));
+                }
+            };
+            if let Some(replaced) = replaced {
+                stale_length += record_length(&key, &replaced);
+            }
+            offset += length;
+        }
+        if offset < log.len() {
+            eprintln!(
+                "Dropping {} bytes of a partly written record at the end of {:?}",
+                log.len() - offset,
+                path
+            );
+            file.set_len(offset as u64)?;
+            file.sync_all()?;
+        }
+
+        let mut inner = Inner {
+            path,
+            file,
+            entries,
+            log_length: offset as u64,
+            stale_length,
+            compaction_threshold: DEFAULT_COMPACTION_THRESHOLD,
+        };
+        if !checksummed {
+            inner.compact()?;
+        }
+        return Ok(KvStore {
+            inner: Arc::new(Mutex::new(inner)),
+        });
+    }
+
+    /// Sets the number of stale bytes above which the log is compacted automatically, if they
+    /// also make up more than half of it. `u64::MAX` turns automatic compaction off.
+    pub fn set_compaction_threshold(&self, bytes: u64) {
+        self.lock().compaction_threshold = bytes;
+    }
+
+    /// Returns the value of an entry.
+    pub fn get(&self, key: &str) -> Option<Vec<u8>> {
+        return self.lock().entries.get(key).cloned();
+    }
+
+    /// Returns the value of an entry as a string, `None` if there is no such entry or it isn't
+    /// valid UTF-8.
+    pub fn get_string(&self, key: &str) -> Option<String> {
+        return self
+            .get(key)
+            .and_then(|value| String::from_utf8(value).ok());
+    }
+
+    /// Returns the value of an entry deserialized from JSON, `None` if there is no such entry or
+    /// it doesn't deserialize into the type.
+    pub fn get_json<T: DeserializeOwned>(&self, key: &str) -> Option<T> {
+        return self
+            .get(key)
+            .and_then(|value| serde_json::from_slice(&value).ok());
+    }
+
+    /// Returns whether there is an entry with the given key.
+    pub fn contains(&self, key: &str) -> bool {
+        return self.lock().entries.contains_key(key);
+    }
+
+    /// Returns the keys of all entries, in no particular order.
+    pub fn keys(&self) -> Vec<String> {
+        return self.lock().entries.keys().cloned().collect();
+    }
+
+    /// Returns the keys of all entries starting with the given prefix, sorted.
+    pub fn keys_with_prefix(&self, prefix: &str) -> Vec<String> {
+        let mut keys: Vec<String> = self
+            .lock()
+            .entries
+            .keys()
+            .filter(|key| key.starts_with(prefix))
+            .cloned()
+            .collect();
+        keys.sort();
+        return keys;
+    }
+
+    /// Returns the number of entries.
+    pub fn len(&self) -> usize {
+        return self.lock().entries.len();
+    }
+
+    /// Returns whether the store has no entries.
+    pub fn is_empty(&self) -> bool {
+        return self.len() == 0;
+    }
+
+    /// Sets the value of an entry, replacing the previous one.
+    ///
+    /// # Errors
+    ///
+    /// Returns an I/O error if the change couldn't be written to the log, the entry is left
+    /// unchanged then.
+    pub fn set<V: AsRef<[u8]>>(&self, key: &str, value: V) -> io::Result<()> {
+        let mut inner = self.lock();
+        return inner.set(key, value.as_ref().to_vec());
+    }
+
+    /// Sets the value of an entry to the JSON representation of a value.
+    ///
+    /// # Errors
+    ///
+    /// Returns an I/O error if the value couldn't be serialized or the change couldn't be written
+    /// to the log.
+    pub fn set_json<T: Serialize>(&self, key: &str, value: &T) -> io::Result<()> {
+        let value = serde_json::to_vec(value)?;
+        return self.set(key, value);
+    }
+
+    /// Removes an entry.
+    ///
+    /// # Returns
+    ///
+    /// - `io::Result<Option<Vec<u8>>>` - The value of the removed entry, `None` if there was no
+    ///   such entry, or an I/O error if the change couldn't be written to the log.
+    pub fn remove(&self, key: &str) -> io::Result<Option<Vec<u8>>> {
+        let mut inner = self.lock();
+        if !inner.entries.contains_key(key) {
+            return Ok(None);
+        }
+        let length = inner.append(OPERATION_REMOVE, key, &[])?;
+        let removed = inner.entries.remove(key);
+        if let Some(ref removed) = removed {
+            inner.stale_length += length + record_length(key, removed);
+        }
+        inner.compact_if_needed();
+        return Ok(removed);
+    }
+
+    /// Adds to a counter stored as the decimal string of an integer, starting at 0 for a missing
+    /// entry. The read and the write happen atomically, so concurrent increments don't get lost.
+    ///
+    /// # Returns
+    ///
+    /// - `io::Result<i64>` - The new value of the counter, or an error of the kind `InvalidData`
+    ///   if the entry isn't an integer.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # use browzer_web::storage::KvStore;
+    /// # let path = std::env::temp_dir().join(format!("browzer_kv_doc_{}", std::process::id()));
+    /// # let _ = std::fs::remove_file(&path);
+    /// let store = KvStore::open(&path).unwrap();
+    ///
+    /// assert_eq!(store.increment("downloads", 1).unwrap(), 1);
+    /// assert_eq!(store.increment("downloads", 2).unwrap(), 3);
+    /// assert_eq!(store.get_string("downloads").as_deref(), Some("3"));
+    /// # let _ = std::fs::remove_file(&path);
+    /// ```
+    pub fn increment(&self, key: &str, by: i64) -> io::Result<i64> {
+        let mut inner = self.lock();
+        let current = match inner.entries.get(key) {
+            Some(value) => std::str::from_utf8(value)
+                .ok()
+                .and_then(|value| value.parse::<i64>().ok())
+                .ok_or_else(|| {
+                    io::Error::new(io::ErrorKind::InvalidData, "entry isn't an integer")
+                })?,
+            None => 0,
+        };
+        let value = current.saturating_add(by);
+        inner.set(key, value.to_string().into_bytes())?;
+        return Ok(value);
+    }
+
+    /// Rewrites the log with only the live entries, replacing the old log atomically.
+    ///
+    /// # Errors
+    ///
+    /// Returns an I/O error if the new log couldn't be written, the old one is kept then.
+    pub fn compact(&self) -> io::Result<()> {
+        return self.lock().compact();
+    }
+
+    /// Makes sure all changes reached the disk.
+    ///
+    /// # Errors
+    ///
+    /// Returns an I/O error if the log couldn't be synced.
+    pub fn sync(&self) -> io::Result<()> {
+        return self.lock().file.sync_all();
+    }
+
+    /// Returns the length of the log in bytes, and how many of them belong to overwritten and
+    /// removed entries.
+    pub fn log_stats(&self) -> (u64, u64) {
+        let inner = self.lock();
+        return (inner.log_length, inner.stale_length);
+    }
+
+    fn lock(&self) -> MutexGuard<'_, Inner> {
+        match self.inner.lock() {
+            Ok(inner) => return inner,
+            Err(poisoned) => return poisoned.into_inner(),
+        }
+    }
+}
+
+// a session kept in an entry, with the time it expires at in seconds since the unix epoch
+#[derive(Serialize, Deserialize)]
+struct StoredSession {
+    data: SessionData,
+    expires_at: u64,
+}
+
+/// Keeps sessions in entries with keys starting with `SESSION_KEY_PREFIX`, so they survive
+/// restarts. Expired sessions are purged lazily whenever a session is saved.
+///
+/// # Examples
+///
+/// ```rust,no_run
+/// use browzer_web::{sessions::Sessions, storage::KvStore, WebServer};
+/// use std::time::Duration;
+///
+/// let store = KvStore::open("data/app.kv").unwrap();
+///
+/// let mut server = WebServer::new("127.0.0.1:8080".to_string(), 4);
+/// server.sessions(Sessions::new(store, Duration::from_secs(3600)));
+/// ```
+impl SessionStore for KvStore {
+    fn load(&self, id: &str) -> Option<SessionData> {
+        let session: StoredSession = self.get_json(&format!("{}{}", SESSION_KEY_PREFIX, id))?;
+        if session.expires_at <= unix_time() {
+            return None;
+        }
+        return Some(session.data);
+    }
+
+    fn save(&self, id: &str, data: SessionData, ttl: Duration) {
+        let now = unix_time();
+        for key in self.keys_with_prefix(SESSION_KEY_PREFIX) {
+            let expired = match self.get_json::<StoredSession>(&key) {
+                Some(session) => session.expires_at <= now,
+                None => true,
+            };
+            if expired {
+                let _ = self.remove(&key);
+            }
+        }
+        let session = StoredSession {
+            data,
+            expires_at: now.saturating_add(ttl.as_secs()),
+        };
+        if let Err(e) = self.set_json(&format!("{}{}", SESSION_KEY_PREFIX, id), &session) {
+            eprintln!("Failed to save session, Error: {}", e);
+        }
+    }
+
+    fn remove(&self, id: &str) {
+        let _ = KvStore::remove(self, &format!("{}{}", SESSION_KEY_PREFIX, id));
+    }
+}
+
+impl Inner {
+    // sets the value of an entry, after appending the change to the log
+    fn set(&mut self, key: &str, value: Vec<u8>) -> io::Result<()> {
+        self.append(OPERATION_SET, key, &value)?;
+        if let Some(replaced) = self.entries.insert(key.to_string(), value) {
+            self.stale_length += record_length(key, &replaced);
+        }
+        self.compact_if_needed();
+        return Ok(());
+    }
+
+    // appends a record to the log, returning it's length
+    fn append(&mut self, operation: u8, key: &str, value: &[u8]) -> io::Result<u64> {
+        let record = encode_record(operation, key, value)?;
+        if let Err(e) = self.file.write_all(&record) {
+            // a partly written record would be dropped on the next open anyway, but records
+            // appended after it would be lost with it
+            let _ = self.file.set_len(self.log_length);
+            return Err(e);
+        }
+        self.log_length += record.len() as u64;
+        return Ok(record.len() as u64);
+    }
+
+    // compacts the log once it consists mostly of stale records
+    fn compact_if_needed(&mut self) {
+        if self.stale_length > self.compaction_threshold && self.stale_length * 2 > self.log_length
+        {
+            if let Err(e) = self.compact() {
+                eprintln!("Failed to compact {:?}, Error: {}", self.path, e);
+            }
+        }
+    }
+
+    fn compact(&mut self) -> io::Result<()> {
+        let mut temp_name = self.path.file_name().unwrap_or_default().to_os_string();
+        temp_name.push(".compact");
+        let temp_path = self.path.with_file_name(temp_name);
+
+        let mut writer = BufWriter::new(fs::File::create(&temp_path)?);
+        writer.write_all(MAGIC)?;
+        let mut log_length = MAGIC.len() as u64;
+        for (key, value) in &self.entries {
+            let record = encode_record(OPERATION_SET, key, value)?;
+            writer.write_all(&record)?;
+            log_length += record.len() as u64;
+        }
+        let file = writer.into_inner().map_err(|e| e.into_error())?;
+        file.sync_all()?;
+        drop(file);
+        fs::rename(&temp_path, &self.path)?;
+        sync_parent_directory(&self.path)?;
+
+        self.file = fs::OpenOptions::new().append(true).open(&self.path)?;
+        self.log_length = log_length;
+        self.stale_length = 0;
+        return Ok(());
+    }
+}
+
+// returns the current time in seconds since the unix epoch
+fn unix_time() -> u64 {
+    return SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_secs())
+        .unwrap_or_default();
+}
+
+// syncs the directory holding the given file, so that a file created or renamed into it survives a
+// crash too
+#[cfg(unix)]
+fn sync_parent_directory(path: &Path) -> io::Result<()> {
+    let directory = match path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent,
+        _ => Path::new("."),
+    };
+    return fs::File::open(directory)?.sync_all();
+}
+
+// directories can't be opened as files to sync them on other platforms
+#[cfg(not(unix))]
+fn sync_parent_directory(_path: &Path) -> io::Result<()> {
+    return Ok(());
+}
+
+// returns the length of the record setting an entry
+fn record_length(key: &str, value: &[u8]) -> u64 {
+    return RECORD_HEAD_LENGTH + key.len() as u64 + value.len() as u64;
+}
+
+fn encode_record(operation: u8, key: &str, value: &[u8]) -> io::Result<Vec<u8>> {
+    let too_large = || io::Error::new(io::ErrorKind::InvalidInput, "entry too large");
+    let key_length = u32::try_from(key.len()).map_err(|_| too_large())?;
+    let value_length = u32::try_from(value.len()).map_err(|_| too_large())?;
+    let mut record = Vec::with_capacity(record_length(key, value) as usize);
+    record.push(operation);
+    record.extend_from_slice(&key_length.to_le_bytes());
+    record.extend_from_slice(&value_length.to_le_bytes());
+    record.extend_from_slice(&[0; 4]);
+    record.extend_from_slice(key.as_bytes());
+    record.extend_from_slice(value);
+    let checksum = record_checksum(&record);
+    record[9..13].copy_from_slice(&checksum.to_le_bytes());
+    return Ok(record);
+}
+
+// returns the CRC-32 of a record, over everything but the checksum itself
+fn record_checksum(record: &[u8]) -> u32 {
+    let mut hasher = crc32fast::Hasher::new();
+    hasher.update(&record[..9]);
+    hasher.update(&record[RECORD_HEAD_LENGTH as usize..]);
+    return hasher.finalize();
+}
+
+// the outcome of parsing a record of the log
+enum Record<'a> {
+    // the operation, key, value and length of a record
+    Complete(u8, String, &'a [u8], usize),
+    // the data ends before the record does
+    Torn,
+    // the record of the given length doesn't match it's checksum or has a key which isn't UTF-8
+    Damaged(usize),
+}
+
+// parses the record at the start of the data, with or without a checksum depending on the version
+// of the log
+fn parse_record(data: &[u8], checksummed: bool) -> Record<'_> {
+    let head_length = match checksummed {
+        true => RECORD_HEAD_LENGTH as usize,
+        false => RECORD_HEAD_LENGTH_V1,
+    };
+    let head = match data.get(..head_length) {
+        Some(head) => head,
+        None => return Record::Torn,
+    };
+    let key_length = u32::from_le_bytes([head[1], head[2], head[3], head[4]]) as usize;
+    let value_length = u32::from_le_bytes([head[5], head[6], head[7], head[8]]) as usize;
+    let key_end = head_length.saturating_add(key_length);
+    let end = key_end.saturating_add(value_length);
+    let record = match data.get(..end) {
+        Some(record) => record,
+        None => return Record::Torn,
+    };
+    if checksummed {
+        let checksum = u32::from_le_bytes([head[9], head[10], head[11], head[12]]);
+        if record_checksum(record) != checksum {
+            return Record::Damaged(end);
+        }
+    }
+    return match std::str::from_utf8(&record[head_length..key_end]) {
+        Ok(key) => Record::Complete(head[0], key.to_string(), &record[key_end..], end),
+        Err(_) => Record::Damaged(end),
+    };
+}
@@ -0,0 +1,385 @@
+//! This module provides streaming response bodies, which are written to the client piece by piece
+//! while they are generated instead of being built as a whole first.
+//!
+//! A streaming handler returns a response carrying a `StreamBody`, once the head of the response
+//! was sent the server runs the stream with a `ResponseWriter` which sends everything written to it
+//! using chunked transfer encoding, or as is for streams whose length is known up front(like
+//! files, see `Context::send_file`). The writer batches small writes into larger chunks, how
+//! eagerly it sends them is controlled by it's `FlushMode`: latency-sensitive streams(like
+//! server-sent events) send every write right away, while bulk downloads only send full buffers.
+
+// internal crate imports
+use crate::cancel;
+
+// standard library imports
+use std::{
+    fmt,
+    io::{self, Read, Write},
+    sync::{Arc, Mutex},
+};
+
+/// A function writing the body of a streaming response, see `StreamBody`.
+pub type StreamFunction = dyn Fn(&mut ResponseWriter) -> io::Result<()> + 'static + Send + Sync;
+
+/// The default size(in bytes) up to which a `ResponseWriter` batches writes into a single chunk.
+pub const DEFAULT_BUFFER_SIZE: usize = 8 * 1024;
+
+/// Controls when a `ResponseWriter` sends the data written to it.
+///
+/// Whatever the mode, a full buffer is always sent and `ResponseWriter::flush` always sends the
+/// buffered data right away.
+///
+/// # Variants
+///
+/// - `Batched` - Flush hints are ignored, data is only sent once the buffer is full. Best for bulk
+///   downloads, where throughput matters more than latency.
+/// - `OnHint` - Data is sent once the buffer is full or at every `ResponseWriter::flush_hint`, the
+///   default. Best for streams of messages, where the end of every message should reach the client
+///   without delay.
+/// - `Immediate` - Every write is sent right away. Best for server-sent events and other
+///   latency-sensitive streams writing one message at a time, note that `write!` may split a
+///   message into several writes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum FlushMode {
+    Batched,
+    #[default]
+    OnHint,
+    Immediate,
+}
+
+/// The body of a streaming response, a function which writes the body to a `ResponseWriter`.
+///
+/// The function is run by the server after the head of the response was sent, returning an error
+/// from it aborts the response and closes the connection, so the client can tell that the body is
+/// incomplete.
+///
+/// A stream with a known length is sent with a `Content-Length` header instead of chunked, it has
+/// to write exactly that many bytes, anything else aborts the response as well.
+///
+/// # Examples
+///
+/// ```rust
+/// use browzer_web::stream::StreamBody;
+/// use std::io::Write;
+///
+/// let body = StreamBody::new(|writer| {
+///     for i in 0..3 {
+///         writeln!(writer, "line {}", i)?;
+///         writer.flush_hint()?;
+///     }
+///     return Ok(());
+/// });
+/// ```
+// ----- StreamBody struct
+#[derive(Clone)]
+pub struct StreamBody {
+    func: Arc<StreamFunction>,
+    length: Option<u64>,
+}
+
+impl fmt::Debug for StreamBody {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("StreamBody")
+            .field(
+                "func",
+                &"Arc<dyn Fn(&mut ResponseWriter) -> io::Result<()> + 'static + Send + Sync>",
+            )
+            .field("length", &self.length)
+            .finish()
+    }
+}
+
+impl StreamBody {
+    /// Creates a new `StreamBody` from the function writing the body.
+    pub fn new<F>(func: F) -> StreamBody
+    where
+        F: Fn(&mut ResponseWriter) -> io::Result<()> + 'static + Send + Sync,
+    {
+        return StreamBody {
+            func: Arc::new(func),
+            length: None,
+        };
+    }
+
+    /// Creates a new `StreamBody` of a known length(in bytes) from the function writing the body.
+    pub fn with_length<F>(length: u64, func: F) -> StreamBody
+    where
+        F: Fn(&mut ResponseWriter) -> io::Result<()> + 'static + Send + Sync,
+    {
+        return StreamBody {
+            func: Arc::new(func),
+            length: Some(length),
+        };
+    }
+
+    /// Creates a new `StreamBody` which copies everything from a reader, like an open file.
+    ///
+    /// The reader is only read once, a stream which is written again(like a cloned response)
+    /// writes nothing the second time.
+    ///
+    /// # Arguments
+    ///
+    /// - `reader` - The source of the body.
+    /// - `length` - The number of bytes the reader will produce, if known up front.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use browzer_web::stream::StreamBody;
+    ///
+    /// let reader = std::io::Cursor::new(b"Hello, World!".to_vec());
+    /// let body = StreamBody::from_reader(reader, Some(13));
+    ///
+    /// assert_eq!(body.length(), Some(13));
+    /// ```
+    pub fn from_reader<R: Read + 'static + Send>(reader: R, length: Option<u64>) -> StreamBody {
+        let reader = Mutex::new(Some(reader));
+        return StreamBody {
+            func: Arc::new(move |writer| {
+                let reader = match reader.lock() {
+                    Ok(mut reader) => reader.take(),
+                    Err(_) => None,
+                };
+                if let Some(mut reader) = reader {
+                    io::copy(&mut reader, writer)?;
+                }
+                return Ok(());
+            }),
+            length,
+        };
+    }
+
+    /// Creates a new `StreamBody` which writes a byte buffer, like a body which was encoded after
+    /// it was generated(see the `compression` module). Unlike a reader, the bytes can be written
+    /// any number of times.
+    pub fn from_bytes(bytes: Vec<u8>) -> StreamBody {
+        let length = bytes.len() as u64;
+        let bytes = Arc::new(bytes);
+        return StreamBody {
+            func: Arc::new(move |writer| writer.write_all(&bytes)),
+            length: Some(length),
+        };
+    }
+
+    /// Returns the length of the body(in bytes), if it is known up front.
+    pub fn length(&self) -> Option<u64> {
+        return self.length;
+    }
+
+    // writes the body to the writer
+    pub(crate) fn write_to(&self, writer: &mut ResponseWriter) -> io::Result<()> {
+        return (self.func)(writer);
+    }
+}
+
+/// Writes the body of a streaming response to the client, as chunks of the chunked transfer
+/// encoding(or as is, for streams with a known length).
+///
+/// Writes are collected in a buffer which is sent as a single chunk, depending on the `FlushMode`
+/// of the writer. The writer implements `std::io::Write`, so `write!` and friends can be used with
+/// it.
+///
+/// # Examples
+///
+/// ```rust
+/// use browzer_web::stream::{FlushMode, StreamBody};
+/// use std::io::Write;
+///
+/// // server-sent events, every event is sent as soon as it is written
+/// let events = StreamBody::new(|writer| {
+///     writer.set_flush_mode(FlushMode::Immediate);
+///     for i in 0..3 {
+///         // formatting the event first sends it as a single chunk
+///         writer.write_all(format!("data: {}\n\n", i).as_bytes())?;
+///     }
+///     return Ok(());
+/// });
+///
+/// // a bulk export, written in large chunks
+/// let export = StreamBody::new(|writer| {
+///     writer.set_flush_mode(FlushMode::Batched);
+///     writer.set_buffer_size(64 * 1024);
+///     for i in 0..100_000 {
+///         writeln!(writer, "{},row", i)?;
+///     }
+///     return Ok(());
+/// });
+/// ```
+// ----- ResponseWriter struct
+pub struct ResponseWriter<'a> {
+    stream: &'a mut dyn Write,
+    buffer: Vec<u8>,
+    buffer_size: usize,
+    flush_mode: FlushMode,
+    cancellation: cancel::CancellationToken,
+    // the number of bytes still to be written by a stream with a known length, `None` for chunked
+    // and close-delimited streams
+    remaining: Option<u64>,
+    // whether a stream without a known length is sent in chunks, otherwise it's body ends when the
+    // connection is closed(for `HTTP/1.0` clients)
+    chunked: bool,
+}
+
+impl fmt::Debug for ResponseWriter<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ResponseWriter")
+            .field("stream", &"&mut dyn Write")
+            .field("buffered", &self.buffer.len())
+            .field("buffer_size", &self.buffer_size)
+            .field("flush_mode", &self.flush_mode)
+            .field("cancellation", &self.cancellation)
+            .field("remaining", &self.remaining)
+            .field("chunked", &self.chunked)
+            .finish()
+    }
+}
+
+impl<'a> ResponseWriter<'a> {
+    // creates a writer sending chunks to the connection stream(or the body as is, if it's length is
+    // known or it isn't `chunked`), the head of the response has to be written already, a failing
+    // write cancels the token of the request
+    pub(crate) fn new(
+        stream: &'a mut dyn Write,
+        cancellation: cancel::CancellationToken,
+        length: Option<u64>,
+        chunked: bool,
+    ) -> ResponseWriter<'a> {
+        return ResponseWriter {
+            stream,
+            buffer: Vec::with_capacity(DEFAULT_BUFFER_SIZE),
+            buffer_size: DEFAULT_BUFFER_SIZE,
+            flush_mode: FlushMode::default(),
+            cancellation,
+            remaining: length,
+            chunked,
+        };
+    }
+
+    /// Returns whether the client disconnected, see `CancellationToken::is_cancelled`.
+    ///
+    /// Streams which wait between writes(like server-sent events waiting for the next event)
+    /// should check it while waiting, since a disconnect is otherwise only noticed by the next
+    /// failing write.
+    pub fn is_cancelled(&self) -> bool {
+        return self.cancellation.is_cancelled();
+    }
+
+    /// Sets the size(in bytes) up to which writes are batched into a single chunk, at least 1 byte.
+    ///
+    /// Buffered data over the new size is sent with the next write.
+    pub fn set_buffer_size(&mut self, buffer_size: usize) {
+        self.buffer_size = buffer_size.max(1);
+    }
+
+    /// Sets when buffered data is sent, see `FlushMode`.
+    ///
+    /// Switching to `FlushMode::Immediate` doesn't send the already buffered data, call
+    /// `ResponseWriter::flush` for that.
+    pub fn set_flush_mode(&mut self, flush_mode: FlushMode) {
+        self.flush_mode = flush_mode;
+    }
+
+    /// Returns the current `FlushMode` of the writer.
+    pub fn flush_mode(&self) -> FlushMode {
+        return self.flush_mode;
+    }
+
+    /// Returns the number of bytes written but not sent yet.
+    pub fn buffered(&self) -> usize {
+        return self.buffer.len();
+    }
+
+    /// Hints that the data written so far is a complete unit(like a message) the client may be
+    /// waiting for.
+    ///
+    /// The buffered data is sent right away unless the writer is in `FlushMode::Batched`, so
+    /// handlers can mark message boundaries without deciding how the stream is buffered.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if sending the buffered data failed, like when the client disconnected.
+    pub fn flush_hint(&mut self) -> io::Result<()> {
+        match self.flush_mode {
+            FlushMode::Batched => return Ok(()),
+            FlushMode::OnHint | FlushMode::Immediate => return self.flush(),
+        }
+    }
+
+    // sends the buffered data as a single chunk
+    fn send_chunk(&mut self) -> io::Result<()> {
+        if self.buffer.is_empty() {
+            return Ok(());
+        }
+        let result = match self.remaining {
+            Some(remaining) => {
+                if self.buffer.len() as u64 > remaining {
+                    return Err(io::Error::new(
+                        io::ErrorKind::InvalidInput,
+                        "Stream wrote more than it's announced length",
+                    ));
+                }
+                self.remaining = Some(remaining - self.buffer.len() as u64);
+                self.stream.write_all(&self.buffer)
+            }
+            None if !self.chunked => self.stream.write_all(&self.buffer),
+            None => write!(self.stream, "{:X}\r\n", self.buffer.len())
+                .and_then(|_| self.stream.write_all(&self.buffer))
+                .and_then(|_| self.stream.write_all(b"\r\n")),
+        };
+        self.buffer.clear();
+        return self.check(result);
+    }
+
+    // cancels the token of the request if writing to the client failed
+    fn check(&self, result: io::Result<()>) -> io::Result<()> {
+        if result.is_err() {
+            self.cancellation.cancel();
+        }
+        return result;
+    }
+
+    // sends the remaining buffered data and terminates a chunked body with the last(empty) chunk,
+    // a stream with a known length has to have written all of it
+    pub(crate) fn finish(mut self) -> io::Result<()> {
+        self.send_chunk()?;
+        let result = match self.remaining {
+            Some(0) => self.stream.flush(),
+            Some(remaining) => {
+                return Err(io::Error::new(
+                    io::ErrorKind::UnexpectedEof,
+                    format!(
+                        "Stream ended {} bytes before it's announced length",
+                        remaining
+                    ),
+                ));
+            }
+            None if !self.chunked => self.stream.flush(),
+            None => self
+                .stream
+                .write_all(b"0\r\n\r\n")
+                .and_then(|_| self.stream.flush()),
+        };
+        return self.check(result);
+    }
+}
+
+impl Write for ResponseWriter<'_> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.buffer.extend_from_slice(buf);
+        match self.flush_mode {
+            FlushMode::Immediate => self.flush()?,
+            FlushMode::Batched | FlushMode::OnHint => {
+                if self.buffer.len() >= self.buffer_size {
+                    self.send_chunk()?;
+                }
+            }
+        }
+        return Ok(buf.len());
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.send_chunk()?;
+        let result = self.stream.flush();
+        return self.check(result);
+    }
+}
@@ -0,0 +1,209 @@
+//! This module runs work handlers spawn off on a background pool of threads, tied to the request
+//! it was spawned for, see `Context::spawn_scoped`.
+//!
+//! A `ScopedTask` has to be joined, cancelled or explicitly detached. One which is simply dropped
+//! (like when the handler returns early) is cancelled and waited for, so work spawned by a request
+//! never outlives it by accident, unlike a thread started with `thread::spawn`.
+
+// internal crate imports
+use crate::{cancel, error, utils::thread_pool};
+
+// standard library imports
+use std::{
+    any::Any,
+    fmt,
+    panic::{self, AssertUnwindSafe},
+    sync::{mpsc, OnceLock},
+    thread,
+};
+
+/// A pool of threads running background work, started once the first task is spawned on it.
+///
+/// # Examples
+///
+/// ```rust
+/// use browzer_web::tasks::BackgroundPool;
+/// use std::sync::mpsc;
+///
+/// let pool = BackgroundPool::new(2);
+/// let (sender, receiver) = mpsc::channel();
+/// pool.execute(move || {
+///     sender.send(21 * 2).unwrap();
+/// });
+/// assert_eq!(receiver.recv().unwrap(), 42);
+/// ```
+// ----- BackgroundPool struct
+#[derive(Debug)]
+pub struct BackgroundPool {
+    size: usize,
+    pool: OnceLock<thread_pool::ThreadPool>,
+}
+
+// default implementation for BackgroundPool struct
+impl Default for BackgroundPool {
+    fn default() -> Self {
+        let size = thread::available_parallelism()
+            .map(|size| size.get())
+            .unwrap_or(4);
+        return BackgroundPool::new(size);
+    }
+}
+
+impl BackgroundPool {
+    /// Creates a new `BackgroundPool`, the default one has as many threads as there are CPU cores.
+    ///
+    /// # Arguments
+    ///
+    /// - `size` - The number of threads.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `size` is 0.
+    pub fn new(size: usize) -> BackgroundPool {
+        assert!(size > 0, "a background pool needs at least one thread");
+        return BackgroundPool {
+            size,
+            pool: OnceLock::new(),
+        };
+    }
+
+    /// Returns the number of threads of the pool.
+    pub fn size(&self) -> usize {
+        return self.size;
+    }
+
+    /// Runs a job on the pool, starting it's threads first if this is the first job.
+    pub fn execute<F>(&self, job: F)
+    where
+        F: FnOnce() + Send + 'static,
+    {
+        let pool = self
+            .pool
+            .get_or_init(|| thread_pool::ThreadPool::new(self.size));
+        if let Err(e) = pool.execute(job) {
+            eprintln!("Failed to run a background task, Error: {}", e);
+        }
+    }
+
+    /// Spawns a task on the pool, see `Context::spawn_scoped`.
+    ///
+    /// # Arguments
+    ///
+    /// - `token` - The cancellation token passed to the task, a child of it is cancelled when the
+    ///   task is.
+    /// - `task` - The task, receiving the token it should check between units of work.
+    pub fn spawn_scoped<F, T>(&self, token: &cancel::CancellationToken, task: F) -> ScopedTask<T>
+    where
+        F: FnOnce(cancel::CancellationToken) -> T + Send + 'static,
+        T: Send + 'static,
+    {
+        let token = token.child();
+        let task_token = token.clone();
+        let (sender, receiver) = mpsc::channel();
+        self.execute(move || {
+            // a panic is handed to whoever joins the task, instead of taking the pool thread down
+            let result = panic::catch_unwind(AssertUnwindSafe(|| task(task_token)));
+            let _ = sender.send(result);
+        });
+        return ScopedTask {
+            token,
+            receiver: Some(receiver),
+        };
+    }
+}
+
+/// A task running on a `BackgroundPool`, tied to the request it was spawned for.
+///
+/// Dropping a task which wasn't joined or detached cancels it and waits for it to return, so a
+/// task which never checks it's cancellation token holds up the response until it's done.
+///
+/// # Examples
+///
+/// ```rust
+/// use browzer_web::{cancel::CancellationToken, tasks::BackgroundPool};
+///
+/// let pool = BackgroundPool::new(1);
+/// let task = pool.spawn_scoped(&CancellationToken::new(), |_| 21 * 2);
+/// assert_eq!(task.join(), Ok(42));
+///
+/// let task = pool.spawn_scoped(&CancellationToken::new(), |token| {
+///     while !token.is_cancelled() {
+///         std::thread::yield_now();
+///     }
+///     return "cancelled";
+/// });
+/// task.cancel();
+/// assert_eq!(task.join(), Ok("cancelled"));
+/// ```
+// ----- ScopedTask struct
+pub struct ScopedTask<T> {
+    token: cancel::CancellationToken,
+    receiver: Option<mpsc::Receiver<thread::Result<T>>>,
+}
+
+impl<T> fmt::Debug for ScopedTask<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ScopedTask")
+            .field("token", &self.token)
+            .field("detached", &self.receiver.is_none())
+            .finish()
+    }
+}
+
+impl<T> ScopedTask<T> {
+    /// Waits for the task to return.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `TaskError::Panicked` error if the task panicked.
+    pub fn join(mut self) -> Result<T, error::TaskError> {
+        // only `join` and `detach` take the receiver, and both consume the task
+        let receiver = match self.receiver.take() {
+            Some(receiver) => receiver,
+            None => return Err(error::TaskError::Panicked(String::new())),
+        };
+        match receiver.recv() {
+            Ok(Ok(value)) => return Ok(value),
+            Ok(Err(payload)) => return Err(error::TaskError::Panicked(panic_message(&payload))),
+            // the job was dropped without running, which only happens if the pool shut down
+            Err(_) => return Err(error::TaskError::Panicked(String::new())),
+        }
+    }
+
+    /// Asks the task to stop, by cancelling the token it received. The task still has to be
+    /// joined(or dropped) to wait for it.
+    pub fn cancel(&self) {
+        self.token.cancel();
+    }
+
+    /// Returns the cancellation token of the task.
+    pub fn cancellation_token(&self) -> cancel::CancellationToken {
+        return self.token.clone();
+    }
+
+    /// Lets the task run to completion on it's own, without anyone waiting for it, like for
+    /// sending a notification email which shouldn't hold up the response.
+    pub fn detach(mut self) {
+        self.receiver = None;
+    }
+}
+
+impl<T> Drop for ScopedTask<T> {
+    fn drop(&mut self) {
+        if let Some(receiver) = self.receiver.take() {
+            self.token.cancel();
+            let _ = receiver.recv();
+        }
+    }
+}
+
+// extracts the message of a panic payload, if it is a string
+fn panic_message(payload: &Box<dyn Any + Send>) -> String {
+    match payload.downcast_ref::<&str>() {
+        Some(message) => return message.to_string(),
+        None => match payload.downcast_ref::<String>() {
+            Some(message) => return message.clone(),
+            None => return String::new(),
+        },
+    }
+}
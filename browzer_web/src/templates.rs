@@ -0,0 +1,941 @@
+//! This module renders HTML templates with the data of a request, see `WebServer::templates` and
+//! `Context::render`.
+//!
+//! Templates use a small subset of the Jinja/Tera syntax:
+//!
+//! - `{{ user.name }}` inserts a value of the data, HTML-escaped. Filters transform the value,
+//!   like `{{ title | upper }}`, see below.
+//! - `{% if user.admin %}..{% elif posts %}..{% else %}..{% endif %}` renders the first branch
+//!   whose condition holds. Conditions can compare values with `==` and `!=`, and be combined
+//!   with `and`, `or` and `not`.
+//! - `{% for post in posts %}..{% else %}..{% endfor %}` repeats it's body for every item of a
+//!   list, the `else` branch is rendered for an empty list. `loop.index`(starting at 1),
+//!   `loop.first` and `loop.last` tell where the loop is. Looping over an object gives it's
+//!   entries as `{key, value}` objects.
+//! - `{% include "partials/nav.html" %}` renders another template in place, with the same data.
+//! - `{% extends "base.html" %}` renders another template instead, replacing the contents of
+//!   it's `{% block name %}..{% endblock %}` tags with the blocks of the same names.
+//! - `{# .. #}` is a comment.
+//!
+//! A `-` right after the opening braces, like `{%- endif %}`, strips the whitespace before the
+//! tag.
+//!
+//! Values which don't exist are rendered as empty and are false in conditions. `null`, `false`,
+//! `0`, empty strings, lists and objects are false too. The filters are `upper`, `lower`, `trim`,
+//! `length`, `default("value")`(for values which don't exist or are empty) and `safe`, which
+//! inserts a value without escaping it.
+
+// external crate imports
+use serde::Serialize;
+use serde_json::Value;
+
+// internal crate imports
+use crate::{error::TemplateError, utils};
+
+// standard library imports
+use std::{
+    collections::HashMap,
+    fmt, fs,
+    path::{Path, PathBuf},
+    sync::{Arc, RwLock},
+    time::SystemTime,
+};
+
+// how deeply templates may include and extend each other, to catch cycles
+const MAX_DEPTH: usize = 32;
+
+// a piece of a template source
+#[derive(Debug)]
+enum Token {
+    Text(String),
+    Expression(String, usize),
+    Tag(String, usize),
+}
+
+// a word, literal or symbol of an expression
+#[derive(Debug, Clone, PartialEq)]
+enum Lexeme {
+    Word(String),
+    Literal(Value),
+    Symbol(&'static str),
+}
+
+#[derive(Debug, Clone)]
+enum Operand {
+    Path(Vec<String>),
+    Literal(Value),
+}
+
+#[derive(Debug, Clone)]
+enum Filter {
+    Upper,
+    Lower,
+    Trim,
+    Length,
+    Default(Value),
+    Safe,
+}
+
+#[derive(Debug, Clone)]
+struct Expression {
+    operand: Operand,
+    filters: Vec<Filter>,
+}
+
+#[derive(Debug, Clone)]
+enum Condition {
+    Or(Vec<Condition>),
+    And(Vec<Condition>),
+    Not(Box<Condition>),
+    Compare(Expression, bool, Expression),
+    Truthy(Expression),
+}
+
+#[derive(Debug, Clone)]
+enum Node {
+    Text(String),
+    Expression(Expression),
+    If(Vec<(Condition, Vec<Node>)>, Vec<Node>),
+    For(String, Expression, Vec<Node>, Vec<Node>),
+    Include(String),
+    Block(String, Vec<Node>),
+}
+
+// a parsed template
+#[derive(Debug)]
+struct Template {
+    parent: Option<String>,
+    nodes: Vec<Node>,
+    blocks: HashMap<String, Vec<Node>>,
+}
+
+// a loaded template, with the file it was read from
+#[derive(Debug)]
+struct Entry {
+    template: Arc<Template>,
+    path: Option<PathBuf>,
+    modified: Option<SystemTime>,
+}
+
+/// A set of HTML templates, see the module documentation for their syntax.
+///
+/// Templates are parsed once when they are added, so syntax errors show up when the server
+/// starts rather than when a page is requested. Templates are named after their path relative to
+/// the template directory, with `/` separators, like `posts/show.html`.
+///
+/// # Examples
+///
+/// ```rust
+/// use browzer_web::templates::Templates;
+/// use serde_json::json;
+///
+/// let mut templates = Templates::new();
+/// templates.add("base.html", "<title>{% block title %}Blog{% endblock %}</title>{% block content %}{% endblock %}").unwrap();
+/// templates.add("posts.html", r#"{% extends "base.html" %}
+/// {% block content %}{% for post in posts %}<h2>{{ loop.index }}. {{ post.title }}</h2>{% else %}No posts{% endfor %}{% endblock %}"#).unwrap();
+///
+/// let page = templates.render("posts.html", &json!({ "posts": [{ "title": "Q&A" }] })).unwrap();
+/// assert_eq!(page, "<title>Blog</title><h2>1. Q&amp;A</h2>");
+/// ```
+// ----- Templates struct
+#[derive(Default)]
+pub struct Templates {
+    dir: Option<PathBuf>,
+    auto_reload: bool,
+    templates: RwLock<HashMap<String, Entry>>,
+}
+
+impl fmt::Debug for Templates {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Templates")
+            .field("dir", &self.dir)
+            .field("auto_reload", &self.auto_reload)
+            .field("templates", &self.names())
+            .finish()
+    }
+}
+
+impl Templates {
+    /// Creates a new, empty `Templates`.
+    pub fn new() -> Templates {
+        return Templates::default();
+    }
+
+    /// Loads all templates of a directory and it's subdirectories, skipping hidden files.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `TemplateError` if a file couldn't be read or isn't a valid template.
+    pub fn from_dir<P: AsRef<Path>>(dir: P) -> Result<Templates, TemplateError> {
+        let dir = dir.as_ref().to_path_buf();
+        let mut files = vec![];
+        collect_files(&dir, "", &mut files)?;
+        let templates = Templates {
+            dir: Some(dir),
+            auto_reload: false,
+            templates: RwLock::new(HashMap::new()),
+        };
+        for (name, path) in files {
+            templates.load(&name, path)?;
+        }
+        return Ok(templates);
+    }
+
+    /// Reads templates of the directory again whenever their file changed, and loads templates
+    /// added to it later on, which is handy during development.
+    pub fn auto_reload(mut self, auto_reload: bool) -> Templates {
+        self.auto_reload = auto_reload;
+        return self;
+    }
+
+    /// Adds a template, replacing the one with the same name.
+    ///
+    /// # Errors
+    ///
+    /// Returns `TemplateError::Syntax` if the source isn't a valid template.
+    pub fn add(&mut self, name: &str, source: &str) -> Result<(), TemplateError> {
+        let template = parse(name, source)?;
+        self.write().insert(
+            name.to_string(),
+            Entry {
+                template: Arc::new(template),
+                path: None,
+                modified: None,
+            },
+        );
+        return Ok(());
+    }
+
+    /// Returns the names of all templates, sorted.
+    pub fn names(&self) -> Vec<String> {
+        let mut names: Vec<String> = self.read().keys().cloned().collect();
+        names.sort();
+        return names;
+    }
+
+    /// Renders a template with the given data, which is usually a struct or map serializing into
+    /// a JSON object.
+    ///
+    /// # Errors
+    ///
+    /// Returns `TemplateError::NotFound` if there is no template with the name(or with the name
+    /// of one it includes or extends), or `TemplateError::Render` if the data couldn't be
+    /// serialized or the templates include or extend each other endlessly.
+    pub fn render<T: Serialize>(&self, name: &str, data: &T) -> Result<String, TemplateError> {
+        let data = serde_json::to_value(data)
+            .map_err(|e| TemplateError::Render(name.to_string(), e.to_string()))?;
+        return self.render_value(name, &data, &[]);
+    }
+
+    // renders a template with data which is a JSON value already, inserting the top-level values
+    // with the given names unescaped like the `safe` filter does(see `pages::PageTemplates`)
+    pub(crate) fn render_value(
+        &self,
+        name: &str,
+        data: &Value,
+        safe: &[&str],
+    ) -> Result<String, TemplateError> {
+        let mut scope = Scope {
+            root: data,
+            safe,
+            locals: vec![],
+        };
+        let mut output = String::new();
+        self.render_template(name, &mut scope, &mut output, 0)?;
+        return Ok(output);
+    }
+
+    // renders a template into the output, after following the templates it extends
+    fn render_template(
+        &self,
+        name: &str,
+        scope: &mut Scope,
+        output: &mut String,
+        depth: usize,
+    ) -> Result<(), TemplateError> {
+        let mut chain = vec![self.get(name)?];
+        while let Some(parent) = chain[chain.len() - 1].parent.clone() {
+            if depth + chain.len() > MAX_DEPTH {
+                return Err(TemplateError::Render(
+                    name.to_string(),
+                    "templates extend each other too deeply".to_string(),
+                ));
+            }
+            chain.push(self.get(&parent)?);
+        }
+
+        // the blocks of a template take precedence over the ones of the templates it extends
+        let mut blocks: HashMap<&str, &[Node]> = HashMap::new();
+        for template in &chain {
+            for (block, nodes) in &template.blocks {
+                blocks.entry(block.as_str()).or_insert(nodes.as_slice());
+            }
+        }
+        let root = &chain[chain.len() - 1];
+        return self.render_nodes(&root.nodes, &blocks, scope, output, depth, name);
+    }
+
+    fn render_nodes(
+        &self,
+        nodes: &[Node],
+        blocks: &HashMap<&str, &[Node]>,
+        scope: &mut Scope,
+        output: &mut String,
+        depth: usize,
+        name: &str,
+    ) -> Result<(), TemplateError> {
+        for node in nodes {
+            match node {
+                Node::Text(text) => output.push_str(text),
+                Node::Expression(expression) => {
+                    let (value, safe) = scope.evaluate(expression);
+                    let text = display(&value);
+                    match safe {
+                        true => output.push_str(&text),
+                        false => output.push_str(&utils::escape_html(&text)),
+                    }
+                }
+                Node::If(branches, otherwise) => {
+                    let branch = branches
+                        .iter()
+                        .find(|(condition, _)| scope.test(condition))
+                        .map(|(_, nodes)| nodes)
+                        .unwrap_or(otherwise);
+                    self.render_nodes(branch, blocks, scope, output, depth, name)?;
+                }
+                Node::For(item, iterable, body, otherwise) => {
+                    let items = match scope.evaluate(iterable).0 {
+                        Value::Array(items) => items,
+                        Value::Object(entries) => entries
+                            .into_iter()
+                            .map(|(key, value)| serde_json::json!({ "key": key, "value": value }))
+                            .collect(),
+                        _ => vec![],
+                    };
+                    if items.is_empty() {
+                        self.render_nodes(otherwise, blocks, scope, output, depth, name)?;
+                    }
+                    let length = items.len();
+                    for (index, value) in items.into_iter().enumerate() {
+                        let state = serde_json::json!({
+                            "index": index + 1,
+                            "index0": index,
+                            "first": index == 0,
+                            "last": index + 1 == length,
+                        });
+                        scope.locals.push((item.clone(), value));
+                        scope.locals.push(("loop".to_string(), state));
+                        let rendered = self.render_nodes(body, blocks, scope, output, depth, name);
+                        scope.locals.truncate(scope.locals.len() - 2);
+                        rendered?;
+                    }
+                }
+                Node::Include(included) => {
+                    if depth >= MAX_DEPTH {
+                        return Err(TemplateError::Render(
+                            name.to_string(),
+                            "templates include each other too deeply".to_string(),
+                        ));
+                    }
+                    self.render_template(included, scope, output, depth + 1)?;
+                }
+                Node::Block(block, nodes) => {
+                    let nodes = blocks.get(block.as_str()).copied().unwrap_or(nodes);
+                    self.render_nodes(nodes, blocks, scope, output, depth, name)?;
+                }
+            }
+        }
+        return Ok(());
+    }
+
+    // returns a template, reading it's file again first if it changed
+    fn get(&self, name: &str) -> Result<Arc<Template>, TemplateError> {
+        if self.auto_reload {
+            self.reload(name)?;
+        }
+        return self
+            .read()
+            .get(name)
+            .map(|entry| Arc::clone(&entry.template))
+            .ok_or_else(|| TemplateError::NotFound(name.to_string()));
+    }
+
+    fn reload(&self, name: &str) -> Result<(), TemplateError> {
+        let (path, modified) = match self.read().get(name) {
+            Some(entry) => match entry.path {
+                Some(ref path) => (path.clone(), entry.modified),
+                None => return Ok(()),
+            },
+            None => match (self.dir.as_ref(), valid_name(name)) {
+                (Some(dir), true) => (dir.join(name), None),
+                _ => return Ok(()),
+            },
+        };
+        let current = fs::metadata(&path).and_then(|metadata| metadata.modified());
+        match current {
+            Ok(current) if Some(current) == modified => return Ok(()),
+            Err(_) if modified.is_none() => return Ok(()),
+            _ => return self.load(name, path),
+        }
+    }
+
+    // reads and parses a template file
+    fn load(&self, name: &str, path: PathBuf) -> Result<(), TemplateError> {
+        let modified = fs::metadata(&path)
+            .and_then(|metadata| metadata.modified())
+            .ok();
+        let source = fs::read_to_string(&path)?;
+        let template = parse(name, &source)?;
+        self.write().insert(
+            name.to_string(),
+            Entry {
+                template: Arc::new(template),
+                path: Some(path),
+                modified,
+            },
+        );
+        return Ok(());
+    }
+
+    fn read(&self) -> std::sync::RwLockReadGuard<'_, HashMap<String, Entry>> {
+        match self.templates.read() {
+            Ok(templates) => return templates,
+            Err(poisoned) => return poisoned.into_inner(),
+        }
+    }
+
+    fn write(&self) -> std::sync::RwLockWriteGuard<'_, HashMap<String, Entry>> {
+        match self.templates.write() {
+            Ok(templates) => return templates,
+            Err(poisoned) => return poisoned.into_inner(),
+        }
+    }
+}
+
+// the data a template is rendered with, and the variables of the loops it is in
+struct Scope<'a> {
+    root: &'a Value,
+    safe: &'a [&'a str],
+    locals: Vec<(String, Value)>,
+}
+
+impl Scope<'_> {
+    // evaluates an expression, returning it's value and whether it is safe to insert unescaped
+    fn evaluate(&self, expression: &Expression) -> (Value, bool) {
+        let mut value = match expression.operand {
+            Operand::Literal(ref value) => value.clone(),
+            Operand::Path(ref path) => self.lookup(path),
+        };
+        let mut safe = match expression.operand {
+            Operand::Path(ref path) => {
+                path.len() == 1
+                    && self.safe.contains(&path[0].as_str())
+                    && !self.locals.iter().any(|(name, _)| *name == path[0])
+            }
+            Operand::Literal(_) => false,
+        };
+        for filter in &expression.filters {
+            value = match filter {
+                Filter::Upper => Value::String(display(&value).to_uppercase()),
+                Filter::Lower => Value::String(display(&value).to_lowercase()),
+                Filter::Trim => Value::String(display(&value).trim().to_string()),
+                Filter::Length => match value {
+                    Value::String(ref text) => Value::from(text.chars().count()),
+                    Value::Array(ref items) => Value::from(items.len()),
+                    Value::Object(ref entries) => Value::from(entries.len()),
+                    _ => Value::from(0),
+                },
+                Filter::Default(ref default) => match value {
+                    Value::Null => default.clone(),
+                    Value::String(ref text) if text.is_empty() => default.clone(),
+                    _ => value,
+                },
+                Filter::Safe => {
+                    safe = true;
+                    value
+                }
+            };
+        }
+        return (value, safe);
+    }
+
+    fn test(&self, condition: &Condition) -> bool {
+        match condition {
+            Condition::Or(conditions) => return conditions.iter().any(|c| self.test(c)),
+            Condition::And(conditions) => return conditions.iter().all(|c| self.test(c)),
+            Condition::Not(condition) => return !self.test(condition),
+            Condition::Compare(left, equal, right) => {
+                let (left, right) = (self.evaluate(left).0, self.evaluate(right).0);
+                let same = match (&left, &right) {
+                    (Value::Number(left), Value::Number(right)) => left.as_f64() == right.as_f64(),
+                    _ => left == right,
+                };
+                return same == *equal;
+            }
+            Condition::Truthy(expression) => return truthy(&self.evaluate(expression).0),
+        }
+    }
+
+    // looks up a dotted path in the loop variables and then in the data
+    fn lookup(&self, path: &[String]) -> Value {
+        let first = match path.first() {
+            Some(first) => first,
+            None => return Value::Null,
+        };
+        let mut value = match self.locals.iter().rev().find(|(name, _)| name == first) {
+            Some((_, value)) => value,
+            None => match self.root.get(first.as_str()) {
+                Some(value) => value,
+                None => return Value::Null,
+            },
+        };
+        for segment in &path[1..] {
+            let next = match value {
+                Value::Object(entries) => entries.get(segment.as_str()),
+                Value::Array(items) => segment.parse::<usize>().ok().and_then(|i| items.get(i)),
+                _ => None,
+            };
+            value = match next {
+                Some(next) => next,
+                None => return Value::Null,
+            };
+        }
+        return value.clone();
+    }
+}
+
+// returns the text a value is inserted as
+fn display(value: &Value) -> String {
+    match value {
+        Value::Null => return String::new(),
+        Value::String(text) => return text.clone(),
+        Value::Bool(_) | Value::Number(_) => return value.to_string(),
+        Value::Array(_) | Value::Object(_) => {
+            return serde_json::to_string(value).unwrap_or_default()
+        }
+    }
+}
+
+fn truthy(value: &Value) -> bool {
+    match value {
+        Value::Null => return false,
+        Value::Bool(value) => return *value,
+        Value::Number(number) => return number.as_f64() != Some(0.0),
+        Value::String(text) => return !text.is_empty(),
+        Value::Array(items) => return !items.is_empty(),
+        Value::Object(entries) => return !entries.is_empty(),
+    }
+}
+
+// returns whether a template name stays within the template directory
+fn valid_name(name: &str) -> bool {
+    return !name
+        .split('/')
+        .any(|part| part.is_empty() || part == "." || part == ".." || part.contains('\\'));
+}
+
+// collects the files of a directory and it's subdirectories, named after their relative path
+fn collect_files(
+    dir: &Path,
+    prefix: &str,
+    files: &mut Vec<(String, PathBuf)>,
+) -> Result<(), TemplateError> {
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        let file_name = entry.file_name().to_string_lossy().to_string();
+        if file_name.starts_with('.') {
+            continue;
+        }
+        let name = format!("{}{}", prefix, file_name);
+        let path = entry.path();
+        match entry.file_type()?.is_dir() {
+            true => collect_files(&path, &format!("{}/", name), files)?,
+            false => files.push((name, path)),
+        }
+    }
+    return Ok(());
+}
+
+// parses a template, the errors carry the name of the template
+fn parse(name: &str, source: &str) -> Result<Template, TemplateError> {
+    let syntax_error = |(line, message)| TemplateError::Syntax(name.to_string(), line, message);
+    let tokens = tokenize(source).map_err(syntax_error)?;
+    let mut parser = Parser {
+        tokens: tokens.into_iter(),
+        parent: None,
+        blocks: HashMap::new(),
+    };
+    let (nodes, _) = parser.parse_nodes(&[]).map_err(syntax_error)?;
+    return Ok(Template {
+        parent: parser.parent,
+        nodes,
+        blocks: parser.blocks,
+    });
+}
+
+// splits a template source into text, expressions and tags, dropping comments. A `-` right inside
+// a tag(`{%- .. %}`) strips the whitespace before it
+fn tokenize(source: &str) -> Result<Vec<Token>, (usize, String)> {
+    let mut tokens = vec![];
+    let mut rest = source;
+    let mut line = 1;
+    loop {
+        let start = ["{{", "{%", "{#"]
+            .iter()
+            .filter_map(|open| rest.find(open))
+            .min();
+        let start = match start {
+            Some(start) => start,
+            None => {
+                if !rest.is_empty() {
+                    tokens.push(Token::Text(rest.to_string()));
+                }
+                return Ok(tokens);
+            }
+        };
+        let open = &rest[start..start + 2];
+        let close = match open {
+            "{{" => "}}",
+            "{%" => "%}",
+            _ => "#}",
+        };
+        let after = &rest[start + 2..];
+        let end = match after.find(close) {
+            Some(end) => end,
+            None => return Err((line, format!("`{}` is never closed", open))),
+        };
+        let mut text = &rest[..start];
+        let inner = match after[..end].strip_prefix('-') {
+            Some(inner) => {
+                text = text.trim_end();
+                inner
+            }
+            None => &after[..end],
+        };
+        if !text.is_empty() {
+            tokens.push(Token::Text(text.to_string()));
+        }
+        line += rest[..start].matches('\n').count();
+        match open {
+            "{{" => tokens.push(Token::Expression(inner.trim().to_string(), line)),
+            "{%" => tokens.push(Token::Tag(inner.trim().to_string(), line)),
+            _ => {}
+        }
+        line += after[..end].matches('\n').count();
+        rest = &after[end + 2..];
+    }
+}
+
+// the tag which ended a list of nodes, with it's arguments and line
+type EndTag = (String, String, usize);
+
+struct Parser {
+    tokens: std::vec::IntoIter<Token>,
+    parent: Option<String>,
+    blocks: HashMap<String, Vec<Node>>,
+}
+
+impl Parser {
+    // parses nodes until one of the given tags, which is returned
+    fn parse_nodes(
+        &mut self,
+        ends: &[&str],
+    ) -> Result<(Vec<Node>, Option<EndTag>), (usize, String)> {
+        let mut nodes = vec![];
+        while let Some(token) = self.tokens.next() {
+            let (tag, line) = match token {
+                Token::Text(text) => {
+                    nodes.push(Node::Text(text));
+                    continue;
+                }
+                Token::Expression(expression, line) => {
+                    let expression = parse_expression(&expression).map_err(|e| (line, e))?;
+                    nodes.push(Node::Expression(expression));
+                    continue;
+                }
+                Token::Tag(tag, line) => (tag, line),
+            };
+            let (keyword, arguments) = match tag.split_once(char::is_whitespace) {
+                Some((keyword, arguments)) => (keyword, arguments.trim()),
+                None => (tag.as_str(), ""),
+            };
+            if ends.contains(&keyword) {
+                return Ok((
+                    nodes,
+                    Some((keyword.to_string(), arguments.to_string(), line)),
+                ));
+            }
+            match keyword {
+                "if" => nodes.push(self.parse_if(arguments, line)?),
+                "for" => nodes.push(self.parse_for(arguments, line)?),
+                "include" => nodes.push(Node::Include(parse_name(arguments, line)?)),
+                "extends" => self.parent = Some(parse_name(arguments, line)?),
+                "block" => {
+                    if arguments.is_empty() || arguments.contains(char::is_whitespace) {
+                        return Err((line, format!("invalid block name `{}`", arguments)));
+                    }
+                    let (body, _) = self.expect(&["endblock"], "block", line)?;
+                    self.blocks.insert(arguments.to_string(), body.clone());
+                    nodes.push(Node::Block(arguments.to_string(), body));
+                }
+                "elif" | "else" | "endif" | "endfor" | "endblock" => {
+                    return Err((line, format!("unexpected `{}`", keyword)));
+                }
+                _ => return Err((line, format!("unknown tag `{}`", keyword))),
+            }
+        }
+        return Ok((nodes, None));
+    }
+
+    // parses nodes until one of the given tags, which has to follow
+    fn expect(
+        &mut self,
+        ends: &[&str],
+        opener: &str,
+        line: usize,
+    ) -> Result<(Vec<Node>, EndTag), (usize, String)> {
+        match self.parse_nodes(ends)? {
+            (nodes, Some(end)) => return Ok((nodes, end)),
+            (_, None) => return Err((line, format!("`{}` is never closed", opener))),
+        }
+    }
+
+    fn parse_if(&mut self, arguments: &str, line: usize) -> Result<Node, (usize, String)> {
+        let mut branches = vec![];
+        let mut condition = parse_condition(arguments).map_err(|e| (line, e))?;
+        loop {
+            let (body, (keyword, arguments, end_line)) =
+                self.expect(&["elif", "else", "endif"], "if", line)?;
+            branches.push((condition, body));
+            match keyword.as_str() {
+                "elif" => condition = parse_condition(&arguments).map_err(|e| (end_line, e))?,
+                "else" => {
+                    let (otherwise, _) = self.expect(&["endif"], "if", line)?;
+                    return Ok(Node::If(branches, otherwise));
+                }
+                _ => return Ok(Node::If(branches, vec![])),
+            }
+        }
+    }
+
+    fn parse_for(&mut self, arguments: &str, line: usize) -> Result<Node, (usize, String)> {
+        let invalid = || (line, format!("invalid loop `{}`", arguments));
+        let (item, iterable) = arguments.split_once(" in ").ok_or_else(invalid)?;
+        let item = item.trim();
+        if item.is_empty() || !item.chars().all(|c| c.is_alphanumeric() || c == '_') {
+            return Err(invalid());
+        }
+        let iterable = parse_expression(iterable).map_err(|e| (line, e))?;
+        let (body, (keyword, _, _)) = self.expect(&["else", "endfor"], "for", line)?;
+        let otherwise = match keyword.as_str() {
+            "else" => self.expect(&["endfor"], "for", line)?.0,
+            _ => vec![],
+        };
+        return Ok(Node::For(item.to_string(), iterable, body, otherwise));
+    }
+}
+
+// parses the quoted template name of an `include` or `extends` tag
+fn parse_name(arguments: &str, line: usize) -> Result<String, (usize, String)> {
+    match lex(arguments).as_deref() {
+        Ok([Lexeme::Literal(Value::String(name))]) => return Ok(name.clone()),
+        _ => {
+            return Err((
+                line,
+                format!("expected a quoted template name, got `{}`", arguments),
+            ))
+        }
+    }
+}
+
+fn parse_expression(source: &str) -> Result<Expression, String> {
+    let mut lexemes = Lexemes::new(source)?;
+    let expression = lexemes.expression()?;
+    lexemes.finish()?;
+    return Ok(expression);
+}
+
+fn parse_condition(source: &str) -> Result<Condition, String> {
+    let mut lexemes = Lexemes::new(source)?;
+    let condition = lexemes.or()?;
+    lexemes.finish()?;
+    return Ok(condition);
+}
+
+// a cursor over the lexemes of an expression or condition
+struct Lexemes {
+    source: String,
+    lexemes: Vec<Lexeme>,
+    position: usize,
+}
+
+impl Lexemes {
+    fn new(source: &str) -> Result<Lexemes, String> {
+        return Ok(Lexemes {
+            source: source.to_string(),
+            lexemes: lex(source)?,
+            position: 0,
+        });
+    }
+
+    fn peek(&self) -> Option<&Lexeme> {
+        return self.lexemes.get(self.position);
+    }
+
+    fn next(&mut self) -> Option<Lexeme> {
+        let lexeme = self.lexemes.get(self.position).cloned();
+        self.position += 1;
+        return lexeme;
+    }
+
+    // consumes the next lexeme if it is the given word or symbol
+    fn accept(&mut self, expected: &str) -> bool {
+        let matches = match self.peek() {
+            Some(Lexeme::Word(word)) => word == expected,
+            Some(Lexeme::Symbol(symbol)) => *symbol == expected,
+            _ => false,
+        };
+        if matches {
+            self.position += 1;
+        }
+        return matches;
+    }
+
+    fn finish(&self) -> Result<(), String> {
+        match self.peek() {
+            Some(lexeme) => return Err(format!("unexpected {:?} in `{}`", lexeme, self.source)),
+            None => return Ok(()),
+        }
+    }
+
+    fn or(&mut self) -> Result<Condition, String> {
+        let mut conditions = vec![self.and()?];
+        while self.accept("or") {
+            conditions.push(self.and()?);
+        }
+        match conditions.len() {
+            1 => return Ok(conditions.remove(0)),
+            _ => return Ok(Condition::Or(conditions)),
+        }
+    }
+
+    fn and(&mut self) -> Result<Condition, String> {
+        let mut conditions = vec![self.not()?];
+        while self.accept("and") {
+            conditions.push(self.not()?);
+        }
+        match conditions.len() {
+            1 => return Ok(conditions.remove(0)),
+            _ => return Ok(Condition::And(conditions)),
+        }
+    }
+
+    fn not(&mut self) -> Result<Condition, String> {
+        if self.accept("not") {
+            return Ok(Condition::Not(Box::new(self.not()?)));
+        }
+        let left = self.expression()?;
+        if self.accept("==") {
+            return Ok(Condition::Compare(left, true, self.expression()?));
+        }
+        if self.accept("!=") {
+            return Ok(Condition::Compare(left, false, self.expression()?));
+        }
+        return Ok(Condition::Truthy(left));
+    }
+
+    fn expression(&mut self) -> Result<Expression, String> {
+        let operand = match self.next() {
+            Some(Lexeme::Literal(value)) => Operand::Literal(value),
+            Some(Lexeme::Word(word)) if !["and", "or", "not"].contains(&word.as_str()) => {
+                Operand::Path(word.split('.').map(|part| part.to_string()).collect())
+            }
+            _ => return Err(format!("expected a value in `{}`", self.source)),
+        };
+        let mut filters = vec![];
+        while self.accept("|") {
+            let filter = match self.next() {
+                Some(Lexeme::Word(word)) => word,
+                _ => return Err(format!("expected a filter in `{}`", self.source)),
+            };
+            filters.push(match filter.as_str() {
+                "upper" => Filter::Upper,
+                "lower" => Filter::Lower,
+                "trim" => Filter::Trim,
+                "length" => Filter::Length,
+                "safe" => Filter::Safe,
+                "default" => {
+                    let value = match (self.accept("("), self.next(), self.accept(")")) {
+                        (true, Some(Lexeme::Literal(value)), true) => value,
+                        _ => return Err(format!("expected `default(value)` in `{}`", self.source)),
+                    };
+                    Filter::Default(value)
+                }
+                _ => return Err(format!("unknown filter `{}`", filter)),
+            });
+        }
+        return Ok(Expression { operand, filters });
+    }
+}
+
+// splits an expression into words(names and dotted paths), literals and symbols
+fn lex(source: &str) -> Result<Vec<Lexeme>, String> {
+    let chars: Vec<char> = source.chars().collect();
+    let mut lexemes = vec![];
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        if c.is_whitespace() {
+            i += 1;
+        } else if c == '"' || c == '\'' {
+            let mut literal = String::new();
+            i += 1;
+            loop {
+                match chars.get(i) {
+                    Some(&quote) if quote == c => break,
+                    Some('\\') if i + 1 < chars.len() => {
+                        literal.push(chars[i + 1]);
+                        i += 1;
+                    }
+                    Some(&other) => literal.push(other),
+                    None => return Err(format!("unterminated string in `{}`", source)),
+                }
+                i += 1;
+            }
+            i += 1;
+            lexemes.push(Lexeme::Literal(Value::String(literal)));
+        } else if c.is_ascii_digit() {
+            let start = i;
+            while i < chars.len() && (chars[i].is_ascii_digit() || chars[i] == '.') {
+                i += 1;
+            }
+            let number: String = chars[start..i].iter().collect();
+            let value = serde_json::from_str::<Value>(&number)
+                .map_err(|_| format!("invalid number `{}`", number))?;
+            lexemes.push(Lexeme::Literal(value));
+        } else if c.is_alphanumeric() || c == '_' {
+            let start = i;
+            while i < chars.len() && (chars[i].is_alphanumeric() || "_.".contains(chars[i])) {
+                i += 1;
+            }
+            let word: String = chars[start..i].iter().collect();
+            match word.as_str() {
+                "true" => lexemes.push(Lexeme::Literal(Value::Bool(true))),
+                "false" => lexemes.push(Lexeme::Literal(Value::Bool(false))),
+                _ => lexemes.push(Lexeme::Word(word)),
+            }
+        } else {
+            let symbol = match (c, chars.get(i + 1)) {
+                ('=', Some('=')) => "==",
+                ('!', Some('=')) => "!=",
+                ('|', _) => "|",
+                ('(', _) => "(",
+                (')', _) => ")",
+                _ => return Err(format!("unexpected `{}` in `{}`", c, source)),
+            };
+            i += symbol.len();
+            lexemes.push(Lexeme::Symbol(symbol));
+        }
+    }
+    return Ok(lexemes);
+}
@@ -0,0 +1,122 @@
+//! This module provides HTTPS support for the `WebServer` using `rustls`.
+//!
+//! It is only available with the `tls` feature enabled. The TLS handshake happens on the worker
+//! thread handling the connection, after which the same router and handlers serve the decrypted
+//! requests exactly like plaintext ones. With `WebServer::accept_plaintext` a connection is only
+//! treated as TLS if it starts with a handshake record.
+
+// internal crate imports
+use crate::{connection, error};
+
+// external crate imports
+use rustls::{
+    pki_types::{CertificateDer, PrivateKeyDer},
+    ServerConfig, ServerConnection, StreamOwned,
+};
+
+// standard library imports
+use std::{
+    fs::File,
+    io::{self, BufReader},
+    net::TcpStream,
+    sync::Arc,
+};
+
+// the content type of TLS handshake records, the first record a client sends is the `ClientHello`
+pub(crate) const HANDSHAKE_RECORD: u8 = 0x16;
+
+/// A TLS stream wrapping an accepted TCP connection.
+pub type TlsStream = StreamOwned<ServerConnection, TcpStream>;
+
+/// Loads a `rustls` server configuration from PEM encoded certificate chain and private key files.
+///
+/// # Arguments
+///
+/// - `cert_path` - The path to the PEM file containing the certificate chain, leaf certificate
+///   first.
+/// - `key_path` - The path to the PEM file containing the private key(PKCS#1, PKCS#8 or SEC1).
+///
+/// # Returns
+///
+/// - `Result<Arc<ServerConfig>, WebServerError>` - The server configuration, or a `TlsError` if the
+///   files couldn't be read or don't contain a usable certificate and key.
+///
+/// # Examples
+///
+/// ```rust,no_run
+/// use browzer_web::tls::load_server_config;
+///
+/// let config = load_server_config("certs/cert.pem", "certs/key.pem").unwrap();
+/// ```
+pub fn load_server_config(
+    cert_path: &str,
+    key_path: &str,
+) -> Result<Arc<ServerConfig>, error::WebServerError> {
+    let tls_error = |message: String| error::WebServerError::TlsError(message);
+
+    let certs = match File::open(cert_path) {
+        Ok(file) => rustls_pemfile::certs(&mut BufReader::new(file))
+            .collect::<Result<Vec<CertificateDer<'static>>, _>>()
+            .map_err(|e| tls_error(format!("Failed to read {}: {}", cert_path, e)))?,
+        Err(e) => return Err(tls_error(format!("Failed to open {}: {}", cert_path, e))),
+    };
+    if certs.is_empty() {
+        return Err(tls_error(format!("No certificates found in {}", cert_path)));
+    }
+
+    let key: PrivateKeyDer<'static> = match File::open(key_path) {
+        Ok(file) => match rustls_pemfile::private_key(&mut BufReader::new(file)) {
+            Ok(Some(key)) => key,
+            Ok(None) => return Err(tls_error(format!("No private key found in {}", key_path))),
+            Err(e) => return Err(tls_error(format!("Failed to read {}: {}", key_path, e))),
+        },
+        Err(e) => return Err(tls_error(format!("Failed to open {}: {}", key_path, e))),
+    };
+
+    let mut config =
+        ServerConfig::builder_with_provider(Arc::new(rustls::crypto::ring::default_provider()))
+            .with_safe_default_protocol_versions()
+            .map_err(|e| tls_error(e.to_string()))?
+            .with_no_client_auth()
+            .with_single_cert(certs, key)
+            .map_err(|e| tls_error(e.to_string()))?;
+    config.alpn_protocols = vec![b"http/1.1".to_vec()];
+    return Ok(Arc::new(config));
+}
+
+// wraps an accepted TCP connection into a TLS stream, the handshake itself happens lazily on the
+// first read or write
+pub(crate) fn accept(
+    config: Arc<ServerConfig>,
+    stream: TcpStream,
+) -> Result<TlsStream, error::WebServerError> {
+    match ServerConnection::new(config) {
+        Ok(connection) => return Ok(StreamOwned::new(connection, stream)),
+        Err(e) => return Err(error::WebServerError::TlsError(e.to_string())),
+    }
+}
+
+// peeks at the first byte of a connection without consuming it, `Some(true)` if it starts a TLS
+// handshake record and `None` if the client closed the connection without sending anything
+pub(crate) fn starts_with_handshake(stream: &TcpStream) -> io::Result<Option<bool>> {
+    let mut first_byte = [0u8; 1];
+    match stream.peek(&mut first_byte)? {
+        0 => return Ok(None),
+        _ => return Ok(Some(first_byte[0] == HANDSHAKE_RECORD)),
+    }
+}
+
+// what the handshake of a connection negotiated, see `Connection::tls`
+pub(crate) fn tls_info(connection: &ServerConnection) -> connection::TlsInfo {
+    return connection::TlsInfo {
+        version: connection
+            .protocol_version()
+            .map(|version| version.as_str().unwrap_or("unknown").to_string()),
+        cipher_suite: connection
+            .negotiated_cipher_suite()
+            .map(|suite| suite.suite().as_str().unwrap_or("unknown").to_string()),
+        alpn_protocol: connection
+            .alpn_protocol()
+            .map(|protocol| String::from_utf8_lossy(protocol).to_string()),
+    };
+}
@@ -0,0 +1,514 @@
+//! This module stops a `WebServer` gracefully, and hands it's listening socket over to a new
+//! process for zero-downtime restarts(like deploying a new build of an application).
+//!
+//! A server stops accepting connections once it's `ShutdownHandle` is triggered, finishes the
+//! requests in flight, closes keep-alive connections after their current response(idle ones right
+//! away) and returns from `WebServer::listen` once every connection is closed(or
+//! `WebServer::drain_timeout` passed).
+//!
+//! With `WebServer::upgrade_socket` the server listens for commands on a unix control socket, one
+//! command per connection, answered with a single line starting with `ok` or `error`:
+//!
+//! - `status` - reports the process id and the number of open connections.
+//! - `stop` - stops the server gracefully.
+//! - `upgrade` - starts the new process(the current executable with the same arguments by
+//!   default, see `WebServer::upgrade_command`), which inherits the listening sockets through the
+//!   `LISTENER_FD_VAR` environment variable, so no connection is refused while both processes
+//!   run. `WebServer::new` and `WebServer::bind` pick the inherited sockets up instead of binding
+//!   new ones. Once the new process is listening it reports back on the control socket, takes it
+//!   over and the old process stops gracefully. A new process which doesn't report back within `READY_TIMEOUT` is killed,
+//!   and the old one keeps serving.
+//!
+//! Commands can be sent with any tool speaking unix sockets(like `socat`), or with `send_command`
+//! from a subcommand of the application itself. Inheriting and polling the listening socket is
+//! only supported on unix platforms, elsewhere a shutdown takes effect once the next connection is
+//! accepted.
+
+// internal crate imports
+use crate::error;
+
+// standard library imports
+#[cfg(unix)]
+use std::{
+    collections::VecDeque,
+    env,
+    ffi::OsString,
+    io::{self, BufRead, BufReader, Write},
+    net::{TcpListener, ToSocketAddrs},
+    os::unix::{
+        io::{AsRawFd, FromRawFd, RawFd},
+        net::{UnixListener, UnixStream},
+        process::{parent_id, CommandExt},
+    },
+    path::{Path, PathBuf},
+    process::{self, Child, Command},
+    sync::{
+        atomic::AtomicUsize,
+        mpsc::{self, Sender},
+        Mutex, MutexGuard,
+    },
+    thread,
+    time::Instant,
+};
+use std::{
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+
+/// The environment variable a new process finds the file descriptors of the inherited listening
+/// sockets in, separated by commas in the order of `WebServer::local_addrs` of the process it
+/// replaces.
+pub const LISTENER_FD_VAR: &str = "BROWZER_LISTENER_FD";
+
+/// The environment variable a new process finds the control socket of the process it replaces in.
+pub const PARENT_SOCKET_VAR: &str = "BROWZER_UPGRADE_PARENT";
+
+/// The environment variable a new process finds the process id of the process it replaces in. The
+/// other variables are only taken from the environment of a process started by that process, so
+/// the processes the new one starts itself ignore the copies they inherit.
+pub const PARENT_PID_VAR: &str = "BROWZER_UPGRADE_PARENT_PID";
+
+/// How long the requests in flight may take to finish once the server stops, by default.
+pub const DEFAULT_DRAIN_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// How long a new process may take to start listening before the upgrade is abandoned.
+pub const READY_TIMEOUT: Duration = Duration::from_secs(30);
+
+// how often the accept loop checks whether the server is stopping while no connection arrives
+#[cfg(unix)]
+const ACCEPT_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+// how often an idle keep-alive connection checks whether the server is stopping, so it's closed
+// right away instead of once it timed out
+pub(crate) const IDLE_POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+// how long a control connection may take to send it's command
+#[cfg(unix)]
+const COMMAND_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Stops a listening `WebServer` gracefully, see `WebServer::shutdown_handle`.
+///
+/// Handles are cheap to clone, and all clones stop the same server.
+///
+/// # Examples
+///
+/// ```rust,no_run
+/// # use browzer_web::WebServer;
+/// let server = WebServer::new("127.0.0.1:8080".to_string(), 4);
+/// let shutdown = server.shutdown_handle();
+///
+/// std::thread::spawn(move || {
+///     std::thread::sleep(std::time::Duration::from_secs(60));
+///     shutdown.shutdown();
+/// });
+/// // returns once the server stopped and every connection was closed
+/// server.listen();
+/// ```
+// ----- ShutdownHandle struct
+#[derive(Debug, Clone, Default)]
+pub struct ShutdownHandle {
+    stopping: Arc<AtomicBool>,
+}
+
+impl ShutdownHandle {
+    /// Stops the server from accepting connections, and lets it finish the ones it has.
+    pub fn shutdown(&self) {
+        self.stopping.store(true, Ordering::SeqCst);
+    }
+
+    /// Returns whether the server is stopping.
+    pub fn is_shutting_down(&self) -> bool {
+        return self.stopping.load(Ordering::SeqCst);
+    }
+}
+
+/// Takes the first listening socket inherited from the process this one replaces(the one of the
+/// address it's `WebServer` was created with), see the module documentation. Every socket is only
+/// taken once, the environment is left as it is(changing it isn't safe while other threads may
+/// read it) and processes started by this one ignore it, see `PARENT_PID_VAR`.
+///
+/// # Returns
+///
+/// - `Option<TcpListener>` - The inherited listener, `None` if this process didn't inherit one or
+///   it was taken already.
+#[cfg(unix)]
+pub fn inherited_listener() -> Option<TcpListener> {
+    let mut inherited = inherited_listeners();
+    return inherited.as_mut()?.pop_front();
+}
+
+// takes the listening socket inherited for an address passed to `WebServer::bind`, the one bound
+// to the same address(or to the same IP address, for port `0`)
+#[cfg(unix)]
+pub(crate) fn inherited_listener_for(address: &str) -> Option<TcpListener> {
+    let mut inherited = inherited_listeners();
+    let inherited = inherited.as_mut()?;
+    let addresses = address.to_socket_addrs().ok()?.collect::<Vec<_>>();
+    let position = inherited.iter().position(|listener| {
+        let local_addr = match listener.local_addr() {
+            Ok(local_addr) => local_addr,
+            Err(_) => return false,
+        };
+        return addresses.iter().any(|address| {
+            return *address == local_addr
+                || (address.port() == 0 && address.ip() == local_addr.ip());
+        });
+    })?;
+    return inherited.remove(position);
+}
+
+// the listening sockets inherited from the process this one replaces which weren't taken yet,
+// read from the environment once this process finds them there. A second `TcpListener` owning
+// the same file descriptor would close it twice, so they are only ever read once
+#[cfg(unix)]
+static INHERITED_LISTENERS: Mutex<Option<VecDeque<TcpListener>>> = Mutex::new(None);
+
+// locks the inherited listening sockets, reading them from the environment if they weren't yet
+#[cfg(unix)]
+fn inherited_listeners() -> MutexGuard<'static, Option<VecDeque<TcpListener>>> {
+    let mut inherited = INHERITED_LISTENERS
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner());
+    if inherited.is_some() {
+        return inherited;
+    }
+    let fds = match inherited_var(LISTENER_FD_VAR) {
+        Some(fds) => fds,
+        None => return inherited,
+    };
+    let listeners = fds
+        .to_string_lossy()
+        .split(',')
+        .filter_map(|fd| fd.trim().parse::<RawFd>().ok())
+        .filter_map(|fd| {
+            // the descriptor has to be open, and is closed again in the processes this one starts
+            if unsafe { libc::fcntl(fd, libc::F_SETFD, libc::FD_CLOEXEC) } == -1 {
+                eprintln!(
+                    "Warning: the inherited listening socket {} isn't open, {}",
+                    fd,
+                    io::Error::last_os_error()
+                );
+                return None;
+            }
+            return Some(unsafe { TcpListener::from_raw_fd(fd) });
+        })
+        .collect();
+    *inherited = Some(listeners);
+    return inherited;
+}
+
+// whether this process reported to the process it replaces, which it does only once
+#[cfg(unix)]
+static PARENT_REPORTED: AtomicBool = AtomicBool::new(false);
+
+// reads a variable passed on by the process this one replaces, `None` unless this process was
+// started by it
+#[cfg(unix)]
+fn inherited_var(name: &str) -> Option<OsString> {
+    let parent = env::var(PARENT_PID_VAR).ok()?.parse::<u32>().ok()?;
+    if parent != parent_id() {
+        return None;
+    }
+    return env::var_os(name);
+}
+
+/// Sends a command to the control socket of a server and returns it's answer.
+///
+/// # Arguments
+///
+/// - `path` - The path of the control socket, see `WebServer::upgrade_socket`.
+/// - `command` - The command, like `upgrade`.
+///
+/// # Returns
+///
+/// - `Result<String, ControlError>` - The answer without it's line break, or a `ControlError` if
+///   the server couldn't be reached or rejected the command.
+///
+/// # Examples
+///
+/// ```rust,no_run
+/// use browzer_web::upgrade::send_command;
+///
+/// match send_command("/run/app/control.sock", "upgrade") {
+///     Ok(answer) => println!("upgraded: {}", answer),
+///     Err(e) => eprintln!("upgrade failed: {}", e),
+/// }
+/// ```
+#[cfg(unix)]
+pub fn send_command<P: AsRef<Path>>(path: P, command: &str) -> Result<String, error::ControlError> {
+    let mut stream = UnixStream::connect(path)?;
+    // an upgrade waits for the new process to start listening
+    stream.set_read_timeout(Some(READY_TIMEOUT + COMMAND_TIMEOUT))?;
+    stream.write_all(format!("{}\n", command.trim()).as_bytes())?;
+    let mut answer = String::new();
+    BufReader::new(stream).read_line(&mut answer)?;
+    let answer = answer.trim_end().to_string();
+    match answer.strip_prefix("error") {
+        Some(message) => {
+            return Err(error::ControlError::Rejected(
+                message.trim_start().to_string(),
+            ))
+        }
+        None if answer.starts_with("ok") => return Ok(answer),
+        None => return Err(error::ControlError::InvalidAnswer(answer)),
+    }
+}
+
+// waits until a connection can be accepted from a listening socket, or the timeout passed
+#[cfg(unix)]
+pub(crate) fn wait_acceptable(listener: &TcpListener) -> bool {
+    let mut poll_fd = libc::pollfd {
+        fd: listener.as_raw_fd(),
+        events: libc::POLLIN,
+        revents: 0,
+    };
+    let timeout = ACCEPT_POLL_INTERVAL.as_millis() as libc::c_int;
+    // errors are left to `accept` to report
+    return unsafe { libc::poll(&mut poll_fd, 1, timeout) } != 0;
+}
+
+// the upgrade in progress, waiting for the new process to report back
+#[cfg(unix)]
+struct PendingUpgrade {
+    pid: u32,
+    ready: Sender<()>,
+}
+
+/// The command line starting the new process of an upgrade.
+///
+/// # Fields
+///
+/// - `program` - The executable.
+/// - `args` - The arguments.
+#[cfg(unix)]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UpgradeCommand {
+    pub program: PathBuf,
+    pub args: Vec<OsString>,
+}
+
+#[cfg(unix)]
+impl UpgradeCommand {
+    /// Returns the command line of the current process, so an upgrade starts the executable at
+    /// it's path(which may have been replaced by a new build since) with the same arguments.
+    pub fn current() -> io::Result<UpgradeCommand> {
+        let program = env::current_exe()?;
+        // the path of a replaced executable is reported with a suffix on linux
+        let program = match program
+            .to_str()
+            .and_then(|path| path.strip_suffix(" (deleted)"))
+        {
+            Some(path) => PathBuf::from(path),
+            None => program,
+        };
+        return Ok(UpgradeCommand {
+            program,
+            args: env::args_os().skip(1).collect(),
+        });
+    }
+}
+
+// the control socket of a server, see the module documentation
+#[cfg(unix)]
+pub(crate) struct ControlSocket {
+    path: PathBuf,
+    command: Option<UpgradeCommand>,
+    listener_fds: Vec<RawFd>,
+    active_connections: Arc<AtomicUsize>,
+    shutdown: ShutdownHandle,
+    pending: Mutex<Option<PendingUpgrade>>,
+    handed_off: AtomicBool,
+}
+
+#[cfg(unix)]
+impl ControlSocket {
+    // takes the control socket over from the process this one replaces, if any, and starts
+    // answering commands on it
+    pub(crate) fn start(
+        path: &Path,
+        command: Option<UpgradeCommand>,
+        listeners: &[&TcpListener],
+        active_connections: Arc<AtomicUsize>,
+        shutdown: ShutdownHandle,
+    ) -> io::Result<Arc<ControlSocket>> {
+        // the old process learns that this one is listening before the socket is taken over, so
+        // it can't miss the report
+        let parent = inherited_var(PARENT_SOCKET_VAR)
+            .filter(|_| !PARENT_REPORTED.swap(true, Ordering::SeqCst));
+        if let Some(parent) = parent {
+            let report = format!("ready {}", process::id());
+            if let Err(e) = send_command(&parent, &report) {
+                eprintln!("Failed to report to the replaced process, Error: {}", e);
+            }
+        }
+        match std::fs::remove_file(path) {
+            Ok(_) => {}
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+            Err(e) => return Err(e),
+        }
+        let control_listener = UnixListener::bind(path)?;
+
+        let control = Arc::new(ControlSocket {
+            path: path.to_path_buf(),
+            command,
+            listener_fds: listeners
+                .iter()
+                .map(|listener| listener.as_raw_fd())
+                .collect(),
+            active_connections,
+            shutdown,
+            pending: Mutex::new(None),
+            handed_off: AtomicBool::new(false),
+        });
+        let weak = Arc::downgrade(&control);
+        thread::spawn(move || {
+            for stream in control_listener.incoming() {
+                let control = match weak.upgrade() {
+                    Some(control) => control,
+                    None => return,
+                };
+                if let Ok(stream) = stream {
+                    // an upgrade waits for the new process, which reports back on this socket
+                    thread::spawn(move || control.answer(stream));
+                }
+            }
+        });
+        return Ok(control);
+    }
+
+    // removes the control socket, unless the process replacing this one took it over
+    pub(crate) fn close(&self) {
+        if !self.handed_off.load(Ordering::SeqCst) {
+            let _ = std::fs::remove_file(&self.path);
+        }
+    }
+
+    // reads a command from a control connection and writes back the answer
+    fn answer(&self, stream: UnixStream) {
+        let _ = stream.set_read_timeout(Some(COMMAND_TIMEOUT));
+        let mut reader = BufReader::new(stream);
+        let mut command = String::new();
+        if reader.read_line(&mut command).is_err() {
+            return;
+        }
+        let answer = match command.split_whitespace().collect::<Vec<_>>()[..] {
+            ["status"] => Ok(format!(
+                "pid={} connections={}",
+                process::id(),
+                self.active_connections.load(Ordering::SeqCst)
+            )),
+            ["stop"] => {
+                self.shutdown.shutdown();
+                Ok("stopping".to_string())
+            }
+            ["upgrade"] => self.upgrade(),
+            ["ready", pid] => self.ready(pid),
+            _ => Err(format!("unknown command {:?}", command.trim())),
+        };
+        let answer = match answer {
+            Ok(message) => format!("ok {}\n", message),
+            Err(message) => format!("error {}\n", message),
+        };
+        let _ = reader.get_mut().write_all(answer.as_bytes());
+    }
+
+    // starts the new process and stops this one once the new one reported back
+    fn upgrade(&self) -> Result<String, String> {
+        if self.shutdown.is_shutting_down() {
+            return Err("the server is stopping".to_string());
+        }
+        let command = match self.command {
+            Some(ref command) => command.clone(),
+            None => UpgradeCommand::current().map_err(|e| e.to_string())?,
+        };
+        let (ready, ready_receiver) = mpsc::channel();
+        let mut pending = self.pending.lock().unwrap_or_else(|e| e.into_inner());
+        if pending.is_some() {
+            return Err("an upgrade is in progress already".to_string());
+        }
+        let mut child = self.spawn(&command).map_err(|e| e.to_string())?;
+        *pending = Some(PendingUpgrade {
+            pid: child.id(),
+            ready,
+        });
+        drop(pending);
+
+        let result = self.wait_ready(&mut child, ready_receiver);
+        *self.pending.lock().unwrap_or_else(|e| e.into_inner()) = None;
+        match result {
+            Ok(_) => {
+                self.handed_off.store(true, Ordering::SeqCst);
+                self.shutdown.shutdown();
+                return Ok(format!("pid={}", child.id()));
+            }
+            Err(message) => {
+                let _ = child.kill();
+                let _ = child.wait();
+                return Err(message);
+            }
+        }
+    }
+
+    // starts the new process, passing it the listening sockets
+    fn spawn(&self, command: &UpgradeCommand) -> io::Result<Child> {
+        let fds = self.listener_fds.clone();
+        let fd_list = fds
+            .iter()
+            .map(|fd| fd.to_string())
+            .collect::<Vec<_>>()
+            .join(",");
+        let mut process = Command::new(&command.program);
+        process
+            .args(&command.args)
+            .env(LISTENER_FD_VAR, fd_list)
+            .env(PARENT_SOCKET_VAR, &self.path)
+            .env(PARENT_PID_VAR, process::id().to_string());
+        // sockets are closed on exec by default, the listening sockets are kept open in the new
+        // process only
+        unsafe {
+            process.pre_exec(move || {
+                for fd in fds.iter() {
+                    if libc::fcntl(*fd, libc::F_SETFD, 0) == -1 {
+                        return Err(io::Error::last_os_error());
+                    }
+                }
+                return Ok(());
+            });
+        }
+        return process.spawn();
+    }
+
+    // waits for the new process to report back, while making sure it is still running
+    fn wait_ready(&self, child: &mut Child, ready: mpsc::Receiver<()>) -> Result<(), String> {
+        let deadline = Instant::now() + READY_TIMEOUT;
+        while Instant::now() < deadline {
+            match ready.recv_timeout(Duration::from_millis(100)) {
+                Ok(_) => return Ok(()),
+                Err(mpsc::RecvTimeoutError::Timeout) => {}
+                Err(mpsc::RecvTimeoutError::Disconnected) => break,
+            }
+            if let Ok(Some(status)) = child.try_wait() {
+                return Err(format!("the new process exited with {}", status));
+            }
+        }
+        return Err(format!(
+            "the new process didn't start listening within {:?}",
+            READY_TIMEOUT
+        ));
+    }
+
+    // handles the report of a new process which started listening
+    fn ready(&self, pid: &str) -> Result<String, String> {
+        let pending = self.pending.lock().unwrap_or_else(|e| e.into_inner());
+        match *pending {
+            Some(ref upgrade) if upgrade.pid.to_string() == pid => {
+                let _ = upgrade.ready.send(());
+                return Ok("handing over".to_string());
+            }
+            _ => return Err(format!("no upgrade to process {} in progress", pid)),
+        }
+    }
+}
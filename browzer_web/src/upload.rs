@@ -0,0 +1,859 @@
+//! This module provides resumable uploads following the tus protocol(https://tus.io, version
+//! 1.0.0, with the `creation` extension).
+//!
+//! Instead of sending a large file in a single request, which has to start over from scratch when
+//! the connection drops, a client first creates an upload by announcing it's length, then appends
+//! the data in one or more `PATCH` requests. After an interruption, a `HEAD` request tells the
+//! client at which offset to resume. The uploaded data is kept by a pluggable `UploadStore`.
+//!
+//! Every `PATCH` request is buffered in memory before it is appended, so clients should send large
+//! files in chunks(most tus clients have a `chunkSize` option) rather than in a single request.
+//!
+//! For plain `multipart/form-data` uploads(like from an HTML form), the module also provides an
+//! `UploadReceiver`, which stores the uploaded files in a directory, the counterpart to
+//! `WebServer::serve_static`.
+
+// internal crate imports
+use crate::{context, digest, error, multipart, response, utils};
+
+// standard library imports
+use std::{
+    collections::BTreeMap,
+    fmt, fs,
+    io::{self, Write},
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+};
+
+// external crate imports
+use serde::Serialize;
+use uuid::Uuid;
+
+/// The version of the tus protocol which is implemented.
+pub const TUS_VERSION: &str = "1.0.0";
+
+// the media type of the body of `PATCH` requests
+const OFFSET_OCTET_STREAM: &str = "application/offset+octet-stream";
+
+/// A boxed hook which is called every time data was appended to an upload.
+pub type ProgressHook = Box<dyn Fn(&UploadInfo) + 'static + Send + Sync>;
+
+/// The state of an upload.
+///
+/// # Fields
+///
+/// - `id` - The id of the upload, which is the last segment of it's URL.
+/// - `offset` - The number of bytes received so far.
+/// - `length` - The total length of the upload in bytes, as announced by the client.
+/// - `metadata` - The raw `Upload-Metadata` header the upload was created with, if any.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UploadInfo {
+    pub id: String,
+    pub offset: u64,
+    pub length: u64,
+    pub metadata: Option<String>,
+}
+
+impl UploadInfo {
+    /// Returns whether all the data of the upload was received.
+    pub fn is_complete(&self) -> bool {
+        return self.offset >= self.length;
+    }
+}
+
+/// A storage backend for resumable uploads.
+///
+/// Implement this trait to keep uploads somewhere other than the local file system, like an object
+/// storage.
+pub trait UploadStore: Send + Sync {
+    /// Creates a new, empty upload with the given length and metadata.
+    ///
+    /// # Returns
+    ///
+    /// - `io::Result<UploadInfo>` - The state of the new upload, including it's generated id.
+    fn create(&self, length: u64, metadata: Option<String>) -> io::Result<UploadInfo>;
+
+    /// Returns the state of the upload with the given id, or `None` if there is no such upload.
+    fn info(&self, id: &str) -> io::Result<Option<UploadInfo>>;
+
+    /// Appends data to the upload with the given id.
+    ///
+    /// This must be atomic with respect to the offset check, as it is what prevents two
+    /// concurrent requests from writing the same range of an upload.
+    ///
+    /// # Arguments
+    ///
+    /// - `id` - The id of the upload.
+    /// - `offset` - The offset the client claims to write at, which must equal the current offset.
+    /// - `data` - The data to append.
+    ///
+    /// # Returns
+    ///
+    /// - `Result<UploadInfo, UploadError>` - The state of the upload after appending the data, or
+    ///   `NotFound`, `OffsetMismatch` or `TooLarge`(if the data exceeds the length of the upload).
+    fn append(&self, id: &str, offset: u64, data: &[u8]) -> Result<UploadInfo, error::UploadError>;
+}
+
+/// An `UploadStore` implementation which keeps uploads as files in a directory.
+///
+/// The data of an upload is written to a file named after the upload id, next to a `.info` file
+/// holding the length and metadata of the upload.
+///
+/// # Examples
+///
+/// ```rust,no_run
+/// use browzer_web::upload::{FileUploadStore, UploadStore};
+///
+/// let store = FileUploadStore::new("uploads").unwrap();
+/// let upload = store.create(11, None).unwrap();
+///
+/// store.append(&upload.id, 0, b"Hello, ").unwrap();
+/// let upload = store.append(&upload.id, 7, b"tus!").unwrap();
+///
+/// assert!(upload.is_complete());
+/// println!("{}", store.path(&upload.id).display());
+/// ```
+// ----- FileUploadStore struct
+#[derive(Debug)]
+pub struct FileUploadStore {
+    dir: PathBuf,
+    lock: Mutex<()>,
+}
+
+impl FileUploadStore {
+    /// Creates a new `FileUploadStore` keeping it's uploads in the given directory, which is
+    /// created if it doesn't exist yet.
+    ///
+    /// # Errors
+    ///
+    /// Returns an I/O error if the directory couldn't be created.
+    pub fn new<P: AsRef<Path>>(dir: P) -> io::Result<FileUploadStore> {
+        fs::create_dir_all(dir.as_ref())?;
+        return Ok(FileUploadStore {
+            dir: dir.as_ref().to_path_buf(),
+            lock: Mutex::new(()),
+        });
+    }
+
+    /// Returns the path of the file holding the data of the upload with the given id.
+    pub fn path(&self, id: &str) -> PathBuf {
+        return self.dir.join(id);
+    }
+
+    // ids come from request paths, so only the generated ones are accepted to rule out path
+    // traversal
+    fn is_valid_id(id: &str) -> bool {
+        return Uuid::parse_str(id).is_ok();
+    }
+
+    fn read_info(&self, id: &str) -> io::Result<Option<UploadInfo>> {
+        if !Self::is_valid_id(id) {
+            return Ok(None);
+        }
+        let info = match fs::read_to_string(self.dir.join(format!("{}.info", id))) {
+            Ok(info) => info,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e),
+        };
+        let (length, metadata) = match info.split_once('\n') {
+            Some((length, metadata)) if !metadata.is_empty() => {
+                (length, Some(metadata.to_string()))
+            }
+            Some((length, _)) => (length, None),
+            None => (info.as_str(), None),
+        };
+        let length = match length.parse::<u64>() {
+            Ok(length) => length,
+            Err(e) => return Err(io::Error::new(io::ErrorKind::InvalidData, e)),
+        };
+        let offset = fs::metadata(self.path(id))?.len();
+        return Ok(Some(UploadInfo {
+            id: id.to_string(),
+            offset,
+            length,
+            metadata,
+        }));
+    }
+}
+
+impl UploadStore for FileUploadStore {
+    fn create(&self, length: u64, metadata: Option<String>) -> io::Result<UploadInfo> {
+        let id = Uuid::new_v4().to_string();
+        fs::File::create(self.path(&id))?;
+        fs::write(
+            self.dir.join(format!("{}.info", id)),
+            format!("{}\n{}", length, metadata.as_deref().unwrap_or("")),
+        )?;
+        return Ok(UploadInfo {
+            id,
+            offset: 0,
+            length,
+            metadata,
+        });
+    }
+
+    fn info(&self, id: &str) -> io::Result<Option<UploadInfo>> {
+        return self.read_info(id);
+    }
+
+    fn append(&self, id: &str, offset: u64, data: &[u8]) -> Result<UploadInfo, error::UploadError> {
+        let _lock = match self.lock.lock() {
+            Ok(lock) => lock,
+            Err(poisoned) => poisoned.into_inner(),
+        };
+        let mut info = match self.read_info(id)? {
+            Some(info) => info,
+            None => return Err(error::UploadError::NotFound),
+        };
+        if info.offset != offset {
+            return Err(error::UploadError::OffsetMismatch(info.offset));
+        }
+        if offset + data.len() as u64 > info.length {
+            return Err(error::UploadError::TooLarge(info.length));
+        }
+        let mut file = fs::OpenOptions::new().append(true).open(self.path(id))?;
+        file.write_all(data)?;
+        info.offset += data.len() as u64;
+        return Ok(info);
+    }
+}
+
+/// A handler for resumable uploads following the tus protocol.
+///
+/// It is registered under a path using `WebServer::resumable_uploads`, which handles:
+///
+/// - `POST <path>` - creates an upload from the `Upload-Length` and `Upload-Metadata` headers,
+///   answered with `201 Created` and the URL of the upload in the `Location` header.
+/// - `HEAD <path>/:id` - returns the current `Upload-Offset` of the upload.
+/// - `PATCH <path>/:id` - appends an `application/offset+octet-stream` body at the given
+///   `Upload-Offset`, answered with `204 No Content` and the new offset.
+/// - `OPTIONS <path>` - returns the supported protocol version, extensions and maximum size.
+///
+/// Requests other than `OPTIONS` must carry a `Tus-Resumable: 1.0.0` header, otherwise they get
+/// `412 Precondition Failed`.
+///
+/// # Fields
+///
+/// - `store` - The `UploadStore` the uploads are kept in.
+/// - `max_size` - The maximum length of an upload in bytes, if any.
+/// - `on_progress` - An optional hook which is called with the state of an upload every time data
+///   was appended to it, like to report progress or to process completed uploads.
+///
+/// # Examples
+///
+/// ```rust,no_run
+/// use browzer_web::{upload::{FileUploadStore, ResumableUploads}, WebServer};
+///
+/// let mut server = WebServer::new("127.0.0.1:8080".to_string(), 4);
+///
+/// let uploads = ResumableUploads::new(FileUploadStore::new("uploads").unwrap())
+///     .max_size(1024 * 1024 * 1024)
+///     .on_progress(|upload| {
+///         println!("{}: {}/{} bytes", upload.id, upload.offset, upload.length);
+///     });
+/// server.resumable_uploads("/files", uploads);
+/// ```
+// ----- ResumableUploads struct
+#[derive(Clone)]
+pub struct ResumableUploads {
+    store: Arc<dyn UploadStore>,
+    max_size: Option<u64>,
+    on_progress: Option<Arc<ProgressHook>>,
+}
+
+impl fmt::Debug for ResumableUploads {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ResumableUploads")
+            .field("store", &"Arc<dyn UploadStore>")
+            .field("max_size", &self.max_size)
+            .field(
+                "on_progress",
+                &"Option<Arc<Box<dyn Fn(&UploadInfo) + 'static + Send + Sync>>>",
+            )
+            .finish()
+    }
+}
+
+impl ResumableUploads {
+    /// Creates a new `ResumableUploads` handler backed by the given store, without a size limit.
+    pub fn new<S>(store: S) -> ResumableUploads
+    where
+        S: UploadStore + 'static,
+    {
+        return ResumableUploads {
+            store: Arc::new(store),
+            max_size: None,
+            on_progress: None,
+        };
+    }
+
+    /// Limits the length of an upload, creating a larger upload gets `413 Payload Too Large`.
+    pub fn max_size(mut self, max_size: u64) -> ResumableUploads {
+        self.max_size = Some(max_size);
+        return self;
+    }
+
+    /// Registers a hook which is called with the state of an upload every time data was appended
+    /// to it.
+    pub fn on_progress<F>(mut self, hook: F) -> ResumableUploads
+    where
+        F: Fn(&UploadInfo) + 'static + Send + Sync,
+    {
+        self.on_progress = Some(Arc::new(Box::new(hook)));
+        return self;
+    }
+
+    /// Returns the store the uploads are kept in.
+    pub fn store(&self) -> &Arc<dyn UploadStore> {
+        return &self.store;
+    }
+
+    /// Handles a tus protocol request.
+    ///
+    /// The upload id is taken from the `id` path parameter, requests without it are treated as
+    /// requests for the upload creation URL.
+    ///
+    /// # Arguments
+    ///
+    /// - `c` - The `Context` of the request.
+    ///
+    /// # Returns
+    ///
+    /// - `Response` - The tus protocol response.
+    pub fn handle(&self, mut c: context::Context) -> response::Response {
+        c.response
+            .headers
+            .insert("Tus-Resumable".to_string(), TUS_VERSION.to_string());
+
+        if c.request.method == utils::HttpMethod::OPTIONS {
+            return self.options(c);
+        }
+        match c.request.header("Tus-Resumable") {
+            Some(version) if version.trim() == TUS_VERSION => {}
+            _ => {
+                c.response
+                    .headers
+                    .insert("Tus-Version".to_string(), TUS_VERSION.to_string());
+                return error_response(c, utils::HttpStatusCode::PreconditionFailed);
+            }
+        }
+
+        let id = c.params.get("id").cloned();
+        match (&c.request.method, id) {
+            (utils::HttpMethod::POST, None) => return self.create(c),
+            (utils::HttpMethod::HEAD, Some(id)) => {
+                return self.head(c, &id);
+            }
+            (utils::HttpMethod::PATCH, Some(id)) => return self.patch(c, &id),
+            _ => return error_response(c, utils::HttpStatusCode::MethodNotAllowed),
+        }
+    }
+
+    fn options(&self, mut c: context::Context) -> response::Response {
+        let headers = &mut c.response.headers;
+        headers.insert("Tus-Version".to_string(), TUS_VERSION.to_string());
+        headers.insert("Tus-Extension".to_string(), "creation".to_string());
+        if let Some(max_size) = self.max_size {
+            headers.insert("Tus-Max-Size".to_string(), max_size.to_string());
+        }
+        return c.send_string(utils::HttpStatusCode::NoContent, "");
+    }
+
+    fn create(&self, mut c: context::Context) -> response::Response {
+        let length = match c.request.header("Upload-Length") {
+            Some(length) if length.trim().bytes().all(|b| b.is_ascii_digit()) => {
+                match length.trim().parse::<u64>() {
+                    Ok(length) => length,
+                    Err(_) => return error_response(c, utils::HttpStatusCode::BadRequest),
+                }
+            }
+            _ => return error_response(c, utils::HttpStatusCode::BadRequest),
+        };
+        if let Some(max_size) = self.max_size {
+            if length > max_size {
+                return error_response(c, utils::HttpStatusCode::PayloadTooLarge);
+            }
+        }
+        let metadata = c.request.header("Upload-Metadata").cloned();
+
+        let upload = match self.store.create(length, metadata) {
+            Ok(upload) => upload,
+            Err(e) => {
+                eprintln!("Failed to create an upload, Error: {}", e);
+                return error_response(c, utils::HttpStatusCode::InternalServerError);
+            }
+        };
+        let location = format!("{}/{}", c.request.path.trim_end_matches('/'), upload.id);
+        c.response.headers.insert("Location".to_string(), location);
+        c.response
+            .headers
+            .insert("Upload-Offset".to_string(), "0".to_string());
+        return c.send_string(utils::HttpStatusCode::Created, "");
+    }
+
+    fn head(&self, mut c: context::Context, id: &str) -> response::Response {
+        let upload = match self.store.info(id) {
+            Ok(Some(upload)) => upload,
+            Ok(None) => return error_response(c, utils::HttpStatusCode::NotFound),
+            Err(e) => {
+                eprintln!("Failed to read upload {}, Error: {}", id, e);
+                return error_response(c, utils::HttpStatusCode::InternalServerError);
+            }
+        };
+        let headers = &mut c.response.headers;
+        headers.insert("Upload-Offset".to_string(), upload.offset.to_string());
+        headers.insert("Upload-Length".to_string(), upload.length.to_string());
+        if let Some(metadata) = upload.metadata {
+            headers.insert("Upload-Metadata".to_string(), metadata);
+        }
+        headers.insert("Cache-Control".to_string(), "no-store".to_string());
+        return c.send_string(utils::HttpStatusCode::OK, "");
+    }
+
+    fn patch(&self, mut c: context::Context, id: &str) -> response::Response {
+        match c.request.header("Content-Type") {
+            Some(content_type) if content_type.trim() == OFFSET_OCTET_STREAM => {}
+            _ => return error_response(c, utils::HttpStatusCode::UnsupportedMediaType),
+        }
+        let offset = match c.request.header("Upload-Offset") {
+            Some(offset) if offset.trim().bytes().all(|b| b.is_ascii_digit()) => {
+                match offset.trim().parse::<u64>() {
+                    Ok(offset) => offset,
+                    Err(_) => return error_response(c, utils::HttpStatusCode::BadRequest),
+                }
+            }
+            _ => return error_response(c, utils::HttpStatusCode::BadRequest),
+        };
+
+        let upload = match self.store.append(id, offset, c.request.body_bytes()) {
+            Ok(upload) => upload,
+            Err(e) => {
+                if let error::UploadError::IO(ref e) = e {
+                    eprintln!("Failed to append to upload {}, Error: {}", id, e);
+                }
+                return error_response(c, e.status_code());
+            }
+        };
+        if let Some(ref hook) = self.on_progress {
+            (hook)(&upload);
+        }
+        c.response
+            .headers
+            .insert("Upload-Offset".to_string(), upload.offset.to_string());
+        return c.send_string(utils::HttpStatusCode::NoContent, "");
+    }
+}
+
+/// Limits applied to the files received by an `UploadReceiver`.
+///
+/// # Fields
+///
+/// - `max_file_size` - The maximum size of a single file in bytes(defaults to 10 MiB).
+/// - `max_files` - The maximum number of files in a single request(defaults to 10).
+/// - `allowed_types` - The media types files may have, like `image/png` or `image/*`, `None`(the
+///   default) allows every type. Files without a `Content-Type` are treated as
+///   `application/octet-stream`.
+///
+/// # Examples
+///
+/// ```rust
+/// use browzer_web::upload::UploadLimits;
+///
+/// let limits = UploadLimits {
+///     max_file_size: 5 * 1024 * 1024,
+///     allowed_types: Some(vec!["image/*".to_string(), "application/pdf".to_string()]),
+///     ..UploadLimits::default()
+/// };
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UploadLimits {
+    pub max_file_size: u64,
+    pub max_files: usize,
+    pub allowed_types: Option<Vec<String>>,
+}
+
+// default implementation for UploadLimits struct
+impl Default for UploadLimits {
+    fn default() -> Self {
+        return UploadLimits {
+            max_file_size: 10 * 1024 * 1024,
+            max_files: 10,
+            allowed_types: None,
+        };
+    }
+}
+
+impl UploadLimits {
+    // whether a file with the given `Content-Type` may be stored
+    fn allows_type(&self, content_type: Option<&str>) -> bool {
+        let allowed_types = match self.allowed_types {
+            Some(ref allowed_types) => allowed_types,
+            None => return true,
+        };
+        let media_type = content_type
+            .and_then(|content_type| content_type.split(';').next())
+            .map(|media_type| media_type.trim().to_ascii_lowercase())
+            .unwrap_or_else(|| "application/octet-stream".to_string());
+        return allowed_types.iter().any(|allowed| {
+            let allowed = allowed.trim().to_ascii_lowercase();
+            match allowed.strip_suffix("/*") {
+                Some(top_level) => media_type.split('/').next() == Some(top_level),
+                None => allowed == media_type,
+            }
+        });
+    }
+}
+
+/// A file stored by an `UploadReceiver`, as described in it's JSON response.
+///
+/// # Fields
+///
+/// - `field` - The name of the form field the file was uploaded with.
+/// - `filename` - The name the file was stored under, inside the upload directory.
+/// - `original_filename` - The file name sent by the client.
+/// - `content_type` - The `Content-Type` of the file sent by the client, if any.
+/// - `size` - The size of the file in bytes.
+/// - `digests` - The base64 encoded digests of the file by the names of their algorithms, empty
+///   unless `WebServer::content_digests` is enabled.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct StoredFile {
+    pub field: String,
+    pub filename: String,
+    pub original_filename: String,
+    pub content_type: Option<String>,
+    pub size: u64,
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub digests: BTreeMap<String, String>,
+}
+
+/// Turns a client supplied file name into one which is safe to store on the local file system.
+///
+/// Directory components are removed, every character but ASCII letters, digits, `.`, `-` and `_`
+/// is replaced with `_`, leading dots are removed(so the file can't be hidden) and the name is
+/// limited to 200 characters.
+///
+/// # Examples
+///
+/// ```rust
+/// use browzer_web::upload::sanitize_filename;
+///
+/// assert_eq!(sanitize_filename("../../etc/passwd"), "passwd");
+/// assert_eq!(sanitize_filename("C:\\Users\\axew\\my photo.png"), "my_photo.png");
+/// assert_eq!(sanitize_filename(".htaccess"), "htaccess");
+/// assert_eq!(sanitize_filename(""), "upload");
+/// ```
+pub fn sanitize_filename(filename: &str) -> String {
+    let basename = filename.rsplit(['/', '\\']).next().unwrap_or("");
+    let sanitized: String = basename
+        .chars()
+        .map(
+            |c| match c.is_ascii_alphanumeric() || c == '.' || c == '-' || c == '_' {
+                true => c,
+                false => '_',
+            },
+        )
+        .collect::<String>()
+        .trim_start_matches('.')
+        .chars()
+        .take(200)
+        .collect();
+    match sanitized.is_empty() {
+        true => return "upload".to_string(),
+        false => return sanitized,
+    }
+}
+
+// the error a sink fails with when a file violates the `UploadLimits`, carrying the status code the
+// request is answered with
+#[derive(Debug)]
+struct Rejected(utils::HttpStatusCode);
+
+impl fmt::Display for Rejected {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Upload rejected: {}", self.0.code().0)
+    }
+}
+
+impl std::error::Error for Rejected {}
+
+// a file sink which enforces the maximum file size, and computes the digests of the file and
+// checks them against the ones sent with the part once it is finished
+struct LimitedFile {
+    file: fs::File,
+    written: u64,
+    max_size: u64,
+    hasher: Option<digest::MultiHasher>,
+    expected: digest::Digests,
+    digests: Arc<Mutex<Vec<StoredFile>>>,
+    index: usize,
+}
+
+impl Write for LimitedFile {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if self.written + buf.len() as u64 > self.max_size {
+            return Err(io::Error::other(Rejected(
+                utils::HttpStatusCode::PayloadTooLarge,
+            )));
+        }
+        let written = self.file.write(buf)?;
+        self.written += written as u64;
+        if let Some(ref mut hasher) = self.hasher {
+            hasher.write_all(&buf[..written])?;
+        }
+        return Ok(written);
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        return self.file.flush();
+    }
+}
+
+impl multipart::PartSink for LimitedFile {
+    fn finish(&mut self) -> io::Result<()> {
+        self.file.flush()?;
+        if let Some(hasher) = self.hasher.take() {
+            let digests = hasher.finish();
+            let mut stored = match self.digests.lock() {
+                Ok(stored) => stored,
+                Err(poisoned) => poisoned.into_inner(),
+            };
+            if let Some(file) = stored.get_mut(self.index) {
+                file.digests = digests.to_base64().into_iter().collect();
+            }
+            if digests.matches(&self.expected) == Some(false) {
+                return Err(io::Error::other(Rejected(
+                    utils::HttpStatusCode::UnprocessableEntity,
+                )));
+            }
+        }
+        return Ok(());
+    }
+}
+
+/// A handler which stores the files of `multipart/form-data` requests in a directory.
+///
+/// It is registered under a path using `WebServer::accept_uploads`. Every file part of a request
+/// is streamed to a file in the upload directory, named after the sanitized file name sent by the
+/// client(see `sanitize_filename`). If a file with that name exists already, a counter is added to
+/// the name(like `photo-1.png`), so existing files are never overwritten.
+///
+/// A successful request is answered with `201 Created` and a JSON body describing the stored files
+/// and the plain form fields:
+///
+/// ```json
+/// {"files":[{"field":"avatar","filename":"photo-1.png","original_filename":"photo.png","content_type":"image/png","size":1024}],"fields":{"title":"Me"}}
+/// ```
+///
+/// A request violating the `UploadLimits` gets `413 Payload Too Large`(for too large or too many
+/// files) or `415 Unsupported Media Type`(for disallowed file types), and none of it's files are
+/// kept.
+///
+/// With `WebServer::content_digests` enabled, the digests of every stored file are included in
+/// it's description(like `"digests":{"sha-256":"..."}`), and the digests a client sends in the
+/// `Repr-Digest`, `Content-Digest`, `Digest` or `Content-MD5` headers of the request(covering the
+/// whole body) or of a file part(covering the file) are verified. A request with a digest which
+/// doesn't match gets `422 Unprocessable Entity`, and none of it's files are kept. Digests of
+/// algorithms which aren't part of the `DigestSet` are ignored.
+///
+/// # Examples
+///
+/// ```rust,no_run
+/// use browzer_web::{upload::{UploadLimits, UploadReceiver}, WebServer};
+///
+/// let receiver = UploadReceiver::new("uploads", UploadLimits::default()).unwrap();
+///
+/// let mut server = WebServer::new("127.0.0.1:8080".to_string(), 4);
+/// server.post("/upload", move |c| receiver.handle(c));
+/// ```
+// ----- UploadReceiver struct
+#[derive(Debug, Clone)]
+pub struct UploadReceiver {
+    dir: PathBuf,
+    limits: UploadLimits,
+}
+
+// the JSON body of a successful upload
+#[derive(Serialize)]
+struct UploadResult<'a> {
+    files: Vec<StoredFile>,
+    fields: &'a std::collections::HashMap<String, String>,
+}
+
+impl UploadReceiver {
+    /// Creates a new `UploadReceiver` storing files in the given directory, which is created if it
+    /// doesn't exist yet.
+    ///
+    /// # Errors
+    ///
+    /// Returns an I/O error if the directory couldn't be created.
+    pub fn new<P: AsRef<Path>>(dir: P, limits: UploadLimits) -> io::Result<UploadReceiver> {
+        fs::create_dir_all(dir.as_ref())?;
+        return Ok(UploadReceiver {
+            dir: dir.as_ref().to_path_buf(),
+            limits,
+        });
+    }
+
+    /// Handles a `multipart/form-data` upload request.
+    ///
+    /// # Arguments
+    ///
+    /// - `c` - The `Context` of the request.
+    ///
+    /// # Returns
+    ///
+    /// - `Response` - `201 Created` with a JSON description of the stored files, or an error
+    ///   response.
+    pub fn handle(&self, mut c: context::Context) -> response::Response {
+        let parser = match multipart::Multipart::from_request(&c.request) {
+            Ok(parser) => parser,
+            Err(e) => return error_response(c, e.status_code()),
+        };
+        let digests = c.digests.clone();
+        if let Some(ref digests) = digests {
+            let expected = digest::Digests::from_headers(|name| {
+                return c.request.header(name).map(String::as_str);
+            });
+            if !expected.is_empty()
+                && digests.digest(c.request.body_bytes()).matches(&expected) == Some(false)
+            {
+                return error_response(c, utils::HttpStatusCode::UnprocessableEntity);
+            }
+        }
+
+        // every stored file in the order they were received, the sizes are filled in once the
+        // whole body was parsed
+        let stored: Arc<Mutex<Vec<StoredFile>>> = Arc::new(Mutex::new(vec![]));
+        let sink_stored = Arc::clone(&stored);
+        let dir = self.dir.clone();
+        let limits = self.limits.clone();
+        let parser = parser.file_sink(move |part| {
+            let original_filename = part.filename.clone().unwrap_or_default();
+            // browsers send an empty file part for file inputs without a selected file
+            if original_filename.is_empty() {
+                return Ok(Box::new(io::sink()));
+            }
+            if !limits.allows_type(part.content_type.as_deref()) {
+                return Err(io::Error::other(Rejected(
+                    utils::HttpStatusCode::UnsupportedMediaType,
+                )));
+            }
+            let mut stored = match sink_stored.lock() {
+                Ok(stored) => stored,
+                Err(poisoned) => poisoned.into_inner(),
+            };
+            if stored.len() >= limits.max_files {
+                return Err(io::Error::other(Rejected(
+                    utils::HttpStatusCode::PayloadTooLarge,
+                )));
+            }
+            let (filename, file) = create_unique(&dir, &sanitize_filename(&original_filename))?;
+            stored.push(StoredFile {
+                field: part.name.to_string(),
+                filename,
+                original_filename,
+                content_type: part.content_type.clone(),
+                size: 0,
+                digests: BTreeMap::new(),
+            });
+            let expected = digest::Digests::from_headers(|name| {
+                return part
+                    .headers
+                    .iter()
+                    .find(|(key, _)| key.eq_ignore_ascii_case(name))
+                    .map(|(_, value)| value.as_str());
+            });
+            return Ok(Box::new(LimitedFile {
+                file,
+                written: 0,
+                max_size: limits.max_file_size,
+                hasher: digests.as_ref().map(|digests| digests.hasher()),
+                expected,
+                digests: Arc::clone(&sink_stored),
+                index: stored.len() - 1,
+            }));
+        });
+
+        let result = parser.parse(c.request.body_bytes());
+        let stored = match stored.lock() {
+            Ok(stored) => stored.clone(),
+            Err(poisoned) => poisoned.into_inner().clone(),
+        };
+        let form = match result {
+            Ok(form) => form,
+            Err(e) => {
+                // don't keep the files of a rejected request
+                for file in &stored {
+                    let _ = fs::remove_file(self.dir.join(&file.filename));
+                }
+                let status_code = match e {
+                    error::MultipartError::IO(ref e) => {
+                        match e.get_ref().and_then(|e| e.downcast_ref::<Rejected>()) {
+                            Some(Rejected(status_code)) => status_code.clone(),
+                            None => {
+                                eprintln!("Failed to store an upload, Error: {}", e);
+                                utils::HttpStatusCode::InternalServerError
+                            }
+                        }
+                    }
+                    _ => e.status_code(),
+                };
+                return error_response(c, status_code);
+            }
+        };
+
+        let sizes = form
+            .files
+            .iter()
+            .filter(|file| !file.headers.filename.as_deref().unwrap_or("").is_empty())
+            .map(|file| file.size);
+        let mut files = stored;
+        for (file, size) in files.iter_mut().zip(sizes) {
+            file.size = size;
+        }
+        return c.send_json(
+            utils::HttpStatusCode::Created,
+            &UploadResult {
+                files,
+                fields: &form.fields,
+            },
+        );
+    }
+}
+
+// creates a new file named `filename` in `dir`, adding a counter to the name(before the extension)
+// until it doesn't collide with an existing file, returning the name the file was created with
+fn create_unique(dir: &Path, filename: &str) -> io::Result<(String, fs::File)> {
+    let (stem, extension) = match filename.rsplit_once('.') {
+        Some((stem, extension)) if !stem.is_empty() => (stem, Some(extension)),
+        _ => (filename, None),
+    };
+    let mut counter = 0;
+    loop {
+        let candidate = match (counter, extension) {
+            (0, _) => filename.to_string(),
+            (_, Some(extension)) => format!("{}-{}.{}", stem, counter, extension),
+            (_, None) => format!("{}-{}", stem, counter),
+        };
+        match fs::OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(dir.join(&candidate))
+        {
+            Ok(file) => return Ok((candidate, file)),
+            Err(e) if e.kind() == io::ErrorKind::AlreadyExists && counter < 10_000 => {
+                counter += 1;
+            }
+            Err(e) => return Err(e),
+        }
+    }
+}
+
+// answers a request with the plain-text reason phrase of the status code
+fn error_response(
+    mut c: context::Context,
+    status_code: utils::HttpStatusCode,
+) -> response::Response {
+    let body = status_code.code().0.to_string();
+    return c.send_string(status_code, &body);
+}
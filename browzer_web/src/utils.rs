@@ -1,8 +1,13 @@
 //! This module contains various utilities used by the `browzer_web` like `HttpMethod` etc
 
+pub mod mime;
+pub mod slug;
 pub mod thread_pool;
 
-use std::time;
+use std::{
+    path::{Component, Path, PathBuf},
+    time,
+};
 
 // internal crate imports
 use crate::error;
@@ -18,19 +23,20 @@ use crate::error;
 ///
 /// # Returns
 /// - `Result<String, WebRouterError>` - A result containing a `String` representing the formatted
-/// path if it was successfully formatted or a `WebRouterError` if there is an error in formatting
-/// the path.
+///   path if it was successfully formatted or a `WebRouterError` if there is an error in formatting
+///   the path.
 ///
 /// # Examples
 ///
 /// ```rust
-/// assert_eq!(format_path_by_slashes("/menu/items/".to_string()), Ok("/menu/items".to_string()));
-/// assert_eq!(format_path_by_slashes("/users/get_user".to_string()), Ok("/users/get_user".to_string()));
-/// assert_eq!(format_path_by_slashes("/users/axew/?pass=\"some_pass\"".to_string()), Ok("/users/axew?pass=\"some_pass\"".to_string()));
-/// assert_eq!(format_path_by_slashes("/".to_string()), Ok("/".to_string()));
+/// # use browzer_web::utils::format_path_by_slashes;
+/// assert_eq!(format_path_by_slashes("/menu/items/".to_string()).unwrap(), "/menu/items".to_string());
+/// assert_eq!(format_path_by_slashes("/users/get_user".to_string()).unwrap(), "/users/get_user".to_string());
+/// assert_eq!(format_path_by_slashes("/users/axew/?pass=\"some_pass\"".to_string()).unwrap(), "/users/axew?pass=\"some_pass\"".to_string());
+/// assert_eq!(format_path_by_slashes("/".to_string()).unwrap(), "".to_string());
 /// ```
 pub fn format_path_by_slashes(mut path: String) -> Result<String, error::WebRouterError> {
-    if path.trim().len() == 0 && path.trim() == "" {
+    if path.trim().is_empty() {
         path = "/".to_string();
     }
     match path.chars().nth(path.len() - 1) {
@@ -49,13 +55,480 @@ pub fn format_path_by_slashes(mut path: String) -> Result<String, error::WebRout
     return Ok(path);
 }
 
+/// Parses a human-friendly byte size, like `10MB` or `512 KiB`
+///
+/// The unit is case-insensitive and may be separated from the number by whitespace. Decimal units
+/// (`KB`, `MB`, `GB`, `TB`, or just `K`, `M`, `G`, `T`) are powers of 1000, binary units(`KiB`,
+/// `MiB`, `GiB`, `TiB`) are powers of 1024, and a number without a unit(or with `B`) is a number
+/// of bytes. Fractional numbers are allowed and rounded down to whole bytes.
+///
+/// # Arguments
+/// - `value` - A string slice holding the size
+///
+/// # Returns
+/// - `Result<u64, ConfigValueError>` - The size in bytes, or an `InvalidSize` error if the value
+///   isn't a valid size or doesn't fit into a `u64`
+///
+/// # Examples
+///
+/// ```rust
+/// # use browzer_web::utils::parse_size;
+/// assert_eq!(parse_size("512").unwrap(), 512);
+/// assert_eq!(parse_size("10MB").unwrap(), 10_000_000);
+/// assert_eq!(parse_size("1 KiB").unwrap(), 1024);
+/// assert_eq!(parse_size("1.5gib").unwrap(), 1_610_612_736);
+/// assert!(parse_size("ten MB").is_err());
+/// ```
+pub fn parse_size(value: &str) -> Result<u64, error::ConfigValueError> {
+    let invalid = || error::ConfigValueError::InvalidSize(value.to_string());
+    let (number, unit) = split_number(value.trim());
+    let multiplier: u64 = match unit.trim().to_ascii_lowercase().as_str() {
+        "" | "b" => 1,
+        "k" | "kb" => 1000,
+        "m" | "mb" => 1000_u64.pow(2),
+        "g" | "gb" => 1000_u64.pow(3),
+        "t" | "tb" => 1000_u64.pow(4),
+        "kib" => 1 << 10,
+        "mib" => 1 << 20,
+        "gib" => 1 << 30,
+        "tib" => 1 << 40,
+        _ => return Err(invalid()),
+    };
+    match number.split_once('.') {
+        None => {
+            let number = number.parse::<u64>().map_err(|_| invalid())?;
+            return number.checked_mul(multiplier).ok_or_else(invalid);
+        }
+        Some(_) => {
+            let number = number.parse::<f64>().map_err(|_| invalid())?;
+            let bytes = number * multiplier as f64;
+            if !bytes.is_finite() || bytes >= u64::MAX as f64 {
+                return Err(invalid());
+            }
+            return Ok(bytes as u64);
+        }
+    }
+}
+
+/// Parses a human-friendly duration, like `30s`, `500ms` or `1h30m`
+///
+/// The supported units are `ms`, `s`, `m`, `h` and `d`, several number and unit pairs can be
+/// combined and a number without a unit is a number of seconds.
+///
+/// # Arguments
+/// - `value` - A string slice holding the duration
+///
+/// # Returns
+/// - `Result<Duration, ConfigValueError>` - The duration, or an `InvalidDuration` error if the
+///   value isn't a valid duration
+///
+/// # Examples
+///
+/// ```rust
+/// # use browzer_web::utils::parse_duration;
+/// # use std::time::Duration;
+/// assert_eq!(parse_duration("30s").unwrap(), Duration::from_secs(30));
+/// assert_eq!(parse_duration("500ms").unwrap(), Duration::from_millis(500));
+/// assert_eq!(parse_duration("1h30m").unwrap(), Duration::from_secs(90 * 60));
+/// assert_eq!(parse_duration("15").unwrap(), Duration::from_secs(15));
+/// assert!(parse_duration("soon").is_err());
+/// ```
+pub fn parse_duration(value: &str) -> Result<time::Duration, error::ConfigValueError> {
+    let invalid = || error::ConfigValueError::InvalidDuration(value.to_string());
+    let mut rest = value.trim();
+    if rest.is_empty() {
+        return Err(invalid());
+    }
+    if let Ok(seconds) = rest.parse::<u64>() {
+        return Ok(time::Duration::from_secs(seconds));
+    }
+
+    let mut duration = time::Duration::ZERO;
+    while !rest.is_empty() {
+        let (number, remainder) = split_number(rest);
+        let unit_len = remainder
+            .find(|c: char| c.is_ascii_digit() || c == '.')
+            .unwrap_or(remainder.len());
+        let (unit, remainder) = remainder.split_at(unit_len);
+        let number = number.parse::<f64>().map_err(|_| invalid())?;
+        let seconds = match unit.trim() {
+            "ms" => number / 1000.0,
+            "s" => number,
+            "m" => number * 60.0,
+            "h" => number * 60.0 * 60.0,
+            "d" => number * 24.0 * 60.0 * 60.0,
+            _ => return Err(invalid()),
+        };
+        let part = time::Duration::try_from_secs_f64(seconds).map_err(|_| invalid())?;
+        duration = duration.checked_add(part).ok_or_else(invalid)?;
+        rest = remainder.trim_start();
+    }
+    return Ok(duration);
+}
+
+/// Decodes the percent-encoded(`%XX`) bytes of a URL path segment
+///
+/// A `+` is kept as is, it only stands for a space in query strings and form data, which are
+/// decoded with `query_decode` instead.
+///
+/// # Arguments
+/// - `value` - A string slice holding the encoded value
+///
+/// # Returns
+/// - `Option<String>` - The decoded value, or `None` if it contains an invalid escape sequence or
+///   doesn't decode to valid UTF-8
+///
+/// # Examples
+///
+/// ```rust
+/// # use browzer_web::utils::percent_decode;
+/// assert_eq!(percent_decode("hello%20world").unwrap(), "hello world");
+/// assert_eq!(percent_decode("..%2F..%2Fetc").unwrap(), "../../etc");
+/// assert_eq!(percent_decode("c++%20notes.txt").unwrap(), "c++ notes.txt");
+/// assert!(percent_decode("100%").is_none());
+/// assert!(percent_decode("%+F").is_none());
+/// ```
+pub fn percent_decode(value: &str) -> Option<String> {
+    let bytes = value.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut index = 0;
+    while index < bytes.len() {
+        match bytes[index] {
+            b'%' => {
+                let hex = bytes.get(index + 1..index + 3)?;
+                // `from_str_radix` would take a leading sign(`%+F`) as well
+                if !hex.iter().all(u8::is_ascii_hexdigit) {
+                    return None;
+                }
+                let hex = std::str::from_utf8(hex).ok()?;
+                decoded.push(u8::from_str_radix(hex, 16).ok()?);
+                index += 3;
+            }
+            byte => {
+                decoded.push(byte);
+                index += 1;
+            }
+        }
+    }
+    return String::from_utf8(decoded).ok();
+}
+
+/// Percent-encodes everything but the unreserved characters of RFC 3986, so that a value can be
+/// used as a single segment of a path or as a query string value
+///
+/// # Examples
+///
+/// ```rust
+/// # use browzer_web::utils::percent_encode;
+/// assert_eq!(percent_encode("a b/c.txt"), "a%20b%2Fc.txt");
+/// ```
+pub fn percent_encode(value: &str) -> String {
+    let mut encoded = String::with_capacity(value.len());
+    for byte in value.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => {
+                encoded.push(byte as char)
+            }
+            _ => encoded.push_str(&format!("%{:02X}", byte)),
+        }
+    }
+    return encoded;
+}
+
+/// Decodes a query string or form key or value, like `percent_decode` but with `+` standing for a
+/// space as in `application/x-www-form-urlencoded` data
+///
+/// # Arguments
+/// - `value` - A string slice holding the encoded key or value
+///
+/// # Returns
+/// - `Option<String>` - The decoded value, or `None` if it contains an invalid escape sequence or
+///   doesn't decode to valid UTF-8
+///
+/// # Examples
+///
+/// ```rust
+/// # use browzer_web::utils::query_decode;
+/// assert_eq!(query_decode("John+Doe").unwrap(), "John Doe");
+/// assert_eq!(query_decode("1%2B1%3D2").unwrap(), "1+1=2");
+/// ```
+pub fn query_decode(value: &str) -> Option<String> {
+    // an encoded plus sign(`%2B`) is only decoded after the plus signs were replaced
+    return percent_decode(&value.replace('+', " "));
+}
+
+/// Resolves a requested path to a file inside a served directory, making sure it can't escape
+/// the directory
+///
+/// The requested path is percent-decoded first, then rejected if it contains a parent(`..`) or
+/// absolute component. The resolved path is canonicalized(following symbolic links) and has to
+/// stay inside the canonicalized directory, so a symbolic link pointing out of the directory is
+/// rejected as well.
+///
+/// # Arguments
+/// - `root` - The served directory
+/// - `requested` - The requested path relative to `root`, as it appears in the request path
+///
+/// # Returns
+/// - `Result<PathBuf, StaticFileError>` - The canonical path of the requested file, a `Forbidden`
+///   error if the path would escape `root` or a `NotFound` error if the file doesn't exist, isn't
+///   a regular file or the path can't be decoded
+///
+/// # Examples
+///
+/// ```rust,no_run
+/// # use browzer_web::{error::StaticFileError, utils::resolve_static_path};
+/// # use std::path::Path;
+/// let path = resolve_static_path(Path::new("static"), "index.html").unwrap();
+///
+/// assert!(matches!(
+///     resolve_static_path(Path::new("static"), "..%2F..%2Fetc%2Fpasswd"),
+///     Err(StaticFileError::Forbidden)
+/// ));
+/// ```
+pub fn resolve_static_path(
+    root: &Path,
+    requested: &str,
+) -> Result<PathBuf, error::StaticFileError> {
+    let path = resolve_static_entry(root, requested)?;
+    if !path.is_file() {
+        return Err(error::StaticFileError::NotFound);
+    }
+    return Ok(path);
+}
+
+// resolves a requested path like `resolve_static_path`, but to a directory as well as to a file,
+// an empty path resolves to the served directory itself
+pub(crate) fn resolve_static_entry(
+    root: &Path,
+    requested: &str,
+) -> Result<PathBuf, error::StaticFileError> {
+    let requested = match percent_decode(requested) {
+        Some(requested) => requested,
+        None => return Err(error::StaticFileError::NotFound),
+    };
+    if requested.contains('\0') {
+        return Err(error::StaticFileError::NotFound);
+    }
+    // backslashes are separators on windows, so they are treated as such everywhere
+    let requested = requested.replace('\\', "/");
+    let requested = Path::new(&requested);
+    for component in requested.components() {
+        match component {
+            Component::Normal(_) | Component::CurDir => {}
+            Component::ParentDir | Component::RootDir | Component::Prefix(_) => {
+                return Err(error::StaticFileError::Forbidden);
+            }
+        }
+    }
+
+    let root = match root.canonicalize() {
+        Ok(root) => root,
+        Err(_) => return Err(error::StaticFileError::NotFound),
+    };
+    let path = match root.join(requested).canonicalize() {
+        Ok(path) => path,
+        Err(_) => return Err(error::StaticFileError::NotFound),
+    };
+    if !path.starts_with(&root) {
+        return Err(error::StaticFileError::Forbidden);
+    }
+    return Ok(path);
+}
+
+// splits a value into it's leading number and the rest
+fn split_number(value: &str) -> (&str, &str) {
+    let number_len = value
+        .find(|c: char| !c.is_ascii_digit() && c != '.')
+        .unwrap_or(value.len());
+    return value.split_at(number_len);
+}
+
+/// Escapes the characters with a special meaning in HTML, so that text can be safely embedded in
+/// an HTML page.
+///
+/// # Examples
+///
+/// ```rust
+/// use browzer_web::utils::escape_html;
+///
+/// assert_eq!(escape_html("<b>\"Tom\" & 'Jerry'</b>"), "&lt;b&gt;&quot;Tom&quot; &amp; &#39;Jerry&#39;&lt;/b&gt;");
+/// ```
+pub fn escape_html(input: &str) -> String {
+    let mut escaped = String::with_capacity(input.len());
+    for c in input.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            c => escaped.push(c),
+        }
+    }
+    return escaped;
+}
+
+/// Formats a point in time as an HTTP date(`IMF-fixdate`), as used in headers like
+/// `Last-Modified` and `Expires`. Sub-second precision is dropped.
+///
+/// # Examples
+///
+/// ```rust
+/// # use browzer_web::utils::http_date;
+/// use std::time::{Duration, UNIX_EPOCH};
+///
+/// let time = UNIX_EPOCH + Duration::from_secs(784111777);
+/// assert_eq!(http_date(time), "Sun, 06 Nov 1994 08:49:37 GMT");
+/// ```
+pub fn http_date(time: time::SystemTime) -> String {
+    let datetime = chrono::DateTime::<chrono::Utc>::from(time);
+    return datetime.format("%a, %d %b %Y %H:%M:%S GMT").to_string();
+}
+
+/// Parses an HTTP date(`IMF-fixdate`), like the value of an `If-Modified-Since` header.
+///
+/// # Returns
+///
+/// - `Option<SystemTime>` - The point in time, or `None` if the value isn't a valid HTTP date. The
+///   obsolete RFC 850 and asctime formats aren't supported.
+///
+/// # Examples
+///
+/// ```rust
+/// # use browzer_web::utils::{http_date, parse_http_date};
+/// let time = parse_http_date("Sun, 06 Nov 1994 08:49:37 GMT").unwrap();
+/// assert_eq!(http_date(time), "Sun, 06 Nov 1994 08:49:37 GMT");
+/// assert_eq!(parse_http_date("yesterday"), None);
+/// ```
+pub fn parse_http_date(value: &str) -> Option<time::SystemTime> {
+    let datetime =
+        chrono::NaiveDateTime::parse_from_str(value.trim(), "%a, %d %b %Y %H:%M:%S GMT").ok()?;
+    return Some(datetime.and_utc().into());
+}
+
+/// Compares two byte strings in a time independent of where they differ, for comparing secrets
+/// like tokens without leaking how much of them was guessed right.
+///
+/// # Examples
+///
+/// ```rust
+/// # use browzer_web::utils::constant_time_eq;
+/// assert!(constant_time_eq(b"secret", b"secret"));
+/// assert!(!constant_time_eq(b"secret", b"secreT"));
+/// assert!(!constant_time_eq(b"secret", b"secret!"));
+/// ```
+pub fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    if a.len() != b.len() {
+        return false;
+    }
+    return a
+        .iter()
+        .zip(b.iter())
+        .fold(0u8, |diff, (x, y)| diff | (x ^ y))
+        == 0;
+}
+
+/// Returns whether a request path matches any of a list of path patterns, like the paths skipped
+/// by a `RequestLogger` or a `Recorder`. The query string of the path is ignored, and a pattern
+/// ending in `*` matches every path starting with the part before it.
+///
+/// # Examples
+///
+/// ```rust
+/// # use browzer_web::utils::matches_path_pattern;
+/// let patterns = ["/health".to_string(), "/static/*".to_string()];
+/// assert!(matches_path_pattern(&patterns, "/health?verbose=1"));
+/// assert!(matches_path_pattern(&patterns, "/static/app.css"));
+/// assert!(!matches_path_pattern(&patterns, "/healthz"));
+/// ```
+pub fn matches_path_pattern(patterns: &[String], path: &str) -> bool {
+    let path = path.split('?').next().unwrap_or_default();
+    // the router strips the slash of the root path
+    let path = match path.is_empty() {
+        true => "/",
+        false => path,
+    };
+    return patterns
+        .iter()
+        .any(|pattern| match pattern.strip_suffix('*') {
+            Some(prefix) => path.starts_with(prefix),
+            None => path == pattern,
+        });
+}
+
+// the alphabet of base64(RFC 4648)
+const BASE64_ALPHABET: &[u8; 64] =
+    b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+// the values base64 characters decode to, `0xff` for bytes which aren't part of the alphabet
+const BASE64_VALUES: [u8; 256] = {
+    let mut values = [0xff; 256];
+    let mut i = 0;
+    while i < BASE64_ALPHABET.len() {
+        values[BASE64_ALPHABET[i] as usize] = i as u8;
+        i += 1;
+    }
+    values
+};
+
+// encodes bytes as base64 with padding
+pub(crate) fn base64_encode(bytes: &[u8]) -> String {
+    let mut encoded = String::with_capacity(bytes.len().div_ceil(3) * 4);
+    for chunk in bytes.chunks(3) {
+        let triple = (chunk[0] as u32) << 16
+            | (*chunk.get(1).unwrap_or(&0) as u32) << 8
+            | *chunk.get(2).unwrap_or(&0) as u32;
+        for i in 0..4 {
+            match i <= chunk.len() {
+                true => {
+                    encoded.push(BASE64_ALPHABET[(triple >> (18 - 6 * i) & 63) as usize] as char)
+                }
+                false => encoded.push('='),
+            }
+        }
+    }
+    return encoded;
+}
+
+// decodes padded base64, `None` if it isn't valid base64
+pub(crate) fn base64_decode(encoded: &str) -> Option<Vec<u8>> {
+    let encoded = encoded.as_bytes();
+    if !encoded.len().is_multiple_of(4) {
+        return None;
+    }
+    let mut decoded = Vec::with_capacity(encoded.len() / 4 * 3);
+    for (i, chunk) in encoded.chunks(4).enumerate() {
+        let last = i == encoded.len() / 4 - 1;
+        let padding = chunk.iter().rev().take_while(|byte| **byte == b'=').count();
+        if padding > 2 || (padding > 0 && !last) {
+            return None;
+        }
+        let mut triple = 0u32;
+        for byte in &chunk[..4 - padding] {
+            let value = BASE64_VALUES[*byte as usize];
+            if value == 0xff {
+                return None;
+            }
+            triple = triple << 6 | value as u32;
+        }
+        triple <<= 6 * padding as u32;
+        decoded.extend_from_slice(&triple.to_be_bytes()[1..4 - padding]);
+    }
+    return Some(decoded);
+}
+
 /// Enumeration of supported HTTP methods.
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum HttpMethod {
     GET,
     POST,
+    PUT,
     PATCH,
     DELETE,
+    HEAD,
+    OPTIONS,
 }
 impl HttpMethod {
     /// Converts an `HttpMethod` enum value to its corresponding method string.
@@ -73,36 +546,65 @@ impl HttpMethod {
     /// assert_eq!(method.to_string(), "GET".to_string());
     /// ```
     pub fn to_string(&self) -> String {
+        return self.as_str().to_string();
+    }
+
+    /// Returns the method string, like `to_string` without allocating.
+    pub fn as_str(&self) -> &'static str {
         match self {
-            HttpMethod::GET => "GET",
-            HttpMethod::POST => "POST",
-            HttpMethod::PATCH => "PATCH",
-            HttpMethod::DELETE => "DELETE",
+            HttpMethod::GET => return "GET",
+            HttpMethod::POST => return "POST",
+            HttpMethod::PUT => return "PUT",
+            HttpMethod::PATCH => return "PATCH",
+            HttpMethod::DELETE => return "DELETE",
+            HttpMethod::HEAD => return "HEAD",
+            HttpMethod::OPTIONS => return "OPTIONS",
         }
-        .to_string()
     }
 }
 
 /// Enumeration of supported HTTP status codes.
-#[derive(Debug, Clone)]
+///
+/// Status codes without a variant of their own, like ones received from an upstream server, are
+/// carried by `HttpStatusCode::Other` with their number and reason phrase. With the `serde`
+/// feature, status codes are serialized as their number, like `404`.
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub enum HttpStatusCode {
     OK,
     Created,
     Accepted,
     NoContent,
+    PartialContent,
     MovedPermanently,
     Found,
     SeeOther,
     NotModified,
+    TemporaryRedirect,
+    PermanentRedirect,
     BadRequest,
     Unauthorized,
     Forbidden,
     NotFound,
     MethodNotAllowed,
+    RequestTimeout,
+    Conflict,
+    Gone,
+    PreconditionFailed,
+    PayloadTooLarge,
+    UnsupportedMediaType,
+    RangeNotSatisfiable,
+    MisdirectedRequest,
+    UnprocessableEntity,
+    TooManyRequests,
+    RequestHeaderFieldsTooLarge,
     InternalServerError,
     NotImplemented,
     BadGateway,
     ServiceUnavailable,
+    GatewayTimeout,
+    HttpVersionNotSupported,
+    /// Any other status code, carrying it's number and reason phrase.
+    Other(u16, String),
 }
 impl HttpStatusCode {
     /// Converts an `HttpStatusCode` enum value to a tuple containing its corresponding reason phrase and status code.
@@ -124,20 +626,145 @@ impl HttpStatusCode {
             HttpStatusCode::OK => ("OK", 200),
             HttpStatusCode::Created => ("Created", 201),
             HttpStatusCode::Accepted => ("Accepted", 202),
-            HttpStatusCode::NoContent => ("NoContent", 204),
+            HttpStatusCode::NoContent => ("No Content", 204),
+            HttpStatusCode::PartialContent => ("Partial Content", 206),
             HttpStatusCode::MovedPermanently => ("Moved Permanently", 301),
             HttpStatusCode::Found => ("Found", 302),
             HttpStatusCode::SeeOther => ("See Other", 303),
             HttpStatusCode::NotModified => ("Not Modified", 304),
+            HttpStatusCode::TemporaryRedirect => ("Temporary Redirect", 307),
+            HttpStatusCode::PermanentRedirect => ("Permanent Redirect", 308),
             HttpStatusCode::BadRequest => ("Bad Request", 400),
             HttpStatusCode::Unauthorized => ("Unauthorized", 401),
             HttpStatusCode::Forbidden => ("Forbidden", 403),
             HttpStatusCode::NotFound => ("Not Found", 404),
             HttpStatusCode::MethodNotAllowed => ("Method Not Allowed", 405),
+            HttpStatusCode::RequestTimeout => ("Request Timeout", 408),
+            HttpStatusCode::Conflict => ("Conflict", 409),
+            HttpStatusCode::Gone => ("Gone", 410),
+            HttpStatusCode::PreconditionFailed => ("Precondition Failed", 412),
+            HttpStatusCode::PayloadTooLarge => ("Payload Too Large", 413),
+            HttpStatusCode::UnsupportedMediaType => ("Unsupported Media Type", 415),
+            HttpStatusCode::RangeNotSatisfiable => ("Range Not Satisfiable", 416),
+            HttpStatusCode::MisdirectedRequest => ("Misdirected Request", 421),
+            HttpStatusCode::UnprocessableEntity => ("Unprocessable Entity", 422),
+            HttpStatusCode::TooManyRequests => ("Too Many Requests", 429),
+            HttpStatusCode::RequestHeaderFieldsTooLarge => ("Request Header Fields Too Large", 431),
             HttpStatusCode::InternalServerError => ("Internal Server Error", 500),
             HttpStatusCode::NotImplemented => ("Not Implemented", 501),
             HttpStatusCode::BadGateway => ("Bad Gateway", 502),
             HttpStatusCode::ServiceUnavailable => ("Service Unavailable", 503),
+            HttpStatusCode::GatewayTimeout => ("Gateway Timeout", 504),
+            HttpStatusCode::HttpVersionNotSupported => ("HTTP Version Not Supported", 505),
+            HttpStatusCode::Other(code, reason) => (reason.as_str(), *code),
+        }
+    }
+
+    /// Converts a status code number to it's corresponding `HttpStatusCode` enum value.
+    ///
+    /// # Arguments
+    ///
+    /// - `code` - The number of the status code, like `404`.
+    ///
+    /// # Returns
+    ///
+    /// - `Option<HttpStatusCode>` - The status code, `None` if it has no variant of it's own.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use browzer_web::utils::HttpStatusCode;
+    ///
+    /// assert_eq!(HttpStatusCode::from_code(404), Some(HttpStatusCode::NotFound));
+    /// assert_eq!(HttpStatusCode::from_code(418), None);
+    /// ```
+    pub fn from_code(code: u16) -> Option<HttpStatusCode> {
+        let status_code = match code {
+            200 => HttpStatusCode::OK,
+            201 => HttpStatusCode::Created,
+            202 => HttpStatusCode::Accepted,
+            204 => HttpStatusCode::NoContent,
+            206 => HttpStatusCode::PartialContent,
+            301 => HttpStatusCode::MovedPermanently,
+            302 => HttpStatusCode::Found,
+            303 => HttpStatusCode::SeeOther,
+            304 => HttpStatusCode::NotModified,
+            307 => HttpStatusCode::TemporaryRedirect,
+            308 => HttpStatusCode::PermanentRedirect,
+            400 => HttpStatusCode::BadRequest,
+            401 => HttpStatusCode::Unauthorized,
+            403 => HttpStatusCode::Forbidden,
+            404 => HttpStatusCode::NotFound,
+            405 => HttpStatusCode::MethodNotAllowed,
+            408 => HttpStatusCode::RequestTimeout,
+            409 => HttpStatusCode::Conflict,
+            410 => HttpStatusCode::Gone,
+            412 => HttpStatusCode::PreconditionFailed,
+            413 => HttpStatusCode::PayloadTooLarge,
+            415 => HttpStatusCode::UnsupportedMediaType,
+            416 => HttpStatusCode::RangeNotSatisfiable,
+            421 => HttpStatusCode::MisdirectedRequest,
+            422 => HttpStatusCode::UnprocessableEntity,
+            429 => HttpStatusCode::TooManyRequests,
+            431 => HttpStatusCode::RequestHeaderFieldsTooLarge,
+            500 => HttpStatusCode::InternalServerError,
+            501 => HttpStatusCode::NotImplemented,
+            502 => HttpStatusCode::BadGateway,
+            503 => HttpStatusCode::ServiceUnavailable,
+            504 => HttpStatusCode::GatewayTimeout,
+            505 => HttpStatusCode::HttpVersionNotSupported,
+            _ => return None,
+        };
+        return Some(status_code);
+    }
+}
+
+// serializes status codes as their number
+#[cfg(feature = "serde")]
+impl serde::Serialize for HttpStatusCode {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        return serializer.serialize_u16(self.code().1);
+    }
+}
+
+// deserializes status codes from their number
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for HttpStatusCode {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let code = <u16 as serde::Deserialize>::deserialize(deserializer)?;
+        match HttpStatusCode::from_code(code) {
+            Some(status_code) => return Ok(status_code),
+            None => {
+                return Err(serde::de::Error::custom(format!(
+                    "unsupported status code {}",
+                    code
+                )))
+            }
+        }
+    }
+}
+
+/// The `SameSite` attribute of a cookie, which controls whether the browser sends the cookie along
+/// with cross-site requests.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum SameSite {
+    /// The cookie is only sent with same-site requests.
+    Strict,
+    /// The cookie is also sent when the user navigates to the site from another one(the default of
+    /// most browsers for cookies without the attribute).
+    Lax,
+    /// The cookie is sent with all requests, browsers only accept this on `Secure` cookies.
+    None,
+}
+
+impl SameSite {
+    /// Returns the value of the attribute, like `Lax`.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            SameSite::Strict => return "Strict",
+            SameSite::Lax => return "Lax",
+            SameSite::None => return "None",
         }
     }
 }
@@ -148,13 +775,15 @@ impl HttpStatusCode {
 /// # Examples
 ///
 /// ```rust
+/// # use browzer_web::utils::Cookie;
 /// let cookie = Cookie::new("auth-token","itisanauthtoken");
 /// assert_eq!(cookie.name, "auth-token".to_string());
 /// assert_eq!(cookie.value, "itisanauthtoken".to_string());
 /// assert_eq!(cookie.http_only, false); // default value
-/// assert_eq!(cookie.path, "/".to_string()); // default value
+/// assert_eq!(cookie.path, None); // default value
 /// ```
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Cookie {
     pub name: String,
     pub value: String,
@@ -165,6 +794,7 @@ pub struct Cookie {
     pub max_age: Option<i64>,
     pub secure: bool,
     pub http_only: bool,
+    pub same_site: Option<SameSite>,
     pub raw: Option<String>,
 }
 impl Cookie {
@@ -178,6 +808,7 @@ impl Cookie {
     /// # Examples
     ///
     /// ```rust
+    /// # use browzer_web::utils::Cookie;
     /// let cookie = Cookie::new("session", "abc123");
     /// assert_eq!(cookie.name, "session".to_string());
     /// assert_eq!(cookie.value, "abc123".to_string());
@@ -190,6 +821,81 @@ impl Cookie {
             ..Default::default()
         };
     }
+
+    /// Creates a `CookieBuilder` for a cookie with the given name-value input
+    ///
+    /// # Arguments
+    ///
+    /// - `name` - A string literal representing the name of the cookie
+    /// - `value`- A string literal representing the value of the cookie
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use browzer_web::utils::{Cookie, SameSite};
+    ///
+    /// let cookie = Cookie::builder("session", "abc123")
+    ///     .path("/")
+    ///     .max_age(3600)
+    ///     .http_only(true)
+    ///     .same_site(SameSite::Lax)
+    ///     .build();
+    ///
+    /// assert_eq!(
+    ///     cookie.to_header_value(),
+    ///     "session=abc123; Path=/; Max-Age=3600; HttpOnly; SameSite=Lax"
+    /// );
+    /// ```
+    pub fn builder(name: &str, value: &str) -> CookieBuilder {
+        return CookieBuilder {
+            cookie: Cookie::new(name, value),
+        };
+    }
+
+    /// Serializes the cookie into the value of a `Set-Cookie` header
+    ///
+    /// A cookie with a `raw` value(like a `Set-Cookie` header passed through by a
+    /// `reverse_proxy::ReverseProxy`) is serialized as that value, verbatim.
+    ///
+    /// # Returns
+    ///
+    /// - `String` - The name-value pair of the cookie followed by it's attributes.
+    pub fn to_header_value(&self) -> String {
+        if let Some(ref raw) = self.raw {
+            return raw.clone();
+        }
+        let mut cookie_string = format!("{}={}", self.name, self.value);
+
+        if let Some(ref path) = self.path {
+            cookie_string.push_str(&format!("; Path={}", path));
+        }
+
+        if let Some(ref domain) = self.domain {
+            cookie_string.push_str(&format!("; Domain={}", domain));
+        }
+
+        if let Some(expires) = self.expires {
+            cookie_string.push_str(&format!("; Expires={}", http_date(expires)));
+        }
+
+        if let Some(max_age) = self.max_age {
+            cookie_string.push_str(&format!("; Max-Age={}", max_age));
+        }
+
+        if self.secure {
+            cookie_string.push_str("; Secure");
+        }
+
+        if self.http_only {
+            cookie_string.push_str("; HttpOnly");
+        }
+
+        if let Some(same_site) = self.same_site {
+            cookie_string.push_str(&format!("; SameSite={}", same_site.as_str()));
+        }
+
+        return cookie_string;
+    }
 }
 impl Default for Cookie {
     fn default() -> Self {
@@ -203,7 +909,69 @@ impl Default for Cookie {
             max_age: None,
             secure: false,
             http_only: false,
+            same_site: None,
             raw: None,
         };
     }
 }
+
+/// A builder for `Cookie`s, created with `Cookie::builder`.
+// ----- CookieBuilder struct
+#[derive(Debug, Clone)]
+pub struct CookieBuilder {
+    cookie: Cookie,
+}
+
+impl CookieBuilder {
+    /// Sets the path the cookie is sent for.
+    pub fn path(mut self, path: &str) -> CookieBuilder {
+        self.cookie.path = Some(path.to_string());
+        return self;
+    }
+
+    /// Sets the domain the cookie is sent to.
+    pub fn domain(mut self, domain: &str) -> CookieBuilder {
+        self.cookie.domain = Some(domain.to_string());
+        return self;
+    }
+
+    /// Sets the point in time the cookie expires at.
+    pub fn expires(mut self, expires: time::SystemTime) -> CookieBuilder {
+        self.cookie.expires = Some(expires);
+        return self;
+    }
+
+    /// Sets the number of seconds until the cookie expires, 0 or less expires it right away.
+    pub fn max_age(mut self, seconds: i64) -> CookieBuilder {
+        self.cookie.max_age = Some(seconds);
+        return self;
+    }
+
+    /// Sets whether the cookie is only sent over HTTPS.
+    pub fn secure(mut self, secure: bool) -> CookieBuilder {
+        self.cookie.secure = secure;
+        return self;
+    }
+
+    /// Sets whether the cookie is hidden from JavaScript.
+    pub fn http_only(mut self, http_only: bool) -> CookieBuilder {
+        self.cookie.http_only = http_only;
+        return self;
+    }
+
+    /// Sets the `SameSite` attribute of the cookie.
+    ///
+    /// `SameSite::None` also marks the cookie as `Secure`, since browsers reject it otherwise.
+    pub fn same_site(mut self, same_site: SameSite) -> CookieBuilder {
+        if same_site == SameSite::None {
+            self.cookie.secure = true;
+        }
+        self.cookie.same_site = Some(same_site);
+        return self;
+    }
+
+    /// Returns the built `Cookie`.
+    pub fn build(self) -> Cookie {
+        return self.cookie;
+    }
+}
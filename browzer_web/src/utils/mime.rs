@@ -0,0 +1,97 @@
+//! This module maps file extensions to MIME types, used to set the `Content-Type` header of
+//! static file responses.
+//!
+//! Text based types carry a `charset=utf-8` parameter, anything not found in the table is served
+//! as `application/octet-stream` so browsers download it instead of guessing it's type.
+
+// standard library imports
+use std::path::Path;
+
+/// The MIME type of files whose type is unknown.
+pub const DEFAULT_MIME_TYPE: &str = "application/octet-stream";
+
+// extension -> MIME type, the extensions are lowercase
+const MIME_TYPES: &[(&str, &str)] = &[
+    // text
+    ("html", "text/html; charset=utf-8"),
+    ("htm", "text/html; charset=utf-8"),
+    ("css", "text/css; charset=utf-8"),
+    ("js", "text/javascript; charset=utf-8"),
+    ("mjs", "text/javascript; charset=utf-8"),
+    ("txt", "text/plain; charset=utf-8"),
+    ("md", "text/markdown; charset=utf-8"),
+    ("csv", "text/csv; charset=utf-8"),
+    ("xml", "application/xml; charset=utf-8"),
+    ("json", "application/json"),
+    ("map", "application/json"),
+    ("webmanifest", "application/manifest+json"),
+    // images
+    ("png", "image/png"),
+    ("jpg", "image/jpeg"),
+    ("jpeg", "image/jpeg"),
+    ("gif", "image/gif"),
+    ("webp", "image/webp"),
+    ("avif", "image/avif"),
+    ("svg", "image/svg+xml"),
+    ("ico", "image/x-icon"),
+    ("bmp", "image/bmp"),
+    // fonts
+    ("woff", "font/woff"),
+    ("woff2", "font/woff2"),
+    ("ttf", "font/ttf"),
+    ("otf", "font/otf"),
+    // audio and video
+    ("mp3", "audio/mpeg"),
+    ("ogg", "audio/ogg"),
+    ("wav", "audio/wav"),
+    ("mp4", "video/mp4"),
+    ("webm", "video/webm"),
+    // other
+    ("wasm", "application/wasm"),
+    ("pdf", "application/pdf"),
+    ("zip", "application/zip"),
+    ("gz", "application/gzip"),
+];
+
+/// Looks up the MIME type of a file extension(without the leading dot), case-insensitively.
+///
+/// # Returns
+///
+/// - `Option<&'static str>` - The MIME type, `None` if the extension is unknown.
+///
+/// # Examples
+///
+/// ```rust
+/// # use browzer_web::utils::mime::from_extension;
+/// assert_eq!(from_extension("PNG"), Some("image/png"));
+/// assert_eq!(from_extension("unknown"), None);
+/// ```
+pub fn from_extension(extension: &str) -> Option<&'static str> {
+    return MIME_TYPES
+        .iter()
+        .find(|(known, _)| known.eq_ignore_ascii_case(extension))
+        .map(|(_, mime_type)| *mime_type);
+}
+
+/// Determines the MIME type of a file from the extension of it's path.
+///
+/// # Returns
+///
+/// - `&'static str` - The MIME type, `DEFAULT_MIME_TYPE` for files with an unknown or without an
+///   extension.
+///
+/// # Examples
+///
+/// ```rust
+/// # use browzer_web::utils::mime::from_path;
+/// # use std::path::Path;
+/// assert_eq!(from_path(Path::new("static/index.html")), "text/html; charset=utf-8");
+/// assert_eq!(from_path(Path::new("fonts/inter.woff2")), "font/woff2");
+/// assert_eq!(from_path(Path::new("LICENSE")), "application/octet-stream");
+/// ```
+pub fn from_path(path: &Path) -> &'static str {
+    return match path.extension().and_then(|extension| extension.to_str()) {
+        Some(extension) => from_extension(extension).unwrap_or(DEFAULT_MIME_TYPE),
+        None => DEFAULT_MIME_TYPE,
+    };
+}
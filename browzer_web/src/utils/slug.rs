@@ -0,0 +1,304 @@
+//! This module generates URL-safe identifiers for dynamic routes, human readable slugs derived
+//! from titles(like `/posts/hello-world`) and random short ids(like `/s/x7Kp2Qa9`).
+//!
+//! Both come with collision-aware helpers, which take a function telling whether an identifier is
+//! already taken(like a lookup in the app's storage) and keep trying until they find a free one.
+
+// internal crate imports
+use crate::error;
+
+// external crate imports
+use uuid::Uuid;
+
+// standard library imports
+use std::collections::HashSet;
+
+/// The alphabet short ids are generated from by default, digits and ASCII letters.
+pub const DEFAULT_ALPHABET: &str = "0123456789abcdefghijklmnopqrstuvwxyzABCDEFGHIJKLMNOPQRSTUVWXYZ";
+
+/// The length of short ids generated by default, 62^8 possible ids.
+pub const DEFAULT_LENGTH: usize = 8;
+
+/// Converts text(like the title of a post) into a slug usable as a path segment.
+///
+/// The slug is lowercase and only contains ASCII letters, digits and single dashes between them.
+/// Common accented Latin letters are replaced with their base letter, apostrophes are dropped and
+/// every other run of characters becomes a single dash.
+///
+/// # Arguments
+///
+/// - `input` - The text to convert.
+///
+/// # Returns
+///
+/// - `String` - The slug, empty if the text contains no letters or digits.
+///
+/// # Examples
+///
+/// ```rust
+/// use browzer_web::utils::slug::slugify;
+///
+/// assert_eq!(slugify("Hello, World!"), "hello-world");
+/// assert_eq!(slugify("  Don't Panic -- it's Rust  "), "dont-panic-its-rust");
+/// assert_eq!(slugify("Crème Brûlée"), "creme-brulee");
+/// assert_eq!(slugify("!!!"), "");
+/// ```
+pub fn slugify(input: &str) -> String {
+    let mut slug = String::with_capacity(input.len());
+    let mut pending_dash = false;
+    for c in input.chars() {
+        let replacement = match c {
+            c if c.is_ascii_alphanumeric() => c.to_ascii_lowercase().to_string(),
+            '\'' | '’' => continue,
+            c => fold_latin(c).map(|s| s.to_string()).unwrap_or_default(),
+        };
+        if replacement.is_empty() {
+            pending_dash = true;
+            continue;
+        }
+        if pending_dash && !slug.is_empty() {
+            slug.push('-');
+        }
+        pending_dash = false;
+        slug.push_str(&replacement);
+    }
+    return slug;
+}
+
+/// Converts text into a slug which isn't taken yet, by appending `-2`, `-3` and so on to it.
+///
+/// Text without letters or digits gets a random short id instead, see `short_id`.
+///
+/// # Arguments
+///
+/// - `input` - The text to convert.
+/// - `is_taken` - A function returning whether a slug is already in use.
+///
+/// # Returns
+///
+/// - `String` - The first slug for which `is_taken` returned `false`.
+///
+/// # Examples
+///
+/// ```rust
+/// use browzer_web::utils::slug::unique_slug;
+/// use std::collections::HashSet;
+///
+/// let taken: HashSet<&str> = ["hello-world", "hello-world-2"].into_iter().collect();
+///
+/// assert_eq!(unique_slug("Hello World", |slug| taken.contains(slug)), "hello-world-3");
+/// assert_eq!(unique_slug("Hello Rust", |slug| taken.contains(slug)), "hello-rust");
+/// ```
+pub fn unique_slug<F: FnMut(&str) -> bool>(input: &str, mut is_taken: F) -> String {
+    let base = slugify(input);
+    if base.is_empty() {
+        let generator = ShortIdGenerator::default();
+        loop {
+            let id = generator.generate();
+            if !is_taken(&id) {
+                return id;
+            }
+        }
+    }
+    if !is_taken(&base) {
+        return base;
+    }
+    let mut suffix = 2usize;
+    loop {
+        let slug = format!("{}-{}", base, suffix);
+        if !is_taken(&slug) {
+            return slug;
+        }
+        suffix += 1;
+    }
+}
+
+/// Generates a random short id of `DEFAULT_LENGTH` characters from the `DEFAULT_ALPHABET`.
+///
+/// # Examples
+///
+/// ```rust
+/// use browzer_web::utils::slug::short_id;
+///
+/// let id = short_id();
+///
+/// assert_eq!(id.len(), 8);
+/// assert!(id.chars().all(|c| c.is_ascii_alphanumeric()));
+/// ```
+pub fn short_id() -> String {
+    return ShortIdGenerator::default().generate();
+}
+
+/// Generates random short ids of a fixed length from a configurable alphabet.
+///
+/// Every character of an id is picked uniformly from the alphabet, using the same randomness
+/// source as the rest of the framework(UUID v4). Pick the length based on how many ids will
+/// exist, random ids collide long before all of them are used up.
+///
+/// # Examples
+///
+/// ```rust
+/// use browzer_web::utils::slug::ShortIdGenerator;
+/// use std::collections::HashSet;
+///
+/// // ids without easily confused characters, for codes which are typed in by hand
+/// let generator = ShortIdGenerator::new("23456789abcdefghjkmnpqrstuvwxyz", 6).unwrap();
+/// let mut taken = HashSet::new();
+///
+/// let id = generator.generate_unique(|id| taken.contains(id), 10).unwrap();
+/// assert_eq!(id.len(), 6);
+/// taken.insert(id);
+/// ```
+// ----- ShortIdGenerator struct
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ShortIdGenerator {
+    alphabet: Vec<char>,
+    length: usize,
+}
+
+// default implementation for ShortIdGenerator struct
+impl Default for ShortIdGenerator {
+    fn default() -> Self {
+        return ShortIdGenerator {
+            alphabet: DEFAULT_ALPHABET.chars().collect(),
+            length: DEFAULT_LENGTH,
+        };
+    }
+}
+
+impl ShortIdGenerator {
+    /// Creates a new `ShortIdGenerator`.
+    ///
+    /// # Arguments
+    ///
+    /// - `alphabet` - The characters ids are made of, between 2 and 256 distinct characters.
+    /// - `length` - The number of characters of every id, at least 1.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `SlugError::InvalidAlphabet` error if the alphabet is too small, too large or
+    /// contains a character twice, or if the length is 0.
+    pub fn new(alphabet: &str, length: usize) -> Result<ShortIdGenerator, error::SlugError> {
+        let characters: Vec<char> = alphabet.chars().collect();
+        if characters.len() < 2 || characters.len() > 256 {
+            return Err(error::SlugError::InvalidAlphabet(format!(
+                "expected between 2 and 256 characters, got {}",
+                characters.len()
+            )));
+        }
+        let mut seen = HashSet::new();
+        for c in characters.iter() {
+            if !seen.insert(c) {
+                return Err(error::SlugError::InvalidAlphabet(format!(
+                    "{:?} appears more than once",
+                    c
+                )));
+            }
+        }
+        if length == 0 {
+            return Err(error::SlugError::InvalidAlphabet(
+                "ids have to be at least 1 character long".to_string(),
+            ));
+        }
+        return Ok(ShortIdGenerator {
+            alphabet: characters,
+            length,
+        });
+    }
+
+    /// Returns the characters ids are made of.
+    pub fn alphabet(&self) -> String {
+        return self.alphabet.iter().collect();
+    }
+
+    /// Returns the number of characters of every id.
+    pub fn length(&self) -> usize {
+        return self.length;
+    }
+
+    /// Generates a random id.
+    pub fn generate(&self) -> String {
+        // bytes at or above the limit would make the first characters of the alphabet more
+        // likely, so they are skipped
+        let size = self.alphabet.len();
+        let limit = 256 - 256 % size;
+        let mut id = String::with_capacity(self.length);
+        let mut count = 0;
+        while count < self.length {
+            for byte in random_bytes() {
+                if (byte as usize) < limit {
+                    id.push(self.alphabet[byte as usize % size]);
+                    count += 1;
+                    if count == self.length {
+                        break;
+                    }
+                }
+            }
+        }
+        return id;
+    }
+
+    /// Generates a random id which isn't taken yet.
+    ///
+    /// # Arguments
+    ///
+    /// - `is_taken` - A function returning whether an id is already in use.
+    /// - `max_attempts` - The number of ids tried before giving up.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `SlugError::Exhausted` error if all attempted ids were taken, which usually means
+    /// that the ids are too short for the number of ids in use.
+    pub fn generate_unique<F: FnMut(&str) -> bool>(
+        &self,
+        mut is_taken: F,
+        max_attempts: usize,
+    ) -> Result<String, error::SlugError> {
+        for _ in 0..max_attempts {
+            let id = self.generate();
+            if !is_taken(&id) {
+                return Ok(id);
+            }
+        }
+        return Err(error::SlugError::Exhausted(max_attempts));
+    }
+}
+
+// returns the random bytes of a fresh UUID v4, leaving out the two bytes carrying it's version
+// and variant bits
+pub(crate) fn random_bytes() -> impl Iterator<Item = u8> {
+    return Uuid::new_v4()
+        .into_bytes()
+        .into_iter()
+        .enumerate()
+        .filter(|(i, _)| *i != 6 && *i != 8)
+        .map(|(_, byte)| byte);
+}
+
+// replaces common accented Latin letters with their ASCII base letter(s)
+fn fold_latin(c: char) -> Option<&'static str> {
+    let folded = match c.to_lowercase().next().unwrap_or(c) {
+        'à' | 'á' | 'â' | 'ã' | 'ä' | 'å' | 'ā' | 'ą' => "a",
+        'æ' => "ae",
+        'ç' | 'ć' | 'č' => "c",
+        'ď' | 'đ' => "d",
+        'è' | 'é' | 'ê' | 'ë' | 'ē' | 'ę' | 'ě' => "e",
+        'ğ' => "g",
+        'ì' | 'í' | 'î' | 'ï' | 'ī' | 'ı' => "i",
+        'ł' => "l",
+        'ñ' | 'ń' | 'ň' => "n",
+        'ò' | 'ó' | 'ô' | 'õ' | 'ö' | 'ø' | 'ō' | 'ő' => "o",
+        'œ' => "oe",
+        'ř' => "r",
+        'ś' | 'š' | 'ş' => "s",
+        'ß' => "ss",
+        'ť' | 'ţ' => "t",
+        'ù' | 'ú' | 'û' | 'ü' | 'ū' | 'ů' | 'ű' => "u",
+        'ý' | 'ÿ' => "y",
+        'ź' | 'ż' | 'ž' => "z",
+        'þ' => "th",
+        'ð' => "d",
+        _ => return None,
+    };
+    return Some(folded);
+}
@@ -3,22 +3,228 @@
 //! The `thread_pool` module provides the `ThreadPool` and `Worker` structs, which are used to manage
 //! a pool of worker threads that can execute tasks concurrently. The module leverages Rust's
 //! standard library threading and synchronization primitives.
+//!
+//! Jobs wait in a `JobQueue` until a worker is free. The queue is unbounded by default, with a
+//! capacity(see `ThreadPool::set_queue_capacity`) `ThreadPool::execute` waits for a queued job to
+//! be taken once the queue is full, which slows whoever submits the jobs down to the pace of the
+//! workers instead of letting the queue grow without bound.
+//!
+//! A pool starts with a fixed number of workers. With a maximum above it(see
+//! `ThreadPool::set_max_workers`), extra workers are spawned while jobs wait and no worker is
+//! idle, and retired again once they were idle for a while, so the pool grows under load without
+//! keeping the threads around afterwards.
+//!
+//! A panicking job doesn't take it's worker down with it: the panic is caught, reported and
+//! counted by the `PanicLog` of the pool(see `ThreadPool::recovered_panics`), and the worker goes
+//! on with the next job.
 
 // external crate imports
 use uuid::Uuid;
 
 // internal crate imports
-use crate::error::*;
+use crate::{affinity, error::*, panics};
 
 // standard library imports
 use std::{
-    sync::{mpsc, Arc, Mutex},
+    panic::{self, AssertUnwindSafe},
+    sync::{mpsc, Arc, Condvar, Mutex, MutexGuard, RwLock},
     thread::{self},
+    time::{Duration, Instant},
 };
 
 /// The type of job that a worker can execute.
 type Job = Box<dyn FnOnce() + Send + 'static>;
 
+/// The jobs submitted to a `ThreadPool` which no worker took yet.
+// ----- JobQueue struct
+#[derive(Debug)]
+pub struct JobQueue {
+    receiver: Mutex<mpsc::Receiver<Job>>,
+    state: Mutex<QueueState>,
+    space: Condvar,
+    panic_log: RwLock<Arc<panics::PanicLog>>,
+}
+
+// the number of queued jobs and how many may be queued, and the workers taking them
+#[derive(Debug, Default)]
+struct QueueState {
+    queued: usize,
+    capacity: Option<usize>,
+    workers: usize,
+    idle: usize,
+    min_workers: usize,
+    max_workers: usize,
+    idle_timeout: Option<Duration>,
+}
+
+impl JobQueue {
+    /// Creates a new, unbounded `JobQueue` of the jobs sent through the sending half of the
+    /// receiver's channel.
+    pub fn new(receiver: mpsc::Receiver<Job>) -> JobQueue {
+        return JobQueue {
+            receiver: Mutex::new(receiver),
+            state: Mutex::new(QueueState::default()),
+            space: Condvar::new(),
+            panic_log: RwLock::new(Arc::new(panics::PanicLog::default())),
+        };
+    }
+
+    /// Returns the `PanicLog` panicking jobs are recorded in.
+    pub fn panic_log(&self) -> Arc<panics::PanicLog> {
+        let panic_log = self
+            .panic_log
+            .read()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        return Arc::clone(&panic_log);
+    }
+
+    // locks the queue state, recovering it from a poisoned lock since it's always consistent
+    fn state(&self) -> MutexGuard<'_, QueueState> {
+        return self
+            .state
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+    }
+
+    /// Returns the number of jobs waiting for a worker.
+    pub fn queued(&self) -> usize {
+        return self.state().queued;
+    }
+
+    /// Returns how many jobs may wait for a worker, `None` if the queue is unbounded.
+    pub fn capacity(&self) -> Option<usize> {
+        return self.state().capacity;
+    }
+
+    /// Returns the number of workers taking jobs from the queue.
+    pub fn workers(&self) -> usize {
+        return self.state().workers;
+    }
+
+    /// Returns the number of workers waiting for a job.
+    pub fn idle(&self) -> usize {
+        return self.state().idle;
+    }
+
+    /// Returns whether the queue is bounded and full.
+    pub fn is_full(&self) -> bool {
+        let state = self.state();
+        return state
+            .capacity
+            .is_some_and(|capacity| state.queued >= capacity);
+    }
+
+    /// Returns whether jobs are waiting while every worker is busy and no more workers can be
+    /// spawned.
+    pub fn is_backlogged(&self) -> bool {
+        let state = self.state();
+        return state.queued > 0 && state.idle == 0 && state.workers >= state.max_workers;
+    }
+
+    // waits until there is room for another job and counts it as queued, returning whether the
+    // pool should spawn another worker for it(which is counted right away, see `grow`)
+    fn reserve(&self) -> bool {
+        let mut state = self.state();
+        while state
+            .capacity
+            .is_some_and(|capacity| state.queued >= capacity)
+        {
+            state = self
+                .space
+                .wait(state)
+                .unwrap_or_else(|poisoned| poisoned.into_inner());
+        }
+        state.queued += 1;
+        return Self::grow(&mut state);
+    }
+
+    // counts the job as queued if there is room for it, returning whether the pool should spawn
+    // another worker for it(which is counted right away, see `grow`)
+    fn try_reserve(&self) -> Result<bool, ThreadPoolError> {
+        let mut state = self.state();
+        if state
+            .capacity
+            .is_some_and(|capacity| state.queued >= capacity)
+        {
+            return Err(ThreadPoolError::QueueFull);
+        }
+        state.queued += 1;
+        return Ok(Self::grow(&mut state));
+    }
+
+    // counts another worker if a queued job has no idle worker to take it, under the same lock as
+    // the job was counted under, so jobs queued at the same time can't grow the pool past
+    // `max_workers`
+    fn grow(state: &mut QueueState) -> bool {
+        if state.queued > state.idle && state.workers < state.max_workers {
+            state.workers += 1;
+            return true;
+        }
+        return false;
+    }
+
+    // counts a queued job as taken, making room for another one
+    fn release(&self) {
+        let mut state = self.state();
+        state.queued = state.queued.saturating_sub(1);
+        self.space.notify_one();
+    }
+
+    // waits for the next job, `None` if the worker was idle for too long and retires, an error
+    // once the pool was dropped
+    fn next(&self) -> Result<Option<Job>, ThreadPoolError> {
+        self.state().idle += 1;
+        let job = self.receive(Instant::now());
+        self.state().idle -= 1;
+        if let Ok(Some(_)) = job {
+            self.release();
+        }
+        return job;
+    }
+
+    // receives the next job, retiring the worker once it waited for `idle_timeout` since
+    // `idle_since`. Only one worker waits on the receiver at a time, the others wait for it's lock,
+    // so the time spent waiting for the lock counts as well, otherwise the idle workers would
+    // retire one per `idle_timeout`
+    fn receive(&self, idle_since: Instant) -> Result<Option<Job>, ThreadPoolError> {
+        let receiver = self.receiver.lock()?;
+        loop {
+            // only the workers over the minimum retire
+            let idle_timeout = {
+                let state = self.state();
+                state
+                    .idle_timeout
+                    .filter(|_| state.workers > state.min_workers)
+            };
+            let timeout = match idle_timeout {
+                Some(timeout) => timeout.saturating_sub(idle_since.elapsed()),
+                None => return Ok(Some(receiver.recv()?)),
+            };
+            let received = match timeout.is_zero() {
+                // a job which is already waiting is still taken
+                true => receiver.try_recv().map_err(|e| match e {
+                    mpsc::TryRecvError::Empty => mpsc::RecvTimeoutError::Timeout,
+                    mpsc::TryRecvError::Disconnected => mpsc::RecvTimeoutError::Disconnected,
+                }),
+                false => receiver.recv_timeout(timeout),
+            };
+            match received {
+                Ok(job) => return Ok(Some(job)),
+                Err(mpsc::RecvTimeoutError::Disconnected) => {
+                    return Err(ThreadPoolError::from(mpsc::RecvError));
+                }
+                Err(mpsc::RecvTimeoutError::Timeout) => {
+                    let mut state = self.state();
+                    if state.workers > state.min_workers {
+                        state.workers -= 1;
+                        return Ok(None);
+                    }
+                }
+            }
+        }
+    }
+}
+
 /// A struct representing a worker in the thread pool.
 /// Each worker has a unique identifier and a thread.
 // ----- Worker struct
@@ -31,10 +237,13 @@ impl Worker {
     /// This function creates a thread which runs a loop, listen for incoming jobs throught the `Receiver`, ensure
     /// the integrity of the job recieved, run the job in the thread, and return the `Worker` object
     ///
+    /// The worker isn't counted by the queue(see `JobQueue::workers`), the `ThreadPool` counts
+    /// it's workers before creating them.
+    ///
     /// # Arguments
     ///
     /// - `id` - A unique identifier for the worker.
-    /// - `receiver` - A shared receiver for receiving jobs from the thread pool.
+    /// - `queue` - The shared queue the worker takes jobs from.
     ///
     /// # Returns
     ///
@@ -44,25 +253,27 @@ impl Worker {
     ///
     /// ```rust
     /// use uuid::Uuid;
-    /// use std::sync::{Arc, Mutex, mpsc};
-    /// use crate::thread_pool::{Worker, Job};
+    /// use std::sync::{Arc, mpsc};
+    /// use browzer_web::utils::thread_pool::{JobQueue, Worker};
     ///
-    /// let (sender, receiver) = mpsc::channel();
-    /// let receiver = Arc::new(Mutex::new(receiver));
-    /// let worker = Worker::new(Uuid::new_v4(), Arc::clone(&receiver));
+    /// let (_sender, receiver) = mpsc::channel();
+    /// let queue = Arc::new(JobQueue::new(receiver));
+    /// let worker = Worker::new(Uuid::new_v4(), Arc::clone(&queue));
     /// ```
-    pub fn new(id: Uuid, receiver: Arc<Mutex<mpsc::Receiver<Job>>>) -> Worker {
+    pub fn new(id: Uuid, queue: Arc<JobQueue>) -> Worker {
         let thread = thread::spawn(move || loop {
-            let message = receiver
-                .lock()
-                .map_err(ThreadPoolError::from)
-                .and_then(|rx| rx.recv().map_err(ThreadPoolError::from));
-            match message {
-                Ok(job) => {
-                    job();
+            match queue.next() {
+                Ok(Some(job)) => {
+                    if let Err(payload) = panic::catch_unwind(AssertUnwindSafe(job)) {
+                        queue
+                            .panic_log()
+                            .record(panics::PanicPhase::Job, payload.as_ref());
+                    }
                 }
+                // the worker was idle for too long and isn't needed anymore
+                Ok(None) => break,
                 Err(_) => {
-                    println!("Worker {} disconnected, shutting down...", id.to_string());
+                    println!("Worker {} disconnected, shutting down...", id);
                     break;
                 }
             }
@@ -81,8 +292,9 @@ impl Worker {
 // ----- ThreadPool struct
 #[derive(Debug)]
 pub struct ThreadPool {
-    workers: Vec<Worker>,
+    workers: Mutex<Vec<Worker>>,
     sender: Option<mpsc::Sender<Job>>,
+    queue: Arc<JobQueue>,
 }
 impl ThreadPool {
     /// This function creates a channel for sending and recieving jobs, create a vector for storing workers, and
@@ -103,7 +315,7 @@ impl ThreadPool {
     /// # Examples
     ///
     /// ```rust
-    /// use crate::thread_pool::ThreadPool;
+    /// use browzer_web::utils::thread_pool::ThreadPool;
     ///
     /// let pool = ThreadPool::new(4);
     /// ```
@@ -111,21 +323,213 @@ impl ThreadPool {
         assert!(size > 0);
 
         let (sender, receiver) = mpsc::channel();
-        let receiver = Arc::new(Mutex::new(receiver));
+        let queue = Arc::new(JobQueue::new(receiver));
+        {
+            let mut state = queue.state();
+            state.workers = size;
+            state.min_workers = size;
+            state.max_workers = size;
+        }
 
         let mut workers = Vec::with_capacity(size);
         for _ in 0..size {
-            workers.push(Worker::new(Uuid::new_v4(), Arc::clone(&receiver)));
+            workers.push(Worker::new(Uuid::new_v4(), Arc::clone(&queue)));
         }
 
         // return the ThreadPool struct
         return ThreadPool {
             sender: Some(sender),
-            workers,
+            workers: Mutex::new(workers),
+            queue,
         };
     }
 
-    /// Sends a job to the thread pool for execution.
+    /// Creates a `ThreadPool` which grows from `min` up to `max` workers under load, see
+    /// `ThreadPool::set_max_workers`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `min` is 0.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use browzer_web::utils::thread_pool::ThreadPool;
+    /// use std::time::Duration;
+    ///
+    /// let pool = ThreadPool::dynamic(2, 16, Duration::from_secs(30));
+    /// assert_eq!(pool.size(), 2);
+    /// assert_eq!(pool.max_workers(), 16);
+    /// ```
+    pub fn dynamic(min: usize, max: usize, idle_timeout: Duration) -> ThreadPool {
+        let pool = ThreadPool::new(min);
+        pool.set_max_workers(max, idle_timeout);
+        return pool;
+    }
+
+    /// Lets the pool grow up to `max` workers.
+    ///
+    /// Whenever a job is queued while no worker is idle, another worker is spawned until there are
+    /// `max` of them. Workers over the number the pool was created with retire once they waited
+    /// for a job for `idle_timeout`. A `max` below that number keeps the pool at it's size.
+    ///
+    /// Workers spawned under load aren't pinned to a core, see `ThreadPool::pin_workers`.
+    pub fn set_max_workers(&self, max: usize, idle_timeout: Duration) {
+        let mut state = self.queue.state();
+        state.max_workers = max.max(state.min_workers);
+        state.idle_timeout = Some(idle_timeout);
+    }
+
+    /// Returns the current number of workers.
+    pub fn size(&self) -> usize {
+        return self.queue.workers();
+    }
+
+    /// Returns the number of workers waiting for a job.
+    pub fn idle(&self) -> usize {
+        return self.queue.idle();
+    }
+
+    /// Returns the queue of the pool, which tells how busy the pool is without access to the pool
+    /// itself.
+    pub fn queue(&self) -> Arc<JobQueue> {
+        return Arc::clone(&self.queue);
+    }
+
+    /// Returns the number of panics the workers recovered from and went on with the next job,
+    /// the total of the `PanicLog` of the pool(see `ThreadPool::set_panic_log`).
+    ///
+    /// Jobs may catch their panics themselves and record them in the same log, like the jobs of a
+    /// `WebServer` serving a connection do, so those are counted as well.
+    pub fn recovered_panics(&self) -> u64 {
+        return self.queue.panic_log().counts().total();
+    }
+
+    /// Records the panicking jobs in the given `PanicLog` from now on, which reports them to it's
+    /// hook instead of printing them to the console.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use browzer_web::{panics::PanicLog, utils::thread_pool::ThreadPool};
+    /// use std::sync::Arc;
+    ///
+    /// let mut panic_log = PanicLog::default();
+    /// panic_log.hook = Some(Box::new(|report| eprintln!("[pool] {}", report)));
+    ///
+    /// let pool = ThreadPool::new(2);
+    /// pool.set_panic_log(Arc::new(panic_log));
+    /// ```
+    pub fn set_panic_log(&self, panic_log: Arc<panics::PanicLog>) {
+        *self
+            .queue
+            .panic_log
+            .write()
+            .unwrap_or_else(|poisoned| poisoned.into_inner()) = panic_log;
+    }
+
+    /// Returns the number of workers the pool keeps while it's idle.
+    pub fn min_workers(&self) -> usize {
+        return self.queue.state().min_workers;
+    }
+
+    /// Returns the number of workers the pool may grow to.
+    pub fn max_workers(&self) -> usize {
+        return self.queue.state().max_workers;
+    }
+
+    // locks the workers, recovering them from a poisoned lock since they're never left half
+    // changed
+    fn workers(&self) -> MutexGuard<'_, Vec<Worker>> {
+        return self
+            .workers
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+    }
+
+    // spawns another worker which was counted already, forgetting the ones which retired meanwhile
+    fn spawn_worker(&self) {
+        let mut workers = self.workers();
+        workers.retain(|worker| {
+            worker
+                .thread
+                .as_ref()
+                .is_some_and(|thread| !thread.is_finished())
+        });
+        workers.push(Worker::new(Uuid::new_v4(), Arc::clone(&self.queue)));
+    }
+
+    /// Bounds the number of jobs waiting for a worker, `None` removes the bound.
+    ///
+    /// Once `capacity` jobs are queued, `execute` waits until a worker took one of them.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use browzer_web::utils::thread_pool::ThreadPool;
+    ///
+    /// let pool = ThreadPool::new(4);
+    /// pool.set_queue_capacity(Some(64));
+    /// assert_eq!(pool.queue_capacity(), Some(64));
+    /// assert!(!pool.is_saturated());
+    /// ```
+    pub fn set_queue_capacity(&self, capacity: Option<usize>) {
+        self.queue.state().capacity = capacity;
+        // waiting submitters may fit into a larger queue
+        self.queue.space.notify_all();
+    }
+
+    /// Returns how many jobs may wait for a worker, `None` if the queue is unbounded.
+    pub fn queue_capacity(&self) -> Option<usize> {
+        return self.queue.capacity();
+    }
+
+    /// Returns the number of jobs waiting for a worker.
+    pub fn queued(&self) -> usize {
+        return self.queue.queued();
+    }
+
+    /// Returns whether the queue is bounded and full, so `execute` would wait.
+    pub fn is_saturated(&self) -> bool {
+        return self.queue.is_full();
+    }
+
+    /// Pins the worker threads to CPU cores, see the `affinity` module.
+    ///
+    /// # Arguments
+    ///
+    /// - `cores` - The cores of the workers, the first worker is pinned to the first core and so
+    ///   on, starting over from the first core if there are more workers than cores.
+    ///
+    /// # Returns
+    ///
+    /// - `usize` - The number of workers which were pinned, 0 if pinning isn't supported on this
+    ///   platform.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use browzer_web::{affinity, utils::thread_pool::ThreadPool};
+    ///
+    /// let pool = ThreadPool::new(4);
+    /// let pinned = pool.pin_workers(&affinity::available_cores());
+    /// ```
+    pub fn pin_workers(&self, cores: &[usize]) -> usize {
+        if cores.is_empty() {
+            return 0;
+        }
+        let mut pinned = 0;
+        for (i, worker) in self.workers().iter().enumerate() {
+            if let Some(thread) = &worker.thread {
+                if affinity::pin_thread(thread, cores[i % cores.len()]) {
+                    pinned += 1;
+                }
+            }
+        }
+        return pinned;
+    }
+
+    /// Sends a job to the thread pool for execution, waiting for room in the queue if it's full.
     ///
     /// # Arguments
     ///
@@ -138,7 +542,7 @@ impl ThreadPool {
     /// # Examples
     ///
     /// ```rust
-    /// use crate::thread_pool::ThreadPool;
+    /// use browzer_web::utils::thread_pool::ThreadPool;
     ///
     /// let pool = ThreadPool::new(4);
     /// pool.execute(|| {
@@ -149,12 +553,68 @@ impl ThreadPool {
     where
         F: FnOnce() + Send + 'static,
     {
-        let _ = self
+        let sender = self
+            .sender
+            .as_ref()
+            .ok_or_else(|| ThreadPoolError::SendError("Sender is not innitialized".to_string()))?;
+        let grow = self.queue.reserve();
+        return self.send(sender, Box::new(f), grow);
+    }
+
+    /// Sends a job to the thread pool for execution like `ThreadPool::execute`, but fails instead
+    /// of waiting if the queue is full.
+    ///
+    /// The queue is checked and the job counted as queued at once, so unlike checking
+    /// `ThreadPool::is_saturated` first, no other job can take the last room in the queue in
+    /// between.
+    ///
+    /// # Arguments
+    ///
+    /// - `f` - A closure representing the job to be executed.
+    ///
+    /// # Errors
+    ///
+    /// - `ThreadPoolError::QueueFull` - The queue is full, the job is dropped without being run.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use browzer_web::{error::ThreadPoolError, utils::thread_pool::ThreadPool};
+    ///
+    /// let pool = ThreadPool::new(1);
+    /// pool.set_queue_capacity(Some(0));
+    /// assert!(matches!(pool.try_execute(|| {}), Err(ThreadPoolError::QueueFull)));
+    /// ```
+    pub fn try_execute<F>(&self, f: F) -> Result<(), ThreadPoolError>
+    where
+        F: FnOnce() + Send + 'static,
+    {
+        let sender = self
             .sender
             .as_ref()
-            .ok_or_else(|| ThreadPoolError::SendError("Sender is not innitialized".to_string()))?
-            .send(Box::new(f))
-            .map_err(|e| ThreadPoolError::SendError(e.to_string()));
+            .ok_or_else(|| ThreadPoolError::SendError("Sender is not innitialized".to_string()))?;
+        let grow = self.queue.try_reserve()?;
+        return self.send(sender, Box::new(f), grow);
+    }
+
+    // sends a job which was counted as queued already, spawning another worker for it if needed
+    fn send(
+        &self,
+        sender: &mpsc::Sender<Job>,
+        job: Job,
+        grow: bool,
+    ) -> Result<(), ThreadPoolError> {
+        if sender.send(job).is_err() {
+            // no worker is left to take the job
+            self.queue.release();
+            if grow {
+                self.queue.state().workers -= 1;
+            }
+            return Ok(());
+        }
+        if grow {
+            self.spawn_worker();
+        }
         Ok(())
     }
 }
@@ -163,8 +623,8 @@ impl ThreadPool {
 impl Drop for ThreadPool {
     fn drop(&mut self) {
         drop(self.sender.take());
-        for worker in &mut self.workers {
-            println!("Shuting down worker {}", worker.id.to_string());
+        for worker in self.workers().iter_mut() {
+            println!("Shuting down worker {}", worker.id);
             if let Some(thread) = worker.thread.take() {
                 thread.join().unwrap();
             }
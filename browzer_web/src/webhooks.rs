@@ -0,0 +1,714 @@
+//! This module delivers outbound webhooks, signed JSON events an app sends to the endpoints other
+//! services registered with it.
+//!
+//! A `Dispatcher` is created once and shared with the handlers which raise events(it is cheap to
+//! clone). `Dispatcher::dispatch` queues a delivery of an event to every registered endpoint
+//! interested in it and returns right away, the deliveries are sent on a background pool. A
+//! delivery which fails(with an I/O error or a status code other than `2xx`) is retried with
+//! exponential backoff, and moved to the dead letters once it ran out of attempts, from where it
+//! can be inspected and redelivered.
+//!
+//! Events follow the Standard Webhooks specification(https://www.standardwebhooks.com), the body
+//! looks like `{"type":"invoice.paid","timestamp":"2024-05-01T12:00:00Z","data":{..}}` and it is
+//! sent with `webhook-id`, `webhook-timestamp` and `webhook-signature` headers, which receivers
+//! verify with the shared secret(see `sign`).
+//!
+//! Deliveries are sent by `HttpTransport`, over HTTPS to `https://` endpoints with the `tls`
+//! feature, plug in a `WebhookTransport` to send them through a queue instead. Deliveries which are
+//! still queued when the last clone of a `Dispatcher` is dropped are lost, call
+//! `Dispatcher::wait_idle` before shutting down.
+
+// internal crate imports
+use crate::{digest, http_client, tasks, utils};
+
+// external crate imports
+use serde::Serialize;
+use uuid::Uuid;
+
+// standard library imports
+use std::{
+    cmp::Reverse,
+    collections::{BinaryHeap, HashMap, VecDeque},
+    fmt,
+    io::{self, BufRead, BufReader, Write},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Condvar, Mutex, MutexGuard, Once, Weak,
+    },
+    thread,
+    time::{Duration, Instant},
+};
+
+// the number of dead letters kept, the oldest ones are dropped once there are more
+const MAX_DEAD_LETTERS: usize = 1000;
+
+// the longest the scheduler sleeps at once, so it notices when it's dispatcher was dropped
+const MAX_SCHEDULER_SLEEP: Duration = Duration::from_secs(1);
+
+/// A boxed hook which is called with every delivery moved to the dead letters.
+pub type DeadLetterHook = Box<dyn Fn(&Delivery) + 'static + Send + Sync>;
+
+/// Computes the `webhook-signature` header of a delivery(Standard Webhooks, HMAC-SHA256 over
+/// `<id>.<timestamp>.<body>`), which receivers compare to the one they got.
+///
+/// # Arguments
+///
+/// - `secret` - The secret shared with the receiver.
+/// - `id` - The `webhook-id` header of the delivery.
+/// - `timestamp` - The `webhook-timestamp` header of the delivery, in seconds since the Unix epoch.
+/// - `body` - The body of the delivery.
+///
+/// # Examples
+///
+/// ```rust
+/// use browzer_web::webhooks::sign;
+///
+/// let signature = sign(b"secret", "msg_1", 1714564800, r#"{"type":"ping"}"#);
+/// assert!(signature.starts_with("v1,"));
+/// ```
+pub fn sign(secret: &[u8], id: &str, timestamp: i64, body: &str) -> String {
+    let message = format!("{}.{}.{}", id, timestamp, body);
+    let mac = digest::hmac_sha256(secret, message.as_bytes());
+    return format!("v1,{}", utils::base64_encode(&mac));
+}
+
+/// Sends deliveries to their endpoints.
+///
+/// Implement this trait to send deliveries with another HTTP client or through a queue.
+pub trait WebhookTransport: Send + Sync {
+    /// Posts a body to an endpoint.
+    ///
+    /// # Returns
+    ///
+    /// - `io::Result<u16>` - The status code the endpoint answered with, or an I/O error if it
+    ///   couldn't be reached.
+    fn post(&self, url: &str, headers: &[(String, String)], body: &[u8]) -> io::Result<u16>;
+}
+
+/// A `WebhookTransport` posting deliveries as HTTP/1.1 requests with an `http_client::Client`, on
+/// a new connection each.
+///
+/// Deliveries to `https://` URLs are sent over TLS with the `tls` feature and fail without it.
+/// Deliveries to URLs `http_client::Url::parse` rejects, or with headers containing line breaks,
+/// fail without anything being sent.
+///
+/// # Examples
+///
+/// ```rust
+/// use browzer_web::{http_client::Client, webhooks::{Dispatcher, HttpTransport}};
+/// use std::time::Duration;
+///
+/// let transport = HttpTransport::new(Client::new().timeout(Duration::from_secs(5)));
+/// let webhooks = Dispatcher::new(b"whsec_shared_with_the_receivers").transport(transport);
+/// ```
+// ----- HttpTransport struct
+#[derive(Debug, Clone)]
+pub struct HttpTransport {
+    client: http_client::Client,
+}
+
+// default implementation for HttpTransport struct
+impl Default for HttpTransport {
+    fn default() -> Self {
+        return HttpTransport::new(http_client::Client::new().timeout(Duration::from_secs(10)));
+    }
+}
+
+impl HttpTransport {
+    /// Creates a new `HttpTransport` sending deliveries with the given client, whose timeout
+    /// applies to connecting, sending and waiting for the status line each. The default transport
+    /// uses a client with a timeout of 10 seconds.
+    pub fn new(client: http_client::Client) -> HttpTransport {
+        return HttpTransport { client };
+    }
+}
+
+impl WebhookTransport for HttpTransport {
+    fn post(&self, url: &str, headers: &[(String, String)], body: &[u8]) -> io::Result<u16> {
+        let invalid = |message: String| io::Error::new(io::ErrorKind::InvalidInput, message);
+        let url = http_client::Url::parse(url).map_err(|e| invalid(e.to_string()))?;
+        let mut head = format!(
+            "POST {} HTTP/1.1\r\nHost: {}\r\nContent-Length: {}\r\nConnection: close\r\n",
+            url.target(),
+            url.authority(),
+            body.len()
+        );
+        for (name, value) in headers {
+            if name.contains(['\r', '\n', ':']) || value.contains(['\r', '\n']) {
+                return Err(invalid(format!("Invalid header: {}", name)));
+            }
+            head.push_str(&format!("{}: {}\r\n", name, value));
+        }
+        head.push_str("\r\n");
+
+        let mut connection = self.client.connect(&url)?;
+        connection.write_all(head.as_bytes())?;
+        connection.write_all(body)?;
+        connection.flush()?;
+
+        let mut status_line = String::new();
+        BufReader::new(connection).read_line(&mut status_line)?;
+        return status_line
+            .split_whitespace()
+            .nth(1)
+            .and_then(|code| code.parse::<u16>().ok())
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "malformed status line"));
+    }
+}
+
+/// How often and when failed deliveries are retried.
+///
+/// The n-th retry happens `initial_backoff * 2^(n - 1)` after the failed attempt, but never
+/// later than `max_backoff`.
+///
+/// # Fields
+///
+/// - `max_attempts` - The number of attempts, the first one included, before a delivery is moved
+///   to the dead letters.
+/// - `initial_backoff` - The wait before the first retry.
+/// - `max_backoff` - The longest wait between two attempts.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RetryPolicy {
+    pub max_attempts: u32,
+    pub initial_backoff: Duration,
+    pub max_backoff: Duration,
+}
+
+// default implementation for RetryPolicy struct
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        return RetryPolicy {
+            max_attempts: 6,
+            initial_backoff: Duration::from_secs(5),
+            max_backoff: Duration::from_secs(15 * 60),
+        };
+    }
+}
+
+impl RetryPolicy {
+    /// Returns the wait after the given number of failed attempts.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use browzer_web::webhooks::RetryPolicy;
+    /// use std::time::Duration;
+    ///
+    /// let policy = RetryPolicy::default();
+    /// assert_eq!(policy.backoff(1), Duration::from_secs(5));
+    /// assert_eq!(policy.backoff(3), Duration::from_secs(20));
+    /// assert_eq!(policy.backoff(30), Duration::from_secs(15 * 60));
+    /// ```
+    pub fn backoff(&self, failed_attempts: u32) -> Duration {
+        let factor = 2u32.saturating_pow(failed_attempts.saturating_sub(1));
+        return self
+            .initial_backoff
+            .checked_mul(factor)
+            .unwrap_or(self.max_backoff)
+            .min(self.max_backoff);
+    }
+}
+
+/// An endpoint deliveries are sent to.
+///
+/// # Fields
+///
+/// - `id` - The id the endpoint was registered under.
+/// - `url` - The URL deliveries are posted to.
+/// - `events` - The types of the events the endpoint gets, empty for all of them.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Endpoint {
+    pub id: String,
+    pub url: String,
+    pub events: Vec<String>,
+}
+
+impl Endpoint {
+    /// Returns whether the endpoint gets events of the given type.
+    pub fn accepts(&self, event: &str) -> bool {
+        return self.events.is_empty() || self.events.iter().any(|e| e == event);
+    }
+}
+
+/// A single event sent to a single endpoint.
+///
+/// # Fields
+///
+/// - `id` - The id of the delivery, sent as the `webhook-id` header. Retries keep the id, so
+///   receivers can detect duplicates.
+/// - `endpoint_id` - The id of the endpoint.
+/// - `url` - The URL of the endpoint.
+/// - `event` - The type of the event.
+/// - `body` - The JSON body.
+/// - `attempts` - The number of attempts made so far.
+/// - `last_error` - Why the last attempt failed, if it did.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Delivery {
+    pub id: String,
+    pub endpoint_id: String,
+    pub url: String,
+    pub event: String,
+    pub body: String,
+    pub attempts: u32,
+    pub last_error: Option<String>,
+}
+
+/// Counters describing the deliveries of a `Dispatcher`.
+///
+/// # Fields
+///
+/// - `queued` - The number of deliveries queued, redeliveries included.
+/// - `delivered` - The number of deliveries the endpoint accepted.
+/// - `failed_attempts` - The number of attempts which failed.
+/// - `dead_lettered` - The number of deliveries moved to the dead letters.
+/// - `pending` - The number of deliveries waiting for their next attempt or being sent right now.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct DeliveryMetrics {
+    pub queued: u64,
+    pub delivered: u64,
+    pub failed_attempts: u64,
+    pub dead_lettered: u64,
+    pub pending: u64,
+}
+
+// the body of a delivery
+#[derive(Serialize)]
+struct Event<'a, T: Serialize> {
+    #[serde(rename = "type")]
+    event: &'a str,
+    timestamp: String,
+    data: &'a T,
+}
+
+// the deliveries waiting for their next attempt, ordered by when it is due
+#[derive(Default)]
+struct Schedule {
+    due: BinaryHeap<Reverse<(Instant, u64)>>,
+    deliveries: HashMap<u64, Delivery>,
+    next: u64,
+}
+
+// the counters behind `DeliveryMetrics`
+#[derive(Debug, Default)]
+struct Counters {
+    queued: AtomicU64,
+    delivered: AtomicU64,
+    failed_attempts: AtomicU64,
+    dead_lettered: AtomicU64,
+    pending: AtomicU64,
+}
+
+// the state shared by all clones of a dispatcher and it's scheduler
+struct Inner {
+    secret: Vec<u8>,
+    endpoints: Mutex<Vec<Endpoint>>,
+    schedule: Mutex<Schedule>,
+    wakeup: Condvar,
+    dead_letters: Mutex<VecDeque<Delivery>>,
+    counters: Counters,
+    idle: (Mutex<()>, Condvar),
+    started: Once,
+    retry_policy: RetryPolicy,
+    transport: Arc<dyn WebhookTransport>,
+    pool: Arc<tasks::BackgroundPool>,
+    dead_letter_hook: Option<DeadLetterHook>,
+}
+
+/// Delivers webhooks to registered endpoints, see the module documentation.
+///
+/// # Examples
+///
+/// ```rust,no_run
+/// use browzer_web::{utils::HttpStatusCode, webhooks::Dispatcher, WebServer};
+/// use serde_json::json;
+///
+/// let webhooks = Dispatcher::new(b"whsec_shared_with_the_receivers");
+/// webhooks.register("http://billing.internal/hooks", &["invoice.paid"]);
+///
+/// let mut server = WebServer::new("127.0.0.1:8080".to_string(), 4);
+/// let dispatcher = webhooks.clone();
+/// server.post("/invoices/:id/pay", move |mut c| {
+///     let id = c.params.get("id").cloned().unwrap_or_default();
+///     // ... mark the invoice as paid ...
+///     let _ = dispatcher.dispatch("invoice.paid", &json!({ "invoice": id }));
+///     return c.send_string(HttpStatusCode::OK, "paid");
+/// });
+/// server.listen();
+/// ```
+// ----- Dispatcher struct
+#[derive(Clone)]
+pub struct Dispatcher {
+    inner: Arc<Inner>,
+}
+
+impl fmt::Debug for Dispatcher {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Dispatcher")
+            .field("endpoints", &self.endpoints())
+            .field("retry_policy", &self.inner.retry_policy)
+            .field("transport", &"Arc<dyn WebhookTransport>")
+            .field("pool", &self.inner.pool)
+            .field(
+                "dead_letter_hook",
+                &self
+                    .inner
+                    .dead_letter_hook
+                    .as_ref()
+                    .map(|_| "Box<dyn Fn(&Delivery) + 'static + Send + Sync>"),
+            )
+            .field("metrics", &self.metrics())
+            .finish()
+    }
+}
+
+impl Dispatcher {
+    /// Creates a new `Dispatcher` signing deliveries with the given secret, sending them with
+    /// `HttpTransport` on a background pool of 2 threads and retrying them with the default
+    /// `RetryPolicy`.
+    pub fn new(secret: &[u8]) -> Dispatcher {
+        return Dispatcher {
+            inner: Arc::new(Inner {
+                secret: secret.to_vec(),
+                endpoints: Mutex::new(vec![]),
+                schedule: Mutex::new(Schedule::default()),
+                wakeup: Condvar::new(),
+                dead_letters: Mutex::new(VecDeque::new()),
+                counters: Counters::default(),
+                idle: (Mutex::new(()), Condvar::new()),
+                started: Once::new(),
+                retry_policy: RetryPolicy::default(),
+                transport: Arc::new(HttpTransport::default()),
+                pool: Arc::new(tasks::BackgroundPool::new(2)),
+                dead_letter_hook: None,
+            }),
+        };
+    }
+
+    /// Retries failed deliveries according to the given policy.
+    ///
+    /// Like the other configuration methods, it only takes effect before the dispatcher is
+    /// cloned.
+    pub fn retry_policy(mut self, policy: RetryPolicy) -> Dispatcher {
+        if let Some(inner) = self.configure() {
+            inner.retry_policy = policy;
+        }
+        return self;
+    }
+
+    /// Sends deliveries with the given transport instead of `HttpTransport`.
+    pub fn transport<T>(mut self, transport: T) -> Dispatcher
+    where
+        T: WebhookTransport + 'static,
+    {
+        if let Some(inner) = self.configure() {
+            inner.transport = Arc::new(transport);
+        }
+        return self;
+    }
+
+    /// Sends deliveries on the given background pool, like the one of the server, instead of a
+    /// pool of it's own.
+    pub fn pool(mut self, pool: Arc<tasks::BackgroundPool>) -> Dispatcher {
+        if let Some(inner) = self.configure() {
+            inner.pool = pool;
+        }
+        return self;
+    }
+
+    /// Calls a hook with every delivery moved to the dead letters, like to alert someone.
+    pub fn on_dead_letter<F>(mut self, hook: F) -> Dispatcher
+    where
+        F: Fn(&Delivery) + 'static + Send + Sync,
+    {
+        if let Some(inner) = self.configure() {
+            inner.dead_letter_hook = Some(Box::new(hook));
+        }
+        return self;
+    }
+
+    // the state of the dispatcher if it wasn't cloned yet
+    fn configure(&mut self) -> Option<&mut Inner> {
+        let inner = Arc::get_mut(&mut self.inner);
+        if inner.is_none() {
+            eprintln!("Failed to configure the webhook dispatcher, it was cloned already");
+        }
+        return inner;
+    }
+
+    /// Registers an endpoint.
+    ///
+    /// # Arguments
+    ///
+    /// - `url` - The URL deliveries are posted to.
+    /// - `events` - The types of the events the endpoint gets, all of them if empty.
+    ///
+    /// # Returns
+    ///
+    /// - `String` - The id of the endpoint, to unregister it with.
+    pub fn register(&self, url: &str, events: &[&str]) -> String {
+        let endpoint = Endpoint {
+            id: Uuid::new_v4().to_string(),
+            url: url.to_string(),
+            events: events.iter().map(|event| event.to_string()).collect(),
+        };
+        let id = endpoint.id.clone();
+        lock(&self.inner.endpoints).push(endpoint);
+        return id;
+    }
+
+    /// Unregisters an endpoint, deliveries queued for it are still sent.
+    ///
+    /// # Returns
+    ///
+    /// - `bool` - Whether an endpoint with the id was registered.
+    pub fn unregister(&self, id: &str) -> bool {
+        let mut endpoints = lock(&self.inner.endpoints);
+        let count = endpoints.len();
+        endpoints.retain(|endpoint| endpoint.id != id);
+        return endpoints.len() != count;
+    }
+
+    /// Returns the registered endpoints.
+    pub fn endpoints(&self) -> Vec<Endpoint> {
+        return lock(&self.inner.endpoints).clone();
+    }
+
+    /// Queues a delivery of an event to every endpoint interested in it.
+    ///
+    /// # Arguments
+    ///
+    /// - `event` - The type of the event, like `invoice.paid`.
+    /// - `data` - The data of the event, sent as the `data` member of the body.
+    ///
+    /// # Returns
+    ///
+    /// - `Result<Vec<String>, serde_json::Error>` - The ids of the queued deliveries, or an error
+    ///   if the data couldn't be serialized.
+    pub fn dispatch<T: Serialize>(
+        &self,
+        event: &str,
+        data: &T,
+    ) -> Result<Vec<String>, serde_json::Error> {
+        let body = serde_json::to_string(&Event {
+            event,
+            timestamp: chrono::Utc::now().to_rfc3339_opts(chrono::SecondsFormat::Secs, true),
+            data,
+        })?;
+        let mut ids = vec![];
+        for endpoint in self.endpoints() {
+            if !endpoint.accepts(event) {
+                continue;
+            }
+            let delivery = Delivery {
+                id: format!("msg_{}", Uuid::new_v4().simple()),
+                endpoint_id: endpoint.id,
+                url: endpoint.url,
+                event: event.to_string(),
+                body: body.clone(),
+                attempts: 0,
+                last_error: None,
+            };
+            ids.push(delivery.id.clone());
+            self.enqueue(delivery);
+        }
+        return Ok(ids);
+    }
+
+    /// Returns the deliveries which ran out of attempts, oldest first.
+    pub fn dead_letters(&self) -> Vec<Delivery> {
+        return lock(&self.inner.dead_letters).iter().cloned().collect();
+    }
+
+    /// Queues a dead letter again, with a fresh set of attempts.
+    ///
+    /// # Returns
+    ///
+    /// - `bool` - Whether a dead letter with the id existed.
+    pub fn redeliver(&self, id: &str) -> bool {
+        let mut dead_letters = lock(&self.inner.dead_letters);
+        let index = match dead_letters.iter().position(|delivery| delivery.id == id) {
+            Some(index) => index,
+            None => return false,
+        };
+        let delivery = dead_letters.remove(index);
+        drop(dead_letters);
+        if let Some(mut delivery) = delivery {
+            delivery.attempts = 0;
+            self.enqueue(delivery);
+        }
+        return true;
+    }
+
+    /// Returns the counters of the deliveries.
+    pub fn metrics(&self) -> DeliveryMetrics {
+        let counters = &self.inner.counters;
+        return DeliveryMetrics {
+            queued: counters.queued.load(Ordering::Relaxed),
+            delivered: counters.delivered.load(Ordering::Relaxed),
+            failed_attempts: counters.failed_attempts.load(Ordering::Relaxed),
+            dead_lettered: counters.dead_lettered.load(Ordering::Relaxed),
+            pending: counters.pending.load(Ordering::Relaxed),
+        };
+    }
+
+    /// Waits until no delivery is pending anymore, each delivered or moved to the dead letters.
+    ///
+    /// # Returns
+    ///
+    /// - `bool` - Whether the dispatcher became idle before the timeout.
+    pub fn wait_idle(&self, timeout: Duration) -> bool {
+        let deadline = Instant::now() + timeout;
+        let (ref mutex, ref condvar) = self.inner.idle;
+        let mut guard = lock(mutex);
+        while self.inner.counters.pending.load(Ordering::SeqCst) > 0 {
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            if remaining.is_zero() {
+                return false;
+            }
+            guard = match condvar.wait_timeout(guard, remaining) {
+                Ok((guard, _)) => guard,
+                Err(poisoned) => poisoned.into_inner().0,
+            };
+        }
+        return true;
+    }
+
+    // queues a delivery for it's next attempt right away
+    fn enqueue(&self, delivery: Delivery) {
+        let inner = &self.inner;
+        inner.counters.queued.fetch_add(1, Ordering::Relaxed);
+        inner.counters.pending.fetch_add(1, Ordering::SeqCst);
+        inner.schedule(delivery, Instant::now());
+        let weak = Arc::downgrade(inner);
+        let pool = Arc::clone(&inner.pool);
+        inner.started.call_once(move || {
+            let spawned = thread::Builder::new()
+                .name("browzer-webhooks".to_string())
+                .spawn(move || run_scheduler(weak, pool));
+            if let Err(e) = spawned {
+                eprintln!("Failed to start the webhook scheduler, Error: {}", e);
+            }
+        });
+    }
+}
+
+impl Inner {
+    // schedules the next attempt of a delivery
+    fn schedule(&self, delivery: Delivery, at: Instant) {
+        let mut schedule = lock(&self.schedule);
+        let key = schedule.next;
+        schedule.next += 1;
+        schedule.deliveries.insert(key, delivery);
+        schedule.due.push(Reverse((at, key)));
+        self.wakeup.notify_one();
+    }
+
+    // makes an attempt to send a delivery, scheduling a retry or moving it to the dead letters if
+    // it failed
+    fn attempt(&self, mut delivery: Delivery) {
+        delivery.attempts += 1;
+        let timestamp = chrono::Utc::now().timestamp();
+        let headers = vec![
+            ("Content-Type".to_string(), "application/json".to_string()),
+            ("webhook-id".to_string(), delivery.id.clone()),
+            ("webhook-timestamp".to_string(), timestamp.to_string()),
+            (
+                "webhook-signature".to_string(),
+                sign(&self.secret, &delivery.id, timestamp, &delivery.body),
+            ),
+        ];
+        let error = match self
+            .transport
+            .post(&delivery.url, &headers, delivery.body.as_bytes())
+        {
+            Ok(status) if (200..300).contains(&status) => None,
+            Ok(status) => Some(format!("endpoint answered with status {}", status)),
+            Err(e) => Some(e.to_string()),
+        };
+
+        let error = match error {
+            Some(error) => error,
+            None => {
+                self.counters.delivered.fetch_add(1, Ordering::Relaxed);
+                self.finish();
+                return;
+            }
+        };
+        self.counters
+            .failed_attempts
+            .fetch_add(1, Ordering::Relaxed);
+        delivery.last_error = Some(error);
+        if delivery.attempts < self.retry_policy.max_attempts {
+            let backoff = self.retry_policy.backoff(delivery.attempts);
+            self.schedule(delivery, Instant::now() + backoff);
+            return;
+        }
+
+        self.counters.dead_lettered.fetch_add(1, Ordering::Relaxed);
+        if let Some(ref hook) = self.dead_letter_hook {
+            hook(&delivery);
+        }
+        let mut dead_letters = lock(&self.dead_letters);
+        if dead_letters.len() >= MAX_DEAD_LETTERS {
+            dead_letters.pop_front();
+        }
+        dead_letters.push_back(delivery);
+        drop(dead_letters);
+        self.finish();
+    }
+
+    // marks a delivery as no longer pending, waking up those waiting for the dispatcher to be idle
+    fn finish(&self) {
+        let (ref mutex, ref condvar) = self.idle;
+        let _guard = lock(mutex);
+        self.counters.pending.fetch_sub(1, Ordering::SeqCst);
+        condvar.notify_all();
+    }
+}
+
+// hands the deliveries whose attempt is due to the background pool, until the dispatcher was
+// dropped. The scheduler keeps the pool alive, so it isn't dropped(and joined) by one of it's own
+// threads finishing the last attempt
+fn run_scheduler(inner: Weak<Inner>, pool: Arc<tasks::BackgroundPool>) {
+    loop {
+        let inner = match inner.upgrade() {
+            Some(inner) => inner,
+            None => return,
+        };
+        let mut schedule = lock(&inner.schedule);
+        let now = Instant::now();
+        let mut due = vec![];
+        while let Some(Reverse((at, key))) = schedule.due.peek().copied() {
+            if at > now {
+                break;
+            }
+            schedule.due.pop();
+            if let Some(delivery) = schedule.deliveries.remove(&key) {
+                due.push(delivery);
+            }
+        }
+        if due.is_empty() {
+            let sleep = match schedule.due.peek() {
+                Some(Reverse((at, _))) => at.saturating_duration_since(now),
+                None => MAX_SCHEDULER_SLEEP,
+            };
+            let _ = inner
+                .wakeup
+                .wait_timeout(schedule, sleep.min(MAX_SCHEDULER_SLEEP));
+            continue;
+        }
+        drop(schedule);
+        for delivery in due {
+            let attempt = Arc::clone(&inner);
+            pool.execute(move || attempt.attempt(delivery));
+        }
+    }
+}
+
+// locks a mutex, recovering it if a thread panicked while holding it
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    match mutex.lock() {
+        Ok(guard) => return guard,
+        Err(poisoned) => return poisoned.into_inner(),
+    }
+}
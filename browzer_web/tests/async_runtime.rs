@@ -0,0 +1,246 @@
+//! End-to-end tests for serving on the tokio runtime(`runtime` module, `WebServer::listen_async`,
+//! `WebServer::register_async_route`).
+#![cfg(feature = "tokio")]
+
+mod support;
+
+use browzer_web::{utils::HttpStatusCode, WebServer};
+use std::{
+    io::{Read, Write},
+    net::{SocketAddr, TcpStream},
+    thread,
+    time::{Duration, Instant},
+};
+
+/// Sends a request and returns the raw response.
+fn get(address: SocketAddr, path: &str) -> String {
+    return support::request(address, "GET", path, "");
+}
+
+#[test]
+fn async_and_blocking_handlers_are_served() {
+    let address = support::start_async_server(|server| {
+        server.get("/blocking", |mut c| {
+            return c.send_string(HttpStatusCode::OK, "blocking");
+        });
+        server.get_async("/async/:name", |mut c| async move {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+            let body = format!(
+                "hello {}",
+                c.params.get("name").cloned().unwrap_or_default()
+            );
+            return c.send_string(HttpStatusCode::OK, &body);
+        });
+    });
+
+    assert!(get(address, "/blocking").ends_with("\r\n\r\nblocking"));
+    let response = get(address, "/async/axew");
+    assert!(response.starts_with("HTTP/1.1 200 OK"), "{}", response);
+    assert!(response.ends_with("\r\n\r\nhello axew"), "{}", response);
+}
+
+#[test]
+fn async_handlers_run_on_worker_threads_too() {
+    let address = support::start_server(|server| {
+        server.post_async("/double", |mut c| async move {
+            let value: u64 = c
+                .request
+                .body
+                .clone()
+                .unwrap_or_default()
+                .parse()
+                .unwrap_or(0);
+            let doubled = tokio::spawn(async move { value * 2 }).await.unwrap();
+            return c.send_string(HttpStatusCode::OK, &doubled.to_string());
+        });
+    });
+
+    let response = support::request_with_body(address, "POST", "/double", "", "21");
+    assert!(response.ends_with("\r\n\r\n42"));
+}
+
+#[test]
+fn idle_connections_dont_hold_worker_threads() {
+    let address = support::start_async_server(|server| {
+        server.keep_alive_timeout = Some(Duration::from_secs(10));
+        server.get("/", |mut c| c.send_string(HttpStatusCode::OK, "ok"));
+    });
+
+    // far more idle keep-alive connections than the server has worker threads
+    let mut idle = Vec::new();
+    for _ in 0..16 {
+        let mut stream = TcpStream::connect(address).unwrap();
+        stream
+            .write_all(b"GET / HTTP/1.1\r\nHost: localhost\r\n\r\n")
+            .unwrap();
+        stream
+            .set_read_timeout(Some(Duration::from_secs(10)))
+            .unwrap();
+        let mut response = [0; 1024];
+        let read = stream.read(&mut response).unwrap();
+        assert!(response[..read].ends_with(b"\r\n\r\nok"));
+        idle.push(stream);
+    }
+
+    let started = Instant::now();
+    assert!(get(address, "/").ends_with("ok"));
+    assert!(started.elapsed() < Duration::from_secs(5));
+
+    // the idle connections are still open for their next request
+    for stream in idle.iter_mut() {
+        stream
+            .write_all(b"GET / HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n")
+            .unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+        assert!(response.ends_with("ok"), "{}", response);
+    }
+}
+
+#[test]
+fn listen_async_returns_once_stopped() {
+    let mut server = WebServer::new("127.0.0.1:0".to_string(), 2);
+    server.hide_banner = true;
+    server.get("/", |mut c| c.send_string(HttpStatusCode::OK, "ok"));
+    let address = server.local_addr().unwrap();
+    let shutdown = server.shutdown_handle();
+    let listening = thread::spawn(move || {
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        return runtime.block_on(server.listen_async());
+    });
+
+    assert!(get(address, "/").ends_with("ok"));
+    shutdown.shutdown();
+    listening.join().unwrap().unwrap();
+    // nothing accepts connections anymore
+    assert!(support::exchange(address, b"GET / HTTP/1.1\r\n\r\n")
+        .map(|response| response.is_empty())
+        .unwrap_or(true));
+}
+
+#[test]
+fn connections_behind_the_proxy_protocol_are_served() {
+    let address = support::start_async_server(|server| {
+        server.proxy_protocol = true;
+        server.get("/client", |mut c| {
+            let remote_addr = c.request.remote_addr.map(|a| a.to_string());
+            return c.send_string(HttpStatusCode::OK, &remote_addr.unwrap_or_default());
+        });
+    });
+
+    let response = support::exchange(
+        address,
+        b"PROXY TCP4 203.0.113.7 10.0.0.1 51000 80\r\nGET /client HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n",
+    )
+    .unwrap();
+    let response = String::from_utf8(response).unwrap();
+    assert!(
+        response.ends_with("\r\n\r\n203.0.113.7:51000"),
+        "{}",
+        response
+    );
+
+    // a connection without the header is dropped
+    let response = support::exchange(address, b"GET /client HTTP/1.1\r\n\r\n").unwrap_or_default();
+    assert!(response.is_empty());
+}
+
+#[test]
+fn waiting_async_handlers_dont_hold_threads() {
+    let address = support::start_async_server(|server| {
+        server.get_async("/sleep", |mut c| async move {
+            tokio::time::sleep(Duration::from_millis(300)).await;
+            return c.send_string(HttpStatusCode::OK, "awake");
+        });
+    });
+
+    // far more sleeping handlers than the runtime has threads of it's own, all of them sleeping
+    // at the same time
+    let started = Instant::now();
+    let requests: Vec<_> = (0..32)
+        .map(|_| thread::spawn(move || get(address, "/sleep")))
+        .collect();
+    for request in requests {
+        assert!(request.join().unwrap().ends_with("awake"));
+    }
+    assert!(
+        started.elapsed() < Duration::from_secs(3),
+        "{:?}",
+        started.elapsed()
+    );
+}
+
+#[test]
+fn streamed_responses_keep_the_connection_open() {
+    let address = support::start_async_server(|server| {
+        server.keep_alive_timeout = Some(Duration::from_secs(10));
+        server.get("/stream", |mut c| {
+            return c.send_stream(HttpStatusCode::OK, |writer| {
+                writer.write_all(b"hello ")?;
+                writer.flush()?;
+                return writer.write_all(b"stream");
+            });
+        });
+        server.get("/", |mut c| c.send_string(HttpStatusCode::OK, "ok"));
+    });
+
+    let mut stream = TcpStream::connect(address).unwrap();
+    stream
+        .set_read_timeout(Some(Duration::from_secs(10)))
+        .unwrap();
+    stream
+        .write_all(b"GET /stream HTTP/1.1\r\nHost: localhost\r\n\r\nGET / HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n")
+        .unwrap();
+    let mut response = String::new();
+    stream.read_to_string(&mut response).unwrap();
+    assert!(response.contains("hello "), "{}", response);
+    assert!(response.contains("stream"), "{}", response);
+    assert!(response.ends_with("\r\n\r\nok"), "{}", response);
+}
+
+#[test]
+fn memory_budgets_apply_on_the_runtime() {
+    let address = support::start_async_server(|server| {
+        server.set_memory_budget(1024);
+        server.post("/echo", |mut c| {
+            let body = c.request.body.clone().unwrap_or_default();
+            return c.send_string(HttpStatusCode::OK, &body);
+        });
+    });
+
+    let response = support::request_with_body(address, "POST", "/echo", "", &"a".repeat(4096));
+    assert!(response.starts_with("HTTP/1.1 413"), "{}", response);
+}
+
+#[test]
+fn streamed_bodies_are_passed_to_handlers() {
+    let address = support::start_async_server(|server| {
+        server.keep_alive_timeout = Some(Duration::from_secs(10));
+        server
+            .post("/upload", |mut c| {
+                let mut body = match c.request.take_body_stream() {
+                    Some(body) => body,
+                    None => return c.send_string(HttpStatusCode::OK, "buffered"),
+                };
+                let mut received = Vec::new();
+                body.read_to_end(&mut received).unwrap();
+                return c.send_string(HttpStatusCode::OK, &received.len().to_string());
+            })
+            .stream_body();
+        server.get("/", |mut c| c.send_string(HttpStatusCode::OK, "ok"));
+    });
+
+    let body = "a".repeat(200 * 1024);
+    let raw = format!(
+        "POST /upload HTTP/1.1\r\nHost: localhost\r\nContent-Length: {}\r\n\r\n{}GET / HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n",
+        body.len(),
+        body
+    );
+    let response = String::from_utf8(support::exchange(address, raw.as_bytes()).unwrap()).unwrap();
+    assert!(
+        response.contains("\r\n\r\n204800HTTP/1.1 200 OK"),
+        "{}",
+        response
+    );
+    assert!(response.ends_with("\r\n\r\nok"), "{}", response);
+}
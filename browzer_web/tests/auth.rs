@@ -0,0 +1,72 @@
+//! End-to-end tests for `Basic` and `Bearer` authentication (`auth::BasicAuth`,
+//! `auth::BearerAuth`).
+
+mod support;
+
+use browzer_web::{
+    auth::{BasicAuth, BearerAuth},
+    utils::HttpStatusCode,
+    WebServer,
+};
+use std::net::SocketAddr;
+
+fn app(server: &mut WebServer) {
+    let basic = BasicAuth::new("admin", |username, password| {
+        return username == "admin" && password == "hunter2";
+    });
+    let bearer = BearerAuth::new("api", |token| match token {
+        "token-of-axew" => Some("axew".to_string()),
+        _ => None,
+    });
+    let whoami = |mut c: browzer_web::context::Context| {
+        let identity = c.identity().cloned().unwrap();
+        return c.send_string(
+            HttpStatusCode::OK,
+            &format!("{} {}", identity.scheme, identity.subject),
+        );
+    };
+    server.get("/admin", basic.wrap(whoami));
+    server.get("/api/me", bearer.wrap(whoami));
+}
+
+/// Sends a request with an `Authorization` header, returning the response.
+fn get(address: SocketAddr, path: &str, authorization: Option<&str>) -> String {
+    let authorization = authorization
+        .map(|value| format!("Authorization: {}\r\n", value))
+        .unwrap_or_default();
+    return support::request(address, "GET", path, &authorization);
+}
+
+#[test]
+fn basic_auth_challenges_and_identifies_clients() {
+    let address = support::start_server(app);
+
+    let response = get(address, "/admin", None);
+    assert!(response.starts_with("HTTP/1.1 401 Unauthorized"));
+    assert!(response.contains("WWW-Authenticate: Basic realm=\"admin\", charset=\"UTF-8\""));
+
+    // base64 of "admin:wrong"
+    let response = get(address, "/admin", Some("Basic YWRtaW46d3Jvbmc="));
+    assert!(response.starts_with("HTTP/1.1 401 Unauthorized"));
+
+    // base64 of "admin:hunter2"
+    let response = get(address, "/admin", Some("basic YWRtaW46aHVudGVyMg=="));
+    assert!(response.starts_with("HTTP/1.1 200 OK"));
+    assert!(response.ends_with("\r\n\r\nBasic admin"));
+}
+
+#[test]
+fn bearer_auth_rejects_invalid_tokens() {
+    let address = support::start_server(app);
+
+    let response = get(address, "/api/me", None);
+    assert!(response.starts_with("HTTP/1.1 401 Unauthorized"));
+    assert!(response.contains("WWW-Authenticate: Bearer realm=\"api\"\r\n"));
+
+    let response = get(address, "/api/me", Some("Bearer forged"));
+    assert!(response.starts_with("HTTP/1.1 401 Unauthorized"));
+    assert!(response.contains("WWW-Authenticate: Bearer realm=\"api\", error=\"invalid_token\""));
+
+    let response = get(address, "/api/me", Some("Bearer token-of-axew"));
+    assert!(response.ends_with("\r\n\r\nBearer axew"));
+}
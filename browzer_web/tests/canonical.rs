@@ -0,0 +1,93 @@
+//! End-to-end tests for redirects to the canonical origin(`WebServer::canonical_origin`).
+
+mod support;
+
+use browzer_web::{canonical::CanonicalOrigin, utils::HttpStatusCode, WebServer};
+use std::net::SocketAddr;
+
+fn app(server: &mut WebServer) {
+    server.canonical_origin(
+        CanonicalOrigin::parse("https://example.com")
+            .unwrap()
+            .trust_forwarded_proto(true),
+    );
+    server.get("/docs", |mut c| {
+        return c.send_string(HttpStatusCode::OK, "docs");
+    });
+}
+
+/// Sends a request with extra header lines, returning the status line and the `Location` header.
+fn request(address: SocketAddr, request_line: &str, headers: &str) -> (String, Option<String>) {
+    let raw = format!(
+        "{}\r\n{}Content-Length: 0\r\nConnection: close\r\n\r\n",
+        request_line, headers
+    );
+    let response = support::exchange(address, raw.as_bytes()).unwrap();
+    let response = String::from_utf8(response).unwrap();
+    let (head, _) = response.split_once("\r\n\r\n").unwrap();
+    let mut lines = head.lines();
+    let status_line = lines.next().unwrap().to_string();
+    let location = lines
+        .find_map(|line| line.strip_prefix("Location: "))
+        .map(|location| location.to_string());
+    return (status_line, location);
+}
+
+#[test]
+fn redirects_other_hosts_and_schemes() {
+    let address = support::start_server(app);
+    let cases = [
+        (
+            "GET /docs?page=2 HTTP/1.1",
+            "Host: www.example.com\r\nX-Forwarded-Proto: https\r\n",
+            "HTTP/1.1 301 Moved Permanently",
+            "https://example.com/docs?page=2",
+        ),
+        (
+            "GET / HTTP/1.1",
+            "Host: example.com:80\r\nX-Forwarded-Proto: http\r\n",
+            "HTTP/1.1 301 Moved Permanently",
+            "https://example.com/",
+        ),
+        (
+            "POST /docs HTTP/1.1",
+            "Host: www.example.com\r\nX-Forwarded-Proto: https\r\n",
+            "HTTP/1.1 308 Permanent Redirect",
+            "https://example.com/docs",
+        ),
+        // without the header the scheme of the server itself(plain HTTP) is used
+        (
+            "GET /docs HTTP/1.1",
+            "Host: example.com\r\n",
+            "HTTP/1.1 301 Moved Permanently",
+            "https://example.com/docs",
+        ),
+    ];
+    for (request_line, headers, status_line, location) in cases {
+        assert_eq!(
+            request(address, request_line, headers),
+            (status_line.to_string(), Some(location.to_string())),
+            "{} with {:?}",
+            request_line,
+            headers
+        );
+    }
+}
+
+#[test]
+fn serves_the_canonical_origin() {
+    let address = support::start_server(app);
+    assert_eq!(
+        request(
+            address,
+            "GET /docs HTTP/1.1",
+            "Host: Example.com\r\nX-Forwarded-Proto: https\r\n"
+        ),
+        ("HTTP/1.1 200 OK".to_string(), None)
+    );
+    // requests without a host can't be redirected anywhere
+    assert_eq!(
+        request(address, "GET /docs HTTP/1.0", ""),
+        ("HTTP/1.0 200 OK".to_string(), None)
+    );
+}
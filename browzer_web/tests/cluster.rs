@@ -0,0 +1,168 @@
+//! Tests for clusters of server instances(`cluster::Cluster`): discovering peers through a seed,
+//! sharing rate limit buckets and session invalidations, and ignoring foreign, replayed and stalled
+//! messages.
+
+use browzer_web::{
+    cluster::Cluster,
+    rate_limit::{Quota, RateLimitDecision, RateLimitStore},
+    sessions::{MemorySessionStore, SessionData, SessionStore},
+};
+use std::{
+    io::{BufRead, BufReader, Write},
+    net::{TcpListener, TcpStream},
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+    thread,
+    time::{Duration, Instant},
+};
+
+const SECRET: &[u8] = b"shared by all instances";
+
+/// Starts a cluster member on an ephemeral port of the loopback interface, gossiping often.
+fn member(secret: &[u8]) -> Cluster {
+    let cluster = Cluster::bind("127.0.0.1:0", secret).unwrap();
+    cluster.set_gossip_interval(Duration::from_millis(20));
+    return cluster;
+}
+
+/// Waits for a condition to become true, failing the test after a few seconds.
+fn wait_for<F: Fn() -> bool>(condition: F) {
+    let deadline = Instant::now() + Duration::from_secs(5);
+    while !condition() {
+        assert!(Instant::now() < deadline, "timed out");
+        thread::sleep(Duration::from_millis(10));
+    }
+}
+
+#[test]
+fn members_discover_each_other_through_seeds() {
+    let a = member(SECRET);
+    let b = member(SECRET);
+    let c = member(SECRET);
+    b.join(&a.local_addr().to_string());
+    c.join(&b.local_addr().to_string());
+
+    // a only learns about c from b
+    wait_for(|| a.peers().len() == 2 && b.peers().len() == 2 && c.peers().len() == 2);
+    assert!(a.peers().contains(&c.local_addr().to_string()));
+    assert!(!a.peers().contains(&a.local_addr().to_string()));
+}
+
+#[test]
+fn rate_limit_buckets_are_shared() {
+    let a = member(SECRET);
+    let b = member(SECRET);
+    b.join(&a.local_addr().to_string());
+    wait_for(|| a.peers().len() == 1);
+
+    let quota = Quota {
+        requests: 3,
+        window: Duration::from_secs(3600),
+    };
+    let (store_a, store_b) = (a.rate_limit_store(), b.rate_limit_store());
+    assert_eq!(
+        store_a.acquire("10.0.0.1", &quota),
+        RateLimitDecision::Allowed { remaining: 2 }
+    );
+    assert_eq!(
+        store_a.acquire("10.0.0.1", &quota),
+        RateLimitDecision::Allowed { remaining: 1 }
+    );
+    // give the hits a few rounds to reach b
+    thread::sleep(Duration::from_millis(300));
+    assert_eq!(
+        store_b.acquire("10.0.0.1", &quota),
+        RateLimitDecision::Allowed { remaining: 0 }
+    );
+    assert!(matches!(
+        store_b.acquire("10.0.0.1", &quota),
+        RateLimitDecision::Limited { .. }
+    ));
+    // other clients aren't affected
+    assert_eq!(
+        store_b.acquire("10.0.0.2", &quota),
+        RateLimitDecision::Allowed { remaining: 2 }
+    );
+}
+
+#[test]
+fn removed_sessions_are_invalidated_on_peers() {
+    let a = member(SECRET);
+    let b = member(SECRET);
+    b.join(&a.local_addr().to_string());
+    wait_for(|| a.peers().len() == 1);
+
+    let (store_a, store_b) = (
+        a.session_store(MemorySessionStore::new()),
+        b.session_store(MemorySessionStore::new()),
+    );
+    let data = SessionData::from([("user".to_string(), "axew".to_string())]);
+    for store in [&store_a, &store_b] {
+        store.save("s1", data.clone(), Duration::from_secs(60));
+        store.save("s2", data.clone(), Duration::from_secs(60));
+    }
+
+    store_a.remove("s1");
+    wait_for(|| store_b.load("s1").is_none());
+    assert_eq!(store_b.load("s2"), Some(data));
+}
+
+#[test]
+fn messages_signed_with_another_secret_are_ignored() {
+    let a = member(SECRET);
+    let intruder = member(b"guessed");
+    intruder.join(&a.local_addr().to_string());
+
+    // give the intruder a few rounds to announce itself
+    thread::sleep(Duration::from_millis(300));
+    assert!(a.peers().is_empty());
+    assert_eq!(intruder.peers(), vec![a.local_addr().to_string()]);
+}
+
+#[test]
+fn replayed_messages_are_ignored() {
+    // captures a message invalidating a session, posing as a peer
+    let a = member(SECRET);
+    let eavesdropper = TcpListener::bind("127.0.0.1:0").unwrap();
+    a.join(&eavesdropper.local_addr().unwrap().to_string());
+    a.remove_session("s1");
+    let line = loop {
+        let mut line = String::new();
+        let (stream, _) = eavesdropper.accept().unwrap();
+        BufReader::new(stream).read_line(&mut line).unwrap();
+        if line.contains("\"s1\"") {
+            break line;
+        }
+    };
+
+    let b = member(SECRET);
+    let invalidations = Arc::new(AtomicUsize::new(0));
+    let hook_invalidations = Arc::clone(&invalidations);
+    b.on_session_removed(move |_| {
+        hook_invalidations.fetch_add(1, Ordering::SeqCst);
+    });
+    for _ in 0..3 {
+        let mut stream = TcpStream::connect(b.local_addr()).unwrap();
+        stream.write_all(line.as_bytes()).unwrap();
+    }
+    wait_for(|| invalidations.load(Ordering::SeqCst) > 0);
+    thread::sleep(Duration::from_millis(200));
+    assert_eq!(invalidations.load(Ordering::SeqCst), 1);
+}
+
+#[test]
+fn a_stalled_peer_does_not_hold_up_the_others() {
+    let a = member(SECRET);
+    // connections which never send their message
+    let _stalled: Vec<TcpStream> = (0..4)
+        .map(|_| TcpStream::connect(a.local_addr()).unwrap())
+        .collect();
+
+    let b = member(SECRET);
+    let started = Instant::now();
+    b.join(&a.local_addr().to_string());
+    wait_for(|| a.peers() == vec![b.local_addr().to_string()]);
+    assert!(started.elapsed() < Duration::from_secs(1));
+}
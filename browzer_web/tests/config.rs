@@ -0,0 +1,53 @@
+//! Tests for loading the settings of a server from configuration values(`WebServer::configure`).
+
+use browzer_web::{error::ConfigValueError, WebServer};
+use std::time::Duration;
+
+fn server() -> WebServer {
+    return WebServer::new("127.0.0.1:0".to_string(), 1);
+}
+
+#[test]
+fn parses_sizes_and_durations() {
+    let mut server = server();
+    server.configure("body_limit", "1 KiB").unwrap();
+    server.configure("MEMORY_BUDGET", "2MB").unwrap();
+    server.configure("request_timeout", "500ms").unwrap();
+    server.configure("drain_timeout", "1h").unwrap();
+
+    assert_eq!(server.body_limit, Some(1024));
+    assert_eq!(server.memory_budget, Some(2_000_000));
+    assert_eq!(server.request_timeout, Some(Duration::from_millis(500)));
+    assert_eq!(server.drain_timeout, Duration::from_secs(60 * 60));
+}
+
+#[test]
+fn disables_optional_settings_with_none() {
+    let mut server = server();
+    server.configure("keep_alive_timeout", "none").unwrap();
+    server.configure("body_limit", "None").unwrap();
+
+    assert_eq!(server.keep_alive_timeout, None);
+    assert_eq!(server.body_limit, None);
+    assert!(server.configure("drain_timeout", "none").is_err());
+}
+
+#[test]
+fn rejects_unknown_settings_and_invalid_values() {
+    let mut server = server();
+    let body_limit = server.body_limit;
+
+    assert_eq!(
+        server.configure("body_limti", "1MB"),
+        Err(ConfigValueError::UnknownSetting("body_limti".to_string()))
+    );
+    assert_eq!(
+        server.configure("body_limit", "lots"),
+        Err(ConfigValueError::InvalidSize("lots".to_string()))
+    );
+    assert_eq!(
+        server.configure("request_timeout", "soon"),
+        Err(ConfigValueError::InvalidDuration("soon".to_string()))
+    );
+    assert_eq!(server.body_limit, body_limit);
+}
@@ -0,0 +1,69 @@
+//! End-to-end tests for the state shared by the requests of a keep-alive connection
+//! (`Context::connection`).
+
+mod support;
+
+use browzer_web::utils::HttpStatusCode;
+use std::{
+    io::{BufRead, BufReader, Read, Write},
+    net::{SocketAddr, TcpStream},
+};
+
+#[derive(Clone)]
+struct Visits(Vec<String>);
+
+/// Sends a keep-alive GET request on an open connection and reads the response body.
+fn get(reader: &mut BufReader<TcpStream>, path: &str) -> String {
+    let raw = format!("GET {} HTTP/1.1\r\nHost: localhost\r\n\r\n", path);
+    reader.get_mut().write_all(raw.as_bytes()).unwrap();
+    let mut content_length = 0;
+    loop {
+        let mut line = String::new();
+        reader.read_line(&mut line).unwrap();
+        if line == "\r\n" {
+            break;
+        }
+        if let Some((name, value)) = line.split_once(": ") {
+            if name.eq_ignore_ascii_case("Content-Length") {
+                content_length = value.trim().parse().unwrap();
+            }
+        }
+    }
+    let mut body = vec![0; content_length];
+    reader.read_exact(&mut body).unwrap();
+    return String::from_utf8(body).unwrap();
+}
+
+fn connect(address: SocketAddr) -> BufReader<TcpStream> {
+    return BufReader::new(TcpStream::connect(address).unwrap());
+}
+
+#[test]
+fn values_survive_across_requests_of_a_connection() {
+    let address = support::start_server(|server| {
+        server.get("/:page", |mut c| {
+            let connection = c.connection().clone();
+            let mut visits = connection.get::<Visits>().unwrap_or(Visits(vec![]));
+            visits
+                .0
+                .push(c.params.get("page").cloned().unwrap_or_default());
+            connection.set(visits.clone());
+            let body = format!(
+                "{} {} {}",
+                connection.request_count(),
+                visits.0.join(","),
+                connection.tls().is_some()
+            );
+            return c.send_string(HttpStatusCode::OK, &body);
+        });
+    });
+
+    let mut first = connect(address);
+    assert_eq!(get(&mut first, "/a"), "1 a false");
+    assert_eq!(get(&mut first, "/b"), "2 a,b false");
+
+    // another connection starts from scratch
+    let mut second = connect(address);
+    assert_eq!(get(&mut second, "/c"), "1 c false");
+    assert_eq!(get(&mut first, "/d"), "3 a,b,d false");
+}
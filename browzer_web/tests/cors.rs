@@ -0,0 +1,219 @@
+//! End-to-end tests for cross-origin resource sharing(`WebServer::cors`), sending the preflight and
+//! actual requests a browser would send.
+
+mod support;
+
+use browzer_web::{cors::Cors, utils::HttpMethod, utils::HttpStatusCode, WebServer};
+use std::{collections::HashMap, net::SocketAddr, time::Duration};
+
+fn app(server: &mut WebServer) {
+    server.cors(
+        Cors::new()
+            .allow_origin("https://app.example.com")
+            .allow_methods(&[HttpMethod::GET, HttpMethod::PUT])
+            .allow_headers(&["Content-Type", "Authorization"])
+            .expose_headers(&["X-Total-Count"])
+            .allow_credentials(true)
+            .max_age(Duration::from_secs(600)),
+    );
+    server.get("/items", |mut c| {
+        return c.send_string(HttpStatusCode::OK, "items");
+    });
+    server.put("/items", |mut c| {
+        return c.send_string(HttpStatusCode::OK, "updated");
+    });
+}
+
+/// Sends a request with extra headers, returning the status line, the headers(with lowercase
+/// names) and the body of the response.
+fn request(
+    address: SocketAddr,
+    method: &str,
+    path: &str,
+    headers: &[(&str, &str)],
+) -> (String, HashMap<String, String>, String) {
+    let headers: String = headers
+        .iter()
+        .map(|(name, value)| format!("{}: {}\r\n", name, value))
+        .collect();
+    let response = support::request(address, method, path, &headers);
+    let (head, body) = response.split_once("\r\n\r\n").unwrap();
+    let mut lines = head.lines();
+    let status_line = lines.next().unwrap().to_string();
+    let headers = lines
+        .filter_map(|line| line.split_once(": "))
+        .map(|(name, value)| (name.to_ascii_lowercase(), value.to_string()))
+        .collect();
+    return (status_line, headers, body.to_string());
+}
+
+#[test]
+fn allowed_preflight_is_answered() {
+    let address = support::start_server(app);
+    let (status_line, headers, body) = request(
+        address,
+        "OPTIONS",
+        "/items",
+        &[
+            ("Origin", "https://app.example.com"),
+            ("Access-Control-Request-Method", "PUT"),
+            (
+                "Access-Control-Request-Headers",
+                "content-type, authorization",
+            ),
+        ],
+    );
+    assert_eq!(status_line, "HTTP/1.1 204 No Content");
+    assert_eq!(body, "");
+    assert_eq!(
+        headers["access-control-allow-origin"],
+        "https://app.example.com"
+    );
+    assert_eq!(headers["access-control-allow-methods"], "GET, PUT");
+    assert_eq!(
+        headers["access-control-allow-headers"],
+        "content-type, authorization"
+    );
+    assert_eq!(headers["access-control-allow-credentials"], "true");
+    assert_eq!(headers["access-control-max-age"], "600");
+    assert!(headers["vary"].contains("Origin"));
+}
+
+#[test]
+fn disallowed_preflights_get_no_cors_headers() {
+    let address = support::start_server(app);
+    let preflights: [&[(&str, &str)]; 3] = [
+        &[
+            ("Origin", "https://evil.example.com"),
+            ("Access-Control-Request-Method", "PUT"),
+        ],
+        &[
+            ("Origin", "https://app.example.com"),
+            ("Access-Control-Request-Method", "DELETE"),
+        ],
+        &[
+            ("Origin", "https://app.example.com"),
+            ("Access-Control-Request-Method", "PUT"),
+            ("Access-Control-Request-Headers", "X-Secret"),
+        ],
+    ];
+    for preflight in preflights {
+        let (status_line, headers, _) = request(address, "OPTIONS", "/items", preflight);
+        assert_eq!(status_line, "HTTP/1.1 204 No Content");
+        assert!(
+            !headers
+                .keys()
+                .any(|name| name.starts_with("access-control-")),
+            "{:?} got {:?}",
+            preflight,
+            headers
+        );
+    }
+}
+
+#[test]
+fn actual_requests_get_cors_headers() {
+    let address = support::start_server(app);
+    let (status_line, headers, body) = request(
+        address,
+        "GET",
+        "/items",
+        &[("Origin", "https://app.example.com")],
+    );
+    assert_eq!(status_line, "HTTP/1.1 200 OK");
+    assert_eq!(body, "items");
+    assert_eq!(
+        headers["access-control-allow-origin"],
+        "https://app.example.com"
+    );
+    assert_eq!(headers["access-control-allow-credentials"], "true");
+    assert_eq!(headers["access-control-expose-headers"], "X-Total-Count");
+    assert_eq!(headers["vary"], "Origin");
+
+    // framework errors are readable cross-origin too
+    let (status_line, headers, _) = request(
+        address,
+        "GET",
+        "/missing",
+        &[("Origin", "https://app.example.com")],
+    );
+    assert_eq!(status_line, "HTTP/1.1 404 Not Found");
+    assert!(headers.contains_key("access-control-allow-origin"));
+
+    let (_, headers, _) = request(
+        address,
+        "GET",
+        "/items",
+        &[("Origin", "https://evil.example.com")],
+    );
+    assert!(!headers.contains_key("access-control-allow-origin"));
+    assert_eq!(headers["vary"], "Origin");
+}
+
+#[test]
+fn plain_options_requests_still_list_allowed_methods() {
+    let address = support::start_server(app);
+    let (status_line, headers, _) = request(address, "OPTIONS", "/items", &[]);
+    assert_eq!(status_line, "HTTP/1.1 204 No Content");
+    assert_eq!(headers["allow"], "GET, HEAD, PUT, OPTIONS");
+    assert!(!headers.contains_key("access-control-allow-origin"));
+}
+
+#[test]
+fn routes_and_groups_override_the_server_policy() {
+    let address = support::start_server(|server| {
+        app(server);
+        server
+            .get("/public", |mut c| {
+                return c.send_string(HttpStatusCode::OK, "public");
+            })
+            .cors(Cors::new().allow_any_origin());
+        let mut partners = server.group("/partners");
+        partners.cors(
+            Cors::new()
+                .allow_origin_fn(|origin| origin.ends_with(".partner.example.com"))
+                .allow_methods(&[HttpMethod::POST]),
+        );
+        partners.post("/orders", |mut c| {
+            return c.send_string(HttpStatusCode::Created, "created");
+        });
+    });
+
+    let origin = [("Origin", "https://elsewhere.example.com")];
+    let (_, headers, _) = request(address, "GET", "/public", &origin);
+    assert_eq!(headers["access-control-allow-origin"], "*");
+    assert!(!headers.contains_key("vary"));
+    // the server's policy still applies to the other routes
+    let (_, headers, _) = request(address, "GET", "/items", &origin);
+    assert!(!headers.contains_key("access-control-allow-origin"));
+
+    // preflights use the policy of the route of the requested method
+    let (status_line, headers, _) = request(
+        address,
+        "OPTIONS",
+        "/partners/orders",
+        &[
+            ("Origin", "https://acme.partner.example.com"),
+            ("Access-Control-Request-Method", "POST"),
+        ],
+    );
+    assert_eq!(status_line, "HTTP/1.1 204 No Content");
+    assert_eq!(
+        headers["access-control-allow-origin"],
+        "https://acme.partner.example.com"
+    );
+    assert_eq!(headers["access-control-allow-methods"], "POST");
+
+    let (status_line, headers, _) = request(
+        address,
+        "POST",
+        "/partners/orders",
+        &[
+            ("Origin", "https://app.example.com"),
+            ("Content-Length", "0"),
+        ],
+    );
+    assert_eq!(status_line, "HTTP/1.1 201 Created");
+    assert!(!headers.contains_key("access-control-allow-origin"));
+    assert_eq!(headers["vary"], "Origin");
+}
@@ -0,0 +1,77 @@
+//! End-to-end tests for the JSON reflection of requests(`echo` module, `WebServer::debug_echo`).
+
+mod support;
+
+use browzer_web::echo::DebugEcho;
+
+/// Returns the status line and the parsed JSON body of an echo response.
+fn echo(response: &str) -> (String, serde_json::Value) {
+    let (head, body) = response.split_once("\r\n\r\n").unwrap();
+    let status_line = head.lines().next().unwrap().to_string();
+    assert!(head.contains("Content-Type: application/json"), "{}", head);
+    return (status_line, serde_json::from_str(body).unwrap());
+}
+
+#[test]
+fn requests_are_reflected_as_parsed() {
+    let address = support::start_server(|server| {
+        server.debug_echo("/debug/echo");
+    });
+
+    let (status_line, echo) = echo(&support::request_with_body(
+        address,
+        "POST",
+        "/debug/echo/a%20b/c?tag=rust&tag=web&q=hello+world",
+        "X-Forwarded-For: 203.0.113.7\r\nCookie: theme=dark\r\n",
+        "hello",
+    ));
+    assert_eq!(status_line, "HTTP/1.1 200 OK");
+    assert_eq!(echo["method"], "POST");
+    assert_eq!(
+        echo["path"],
+        "/debug/echo/a%20b/c?tag=rust&tag=web&q=hello+world"
+    );
+    assert_eq!(echo["params"]["rest"], "a b/c");
+    assert_eq!(echo["query"]["tag"], serde_json::json!(["rust", "web"]));
+    assert_eq!(echo["query"]["q"], serde_json::json!(["hello world"]));
+    assert_eq!(echo["headers"]["X-Forwarded-For"], "203.0.113.7");
+    assert_eq!(echo["cookies"]["theme"], "dark");
+    // no proxy is trusted, so the header doesn't change the client address
+    assert_eq!(echo["client_ip"], "127.0.0.1");
+    assert_eq!(echo["tls"], false);
+    assert_eq!(
+        echo["body"],
+        serde_json::json!({"length": 5, "preview": "hello", "truncated": false})
+    );
+}
+
+#[test]
+fn the_echo_path_itself_answers_any_method() {
+    let address = support::start_server(|server| {
+        server.debug_echo("/debug/echo");
+    });
+
+    let (status_line, echo) = echo(&support::request(address, "DELETE", "/debug/echo", ""));
+    assert_eq!(status_line, "HTTP/1.1 200 OK");
+    assert_eq!(echo["method"], "DELETE");
+    assert_eq!(echo["params"], serde_json::json!({}));
+}
+
+#[test]
+fn body_previews_are_truncated() {
+    let address = support::start_server(|server| {
+        server.serve_debug_echo("/echo", DebugEcho::new().body_preview(4));
+    });
+
+    let (_, echo) = echo(&support::request_with_body(
+        address,
+        "PUT",
+        "/echo",
+        "",
+        "hello world",
+    ));
+    assert_eq!(
+        echo["body"],
+        serde_json::json!({"length": 11, "preview": "hell", "truncated": true})
+    );
+}
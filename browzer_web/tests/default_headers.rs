@@ -0,0 +1,156 @@
+//! End-to-end tests for the default response headers of servers, routes and route groups
+//! (`WebServer::default_header`, `RouteBuilder::header`, `RouteGroup::header`).
+
+mod support;
+
+use browzer_web::utils::HttpStatusCode;
+use std::{net::SocketAddr, thread, time::Duration};
+
+/// Sends a GET request and returns the raw response.
+fn get(address: SocketAddr, path: &str) -> String {
+    return support::request(address, "GET", path, "");
+}
+
+fn start() -> SocketAddr {
+    return support::start_server(|server| {
+        server.default_header("X-Frame-Options", "DENY");
+        server.default_header("Cache-Control", "no-store");
+        server.get("/", |mut c| c.send_string(HttpStatusCode::OK, "home"));
+        server.get("/framed", |mut c| {
+            c.response
+                .headers
+                .insert("x-frame-options".to_string(), "SAMEORIGIN".to_string());
+            return c.send_string(HttpStatusCode::OK, "framed");
+        });
+        server
+            .get("/logo", |mut c| c.send_string(HttpStatusCode::OK, "logo"))
+            .header("Cache-Control", "max-age=60");
+
+        let mut assets = server.group("/assets");
+        assets.header("Cache-Control", "public, max-age=86400");
+        assets.get("/app.js", |mut c| c.send_string(HttpStatusCode::OK, "js"));
+        assets
+            .get("/index.html", |mut c| {
+                c.send_string(HttpStatusCode::OK, "html")
+            })
+            .header("Cache-Control", "no-cache");
+    });
+}
+
+#[test]
+fn defaults_are_added_to_every_response() {
+    let address = start();
+
+    let response = get(address, "/");
+    assert!(
+        response.contains("X-Frame-Options: DENY\r\n"),
+        "{}",
+        response
+    );
+    assert!(
+        response.contains("Cache-Control: no-store\r\n"),
+        "{}",
+        response
+    );
+
+    // framework-generated errors get them too
+    let response = get(address, "/missing");
+    assert!(response.starts_with("HTTP/1.1 404"), "{}", response);
+    assert!(
+        response.contains("X-Frame-Options: DENY\r\n"),
+        "{}",
+        response
+    );
+}
+
+#[test]
+fn headers_set_by_the_handler_win() {
+    let response = get(start(), "/framed");
+    assert!(
+        response.contains("x-frame-options: SAMEORIGIN\r\n"),
+        "{}",
+        response
+    );
+    assert!(!response.contains("DENY"), "{}", response);
+}
+
+#[test]
+fn route_and_group_headers_take_precedence_over_the_server_ones() {
+    let address = start();
+
+    let response = get(address, "/logo");
+    assert!(
+        response.contains("Cache-Control: max-age=60\r\n"),
+        "{}",
+        response
+    );
+    assert!(!response.contains("no-store"), "{}", response);
+
+    let response = get(address, "/assets/app.js");
+    assert!(
+        response.contains("Cache-Control: public, max-age=86400\r\n"),
+        "{}",
+        response
+    );
+    assert!(
+        response.contains("X-Frame-Options: DENY\r\n"),
+        "{}",
+        response
+    );
+
+    // the header of the route comes before the one it got from it's group
+    let response = get(address, "/assets/index.html");
+    assert!(
+        response.contains("Cache-Control: no-cache\r\n"),
+        "{}",
+        response
+    );
+    assert_eq!(response.matches("Cache-Control").count(), 1, "{}", response);
+}
+
+#[test]
+fn responses_generated_by_the_server_get_defaults_too() {
+    let address = support::start_server(|server| {
+        server.default_header("X-Frame-Options", "DENY");
+        server
+            .get("/slow", |mut c| {
+                thread::sleep(Duration::from_millis(300));
+                return c.send_string(HttpStatusCode::OK, "slow");
+            })
+            .timeout(Duration::from_millis(50))
+            .header("Cache-Control", "no-store");
+        server.get("/panic", |_| panic!("handler failed"));
+    });
+
+    // a head which can't be parsed never reaches the router
+    let response = support::exchange(address, b"NONSENSE\r\n\r\n").unwrap();
+    let response = String::from_utf8(response).unwrap();
+    assert!(response.starts_with("HTTP/1.1 400"), "{}", response);
+    assert!(
+        response.contains("X-Frame-Options: DENY\r\n"),
+        "{}",
+        response
+    );
+
+    // the timed out request still gets the defaults of it's route
+    let response = get(address, "/slow");
+    assert!(response.starts_with("HTTP/1.1 503"), "{}", response);
+    assert!(
+        response.contains("X-Frame-Options: DENY\r\n"),
+        "{}",
+        response
+    );
+    assert!(
+        response.contains("Cache-Control: no-store\r\n"),
+        "{}",
+        response
+    );
+
+    let response = get(address, "/panic");
+    assert!(response.starts_with("HTTP/1.1 500"), "{}", response);
+    assert!(
+        response.contains("X-Frame-Options: DENY\r\n"),
+        "{}",
+        response
+    );
+}
@@ -0,0 +1,143 @@
+//! Tests for content digests(`WebServer::content_digests`): the built-in hash functions, the
+//! digest headers of static files and the verification of uploads.
+#![cfg(feature = "md5")]
+
+mod support;
+
+use browzer_web::{
+    digest::{DigestSet, Md5, Sha1, Sha256},
+    upload::{UploadLimits, UploadReceiver},
+    WebServer,
+};
+use std::{
+    fs,
+    io::Write,
+    net::SocketAddr,
+    path::{Path, PathBuf},
+};
+
+// the base64 encoded SHA-256 and MD5 digests of `hello`
+const HELLO_SHA256: &str = "LPJNul+wow4m6DsqxbninhsWHlwfp0JecwQzYpOLmCQ=";
+const HELLO_MD5: &str = "XUFAKrxLKna5cZ2REBfFkg==";
+
+fn hex(bytes: &[u8]) -> String {
+    return bytes.iter().map(|byte| format!("{:02x}", byte)).collect();
+}
+
+/// Creates an empty temporary directory for a test.
+fn temp_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("browzer_digests_{}_{}", std::process::id(), name));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    return dir;
+}
+
+fn app(server: &mut WebServer, dir: &Path) {
+    server.content_digests(DigestSet::new().with(Sha256).with(Md5));
+    server.serve_static(dir.join("public").to_str().unwrap(), "/files");
+    let receiver = UploadReceiver::new(dir.join("uploads"), UploadLimits::default()).unwrap();
+    server.post("/upload", move |c| receiver.handle(c));
+}
+
+/// Uploads `hello` as a file, with extra headers on the request and on the file part.
+fn upload(address: SocketAddr, request_headers: &str, part_headers: &str) -> String {
+    let body = format!(
+        "--XYZ\r\nContent-Disposition: form-data; name=\"file\"; filename=\"hello.txt\"\r\n{}\r\nhello\r\n--XYZ--\r\n",
+        part_headers
+    );
+    let headers = format!(
+        "Content-Type: multipart/form-data; boundary=XYZ\r\n{}",
+        request_headers
+    );
+    return support::request_with_body(address, "POST", "/upload", &headers, &body);
+}
+
+#[test]
+fn hash_functions_match_known_answers() {
+    let digests = DigestSet::new().with(Sha256).with(Sha1).with(Md5);
+    let abc = digests.digest(b"abc");
+    assert_eq!(
+        hex(abc.get("sha-256").unwrap()),
+        "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+    );
+    assert_eq!(
+        hex(abc.get("sha").unwrap()),
+        "a9993e364706816aba3e25717850c26c9cd0d89d"
+    );
+    assert_eq!(
+        hex(abc.get("md5").unwrap()),
+        "900150983cd24fb0d6963f7d28e17f72"
+    );
+
+    // messages spanning several blocks give the same digests however they are written
+    let message: Vec<u8> = (0..1000u32).map(|i| (i % 251) as u8).collect();
+    let mut hasher = digests.hasher();
+    for piece in message.chunks(37) {
+        hasher.write_all(piece).unwrap();
+    }
+    assert_eq!(hasher.finish(), digests.digest(&message));
+}
+
+#[test]
+fn static_files_carry_their_digests() {
+    let dir = temp_dir("static");
+    fs::create_dir_all(dir.join("public")).unwrap();
+    fs::write(dir.join("public/hello.txt"), "hello").unwrap();
+    let app_dir = dir.clone();
+    let address = support::start_server(move |server| app(server, &app_dir));
+
+    let response = support::request(address, "GET", "/files/hello.txt", "");
+    assert!(response.starts_with("HTTP/1.1 200 OK"));
+    assert!(response.contains(&format!(
+        "Repr-Digest: sha-256=:{}:, md5=:{}:",
+        HELLO_SHA256, HELLO_MD5
+    )));
+    assert!(response.contains(&format!(
+        "Digest: SHA-256={},MD5={}",
+        HELLO_SHA256, HELLO_MD5
+    )));
+    assert!(response.contains(&format!("Content-MD5: {}", HELLO_MD5)));
+
+    // a partial response still describes the whole file, but not it's body
+    let response = support::request(address, "GET", "/files/hello.txt", "Range: bytes=0-1\r\n");
+    assert!(response.starts_with("HTTP/1.1 206 Partial Content"));
+    assert!(response.contains(&format!("Repr-Digest: sha-256=:{}:", HELLO_SHA256)));
+    assert!(!response.contains("Content-MD5"));
+
+    let _ = fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn uploads_are_verified_against_client_digests() {
+    let dir = temp_dir("uploads");
+    let app_dir = dir.clone();
+    let address = support::start_server(move |server| app(server, &app_dir));
+
+    let response = upload(
+        address,
+        "",
+        &format!("Repr-Digest: sha-256=:{}:\r\n", HELLO_SHA256),
+    );
+    assert!(response.starts_with("HTTP/1.1 201 Created"));
+    assert!(response.contains(&format!(
+        "\"digests\":{{\"md5\":\"{}\",\"sha-256\":\"{}\"}}",
+        HELLO_MD5, HELLO_SHA256
+    )));
+    assert_eq!(fs::read(dir.join("uploads/hello.txt")).unwrap(), b"hello");
+
+    // a file part whose digest doesn't match isn't kept
+    let response = upload(address, "", "Content-MD5: AAAAAAAAAAAAAAAAAAAAAA==\r\n");
+    assert!(response.starts_with("HTTP/1.1 422 Unprocessable Entity"));
+    assert!(!dir.join("uploads/hello-1.txt").exists());
+
+    // neither is a request whose body doesn't match the digest of the request
+    let response = upload(
+        address,
+        &format!("Digest: SHA-256={}\r\n", HELLO_SHA256),
+        "",
+    );
+    assert!(response.starts_with("HTTP/1.1 422 Unprocessable Entity"));
+    assert!(!dir.join("uploads/hello-1.txt").exists());
+
+    let _ = fs::remove_dir_all(&dir);
+}
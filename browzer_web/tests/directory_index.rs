@@ -0,0 +1,154 @@
+//! End-to-end tests for directory requests to static files(`static_files::StaticFiles`): index
+//! files and generated directory listings.
+
+mod support;
+
+use browzer_web::static_files::StaticFiles;
+use std::{fs, net::SocketAddr, path::PathBuf};
+
+/// Creates a served directory:
+///
+/// ```text
+/// <dir>/index.html
+/// <dir>/docs/guide & notes.txt
+/// <dir>/docs/api/
+/// <dir>/docs/.secret
+/// ```
+fn served_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!(
+        "browzer_directory_index_{}_{}",
+        std::process::id(),
+        name
+    ));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(dir.join("docs/api")).unwrap();
+    fs::write(dir.join("index.html"), "<h1>home</h1>").unwrap();
+    fs::write(dir.join("docs/guide & notes.txt"), "guide").unwrap();
+    fs::write(dir.join("docs/.secret"), "secret").unwrap();
+    return dir;
+}
+
+/// Sends a GET request, returning the head and the body of the response.
+fn exchange(address: SocketAddr, path: &str) -> (String, String) {
+    let response = support::request(address, "GET", path, "");
+    let (head, body) = response.split_once("\r\n\r\n").unwrap();
+    return (head.to_string(), body.to_string());
+}
+
+/// Sends a GET request, returning the status line and the body of the response.
+fn get(address: SocketAddr, path: &str) -> (String, String) {
+    let (head, body) = exchange(address, path);
+    return (head.lines().next().unwrap().to_string(), body);
+}
+
+#[test]
+fn directories_are_answered_with_their_index_file() {
+    let dir = served_dir("index");
+    let root = dir.to_str().unwrap().to_string();
+    let address = support::start_server(move |server| {
+        server.serve_static(&root, "/static");
+    });
+
+    assert_eq!(get(address, "/static/").1, "<h1>home</h1>");
+    assert_eq!(
+        get(address, "/static/docs/guide%20%26%20notes.txt").1,
+        "guide"
+    );
+    // no listing without opting into it
+    assert_eq!(get(address, "/static/docs/").0, "HTTP/1.1 404 Not Found");
+    assert_eq!(
+        get(address, "/static/docs/..%2F..%2F").0,
+        "HTTP/1.1 403 Forbidden"
+    );
+
+    let _ = fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn directories_without_an_index_are_listed() {
+    let dir = served_dir("listing");
+    let root = dir.to_str().unwrap().to_string();
+    let address = support::start_server(move |server| {
+        server.serve_static_files("/files", StaticFiles::new(&root).listing(true));
+    });
+
+    let (status, body) = get(address, "/files/docs/");
+    assert_eq!(status, "HTTP/1.1 200 OK");
+    assert!(
+        body.contains("<title>Index of /files/docs</title>"),
+        "{}",
+        body
+    );
+    assert!(
+        body.contains("<li><a href=\"/files/\">../</a></li>"),
+        "{}",
+        body
+    );
+    // directories come first, names are escaped and links encoded
+    let api = body.find("<a href=\"/files/docs/api/\">api/</a>").unwrap();
+    let guide = body
+        .find("<a href=\"/files/docs/guide%20%26%20notes.txt\">guide &amp; notes.txt</a>")
+        .unwrap();
+    assert!(api < guide);
+    assert!(!body.contains(".secret"));
+
+    // the index file still wins over the listing
+    assert_eq!(get(address, "/files/").1, "<h1>home</h1>");
+
+    let _ = fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn directories_are_redirected_to_their_path_with_a_trailing_slash() {
+    let dir = served_dir("redirect");
+    let root = dir.to_str().unwrap().to_string();
+    let address = support::start_server(move |server| {
+        server.serve_static_files("/files", StaticFiles::new(&root).listing(true));
+    });
+
+    for (path, location) in [
+        ("/files", "/files/"),
+        ("/files/docs", "/files/docs/"),
+        ("/files/docs/api?sort=name", "/files/docs/api/?sort=name"),
+    ] {
+        let (head, _) = exchange(address, path);
+        assert!(head.starts_with("HTTP/1.1 301"), "{}", head);
+        assert!(
+            head.contains(&format!("\r\nLocation: {}", location)),
+            "{}",
+            head
+        );
+    }
+    // files are never redirected
+    assert_eq!(
+        get(address, "/files/docs/guide%20%26%20notes.txt").0,
+        "HTTP/1.1 200 OK"
+    );
+
+    let _ = fs::remove_dir_all(&dir);
+}
+
+#[cfg(unix)]
+#[test]
+fn index_files_linking_out_of_the_directory_are_forbidden() {
+    let dir = served_dir("escaping_index");
+    let outside = dir.with_extension("outside");
+    fs::write(&outside, "<h1>outside</h1>").unwrap();
+    std::os::unix::fs::symlink(&outside, dir.join("docs/index.html")).unwrap();
+    let root = dir.to_str().unwrap().to_string();
+    let address = support::start_server(move |server| {
+        server.serve_static_files("/files", StaticFiles::new(&root).listing(true));
+    });
+
+    // neither the linked file nor the listing is sent
+    let (status, body) = get(address, "/files/docs/");
+    assert_eq!(status, "HTTP/1.1 403 Forbidden");
+    assert!(
+        !body.contains("outside") && !body.contains("guide"),
+        "{}",
+        body
+    );
+
+    let _ = fs::remove_dir_all(&dir);
+    let _ = fs::remove_file(&outside);
+}
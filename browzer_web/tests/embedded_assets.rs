@@ -0,0 +1,148 @@
+//! End-to-end tests for serving files held in memory(`static_files::EmbeddedAssets`) through
+//! `StaticFiles::embedded`.
+
+mod support;
+
+use browzer_web::static_files::{EmbeddedAssets, StaticFiles};
+use std::{
+    net::SocketAddr,
+    time::{Duration, SystemTime},
+};
+
+const LOGO: &[u8] = b"\x89PNG\r\n\x1a\n\x00\x00binary";
+
+/// The assets of a small site, with a binary file and a nested directory.
+fn assets() -> EmbeddedAssets {
+    return EmbeddedAssets::new()
+        .file("index.html", &b"<h1>Home</h1>"[..])
+        .file("css/site.css", &b"h1 { color: teal; }"[..])
+        .file("img/logo.png", LOGO)
+        .file("docs/guide/intro.txt", b"intro".to_vec());
+}
+
+/// Sends a GET request with extra header lines, returning the head and the body of the response.
+fn get(address: SocketAddr, path: &str, headers: &str) -> (String, Vec<u8>) {
+    let response = support::request_bytes(address, "GET", path, headers);
+    let split = response
+        .windows(4)
+        .position(|window| window == b"\r\n\r\n")
+        .unwrap();
+    let head = String::from_utf8(response[..split].to_vec()).unwrap();
+    return (head, response[split + 4..].to_vec());
+}
+
+/// Returns the value of a header of a response head.
+fn header(head: &str, name: &str) -> Option<String> {
+    return head.lines().find_map(|line| {
+        let (key, value) = line.split_once(": ")?;
+        return key.eq_ignore_ascii_case(name).then(|| value.to_string());
+    });
+}
+
+#[test]
+fn embedded_files_are_served_like_files_on_disk() {
+    let address = support::start_server(|server| {
+        server.serve_static_files(
+            "/static",
+            StaticFiles::embedded(assets()).cache_control("public, max-age=60"),
+        );
+    });
+
+    let (head, body) = get(address, "/static/css/site.css", "");
+    assert!(head.starts_with("HTTP/1.1 200"), "{}", head);
+    assert_eq!(body, b"h1 { color: teal; }");
+    assert!(header(&head, "Content-Type")
+        .unwrap()
+        .starts_with("text/css"));
+    assert_eq!(
+        header(&head, "Cache-Control").unwrap(),
+        "public, max-age=60"
+    );
+    // no modification time was given
+    assert!(header(&head, "Last-Modified").is_none());
+
+    // binary content is sent as is
+    let (head, body) = get(address, "/static/img/logo.png", "");
+    assert_eq!(header(&head, "Content-Type").unwrap(), "image/png");
+    assert_eq!(body, LOGO);
+
+    // the served directory itself is answered with it's index file, from a path ending with a
+    // slash
+    let (head, _) = get(address, "/static?v=2", "");
+    assert!(head.starts_with("HTTP/1.1 301"), "{}", head);
+    assert_eq!(header(&head, "Location").unwrap(), "/static/?v=2");
+    let (_, body) = get(address, "/static/", "");
+    assert_eq!(body, b"<h1>Home</h1>");
+
+    let (head, _) = get(address, "/static/missing.js", "");
+    assert!(head.starts_with("HTTP/1.1 404"), "{}", head);
+    let (head, _) = get(address, "/static/css/..%2F..%2Fsecret", "");
+    assert!(head.starts_with("HTTP/1.1 403"), "{}", head);
+}
+
+#[test]
+fn embedded_files_are_revalidated_by_their_content() {
+    let modified = SystemTime::UNIX_EPOCH + Duration::from_secs(1_700_000_000);
+    let address = support::start_server(move |server| {
+        server.serve_static_files(
+            "/static",
+            StaticFiles::embedded(assets().modified(modified)),
+        );
+    });
+
+    let (head, _) = get(address, "/static/css/site.css", "");
+    let etag = header(&head, "ETag").unwrap();
+    let last_modified = header(&head, "Last-Modified").unwrap();
+    assert_eq!(last_modified, "Tue, 14 Nov 2023 22:13:20 GMT");
+
+    let (head, body) = get(
+        address,
+        "/static/css/site.css",
+        &format!("If-None-Match: {}\r\n", etag),
+    );
+    assert!(head.starts_with("HTTP/1.1 304"), "{}", head);
+    assert!(body.is_empty());
+    let (head, _) = get(
+        address,
+        "/static/css/site.css",
+        &format!("If-Modified-Since: {}\r\n", last_modified),
+    );
+    assert!(head.starts_with("HTTP/1.1 304"), "{}", head);
+
+    // another file has another entity tag
+    let (head, _) = get(address, "/static/index.html", "");
+    assert_ne!(header(&head, "ETag").unwrap(), etag);
+}
+
+#[test]
+fn embedded_files_answer_range_requests() {
+    let address = support::start_server(|server| {
+        server.serve_static_files("/static", StaticFiles::embedded(assets()));
+    });
+
+    let (head, body) = get(address, "/static/css/site.css", "Range: bytes=0-1\r\n");
+    assert!(head.starts_with("HTTP/1.1 206"), "{}", head);
+    assert_eq!(header(&head, "Content-Range").unwrap(), "bytes 0-1/19");
+    assert_eq!(body, b"h1");
+}
+
+#[test]
+fn embedded_directories_are_listed_if_enabled() {
+    let address = support::start_server(|server| {
+        server.serve_static_files("/plain", StaticFiles::embedded(assets()));
+        server.serve_static_files("/listed", StaticFiles::embedded(assets()).listing(true));
+    });
+
+    let (head, _) = get(address, "/plain/docs/", "");
+    assert!(head.starts_with("HTTP/1.1 404"), "{}", head);
+
+    let (head, body) = get(address, "/listed/docs/", "");
+    assert!(head.starts_with("HTTP/1.1 200"), "{}", head);
+    let body = String::from_utf8(body).unwrap();
+    assert!(
+        body.contains("<a href=\"/listed/docs/guide/\">guide/</a>"),
+        "{}",
+        body
+    );
+    assert!(body.contains("<a href=\"/listed/\">../</a>"), "{}", body);
+}
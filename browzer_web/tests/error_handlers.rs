@@ -0,0 +1,136 @@
+//! End-to-end tests for custom error handlers(`WebServer::not_found`,
+//! `WebServer::method_not_allowed` and `WebServer::error_handler`).
+
+mod support;
+
+use browzer_web::{
+    auth::BasicAuth, response::Response, static_files::StaticFiles, utils::HttpStatusCode,
+    WebServer,
+};
+use std::net::SocketAddr;
+
+fn routes(server: &mut WebServer) {
+    server.get("/users", |mut c| {
+        return c.send_string(HttpStatusCode::OK, "users");
+    });
+    server.get("/missing", |mut c| {
+        return c.send_string(HttpStatusCode::NotFound, "no such thing");
+    });
+}
+
+/// Sends a request, returning the status line, the header lines and the body.
+fn request(address: SocketAddr, method: &str, path: &str) -> (String, Vec<String>, String) {
+    let response = support::request_with_body(address, method, path, "", "");
+    let (head, body) = response.split_once("\r\n\r\n").unwrap();
+    let mut lines = head.lines().map(|line| line.to_string());
+    let status_line = lines.next().unwrap();
+    return (status_line, lines.collect(), body.to_string());
+}
+
+#[test]
+fn uses_the_dedicated_handlers() {
+    let address = support::start_server(|server| {
+        routes(server);
+        server.not_found(|status_code, path| {
+            return Response::new(status_code, format!("<h1>Nothing at {}</h1>", path));
+        });
+        // the status code is always the one of the error
+        server.method_not_allowed(|_, _| {
+            return Response::new(HttpStatusCode::OK, "wrong method".to_string());
+        });
+    });
+
+    let (status_line, _, body) = request(address, "GET", "/nope");
+    assert_eq!(
+        (status_line.as_str(), body.as_str()),
+        ("HTTP/1.1 404 Not Found", "<h1>Nothing at /nope</h1>")
+    );
+    let (status_line, headers, body) = request(address, "DELETE", "/users");
+    assert_eq!(
+        (status_line.as_str(), body.as_str()),
+        ("HTTP/1.1 405 Method Not Allowed", "wrong method")
+    );
+    assert!(headers.iter().any(|header| header.starts_with("Allow: ")));
+    // errors without a dedicated handler keep the default body
+    let (status_line, _, body) = request(address, "GET", "/users?=1");
+    assert_eq!(
+        (status_line.as_str(), body.as_str()),
+        ("HTTP/1.1 400 Bad Request", "Bad Request")
+    );
+    // and responses of route handlers are left alone
+    assert_eq!(request(address, "GET", "/missing").2, "no such thing");
+}
+
+#[test]
+fn falls_back_to_the_error_handler() {
+    let address = support::start_server(|server| {
+        routes(server);
+        server.problem_details(None);
+        server.error_handler(|status_code, _| {
+            let body = format!("{{\"error\":{}}}", status_code.code().1);
+            return Response::new(status_code, body);
+        });
+    });
+
+    let cases = [
+        ("GET", "/nope", "HTTP/1.1 404 Not Found", "{\"error\":404}"),
+        (
+            "DELETE",
+            "/users",
+            "HTTP/1.1 405 Method Not Allowed",
+            "{\"error\":405}",
+        ),
+        (
+            "GET",
+            "/users?=1",
+            "HTTP/1.1 400 Bad Request",
+            "{\"error\":400}",
+        ),
+    ];
+    for (method, path, expected_status_line, expected_body) in cases {
+        let (status_line, _, body) = request(address, method, path);
+        assert_eq!(
+            (status_line.as_str(), body.as_str()),
+            (expected_status_line, expected_body),
+            "{} {}",
+            method,
+            path
+        );
+    }
+}
+
+#[test]
+fn errors_of_handlers_built_into_the_framework_use_the_handlers_too() {
+    let dir = std::env::temp_dir().join(format!("browzer_error_handlers_{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let address = support::start_server(|server| {
+        server.serve_static_files("/static", StaticFiles::new(dir.to_str().unwrap()));
+        let auth = BasicAuth::new("admin", |_, password| password == "secret");
+        server.get(
+            "/admin",
+            auth.wrap(|mut c| c.send_string(HttpStatusCode::OK, "admin")),
+        );
+        server.error_handler(|status_code, path| {
+            let body = format!(
+                "{} {} at {}",
+                status_code.code().1,
+                status_code.code().0,
+                path
+            );
+            return Response::new(status_code, body);
+        });
+    });
+
+    let (status_line, _, body) = request(address, "GET", "/static/missing.txt");
+    assert_eq!(status_line, "HTTP/1.1 404 Not Found");
+    assert_eq!(body, "404 Not Found at /static/missing.txt");
+    // challenges keep their header
+    let (status_line, headers, body) = request(address, "GET", "/admin");
+    assert_eq!(status_line, "HTTP/1.1 401 Unauthorized");
+    assert_eq!(body, "401 Unauthorized at /admin");
+    assert!(headers
+        .iter()
+        .any(|header| header.starts_with("WWW-Authenticate: Basic realm=\"admin\"")));
+
+    let _ = std::fs::remove_dir_all(&dir);
+}
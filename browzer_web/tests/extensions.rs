@@ -0,0 +1,53 @@
+//! End-to-end tests for the values middlewares attach to a request(`Context::set`) for the
+//! handlers after them.
+
+mod support;
+
+use browzer_web::utils::HttpStatusCode;
+use std::net::SocketAddr;
+
+struct CurrentUser(String);
+
+struct Hops(Vec<&'static str>);
+
+/// Sends a GET request with an optional `X-User` header, returning the body.
+fn get(address: SocketAddr, path: &str, user: Option<&str>) -> String {
+    let user = user
+        .map(|user| format!("X-User: {}\r\n", user))
+        .unwrap_or_default();
+    let response = support::request(address, "GET", path, &user);
+    return response.split_once("\r\n\r\n").unwrap().1.to_string();
+}
+
+#[test]
+fn middlewares_pass_values_to_handlers() {
+    let address = support::start_server(|server| {
+        server.middleware(|mut ctx| {
+            if let Some(user) = ctx.request.header("X-User").cloned() {
+                ctx.set(CurrentUser(user));
+            }
+            ctx.set(Hops(vec!["global"]));
+            return ctx;
+        });
+        server
+            .get("/me", |mut c| {
+                let user = match c.remove::<CurrentUser>() {
+                    Some(CurrentUser(user)) => user,
+                    None => "anonymous".to_string(),
+                };
+                let hops = c.get::<Hops>().map(|hops| hops.0.join(",")).unwrap();
+                let body = format!("{} {} {}", user, hops, c.get::<CurrentUser>().is_some());
+                return c.send_string(HttpStatusCode::OK, &body);
+            })
+            .middleware(|mut ctx| {
+                if let Some(hops) = ctx.get_mut::<Hops>() {
+                    hops.0.push("route");
+                }
+                return ctx;
+            });
+    });
+
+    assert_eq!(get(address, "/me", Some("axew")), "axew global,route false");
+    // values don't leak from one request into the next
+    assert_eq!(get(address, "/me", None), "anonymous global,route false");
+}
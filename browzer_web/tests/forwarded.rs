@@ -0,0 +1,59 @@
+//! End-to-end tests for the client address behind trusted reverse proxies
+//! (`WebServer::trusted_proxies`).
+
+mod support;
+
+use browzer_web::{forwarded::TrustedProxies, utils::HttpStatusCode, WebServer};
+use std::net::SocketAddr;
+
+fn app(server: &mut WebServer) {
+    server.get("/ip", |mut c| {
+        let ip = c.client_ip().map(|ip| ip.to_string()).unwrap_or_default();
+        return c.send_string(HttpStatusCode::OK, &ip);
+    });
+}
+
+/// Sends a request with a forwarding header, returning the body of the response.
+fn client_ip(address: SocketAddr, header: &str) -> String {
+    let response = support::request(address, "GET", "/ip", header);
+    return response.split_once("\r\n\r\n").unwrap().1.to_string();
+}
+
+#[test]
+fn forwarding_headers_of_trusted_proxies_name_the_client() {
+    let address = support::start_server(|server| {
+        server.trusted_proxies(TrustedProxies::parse(&["127.0.0.0/8", "10.0.0.0/8"]).unwrap());
+        app(server);
+    });
+
+    assert_eq!(client_ip(address, ""), "127.0.0.1");
+    assert_eq!(
+        client_ip(
+            address,
+            "X-Forwarded-For: 6.6.6.6, 203.0.113.7, 10.1.2.3\r\n"
+        ),
+        "203.0.113.7"
+    );
+    // `Forwarded` takes precedence over `X-Forwarded-For`
+    assert_eq!(
+        client_ip(
+            address,
+            "X-Forwarded-For: 6.6.6.6\r\nForwarded: for=\"[2001:db8::7]:4711\";proto=https, for=10.0.0.1\r\n"
+        ),
+        "2001:db8::7"
+    );
+    // obfuscated hops can't be followed, the last trusted proxy is the best known address
+    assert_eq!(
+        client_ip(address, "Forwarded: for=_hidden, for=10.0.0.1\r\n"),
+        "10.0.0.1"
+    );
+}
+
+#[test]
+fn forwarding_headers_are_ignored_without_trusted_proxies() {
+    let address = support::start_server(app);
+    assert_eq!(
+        client_ip(address, "X-Forwarded-For: 203.0.113.7\r\n"),
+        "127.0.0.1"
+    );
+}
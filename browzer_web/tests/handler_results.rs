@@ -0,0 +1,175 @@
+//! End-to-end tests for route handlers returning a `Result`, see `response::IntoResponse`.
+
+mod support;
+
+use browzer_web::prelude::*;
+use std::{fs, net::SocketAddr};
+
+/// Sends a request, returning the status line and the body.
+fn request(
+    address: SocketAddr,
+    method: &str,
+    path: &str,
+    headers: &str,
+    body: &str,
+) -> (String, String) {
+    let response = support::request_with_body(address, method, path, headers, body);
+    let (head, body) = response.split_once("\r\n\r\n").unwrap();
+    return (head.lines().next().unwrap().to_string(), body.to_string());
+}
+
+#[test]
+fn maps_returned_errors_to_responses() {
+    let address = support::start_server(|server| {
+        server
+            .register_route(
+                "/echo",
+                HttpMethod::POST,
+                |mut c| -> Result<Response, ErrorResponse> {
+                    let value: serde_json::Value = c.bind_json()?;
+                    let name = value["name"].as_str().ok_or_else(|| {
+                        ErrorResponse::new(HttpStatusCode::UnprocessableEntity, "No name")
+                    })?;
+                    return Ok(c.send_string(HttpStatusCode::OK, name));
+                },
+            )
+            .unwrap();
+        server
+            .register_route(
+                "/missing",
+                HttpMethod::GET,
+                |_| -> Result<Response, ErrorResponse> {
+                    return Err(HttpStatusCode::NotFound.into());
+                },
+            )
+            .unwrap();
+        server
+            .register_route(
+                "/config",
+                HttpMethod::GET,
+                |mut c| -> Result<Response, std::io::Error> {
+                    let config = fs::read_to_string("/nonexistent/browzer/config.toml")?;
+                    return Ok(c.send_string(HttpStatusCode::OK, &config));
+                },
+            )
+            .unwrap();
+    });
+
+    let json = "Content-Type: application/json\r\n";
+    let cases = [
+        (
+            "POST",
+            "/echo",
+            json,
+            "{\"name\":\"axew\"}",
+            "HTTP/1.1 200 OK",
+            "axew",
+        ),
+        (
+            "POST",
+            "/echo",
+            json,
+            "{}",
+            "HTTP/1.1 422 Unprocessable Entity",
+            "No name",
+        ),
+        (
+            "POST",
+            "/echo",
+            "",
+            "{}",
+            "HTTP/1.1 415 Unsupported Media Type",
+            "Missing Content-Type header, expected application/json",
+        ),
+        (
+            "GET",
+            "/missing",
+            "",
+            "",
+            "HTTP/1.1 404 Not Found",
+            "Not Found",
+        ),
+        (
+            "GET",
+            "/config",
+            "",
+            "",
+            "HTTP/1.1 404 Not Found",
+            "Not Found",
+        ),
+    ];
+    for (method, path, headers, body, status_line, response_body) in cases {
+        assert_eq!(
+            request(address, method, path, headers, body),
+            (status_line.to_string(), response_body.to_string()),
+            "{} {} {}",
+            method,
+            path,
+            body
+        );
+    }
+}
+
+#[test]
+fn answers_returned_errors_like_framework_errors() {
+    let address = support::start_server(|server| {
+        server.problem_details(None);
+        server
+            .register_route(
+                "/users/:id",
+                HttpMethod::GET,
+                |_| -> Result<Response, ErrorResponse> {
+                    return Err(ErrorResponse::new(
+                        HttpStatusCode::NotFound,
+                        "No user with id 42",
+                    ));
+                },
+            )
+            .unwrap();
+        server
+            .register_route(
+                "/crash",
+                HttpMethod::GET,
+                |_| -> Result<Response, std::io::Error> {
+                    return Err(std::io::Error::other("disk on fire"));
+                },
+            )
+            .unwrap();
+    });
+    assert_eq!(
+        request(address, "GET", "/users/42", "", ""),
+        (
+            "HTTP/1.1 404 Not Found".to_string(),
+            "{\"type\":\"about:blank\",\"title\":\"Not Found\",\"status\":404,\"detail\":\"No user with id 42\",\"instance\":\"/users/42\"}".to_string()
+        )
+    );
+    assert_eq!(
+        request(address, "GET", "/crash", "", ""),
+        (
+            "HTTP/1.1 500 Internal Server Error".to_string(),
+            "{\"type\":\"about:blank\",\"title\":\"Internal Server Error\",\"status\":500,\"instance\":\"/crash\"}".to_string()
+        )
+    );
+
+    let address = support::start_server(|server| {
+        server.error_handler(|status_code, path| {
+            return Response::new(status_code, format!("failed to serve {}", path));
+        });
+        server
+            .register_route(
+                "/missing",
+                HttpMethod::GET,
+                |_| -> Result<Response, ErrorResponse> {
+                    return Err(HttpStatusCode::NotFound.into());
+                },
+            )
+            .unwrap();
+    });
+    assert_eq!(
+        request(address, "GET", "/missing", "", ""),
+        (
+            "HTTP/1.1 404 Not Found".to_string(),
+            "failed to serve /missing".to_string()
+        )
+    );
+}
@@ -0,0 +1,152 @@
+//! End-to-end tests for the health checks of a server(`health` module,
+//! `WebServer::health_check`, `WebServer::readiness_check`).
+
+mod support;
+
+use browzer_web::{
+    health::{HealthCheck, HealthStatus},
+    utils::HttpStatusCode,
+};
+use std::{
+    net::SocketAddr,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+};
+
+/// Sends a request and returns the raw response.
+fn get(address: SocketAddr, path: &str) -> String {
+    return support::request(address, "GET", path, "");
+}
+
+/// Returns the JSON body of a response.
+fn body(response: &str) -> serde_json::Value {
+    return serde_json::from_str(response.split_once("\r\n\r\n").unwrap().1).unwrap();
+}
+
+#[test]
+fn passing_checks_are_answered_with_ok() {
+    let address = support::start_server(|server| {
+        server.health_check("/healthz", || HealthStatus::Healthy);
+    });
+
+    let response = get(address, "/healthz");
+    assert!(response.starts_with("HTTP/1.1 200"), "{}", response);
+    assert!(
+        response.contains("Content-Type: application/json"),
+        "{}",
+        response
+    );
+    assert!(response.contains("Cache-Control: no-store"), "{}", response);
+    assert_eq!(
+        body(&response),
+        serde_json::json!({ "status": "ok", "checks": { "app": { "status": "ok" } } })
+    );
+}
+
+#[test]
+fn failing_checks_are_answered_with_service_unavailable() {
+    let healthy = Arc::new(AtomicBool::new(true));
+    let check = Arc::clone(&healthy);
+    let address = support::start_server(move |server| {
+        server.health_check("/healthz", move || match check.load(Ordering::SeqCst) {
+            true => HealthStatus::Healthy,
+            false => HealthStatus::Unhealthy("the database is unreachable".to_string()),
+        });
+    });
+    assert!(get(address, "/healthz").starts_with("HTTP/1.1 200"));
+
+    // the check runs for every request
+    healthy.store(false, Ordering::SeqCst);
+    let response = get(address, "/healthz");
+    assert!(response.starts_with("HTTP/1.1 503"), "{}", response);
+    assert_eq!(
+        body(&response),
+        serde_json::json!({
+            "status": "unavailable",
+            "checks": {
+                "app": { "status": "unavailable", "detail": "the database is unreachable" }
+            }
+        })
+    );
+}
+
+#[test]
+fn degraded_checks_still_pass() {
+    let address = support::start_server(|server| {
+        let health = HealthCheck::new()
+            .check("database", || HealthStatus::Healthy)
+            .check("cache", || {
+                HealthStatus::Degraded("using the fallback".to_string())
+            });
+        server.serve_health_check("/healthz", health);
+    });
+
+    let response = get(address, "/healthz");
+    assert!(response.starts_with("HTTP/1.1 200"), "{}", response);
+    let body = body(&response);
+    assert_eq!(body["status"], "degraded");
+    assert_eq!(body["checks"]["database"]["status"], "ok");
+    assert_eq!(body["checks"]["cache"]["detail"], "using the fallback");
+}
+
+#[test]
+fn readiness_checks_report_the_server() {
+    let address = support::start_server(|server| {
+        server.readiness_check("/readyz", || HealthStatus::Healthy);
+        server.health_check("/healthz", || HealthStatus::Healthy);
+    });
+
+    let response = get(address, "/readyz");
+    assert!(response.starts_with("HTTP/1.1 200"), "{}", response);
+    assert_eq!(
+        body(&response),
+        serde_json::json!({
+            "status": "ok",
+            "checks": {
+                "shutdown": { "status": "ok" },
+                "workers": { "status": "ok" },
+                "app": { "status": "ok" }
+            }
+        })
+    );
+    // liveness checks leave the server out
+    assert_eq!(
+        body(&get(address, "/healthz"))["checks"]
+            .as_object()
+            .unwrap()
+            .len(),
+        1
+    );
+}
+
+#[test]
+fn stopping_servers_are_not_ready() {
+    let address = support::start_server(|server| {
+        let shutdown = server.shutdown_handle();
+        server.get("/stop", move |mut c| {
+            shutdown.shutdown();
+            return c.send_string(HttpStatusCode::OK, "stopping");
+        });
+        server.readiness_check("/readyz", || HealthStatus::Healthy);
+    });
+
+    // the readiness check is pipelined behind the request stopping the server, so it's read from
+    // the connection accepted before
+    let response = support::exchange(
+        address,
+        b"GET /stop HTTP/1.1\r\nHost: localhost\r\n\r\nGET /readyz HTTP/1.1\r\nHost: localhost\r\n\r\n",
+    )
+    .unwrap();
+    let response = String::from_utf8(response).unwrap();
+    let readiness = &response[response.rfind("HTTP/1.1").unwrap()..];
+    assert!(readiness.starts_with("HTTP/1.1 503"), "{}", response);
+    let body = body(readiness);
+    assert_eq!(body["checks"]["shutdown"]["status"], "unavailable");
+    assert_eq!(
+        body["checks"]["shutdown"]["detail"],
+        "the server is stopping"
+    );
+    assert_eq!(body["checks"]["app"]["status"], "ok");
+}
@@ -0,0 +1,170 @@
+//! End-to-end tests for registering routes while the server is listening(`router::RouteRegistry`,
+//! `WebServer::route_registry`).
+
+mod support;
+
+use browzer_web::{
+    cache::ResponseCache,
+    error::WebRouterError,
+    router::{RouteGroup, RouteRegistry},
+    utils::{HttpMethod, HttpStatusCode},
+};
+use std::{
+    net::SocketAddr,
+    sync::{Arc, Mutex},
+    thread,
+    time::{Duration, Instant},
+};
+
+/// Sends a request on a new connection and returns the raw response.
+fn request(address: SocketAddr, method: &str, path: &str) -> String {
+    return support::request(address, method, path, "");
+}
+
+/// Starts a server and returns it's address with it's route registry, once the registry works.
+fn start_server<F: FnOnce(&mut browzer_web::WebServer)>(
+    configure: F,
+) -> (SocketAddr, RouteRegistry) {
+    let registry = Arc::new(Mutex::new(None));
+    let address = {
+        let registry = Arc::clone(&registry);
+        support::start_server(move |server| {
+            *registry.lock().unwrap() = Some(server.route_registry());
+            configure(server);
+        })
+    };
+    let registry = registry.lock().unwrap().take().unwrap();
+    let deadline = Instant::now() + Duration::from_secs(10);
+    while !registry.is_attached() {
+        assert!(
+            Instant::now() < deadline,
+            "the server didn't start listening"
+        );
+        thread::sleep(Duration::from_millis(10));
+    }
+    return (address, registry);
+}
+
+#[test]
+fn routes_registered_while_listening_are_served() {
+    let (address, registry) = start_server(|server| {
+        server.middleware(|mut c| {
+            c.response
+                .headers
+                .insert("X-Middleware".to_string(), "ran".to_string());
+            return c;
+        });
+    });
+
+    assert!(request(address, "GET", "/late").starts_with("HTTP/1.1 404"));
+    registry
+        .get("/late", |mut c| c.send_string(HttpStatusCode::OK, "late"))
+        .unwrap();
+
+    let response = request(address, "GET", "/late");
+    assert!(response.starts_with("HTTP/1.1 200"), "{}", response);
+    assert!(response.ends_with("late"), "{}", response);
+    // the global middlewares run for the new route too
+    assert!(response.contains("X-Middleware: ran"), "{}", response);
+    // other methods of the path are known now
+    assert!(request(address, "POST", "/late").starts_with("HTTP/1.1 405"));
+}
+
+#[test]
+fn routes_are_configured_before_they_can_be_requested() {
+    let (address, registry) = start_server(|_| {});
+
+    registry
+        .route(
+            "/guarded",
+            HttpMethod::GET,
+            |mut c| c.send_string(HttpStatusCode::OK, "handler"),
+            |route| {
+                route.middleware(|mut c| {
+                    c.response
+                        .headers
+                        .insert("X-Route".to_string(), "configured".to_string());
+                    return c;
+                })
+            },
+        )
+        .unwrap();
+
+    let response = request(address, "GET", "/guarded");
+    assert!(response.contains("X-Route: configured"), "{}", response);
+}
+
+#[test]
+fn cached_not_found_paths_are_forgotten_once_a_route_matches_them() {
+    let (address, registry) = start_server(|server| {
+        server.cache_not_found(100);
+    });
+
+    // the second request is answered from the cache
+    assert!(request(address, "GET", "/plugin").starts_with("HTTP/1.1 404"));
+    assert!(request(address, "GET", "/plugin").starts_with("HTTP/1.1 404"));
+
+    registry
+        .get("/plugin", |mut c| {
+            c.send_string(HttpStatusCode::OK, "loaded")
+        })
+        .unwrap();
+    assert!(request(address, "GET", "/plugin").ends_with("loaded"));
+}
+
+#[test]
+fn cached_responses_are_forgotten_once_their_route_is_replaced() {
+    let (address, registry) = start_server(|server| {
+        server.response_cache(ResponseCache::new(Duration::from_secs(60)).path("/plugin"));
+        server.get("/plugin", |mut c| c.send_string(HttpStatusCode::OK, "v1"));
+    });
+
+    assert!(request(address, "GET", "/plugin").ends_with("v1"));
+    assert!(request(address, "GET", "/plugin").ends_with("v1"));
+
+    registry
+        .get("/plugin", |mut c| c.send_string(HttpStatusCode::OK, "v2"))
+        .unwrap();
+    assert!(request(address, "GET", "/plugin").ends_with("v2"));
+}
+
+#[test]
+fn handlers_can_register_routes() {
+    let (address, _) = start_server(|server| {
+        let registry = server.route_registry();
+        server.post("/plugins/:name", move |mut c| {
+            let path = format!("/plugins/{}/status", c.params["name"]);
+            registry
+                .get(&path, |mut c| c.send_string(HttpStatusCode::OK, "running"))
+                .unwrap();
+            return c.send_string(HttpStatusCode::Created, &path);
+        });
+    });
+
+    assert!(request(address, "POST", "/plugins/search").starts_with("HTTP/1.1 201"));
+    assert!(request(address, "GET", "/plugins/search/status").ends_with("running"));
+}
+
+#[test]
+fn registering_fails_while_the_server_isnt_listening() {
+    let server = browzer_web::WebServer::new("127.0.0.1:0".to_string(), 1);
+    let registry = server.route_registry();
+
+    assert!(!registry.is_attached());
+    assert!(matches!(
+        registry.get("/", |mut c| c.send_string(HttpStatusCode::OK, "")),
+        Err(WebRouterError::NotListening)
+    ));
+}
+
+#[test]
+fn registering_with_a_no_op_group_fails() {
+    let mut group = RouteGroup::new(None, "/api");
+
+    assert!(matches!(
+        group.register_route("/users", HttpMethod::GET, |mut c| {
+            c.send_string(HttpStatusCode::OK, "")
+        }),
+        Err(WebRouterError::Listening)
+    ));
+}
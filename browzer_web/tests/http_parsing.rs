@@ -0,0 +1,256 @@
+//! Test vectors for the HTTP request parser, exercising both the permissive(default) and the
+//! strict(`WebServer::strict_http`) parsing modes.
+//!
+//! Every vector is a raw request as it would arrive over a connection. The messages target the
+//! usual request smuggling techniques, where a server and an intermediary in front of it disagree
+//! about where one request ends and the next one begins, along with the RFC 7230 grammar violations
+//! that only strict mode rejects.
+
+mod support;
+
+use browzer_web::{accept::AcceptErrorKind, error::WebServerError, request::Request};
+use std::{
+    sync::{Arc, Mutex},
+    thread,
+    time::Duration,
+};
+
+/// The expected outcome of parsing a test vector.
+#[derive(Debug, PartialEq)]
+enum Outcome {
+    /// The request is accepted.
+    Accept,
+    /// The request is rejected as malformed, which the server answers with `400 Bad Request`.
+    Reject,
+}
+
+/// A single test vector: a name, the raw request, and the expected outcome in permissive and in
+/// strict mode.
+struct Vector {
+    name: &'static str,
+    raw: &'static str,
+    permissive: Outcome,
+    strict: Outcome,
+}
+
+const VECTORS: &[Vector] = &[
+    Vector {
+        name: "well-formed request",
+        raw: "GET /users HTTP/1.1\r\nHost: example.com\r\n\r\n",
+        permissive: Outcome::Accept,
+        strict: Outcome::Accept,
+    },
+    Vector {
+        name: "well-formed request with a body",
+        raw: "POST /users HTTP/1.1\r\nHost: example.com\r\nContent-Length: 4\r\n\r\naxew",
+        permissive: Outcome::Accept,
+        strict: Outcome::Accept,
+    },
+    Vector {
+        name: "bare LF line endings",
+        raw: "GET /users HTTP/1.1\nHost: example.com\n\n",
+        permissive: Outcome::Accept,
+        strict: Outcome::Reject,
+    },
+    Vector {
+        name: "bare LF ending a single header line",
+        raw: "GET /users HTTP/1.1\r\nHost: example.com\nX-Smuggled: 1\r\n\r\n",
+        permissive: Outcome::Accept,
+        strict: Outcome::Reject,
+    },
+    Vector {
+        name: "bare CR inside a header line",
+        raw: "GET /users HTTP/1.1\r\nHost: example.com\rX-Smuggled: 1\r\n\r\n",
+        permissive: Outcome::Accept,
+        strict: Outcome::Reject,
+    },
+    Vector {
+        name: "multiple spaces in the request line",
+        raw: "GET  /users  HTTP/1.1\r\nHost: example.com\r\n\r\n",
+        permissive: Outcome::Accept,
+        strict: Outcome::Reject,
+    },
+    Vector {
+        name: "tab in the request line",
+        raw: "GET\t/users HTTP/1.1\r\nHost: example.com\r\n\r\n",
+        permissive: Outcome::Accept,
+        strict: Outcome::Reject,
+    },
+    Vector {
+        name: "missing HTTP version",
+        raw: "GET /users\r\nHost: example.com\r\n\r\n",
+        permissive: Outcome::Reject,
+        strict: Outcome::Reject,
+    },
+    Vector {
+        name: "whitespace between header name and colon",
+        raw: "POST /users HTTP/1.1\r\nContent-Length : 4\r\n\r\naxew",
+        permissive: Outcome::Accept,
+        strict: Outcome::Reject,
+    },
+    Vector {
+        name: "obsolete line folding",
+        raw: "GET /users HTTP/1.1\r\nX-Folded: first\r\n second\r\n\r\n",
+        permissive: Outcome::Accept,
+        strict: Outcome::Reject,
+    },
+    Vector {
+        name: "raw non-ASCII characters in the request-target",
+        raw: "GET /é HTTP/1.1\r\nHost: example.com\r\n\r\n",
+        permissive: Outcome::Reject,
+        strict: Outcome::Reject,
+    },
+    Vector {
+        name: "header line without a colon",
+        raw: "GET /users HTTP/1.1\r\nHost example.com\r\n\r\n",
+        permissive: Outcome::Accept,
+        strict: Outcome::Reject,
+    },
+    Vector {
+        name: "conflicting Content-Length headers (CL.CL)",
+        raw: "POST /users HTTP/1.1\r\nContent-Length: 4\r\nContent-Length: 40\r\n\r\naxew",
+        permissive: Outcome::Reject,
+        strict: Outcome::Reject,
+    },
+    Vector {
+        name: "repeated identical Content-Length headers",
+        raw: "POST /users HTTP/1.1\r\nContent-Length: 4\r\nContent-Length: 4\r\n\r\naxew",
+        permissive: Outcome::Accept,
+        strict: Outcome::Accept,
+    },
+    Vector {
+        name: "signed Content-Length",
+        raw: "POST /users HTTP/1.1\r\nContent-Length: +4\r\n\r\naxew",
+        permissive: Outcome::Reject,
+        strict: Outcome::Reject,
+    },
+    Vector {
+        name: "non-numeric Content-Length",
+        raw: "POST /users HTTP/1.1\r\nContent-Length: 4, 40\r\n\r\naxew",
+        permissive: Outcome::Reject,
+        strict: Outcome::Reject,
+    },
+    Vector {
+        name: "Transfer-Encoding alongside Content-Length (TE.CL)",
+        raw: "POST /users HTTP/1.1\r\nContent-Length: 4\r\nTransfer-Encoding: chunked\r\n\r\n0\r\n\r\n",
+        permissive: Outcome::Reject,
+        strict: Outcome::Reject,
+    },
+    Vector {
+        name: "multiple Host headers differing in case",
+        raw: "GET /users HTTP/1.1\r\nHost: example.com\r\nhost: evil.com\r\n\r\n",
+        permissive: Outcome::Reject,
+        strict: Outcome::Reject,
+    },
+    Vector {
+        name: "repeated Host header",
+        raw: "GET /users HTTP/1.1\r\nHost: example.com\r\nHost: example.com\r\n\r\n",
+        permissive: Outcome::Reject,
+        strict: Outcome::Reject,
+    },
+    Vector {
+        name: "obfuscated Transfer-Encoding",
+        raw: "POST /users HTTP/1.1\r\ntransfer-encoding : chunked\r\n\r\n0\r\n\r\n",
+        permissive: Outcome::Reject,
+        strict: Outcome::Reject,
+    },
+];
+
+fn parse(raw: &str, strict: bool) -> Result<Option<Request>, WebServerError> {
+    let mut input = raw.as_bytes();
+    return Request::read_from(&mut input, strict);
+}
+
+fn outcome(raw: &str, strict: bool) -> Outcome {
+    match parse(raw, strict) {
+        Ok(Some(_)) => return Outcome::Accept,
+        Ok(None) => panic!("the test vector is empty"),
+        Err(WebServerError::RequestParseError(_)) => return Outcome::Reject,
+        Err(e) => panic!("unexpected error: {}", e),
+    }
+}
+
+#[test]
+fn permissive_mode() {
+    for vector in VECTORS {
+        assert_eq!(
+            outcome(vector.raw, false),
+            vector.permissive,
+            "permissive mode: {}",
+            vector.name
+        );
+    }
+}
+
+#[test]
+fn strict_mode() {
+    for vector in VECTORS {
+        assert_eq!(
+            outcome(vector.raw, true),
+            vector.strict,
+            "strict mode: {}",
+            vector.name
+        );
+    }
+}
+
+#[test]
+fn content_length_is_matched_case_insensitively() {
+    // the body of the first request must be consumed completely, otherwise it would be parsed as
+    // the start of a second, smuggled request
+    let raw =
+        "POST /users HTTP/1.1\r\ncontent-length: 29\r\n\r\nGET /admin HTTP/1.1\r\nX: y\r\n\r\n";
+    for strict in [false, true] {
+        let mut input = raw.as_bytes();
+        let request = Request::read_from(&mut input, strict).unwrap().unwrap();
+        assert_eq!(request.path, "/users");
+        assert_eq!(
+            request.body.as_deref(),
+            Some("GET /admin HTTP/1.1\r\nX: y\r\n\r\n")
+        );
+        assert!(Request::read_from(&mut input, strict).unwrap().is_none());
+    }
+}
+
+#[test]
+fn pipelined_requests_are_read_one_at_a_time() {
+    let raw = "POST /a HTTP/1.1\r\nContent-Length: 2\r\n\r\nhiGET /b HTTP/1.1\r\n\r\n";
+    for strict in [false, true] {
+        let mut input = raw.as_bytes();
+        let first = Request::read_from(&mut input, strict).unwrap().unwrap();
+        assert_eq!(first.path, "/a");
+        assert_eq!(first.body.as_deref(), Some("hi"));
+        let second = Request::read_from(&mut input, strict).unwrap().unwrap();
+        assert_eq!(second.path, "/b");
+        assert!(Request::read_from(&mut input, strict).unwrap().is_none());
+    }
+}
+
+#[test]
+fn malformed_heads_are_answered_and_reported_rate_limited() {
+    let reports = Arc::new(Mutex::new(Vec::new()));
+    let recorded = Arc::clone(&reports);
+    let address = support::start_server(move |server| {
+        server.accept_error_log.interval = Duration::from_secs(60);
+        server.on_accept_error(move |report| recorded.lock().unwrap().push(report.kind));
+    });
+
+    for _ in 0..3 {
+        let response = support::exchange(
+            address,
+            "GET /é HTTP/1.1\r\nHost: localhost\r\n\r\n".as_bytes(),
+        )
+        .unwrap();
+        assert!(response.starts_with(b"HTTP/1.1 400 Bad Request"));
+    }
+    // the errors are reported once the connection was answered, and only the first one isn't
+    // suppressed by the rate limit
+    for _ in 0..100 {
+        if !reports.lock().unwrap().is_empty() {
+            break;
+        }
+        thread::sleep(Duration::from_millis(10));
+    }
+    thread::sleep(Duration::from_millis(50));
+    assert_eq!(*reports.lock().unwrap(), vec![AcceptErrorKind::Connection]);
+}
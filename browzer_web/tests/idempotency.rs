@@ -0,0 +1,207 @@
+//! End-to-end tests for `Idempotency-Key` handling(`idempotency::Idempotency`).
+
+mod support;
+
+use browzer_web::{
+    idempotency::{Idempotency, MemoryIdempotencyStore},
+    utils::HttpStatusCode,
+};
+use std::{
+    io::Write,
+    net::SocketAddr,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+
+/// Sends a `POST` request and returns the raw response.
+fn post(address: SocketAddr, headers: &str, body: &str) -> String {
+    return support::request_with_body(address, "POST", "/payments", headers, body);
+}
+
+#[test]
+fn a_panicking_handler_leaves_the_key_usable() {
+    let calls = Arc::new(AtomicUsize::new(0));
+    let counter = Arc::clone(&calls);
+    let address = support::start_server(move |server| {
+        let idempotency = Idempotency::new(MemoryIdempotencyStore::new(), Duration::from_secs(60));
+        server.post(
+            "/payments",
+            idempotency.wrap(move |mut c| {
+                if counter.fetch_add(1, Ordering::SeqCst) == 0 {
+                    panic!("the card processor went away");
+                }
+                return c.send_string(HttpStatusCode::Created, "charged");
+            }),
+        );
+    });
+
+    let response = post(address, "Idempotency-Key: 4f1c\r\n", "42");
+    assert!(
+        response.starts_with("HTTP/1.1 500 Internal Server Error\r\n"),
+        "{}",
+        response
+    );
+    // the retry runs the handler instead of being told the first attempt is still running
+    let response = post(address, "Idempotency-Key: 4f1c\r\n", "42");
+    assert!(
+        response.starts_with("HTTP/1.1 201 Created\r\n"),
+        "{}",
+        response
+    );
+    let response = post(address, "Idempotency-Key: 4f1c\r\n", "42");
+    assert!(
+        response.contains("Idempotent-Replayed: true\r\n"),
+        "{}",
+        response
+    );
+    assert_eq!(calls.load(Ordering::SeqCst), 2);
+}
+
+#[test]
+fn matches_the_header_case_insensitively() {
+    let calls = Arc::new(AtomicUsize::new(0));
+    let counter = Arc::clone(&calls);
+    let address = support::start_server(move |server| {
+        let idempotency = Idempotency::new(MemoryIdempotencyStore::new(), Duration::from_secs(60));
+        server.post(
+            "/payments",
+            idempotency.wrap(move |mut c| {
+                counter.fetch_add(1, Ordering::SeqCst);
+                return c.send_string(HttpStatusCode::Created, "charged");
+            }),
+        );
+    });
+
+    post(address, "idempotency-key: 9a2e\r\n", "42");
+    let response = post(address, "IDEMPOTENCY-KEY: 9a2e\r\n", "42");
+    assert!(
+        response.contains("Idempotent-Replayed: true\r\n"),
+        "{}",
+        response
+    );
+    assert_eq!(calls.load(Ordering::SeqCst), 1);
+}
+
+#[test]
+fn rejects_a_key_reused_for_another_body() {
+    let address = support::start_server(|server| {
+        let idempotency = Idempotency::new(MemoryIdempotencyStore::new(), Duration::from_secs(60));
+        server.post(
+            "/payments",
+            idempotency.wrap(|mut c| c.send_string(HttpStatusCode::Created, "charged")),
+        );
+    });
+
+    post(address, "Idempotency-Key: 77d0\r\n", "{\"amount\":42}");
+    let response = post(address, "Idempotency-Key: 77d0\r\n", "{\"amount\":4200}");
+    assert!(
+        response.starts_with("HTTP/1.1 422 Unprocessable Entity\r\n"),
+        "{}",
+        response
+    );
+    let response = post(address, "Idempotency-Key: 77d0\r\n", "{\"amount\":42}");
+    assert!(
+        response.starts_with("HTTP/1.1 201 Created\r\n"),
+        "{}",
+        response
+    );
+}
+
+#[test]
+fn a_streamed_response_is_not_replayed() {
+    let calls = Arc::new(AtomicUsize::new(0));
+    let counter = Arc::clone(&calls);
+    let address = support::start_server(move |server| {
+        let idempotency = Idempotency::new(MemoryIdempotencyStore::new(), Duration::from_secs(60));
+        server.post(
+            "/payments",
+            idempotency.wrap(move |mut c| {
+                counter.fetch_add(1, Ordering::SeqCst);
+                return c.send_stream(HttpStatusCode::Created, |writer| {
+                    return writer.write_all(b"receipt");
+                });
+            }),
+        );
+    });
+
+    post(address, "Idempotency-Key: 51b3\r\n", "42");
+    // the stream was written to the first client, so the retry gets a fresh one instead of an
+    // empty replay
+    let response = post(address, "Idempotency-Key: 51b3\r\n", "42");
+    assert!(
+        response.starts_with("HTTP/1.1 201 Created\r\n"),
+        "{}",
+        response
+    );
+    assert!(response.contains("receipt"), "{}", response);
+    assert!(!response.contains("Idempotent-Replayed"), "{}", response);
+    assert_eq!(calls.load(Ordering::SeqCst), 2);
+}
+
+#[test]
+fn keys_are_scoped_to_the_caller() {
+    let calls = Arc::new(AtomicUsize::new(0));
+    let counter = Arc::clone(&calls);
+    let address = support::start_server(move |server| {
+        let idempotency = Idempotency::new(MemoryIdempotencyStore::new(), Duration::from_secs(60));
+        server.post(
+            "/payments",
+            idempotency.wrap(move |mut c| {
+                let call = counter.fetch_add(1, Ordering::SeqCst);
+                return c.send_string(HttpStatusCode::Created, &format!("payment {}", call));
+            }),
+        );
+    });
+
+    let alice = "Authorization: Bearer alice\r\nIdempotency-Key: 3c9d\r\n";
+    let mallory = "Authorization: Bearer mallory\r\nIdempotency-Key: 3c9d\r\n";
+    assert!(post(address, alice, "42").ends_with("payment 0"));
+    // another caller reusing the key gets a response of it's own, not the stored one
+    let response = post(address, mallory, "42");
+    assert!(response.ends_with("payment 1"), "{}", response);
+    assert!(!response.contains("Idempotent-Replayed"), "{}", response);
+    let response = post(address, alice, "42");
+    assert!(
+        response.contains("Idempotent-Replayed: true\r\n"),
+        "{}",
+        response
+    );
+    assert!(response.ends_with("payment 0"), "{}", response);
+    assert_eq!(calls.load(Ordering::SeqCst), 2);
+}
+
+#[test]
+fn the_app_can_scope_the_keys() {
+    let calls = Arc::new(AtomicUsize::new(0));
+    let counter = Arc::clone(&calls);
+    let address = support::start_server(move |server| {
+        let idempotency = Idempotency::new(MemoryIdempotencyStore::new(), Duration::from_secs(60))
+            .scope_by(|c| c.request.header("X-Account").cloned());
+        server.post(
+            "/payments",
+            idempotency.wrap(move |mut c| {
+                counter.fetch_add(1, Ordering::SeqCst);
+                return c.send_string(HttpStatusCode::Created, "charged");
+            }),
+        );
+    });
+
+    post(address, "X-Account: 1\r\nIdempotency-Key: e41a\r\n", "42");
+    post(address, "X-Account: 2\r\nIdempotency-Key: e41a\r\n", "42");
+    let response = post(address, "X-Account: 1\r\nIdempotency-Key: e41a\r\n", "42");
+    assert!(
+        response.contains("Idempotent-Replayed: true\r\n"),
+        "{}",
+        response
+    );
+    assert_eq!(calls.load(Ordering::SeqCst), 2);
+
+    // requests without a scope are never replayed
+    post(address, "Idempotency-Key: e41a\r\n", "42");
+    let response = post(address, "Idempotency-Key: e41a\r\n", "42");
+    assert!(!response.contains("Idempotent-Replayed"), "{}", response);
+    assert_eq!(calls.load(Ordering::SeqCst), 4);
+}
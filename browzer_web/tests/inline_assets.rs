@@ -0,0 +1,132 @@
+//! End-to-end tests for inlining static assets into HTML responses(`WebServer::inline_assets`).
+
+mod support;
+
+use browzer_web::{inline::AssetInliner, utils::HttpStatusCode};
+use std::{fs, path::PathBuf};
+
+/// A temporary static directory with a stylesheet outside of it, removed again when dropped.
+struct Assets {
+    base: PathBuf,
+}
+
+impl Assets {
+    fn new(name: &str) -> Assets {
+        let base = std::env::temp_dir().join(format!(
+            "browzer_inline_assets_{}_{}",
+            std::process::id(),
+            name
+        ));
+        let _ = fs::remove_dir_all(&base);
+        fs::create_dir_all(base.join("public")).unwrap();
+        fs::write(base.join("public/critical.css"), "h1{color:red}").unwrap();
+        fs::write(base.join("public/large.css"), "p{}".repeat(100)).unwrap();
+        fs::write(
+            base.join("public/relative.css"),
+            "body{background:url(bg.png)}",
+        )
+        .unwrap();
+        fs::write(base.join("public/logo.svg"), "<svg/>").unwrap();
+        fs::write(base.join("secret.css"), "secret{}").unwrap();
+        return Assets { base };
+    }
+
+    fn root(&self) -> String {
+        return self.base.join("public").to_string_lossy().into_owned();
+    }
+}
+
+impl Drop for Assets {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.base);
+    }
+}
+
+const PAGE: &str = concat!(
+    "<html><head>",
+    "<link rel=stylesheet href=\"/assets/critical.css?v=2\" media=\"screen\">",
+    "<link rel=\"stylesheet\" href=\"/assets/large.css\">",
+    "<link rel=\"stylesheet\" href=\"/assets/relative.css\">",
+    "<link rel=\"stylesheet\" href=\"/assets/..%2Fsecret.css\">",
+    "<link rel=\"icon\" href=\"/assets/logo.svg\">",
+    "<!-- <link rel=\"stylesheet\" href=\"/assets/critical.css\"> -->",
+    "<script>var img = '<img src=\"/assets/logo.svg\">';</script>",
+    "</head><body><IMG SRC='/assets/logo.svg' alt=\"logo\"></body></html>",
+);
+
+const INLINED: &str = concat!(
+    "<html><head>",
+    "<style media=\"screen\">h1{color:red}</style>",
+    "<link rel=\"stylesheet\" href=\"/assets/large.css\">",
+    "<link rel=\"stylesheet\" href=\"/assets/relative.css\">",
+    "<link rel=\"stylesheet\" href=\"/assets/..%2Fsecret.css\">",
+    "<link rel=\"icon\" href=\"/assets/logo.svg\">",
+    "<!-- <link rel=\"stylesheet\" href=\"/assets/critical.css\"> -->",
+    "<script>var img = '<img src=\"/assets/logo.svg\">';</script>",
+    "</head><body><IMG SRC='data:image/svg+xml;base64,PHN2Zy8+' alt=\"logo\"></body></html>",
+);
+
+fn get(address: std::net::SocketAddr, path: &str) -> String {
+    let response = support::request(address, "GET", path, "");
+    return response.split_once("\r\n\r\n").unwrap().1.to_string();
+}
+
+#[test]
+fn inlines_eligible_assets_of_html_responses() {
+    let assets = Assets::new("eligible");
+    let root = assets.root();
+    let address = support::start_server(|server| {
+        server.serve_static(&root, "/assets");
+        server.inline_assets(AssetInliner::new(&root, "/assets").max_stylesheet_size(100));
+        server.get("/", |mut c| {
+            c.response.headers.insert(
+                "Content-Type".to_string(),
+                "text/html; charset=utf-8".to_string(),
+            );
+            return c.send_string(HttpStatusCode::OK, PAGE);
+        });
+        server.get("/plain", |mut c| {
+            return c.send_string(HttpStatusCode::OK, PAGE);
+        });
+    });
+
+    assert_eq!(get(address, "/"), INLINED);
+    // responses which aren't HTML are left alone
+    assert_eq!(get(address, "/plain"), PAGE);
+    // and so are the assets themselves
+    assert_eq!(get(address, "/assets/logo.svg"), "<svg/>");
+}
+
+#[test]
+fn only_inlines_assets_matching_the_patterns() {
+    let assets = Assets::new("patterns");
+    let inliner = AssetInliner::new(&assets.root(), "/assets/").patterns(&["*.css"]);
+    assert_eq!(
+        inliner.inline(
+            "<link rel=\"stylesheet\" href=\"/assets/critical.css\"><img src=\"/assets/logo.svg\">"
+        ),
+        "<style>h1{color:red}</style><img src=\"/assets/logo.svg\">"
+    );
+
+    let inliner = AssetInliner::new(&assets.root(), "/assets").patterns(&["logo.???"]);
+    assert_eq!(
+        inliner.inline("<img src=\"/assets/logo.svg\"><img src=\"/other/logo.svg\">"),
+        "<img src=\"data:image/svg+xml;base64,PHN2Zy8+\"><img src=\"/other/logo.svg\">"
+    );
+}
+
+#[test]
+fn picks_up_changed_assets() {
+    let assets = Assets::new("changes");
+    let inliner = AssetInliner::new(&assets.root(), "/assets");
+    let html = "<link rel=\"stylesheet\" href=\"/assets/critical.css\">";
+    assert_eq!(inliner.inline(html), "<style>h1{color:red}</style>");
+
+    let path = assets.base.join("public/critical.css");
+    fs::write(&path, "h1{color:blue}").unwrap();
+    // make sure the modification time differs even on file systems with a coarse resolution
+    let file = fs::File::options().write(true).open(&path).unwrap();
+    file.set_modified(std::time::SystemTime::now() + std::time::Duration::from_secs(5))
+        .unwrap();
+    assert_eq!(inliner.inline(html), "<style>h1{color:blue}</style>");
+}
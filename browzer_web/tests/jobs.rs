@@ -0,0 +1,125 @@
+//! End-to-end tests for background jobs(`WebServer::jobs` and `Context::accept_async`).
+
+mod support;
+
+use browzer_web::jobs::{Jobs, MemoryJobStore};
+use std::{
+    net::SocketAddr,
+    thread,
+    time::{Duration, Instant},
+};
+
+/// Sends a request, returning the status line, the header lines and the body.
+fn request(address: SocketAddr, method: &str, path: &str) -> (String, Vec<String>, String) {
+    let response = support::request_with_body(address, method, path, "", "");
+    let (head, body) = response.split_once("\r\n\r\n").unwrap();
+    let mut lines = head.lines().map(|line| line.to_string());
+    let status_line = lines.next().unwrap();
+    return (status_line, lines.collect(), body.to_string());
+}
+
+/// Starts a job, checks the `202 Accepted` response and polls the status URL until the job
+/// finished, returning the final status body.
+fn run_job(address: SocketAddr, path: &str) -> String {
+    let (status_line, headers, body) = request(address, "POST", path);
+    assert_eq!(status_line, "HTTP/1.1 202 Accepted");
+    let location = headers
+        .iter()
+        .find_map(|header| header.strip_prefix("Location: "))
+        .unwrap()
+        .to_string();
+    let id = location.strip_prefix("/tasks/").unwrap();
+    assert_eq!(
+        body,
+        format!(
+            "{{\"id\":\"{}\",\"status\":\"pending\",\"status_url\":\"{}\"}}",
+            id, location
+        )
+    );
+
+    let started = Instant::now();
+    loop {
+        let (status_line, headers, body) = request(address, "GET", &location);
+        assert_eq!(status_line, "HTTP/1.1 200 OK");
+        if !headers
+            .iter()
+            .any(|header| header.starts_with("Retry-After: "))
+        {
+            return body.replace(id, "ID");
+        }
+        assert!(started.elapsed() < Duration::from_secs(5), "{}", body);
+        thread::sleep(Duration::from_millis(10));
+    }
+}
+
+#[test]
+fn runs_jobs_and_serves_their_status() {
+    let address = support::start_server(|server| {
+        server.jobs(
+            Jobs::new(MemoryJobStore::new(), Duration::from_secs(60))
+                .route_path("/tasks/")
+                .pool("reports", 1),
+        );
+        server.post("/reports", |mut c| {
+            return c.accept_async("reports", || {
+                thread::sleep(Duration::from_millis(50));
+                return Ok("report.pdf".to_string());
+            });
+        });
+        server.post("/broken", |mut c| {
+            return c.accept_async("default", || Err("disk full".to_string()));
+        });
+        server.post("/unknown", |mut c| {
+            return c.accept_async("missing", || Ok(String::new()));
+        });
+    });
+
+    assert_eq!(
+        run_job(address, "/reports"),
+        "{\"id\":\"ID\",\"status\":\"succeeded\",\"result\":\"report.pdf\"}"
+    );
+    assert_eq!(
+        run_job(address, "/broken"),
+        "{\"id\":\"ID\",\"status\":\"failed\",\"error\":\"disk full\"}"
+    );
+    assert_eq!(
+        request(address, "POST", "/unknown").0,
+        "HTTP/1.1 500 Internal Server Error"
+    );
+    assert_eq!(
+        request(address, "GET", "/tasks/nope").0,
+        "HTTP/1.1 404 Not Found"
+    );
+}
+
+#[test]
+fn keeps_unfinished_jobs_past_their_ttl() {
+    let address = support::start_server(|server| {
+        server.jobs(Jobs::new(MemoryJobStore::new(), Duration::from_millis(50)));
+        server.post("/slow", |mut c| {
+            return c.accept_async("default", || {
+                thread::sleep(Duration::from_millis(300));
+                return Ok("done".to_string());
+            });
+        });
+    });
+
+    let (_, headers, _) = request(address, "POST", "/slow");
+    let location = headers
+        .iter()
+        .find_map(|header| header.strip_prefix("Location: "))
+        .unwrap()
+        .to_string();
+
+    // the job runs for longer than the ttl, but only it's outcome expires
+    thread::sleep(Duration::from_millis(150));
+    let (status_line, _, body) = request(address, "GET", &location);
+    assert_eq!(status_line, "HTTP/1.1 200 OK");
+    assert!(body.contains("\"status\":\"running\""), "{}", body);
+
+    thread::sleep(Duration::from_millis(400));
+    assert_eq!(
+        request(address, "GET", &location).0,
+        "HTTP/1.1 404 Not Found"
+    );
+}
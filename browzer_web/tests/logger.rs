@@ -0,0 +1,105 @@
+//! End-to-end tests for request logging(`WebServer::request_logger`).
+
+mod support;
+
+use browzer_web::{
+    logger::{LogFormat, RequestLog, RequestLogger},
+    utils::{HttpMethod, HttpStatusCode},
+};
+use std::{
+    io::{self, Write},
+    net::SocketAddr,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+/// A writer appending to a shared buffer, standing in for a log file.
+#[derive(Clone, Default)]
+struct SharedBuffer(Arc<Mutex<Vec<u8>>>);
+
+impl Write for SharedBuffer {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.lock().unwrap().extend_from_slice(buf);
+        return Ok(buf.len());
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        return Ok(());
+    }
+}
+
+fn send(address: SocketAddr, method: &str, path: &str) {
+    support::request_with_body(address, method, path, "", "");
+}
+
+fn routes(server: &mut browzer_web::WebServer) {
+    server.get("/", |mut c| {
+        return c.send_string(HttpStatusCode::OK, "home");
+    });
+    server.get("/health", |mut c| {
+        return c.send_string(HttpStatusCode::OK, "ok");
+    });
+    server.post("/users/:id", |mut c| {
+        std::thread::sleep(Duration::from_millis(20));
+        return c.send_string(HttpStatusCode::Created, "created");
+    });
+}
+
+#[test]
+fn logs_every_answered_request() {
+    let logs: Arc<Mutex<Vec<RequestLog>>> = Arc::default();
+    let collected = Arc::clone(&logs);
+    let address = support::start_server(move |server| {
+        routes(server);
+        server.request_logger(
+            RequestLogger::custom(move |log| collected.lock().unwrap().push(log.clone()))
+                .skip_path("/health"),
+        );
+    });
+
+    send(address, "GET", "/?token=secret");
+    send(address, "GET", "/health");
+    send(address, "POST", "/users/7");
+    send(address, "GET", "/missing");
+
+    let logs = logs.lock().unwrap();
+    let summary: Vec<_> = logs
+        .iter()
+        .map(|log| (log.method.clone(), log.path.as_str(), log.status, log.size))
+        .collect();
+    assert_eq!(
+        summary,
+        vec![
+            (HttpMethod::GET, "/", 200, Some(4)),
+            (HttpMethod::POST, "/users/7", 201, Some(7)),
+            (HttpMethod::GET, "/missing", 404, Some(9)),
+        ]
+    );
+    assert!(logs[1].latency >= Duration::from_millis(20));
+}
+
+#[test]
+fn writes_json_lines() {
+    let buffer = SharedBuffer::default();
+    let writer = buffer.clone();
+    let address = support::start_server(move |server| {
+        routes(server);
+        server.request_logger(RequestLogger::new(LogFormat::Json).writer(writer));
+    });
+
+    send(address, "GET", "/");
+    send(address, "POST", "/users/1");
+
+    let output = String::from_utf8(buffer.0.lock().unwrap().clone()).unwrap();
+    let lines: Vec<serde_json::Value> = output
+        .lines()
+        .map(|line| serde_json::from_str(line).unwrap())
+        .collect();
+    assert_eq!(lines.len(), 2);
+    assert_eq!(lines[0]["method"], "GET");
+    assert_eq!(lines[0]["path"], "/");
+    assert_eq!(lines[0]["status"], 200);
+    assert_eq!(lines[0]["size"], 4);
+    assert_eq!(lines[1]["status"], 201);
+    assert!(lines[1]["latency_ms"].as_f64().unwrap() >= 20.0);
+}
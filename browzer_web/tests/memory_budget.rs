@@ -0,0 +1,56 @@
+//! End-to-end tests for per-request memory budgets (`WebServer::set_memory_budget`).
+
+mod support;
+
+use browzer_web::{utils::HttpStatusCode, WebServer};
+use std::net::SocketAddr;
+
+fn app(server: &mut WebServer) {
+    server.set_memory_budget(1024);
+    server.post("/echo/:times", |mut c| {
+        let body = c.request.body.clone().unwrap_or_default();
+        let times = c.params.get("times").cloned().unwrap_or_default();
+        let times = times.parse::<usize>().unwrap_or(1);
+        return c.send_string(HttpStatusCode::OK, &body.repeat(times));
+    });
+    server
+        .post("/upload", |mut c| {
+            c.send_string(HttpStatusCode::OK, "stored")
+        })
+        .memory_budget(64 * 1024);
+}
+
+/// Posts a body to a path, returning the status line of the response.
+fn post(address: SocketAddr, path: &str, body: &str) -> String {
+    let response = support::request_with_body(address, "POST", path, "", body);
+    return response.lines().next().unwrap_or_default().to_string();
+}
+
+#[test]
+fn requests_within_budget_are_served() {
+    let address = support::start_server(app);
+    assert_eq!(post(address, "/echo/2", "hello"), "HTTP/1.1 200 OK");
+}
+
+#[test]
+fn oversized_bodies_are_rejected_before_handling() {
+    let address = support::start_server(app);
+    assert_eq!(
+        post(address, "/echo/1", &"x".repeat(2048)),
+        "HTTP/1.1 413 Payload Too Large"
+    );
+    // the route overrides the budget of the server
+    assert_eq!(
+        post(address, "/upload", &"x".repeat(2048)),
+        "HTTP/1.1 200 OK"
+    );
+}
+
+#[test]
+fn responses_pushing_requests_over_budget_are_replaced() {
+    let address = support::start_server(app);
+    assert_eq!(
+        post(address, "/echo/100", "0123456789"),
+        "HTTP/1.1 500 Internal Server Error"
+    );
+}
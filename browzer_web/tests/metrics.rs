@@ -0,0 +1,174 @@
+//! End-to-end tests for the Prometheus metrics of a server(`metrics` module,
+//! `WebServer::enable_metrics`).
+
+mod support;
+
+use browzer_web::{metrics::Metrics, utils::HttpStatusCode};
+use std::{net::SocketAddr, time::Duration};
+
+/// Sends a request and returns the raw response.
+fn request(address: SocketAddr, method: &str, path: &str) -> String {
+    return support::request(address, method, path, "");
+}
+
+/// Returns the value of a sample of the metrics, by it's name and labels.
+fn sample(metrics: &str, series: &str) -> Option<f64> {
+    return metrics.lines().find_map(|line| {
+        let (name, value) = line.rsplit_once(' ')?;
+        return (name == series).then(|| value.parse().unwrap());
+    });
+}
+
+#[test]
+fn requests_are_counted_by_route_method_and_status() {
+    let address = support::start_server(|server| {
+        server.enable_metrics("/metrics");
+        server.get("/users/:id", |mut c| {
+            c.send_string(HttpStatusCode::OK, "user")
+        });
+        server.post("/users/:id", |mut c| {
+            c.send_string(HttpStatusCode::BadRequest, "invalid")
+        });
+    });
+
+    request(address, "GET", "/users/1");
+    request(address, "GET", "/users/2?tab=posts");
+    request(address, "POST", "/users/3");
+    request(address, "GET", "/missing/1");
+
+    let response = request(address, "GET", "/metrics");
+    assert!(response.starts_with("HTTP/1.1 200"), "{}", response);
+    assert!(
+        response.contains("Content-Type: text/plain; version=0.0.4; charset=utf-8"),
+        "{}",
+        response
+    );
+    let metrics = response.split_once("\r\n\r\n").unwrap().1;
+
+    // different paths of a route share it's series
+    assert_eq!(
+        sample(
+            metrics,
+            r#"browzer_requests_total{route="/users/:id",method="GET",status="200"}"#
+        ),
+        Some(2.0)
+    );
+    assert_eq!(
+        sample(
+            metrics,
+            r#"browzer_requests_total{route="/users/:id",method="POST",status="400"}"#
+        ),
+        Some(1.0)
+    );
+    assert_eq!(
+        sample(
+            metrics,
+            r#"browzer_requests_total{route="unmatched",method="GET",status="404"}"#
+        ),
+        Some(1.0)
+    );
+    assert_eq!(
+        sample(
+            metrics,
+            r#"browzer_request_duration_seconds_count{route="/users/:id",method="GET"}"#
+        ),
+        Some(2.0)
+    );
+    assert_eq!(
+        sample(
+            metrics,
+            r#"browzer_request_duration_seconds_bucket{route="/users/:id",method="GET",le="+Inf"}"#
+        ),
+        Some(2.0)
+    );
+
+    // the scrape itself is on a connection which is still open, the earlier ones may not be
+    // closed on the server's side yet
+    assert!(sample(metrics, "browzer_active_connections").unwrap() >= 1.0);
+    assert_eq!(sample(metrics, "browzer_queued_connections"), Some(0.0));
+    assert_eq!(sample(metrics, "browzer_workers"), Some(2.0));
+    assert_eq!(
+        sample(metrics, r#"browzer_worker_panics_total{phase="handling"}"#),
+        Some(0.0)
+    );
+    assert_eq!(
+        sample(metrics, r#"browzer_worker_panics_total{phase="job"}"#),
+        Some(0.0)
+    );
+}
+
+#[test]
+fn latency_buckets_are_cumulative() {
+    let metrics = Metrics::new().buckets(&[Duration::from_millis(100), Duration::from_millis(10)]);
+    metrics.record("/", "GET", 200, Duration::from_millis(5));
+    metrics.record("/", "GET", 200, Duration::from_millis(50));
+    metrics.record("/", "GET", 200, Duration::from_secs(1));
+
+    let text = metrics.render();
+    let bucket = |le: &str| {
+        sample(
+            &text,
+            &format!(
+                r#"browzer_request_duration_seconds_bucket{{route="/",method="GET",le="{}"}}"#,
+                le
+            ),
+        )
+    };
+    assert_eq!(bucket("0.01"), Some(1.0));
+    assert_eq!(bucket("0.1"), Some(2.0));
+    assert_eq!(bucket("+Inf"), Some(3.0));
+    assert_eq!(
+        sample(
+            &text,
+            r#"browzer_request_duration_seconds_sum{route="/",method="GET"}"#
+        ),
+        Some(1.055)
+    );
+    // there is no server to report on
+    assert!(!text.contains("browzer_active_connections"));
+}
+
+#[test]
+fn label_values_are_escaped() {
+    let metrics = Metrics::new();
+    metrics.record("/say/\"hi\"", "GET", 200, Duration::ZERO);
+    assert!(metrics
+        .render()
+        .contains(r#"browzer_requests_total{route="/say/\"hi\"",method="GET",status="200"} 1"#));
+}
+
+#[test]
+fn responses_generated_by_the_server_are_counted() {
+    let address = support::start_server(|server| {
+        server.enable_metrics("/metrics");
+        server.body_limit = Some(4);
+        server.post("/upload", |mut c| c.send_string(HttpStatusCode::OK, "ok"));
+    });
+
+    // the body is over the limit, the request never reaches the router
+    let raw = b"POST /upload HTTP/1.1\r\nHost: localhost\r\nContent-Length: 10\r\n\r\n0123456789";
+    let response = String::from_utf8(support::exchange(address, raw).unwrap()).unwrap();
+    assert!(response.starts_with("HTTP/1.1 413"), "{}", response);
+    // neither does a request whose head can't be parsed
+    let response = support::exchange(address, b"NONSENSE\r\n\r\n").unwrap();
+    assert!(String::from_utf8(response)
+        .unwrap()
+        .starts_with("HTTP/1.1 400"));
+
+    let response = request(address, "GET", "/metrics");
+    let metrics = response.split_once("\r\n\r\n").unwrap().1;
+    assert_eq!(
+        sample(
+            metrics,
+            r#"browzer_requests_total{route="/upload",method="POST",status="413"}"#
+        ),
+        Some(1.0)
+    );
+    assert_eq!(
+        sample(
+            metrics,
+            r#"browzer_requests_total{route="unmatched",method="unknown",status="400"}"#
+        ),
+        Some(1.0)
+    );
+}
@@ -0,0 +1,181 @@
+//! Tests for the middleware chains compiled for every route(`WebRouter::compile`), the order they
+//! run in and how they are listed by `WebRouter::routes`.
+
+mod support;
+
+use browzer_web::{
+    request::Request,
+    router::{ChainStep, RouteBuilder, RouteGroup, WebRouter},
+    utils::{HttpMethod, HttpStatusCode},
+};
+use std::sync::Arc;
+
+struct Hops(Vec<&'static str>);
+
+/// Appends a hop to the request's `Hops`.
+fn hop(
+    mut ctx: browzer_web::context::Context,
+    name: &'static str,
+) -> browzer_web::context::Context {
+    match ctx.get_mut::<Hops>() {
+        Some(hops) => hops.0.push(name),
+        None => {
+            ctx.set(Hops(vec![name]));
+        }
+    }
+    return ctx;
+}
+
+#[test]
+fn chains_run_global_group_route_policy_and_handler_in_order() {
+    let address = support::start_server(|server| {
+        server.middleware(|ctx| hop(ctx, "global"));
+        server.policy("staff", |ctx| {
+            return ctx.get::<Hops>().map(|hops| hops.0.join(",")).as_deref()
+                == Some("global,group,route");
+        });
+        let mut admin = server.group("/admin");
+        admin.middleware(|ctx| hop(ctx, "group"));
+        admin
+            .get("/stats", |mut c| {
+                let hops = c.get::<Hops>().map(|hops| hops.0.join(",")).unwrap();
+                return c.send_string(HttpStatusCode::OK, &hops);
+            })
+            .middleware(|ctx| hop(ctx, "route"))
+            .require_policy("staff");
+        admin
+            .get("/other", |mut c| c.send_string(HttpStatusCode::OK, "other"))
+            .require_policy("staff");
+    });
+
+    let response = support::request(address, "GET", "/admin/stats", "");
+    assert!(response.starts_with("HTTP/1.1 200"), "{}", response);
+    assert!(
+        response.ends_with("\r\n\r\nglobal,group,route"),
+        "{}",
+        response
+    );
+
+    // the policy sees only the hops of the middlewares before it
+    let response = support::request(address, "GET", "/admin/other", "");
+    assert!(response.starts_with("HTTP/1.1 403"), "{}", response);
+}
+
+#[test]
+fn routes_list_their_chains() {
+    let mut router = WebRouter::new();
+    router.add_middleware(|ctx| ctx);
+    router.add_middleware(|ctx| ctx);
+    {
+        let mut api = RouteGroup::new(Some(&mut router), "/api");
+        api.middleware(|ctx| ctx).require_policy("member");
+        api.get("/items", |mut c| c.send_string(HttpStatusCode::OK, "items"))
+            .middleware(|ctx| ctx)
+            .require_policy("editor");
+    }
+    router
+        .add("/".to_string(), HttpMethod::GET, |mut c| {
+            return c.send_string(HttpStatusCode::OK, "home");
+        })
+        .ok();
+
+    assert_eq!(router.compile(), 2);
+    let routes = router.routes();
+    assert_eq!(routes[0].path, "/");
+    assert_eq!(
+        routes[0].chain,
+        vec![
+            ChainStep::GlobalMiddleware(0),
+            ChainStep::GlobalMiddleware(1),
+            ChainStep::Handler,
+        ]
+    );
+    assert_eq!(routes[1].path, "/api/items");
+    assert_eq!(
+        routes[1].chain,
+        vec![
+            ChainStep::GlobalMiddleware(0),
+            ChainStep::GlobalMiddleware(1),
+            ChainStep::GroupMiddleware(0),
+            ChainStep::RouteMiddleware(0),
+            ChainStep::Policy("member".to_string()),
+            ChainStep::Policy("editor".to_string()),
+            ChainStep::Handler,
+        ]
+    );
+    let steps: Vec<String> = routes[1]
+        .chain
+        .iter()
+        .map(|step| step.to_string())
+        .collect();
+    assert_eq!(steps[2..4], ["group middleware 0", "route middleware 0"]);
+}
+
+#[test]
+fn an_aborting_middleware_skips_the_rest_of_the_chain() {
+    let address = support::start_server(|server| {
+        server.middleware(|mut ctx| {
+            if ctx.request.path.starts_with("/global") {
+                let response = ctx.send_string(HttpStatusCode::Forbidden, "global");
+                ctx.abort(response);
+            }
+            return ctx;
+        });
+        server
+            .get("/route", |mut c| {
+                return c.send_string(HttpStatusCode::OK, "handler");
+            })
+            .middleware(|mut ctx| {
+                let response = ctx.send_string(HttpStatusCode::Unauthorized, "route");
+                ctx.abort(response);
+                return ctx;
+            })
+            .middleware(|_| panic!("runs after an aborting middleware"));
+        server.get("/global", |mut c| {
+            return c.send_string(HttpStatusCode::OK, "handler");
+        });
+    });
+
+    let response = support::request(address, "GET", "/route", "");
+    assert!(response.starts_with("HTTP/1.1 401"), "{}", response);
+    assert!(response.ends_with("\r\n\r\nroute"), "{}", response);
+
+    let response = support::request(address, "GET", "/global", "");
+    assert!(response.starts_with("HTTP/1.1 403"), "{}", response);
+    assert!(response.ends_with("\r\n\r\nglobal"), "{}", response);
+}
+
+#[test]
+fn compiling_picks_up_middlewares_and_policies_added_after_a_route_was_requested() {
+    let mut router = WebRouter::new();
+    router
+        .add("/".to_string(), HttpMethod::GET, |mut c| {
+            return c.send_string(HttpStatusCode::OK, "home");
+        })
+        .map(|route| RouteBuilder::new(Some(route)).require_policy("open"))
+        .ok();
+    let request = || {
+        let mut request = Request::default();
+        request.path = "/".to_string();
+        return request;
+    };
+    // the policy isn't registered yet
+    let response = router.handle_request(request()).unwrap();
+    assert_eq!(response.status_code, HttpStatusCode::Forbidden);
+
+    router.add_middleware(|ctx| ctx);
+    router
+        .policies
+        .insert("open".to_string(), Arc::new(Box::new(|_| true)));
+    router.compile();
+    assert_eq!(
+        router.routes()[0].chain,
+        vec![
+            ChainStep::GlobalMiddleware(0),
+            ChainStep::Policy("open".to_string()),
+            ChainStep::Handler,
+        ]
+    );
+    let response = router.handle_request(request()).unwrap();
+    assert_eq!(response.body, "home");
+}
@@ -0,0 +1,66 @@
+//! End-to-end tests for serving several addresses from one server(`WebServer::bind`).
+
+mod support;
+
+use browzer_web::{utils::HttpStatusCode, WebServer};
+use std::{
+    net::{SocketAddr, TcpStream},
+    thread,
+};
+
+/// Sends a GET request and returns the raw response.
+fn get(address: SocketAddr, path: &str) -> String {
+    return support::request(address, "GET", path, "");
+}
+
+/// Creates a server on an ephemeral port of the loopback interface.
+fn server() -> WebServer {
+    let mut server = WebServer::new("127.0.0.1:0".to_string(), 2);
+    server.hide_banner = true;
+    server.get("/", |mut c| c.send_string(HttpStatusCode::OK, "hello"));
+    return server;
+}
+
+#[test]
+fn every_address_is_served_by_the_same_router() {
+    let mut server = server();
+    let second = server.bind("127.0.0.1:0").unwrap();
+    // IPv6 may be unavailable where the tests run
+    let ipv6 = server.bind("[::1]:0").ok();
+    let addresses = server.local_addrs();
+    assert_eq!(addresses[0], server.listener.local_addr().unwrap());
+    assert_eq!(addresses[1], second);
+    assert_ne!(addresses[0], second);
+    thread::spawn(move || server.listen());
+
+    for address in addresses {
+        let response = get(address, "/");
+        assert!(response.starts_with("HTTP/1.1 200"), "{}", response);
+    }
+    if let Some(ipv6) = ipv6 {
+        assert!(get(ipv6, "/").starts_with("HTTP/1.1 200"));
+    }
+}
+
+#[test]
+fn shutdown_stops_every_address() {
+    let mut server = server();
+    let second = server.bind("127.0.0.1:0").unwrap();
+    let first = server.listener.local_addr().unwrap();
+    let shutdown = server.shutdown_handle();
+    let listening = thread::spawn(move || server.listen());
+    assert!(get(second, "/").starts_with("HTTP/1.1 200"));
+
+    shutdown.shutdown();
+    listening.join().unwrap();
+    assert!(TcpStream::connect(first).is_err());
+    assert!(TcpStream::connect(second).is_err());
+}
+
+#[test]
+fn binding_an_address_in_use_fails() {
+    let mut server = server();
+    let address = server.listener.local_addr().unwrap();
+    assert!(server.bind(&address.to_string()).is_err());
+    assert_eq!(server.local_addrs(), vec![address]);
+}
@@ -0,0 +1,236 @@
+//! End-to-end tests for the `Retry-After` header of the `503 Service Unavailable` responses a
+//! server sends under overload(`overload` module, `WebServer::retry_after`).
+
+mod support;
+
+use browzer_web::{limits::QueuePolicy, overload::OverloadReason, utils::HttpStatusCode};
+use std::{
+    collections::HashSet,
+    io::{Read, Write},
+    net::TcpStream,
+    sync::{mpsc, Arc, Mutex},
+    thread,
+    time::{Duration, Instant},
+};
+
+/// Returns the value of a header of a raw response.
+fn header(response: &str, name: &str) -> Option<String> {
+    let head = response
+        .split_once("\r\n\r\n")
+        .map_or(response, |(head, _)| head);
+    return head.lines().find_map(|line| {
+        let (key, value) = line.split_once(": ")?;
+        return key.eq_ignore_ascii_case(name).then(|| value.to_string());
+    });
+}
+
+#[test]
+fn shed_connections_are_told_when_to_retry() {
+    let reasons = Arc::new(Mutex::new(vec![]));
+    let seen = Arc::clone(&reasons);
+    let address = support::start_server(move |server| {
+        server.max_connections = 1;
+        server.retry_after(move |state| {
+            seen.lock()
+                .unwrap()
+                .push((state.reason, state.active_connections));
+            return Some(Duration::from_millis(6500));
+        });
+        server.get("/", |mut c| c.send_string(HttpStatusCode::OK, "hello"));
+    });
+
+    // an idle keep-alive connection takes the only slot
+    let mut first = TcpStream::connect(address).unwrap();
+    first
+        .write_all(b"GET / HTTP/1.1\r\nHost: localhost\r\n\r\n")
+        .unwrap();
+    let mut buffer = [0; 1024];
+    let read = first.read(&mut buffer).unwrap();
+    assert!(String::from_utf8_lossy(&buffer[..read]).starts_with("HTTP/1.1 200"));
+
+    // the connection over the cap is answered right away, before it's request is read
+    let mut second = TcpStream::connect(address).unwrap();
+    let mut response = String::new();
+    second.read_to_string(&mut response).unwrap();
+    assert!(response.starts_with("HTTP/1.1 503"), "{}", response);
+    // fractions of a second are rounded up
+    assert_eq!(header(&response, "Retry-After").unwrap(), "7");
+    assert_eq!(
+        *reasons.lock().unwrap(),
+        vec![(OverloadReason::ConnectionLimit, 1)]
+    );
+}
+
+#[test]
+fn timed_out_requests_get_the_default_retry_after() {
+    let address = support::start_server(|server| {
+        server.request_timeout = Some(Duration::from_millis(100));
+        server.get("/slow", |mut c| {
+            thread::sleep(Duration::from_millis(500));
+            return c.send_string(HttpStatusCode::OK, "done");
+        });
+    });
+
+    let response = support::request(address, "GET", "/slow", "");
+    assert!(response.starts_with("HTTP/1.1 503"), "{}", response);
+    let seconds: u64 = header(&response, "Retry-After").unwrap().parse().unwrap();
+    assert!((1..=60).contains(&seconds), "{}", seconds);
+}
+
+#[test]
+fn timed_out_handlers_are_cancelled_on_their_worker() {
+    let threads = Arc::new(Mutex::new(HashSet::new()));
+    let seen = Arc::clone(&threads);
+    let address = support::start_server(move |server| {
+        server.request_timeout = Some(Duration::from_millis(50));
+        server.get("/slow", move |mut c| {
+            seen.lock().unwrap().insert(thread::current().id());
+            // the handler learns about the timeout instead of being left running
+            let cancellation = c.cancellation_token();
+            let deadline = Instant::now() + Duration::from_secs(5);
+            while !cancellation.is_cancelled() && Instant::now() < deadline {
+                thread::sleep(Duration::from_millis(5));
+            }
+            return c.send_string(HttpStatusCode::OK, "done");
+        });
+    });
+
+    let raw = b"GET /slow HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n";
+    let started = Instant::now();
+    for _ in 0..6 {
+        // the request is sent without shutting down the connection, which would read as a hangup
+        let mut stream = TcpStream::connect(address).unwrap();
+        stream.write_all(raw).unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+        assert!(response.starts_with("HTTP/1.1 503"), "{}", response);
+    }
+    assert!(started.elapsed() < Duration::from_secs(5));
+    // the handlers ran on the workers of the server rather than on a thread each
+    assert!(threads.lock().unwrap().len() <= 2);
+}
+
+#[test]
+fn requests_are_turned_away_in_maintenance_mode() {
+    let handle = Arc::new(Mutex::new(None));
+    let shared = Arc::clone(&handle);
+    let address = support::start_server(move |server| {
+        *shared.lock().unwrap() = Some(server.maintenance_handle());
+        server.get("/", |mut c| c.send_string(HttpStatusCode::OK, "hello"));
+    });
+    let maintenance = handle.lock().unwrap().take().unwrap();
+
+    maintenance.enable();
+    let response = support::request(address, "GET", "/", "");
+    assert!(response.starts_with("HTTP/1.1 503"), "{}", response);
+    // the backlog says nothing about when the maintenance is over
+    assert_eq!(header(&response, "Retry-After").unwrap(), "60");
+    assert_eq!(header(&response, "Connection").unwrap(), "close");
+
+    maintenance.disable();
+    let response = support::request(address, "GET", "/", "");
+    assert!(response.starts_with("HTTP/1.1 200"), "{}", response);
+}
+
+#[test]
+fn the_drain_rate_counts_completed_requests() {
+    let states = Arc::new(Mutex::new(vec![]));
+    let seen = Arc::clone(&states);
+    let handle = Arc::new(Mutex::new(None));
+    let shared = Arc::clone(&handle);
+    let address = support::start_server(move |server| {
+        *shared.lock().unwrap() = Some(server.maintenance_handle());
+        server.retry_after(move |state| {
+            seen.lock().unwrap().push((state.reason, state.drain_rate));
+            return None;
+        });
+        server.get("/", |mut c| c.send_string(HttpStatusCode::OK, "hello"));
+    });
+    let maintenance = handle.lock().unwrap().take().unwrap();
+
+    // the requests of a keep-alive connection are completed long before the connection is closed
+    let mut stream = TcpStream::connect(address).unwrap();
+    let mut buffer = [0; 1024];
+    for _ in 0..5 {
+        stream
+            .write_all(b"GET / HTTP/1.1\r\nHost: localhost\r\n\r\n")
+            .unwrap();
+        let read = stream.read(&mut buffer).unwrap();
+        assert!(String::from_utf8_lossy(&buffer[..read]).starts_with("HTTP/1.1 200"));
+    }
+
+    maintenance.enable();
+    stream
+        .write_all(b"GET / HTTP/1.1\r\nHost: localhost\r\n\r\n")
+        .unwrap();
+    let mut response = String::new();
+    stream.read_to_string(&mut response).unwrap();
+    assert!(response.starts_with("HTTP/1.1 503"), "{}", response);
+    // the policy left the header out
+    assert_eq!(header(&response, "Retry-After"), None);
+
+    let states = states.lock().unwrap();
+    assert_eq!(states.len(), 1);
+    let (reason, drain_rate) = states[0];
+    assert_eq!(reason, OverloadReason::Maintenance);
+    // five requests within the first second, which counts as a whole one
+    assert!((1.0..=5.0).contains(&drain_rate), "{}", drain_rate);
+}
+
+#[test]
+fn connections_over_the_queue_limit_are_told_when_to_retry() {
+    let reasons = Arc::new(Mutex::new(vec![]));
+    let seen = Arc::clone(&reasons);
+    let (started, started_receiver) = mpsc::channel();
+    let (release, release_receiver) = mpsc::channel::<()>();
+    let release_receiver = Arc::new(Mutex::new(release_receiver));
+    let address = support::start_server(move |server| {
+        server.queue_limit(1, QueuePolicy::Reject);
+        server.retry_after(move |state| {
+            seen.lock()
+                .unwrap()
+                .push((state.reason, state.active_connections));
+            return Some(Duration::from_secs(3));
+        });
+        server.get("/busy", move |mut c| {
+            started.send(()).unwrap();
+            let _ = release_receiver.lock().unwrap().recv();
+            return c.send_string(HttpStatusCode::OK, "done");
+        });
+    });
+    let send = || {
+        let mut stream = TcpStream::connect(address).unwrap();
+        stream
+            .write_all(b"GET /busy HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n")
+            .unwrap();
+        return stream;
+    };
+
+    // both workers are busy and a third connection fills the queue
+    let mut waiting = vec![];
+    for _ in 0..2 {
+        waiting.push(send());
+        started_receiver
+            .recv_timeout(Duration::from_secs(10))
+            .unwrap();
+    }
+    waiting.push(send());
+    thread::sleep(Duration::from_millis(200));
+
+    let mut rejected = TcpStream::connect(address).unwrap();
+    let mut response = String::new();
+    rejected.read_to_string(&mut response).unwrap();
+    assert!(response.starts_with("HTTP/1.1 503"), "{}", response);
+    assert_eq!(header(&response, "Retry-After").unwrap(), "3");
+    assert_eq!(
+        *reasons.lock().unwrap(),
+        vec![(OverloadReason::QueueFull, 3)]
+    );
+
+    drop(release);
+    for mut stream in waiting {
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+        assert!(response.ends_with("done"), "{}", response);
+    }
+}
@@ -0,0 +1,60 @@
+//! End-to-end tests for the pages generated by the framework(`WebServer::page_templates`).
+
+mod support;
+
+use browzer_web::{pages::PageTemplates, utils::HttpStatusCode};
+use std::net::SocketAddr;
+
+/// Sends a request, returning the status line, the header lines and the body.
+fn request(address: SocketAddr, method: &str, path: &str) -> (String, Vec<String>, String) {
+    let response = support::request_with_body(address, method, path, "", "");
+    let (head, body) = response.split_once("\r\n\r\n").unwrap();
+    let mut lines = head.lines().map(|line| line.to_string());
+    let status_line = lines.next().unwrap();
+    return (status_line, lines.collect(), body.to_string());
+}
+
+#[test]
+fn renders_error_pages_and_the_route_listing_from_the_templates() {
+    let address = support::start_server(|server| {
+        server.page_templates(
+            PageTemplates::new()
+                .layout("<title>{{title}} | Acme</title>\n{{content}}")
+                .error_page("<h1>{{status}}: {{reason}}</h1>\n<p>{{path}}</p>\n"),
+        );
+        server.enable_route_help("/_routes");
+        server.get("/users", |mut c| {
+            return c.send_string(HttpStatusCode::OK, "users");
+        });
+    });
+
+    let (status_line, headers, body) = request(address, "GET", "/<none>");
+    assert_eq!(status_line, "HTTP/1.1 404 Not Found");
+    assert!(headers.contains(&"Content-Type: text/html; charset=utf-8".to_string()));
+    assert_eq!(
+        body,
+        "<title>404 Not Found | Acme</title>\n<h1>404: Not Found</h1>\n<p>/&lt;none&gt;</p>\n"
+    );
+
+    let (status_line, _, body) = request(address, "DELETE", "/users");
+    assert_eq!(status_line, "HTTP/1.1 405 Method Not Allowed");
+    assert!(body.starts_with("<title>405 Method Not Allowed | Acme</title>\n"));
+
+    let (status_line, _, body) = request(address, "GET", "/_routes");
+    assert_eq!(status_line, "HTTP/1.1 200 OK");
+    assert!(body.starts_with("<title>Routes | Acme</title>\n<h1>Routes</h1>\n"));
+}
+
+#[test]
+fn page_templates_use_the_template_syntax() {
+    let templates = PageTemplates::new()
+        .layout("<title>{{ title | upper }}</title>{{ content }}")
+        .error_page("{% if status == 404 %}No {{ path }}{% else %}{{ reason }}{% endif %}")
+        // an invalid template keeps the current one
+        .error_page("{% if %}");
+
+    let response = templates.error_response(HttpStatusCode::NotFound, "/<a>");
+    assert_eq!(response.body, "<title>404 NOT FOUND</title>No /&lt;a&gt;");
+    let response = templates.error_response(HttpStatusCode::Gone, "/a");
+    assert_eq!(response.body, "<title>410 GONE</title>Gone");
+}
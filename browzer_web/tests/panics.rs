@@ -0,0 +1,63 @@
+//! End-to-end tests for answering panicking route handlers with `500 Internal Server Error`.
+
+mod support;
+
+use browzer_web::{panics::PanicPhase, response::Response, utils::HttpStatusCode};
+use std::{
+    net::SocketAddr,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+/// Sends a request, returning the response head and body.
+fn get(address: SocketAddr, path: &str) -> (String, String) {
+    let raw = format!(
+        "GET {} HTTP/1.1\r\nHost: localhost\r\nConnection: keep-alive\r\n\r\n",
+        path
+    );
+    let response = support::exchange(address, raw.as_bytes()).unwrap();
+    let response = String::from_utf8(response).unwrap();
+    let (head, body) = response.split_once("\r\n\r\n").unwrap();
+    return (head.to_string(), body.to_string());
+}
+
+#[test]
+fn answers_panics_with_a_server_error() {
+    let reports = Arc::new(Mutex::new(Vec::new()));
+    let hook_reports = Arc::clone(&reports);
+    let address = support::start_server(move |server| {
+        server.keep_alive_timeout = Some(Duration::from_secs(1));
+        server.on_panic(move |report| {
+            hook_reports
+                .lock()
+                .unwrap()
+                .push((report.phase, report.message.map(|m| m.to_string())));
+        });
+        server.error_handler(|status_code, path| {
+            return Response::new(status_code, format!("failed to serve {}", path));
+        });
+        server.get("/boom", |_| {
+            panic!("boom");
+        });
+        server.get("/ok", |mut c| {
+            return c.send_string(HttpStatusCode::OK, "ok");
+        });
+    });
+
+    let (head, body) = get(address, "/boom");
+    assert!(head.starts_with("HTTP/1.1 500 Internal Server Error\r\n"));
+    // the connection isn't reused after a panic, even though the client asked to keep it alive
+    assert!(head.contains("\r\nConnection: close"));
+    assert_eq!(body, "failed to serve /boom");
+    assert_eq!(
+        *reports.lock().unwrap(),
+        vec![(PanicPhase::Handling, Some("boom".to_string()))]
+    );
+
+    // the worker threads keep serving requests
+    for _ in 0..4 {
+        let (head, body) = get(address, "/ok");
+        assert!(head.starts_with("HTTP/1.1 200 OK\r\n"));
+        assert_eq!(body, "ok");
+    }
+}
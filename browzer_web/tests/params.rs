@@ -0,0 +1,74 @@
+//! End-to-end tests for route and query parameters, their decoding and typed access.
+
+mod support;
+
+use browzer_web::utils::{HttpMethod, HttpStatusCode};
+use std::net::SocketAddr;
+
+/// Sends a GET request, returning the status line and the body.
+fn get(address: SocketAddr, path: &str) -> (String, String) {
+    let response = support::request(address, "GET", path, "");
+    let (head, body) = response.split_once("\r\n\r\n").unwrap();
+    return (head.lines().next().unwrap().to_string(), body.to_string());
+}
+
+#[test]
+fn decodes_route_and_query_parameters() {
+    let address = support::start_server(|server| {
+        server.get("/users/:name", |mut c| {
+            let body = format!(
+                "{}|{}|{}|{}",
+                c.params["name"],
+                c.raw_params["name"],
+                c.query_params.get("q").cloned().unwrap_or_default(),
+                c.raw_query_params.get("q").cloned().unwrap_or_default()
+            );
+            return c.send_string(HttpStatusCode::OK, &body);
+        });
+    });
+
+    assert_eq!(
+        get(address, "/users/John%20Doe?q=a+b%2Bc%26d"),
+        (
+            "HTTP/1.1 200 OK".to_string(),
+            "John Doe|John%20Doe|a b+c&d|a+b%2Bc%26d".to_string()
+        )
+    );
+    // a plus sign only stands for a space in the query string
+    assert_eq!(get(address, "/users/a+b").1, "a+b|a+b||");
+    assert_eq!(
+        get(address, "/users/%E2%9C%93?q=%zz").0,
+        "HTTP/1.1 400 Bad Request"
+    );
+    assert_eq!(get(address, "/users/%FF").0, "HTTP/1.1 400 Bad Request");
+}
+
+#[test]
+fn parses_repeated_and_typed_query_parameters() {
+    let address = support::start_server(|server| {
+        server
+            .register_route(
+                "/posts",
+                HttpMethod::GET,
+                |mut c| -> Result<_, browzer_web::prelude::ErrorResponse> {
+                    let page = c.query_parse::<u32>("page")?;
+                    let tags = c.query_all("tag").join(",");
+                    return Ok(c.send_string(HttpStatusCode::OK, &format!("{} {}", page, tags)));
+                },
+            )
+            .unwrap();
+    });
+
+    assert_eq!(
+        get(address, "/posts?page=2&tag=rust&tag=web+dev&&"),
+        ("HTTP/1.1 200 OK".to_string(), "2 rust,web dev".to_string())
+    );
+    assert_eq!(
+        get(address, "/posts?page=two"),
+        (
+            "HTTP/1.1 400 Bad Request".to_string(),
+            "Invalid query parameter page: invalid digit found in string".to_string()
+        )
+    );
+    assert_eq!(get(address, "/posts").1, "Missing query parameter: page");
+}
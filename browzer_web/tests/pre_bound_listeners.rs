@@ -0,0 +1,35 @@
+//! End-to-end tests for serving sockets bound before the server was created
+//! (`WebServer::from_listener`, `WebServer::add_listener`).
+
+mod support;
+
+use browzer_web::{utils::HttpStatusCode, WebServer};
+use std::{
+    net::{SocketAddr, TcpListener},
+    thread,
+};
+
+/// Sends a GET request and returns the raw response.
+fn get(address: SocketAddr) -> String {
+    return support::request(address, "GET", "/", "");
+}
+
+#[test]
+fn pre_bound_listeners_are_served() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let address = listener.local_addr().unwrap();
+    // sockets handed over by a service manager may be non-blocking
+    let second = TcpListener::bind("127.0.0.1:0").unwrap();
+    second.set_nonblocking(true).unwrap();
+
+    let mut server = WebServer::from_listener(listener, 2);
+    server.hide_banner = true;
+    server.get("/", |mut c| c.send_string(HttpStatusCode::OK, "hello"));
+    let second = server.add_listener(second).unwrap();
+    assert_eq!(server.local_addr().unwrap(), address);
+    assert_eq!(server.local_addrs(), vec![address, second]);
+    thread::spawn(move || server.listen());
+
+    assert!(get(address).ends_with("hello"));
+    assert!(get(second).ends_with("hello"));
+}
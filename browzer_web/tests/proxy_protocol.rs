@@ -0,0 +1,83 @@
+//! End-to-end tests for PROXY protocol headers(`WebServer::proxy_protocol`).
+
+mod support;
+
+use browzer_web::utils::HttpStatusCode;
+use std::net::SocketAddr;
+
+const REQUEST: &[u8] = b"GET /ip HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n";
+
+/// Sends a PROXY protocol preamble followed by a request, returning the raw response.
+fn send(address: SocketAddr, preamble: &[u8]) -> String {
+    let raw = [preamble, REQUEST].concat();
+    // a dropped connection may be reset before the client read anything
+    let response = support::exchange(address, &raw).unwrap_or_default();
+    return String::from_utf8(response).unwrap();
+}
+
+fn body(response: &str) -> &str {
+    return response.split_once("\r\n\r\n").unwrap().1;
+}
+
+#[test]
+fn takes_the_client_address_from_the_header() {
+    let address = support::start_server(|server| {
+        server.proxy_protocol(true);
+        server.get("/ip", |mut c| {
+            let remote_addr = c
+                .remote_addr()
+                .map(|addr| addr.to_string())
+                .unwrap_or_default();
+            return c.send_string(HttpStatusCode::OK, &remote_addr);
+        });
+    });
+
+    let response = send(address, b"PROXY TCP4 203.0.113.7 192.0.2.1 56324 443\r\n");
+    assert_eq!(body(&response), "203.0.113.7:56324");
+    let response = send(address, b"PROXY TCP6 2001:db8::7 2001:db8::1 56324 443\r\n");
+    assert_eq!(body(&response), "[2001:db8::7]:56324");
+
+    // version 2, TCP over IPv4 from 198.51.100.9:4000 to 192.0.2.1:443
+    let mut v2 = b"\r\n\r\n\0\r\nQUIT\n\x21\x11\x00\x0c".to_vec();
+    v2.extend_from_slice(&[198, 51, 100, 9, 192, 0, 2, 1, 0x0f, 0xa0, 0x01, 0xbb]);
+    assert_eq!(body(&send(address, &v2)), "198.51.100.9:4000");
+
+    // health checks of the load balancer itself keep the address of the connection
+    let response = send(address, b"PROXY UNKNOWN\r\n");
+    assert!(body(&response).starts_with("127.0.0.1:"));
+    let local = b"\r\n\r\n\0\r\nQUIT\n\x20\x00\x00\x00";
+    assert!(body(&send(address, local)).starts_with("127.0.0.1:"));
+
+    // connections without a valid header are closed without a response
+    assert_eq!(send(address, b""), "");
+    assert_eq!(
+        send(address, b"PROXY TCP4 203.0.113.7 2001:db8::1 56324 443\r\n"),
+        ""
+    );
+}
+
+#[test]
+fn expects_the_header_on_configured_listeners_only() {
+    let mut balanced = None;
+    let direct = support::start_server(|server| {
+        let address = server.bind("127.0.0.1:0").unwrap();
+        server.proxy_protocol_on(address);
+        balanced = Some(address);
+        server.get("/ip", |mut c| {
+            let remote_addr = c
+                .remote_addr()
+                .map(|addr| addr.ip().to_string())
+                .unwrap_or_default();
+            return c.send_string(HttpStatusCode::OK, &remote_addr);
+        });
+    });
+    let balanced = balanced.unwrap();
+
+    let preamble = b"PROXY TCP4 203.0.113.7 192.0.2.1 56324 443\r\n";
+    assert_eq!(body(&send(balanced, preamble)), "203.0.113.7");
+    assert_eq!(send(balanced, b""), "");
+
+    // clients connecting directly send no header, and can't claim an address with one
+    assert_eq!(body(&send(direct, b"")), "127.0.0.1");
+    assert!(send(direct, preamble).starts_with("HTTP/1.1 400 Bad Request"));
+}
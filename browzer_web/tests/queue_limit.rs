@@ -0,0 +1,139 @@
+//! End-to-end tests for bounding the queue of connections waiting for a worker
+//! (`WebServer::queue_limit`).
+
+use browzer_web::{limits::QueuePolicy, utils::HttpStatusCode, WebServer};
+use std::{
+    io::{self, Read, Write},
+    net::{SocketAddr, TcpStream},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    thread,
+    time::{Duration, Instant},
+};
+
+/// Starts a server with a single worker, whose `/busy` route blocks until `release` is set.
+fn start_server(
+    policy: QueuePolicy,
+    started: Arc<AtomicBool>,
+    release: Arc<AtomicBool>,
+) -> SocketAddr {
+    let mut server = WebServer::new("127.0.0.1:0".to_string(), 1);
+    server.hide_banner = true;
+    server.queue_limit(1, policy);
+    server.get("/busy", move |mut c| {
+        started.store(true, Ordering::SeqCst);
+        while !release.load(Ordering::SeqCst) {
+            thread::sleep(Duration::from_millis(5));
+        }
+        return c.send_string(HttpStatusCode::OK, "done");
+    });
+    let address = server.local_addr().unwrap();
+    thread::spawn(move || server.listen());
+    return address;
+}
+
+/// Opens a connection and sends a request on it, without waiting for the response.
+fn send(address: SocketAddr, path: &str) -> TcpStream {
+    let mut stream = TcpStream::connect(address).unwrap();
+    stream
+        .write_all(
+            format!(
+                "GET {} HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n",
+                path
+            )
+            .as_bytes(),
+        )
+        .unwrap();
+    return stream;
+}
+
+/// Reads a response until the server closes the connection.
+fn read_response(mut stream: TcpStream) -> String {
+    stream
+        .set_read_timeout(Some(Duration::from_secs(10)))
+        .unwrap();
+    let mut response = String::new();
+    stream.read_to_string(&mut response).unwrap();
+    return response;
+}
+
+/// Waits until the flag is set, failing the test after a few seconds.
+fn wait_for(flag: &AtomicBool) {
+    let deadline = Instant::now() + Duration::from_secs(10);
+    while !flag.load(Ordering::SeqCst) {
+        assert!(Instant::now() < deadline, "timed out");
+        thread::sleep(Duration::from_millis(5));
+    }
+}
+
+#[test]
+fn connections_over_the_queue_limit_are_rejected() {
+    let (started, release) = (
+        Arc::new(AtomicBool::new(false)),
+        Arc::new(AtomicBool::new(false)),
+    );
+    let address = start_server(
+        QueuePolicy::Reject,
+        Arc::clone(&started),
+        Arc::clone(&release),
+    );
+
+    // the worker is busy with the first connection and the second one fills the queue
+    let busy = send(address, "/busy");
+    wait_for(&started);
+    let queued = send(address, "/busy");
+    thread::sleep(Duration::from_millis(200));
+
+    // the connection over the limit is answered right away, before it's request is read
+    let rejected = read_response(TcpStream::connect(address).unwrap());
+    assert!(
+        rejected.starts_with("HTTP/1.1 503 Service Unavailable"),
+        "{}",
+        rejected
+    );
+    assert!(rejected.contains("Retry-After: "), "{}", rejected);
+
+    release.store(true, Ordering::SeqCst);
+    assert!(read_response(busy).ends_with("done"));
+    assert!(read_response(queued).ends_with("done"));
+}
+
+#[test]
+fn connections_over_the_queue_limit_wait_with_the_block_policy() {
+    let (started, release) = (
+        Arc::new(AtomicBool::new(false)),
+        Arc::new(AtomicBool::new(false)),
+    );
+    let address = start_server(
+        QueuePolicy::Block,
+        Arc::clone(&started),
+        Arc::clone(&release),
+    );
+
+    let busy = send(address, "/busy");
+    wait_for(&started);
+    let queued = send(address, "/busy");
+    thread::sleep(Duration::from_millis(200));
+
+    // the third connection waits in the backlog of the listening socket
+    let mut waiting = send(address, "/busy");
+    waiting
+        .set_read_timeout(Some(Duration::from_millis(200)))
+        .unwrap();
+    let error = waiting.read(&mut [0; 64]).unwrap_err();
+    assert!(
+        matches!(
+            error.kind(),
+            io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut
+        ),
+        "{}",
+        error
+    );
+
+    release.store(true, Ordering::SeqCst);
+    assert!(read_response(busy).ends_with("done"));
+    assert!(read_response(queued).ends_with("done"));
+    assert!(read_response(waiting).ends_with("done"));
+}
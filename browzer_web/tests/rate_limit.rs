@@ -0,0 +1,85 @@
+//! End-to-end tests for per-client rate limiting (`WebServer::rate_limit`).
+
+mod support;
+
+use browzer_web::{
+    rate_limit::{MemoryRateLimitStore, Quota, RateLimit, RateLimitStore},
+    utils::HttpStatusCode,
+    WebServer,
+};
+use std::{net::SocketAddr, thread, time::Duration};
+
+fn app(server: &mut WebServer) {
+    server.get("/", |mut c| c.send_string(HttpStatusCode::OK, "hi"));
+}
+
+/// Sends a request with extra headers, returning the head of the response.
+fn get(address: SocketAddr, headers: &str) -> String {
+    let response = support::request(address, "GET", "/", headers);
+    return response.split_once("\r\n\r\n").unwrap().0.to_string();
+}
+
+#[test]
+fn clients_over_their_quota_get_too_many_requests() {
+    let address = support::start_server(|server| {
+        server.rate_limit(RateLimit::new(2, Duration::from_secs(60)));
+        app(server);
+    });
+
+    assert!(get(address, "").starts_with("HTTP/1.1 200 OK"));
+    assert!(get(address, "").starts_with("HTTP/1.1 200 OK"));
+    let limited = get(address, "");
+    assert!(limited.starts_with("HTTP/1.1 429 Too Many Requests"));
+    // a token is refilled every 30 seconds
+    assert!(limited.contains("Retry-After: 30"));
+}
+
+#[test]
+fn custom_keys_limit_clients_separately() {
+    let address = support::start_server(|server| {
+        server.rate_limit(
+            RateLimit::new(1, Duration::from_secs(60))
+                .key_by(|request| request.header("X-Api-Key").cloned()),
+        );
+        app(server);
+    });
+
+    assert!(get(address, "X-Api-Key: a\r\n").starts_with("HTTP/1.1 200 OK"));
+    assert!(get(address, "X-Api-Key: a\r\n").starts_with("HTTP/1.1 429"));
+    assert!(get(address, "X-Api-Key: b\r\n").starts_with("HTTP/1.1 200 OK"));
+    // requests without a key aren't limited
+    assert!(get(address, "").starts_with("HTTP/1.1 200 OK"));
+    assert!(get(address, "").starts_with("HTTP/1.1 200 OK"));
+}
+
+#[test]
+fn a_quota_of_zero_limits_every_request() {
+    let address = support::start_server(|server| {
+        server.rate_limit(RateLimit::new(0, Duration::from_secs(60)));
+        app(server);
+    });
+
+    let limited = get(address, "");
+    assert!(limited.starts_with("HTTP/1.1 429 Too Many Requests"));
+    assert!(limited.contains("Retry-After: 60"));
+}
+
+#[test]
+fn expired_buckets_are_purged_once_per_window() {
+    let store = MemoryRateLimitStore::new();
+    let quota = Quota {
+        requests: 1,
+        window: Duration::from_millis(100),
+    };
+
+    store.acquire("a", &quota);
+    thread::sleep(Duration::from_millis(150));
+    // the first new client after a window purges the full bucket of `a`
+    store.acquire("b", &quota);
+    assert_eq!(store.len(), 1);
+    thread::sleep(Duration::from_millis(150));
+    store.acquire("c", &quota);
+    // the next one doesn't sweep the store again within the same window
+    store.acquire("d", &quota);
+    assert_eq!(store.len(), 2);
+}
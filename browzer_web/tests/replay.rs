@@ -0,0 +1,136 @@
+//! End-to-end tests for recording the requests a server answers(`WebServer::record_requests`) and
+//! replaying the recording through a router(`replay::Replay`).
+
+mod support;
+
+use browzer_web::{
+    replay::{Recorder, Replay},
+    router::WebRouter,
+    utils::{HttpMethod, HttpStatusCode},
+};
+use std::{
+    io::{self, Write},
+    net::SocketAddr,
+    sync::{Arc, Mutex},
+};
+
+/// A writer whose contents the test can read while the server writes to it.
+#[derive(Clone, Default)]
+struct SharedBuffer(Arc<Mutex<Vec<u8>>>);
+
+impl Write for SharedBuffer {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.lock().unwrap().extend_from_slice(buf);
+        return Ok(buf.len());
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        return Ok(());
+    }
+}
+
+fn record(recorder: Recorder) -> SocketAddr {
+    return support::start_server(|server| {
+        server.record_requests(recorder);
+        server.post("/users", |mut c| {
+            let body = c.request.body.clone().unwrap_or_default();
+            return c.send_string(HttpStatusCode::Created, &body);
+        });
+        server.get("/health", |mut c| c.send_string(HttpStatusCode::OK, "ok"));
+    });
+}
+
+#[test]
+fn records_redacted_requests_with_their_status() {
+    let buffer = SharedBuffer::default();
+    let address = record(
+        Recorder::new(buffer.clone())
+            .redact_query("token")
+            .skip_path("/health"),
+    );
+
+    support::request_with_body(
+        address,
+        "POST",
+        "/users?token=secret&page=1",
+        "Authorization: Bearer secret\r\n",
+        "axew",
+    );
+    support::request(address, "GET", "/health", "");
+    support::request(address, "GET", "/missing", "");
+
+    let recording = String::from_utf8(buffer.0.lock().unwrap().clone()).unwrap();
+    let lines: Vec<serde_json::Value> = recording
+        .lines()
+        .map(|line| serde_json::from_str(line).unwrap())
+        .collect();
+    assert_eq!(lines.len(), 2, "{}", recording);
+    assert_eq!(lines[0]["method"], "POST");
+    assert_eq!(lines[0]["path"], "/users?token=[REDACTED]&page=1");
+    assert_eq!(lines[0]["headers"]["Authorization"], "[REDACTED]");
+    assert_eq!(lines[0]["body"], "axew");
+    assert_eq!(lines[0]["status"], 201);
+    assert_eq!(lines[1]["path"], "/missing");
+    assert_eq!(lines[1]["status"], 404);
+    assert!(!recording.contains("secret"), "{}", recording);
+}
+
+#[test]
+fn samples_a_fraction_of_the_requests() {
+    let buffer = SharedBuffer::default();
+    let address = record(Recorder::new(buffer.clone()).sample_rate(0.5));
+
+    for _ in 0..4 {
+        support::request(address, "GET", "/health", "");
+    }
+    let recording = String::from_utf8(buffer.0.lock().unwrap().clone()).unwrap();
+    assert_eq!(recording.lines().count(), 2, "{}", recording);
+}
+
+#[test]
+fn replays_a_recording_through_a_router() {
+    let buffer = SharedBuffer::default();
+    let address = record(Recorder::new(buffer.clone()).record_bodies(true));
+    support::request_with_body(address, "POST", "/users", "", "axew");
+    support::request(address, "GET", "/health", "");
+    let recording = buffer.0.lock().unwrap().clone();
+
+    // the new code no longer serves the health check
+    let bodies = Arc::new(Mutex::new(vec![]));
+    let mut router = WebRouter::new();
+    let received = Arc::clone(&bodies);
+    router
+        .add("/users".to_string(), HttpMethod::POST, move |mut c| {
+            let body = c.request.body.clone().unwrap_or_default();
+            received.lock().unwrap().push(body);
+            return c.send_string(HttpStatusCode::Created, "created");
+        })
+        .unwrap();
+
+    let replay = Replay::from_reader(recording.as_slice())
+        .unwrap()
+        .repeat(3)
+        .threads(2);
+    assert_eq!(replay.requests().len(), 2);
+    let report = replay.run(&router);
+    assert_eq!(report.results.len(), 12);
+    assert_eq!(bodies.lock().unwrap().len(), 6);
+    assert!(bodies.lock().unwrap().iter().all(|body| body == "axew"));
+
+    let regressions = report.regressions();
+    assert_eq!(regressions.len(), 6);
+    assert!(regressions.iter().all(|result| result.index == 1
+        && result.recorded_status == Some(200)
+        && result.status == Some(404)));
+}
+
+#[test]
+fn rejects_invalid_recordings() {
+    let recording = "{\"method\":\"GET\"}\n";
+    let e = Replay::from_reader(recording.as_bytes()).unwrap_err();
+    assert!(
+        e.to_string().starts_with("Invalid record on line 1"),
+        "{}",
+        e
+    );
+}
@@ -0,0 +1,142 @@
+//! End-to-end tests for request IDs(`request_id` module, `WebServer::request_ids`).
+
+mod support;
+
+use browzer_web::{
+    logger::{RequestLog, RequestLogger},
+    request_id::RequestIds,
+    utils::HttpStatusCode,
+    WebServer,
+};
+use std::{
+    net::SocketAddr,
+    sync::{Arc, Mutex},
+};
+
+/// Sends a request with the extra header lines and returns the raw response.
+fn get(address: SocketAddr, path: &str, headers: &str) -> String {
+    return support::request(address, "GET", path, headers);
+}
+
+/// Returns the value of a header of a raw response.
+fn header<'a>(response: &'a str, name: &str) -> Option<&'a str> {
+    return response.lines().find_map(|line| {
+        let (key, value) = line.split_once(": ")?;
+        return key.eq_ignore_ascii_case(name).then_some(value);
+    });
+}
+
+/// Registers a route answering with the ID the handler sees.
+fn routes(server: &mut WebServer) {
+    server.get("/", |mut c| {
+        let body = c.request_id().unwrap_or("none").to_string();
+        return c.send_string(HttpStatusCode::OK, &body);
+    });
+}
+
+#[test]
+fn requests_without_an_id_get_a_new_one() {
+    let address = support::start_server(|server| {
+        server.request_ids(RequestIds::new());
+        routes(server);
+    });
+
+    let first = get(address, "/", "");
+    let second = get(address, "/", "");
+    let id = header(&first, "X-Request-Id").unwrap();
+    assert_eq!(id.len(), 36, "{}", first);
+    // the handler sees the ID the response carries
+    assert!(first.ends_with(id), "{}", first);
+    assert_ne!(header(&second, "X-Request-Id").unwrap(), id);
+}
+
+#[test]
+fn incoming_ids_are_kept() {
+    let address = support::start_server(|server| {
+        server.request_ids(RequestIds::new());
+        routes(server);
+    });
+
+    let response = get(address, "/", "x-request-id: upstream-42\r\n");
+    assert_eq!(header(&response, "X-Request-Id"), Some("upstream-42"));
+    assert!(response.ends_with("upstream-42"), "{}", response);
+
+    // an ID which doesn't fit in a log line is replaced
+    let long = "a".repeat(300);
+    let response = get(address, "/", &format!("X-Request-Id: {}\r\n", long));
+    assert_eq!(header(&response, "X-Request-Id").unwrap().len(), 36);
+}
+
+#[test]
+fn incoming_ids_can_be_ignored() {
+    let address = support::start_server(|server| {
+        server.request_ids(
+            RequestIds::new()
+                .header("X-Correlation-Id")
+                .trust_incoming(false),
+        );
+        routes(server);
+    });
+
+    let response = get(address, "/", "X-Correlation-Id: chosen-by-client\r\n");
+    let id = header(&response, "X-Correlation-Id").unwrap();
+    assert_ne!(id, "chosen-by-client");
+    assert!(response.ends_with(id), "{}", response);
+    assert_eq!(header(&response, "X-Request-Id"), None);
+}
+
+#[test]
+fn ids_are_logged_and_set_on_framework_responses() {
+    let logs: Arc<Mutex<Vec<RequestLog>>> = Arc::default();
+    let collected = Arc::clone(&logs);
+    let address = support::start_server(move |server| {
+        server.request_ids(RequestIds::new());
+        server.request_logger(RequestLogger::custom(move |log| {
+            collected.lock().unwrap().push(log.clone())
+        }));
+        routes(server);
+    });
+
+    let response = get(address, "/missing", "X-Request-Id: lost-1\r\n");
+    assert!(response.starts_with("HTTP/1.1 404"), "{}", response);
+    assert_eq!(header(&response, "X-Request-Id"), Some("lost-1"));
+
+    let logs = logs.lock().unwrap();
+    assert_eq!(logs.len(), 1);
+    assert_eq!(logs[0].request_id.as_deref(), Some("lost-1"));
+}
+
+#[test]
+fn servers_without_request_ids_leave_requests_alone() {
+    let address = support::start_server(routes);
+
+    let response = get(address, "/", "X-Request-Id: upstream-42\r\n");
+    assert_eq!(header(&response, "X-Request-Id"), None);
+    assert!(response.ends_with("none"), "{}", response);
+}
+
+#[test]
+fn responses_generated_by_the_server_carry_ids() {
+    let address = support::start_server(|server| {
+        server.request_ids(RequestIds::new());
+        server.body_limit = Some(4);
+        routes(server);
+    });
+
+    // the body is never read, but the ID of the request is kept
+    let response = support::request_with_body(
+        address,
+        "POST",
+        "/",
+        "X-Request-Id: abc-123\r\n",
+        "0123456789",
+    );
+    assert!(response.starts_with("HTTP/1.1 413"), "{}", response);
+    assert_eq!(header(&response, "X-Request-Id"), Some("abc-123"));
+
+    // a head which can't be parsed gets a new ID
+    let response =
+        String::from_utf8(support::exchange(address, b"GARBAGE\r\n\r\n").unwrap()).unwrap();
+    assert!(response.starts_with("HTTP/1.1 400"), "{}", response);
+    assert_eq!(header(&response, "X-Request-Id").unwrap().len(), 36);
+}
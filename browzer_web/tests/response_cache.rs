@@ -0,0 +1,125 @@
+//! End-to-end tests for the response cache(`WebServer::response_cache`), it's purge endpoint and
+//! warm-up.
+
+mod support;
+
+use browzer_web::{cache::ResponseCache, utils::HttpStatusCode};
+use std::{
+    net::SocketAddr,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+
+/// Starts a server whose `/posts/{id}` and `/admin` handlers respond with the number of times any
+/// of them ran.
+fn start(cache: ResponseCache) -> (SocketAddr, Arc<AtomicUsize>) {
+    let calls = Arc::new(AtomicUsize::new(0));
+    let posts_calls = Arc::clone(&calls);
+    let admin_calls = Arc::clone(&calls);
+    let address = support::start_server(move |server| {
+        server.response_cache(cache);
+        server.get("/posts/:id", move |mut c| {
+            let call = posts_calls.fetch_add(1, Ordering::SeqCst) + 1;
+            return c.send_string(HttpStatusCode::OK, &format!("post call {}", call));
+        });
+        server.get("/admin", move |mut c| {
+            let call = admin_calls.fetch_add(1, Ordering::SeqCst) + 1;
+            return c.send_string(HttpStatusCode::OK, &format!("admin call {}", call));
+        });
+    });
+    return (address, calls);
+}
+
+/// Sends a request with extra header lines, returning the status line, the `Age` header and the
+/// body.
+fn request(address: SocketAddr, method: &str, path: &str, headers: &str) -> (String, bool, String) {
+    let response = support::request_with_body(address, method, path, headers, "");
+    let (head, body) = response.split_once("\r\n\r\n").unwrap();
+    let mut lines = head.lines();
+    let status_line = lines.next().unwrap().to_string();
+    let cached = lines.any(|line| line.starts_with("Age: "));
+    return (status_line, cached, body.to_string());
+}
+
+fn get(address: SocketAddr, path: &str) -> String {
+    return request(address, "GET", path, "").2;
+}
+
+#[test]
+fn serves_cached_responses_until_purged() {
+    let cache = ResponseCache::new(Duration::from_secs(60)).path("/posts/*");
+    let (address, calls) = start(cache.clone());
+
+    assert_eq!(get(address, "/posts/1"), "post call 1");
+    assert_eq!(
+        request(address, "GET", "/posts/1", ""),
+        (
+            "HTTP/1.1 200 OK".to_string(),
+            true,
+            "post call 1".to_string()
+        )
+    );
+    // the query string is part of the key
+    assert_eq!(get(address, "/posts/1?page=2"), "post call 2");
+    // requests with credentials and paths outside of the cache always reach the handler
+    assert_eq!(
+        request(address, "GET", "/posts/1", "Authorization: Bearer abc\r\n").2,
+        "post call 3"
+    );
+    assert_eq!(get(address, "/admin"), "admin call 4");
+    assert_eq!(get(address, "/admin"), "admin call 5");
+
+    assert_eq!(cache.purge("/posts/1"), 2);
+    assert_eq!(get(address, "/posts/1"), "post call 6");
+    assert_eq!(calls.load(Ordering::SeqCst), 6);
+}
+
+#[test]
+fn purges_through_the_endpoint() {
+    let cache = ResponseCache::new(Duration::from_secs(60))
+        .path("/posts/*")
+        .purge_endpoint("/_cache/purge", "s3cret");
+    let (address, _) = start(cache.clone());
+    get(address, "/posts/1");
+    get(address, "/posts/2");
+
+    let cases = [
+        ("", "HTTP/1.1 401 Unauthorized", "Unauthorized"),
+        (
+            "Authorization: Bearer wrong\r\n",
+            "HTTP/1.1 401 Unauthorized",
+            "Unauthorized",
+        ),
+        (
+            "Authorization: Bearer s3cret\r\n",
+            "HTTP/1.1 200 OK",
+            "{\"purged\":2}",
+        ),
+    ];
+    for (headers, status_line, body) in cases {
+        let (status, _, response_body) =
+            request(address, "POST", "/_cache/purge?path=%2Fposts%2F*", headers);
+        assert_eq!(
+            (status.as_str(), response_body.as_str()),
+            (status_line, body)
+        );
+    }
+    assert!(cache.is_empty());
+}
+
+#[test]
+fn warms_up_configured_urls_at_startup() {
+    let cache = ResponseCache::new(Duration::from_secs(60))
+        .path("/posts/*")
+        .warm_up(&["/posts/1", "http://localhost/posts/2"]);
+    let (address, calls) = start(cache.clone());
+
+    // the warm-up is done before the first connection is accepted
+    assert_eq!(get(address, "/posts/1"), "post call 1");
+    assert_eq!(get(address, "/posts/2"), "post call 2");
+    assert_eq!(calls.load(Ordering::SeqCst), 2);
+    assert_eq!(cache.len(), 2);
+}
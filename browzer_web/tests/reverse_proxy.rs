@@ -0,0 +1,398 @@
+//! End-to-end tests for forwarding requests to an upstream server(`WebServer::proxy`), with
+//! another `WebServer` as the upstream.
+
+mod support;
+
+use browzer_web::{reverse_proxy::ReverseProxy, utils::Cookie, utils::HttpStatusCode};
+use std::{
+    io::{BufRead, BufReader, Read, Write},
+    net::{SocketAddr, TcpListener},
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+    thread,
+};
+
+/// Sends a request and returns the raw response.
+fn send(address: SocketAddr, method: &str, path: &str, headers: &str, body: &str) -> String {
+    let raw = format!(
+        "{} {} HTTP/1.1\r\nHost: gateway.test\r\n{}Content-Length: {}\r\nConnection: close\r\n\r\n{}",
+        method,
+        path,
+        headers,
+        body.len(),
+        body
+    );
+    let response = support::exchange(address, raw.as_bytes()).unwrap();
+    return String::from_utf8(response).unwrap();
+}
+
+/// Returns the JSON body of a response.
+fn json_body(response: &str) -> serde_json::Value {
+    let (_, body) = response.split_once("\r\n\r\n").unwrap();
+    return serde_json::from_str(body).unwrap();
+}
+
+fn start_upstream() -> SocketAddr {
+    return support::start_server(|server| {
+        server.debug_echo("/v1/echo");
+        server.get("/v1/created", |mut c| {
+            c.response
+                .headers
+                .insert("X-Upstream".to_string(), "yes".to_string());
+            c.set_cookie(Cookie::new("session", "abc"));
+            c.set_cookie(Cookie::new("theme", "dark"));
+            // attributes `Cookie` doesn't know about, and a date in an obsolete format
+            let mut tracking = Cookie::new("id", "7");
+            tracking.raw = Some(
+                "id=7; Expires=Sunday, 06-Nov-94 08:49:37 GMT; Partitioned; Priority=High"
+                    .to_string(),
+            );
+            c.set_cookie(tracking);
+            return c.send_string(HttpStatusCode::Created, "created");
+        });
+        server.get("/v1/stream", |mut c| {
+            return c.send_stream(HttpStatusCode::OK, |writer| {
+                writer.write_all(b"first,")?;
+                writer.flush()?;
+                writer.write_all(b"second")?;
+                return Ok(());
+            });
+        });
+        server.get("/v1/moved", |mut c| {
+            c.response
+                .headers
+                .insert("Location".to_string(), "/v1/echo".to_string());
+            return c.send_string(HttpStatusCode::TemporaryRedirect, "");
+        });
+        server.get("/v1/gone", |mut c| {
+            c.send_string(HttpStatusCode::Gone, "gone")
+        });
+        server.get("/v1/teapot", |mut c| {
+            let status_code = HttpStatusCode::Other(418, "I'm a teapot".to_string());
+            return c.send_string(status_code, "short and stout");
+        });
+        server.post("/v1/length", |mut c| {
+            let length = c.request.body_bytes().len();
+            return c.send_string(HttpStatusCode::OK, &length.to_string());
+        });
+        server.get("/v1/count", |mut c| {
+            let count = c.connection().request_count();
+            return c.send_string(HttpStatusCode::OK, &count.to_string());
+        });
+    });
+}
+
+/// Starts an upstream which answers the first request on every connection, and closes the
+/// connection without answering the next one. Returns it's address and the number of requests for
+/// `/v1/apply` it received.
+fn start_closing_upstream() -> (SocketAddr, Arc<AtomicUsize>) {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let address = listener.local_addr().unwrap();
+    let applied = Arc::new(AtomicUsize::new(0));
+    let counter = Arc::clone(&applied);
+    thread::spawn(move || {
+        for stream in listener.incoming() {
+            let mut reader = BufReader::new(stream.unwrap());
+            for answered in [true, false] {
+                let mut request_line = String::new();
+                let mut content_length = 0;
+                let mut line = String::new();
+                reader.read_line(&mut request_line).unwrap();
+                while reader.read_line(&mut line).unwrap() > 2 {
+                    if let Some(value) = line.strip_prefix("Content-Length: ") {
+                        content_length = value.trim().parse().unwrap();
+                    }
+                    line.clear();
+                }
+                let mut body = vec![0; content_length];
+                reader.read_exact(&mut body).unwrap();
+                if request_line.contains("/v1/apply") {
+                    counter.fetch_add(1, Ordering::SeqCst);
+                }
+                if answered {
+                    let response = "HTTP/1.1 200 OK\r\nContent-Length: 2\r\n\r\nok";
+                    reader.get_mut().write_all(response.as_bytes()).unwrap();
+                }
+            }
+        }
+    });
+    return (address, applied);
+}
+
+fn start_gateway(upstream: SocketAddr) -> SocketAddr {
+    return support::start_server(|server| {
+        server.proxy("/api/*path", &format!("http://{}/v1", upstream));
+    });
+}
+
+#[test]
+fn upstream_cookies_are_keyed_by_name() {
+    let upstream = start_upstream();
+    let gateway = support::start_server(|server| {
+        server.proxy("/api/*path", &format!("http://{}/v1", upstream));
+        server.after_middleware(|_, mut response| {
+            // middlewares find the cookies of the upstream by name, and replace them like any other
+            if let Some(session) = response.cookies.get("session") {
+                let session = Cookie::new("session", &format!("{}-renewed", session.value));
+                response.cookies.insert("session".to_string(), session);
+            }
+            return response;
+        });
+    });
+
+    let response = send(gateway, "GET", "/api/created", "", "");
+    assert!(
+        response.starts_with("HTTP/1.1 201 Created\r\n"),
+        "{}",
+        response
+    );
+    assert!(
+        response.contains("Set-Cookie: session=abc-renewed\r\n"),
+        "{}",
+        response
+    );
+    assert_eq!(
+        response.matches("Set-Cookie: session=").count(),
+        1,
+        "{}",
+        response
+    );
+    assert!(response.contains("Set-Cookie: theme=dark"), "{}", response);
+}
+
+#[test]
+fn forwards_the_request_upstream() {
+    let gateway = start_gateway(start_upstream());
+
+    let response = send(
+        gateway,
+        "POST",
+        "/api/echo/users?page=2",
+        "X-Custom: kept\r\nX-Forwarded-For: 203.0.113.7\r\nKeep-Alive: timeout=5\r\n",
+        "hello",
+    );
+    assert!(response.starts_with("HTTP/1.1 200 OK\r\n"), "{}", response);
+    let echo = json_body(&response);
+    assert_eq!(echo["method"], "POST");
+    assert_eq!(echo["path"], "/v1/echo/users?page=2");
+    assert_eq!(echo["body"]["preview"], "hello");
+    let headers = &echo["headers"];
+    assert!(headers["Host"].as_str().unwrap().starts_with("127.0.0.1:"));
+    assert_eq!(headers["X-Custom"], "kept");
+    assert_eq!(headers["X-Forwarded-For"], "203.0.113.7, 127.0.0.1");
+    assert_eq!(headers["X-Forwarded-Proto"], "http");
+    assert_eq!(headers["X-Forwarded-Host"], "gateway.test");
+    // hop-by-hop headers only concern the connection to the gateway
+    assert!(headers.get("Keep-Alive").is_none(), "{}", headers);
+}
+
+#[test]
+fn answers_with_the_upstream_response() {
+    let gateway = start_gateway(start_upstream());
+
+    let response = send(gateway, "GET", "/api/created", "", "");
+    assert!(
+        response.starts_with("HTTP/1.1 201 Created\r\n"),
+        "{}",
+        response
+    );
+    assert!(response.contains("X-Upstream: yes\r\n"), "{}", response);
+    assert!(response.contains("Set-Cookie: session=abc"), "{}", response);
+    assert!(response.contains("Set-Cookie: theme=dark"), "{}", response);
+    // cookies are passed through verbatim
+    assert!(
+        response.contains(
+            "Set-Cookie: id=7; Expires=Sunday, 06-Nov-94 08:49:37 GMT; Partitioned; Priority=High\r\n"
+        ),
+        "{}",
+        response
+    );
+    assert!(response.ends_with("\r\n\r\ncreated"), "{}", response);
+
+    // a chunked upstream body is streamed, and chunked again by the gateway
+    let response = send(gateway, "GET", "/api/stream", "", "");
+    assert!(
+        response.contains("Transfer-Encoding: chunked\r\n"),
+        "{}",
+        response
+    );
+    let (_, body) = response.split_once("\r\n\r\n").unwrap();
+    let body: String = body
+        .split("\r\n")
+        .skip(1)
+        .step_by(2)
+        .collect::<Vec<_>>()
+        .concat();
+    assert_eq!(body, "first,second");
+}
+
+#[test]
+fn streams_request_bodies_upstream() {
+    let upstream = start_upstream();
+    let gateway = support::start_server(|server| {
+        // a buffered body would be turned away by the budget
+        server.set_memory_budget(64 * 1024);
+        server.proxy("/api/*path", &format!("http://{}/v1", upstream));
+    });
+
+    let body = "a".repeat(1024 * 1024);
+    let response = send(gateway, "POST", "/api/length", "", &body);
+    assert!(response.starts_with("HTTP/1.1 200 OK\r\n"), "{}", response);
+    assert!(response.ends_with("\r\n\r\n1048576"), "{}", response);
+
+    // a body cut short by the client is never forwarded as a whole
+    let response = support::exchange(
+        gateway,
+        b"POST /api/length HTTP/1.1\r\nHost: gateway.test\r\nContent-Length: 100\r\n\r\nshort",
+    )
+    .unwrap();
+    let response = String::from_utf8(response).unwrap();
+    assert!(
+        response.starts_with("HTTP/1.1 400 Bad Request\r\n"),
+        "{}",
+        response
+    );
+}
+
+#[test]
+fn passes_upstream_status_codes_through() {
+    let gateway = start_gateway(start_upstream());
+
+    let response = send(gateway, "GET", "/api/moved", "", "");
+    assert!(
+        response.starts_with("HTTP/1.1 307 Temporary Redirect\r\n"),
+        "{}",
+        response
+    );
+    assert!(response.contains("Location: /v1/echo\r\n"), "{}", response);
+
+    let response = send(gateway, "GET", "/api/gone", "", "");
+    assert!(
+        response.starts_with("HTTP/1.1 410 Gone\r\n"),
+        "{}",
+        response
+    );
+    assert!(response.ends_with("\r\n\r\ngone"), "{}", response);
+
+    // status codes without a variant of their own keep their reason phrase
+    let response = send(gateway, "GET", "/api/teapot", "", "");
+    assert!(
+        response.starts_with("HTTP/1.1 418 I'm a teapot\r\n"),
+        "{}",
+        response
+    );
+    assert!(
+        response.ends_with("\r\n\r\nshort and stout"),
+        "{}",
+        response
+    );
+}
+
+#[test]
+fn reuses_upstream_connections() {
+    let gateway = start_gateway(start_upstream());
+
+    let counts: Vec<u64> = (0..3)
+        .map(|_| {
+            let response = send(gateway, "GET", "/api/count", "", "");
+            let (_, body) = response.split_once("\r\n\r\n").unwrap();
+            return body.parse().unwrap();
+        })
+        .collect();
+    assert_eq!(counts, vec![1, 2, 3]);
+}
+
+#[test]
+fn resolves_dot_segments_of_the_forwarded_path() {
+    let gateway = start_gateway(start_upstream());
+
+    let response = send(gateway, "GET", "/api/count/../echo/./users", "", "");
+    assert_eq!(json_body(&response)["path"], "/v1/echo/users");
+    let response = send(gateway, "GET", "/api/count/%2E%2e/echo", "", "");
+    assert_eq!(json_body(&response)["path"], "/v1/echo");
+
+    // paths climbing above the path of the upstream never reach it
+    for path in [
+        "/api/../../admin",
+        "/api/%2e%2e/admin",
+        "/api/echo/../../v2",
+    ] {
+        let response = send(gateway, "GET", path, "", "");
+        assert!(
+            response.starts_with("HTTP/1.1 400 Bad Request\r\n"),
+            "{}: {}",
+            path,
+            response
+        );
+    }
+}
+
+#[test]
+fn only_resends_replayable_requests() {
+    let (upstream, applied) = start_closing_upstream();
+    let gateway = start_gateway(upstream);
+
+    // the upstream closes the reused connection, the request is sent again on a new one
+    send(gateway, "GET", "/api/warm", "", "");
+    let response = send(gateway, "GET", "/api/apply", "", "");
+    assert!(response.starts_with("HTTP/1.1 200 OK\r\n"), "{}", response);
+    assert_eq!(applied.load(Ordering::SeqCst), 2);
+
+    // a POST request may have been applied, so it's never sent twice
+    send(gateway, "GET", "/api/warm", "", "");
+    let response = send(gateway, "POST", "/api/apply", "", "charge");
+    assert!(
+        response.starts_with("HTTP/1.1 502 Bad Gateway\r\n"),
+        "{}",
+        response
+    );
+    assert_eq!(applied.load(Ordering::SeqCst), 3);
+
+    // unless the client made it safe to repeat
+    send(gateway, "GET", "/api/warm", "", "");
+    let response = send(
+        gateway,
+        "POST",
+        "/api/apply",
+        "Idempotency-Key: 8e03978e\r\n",
+        "charge",
+    );
+    assert!(response.starts_with("HTTP/1.1 200 OK\r\n"), "{}", response);
+    assert_eq!(applied.load(Ordering::SeqCst), 5);
+}
+
+#[test]
+fn unreachable_upstreams_get_a_bad_gateway() {
+    // a port nothing listens on anymore
+    let upstream = TcpListener::bind("127.0.0.1:0")
+        .unwrap()
+        .local_addr()
+        .unwrap();
+    let gateway = start_gateway(upstream);
+
+    let response = send(gateway, "GET", "/api/users", "", "");
+    assert!(
+        response.starts_with("HTTP/1.1 502 Bad Gateway\r\n"),
+        "{}",
+        response
+    );
+}
+
+#[test]
+fn rejects_unsupported_upstreams() {
+    assert!(ReverseProxy::new("http://127.0.0.1:9000").is_ok());
+    // without the `tls` feature requests to HTTPS upstreams would be sent in plaintext
+    assert_eq!(
+        ReverseProxy::new("https://127.0.0.1:9000").is_ok(),
+        cfg!(feature = "tls")
+    );
+    assert!(ReverseProxy::new("http://127.0.0.1:9000/?debug=1").is_err());
+    assert!(ReverseProxy::new("http:///v1").is_err());
+    assert!(ReverseProxy::new("http://127.0.0.1:9000/v1\r\nX-Injected: 1").is_err());
+    assert_eq!(
+        ReverseProxy::new("http://[::1]/v1/").unwrap().upstream(),
+        "http://[::1]/v1"
+    );
+}
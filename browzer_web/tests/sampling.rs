@@ -0,0 +1,86 @@
+//! End-to-end tests for the request sampling hook (`WebServer::sample_requests`).
+
+mod support;
+
+use browzer_web::{
+    sampling::{RequestProfile, Sampler},
+    utils::{HttpMethod, HttpStatusCode},
+    WebServer,
+};
+use std::{
+    sync::{Arc, Mutex},
+    thread,
+    time::Duration,
+};
+
+fn app(server: &mut WebServer) {
+    server.get("/slow", |mut c| {
+        thread::sleep(Duration::from_millis(50));
+        return c.send_string(HttpStatusCode::OK, "done");
+    });
+}
+
+fn get(address: std::net::SocketAddr, path: &str) {
+    support::request(address, "GET", path, "");
+}
+
+/// Waits until the sink received `count` profiles, the sink runs after the response was written.
+fn wait_for(profiles: &Mutex<Vec<RequestProfile>>, count: usize) -> Vec<RequestProfile> {
+    for _ in 0..100 {
+        let profiles = profiles.lock().unwrap();
+        if profiles.len() >= count {
+            return profiles.clone();
+        }
+        drop(profiles);
+        thread::sleep(Duration::from_millis(20));
+    }
+    return profiles.lock().unwrap().clone();
+}
+
+#[test]
+fn sampled_requests_are_broken_down_into_phases() {
+    let profiles = Arc::new(Mutex::new(Vec::new()));
+    let sink = profiles.clone();
+    let address = support::start_server(move |server| {
+        server.sample_requests(Sampler::new(1.0, move |profile| {
+            sink.lock().unwrap().push(profile.clone());
+        }));
+        app(server);
+    });
+
+    get(address, "/slow?verbose=1");
+    let profiles = wait_for(&profiles, 1);
+    assert_eq!(profiles.len(), 1);
+    let profile = &profiles[0];
+    assert_eq!(profile.method, HttpMethod::GET);
+    assert_eq!(profile.path, "/slow");
+    assert_eq!(profile.status, 200);
+    assert!(profile.handler >= Duration::from_millis(50));
+    assert!(profile.middleware < profile.handler);
+    assert!(profile.total() >= profile.handler);
+}
+
+#[test]
+fn only_the_sampled_fraction_is_profiled() {
+    let profiles = Arc::new(Mutex::new(Vec::new()));
+    let sink = profiles.clone();
+    let address = support::start_server(move |server| {
+        server.sample_requests(Sampler::new(0.5, move |profile| {
+            sink.lock().unwrap().push(profile.clone());
+        }));
+        server.get("/", |mut c| c.send_string(HttpStatusCode::OK, "hi"));
+    });
+
+    for _ in 0..4 {
+        get(address, "/");
+    }
+    // requests which don't reach a handler are profiled too
+    for _ in 0..2 {
+        get(address, "/missing");
+    }
+    let profiles = wait_for(&profiles, 3);
+    assert_eq!(profiles.len(), 3);
+    let missing = profiles.last().unwrap();
+    assert_eq!(missing.status, 404);
+    assert_eq!(missing.handler, Duration::ZERO);
+}
@@ -0,0 +1,80 @@
+//! Tests for the `Serialize`/`Deserialize` implementations of the core types, which only exist
+//! with the `serde` feature(`cargo test --features serde`).
+
+#![cfg(feature = "serde")]
+
+use browzer_web::{
+    request::Request,
+    response::Response,
+    utils::{Cookie, HttpMethod, HttpStatusCode, SameSite},
+};
+use std::time::Duration;
+
+#[test]
+fn status_codes_are_serialized_as_numbers() {
+    assert_eq!(
+        serde_json::to_string(&HttpStatusCode::NotFound).unwrap(),
+        "404"
+    );
+    assert_eq!(
+        serde_json::from_str::<HttpStatusCode>("201").unwrap(),
+        HttpStatusCode::Created
+    );
+    assert!(serde_json::from_str::<HttpStatusCode>("418").is_err());
+}
+
+#[test]
+fn methods_are_serialized_as_their_name() {
+    assert_eq!(
+        serde_json::to_string(&HttpMethod::PATCH).unwrap(),
+        "\"PATCH\""
+    );
+    assert_eq!(
+        serde_json::from_str::<HttpMethod>("\"DELETE\"").unwrap(),
+        HttpMethod::DELETE
+    );
+}
+
+#[test]
+fn responses_round_trip() {
+    let mut response = Response::new(HttpStatusCode::Created, "{\"id\":1}".to_string());
+    response
+        .headers
+        .insert("Content-Type".to_string(), "application/json".to_string());
+    let cookie = Cookie::builder("session", "abc123")
+        .path("/")
+        .same_site(SameSite::None)
+        .build();
+    response.cookies.insert("session".to_string(), cookie);
+
+    let json = serde_json::to_value(&response).unwrap();
+    assert_eq!(json["status_code"], 201);
+    assert_eq!(json["cookies"]["session"]["same_site"], "None");
+    assert!(json.get("stream").is_none());
+
+    let restored: Response = serde_json::from_value(json).unwrap();
+    assert_eq!(restored.to_string(), response.to_string());
+}
+
+#[test]
+fn requests_round_trip() {
+    let raw = "POST /users?page=2 HTTP/1.1\r\nHost: example.com\r\nCookie: theme=dark\r\nContent-Length: 4\r\n\r\naxew";
+    let request = Request::read_from(&mut raw.as_bytes(), false)
+        .unwrap()
+        .unwrap();
+
+    let json = serde_json::to_string(&request).unwrap();
+    let restored: Request = serde_json::from_str(&json).unwrap();
+
+    assert_eq!(restored.method, HttpMethod::POST);
+    assert_eq!(restored.path, request.path);
+    assert_eq!(restored.headers, request.headers);
+    assert_eq!(restored.body.as_deref(), Some("axew"));
+    assert_eq!(restored.cookies["theme"].value, "dark");
+    assert_eq!(
+        restored.received_at.system_time,
+        request.received_at.system_time
+    );
+    assert!(restored.received_at.elapsed() < Duration::from_secs(5));
+    assert!(!restored.cancellation.is_cancelled());
+}
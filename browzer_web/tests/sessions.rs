@@ -0,0 +1,121 @@
+//! End-to-end tests for sessions(`WebServer::sessions`), following a visitor through logging in,
+//! using and ending a session.
+
+mod support;
+
+use browzer_web::{
+    sessions::{MemorySessionStore, Sessions},
+    utils::HttpStatusCode,
+    WebServer,
+};
+use std::{net::SocketAddr, time::Duration};
+
+fn app(server: &mut WebServer) {
+    server.sessions(Sessions::new(
+        MemorySessionStore::new(),
+        Duration::from_secs(60),
+    ));
+    server.get("/visit", |mut c| {
+        let visits = match c.session().get("visits") {
+            Some(visits) => visits.parse::<u64>().unwrap() + 1,
+            None => 1,
+        };
+        c.session().set("visits", &visits.to_string());
+        return c.send_string(HttpStatusCode::OK, &visits.to_string());
+    });
+    server.post("/login", |mut c| {
+        c.session().regenerate();
+        c.session().set("user", "axew");
+        return c.send_string(HttpStatusCode::OK, "logged in");
+    });
+    server.get("/me", |mut c| match c.session().get("user") {
+        Some(user) => return c.send_string(HttpStatusCode::OK, &user),
+        None => return c.send_string(HttpStatusCode::Unauthorized, "anonymous"),
+    });
+    server.post("/logout", |mut c| {
+        c.session().destroy();
+        return c.send_string(HttpStatusCode::OK, "logged out");
+    });
+}
+
+/// Sends a request with an optional session cookie, returning the body and the `Set-Cookie`
+/// header of the response.
+fn request(
+    address: SocketAddr,
+    method: &str,
+    path: &str,
+    session: Option<&str>,
+) -> (String, Option<String>) {
+    let cookie = match session {
+        Some(id) => format!("Cookie: browzer_session={}\r\n", id),
+        None => String::new(),
+    };
+    let response = support::request_with_body(address, method, path, &cookie, "");
+    let (head, body) = response.split_once("\r\n\r\n").unwrap();
+    let set_cookie = head
+        .lines()
+        .find_map(|line| line.strip_prefix("Set-Cookie: "))
+        .map(|value| value.to_string());
+    return (body.to_string(), set_cookie);
+}
+
+/// Extracts the session id from a `Set-Cookie` header value.
+fn session_id(set_cookie: &str) -> String {
+    let (pair, _) = set_cookie.split_once(';').unwrap();
+    return pair.strip_prefix("browzer_session=").unwrap().to_string();
+}
+
+#[test]
+fn sessions_are_only_created_once_used() {
+    let address = support::start_server(app);
+
+    let (body, set_cookie) = request(address, "GET", "/me", None);
+    assert_eq!(body, "anonymous");
+    assert_eq!(set_cookie, None);
+}
+
+#[test]
+fn sessions_keep_their_values_between_requests() {
+    let address = support::start_server(app);
+
+    let (body, set_cookie) = request(address, "GET", "/visit", None);
+    assert_eq!(body, "1");
+    let set_cookie = set_cookie.unwrap();
+    assert!(set_cookie.ends_with("; Path=/; Max-Age=60; HttpOnly; SameSite=Lax"));
+    let id = session_id(&set_cookie);
+
+    let (body, set_cookie) = request(address, "GET", "/visit", Some(&id));
+    assert_eq!(body, "2");
+    assert_eq!(session_id(&set_cookie.unwrap()), id);
+
+    // unknown session ids get a fresh session
+    let (body, set_cookie) = request(address, "GET", "/visit", Some("forged"));
+    assert_eq!(body, "1");
+    assert_ne!(session_id(&set_cookie.unwrap()), "forged");
+}
+
+#[test]
+fn logging_in_regenerates_and_logging_out_destroys_the_session() {
+    let address = support::start_server(app);
+
+    let (_, set_cookie) = request(address, "GET", "/visit", None);
+    let anonymous_id = session_id(&set_cookie.unwrap());
+
+    let (_, set_cookie) = request(address, "POST", "/login", Some(&anonymous_id));
+    let id = session_id(&set_cookie.unwrap());
+    assert_ne!(id, anonymous_id);
+    assert_eq!(request(address, "GET", "/me", Some(&id)).0, "axew");
+    // the session id from before logging in is no longer valid
+    assert_eq!(
+        request(address, "GET", "/me", Some(&anonymous_id)).0,
+        "anonymous"
+    );
+    // values stored before logging in are kept
+    assert_eq!(request(address, "GET", "/visit", Some(&id)).0, "2");
+
+    let (_, set_cookie) = request(address, "POST", "/logout", Some(&id));
+    assert!(set_cookie
+        .unwrap()
+        .starts_with("browzer_session=; Path=/; Max-Age=0"));
+    assert_eq!(request(address, "GET", "/me", Some(&id)).0, "anonymous");
+}
@@ -0,0 +1,123 @@
+//! End-to-end tests for the validators and caching directives of static files
+//! (`static_files::StaticFiles`), and the `304 Not Modified` responses they allow.
+
+mod support;
+
+use browzer_web::static_files::StaticFiles;
+use std::{fs, net::SocketAddr, path::PathBuf};
+
+/// Creates a served directory holding `app.css`.
+fn served_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!(
+        "browzer_static_caching_{}_{}",
+        std::process::id(),
+        name
+    ));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    fs::write(dir.join("app.css"), "body { margin: 0 }").unwrap();
+    return dir;
+}
+
+/// Sends a GET request with extra header lines, returning the head and the body of the response.
+fn get(address: SocketAddr, path: &str, headers: &str) -> (String, String) {
+    let response = support::request(address, "GET", path, headers);
+    let (head, body) = response.split_once("\r\n\r\n").unwrap();
+    return (head.to_string(), body.to_string());
+}
+
+/// Returns the value of a header of a response head.
+fn header(head: &str, name: &str) -> Option<String> {
+    return head.lines().find_map(|line| {
+        let (key, value) = line.split_once(": ")?;
+        return key.eq_ignore_ascii_case(name).then(|| value.to_string());
+    });
+}
+
+#[test]
+fn revalidations_are_answered_with_not_modified() {
+    let dir = served_dir("revalidate");
+    let root = dir.to_str().unwrap().to_string();
+    let address = support::start_server(move |server| {
+        server.serve_static_files(
+            "/assets",
+            StaticFiles::new(&root).cache_control("public, max-age=60"),
+        );
+    });
+
+    let (head, body) = get(address, "/assets/app.css", "");
+    assert!(head.starts_with("HTTP/1.1 200"), "{}", head);
+    assert_eq!(body, "body { margin: 0 }");
+    assert_eq!(
+        header(&head, "Cache-Control").unwrap(),
+        "public, max-age=60"
+    );
+    let etag = header(&head, "ETag").unwrap();
+    let last_modified = header(&head, "Last-Modified").unwrap();
+
+    let (head, body) = get(
+        address,
+        "/assets/app.css",
+        &format!("If-None-Match: {}\r\n", etag),
+    );
+    assert!(head.starts_with("HTTP/1.1 304"), "{}", head);
+    assert_eq!(body, "");
+    assert_eq!(header(&head, "ETag").unwrap(), etag);
+    assert_eq!(
+        header(&head, "Cache-Control").unwrap(),
+        "public, max-age=60"
+    );
+
+    let (head, _) = get(
+        address,
+        "/assets/app.css",
+        &format!("If-Modified-Since: {}\r\n", last_modified),
+    );
+    assert!(head.starts_with("HTTP/1.1 304"), "{}", head);
+
+    // a stale entity tag wins over a matching date
+    let (head, body) = get(
+        address,
+        "/assets/app.css",
+        &format!(
+            "If-None-Match: \"stale\"\r\nIf-Modified-Since: {}\r\n",
+            last_modified
+        ),
+    );
+    assert!(head.starts_with("HTTP/1.1 200"), "{}", head);
+    assert_eq!(body, "body { margin: 0 }");
+
+    let (head, _) = get(
+        address,
+        "/assets/app.css",
+        "If-Modified-Since: Sun, 06 Nov 1994 08:49:37 GMT\r\n",
+    );
+    assert!(head.starts_with("HTTP/1.1 200"), "{}", head);
+
+    let _ = fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn changed_files_get_new_validators() {
+    let dir = served_dir("changed");
+    let root = dir.to_str().unwrap().to_string();
+    let address = support::start_server(move |server| {
+        server.serve_static(&root, "/static");
+    });
+
+    let (head, _) = get(address, "/static/app.css", "");
+    let etag = header(&head, "ETag").unwrap();
+    assert_eq!(header(&head, "Cache-Control"), None);
+
+    fs::write(dir.join("app.css"), "body { margin: 1em }").unwrap();
+    let (head, body) = get(
+        address,
+        "/static/app.css",
+        &format!("If-None-Match: {}\r\n", etag),
+    );
+    assert!(head.starts_with("HTTP/1.1 200"), "{}", head);
+    assert_eq!(body, "body { margin: 1em }");
+    assert_ne!(header(&head, "ETag").unwrap(), etag);
+
+    let _ = fs::remove_dir_all(&dir);
+}
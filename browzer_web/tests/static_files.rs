@@ -0,0 +1,195 @@
+//! Tests for resolving static file requests(`utils::resolve_static_path`, as used by
+//! `WebServer::serve_static`), focusing on directory traversal attempts.
+//!
+//! Every test builds a served directory with a secret file next to it, outside of the served
+//! directory, which none of the traversal attempts may reach however they are encoded.
+
+use browzer_web::{
+    error::StaticFileError,
+    utils::{resolve_static_path, HttpStatusCode},
+};
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+/// A temporary directory layout, removed again when dropped:
+///
+/// ```text
+/// <base>/public/index.html
+/// <base>/public/css/main.css
+/// <base>/secret.txt
+/// ```
+struct Layout {
+    base: PathBuf,
+}
+
+impl Layout {
+    fn new(name: &str) -> Layout {
+        let base = std::env::temp_dir().join(format!(
+            "browzer_static_files_{}_{}",
+            std::process::id(),
+            name
+        ));
+        let _ = fs::remove_dir_all(&base);
+        fs::create_dir_all(base.join("public/css")).unwrap();
+        fs::write(base.join("public/index.html"), "<h1>index</h1>").unwrap();
+        fs::write(base.join("public/css/main.css"), "body {}").unwrap();
+        fs::write(base.join("secret.txt"), "secret").unwrap();
+        return Layout { base };
+    }
+
+    fn root(&self) -> PathBuf {
+        return self.base.join("public");
+    }
+
+    fn resolve(&self, requested: &str) -> Result<PathBuf, StaticFileError> {
+        return resolve_static_path(&self.root(), requested);
+    }
+}
+
+impl Drop for Layout {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.base);
+    }
+}
+
+fn assert_forbidden(layout: &Layout, requested: &str) {
+    match layout.resolve(requested) {
+        Err(StaticFileError::Forbidden) => {}
+        other => panic!("{:?} should be forbidden, got {:?}", requested, other),
+    }
+}
+
+fn assert_not_found(layout: &Layout, requested: &str) {
+    match layout.resolve(requested) {
+        Err(StaticFileError::NotFound) => {}
+        other => panic!("{:?} should not be found, got {:?}", requested, other),
+    }
+}
+
+fn assert_resolves_to(layout: &Layout, requested: &str, expected: &Path) {
+    let expected = layout.root().join(expected).canonicalize().unwrap();
+    match layout.resolve(requested) {
+        Ok(path) => assert_eq!(path, expected, "{:?}", requested),
+        Err(e) => panic!("{:?} should resolve, got {:?}", requested, e),
+    }
+}
+
+#[test]
+fn resolves_files_inside_the_directory() {
+    let layout = Layout::new("inside");
+
+    assert_resolves_to(&layout, "index.html", Path::new("index.html"));
+    assert_resolves_to(&layout, "./index.html", Path::new("index.html"));
+    assert_resolves_to(&layout, "css%2Fmain.css", Path::new("css/main.css"));
+    assert_resolves_to(&layout, "%69ndex.html", Path::new("index.html"));
+}
+
+#[test]
+fn keeps_plus_signs_in_paths() {
+    let layout = Layout::new("plus");
+    fs::write(layout.root().join("c++.txt"), "c++").unwrap();
+
+    // a `+` only stands for a space in query strings
+    assert_resolves_to(&layout, "c++.txt", Path::new("c++.txt"));
+    assert_resolves_to(&layout, "c%2B%2B.txt", Path::new("c++.txt"));
+    assert_not_found(&layout, "c%20%20.txt");
+}
+
+#[test]
+fn rejects_plain_traversal() {
+    let layout = Layout::new("plain");
+
+    assert_forbidden(&layout, "..");
+    assert_forbidden(&layout, "../secret.txt");
+    assert_forbidden(&layout, "css/../../secret.txt");
+    // even traversal which would end up inside the directory again is rejected
+    assert_forbidden(&layout, "css/../index.html");
+}
+
+#[test]
+fn rejects_percent_encoded_traversal() {
+    let layout = Layout::new("encoded");
+
+    assert_forbidden(&layout, "..%2Fsecret.txt");
+    assert_forbidden(&layout, "..%2f..%2f..%2fetc%2fpasswd");
+    assert_forbidden(&layout, "..%2F..%2Fetc%2Fpasswd");
+    assert_forbidden(&layout, "%2E%2E%2Fsecret.txt");
+    assert_forbidden(&layout, "%2e%2e/secret.txt");
+    assert_forbidden(&layout, "css%2F..%2F..%2Fsecret.txt");
+}
+
+#[test]
+fn rejects_backslash_traversal() {
+    let layout = Layout::new("backslash");
+
+    assert_forbidden(&layout, "..\\secret.txt");
+    assert_forbidden(&layout, "..%5Csecret.txt");
+    assert_forbidden(&layout, "css%5C..%5C..%5Csecret.txt");
+}
+
+#[test]
+fn rejects_absolute_paths() {
+    let layout = Layout::new("absolute");
+    let secret = layout.base.join("secret.txt");
+
+    assert_forbidden(&layout, "/etc/passwd");
+    assert_forbidden(&layout, "%2Fetc%2Fpasswd");
+    assert_forbidden(&layout, &secret.to_string_lossy());
+}
+
+#[test]
+fn double_encoding_is_only_decoded_once() {
+    let layout = Layout::new("double");
+
+    // `%252F` decodes to the literal `%2F`, which is just part of a(missing) filename
+    assert_not_found(&layout, "..%252Fsecret.txt");
+}
+
+#[test]
+fn rejects_missing_and_invalid_paths() {
+    let layout = Layout::new("missing");
+
+    assert_not_found(&layout, "missing.html");
+    assert_not_found(&layout, "css");
+    assert_not_found(&layout, "");
+    assert_not_found(&layout, "index.html%00.png");
+    assert_not_found(&layout, "%ZZindex.html");
+    assert_not_found(&layout, "index.html%");
+    assert_not_found(&layout, "%FF%FE");
+}
+
+#[cfg(unix)]
+#[test]
+fn rejects_symbolic_links_out_of_the_directory() {
+    let layout = Layout::new("symlink");
+    std::os::unix::fs::symlink(
+        layout.base.join("secret.txt"),
+        layout.root().join("link.txt"),
+    )
+    .unwrap();
+    std::os::unix::fs::symlink(layout.base.clone(), layout.root().join("up")).unwrap();
+    std::os::unix::fs::symlink(
+        layout.root().join("index.html"),
+        layout.root().join("home.html"),
+    )
+    .unwrap();
+
+    assert_forbidden(&layout, "link.txt");
+    assert_forbidden(&layout, "up%2Fsecret.txt");
+    // links staying inside the directory are fine
+    assert_resolves_to(&layout, "home.html", Path::new("index.html"));
+}
+
+#[test]
+fn errors_map_to_status_codes() {
+    assert_eq!(
+        StaticFileError::Forbidden.status_code(),
+        HttpStatusCode::Forbidden
+    );
+    assert_eq!(
+        StaticFileError::NotFound.status_code(),
+        HttpStatusCode::NotFound
+    );
+}
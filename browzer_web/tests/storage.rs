@@ -0,0 +1,193 @@
+//! Tests for the embedded key-value store(`storage::KvStore`): persistence across reopening,
+//! compaction of the log, recovery from a partly written record, detecting damaged records,
+//! upgrading logs of the first format and keeping sessions.
+
+use browzer_web::{
+    sessions::{SessionData, SessionStore},
+    storage::KvStore,
+};
+use std::{fs, io::Write, path::PathBuf, time::Duration};
+
+/// Returns the path of a log for a test, removing any left over from an earlier run.
+fn log_path(name: &str) -> PathBuf {
+    let path = std::env::temp_dir().join(format!("browzer_kv_{}_{}", std::process::id(), name));
+    let _ = fs::remove_file(&path);
+    return path;
+}
+
+#[test]
+fn entries_survive_reopening() {
+    let path = log_path("reopen");
+    {
+        let store = KvStore::open(&path).unwrap();
+        store.set("greeting", "hello").unwrap();
+        store.set("greeting", "hi").unwrap();
+        store.set("temporary", b"x").unwrap();
+        store.set_json("user:1", &vec!["admin", "editor"]).unwrap();
+        assert_eq!(store.remove("temporary").unwrap(), Some(b"x".to_vec()));
+        assert_eq!(store.remove("temporary").unwrap(), None);
+        assert_eq!(store.increment("visits", 5).unwrap(), 5);
+    }
+
+    let store = KvStore::open(&path).unwrap();
+    assert_eq!(store.len(), 3);
+    assert_eq!(store.get_string("greeting").as_deref(), Some("hi"));
+    assert!(!store.contains("temporary"));
+    assert_eq!(
+        store.get_json::<Vec<String>>("user:1").unwrap(),
+        vec!["admin", "editor"]
+    );
+    assert_eq!(store.increment("visits", -2).unwrap(), 3);
+    assert!(store.increment("greeting", 1).is_err());
+    assert_eq!(store.keys_with_prefix("user:"), vec!["user:1"]);
+
+    let _ = fs::remove_file(&path);
+}
+
+#[test]
+fn stale_records_are_compacted() {
+    let path = log_path("compact");
+    let store = KvStore::open(&path).unwrap();
+    store.set_compaction_threshold(u64::MAX);
+    for i in 0..100 {
+        store.set("counter", i.to_string()).unwrap();
+    }
+    let (length, stale) = store.log_stats();
+    assert_eq!(fs::metadata(&path).unwrap().len(), length);
+    assert!(stale > length / 2);
+
+    store.compact().unwrap();
+    let (compacted, stale) = store.log_stats();
+    assert_eq!(stale, 0);
+    assert!(compacted < length / 10);
+    assert_eq!(fs::metadata(&path).unwrap().len(), compacted);
+
+    // writes after compaction go to the new log
+    store.set("other", "value").unwrap();
+    drop(store);
+    let store = KvStore::open(&path).unwrap();
+    assert_eq!(store.get_string("counter").as_deref(), Some("99"));
+    assert_eq!(store.get_string("other").as_deref(), Some("value"));
+
+    // a low threshold compacts automatically
+    store.set_compaction_threshold(64);
+    for i in 0..100 {
+        store.set("counter", i.to_string()).unwrap();
+    }
+    let (length, stale) = store.log_stats();
+    assert!(stale * 2 <= length || stale <= 64);
+    assert!(length < 200);
+
+    let _ = fs::remove_file(&path);
+}
+
+#[test]
+fn partly_written_records_are_dropped() {
+    let path = log_path("torn");
+    {
+        let store = KvStore::open(&path).unwrap();
+        store.set("kept", "yes").unwrap();
+    }
+    let complete = fs::metadata(&path).unwrap().len();
+    // the head of a record announcing more data than follows it
+    let mut file = fs::OpenOptions::new().append(true).open(&path).unwrap();
+    file.write_all(&[1, 4, 0, 0, 0, 100, 0, 0, 0, 0, 0, 0, 0, b'l', b'o'])
+        .unwrap();
+    drop(file);
+
+    let store = KvStore::open(&path).unwrap();
+    assert_eq!(store.get_string("kept").as_deref(), Some("yes"));
+    assert_eq!(store.len(), 1);
+    assert_eq!(fs::metadata(&path).unwrap().len(), complete);
+    store.set("after", "crash").unwrap();
+    drop(store);
+    let store = KvStore::open(&path).unwrap();
+    assert_eq!(store.get_string("after").as_deref(), Some("crash"));
+
+    // other files aren't mistaken for a log
+    let other = log_path("other");
+    fs::write(&other, "not a log").unwrap();
+    assert!(KvStore::open(&other).is_err());
+
+    let _ = fs::remove_file(&path);
+    let _ = fs::remove_file(&other);
+}
+
+#[test]
+fn damaged_records_fail_opening_unless_they_are_the_last_one() {
+    let path = log_path("damaged");
+    {
+        let store = KvStore::open(&path).unwrap();
+        store.set("first", "one").unwrap();
+        store.set("second", "two").unwrap();
+    }
+    let log = fs::read(&path).unwrap();
+
+    // a flipped bit in the value of the last record drops it, like a partly written one
+    let mut damaged = log.clone();
+    *damaged.last_mut().unwrap() ^= 1;
+    fs::write(&path, &damaged).unwrap();
+    let store = KvStore::open(&path).unwrap();
+    assert_eq!(store.get_string("first").as_deref(), Some("one"));
+    assert!(!store.contains("second"));
+    drop(store);
+
+    // while one in an earlier record would silently lose the entries after it
+    let mut damaged = log.clone();
+    let value = log.windows(3).position(|window| window == b"one").unwrap();
+    damaged[value] ^= 1;
+    fs::write(&path, &damaged).unwrap();
+    let error = KvStore::open(&path).unwrap_err();
+    assert_eq!(error.kind(), std::io::ErrorKind::InvalidData);
+    assert_eq!(fs::read(&path).unwrap(), damaged);
+
+    let _ = fs::remove_file(&path);
+}
+
+#[test]
+fn logs_without_checksums_are_upgraded() {
+    let path = log_path("upgrade");
+    let mut log = b"BZKV\x00\x00\x00\x01".to_vec();
+    log.extend_from_slice(&[1, 3, 0, 0, 0, 2, 0, 0, 0]);
+    log.extend_from_slice(b"keyhi");
+    fs::write(&path, &log).unwrap();
+
+    let store = KvStore::open(&path).unwrap();
+    assert_eq!(store.get_string("key").as_deref(), Some("hi"));
+    store.set("other", "value").unwrap();
+    drop(store);
+    assert!(fs::read(&path)
+        .unwrap()
+        .starts_with(b"BZKV\x00\x00\x00\x02"));
+    let store = KvStore::open(&path).unwrap();
+    assert_eq!(store.get_string("key").as_deref(), Some("hi"));
+    assert_eq!(store.get_string("other").as_deref(), Some("value"));
+
+    let _ = fs::remove_file(&path);
+}
+
+#[test]
+fn sessions_survive_reopening() {
+    let path = log_path("sessions");
+    let data = SessionData::from([("user".to_string(), "axew".to_string())]);
+    {
+        let store = KvStore::open(&path).unwrap();
+        store.save("kept", data.clone(), Duration::from_secs(60));
+        store.save("expired", data.clone(), Duration::ZERO);
+        store.save("removed", data.clone(), Duration::from_secs(60));
+        SessionStore::remove(&store, "removed");
+        assert_eq!(store.load("expired"), None);
+        assert_eq!(store.load("removed"), None);
+    }
+
+    let store = KvStore::open(&path).unwrap();
+    assert_eq!(store.load("kept"), Some(data.clone()));
+    // saving purges the expired sessions
+    store.save("other", data, Duration::from_secs(60));
+    assert_eq!(
+        store.keys_with_prefix("session:"),
+        vec!["session:kept", "session:other"]
+    );
+
+    let _ = fs::remove_file(&path);
+}
@@ -0,0 +1,422 @@
+//! A harness for end-to-end tests, which run a real `WebServer` on an ephemeral port and talk to it
+//! over raw sockets.
+//!
+//! Scenarios are pairs of files in a directory: `<name>.request` holds the raw bytes sent to the
+//! server, `<name>.response` the golden(expected) bytes it answers with. Both use the same text
+//! format, so that line endings and malformed input stay visible in review:
+//!
+//! - every line of the file ends with `\r\n` on the wire, the last line included
+//! - a line ending in a single `\` isn't terminated at all(like a body without a trailing newline)
+//! - `\r`, `\n`, `\t`, `\\` and `\xNN` escapes stand for the matching bytes, so a bare LF is
+//!   written as `\n`
+//!
+//! The server's response is read until the server closes the connection and normalized before the
+//! comparison: headers are sorted by name, since their order isn't stable, and the values of
+//! `VOLATILE_HEADERS` are masked. Everything else, bodies included, is compared byte for byte.
+//!
+//! To add a scenario, write it's `.request` file by hand(or with `write_scenario`, for requests
+//! which are easier to build in code) and run the tests with `BROWZER_BLESS=1` set, which writes
+//! the `.response` files of the scenarios from the actual responses instead of comparing them.
+//! Review the new golden file like any other change.
+
+// the helpers are shared by several test crates, which don't all use every one of them
+#![allow(dead_code)]
+
+use browzer_web::WebServer;
+use std::{
+    env, fs,
+    io::{self, Read, Write},
+    net::{Shutdown, SocketAddr, TcpStream},
+    path::{Path, PathBuf},
+    thread,
+    time::Duration,
+};
+
+/// Headers whose values change from run to run, masked in normalized responses.
+pub const VOLATILE_HEADERS: &[&str] = &["Date", "Last-Modified"];
+
+/// The environment variable which makes `check_scenarios` write the golden files.
+pub const BLESS_VAR: &str = "BROWZER_BLESS";
+
+/// How long reading a response may take before the scenario fails.
+const READ_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Starts a server on an ephemeral port of the loopback interface, listening on a background
+/// thread for the rest of the test process.
+///
+/// # Arguments
+///
+/// - `configure` - Registers the routes and settings of the server.
+///
+/// # Returns
+///
+/// - `SocketAddr` - The address the server listens on.
+pub fn start_server<F: FnOnce(&mut WebServer)>(configure: F) -> SocketAddr {
+    let mut server = WebServer::new("127.0.0.1:0".to_string(), 2);
+    server.hide_banner = true;
+    configure(&mut server);
+    let address = server.listener.local_addr().unwrap();
+    thread::spawn(move || server.listen());
+    return address;
+}
+
+/// Starts a server like `start_server`, but listening with `WebServer::listen_async` on a tokio
+/// runtime of it's own.
+#[cfg(feature = "tokio")]
+pub fn start_async_server<F: FnOnce(&mut WebServer)>(configure: F) -> SocketAddr {
+    let mut server = WebServer::new("127.0.0.1:0".to_string(), 2);
+    server.hide_banner = true;
+    configure(&mut server);
+    let address = server.listener.local_addr().unwrap();
+    thread::spawn(move || {
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        runtime.block_on(server.listen_async()).unwrap();
+    });
+    return address;
+}
+
+/// Starts a server like `start_server`, but serving HTTPS with the test certificate in
+/// `tests/fixtures/tls`, which is issued for `localhost` and `127.0.0.1` by the CA in `ca.pem`.
+#[cfg(feature = "tls")]
+pub fn start_tls_server<F: FnOnce(&mut WebServer)>(configure: F) -> SocketAddr {
+    let server = tls_server(configure);
+    let address = server.listener.local_addr().unwrap();
+    thread::spawn(move || server.listen());
+    return address;
+}
+
+/// Starts a server like `start_tls_server`, but serving it on a tokio runtime like
+/// `start_async_server`.
+#[cfg(all(feature = "tls", feature = "tokio"))]
+pub fn start_async_tls_server<F: FnOnce(&mut WebServer)>(configure: F) -> SocketAddr {
+    let server = tls_server(configure);
+    let address = server.listener.local_addr().unwrap();
+    thread::spawn(move || {
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        runtime.block_on(server.listen_async()).unwrap();
+    });
+    return address;
+}
+
+// a server with the test certificate, configured by the test
+#[cfg(feature = "tls")]
+fn tls_server<F: FnOnce(&mut WebServer)>(configure: F) -> WebServer {
+    let fixtures = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/tls");
+    let mut server = WebServer::new_tls(
+        "127.0.0.1:0".to_string(),
+        2,
+        fixtures.join("cert.pem").to_str().unwrap(),
+        fixtures.join("key.pem").to_str().unwrap(),
+    );
+    server.hide_banner = true;
+    configure(&mut server);
+    return server;
+}
+
+/// Sends raw bytes to the server, closes the writing half of the connection and reads everything
+/// the server sends back until it closes the connection.
+pub fn exchange(address: SocketAddr, request: &[u8]) -> io::Result<Vec<u8>> {
+    let mut stream = TcpStream::connect(address)?;
+    stream.set_read_timeout(Some(READ_TIMEOUT))?;
+    stream.write_all(request)?;
+    stream.shutdown(Shutdown::Write)?;
+    let mut response = Vec::new();
+    stream.read_to_end(&mut response)?;
+    return Ok(response);
+}
+
+/// Sends a request with the extra header lines(each ending in `\r\n`) on a new connection,
+/// returning the whole response.
+pub fn request(address: SocketAddr, method: &str, path: &str, headers: &str) -> String {
+    return String::from_utf8(request_bytes(address, method, path, headers)).unwrap();
+}
+
+/// Sends a request like `request`, returning the response as bytes for bodies which aren't text.
+pub fn request_bytes(address: SocketAddr, method: &str, path: &str, headers: &str) -> Vec<u8> {
+    let raw = format!(
+        "{} {} HTTP/1.1\r\nHost: localhost\r\n{}Connection: close\r\n\r\n",
+        method, path, headers
+    );
+    return exchange(address, raw.as_bytes()).unwrap();
+}
+
+/// Sends a request like `request`, with a body and it's `Content-Length` header.
+pub fn request_with_body(
+    address: SocketAddr,
+    method: &str,
+    path: &str,
+    headers: &str,
+    body: &str,
+) -> String {
+    let raw = format!(
+        "{} {} HTTP/1.1\r\nHost: localhost\r\n{}Content-Length: {}\r\nConnection: close\r\n\r\n{}",
+        method,
+        path,
+        headers,
+        body.len(),
+        body
+    );
+    let response = exchange(address, raw.as_bytes()).unwrap();
+    return String::from_utf8(response).unwrap();
+}
+
+/// Encodes bytes into the text format of scenario files.
+pub fn encode(bytes: &[u8]) -> String {
+    let mut text = String::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'\r' if bytes.get(i + 1) == Some(&b'\n') => {
+                text.push('\n');
+                i += 2;
+                continue;
+            }
+            b'\r' => text.push_str("\\r"),
+            b'\n' => text.push_str("\\n"),
+            b'\t' => text.push_str("\\t"),
+            b'\\' => text.push_str("\\\\"),
+            byte @ 0x20..=0x7e => text.push(byte as char),
+            byte => text.push_str(&format!("\\x{:02x}", byte)),
+        }
+        i += 1;
+    }
+    if !bytes.ends_with(b"\r\n") {
+        text.push_str("\\\n");
+    }
+    return text;
+}
+
+/// Decodes the text format of scenario files into the bytes it stands for.
+///
+/// # Panics
+///
+/// Panics on an unknown or incomplete escape.
+pub fn decode(text: &str) -> Vec<u8> {
+    let text = text.strip_suffix('\n').unwrap_or(text);
+    let mut bytes = Vec::with_capacity(text.len());
+    for line in text.split('\n') {
+        // tolerate files checked out with CRLF line endings, a CR on the wire is always escaped
+        let line = line.strip_suffix('\r').unwrap_or(line);
+        let mut terminated = true;
+        let mut chars = line.chars();
+        while let Some(c) = chars.next() {
+            if c != '\\' {
+                let mut buffer = [0; 4];
+                bytes.extend_from_slice(c.encode_utf8(&mut buffer).as_bytes());
+                continue;
+            }
+            match chars.next() {
+                None => terminated = false,
+                Some('r') => bytes.push(b'\r'),
+                Some('n') => bytes.push(b'\n'),
+                Some('t') => bytes.push(b'\t'),
+                Some('\\') => bytes.push(b'\\'),
+                Some('x') => {
+                    let hex: String = chars.by_ref().take(2).collect();
+                    match u8::from_str_radix(&hex, 16) {
+                        Ok(byte) if hex.len() == 2 => bytes.push(byte),
+                        _ => panic!("invalid escape \\x{} in line {:?}", hex, line),
+                    }
+                }
+                Some(other) => panic!("unknown escape \\{} in line {:?}", other, line),
+            }
+        }
+        if terminated {
+            bytes.extend_from_slice(b"\r\n");
+        }
+    }
+    return bytes;
+}
+
+/// Normalizes the responses read from a connection, so that they can be compared byte for byte.
+///
+/// The headers of every response are sorted by name and the values of `VOLATILE_HEADERS` are
+/// replaced with `<masked>`, bodies are left untouched.
+pub fn normalize(responses: &[u8]) -> Vec<u8> {
+    let mut normalized = Vec::with_capacity(responses.len());
+    let mut rest = responses;
+    while !rest.is_empty() {
+        let head_length = match find(rest, b"\r\n\r\n") {
+            Some(position) => position,
+            None => {
+                normalized.extend_from_slice(rest);
+                break;
+            }
+        };
+        let head = String::from_utf8_lossy(&rest[..head_length]).into_owned();
+        rest = &rest[head_length + 4..];
+
+        let mut lines = head.split("\r\n");
+        let status_line = lines.next().unwrap_or_default();
+        let mut headers: Vec<String> = lines.map(mask).collect();
+        headers.sort_by_key(|header| header.to_ascii_lowercase());
+        normalized.extend_from_slice(status_line.as_bytes());
+        normalized.extend_from_slice(b"\r\n");
+        for header in headers.iter() {
+            normalized.extend_from_slice(header.as_bytes());
+            normalized.extend_from_slice(b"\r\n");
+        }
+        normalized.extend_from_slice(b"\r\n");
+
+        let body_length = body_length(&headers, rest);
+        normalized.extend_from_slice(&rest[..body_length]);
+        rest = &rest[body_length..];
+    }
+    return normalized;
+}
+
+/// A scenario of a transcript directory.
+pub struct Scenario {
+    pub name: String,
+    pub request: Vec<u8>,
+    pub golden_path: PathBuf,
+}
+
+/// Loads the scenarios of a directory, sorted by name.
+pub fn scenarios(directory: &Path) -> Vec<Scenario> {
+    let mut scenarios = Vec::new();
+    for entry in fs::read_dir(directory).unwrap() {
+        let path = entry.unwrap().path();
+        if path.extension().and_then(|extension| extension.to_str()) != Some("request") {
+            continue;
+        }
+        let name = path.file_stem().unwrap().to_string_lossy().into_owned();
+        scenarios.push(Scenario {
+            request: decode(&fs::read_to_string(&path).unwrap()),
+            golden_path: path.with_extension("response"),
+            name,
+        });
+    }
+    scenarios.sort_by(|a, b| a.name.cmp(&b.name));
+    return scenarios;
+}
+
+/// Writes the `.request` file of a new scenario from raw bytes.
+pub fn write_scenario(directory: &Path, name: &str, request: &[u8]) {
+    fs::write(directory.join(format!("{}.request", name)), encode(request)).unwrap();
+}
+
+/// Runs every scenario of a directory against a server and compares the normalized responses
+/// with the golden files, or writes the golden files if `BLESS_VAR` is set.
+///
+/// # Panics
+///
+/// Panics listing every scenario whose response differs from it's golden file.
+pub fn check_scenarios(address: SocketAddr, directory: &Path) {
+    let bless = env::var_os(BLESS_VAR).is_some();
+    let scenarios = scenarios(directory);
+    assert!(!scenarios.is_empty(), "no scenarios in {:?}", directory);
+
+    let mut failures = Vec::new();
+    for scenario in scenarios.iter() {
+        let actual = match exchange(address, &scenario.request) {
+            Ok(response) => normalize(&response),
+            Err(e) => {
+                failures.push(format!("{}: the exchange failed: {}", scenario.name, e));
+                continue;
+            }
+        };
+        if bless {
+            fs::write(&scenario.golden_path, encode(&actual)).unwrap();
+            continue;
+        }
+        let expected = match fs::read_to_string(&scenario.golden_path) {
+            Ok(golden) => decode(&golden),
+            Err(_) => {
+                failures.push(format!(
+                    "{}: the golden file is missing, run the tests with {}=1 to write it",
+                    scenario.name, BLESS_VAR
+                ));
+                continue;
+            }
+        };
+        if actual != expected {
+            failures.push(format!(
+                "{}: the response differs from the golden file\n--- expected\n{}--- actual\n{}",
+                scenario.name,
+                encode(&expected),
+                encode(&actual)
+            ));
+        }
+    }
+    assert!(
+        failures.is_empty(),
+        "{} of {} scenarios failed:\n\n{}",
+        failures.len(),
+        scenarios.len(),
+        failures.join("\n")
+    );
+}
+
+// replaces the value of a volatile header line
+fn mask(header: &str) -> String {
+    let name = header.split(':').next().unwrap_or_default();
+    match VOLATILE_HEADERS
+        .iter()
+        .any(|volatile| volatile.eq_ignore_ascii_case(name.trim()))
+    {
+        true => return format!("{}: <masked>", name),
+        false => return header.to_string(),
+    }
+}
+
+// determines how many of the remaining bytes belong to the body of a response
+fn body_length(headers: &[String], rest: &[u8]) -> usize {
+    let header = |wanted: &str| {
+        headers.iter().find_map(|header| {
+            let (name, value) = header.split_once(':')?;
+            match name.trim().eq_ignore_ascii_case(wanted) {
+                true => Some(value.trim().to_string()),
+                false => None,
+            }
+        })
+    };
+    if header("Transfer-Encoding").is_some_and(|value| value.eq_ignore_ascii_case("chunked")) {
+        return chunked_length(rest).unwrap_or(rest.len());
+    }
+    let length = match header("Content-Length").and_then(|value| value.parse::<usize>().ok()) {
+        Some(length) => length.min(rest.len()),
+        None => return rest.len(),
+    };
+    // responses to HEAD requests announce the length of a body they don't have, so if the next
+    // response starts right away the body is empty
+    if rest.starts_with(b"HTTP/1.")
+        && !rest[length..].is_empty()
+        && !rest[length..].starts_with(b"HTTP/1.")
+    {
+        return 0;
+    }
+    return length;
+}
+
+// determines the length of a chunked body, up to and including the terminating empty chunk
+fn chunked_length(body: &[u8]) -> Option<usize> {
+    let mut position = 0;
+    loop {
+        let line_length = find(&body[position..], b"\r\n")?;
+        let size_line = std::str::from_utf8(&body[position..position + line_length]).ok()?;
+        let size = usize::from_str_radix(size_line.split(';').next()?.trim(), 16).ok()?;
+        position += line_length + 2;
+        if size == 0 {
+            // skip the trailers up to the final empty line
+            loop {
+                let trailer_length = find(&body[position..], b"\r\n")?;
+                position += trailer_length + 2;
+                if trailer_length == 0 {
+                    return Some(position);
+                }
+            }
+        }
+        position += size + 2;
+        if position > body.len() {
+            return None;
+        }
+    }
+}
+
+// returns the position of the first occurrence of a needle
+fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    return haystack
+        .windows(needle.len())
+        .position(|window| window == needle);
+}
@@ -0,0 +1,59 @@
+//! End-to-end tests for tasks spawned by handlers with `Context::spawn_scoped`.
+
+mod support;
+
+use browzer_web::utils::HttpStatusCode;
+use std::{
+    net::SocketAddr,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    thread,
+    time::Duration,
+};
+
+fn get(address: SocketAddr, path: &str) -> String {
+    let response = support::request(address, "GET", path, "");
+    return response.split_once("\r\n\r\n").unwrap().1.to_string();
+}
+
+#[test]
+fn joins_tasks_and_stops_dropped_ones_before_responding() {
+    let stopped = Arc::new(AtomicBool::new(false));
+    let task_stopped = Arc::clone(&stopped);
+    let address = support::start_server(move |server| {
+        server.background_workers(2);
+        server.get("/sum", |mut c| {
+            let tasks: Vec<_> = (1..=4u64).map(|n| c.spawn_scoped(move |_| n * n)).collect();
+            let sum: u64 = tasks.into_iter().map(|task| task.join().unwrap()).sum();
+            return c.send_string(HttpStatusCode::OK, &sum.to_string());
+        });
+        server.get("/early", move |mut c| {
+            let stopped = Arc::clone(&task_stopped);
+            let _task = c.spawn_scoped(move |token| {
+                while !token.is_cancelled() {
+                    thread::sleep(Duration::from_millis(5));
+                }
+                stopped.store(true, Ordering::SeqCst);
+            });
+            // returning drops the task, which cancels it and waits for it
+            return c.send_string(HttpStatusCode::OK, "early");
+        });
+        server.get("/panic", |mut c| {
+            let task = c.spawn_scoped(|_| -> u64 { panic!("task failed") });
+            let body = match task.join() {
+                Ok(_) => "joined".to_string(),
+                Err(e) => e.to_string(),
+            };
+            return c.send_string(HttpStatusCode::OK, &body);
+        });
+    });
+
+    assert_eq!(get(address, "/sum"), "30");
+    assert_eq!(get(address, "/early"), "early");
+    assert!(stopped.load(Ordering::SeqCst));
+    // a panicking task is reported to whoever joins it, and the pool keeps running
+    assert_eq!(get(address, "/panic"), "Task panicked: task failed");
+    assert_eq!(get(address, "/sum"), "30");
+}
@@ -0,0 +1,182 @@
+//! Tests for HTML templates(`templates::Templates`) and rendering them from handlers
+//! (`Context::render`).
+
+mod support;
+
+use browzer_web::{error::TemplateError, pages::PageTemplates, templates::Templates};
+use serde_json::json;
+use std::{fs, net::SocketAddr, path::PathBuf, thread, time::Duration};
+
+/// Creates a template directory for a test, removing any left over from an earlier run.
+fn template_dir(name: &str, files: &[(&str, &str)]) -> PathBuf {
+    let dir =
+        std::env::temp_dir().join(format!("browzer_templates_{}_{}", std::process::id(), name));
+    let _ = fs::remove_dir_all(&dir);
+    for (path, source) in files {
+        let path = dir.join(path);
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, source).unwrap();
+    }
+    return dir;
+}
+
+/// Sends a GET request, returning the status line, the `Content-Type` header and the body.
+fn get(address: SocketAddr, path: &str) -> (String, String, String) {
+    let response = support::request(address, "GET", path, "");
+    let (head, body) = response.split_once("\r\n\r\n").unwrap();
+    let content_type = head
+        .lines()
+        .find_map(|line| line.strip_prefix("Content-Type: "))
+        .unwrap_or_default();
+    return (
+        head.lines().next().unwrap().to_string(),
+        content_type.to_string(),
+        body.to_string(),
+    );
+}
+
+#[test]
+fn handlers_render_templates_of_a_directory() {
+    let dir = template_dir(
+        "server",
+        &[
+            (
+                "base.html",
+                "<title>{% block title %}Blog{% endblock %}</title>\n{% block content %}{% endblock %}\n{% include \"partials/footer.html\" %}",
+            ),
+            ("partials/footer.html", "<footer>{{ site | upper }}</footer>"),
+            (
+                "profile.html",
+                "{% extends \"base.html\" %}\n{% block title %}{{ name }} - {{ super | default(\"Blog\") }}{% endblock %}\n{% block content %}\n{%- if admin and name != \"root\" %}<b>admin</b>{% elif posts | length == 0 %}no posts{% else %}{{ posts | length }} posts{% endif %}\n{%- endblock %}",
+            ),
+            (".hidden", "{% broken"),
+        ],
+    );
+    let templates = Templates::from_dir(&dir).unwrap();
+    assert_eq!(
+        templates.names(),
+        vec!["base.html", "partials/footer.html", "profile.html"]
+    );
+
+    let address = support::start_server(move |server| {
+        server.templates(templates);
+        server.page_templates(PageTemplates::new().layout("{{ content }}"));
+        server.get("/users/:name", |mut c| {
+            let name = c.params["name"].clone();
+            let data = json!({
+                "name": name,
+                "admin": name == "axew",
+                "posts": if name == "bob" { vec!["hello"] } else { vec![] },
+                "site": "acme",
+            });
+            return c.render("profile.html", &data);
+        });
+        server.get("/missing", |mut c| {
+            return c.render("missing.html", &json!({}));
+        });
+    });
+
+    assert_eq!(
+        get(address, "/users/axew"),
+        (
+            "HTTP/1.1 200 OK".to_string(),
+            "text/html; charset=utf-8".to_string(),
+            "<title>axew - Blog</title>\n<b>admin</b>\n<footer>ACME</footer>".to_string()
+        )
+    );
+    assert_eq!(
+        get(address, "/users/%3Cbob%3E").2,
+        "<title>&lt;bob&gt; - Blog</title>\nno posts\n<footer>ACME</footer>"
+    );
+    assert_eq!(
+        get(address, "/users/bob").2,
+        "<title>bob - Blog</title>\n1 posts\n<footer>ACME</footer>"
+    );
+    // failures are answered like any other error of the framework
+    assert_eq!(
+        get(address, "/missing"),
+        (
+            "HTTP/1.1 500 Internal Server Error".to_string(),
+            "text/html; charset=utf-8".to_string(),
+            "<h1>500 Internal Server Error</h1>\n".to_string()
+        )
+    );
+
+    let _ = fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn loops_expose_their_position() {
+    let mut templates = Templates::new();
+    templates
+        .add(
+            "list.html",
+            "{% for tag in tags %}{% if not loop.first %}, {% endif %}{{ loop.index }}:{{ tag }}{% if loop.last %}.{% endif %}{% else %}none{% endfor %}|{% for entry in counts %}{{ entry.key }}={{ entry.value }}{% endfor %}|{{ html | safe }}{# a comment #}",
+        )
+        .unwrap();
+
+    assert_eq!(
+        templates
+            .render(
+                "list.html",
+                &json!({ "tags": ["a", "b&c"], "counts": { "x": 1 }, "html": "<br>" })
+            )
+            .unwrap(),
+        "1:a, 2:b&amp;c.|x=1|<br>"
+    );
+    assert_eq!(templates.render("list.html", &json!({})).unwrap(), "none||");
+}
+
+#[test]
+fn invalid_templates_are_rejected() {
+    let mut templates = Templates::new();
+    for (source, line) in [
+        ("{% if a %}open", 1),
+        ("line\n{{ a | unknown }}", 2),
+        ("\n\n{% endfor %}", 3),
+        ("{% for in items %}{% endfor %}", 1),
+        ("{{ name", 1),
+    ] {
+        match templates.add("broken.html", source) {
+            Err(TemplateError::Syntax(name, error_line, _)) => {
+                assert_eq!(
+                    (name.as_str(), error_line),
+                    ("broken.html", line),
+                    "{}",
+                    source
+                )
+            }
+            other => panic!("{} wasn't rejected: {:?}", source, other),
+        }
+    }
+
+    // templates extending themselves are caught while rendering
+    templates
+        .add("loop.html", "{% extends \"loop.html\" %}")
+        .unwrap();
+    assert!(matches!(
+        templates.render("loop.html", &json!({})),
+        Err(TemplateError::Render(..))
+    ));
+    assert!(matches!(
+        templates.render("missing.html", &json!({})),
+        Err(TemplateError::NotFound(_))
+    ));
+}
+
+#[test]
+fn changed_templates_are_reloaded() {
+    let dir = template_dir("reload", &[("index.html", "first")]);
+    let templates = Templates::from_dir(&dir).unwrap().auto_reload(true);
+    assert_eq!(templates.render("index.html", &()).unwrap(), "first");
+
+    // file systems with a coarse modification time need a moment to tell the versions apart
+    thread::sleep(Duration::from_millis(1100));
+    fs::write(dir.join("index.html"), "second").unwrap();
+    fs::write(dir.join("new.html"), "new").unwrap();
+    assert_eq!(templates.render("index.html", &()).unwrap(), "second");
+    assert_eq!(templates.render("new.html", &()).unwrap(), "new");
+    assert!(templates.render("../index.html", &()).is_err());
+
+    let _ = fs::remove_dir_all(&dir);
+}
@@ -0,0 +1,197 @@
+//! Tests for the worker threads of a `utils::thread_pool::ThreadPool`, growing and shrinking with
+//! the load and surviving panicking jobs.
+
+use browzer_web::{
+    error::ThreadPoolError,
+    panics::{PanicLog, PanicPhase},
+    utils::thread_pool::ThreadPool,
+};
+use std::{
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        mpsc, Arc, Barrier, Mutex,
+    },
+    thread,
+    time::{Duration, Instant},
+};
+
+/// Waits until a condition holds, failing the test after a few seconds.
+fn wait_for<F: Fn() -> bool>(condition: F) {
+    let deadline = Instant::now() + Duration::from_secs(10);
+    while !condition() {
+        assert!(Instant::now() < deadline, "timed out");
+        thread::sleep(Duration::from_millis(10));
+    }
+}
+
+#[test]
+fn pools_grow_under_load_and_shrink_when_idle() {
+    let pool = ThreadPool::dynamic(1, 3, Duration::from_millis(100));
+    assert_eq!(pool.size(), 1);
+
+    // every job blocks until all of them run at the same time, which needs 3 workers
+    let barrier = Arc::new(Barrier::new(4));
+    for _ in 0..3 {
+        let barrier = Arc::clone(&barrier);
+        pool.execute(move || {
+            barrier.wait();
+        })
+        .unwrap();
+    }
+    barrier.wait();
+    assert_eq!(pool.size(), 3);
+
+    // the extra workers retire, the pool keeps it's initial one
+    wait_for(|| pool.size() == 1);
+    thread::sleep(Duration::from_millis(300));
+    assert_eq!(pool.size(), 1);
+}
+
+#[test]
+fn pools_never_grow_past_their_maximum() {
+    let pool = ThreadPool::dynamic(1, 2, Duration::from_secs(10));
+    let release = Arc::new(Barrier::new(3));
+    for _ in 0..4 {
+        let release = Arc::clone(&release);
+        pool.execute(move || {
+            release.wait();
+        })
+        .unwrap();
+    }
+    wait_for(|| pool.queued() == 2);
+    assert_eq!(pool.size(), 2);
+
+    // two rounds of two jobs each
+    release.wait();
+    release.wait();
+    wait_for(|| pool.queued() == 0 && pool.idle() == 2);
+}
+
+#[test]
+fn pools_submitted_to_concurrently_never_grow_past_their_maximum() {
+    let pool = Arc::new(ThreadPool::dynamic(1, 2, Duration::from_secs(10)));
+    let largest = Arc::new(AtomicUsize::new(0));
+    let submitters = (0..8)
+        .map(|_| {
+            let (pool, largest) = (Arc::clone(&pool), Arc::clone(&largest));
+            return thread::spawn(move || {
+                for _ in 0..50 {
+                    pool.execute(|| thread::sleep(Duration::from_millis(1)))
+                        .unwrap();
+                    largest.fetch_max(pool.size(), Ordering::SeqCst);
+                }
+            });
+        })
+        .collect::<Vec<_>>();
+    for submitter in submitters {
+        submitter.join().unwrap();
+    }
+    assert_eq!(largest.load(Ordering::SeqCst), 2);
+}
+
+#[test]
+fn idle_workers_retire_together() {
+    let pool = ThreadPool::dynamic(1, 4, Duration::from_millis(300));
+    let barrier = Arc::new(Barrier::new(5));
+    for _ in 0..4 {
+        let barrier = Arc::clone(&barrier);
+        pool.execute(move || {
+            barrier.wait();
+        })
+        .unwrap();
+    }
+    barrier.wait();
+    assert_eq!(pool.size(), 4);
+
+    // the three extra workers were idle since the same moment, so they don't retire one after
+    // the other
+    let idle_since = Instant::now();
+    wait_for(|| pool.size() == 1);
+    assert!(
+        idle_since.elapsed() < Duration::from_millis(600),
+        "{:?}",
+        idle_since.elapsed()
+    );
+}
+
+#[test]
+fn fixed_pools_keep_their_size() {
+    let pool = ThreadPool::new(2);
+    assert_eq!((pool.min_workers(), pool.max_workers()), (2, 2));
+    for _ in 0..10 {
+        pool.execute(|| thread::sleep(Duration::from_millis(10)))
+            .unwrap();
+    }
+    assert_eq!(pool.size(), 2);
+}
+
+#[test]
+fn workers_survive_panicking_jobs() {
+    let pool = ThreadPool::new(1);
+    pool.execute(|| panic!("job failed")).unwrap();
+    pool.execute(|| panic!("{}", "job failed again".to_string()))
+        .unwrap();
+
+    // the only worker is still there to run the next job
+    let (sender, receiver) = std::sync::mpsc::channel();
+    pool.execute(move || sender.send("done").unwrap()).unwrap();
+    assert_eq!(
+        receiver.recv_timeout(Duration::from_secs(10)).unwrap(),
+        "done"
+    );
+    assert_eq!(pool.recovered_panics(), 2);
+    assert_eq!(pool.size(), 1);
+}
+
+#[test]
+fn panicking_jobs_are_reported_to_the_panic_log() {
+    let reports = Arc::new(Mutex::new(vec![]));
+    let seen = Arc::clone(&reports);
+    let mut panic_log = PanicLog::default();
+    panic_log.hook = Some(Box::new(move |report| {
+        seen.lock()
+            .unwrap()
+            .push((report.phase, report.message.map(|m| m.to_string())));
+    }));
+    let panic_log = Arc::new(panic_log);
+    let pool = ThreadPool::new(1);
+    pool.set_panic_log(Arc::clone(&panic_log));
+
+    pool.execute(|| panic!("job failed")).unwrap();
+    // the panic is counted before it's reported
+    wait_for(|| reports.lock().unwrap().len() == 1);
+    assert_eq!(pool.recovered_panics(), 1);
+    assert_eq!(
+        *reports.lock().unwrap(),
+        vec![(PanicPhase::Job, Some("job failed".to_string()))]
+    );
+    assert_eq!(panic_log.counts().jobs, 1);
+}
+
+#[test]
+fn full_queues_reject_jobs_without_waiting() {
+    let pool = ThreadPool::new(1);
+    pool.set_queue_capacity(Some(1));
+    let (started, started_receiver) = mpsc::channel();
+    let (release, release_receiver) = mpsc::channel::<()>();
+    pool.try_execute(move || {
+        started.send(()).unwrap();
+        let _ = release_receiver.recv();
+    })
+    .unwrap();
+    started_receiver
+        .recv_timeout(Duration::from_secs(10))
+        .unwrap();
+
+    // the only worker is busy, so the first job waits in the queue and the second doesn't fit
+    let queued = pool.try_execute(|| {});
+    let rejected = pool.try_execute(|| {});
+    let queued_jobs = pool.queued();
+    drop(release);
+    queued.unwrap();
+    assert!(matches!(rejected, Err(ThreadPoolError::QueueFull)));
+    assert_eq!(queued_jobs, 1);
+
+    wait_for(|| pool.queued() == 0);
+    pool.try_execute(|| {}).unwrap();
+}
@@ -0,0 +1,192 @@
+//! End-to-end tests for HTTPS(`WebServer::new_tls`) and serving plain HTTP on the same port
+//! (`WebServer::accept_plaintext`), and for the HTTPS client of the reverse proxy and the webhooks.
+#![cfg(feature = "tls")]
+
+mod support;
+
+use browzer_web::{
+    http_client::Client,
+    reverse_proxy::ReverseProxy,
+    utils::HttpStatusCode,
+    webhooks::{Dispatcher, HttpTransport},
+    WebServer,
+};
+use rustls::{pki_types::ServerName, ClientConfig, ClientConnection, RootCertStore, StreamOwned};
+use serde_json::json;
+use std::{
+    fs::File,
+    io::{BufReader, Read, Write},
+    net::{SocketAddr, TcpStream},
+    path::Path,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+const REQUEST: &[u8] = b"GET /scheme HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n";
+
+fn app(server: &mut WebServer) {
+    server.get("/scheme", |mut c| {
+        let scheme = match c.request.tls {
+            true => "https",
+            false => "http",
+        };
+        return c.send_string(HttpStatusCode::OK, scheme);
+    });
+}
+
+// a client trusting only the CA of the test certificate
+fn client_config() -> Arc<ClientConfig> {
+    let ca_path = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/tls/ca.pem");
+    let mut roots = RootCertStore::empty();
+    for cert in rustls_pemfile::certs(&mut BufReader::new(File::open(ca_path).unwrap())) {
+        roots.add(cert.unwrap()).unwrap();
+    }
+    let config =
+        ClientConfig::builder_with_provider(Arc::new(rustls::crypto::ring::default_provider()))
+            .with_safe_default_protocol_versions()
+            .unwrap()
+            .with_root_certificates(roots)
+            .with_no_client_auth();
+    return Arc::new(config);
+}
+
+/// Sends a request over TLS, returning everything the server sent back decrypted.
+fn https_exchange(address: SocketAddr, request: &[u8]) -> String {
+    let stream = TcpStream::connect(address).unwrap();
+    stream
+        .set_read_timeout(Some(Duration::from_secs(10)))
+        .unwrap();
+    let server_name = ServerName::try_from("localhost").unwrap();
+    let connection = ClientConnection::new(client_config(), server_name).unwrap();
+    let mut tls = StreamOwned::new(connection, stream);
+    tls.write_all(request).unwrap();
+    let mut response = Vec::new();
+    // the server closes the TCP connection without a `close_notify` alert after the response
+    if let Err(e) = tls.read_to_end(&mut response) {
+        assert_eq!(e.kind(), std::io::ErrorKind::UnexpectedEof, "{}", e);
+    }
+    return String::from_utf8(response).unwrap();
+}
+
+fn body(response: &str) -> &str {
+    return response.split_once("\r\n\r\n").unwrap().1;
+}
+
+#[test]
+fn serves_https() {
+    let address = support::start_tls_server(app);
+    let response = https_exchange(address, REQUEST);
+    assert!(response.starts_with("HTTP/1.1 200 OK\r\n"), "{}", response);
+    assert_eq!(body(&response), "https");
+
+    // without `accept_plaintext` a plain HTTP request fails the handshake
+    let response = support::exchange(address, REQUEST).unwrap_or_default();
+    assert!(!response.starts_with(b"HTTP/1.1 200"));
+}
+
+#[test]
+fn handlers_see_what_the_handshake_negotiated() {
+    let address = support::start_tls_server(|server| {
+        app(server);
+        server.get("/tls", |mut c| {
+            let tls = match c.connection().tls() {
+                Some(tls) => format!(
+                    "{} {}",
+                    tls.version.clone().unwrap_or_default(),
+                    tls.cipher_suite.clone().unwrap_or_default()
+                ),
+                None => "none".to_string(),
+            };
+            return c.send_string(HttpStatusCode::OK, &tls);
+        });
+        server.accept_plaintext(true);
+    });
+
+    let request = b"GET /tls HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n";
+    let response = https_exchange(address, request);
+    let (version, cipher_suite) = body(&response).split_once(' ').unwrap();
+    assert!(version.starts_with("TLSv1_"), "{}", response);
+    assert!(cipher_suite.starts_with("TLS"), "{}", response);
+
+    let response = String::from_utf8(support::exchange(address, request).unwrap()).unwrap();
+    assert_eq!(body(&response), "none");
+}
+
+#[test]
+fn detects_plaintext_connections_on_the_tls_port() {
+    let address = support::start_tls_server(|server| {
+        app(server);
+        server.accept_plaintext(true);
+    });
+
+    let response = https_exchange(address, REQUEST);
+    assert!(response.starts_with("HTTP/1.1 200 OK\r\n"), "{}", response);
+    assert_eq!(body(&response), "https");
+
+    let response = String::from_utf8(support::exchange(address, REQUEST).unwrap()).unwrap();
+    assert!(response.starts_with("HTTP/1.1 200 OK\r\n"), "{}", response);
+    assert_eq!(body(&response), "http");
+
+    // a connection closed before sending anything doesn't take the server down
+    drop(TcpStream::connect(address).unwrap());
+    assert_eq!(body(&https_exchange(address, REQUEST)), "https");
+}
+
+#[cfg(feature = "tokio")]
+#[test]
+fn serves_https_on_the_tokio_runtime() {
+    let address = support::start_async_tls_server(|server| {
+        app(server);
+        server.get_async("/async", |mut c| async move {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+            return c.send_string(HttpStatusCode::OK, "async");
+        });
+        server.accept_plaintext(true);
+    });
+
+    let response = https_exchange(address, REQUEST);
+    assert!(response.starts_with("HTTP/1.1 200 OK\r\n"), "{}", response);
+    assert_eq!(body(&response), "https");
+    let request = b"GET /async HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n";
+    assert_eq!(body(&https_exchange(address, request)), "async");
+
+    let response = String::from_utf8(support::exchange(address, REQUEST).unwrap()).unwrap();
+    assert_eq!(body(&response), "http");
+}
+
+#[test]
+fn delivers_webhooks_over_https() {
+    let received = Arc::new(Mutex::new(vec![]));
+    let receiver_received = Arc::clone(&received);
+    let address = support::start_tls_server(move |server| {
+        server.post("/hooks", move |mut c| {
+            receiver_received
+                .lock()
+                .unwrap()
+                .push(c.request.body.clone().unwrap_or_default());
+            return c.send_string(HttpStatusCode::OK, "ok");
+        });
+    });
+
+    let transport = HttpTransport::new(Client::new().tls_config(client_config()));
+    let dispatcher = Dispatcher::new(b"whsec_test").transport(transport);
+    dispatcher.register(&format!("https://localhost:{}/hooks", address.port()), &[]);
+    dispatcher.dispatch("ping", &json!({"n": 1})).unwrap();
+
+    assert!(dispatcher.wait_idle(Duration::from_secs(5)));
+    assert_eq!(dispatcher.metrics().delivered, 1);
+    assert!(received.lock().unwrap()[0].ends_with("\"data\":{\"n\":1}}"));
+}
+
+#[test]
+fn proxies_to_https_upstreams() {
+    let upstream = support::start_tls_server(app);
+    let proxy = ReverseProxy::new(&format!("https://localhost:{}", upstream.port()))
+        .unwrap()
+        .client(Client::new().tls_config(client_config()));
+    let address = support::start_server(move |server| server.serve_proxy("/*path", proxy));
+
+    let response = String::from_utf8(support::exchange(address, REQUEST).unwrap()).unwrap();
+    assert!(response.starts_with("HTTP/1.1 200 OK\r\n"), "{}", response);
+    assert_eq!(body(&response), "https");
+}
@@ -0,0 +1,176 @@
+//! End-to-end tests for the `tracing` span of every request(`tracing` feature).
+#![cfg(feature = "tracing")]
+
+mod support;
+
+use browzer_web::{request_id::RequestIds, utils::HttpStatusCode};
+use std::{
+    cell::RefCell,
+    collections::HashMap,
+    fmt,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex, OnceLock,
+    },
+};
+use tracing::{
+    field::{Field, Visit},
+    span, Event, Metadata, Subscriber,
+};
+
+/// A recorded span, with it's fields and the messages of the events inside it.
+#[derive(Debug, Clone, Default)]
+struct RecordedSpan {
+    name: String,
+    fields: HashMap<String, String>,
+    events: Vec<String>,
+}
+
+/// Collects the fields of every value it visits.
+struct FieldVisitor<'a>(&'a mut HashMap<String, String>);
+
+impl Visit for FieldVisitor<'_> {
+    fn record_str(&mut self, field: &Field, value: &str) {
+        self.0.insert(field.name().to_string(), value.to_string());
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        self.0
+            .insert(field.name().to_string(), format!("{:?}", value));
+    }
+}
+
+thread_local! {
+    // the spans entered on the current thread
+    static ENTERED: RefCell<Vec<u64>> = const { RefCell::new(Vec::new()) };
+}
+
+/// A subscriber recording every span, standing in for `tracing-subscriber`.
+#[derive(Default)]
+struct Recorder {
+    next_id: AtomicU64,
+    spans: Mutex<HashMap<u64, RecordedSpan>>,
+}
+
+impl Subscriber for Recorder {
+    fn enabled(&self, _: &Metadata<'_>) -> bool {
+        return true;
+    }
+
+    fn new_span(&self, attributes: &span::Attributes<'_>) -> span::Id {
+        let id = self.next_id.fetch_add(1, Ordering::SeqCst) + 1;
+        let mut recorded = RecordedSpan {
+            name: attributes.metadata().name().to_string(),
+            ..RecordedSpan::default()
+        };
+        attributes.record(&mut FieldVisitor(&mut recorded.fields));
+        self.spans.lock().unwrap().insert(id, recorded);
+        return span::Id::from_u64(id);
+    }
+
+    fn record(&self, span: &span::Id, values: &span::Record<'_>) {
+        if let Some(recorded) = self.spans.lock().unwrap().get_mut(&span.into_u64()) {
+            values.record(&mut FieldVisitor(&mut recorded.fields));
+        }
+    }
+
+    fn record_follows_from(&self, _: &span::Id, _: &span::Id) {}
+
+    fn event(&self, event: &Event<'_>) {
+        let mut fields = HashMap::new();
+        event.record(&mut FieldVisitor(&mut fields));
+        if let Some(id) = ENTERED.with(|entered| entered.borrow().last().copied()) {
+            if let Some(recorded) = self.spans.lock().unwrap().get_mut(&id) {
+                recorded
+                    .events
+                    .push(fields.remove("message").unwrap_or_default());
+            }
+        }
+    }
+
+    fn enter(&self, span: &span::Id) {
+        ENTERED.with(|entered| entered.borrow_mut().push(span.into_u64()));
+    }
+
+    fn exit(&self, _: &span::Id) {
+        ENTERED.with(|entered| entered.borrow_mut().pop());
+    }
+}
+
+/// Returns the subscriber of the test process, whose servers run on threads of their own.
+fn recorder() -> &'static Arc<Recorder> {
+    static RECORDER: OnceLock<Arc<Recorder>> = OnceLock::new();
+    return RECORDER.get_or_init(|| {
+        let recorder = Arc::new(Recorder::default());
+        tracing::subscriber::set_global_default(Arc::clone(&recorder)).unwrap();
+        return recorder;
+    });
+}
+
+/// Returns the recorded request spans for a path.
+fn request_spans(path: &str) -> Vec<RecordedSpan> {
+    return recorder()
+        .spans
+        .lock()
+        .unwrap()
+        .values()
+        .filter(|span| {
+            span.name == "request" && span.fields.get("path").map(String::as_str) == Some(path)
+        })
+        .cloned()
+        .collect();
+}
+
+#[test]
+fn requests_run_in_a_span() {
+    recorder();
+    let address = support::start_server(|server| {
+        server.request_ids(RequestIds::new());
+        server.middleware(|c| {
+            tracing::info!("checking the session");
+            return c;
+        });
+        server.get("/users/:id", |mut c| {
+            tracing::info!("loading the user");
+            return c.send_string(HttpStatusCode::OK, "user");
+        });
+    });
+
+    support::request(
+        address,
+        "GET",
+        "/users/1?tab=posts",
+        "X-Request-Id: trace-1\r\n",
+    );
+
+    let spans = request_spans("/users/1");
+    assert_eq!(spans.len(), 1, "{:?}", spans);
+    let span = &spans[0];
+    assert_eq!(span.fields["method"], "GET");
+    assert_eq!(span.fields["request_id"], "trace-1");
+    assert_eq!(span.fields["status"], "200");
+    assert!(
+        span.fields["remote_addr"].starts_with("127.0.0.1:"),
+        "{:?}",
+        span
+    );
+    // the middleware and the handler run inside the span
+    assert_eq!(
+        span.events,
+        vec!["checking the session", "loading the user"]
+    );
+}
+
+#[test]
+fn framework_responses_record_their_status() {
+    recorder();
+    let address = support::start_server(|_| {});
+
+    support::request(address, "GET", "/nowhere", "");
+
+    let spans = request_spans("/nowhere");
+    assert_eq!(spans.len(), 1, "{:?}", spans);
+    assert_eq!(spans[0].fields["status"], "404");
+    // request IDs aren't assigned by this server
+    assert!(!spans[0].fields.contains_key("request_id"));
+}
@@ -0,0 +1,111 @@
+//! End-to-end tests replaying the HTTP transcripts in `tests/transcripts` against a running
+//! server, see the `support` module for the transcript format and how to add new scenarios.
+//!
+//! All scenarios run against the same small application defined in `app`, so a scenario can rely
+//! on it's routes and settings.
+
+mod support;
+
+use browzer_web::{
+    limits::HeadLimits,
+    utils::{Cookie, HttpStatusCode, SameSite},
+    WebServer,
+};
+use std::{io::Write, path::Path};
+
+/// The application the scenarios are replayed against.
+fn app(server: &mut WebServer) {
+    server.set_max_body_size(64);
+    server.set_head_limits(HeadLimits {
+        max_request_line: 1024,
+        max_headers: 16,
+        max_head_size: 4096,
+    });
+    server.enable_route_help("/_routes");
+    server.get("/", |mut c| {
+        return c.send_string(HttpStatusCode::OK, "Hello, World!");
+    });
+    server
+        .get("/users/:id", |mut c| {
+            let id = c.params.get("id").cloned().unwrap_or_default();
+            return c.send_string(HttpStatusCode::OK, &format!("user {}", id));
+        })
+        .doc("Returns the user with the given id");
+    server.delete("/users/:id", |mut c| {
+        // the body of a `204 No Content` response is never sent
+        return c.send_string(HttpStatusCode::NoContent, "deleted");
+    });
+    server.get("/greeting", |mut c| {
+        return c.send_string(HttpStatusCode::OK, "Grüße, 世界");
+    });
+    server.get("/report", |mut c| {
+        // the length of the report is only announced to `HEAD` requests, a `GET` request gets the
+        // empty body it actually has
+        let mut response = c.send_string(HttpStatusCode::OK, "");
+        response
+            .headers
+            .insert("Content-Length".to_string(), "1024".to_string());
+        return response;
+    });
+    server.get("/visits", |mut c| {
+        let visits = match c.get_cookie("visits") {
+            Some(cookie) => cookie.value.parse::<u64>().unwrap_or(0),
+            None => 0,
+        };
+        c.set_cookie(
+            Cookie::builder("visits", &(visits + 1).to_string())
+                .path("/")
+                .http_only(true)
+                .same_site(SameSite::Lax)
+                .build(),
+        );
+        return c.send_string(HttpStatusCode::OK, &format!("visits {}", visits));
+    });
+    server
+        .post("/echo", |mut c| {
+            let body = c.request.body.clone().unwrap_or_default();
+            return c.send_string(HttpStatusCode::OK, &body);
+        })
+        .doc("Echoes the request body, which may be <= 64 bytes");
+    server.get("/events", |mut c| {
+        return c.send_stream(HttpStatusCode::OK, |writer| {
+            writer.write_all(b"data: 1\n\n")?;
+            writer.flush_hint()?;
+            writer.write_all(b"data: 2\n\n")?;
+            return Ok(());
+        });
+    });
+}
+
+#[test]
+fn transcripts() {
+    let address = support::start_server(app);
+    let directory = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/transcripts");
+    support::check_scenarios(address, &directory);
+}
+
+// the async mode answers exactly like the worker threads do
+#[cfg(feature = "tokio")]
+#[test]
+fn transcripts_async() {
+    let address = support::start_async_server(app);
+    let directory = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/transcripts");
+    support::check_scenarios(address, &directory);
+}
+
+#[test]
+fn transcript_format_round_trips() {
+    let samples: &[&[u8]] = &[
+        b"",
+        b"GET / HTTP/1.1\r\n\r\n",
+        b"POST /echo HTTP/1.1\r\nContent-Length: 4\r\n\r\naxew",
+        b"GET / HTTP/1.1\nHost: a\rb\\c\t\x00\xff\r\n",
+    ];
+    for sample in samples {
+        assert_eq!(support::decode(&support::encode(sample)), *sample);
+    }
+    assert_eq!(
+        support::decode("GET / HTTP/1.1\nHost: a\n\n"),
+        b"GET / HTTP/1.1\r\nHost: a\r\n\r\n"
+    );
+}
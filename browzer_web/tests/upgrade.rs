@@ -0,0 +1,270 @@
+//! End-to-end tests for stopping a server gracefully and handing it's listening socket over to a
+//! new process(`upgrade` module, `WebServer::shutdown_handle`, `WebServer::upgrade_socket`).
+#![cfg(unix)]
+
+use browzer_web::{upgrade, utils::HttpStatusCode, WebServer};
+use std::{
+    env,
+    io::{Read, Write},
+    net::{SocketAddr, TcpListener, TcpStream},
+    os::unix::io::IntoRawFd,
+    path::PathBuf,
+    sync::{Mutex, MutexGuard},
+    thread,
+    time::{Duration, Instant},
+};
+
+// `WebServer::new` takes the inherited listener from the environment, so servers are created one
+// at a time
+static ENVIRONMENT: Mutex<()> = Mutex::new(());
+
+fn environment() -> MutexGuard<'static, ()> {
+    return ENVIRONMENT.lock().unwrap_or_else(|e| e.into_inner());
+}
+
+/// Returns a control socket path unique to the test.
+fn socket_path(name: &str) -> PathBuf {
+    return env::temp_dir().join(format!("browzer-{}-{}.sock", name, std::process::id()));
+}
+
+/// Sends a request on a new connection and returns the raw response.
+fn get(address: SocketAddr, path: &str) -> String {
+    let mut stream = TcpStream::connect(address).unwrap();
+    stream
+        .set_read_timeout(Some(Duration::from_secs(10)))
+        .unwrap();
+    stream
+        .write_all(
+            format!(
+                "GET {} HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n",
+                path
+            )
+            .as_bytes(),
+        )
+        .unwrap();
+    let mut response = String::new();
+    stream.read_to_string(&mut response).unwrap();
+    return response;
+}
+
+/// Waits for a condition, failing the test after a few seconds.
+fn wait_for<F: Fn() -> bool>(condition: F) {
+    let deadline = Instant::now() + Duration::from_secs(10);
+    while !condition() {
+        assert!(Instant::now() < deadline, "timed out");
+        thread::sleep(Duration::from_millis(10));
+    }
+}
+
+#[test]
+fn shutdown_drains_requests_in_flight() {
+    let mut server = {
+        let _environment = environment();
+        WebServer::new("127.0.0.1:0".to_string(), 2)
+    };
+    server.hide_banner = true;
+    server.get("/slow", |mut c| {
+        thread::sleep(Duration::from_millis(300));
+        return c.send_string(HttpStatusCode::OK, "done");
+    });
+    let address = server.listener.local_addr().unwrap();
+    let shutdown = server.shutdown_handle();
+    let listening = thread::spawn(move || server.listen());
+
+    let in_flight = thread::spawn(move || get(address, "/slow"));
+    thread::sleep(Duration::from_millis(100));
+    shutdown.shutdown();
+
+    // the request accepted before the shutdown is finished, then `listen` returns
+    assert!(in_flight.join().unwrap().ends_with("done"));
+    listening.join().unwrap();
+    assert!(TcpStream::connect(address).is_err());
+}
+
+#[test]
+fn shutdown_closes_keep_alive_connections_after_their_response() {
+    let mut server = {
+        let _environment = environment();
+        WebServer::new("127.0.0.1:0".to_string(), 2)
+    };
+    server.hide_banner = true;
+    server.get("/slow", |mut c| {
+        thread::sleep(Duration::from_millis(300));
+        return c.send_string(HttpStatusCode::OK, "done");
+    });
+    let address = server.listener.local_addr().unwrap();
+    let shutdown = server.shutdown_handle();
+    let listening = thread::spawn(move || server.listen());
+
+    let mut stream = TcpStream::connect(address).unwrap();
+    stream
+        .set_read_timeout(Some(Duration::from_secs(10)))
+        .unwrap();
+    stream
+        .write_all(b"GET /slow HTTP/1.1\r\nHost: localhost\r\n\r\n")
+        .unwrap();
+    thread::sleep(Duration::from_millis(100));
+    shutdown.shutdown();
+
+    // the response is finished, then the connection is closed instead of waiting for the next
+    // request
+    let mut response = String::new();
+    stream.read_to_string(&mut response).unwrap();
+    assert!(response.starts_with("HTTP/1.1 200"), "{}", response);
+    assert!(response.ends_with("done"), "{}", response);
+    listening.join().unwrap();
+}
+
+#[test]
+fn shutdown_closes_idle_keep_alive_connections_at_once() {
+    let mut server = {
+        let _environment = environment();
+        WebServer::new("127.0.0.1:0".to_string(), 2)
+    };
+    server.hide_banner = true;
+    server.keep_alive_timeout = Some(Duration::from_secs(30));
+    server.get("/", |mut c| c.send_string(HttpStatusCode::OK, "hello"));
+    let address = server.listener.local_addr().unwrap();
+    let shutdown = server.shutdown_handle();
+    let listening = thread::spawn(move || server.listen());
+
+    let mut stream = TcpStream::connect(address).unwrap();
+    stream
+        .set_read_timeout(Some(Duration::from_secs(10)))
+        .unwrap();
+    stream
+        .write_all(b"GET / HTTP/1.1\r\nHost: localhost\r\n\r\n")
+        .unwrap();
+    let mut buffer = [0; 1024];
+    let read = stream.read(&mut buffer).unwrap();
+    assert!(String::from_utf8_lossy(&buffer[..read]).contains("Connection: keep-alive"));
+
+    // the connection waits for it's next request, and isn't kept open until it timed out
+    let stopped_at = Instant::now();
+    shutdown.shutdown();
+    assert_eq!(stream.read(&mut buffer).unwrap(), 0);
+    listening.join().unwrap();
+    assert!(stopped_at.elapsed() < Duration::from_secs(5));
+}
+
+#[test]
+fn control_socket_answers_status_and_stop() {
+    let path = socket_path("control");
+    let mut server = {
+        let _environment = environment();
+        WebServer::new("127.0.0.1:0".to_string(), 2)
+    };
+    server.hide_banner = true;
+    server.upgrade_socket(path.to_str().unwrap());
+    let listening = thread::spawn(move || server.listen());
+    wait_for(|| path.exists());
+
+    let status = upgrade::send_command(&path, "status").unwrap();
+    assert_eq!(
+        status,
+        format!("ok pid={} connections=0", std::process::id())
+    );
+    assert!(matches!(
+        upgrade::send_command(&path, "restart"),
+        Err(browzer_web::error::ControlError::Rejected(_))
+    ));
+
+    assert_eq!(upgrade::send_command(&path, "stop").unwrap(), "ok stopping");
+    listening.join().unwrap();
+    // the socket is removed once the server stopped
+    assert!(!path.exists());
+}
+
+#[test]
+fn new_server_takes_the_inherited_listener() {
+    // the listeners are only read from the environment once per process, so one test covers both
+    // the listener of `WebServer::new` and the ones of `WebServer::bind`
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let address = listener.local_addr().unwrap();
+    let additional = TcpListener::bind("127.0.0.1:0").unwrap();
+    let additional_address = additional.local_addr().unwrap();
+    let fds = format!("{},{}", listener.into_raw_fd(), additional.into_raw_fd());
+
+    let _environment = environment();
+    env::set_var(upgrade::LISTENER_FD_VAR, &fds);
+    // a process started by the new process inherits the variables, but not the listeners
+    env::set_var(upgrade::PARENT_PID_VAR, std::process::id().to_string());
+    let server = WebServer::new("127.0.0.1:0".to_string(), 2);
+    assert_ne!(server.listener.local_addr().unwrap(), address);
+
+    env::set_var(
+        upgrade::PARENT_PID_VAR,
+        std::os::unix::process::parent_id().to_string(),
+    );
+    let mut server = WebServer::new("127.0.0.1:1".to_string(), 2);
+    assert_eq!(server.listener.local_addr().unwrap(), address);
+    // listeners bound to another address are bound as usual
+    assert_ne!(server.bind("127.0.0.2:0").unwrap(), additional_address);
+    assert_eq!(
+        server.bind(&additional_address.to_string()).unwrap(),
+        additional_address
+    );
+    // the environment is left as it is, and every listener is only taken once
+    assert_eq!(env::var(upgrade::LISTENER_FD_VAR).unwrap(), fds);
+    let mut other = WebServer::new("127.0.0.1:0".to_string(), 2);
+    assert_ne!(other.listener.local_addr().unwrap(), address);
+    assert!(other.bind(&additional_address.to_string()).is_err());
+
+    env::remove_var(upgrade::LISTENER_FD_VAR);
+    env::remove_var(upgrade::PARENT_PID_VAR);
+}
+
+#[test]
+fn upgrade_hands_the_listener_to_a_new_process() {
+    let path = socket_path("upgrade");
+    let mut server = {
+        let _environment = environment();
+        WebServer::new("127.0.0.1:0".to_string(), 2)
+    };
+    server.hide_banner = true;
+    server.get("/", |mut c| c.send_string(HttpStatusCode::OK, "old"));
+    server.upgrade_socket(path.to_str().unwrap());
+    // the new process runs `upgraded_process_serves` of this test binary
+    server.upgrade_command(upgrade::UpgradeCommand {
+        program: env::current_exe().unwrap(),
+        args: vec![
+            "upgraded_process_serves".into(),
+            "--exact".into(),
+            "--nocapture".into(),
+        ],
+    });
+    let address = server.listener.local_addr().unwrap();
+    let additional_address = server.bind("127.0.0.1:0").unwrap();
+    let listening = thread::spawn(move || server.listen());
+    wait_for(|| path.exists());
+    assert!(get(address, "/").ends_with("old"));
+    assert!(get(additional_address, "/").ends_with("old"));
+
+    let answer = upgrade::send_command(&path, "upgrade").unwrap();
+    assert!(answer.starts_with("ok pid="), "{}", answer);
+    listening.join().unwrap();
+
+    // the same address is served by the new process, which took the control socket over
+    assert!(get(address, "/").ends_with("new"));
+    assert!(get(additional_address, "/").ends_with("new"));
+    let status = upgrade::send_command(&path, "status").unwrap();
+    assert!(!status.contains(&format!("pid={} ", std::process::id())));
+    assert_eq!(upgrade::send_command(&path, "stop").unwrap(), "ok stopping");
+    wait_for(|| !path.exists());
+}
+
+// the new process of `upgrade_hands_the_listener_to_a_new_process`, a no-op in any other process
+#[test]
+fn upgraded_process_serves() {
+    let path = match env::var_os(upgrade::PARENT_SOCKET_VAR) {
+        Some(path) => PathBuf::from(path),
+        None => return,
+    };
+    let mut server = WebServer::new("127.0.0.1:0".to_string(), 2);
+    // picks up the second inherited listener, port `0` matches any port
+    server.bind("127.0.0.1:0").unwrap();
+    server.hide_banner = true;
+    server.get("/", |mut c| c.send_string(HttpStatusCode::OK, "new"));
+    server.upgrade_socket(path.to_str().unwrap());
+    server.listen();
+}
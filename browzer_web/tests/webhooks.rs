@@ -0,0 +1,141 @@
+//! End-to-end tests for outbound webhook deliveries (`webhooks::Dispatcher`).
+
+mod support;
+
+use browzer_web::{
+    utils::HttpStatusCode,
+    webhooks::{sign, Dispatcher, RetryPolicy, WebhookTransport},
+};
+use serde_json::json;
+use std::{
+    io,
+    net::TcpListener,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, Mutex,
+    },
+    time::Duration,
+};
+
+const SECRET: &[u8] = b"whsec_test";
+
+fn fast_retries(max_attempts: u32) -> RetryPolicy {
+    return RetryPolicy {
+        max_attempts,
+        initial_backoff: Duration::from_millis(10),
+        max_backoff: Duration::from_millis(50),
+    };
+}
+
+/// A transport which fails every attempt, counting them.
+#[derive(Default)]
+struct Unreachable {
+    attempts: Arc<AtomicUsize>,
+}
+
+impl WebhookTransport for Unreachable {
+    fn post(&self, _url: &str, _headers: &[(String, String)], _body: &[u8]) -> io::Result<u16> {
+        self.attempts.fetch_add(1, Ordering::SeqCst);
+        return Err(io::Error::new(io::ErrorKind::ConnectionRefused, "refused"));
+    }
+}
+
+#[test]
+fn deliveries_are_signed_and_retried_until_accepted() {
+    // the receiver fails the first attempt, and records the verified bodies
+    let received: Arc<Mutex<Vec<String>>> = Arc::new(Mutex::new(vec![]));
+    let calls = Arc::new(AtomicUsize::new(0));
+    let receiver_received = Arc::clone(&received);
+    let address = support::start_server(move |server| {
+        server.post("/hooks", move |mut c| {
+            if calls.fetch_add(1, Ordering::SeqCst) == 0 {
+                return c.send_string(HttpStatusCode::InternalServerError, "try again");
+            }
+            let header = |name: &str| c.request.header(name).cloned().unwrap_or_default();
+            let body = c.request.body.clone().unwrap_or_default();
+            let timestamp = header("webhook-timestamp").parse::<i64>().unwrap();
+            let expected = sign(SECRET, &header("webhook-id"), timestamp, &body);
+            if header("webhook-signature") != expected {
+                return c.send_string(HttpStatusCode::Unauthorized, "bad signature");
+            }
+            receiver_received.lock().unwrap().push(body);
+            return c.send_string(HttpStatusCode::OK, "ok");
+        });
+    });
+
+    let dispatcher = Dispatcher::new(SECRET).retry_policy(fast_retries(3));
+    dispatcher.register(&format!("http://{}/hooks", address), &["invoice.paid"]);
+    let ids = dispatcher
+        .dispatch("invoice.paid", &json!({"invoice": 7}))
+        .unwrap();
+    assert_eq!(ids.len(), 1);
+    // endpoints only get the events they registered for
+    assert!(dispatcher
+        .dispatch("invoice.created", &json!({}))
+        .unwrap()
+        .is_empty());
+
+    assert!(dispatcher.wait_idle(Duration::from_secs(5)));
+    let received = received.lock().unwrap();
+    assert_eq!(received.len(), 1);
+    assert!(received[0].starts_with("{\"type\":\"invoice.paid\",\"timestamp\":\""));
+    assert!(received[0].ends_with("\"data\":{\"invoice\":7}}"));
+    let metrics = dispatcher.metrics();
+    assert_eq!(
+        (metrics.queued, metrics.delivered, metrics.failed_attempts),
+        (1, 1, 1)
+    );
+}
+
+#[test]
+fn deliveries_out_of_attempts_become_dead_letters() {
+    let transport = Unreachable::default();
+    let attempts = Arc::clone(&transport.attempts);
+    let dead: Arc<Mutex<Vec<String>>> = Arc::new(Mutex::new(vec![]));
+    let hook_dead = Arc::clone(&dead);
+    let dispatcher = Dispatcher::new(SECRET)
+        .retry_policy(fast_retries(3))
+        .transport(transport)
+        .on_dead_letter(move |delivery| hook_dead.lock().unwrap().push(delivery.id.clone()));
+    dispatcher.register("http://127.0.0.1:9/hooks", &[]);
+
+    let ids = dispatcher.dispatch("ping", &json!({})).unwrap();
+    assert!(dispatcher.wait_idle(Duration::from_secs(5)));
+    assert_eq!(attempts.load(Ordering::SeqCst), 3);
+    assert_eq!(*dead.lock().unwrap(), ids);
+    let dead_letters = dispatcher.dead_letters();
+    assert_eq!(dead_letters.len(), 1);
+    assert_eq!(dead_letters[0].attempts, 3);
+    assert_eq!(dead_letters[0].last_error.as_deref(), Some("refused"));
+    assert_eq!(dispatcher.metrics().dead_lettered, 1);
+
+    // a redelivery gets a fresh set of attempts
+    assert!(dispatcher.redeliver(&ids[0]));
+    assert!(dispatcher.dead_letters().is_empty());
+    assert!(dispatcher.wait_idle(Duration::from_secs(5)));
+    assert_eq!(attempts.load(Ordering::SeqCst), 6);
+    assert_eq!(dispatcher.dead_letters().len(), 1);
+}
+
+#[test]
+fn urls_which_could_inject_headers_are_never_sent() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let address = listener.local_addr().unwrap();
+    let dispatcher = Dispatcher::new(SECRET).retry_policy(fast_retries(1));
+    dispatcher.register(&format!("http://{}/hooks\r\nX-Injected: 1", address), &[]);
+
+    dispatcher.dispatch("ping", &json!({})).unwrap();
+    assert!(dispatcher.wait_idle(Duration::from_secs(5)));
+    let dead_letters = dispatcher.dead_letters();
+    assert!(dead_letters[0]
+        .last_error
+        .as_deref()
+        .unwrap()
+        .starts_with("Invalid URL"));
+    // the endpoint was never connected to
+    listener.set_nonblocking(true).unwrap();
+    assert_eq!(
+        listener.accept().unwrap_err().kind(),
+        io::ErrorKind::WouldBlock
+    );
+}
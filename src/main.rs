@@ -1,12 +1,12 @@
 mod utils;
 
-use browzer_web;
+use browzer_web::prelude::*;
 
 fn main() {
-    let mut server = browzer_web::WebServer::new(format!("0.0.0.0:{}", utils::PORT), 5);
+    let mut server = WebServer::new(format!("0.0.0.0:{}", utils::PORT), 5);
 
     server.get("/", |mut c| {
-        return c.send_string(browzer_web::utils::HttpStatusCode::OK, "Hello,World!");
+        return c.send_string(HttpStatusCode::OK, "Hello,World!");
     });
 
     server.listen();